@@ -0,0 +1,136 @@
+use std::str::FromStr;
+
+use axum::{extract::{Query, State}, http::StatusCode, Json};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use crate::{erros::ErroApi, ids::gera_id, models::{self, chaves_pix::{ChavePix, TipoChavePix}}, pix::normaliza_chave_pix};
+
+use super::{obtem_conn, sessoes::{verifica_usuario, verifica_usuario_ou_admin, UsuarioAutenticado}, usuarios::IdInput, Pool};
+
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct ChavePixInput{
+    pub idusuario: String,
+    /// "cpf", "cnpj", "email", "telefone" ou "aleatoria".
+    pub tipo: String,
+    pub chave: String
+}
+
+#[utoipa::path(
+    post,
+    tag = "Pagamentos",
+    path = "/cadastra_chave_pix",
+    description = "Cadastra uma chave PIX em que o locador recebe os pagamentos dos contratos.
+    A chave é validada conforme o tipo e guardada no formato do DICT.",
+    responses(
+        (
+            status = 201,
+            description = "Chave cadastrada.",
+            body = ChavePix
+        ),
+        (
+            status = 400,
+            description = "Tipo ou chave inválidos."
+        ),
+        (
+            status = 403,
+            description = "O usuário autenticado não é o dono da conta ou não é locador."
+        ),
+        (
+            status = 409,
+            description = "A chave já está cadastrada."
+        ),
+    ),
+    request_body = ChavePixInput
+)]
+pub async fn cadastra_chave_pix(State(pool): State<Pool>, usuario: UsuarioAutenticado, input: Json<ChavePixInput>)
+    -> Result<(StatusCode, Json<ChavePix>), ErroApi>{
+    if input.idusuario.trim().is_empty() || input.tipo.trim().is_empty() || input.chave.trim().is_empty(){
+        return Err(ErroApi::validacao("Um ou mais campos estão vazios."))
+    }
+    verifica_usuario(&usuario, &input.idusuario)?;
+
+    let tipo = TipoChavePix::from_str(&input.tipo)?;
+    let chave = normaliza_chave_pix(tipo, &input.chave)?;
+
+    let chavepix = ChavePix{
+        idchavepix: gera_id(),
+        idusuario: input.idusuario.trim().to_string(),
+        tipo,
+        chave,
+        datacadastro: chrono::Utc::now().naive_utc()
+    };
+
+    let conn = &obtem_conn(&pool).await?;
+    let chavepix = models::chaves_pix::cadastra_chave_pix(conn, chavepix).await?;
+    return Ok((StatusCode::CREATED, Json(chavepix)))
+}
+
+#[utoipa::path(
+    get,
+    tag = "Pagamentos",
+    path = "/busca_chaves_pix_idusuario/{id}",
+    description = "Lista as chaves PIX do usuário.",
+    responses(
+        (
+            status = 200,
+            description = "Chaves do usuário.",
+            body = Vec<ChavePix>
+        ),
+        (
+            status = 403,
+            description = "O usuário autenticado não é o dono da conta nem administrador."
+        ),
+    ),
+    params(
+        ("id" = String, Path, description = "ID do usuário"),
+    )
+)]
+pub async fn busca_chaves_pix_idusuario(State(pool): State<Pool>, usuario: UsuarioAutenticado, Query(input): Query<IdInput>)
+    -> Result<(StatusCode, Json<Vec<ChavePix>>), ErroApi>{
+    if input.id.trim().is_empty(){
+        return Err(ErroApi::validacao("Um ou mais campos estão vazios."))
+    }
+    verifica_usuario_ou_admin(&usuario, &input.id)?;
+
+    let conn = &obtem_conn(&pool).await?;
+    let chaves = models::chaves_pix::busca_chaves_pix_idusuario(conn, input.id.trim().to_string()).await?;
+    return Ok((StatusCode::OK, Json(chaves)))
+}
+
+#[utoipa::path(
+    delete,
+    tag = "Pagamentos",
+    path = "/deleta_chave_pix/{id}",
+    description = "Remove uma chave PIX. As cobranças já geradas com ela continuam valendo.",
+    responses(
+        (
+            status = 200,
+            description = "Chave removida."
+        ),
+        (
+            status = 403,
+            description = "A chave é de outro usuário."
+        ),
+        (
+            status = 404,
+            description = "A chave não existe."
+        ),
+    ),
+    params(
+        ("id" = String, Path, description = "ID da chave PIX"),
+    )
+)]
+pub async fn deleta_chave_pix(State(pool): State<Pool>, usuario: UsuarioAutenticado, Query(input): Query<IdInput>)
+    -> Result<StatusCode, ErroApi>{
+    if input.id.trim().is_empty(){
+        return Err(ErroApi::validacao("Um ou mais campos estão vazios."))
+    }
+
+    let conn = &obtem_conn(&pool).await?;
+    let chavepix = models::chaves_pix::busca_chave_pix_id(conn, input.id.trim().to_string()).await?;
+    verifica_usuario(&usuario, &chavepix.idusuario)?;
+
+    models::chaves_pix::deleta_chave_pix(conn, chavepix.idchavepix).await?;
+    return Ok(StatusCode::OK)
+}
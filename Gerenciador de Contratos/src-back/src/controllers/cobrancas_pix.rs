@@ -0,0 +1,293 @@
+use axum::{extract::{Query, State}, http::{HeaderMap, StatusCode}, Json};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use crate::{dinheiro::{formata_reais, Dinheiro}, erros::ErroApi, ids::gera_id, models::{self, cobrancas_pix::{CobrancaPix, StatusCobranca}, faturas::StatusFatura}, pix::{gera_br_code, gera_qrcode_svg, gera_txid, ConfiguracaoPix, DadosBrCode, DestinoPix}};
+
+use super::{contratos::{notifica_partes_contrato, verifica_parte_contrato}, faturas::notifica_fatura_paga, obtem_conn, sessoes::{extrai_token_bearer, UsuarioAutenticado}, usuarios::IdInput, Pool};
+
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct CobrancaPixInput{
+    pub idfatura: String,
+    /// Chave do locador em que o pagamento cai. Sem ela, vale a chave mais antiga dele.
+    pub idchavepix: Option<String>,
+    /// Gera a cobrança dinâmica, publicada pelo provedor de pagamentos. Sem ela, a cobrança é estática.
+    #[serde(default)]
+    pub dinamica: bool
+}
+
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct CobrancaPixReturn{
+    #[serde(flatten)]
+    pub cobranca: CobrancaPix,
+    /// QR Code do payload, em SVG.
+    pub qrcode: String
+}
+
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct ConfirmacaoPagamentoPixInput{
+    pub txid: String,
+    pub valor: Dinheiro,
+    /// ID do pagamento no provedor (o `endToEndId` do PIX).
+    pub idpagamento: String
+}
+
+fn retorno_cobranca(cobranca: CobrancaPix) -> Result<CobrancaPixReturn, ErroApi>{
+    let qrcode = gera_qrcode_svg(&cobranca.payload)?;
+    return Ok(CobrancaPixReturn{
+        cobranca,
+        qrcode
+    })
+}
+
+#[utoipa::path(
+    post,
+    tag = "Pagamentos",
+    path = "/gera_cobranca_pix",
+    description = "Gera a cobrança PIX do valor em aberto de uma fatura, com o \"copia e cola\" e o QR Code.
+    Enquanto o valor e a chave não mudam, a mesma cobrança pendente é devolvida; senão, a nova cobrança
+    cancela as pendentes da fatura. O pagamento é somado ao valor pago da fatura.",
+    responses(
+        (
+            status = 200,
+            description = "Cobrança pendente já existente.",
+            body = CobrancaPixReturn
+        ),
+        (
+            status = 201,
+            description = "Cobrança gerada.",
+            body = CobrancaPixReturn
+        ),
+        (
+            status = 400,
+            description = "A chave não é do locador do contrato OU as cobranças dinâmicas não estão habilitadas."
+        ),
+        (
+            status = 403,
+            description = "O usuário autenticado não faz parte do contrato."
+        ),
+        (
+            status = 404,
+            description = "A fatura não existe."
+        ),
+        (
+            status = 409,
+            description = "A fatura já está paga OU o locador não tem chave PIX."
+        ),
+    ),
+    request_body = CobrancaPixInput
+)]
+pub async fn gera_cobranca_pix(State(pool): State<Pool>, usuario: UsuarioAutenticado, input: Json<CobrancaPixInput>)
+    -> Result<(StatusCode, Json<CobrancaPixReturn>), ErroApi>{
+    if input.idfatura.trim().is_empty(){
+        return Err(ErroApi::validacao("Um ou mais campos estão vazios."))
+    }
+
+    let conn = &obtem_conn(&pool).await?;
+    let fatura = models::faturas::busca_fatura_id(conn, input.idfatura.trim().to_string()).await?;
+    let contrato = models::contratos::busca_contrato_id(conn, fatura.idcontrato.clone()).await?;
+    verifica_parte_contrato(&usuario, &contrato)?;
+
+    let valor = fatura.valor - fatura.valorpago;
+    if fatura.status == StatusFatura::Paga || !valor.e_positivo(){
+        return Err(ErroApi::Conflito("A fatura já está paga.".to_string()))
+    }
+    let cobrancas = models::cobrancas_pix::busca_cobrancas_idfatura(conn, fatura.idfatura.clone()).await?;
+
+    let chavepix = match input.idchavepix.as_deref().map(str::trim){
+        Some(idchavepix) if !idchavepix.is_empty() => {
+            let chavepix = models::chaves_pix::busca_chave_pix_id(conn, idchavepix.to_string()).await?;
+            if chavepix.idusuario != contrato.idlocador{
+                return Err(ErroApi::validacao_campo("idchavepix", "A chave PIX não é do locador do contrato."))
+            }
+            chavepix
+        },
+        _ => {
+            match models::chaves_pix::busca_chaves_pix_idusuario(conn, contrato.idlocador.clone()).await?.into_iter().next(){
+                Some(chavepix) => {
+                    chavepix
+                },
+                None => {
+                    return Err(ErroApi::Conflito("O locador ainda não cadastrou uma chave PIX.".to_string()))
+                }
+            }
+        }
+    };
+
+    let pendente = cobrancas.into_iter().find(|cobranca| cobranca.status == StatusCobranca::Pendente
+        && cobranca.dinamica == input.dinamica && cobranca.chave == chavepix.chave && cobranca.valor == valor);
+    if let Some(cobranca) = pendente{
+        return Ok((StatusCode::OK, Json(retorno_cobranca(cobranca)?)))
+    }
+
+    let txid = gera_txid(input.dinamica);
+    let destino = if input.dinamica{
+        match ConfiguracaoPix::de_ambiente().url_cobranca{
+            Some(url) => {
+                DestinoPix::Url(format!("{}/{}", url, txid))
+            },
+            None => {
+                return Err(ErroApi::validacao_campo("dinamica", "As cobranças PIX dinâmicas não estão habilitadas."))
+            }
+        }
+    } else {
+        DestinoPix::Chave(chavepix.chave.clone())
+    };
+
+    let locador = models::usuarios::busca_usuario_id(conn, contrato.idlocador.clone()).await?;
+    let payload = gera_br_code(&DadosBrCode{
+        destino,
+        valor: Some(valor),
+        nome_recebedor: locador.nome,
+        cidade: contrato.cidadeforo.clone(),
+        txid: Some(txid.clone())
+    })?;
+
+    let cobranca = CobrancaPix{
+        idcobranca: gera_id(),
+        idcontrato: contrato.idcontrato,
+        chave: chavepix.chave,
+        txid,
+        valor,
+        dinamica: input.dinamica,
+        payload,
+        status: StatusCobranca::Pendente,
+        datacriacao: chrono::Utc::now().naive_utc(),
+        datapagamento: None,
+        idpagamento: None,
+        idfatura: Some(fatura.idfatura)
+    };
+    let cobranca = models::cobrancas_pix::cadastra_cobranca_pix(conn, cobranca).await?;
+    return Ok((StatusCode::CREATED, Json(retorno_cobranca(cobranca)?)))
+}
+
+#[utoipa::path(
+    get,
+    tag = "Pagamentos",
+    path = "/busca_cobrancas_pix_idcontrato/{id}",
+    description = "Lista as cobranças PIX do contrato, pagas e pendentes.",
+    responses(
+        (
+            status = 200,
+            description = "Cobranças do contrato.",
+            body = Vec<CobrancaPix>
+        ),
+        (
+            status = 403,
+            description = "O usuário autenticado não faz parte do contrato."
+        ),
+        (
+            status = 404,
+            description = "O contrato não existe."
+        ),
+    ),
+    params(
+        ("id" = String, Path, description = "ID do contrato"),
+    )
+)]
+pub async fn busca_cobrancas_pix_idcontrato(State(pool): State<Pool>, usuario: UsuarioAutenticado, Query(input): Query<IdInput>)
+    -> Result<(StatusCode, Json<Vec<CobrancaPix>>), ErroApi>{
+    if input.id.trim().is_empty(){
+        return Err(ErroApi::validacao("Um ou mais campos estão vazios."))
+    }
+
+    let conn = &obtem_conn(&pool).await?;
+    let contrato = models::contratos::busca_contrato_id(conn, input.id.trim().to_string()).await?;
+    verifica_parte_contrato(&usuario, &contrato)?;
+
+    let cobrancas = models::cobrancas_pix::busca_cobrancas_idcontrato(conn, contrato.idcontrato).await?;
+    return Ok((StatusCode::OK, Json(cobrancas)))
+}
+
+/// Marca a cobrança como paga, soma o valor à fatura cobrada e avisa as partes do contrato. Quando
+/// a fatura é quitada, o recibo é enviado como no registro manual. O provedor pode repetir o aviso
+/// de um pagamento; a repetição devolve a cobrança sem avisar as partes de novo.
+pub async fn registra_pagamento_pix(pool: &Pool, input: ConfirmacaoPagamentoPixInput)
+    -> Result<CobrancaPix, ErroApi>{
+    if input.txid.trim().is_empty() || input.idpagamento.trim().is_empty(){
+        return Err(ErroApi::validacao("Um ou mais campos estão vazios."))
+    }
+    if input.idpagamento.trim().len() > 64{
+        return Err(ErroApi::validacao_campo("idpagamento", "O ID do pagamento tem mais de 64 caracteres."))
+    }
+    let idpagamento = input.idpagamento.trim().to_string();
+
+    let conn = &obtem_conn(pool).await?;
+    let cobranca = models::cobrancas_pix::busca_cobranca_txid(conn, input.txid.trim().to_string()).await?;
+    if cobranca.status == StatusCobranca::Paga{
+        if cobranca.idpagamento.as_deref() == Some(idpagamento.as_str()){
+            return Ok(cobranca)
+        }
+        return Err(ErroApi::Conflito("A cobrança já foi paga.".to_string()))
+    }
+    if cobranca.status == StatusCobranca::Cancelada{
+        return Err(ErroApi::Conflito("A cobrança foi substituída por outra. Pague a cobrança mais recente da fatura.".to_string()))
+    }
+    if input.valor != cobranca.valor{
+        return Err(ErroApi::validacao_campo("valor", "O valor pago é diferente do valor da cobrança."))
+    }
+
+    let (cobranca, fatura) = models::cobrancas_pix::confirma_pagamento_cobranca(conn, cobranca.txid, idpagamento).await?;
+
+    let contrato = models::contratos::busca_contrato_id(conn, cobranca.idcontrato.clone()).await?;
+    match fatura{
+        Some(fatura) if fatura.status == StatusFatura::Paga => {
+            notifica_fatura_paga(pool, &contrato, &fatura).await?;
+        },
+        Some(fatura) => {
+            notifica_partes_contrato(pool, &contrato, "Pagamento PIX confirmado!",
+                &format!("O pagamento de {} da parcela {} foi confirmado. Faltam {}. Clique aqui para ver seus contratos.",
+                    formata_reais(cobranca.valor), fatura.numeroparcela, formata_reais(fatura.valor - fatura.valorpago)), None).await?;
+        },
+        None => {
+            notifica_partes_contrato(pool, &contrato, "Pagamento PIX confirmado!",
+                &format!("O pagamento de {} do contrato foi confirmado. Clique aqui para ver seus contratos.",
+                    formata_reais(cobranca.valor)), None).await?;
+        }
+    }
+
+    return Ok(cobranca)
+}
+
+#[utoipa::path(
+    post,
+    tag = "Pagamentos",
+    path = "/confirma_pagamento_pix",
+    description = "Chamada pelo provedor de pagamentos quando uma cobrança PIX é paga. Exige o token
+    `PIX_TOKEN_CONFIRMACAO` no cabeçalho `Authorization: Bearer`.",
+    responses(
+        (
+            status = 200,
+            description = "Pagamento registrado.",
+            body = CobrancaPix
+        ),
+        (
+            status = 400,
+            description = "O valor pago é diferente do valor da cobrança."
+        ),
+        (
+            status = 401,
+            description = "Token de confirmação ausente ou inválido."
+        ),
+        (
+            status = 404,
+            description = "Não há cobrança com esse txid."
+        ),
+        (
+            status = 409,
+            description = "A cobrança já foi paga por outro pagamento OU foi cancelada por uma mais nova."
+        ),
+        (
+            status = 503,
+            description = "O token de confirmação não está configurado."
+        ),
+    ),
+    request_body = ConfirmacaoPagamentoPixInput
+)]
+pub async fn confirma_pagamento_pix(State(pool): State<Pool>, headers: HeaderMap, input: Json<ConfirmacaoPagamentoPixInput>)
+    -> Result<(StatusCode, Json<CobrancaPix>), ErroApi>{
+    ConfiguracaoPix::de_ambiente().verifica_token(&extrai_token_bearer(&headers)?)?;
+
+    let cobranca = registra_pagamento_pix(&pool, input.0).await?;
+    return Ok((StatusCode::OK, Json(cobranca)))
+}
@@ -1,45 +1,103 @@
-use axum::{extract::Query, http::StatusCode, Json};
-use chrono::Days;
+use axum::{extract::{Query, State}, http::StatusCode, Json};
+use chrono::Duration;
 use diesel::{ExpressionMethods, RunQueryDsl};
-use rand::Rng;
+use rand::{rngs::OsRng, Rng, RngCore};
 use serde::{Serialize, Deserialize};
-use crate::{controllers::usuarios::{busca_usuario_id, IdInput}, models::{self, codigos_recuperacao::{cadastra_codigo_recuperacao_db, CodigoRecuperacao}}};
+use sha2::{Digest, Sha256};
+use utoipa::ToSchema;
+use crate::{controllers::usuarios::{busca_dados_usuario, IdInput}, erros::ErroApi, ids::gera_id, senhas::gera_hash_senha, models::{self, codigos_recuperacao::{cadastra_codigo_recuperacao_db, cadastra_token_redefinicao, verifica_bloqueio_codigo, CodigoRecuperacao}, executa}};
 
-use super::{cria_conn, envia_emails::envia_email_codigo, gera_hash, usuarios::{busca_usuario_email, valida_email, EmailInput}};
+use super::{cria_pool, obtem_conn, envia_emails::{envia_email_codigo, FinalidadeCodigo}, usuarios::{busca_usuario_email, valida_email, EmailInput}, Pool};
 use models::codigos_recuperacao::verifica_codigo_recuperacao_db;
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, ToSchema)]
 pub struct CodigoRecuperacaoInput{
-    pub idusuario: String,
+    pub email: String,
     pub codigodigitado: String
 }
 
-#[derive(Serialize, Deserialize)]
+/// Resposta da conferência do código: o token que autoriza uma redefinição de senha.
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct TokenRedefinicaoReturn{
+    pub tokenredefinicao: String
+}
+
+/// Resposta do envio. O código só vai no e-mail, nunca na resposta.
+#[derive(Serialize, Deserialize, ToSchema)]
 pub struct CodigoRecuperacaoReturn{
+    pub idcodigo: String
+}
+
+/// Um código recém-gerado, ainda em texto puro para ser enviado por e-mail.
+pub struct CodigoGerado{
     pub idcodigo: String,
     pub codigo: String
 }
 
-pub async fn verifica_codigo_recuperacao(input: Json<CodigoRecuperacaoInput>)
-    -> Result<(StatusCode, Json<String>), (StatusCode, Json<String>)>{
+/// Tempo de validade de um código de recuperação ou de ativação.
+pub const MINUTOS_VALIDADE_CODIGO: i64 = 30;
+/// Tempo que o usuário tem para trocar a senha depois de conferir o código.
+pub const MINUTOS_VALIDADE_TOKEN: i64 = 10;
+const DIGITOS_CODIGO: u32 = 6;
+
+/// Token de redefinição de senha: 32 bytes do gerador do sistema operacional, em hexadecimal.
+pub fn gera_token_redefinicao() -> String{
+    let mut bytes = [0_u8; 32];
+    OsRng.fill_bytes(&mut bytes);
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+/// O token é aleatório e longo, então um SHA-256 basta para guardá-lo.
+pub fn hash_token_redefinicao(token: &str) -> String{
+    format!("{:x}", Sha256::digest(token.trim().as_bytes()))
+}
+
+#[utoipa::path(
+    post,
+    tag = "Código de Recuperação",
+    path = "/verifica_codigo_recuperacao",
+    description = "Confere o código de recuperação enviado para o e-mail. O token devolvido autoriza uma
+    única redefinição de senha, em até 10 minutos.",
+    responses(
+        (
+            status = 200,
+            description = "Código correto.",
+            body = TokenRedefinicaoReturn
+        ),
+        (
+            status = 400,
+            description = "O código está incorreto ou expirou."
+        ),
+        (
+            status = 403,
+            description = "O usuário errou o código muitas vezes e está bloqueado por alguns minutos."
+        ),
+    ),
+    request_body = CodigoRecuperacaoInput
+)]
+pub async fn verifica_codigo_recuperacao(State(pool): State<Pool>, input: Json<CodigoRecuperacaoInput>)
+    -> Result<(StatusCode, Json<TokenRedefinicaoReturn>), ErroApi>{
     let codigodigitado = input.codigodigitado.trim().to_string();
     if codigodigitado.is_empty(){
-        return Err((StatusCode::BAD_REQUEST, Json("O código não pode estar vazio.".to_string())))
+        return Err(ErroApi::validacao("O código não pode estar vazio."))
     }
 
-    let idusuario = input.idusuario.trim().to_string();
-    if idusuario.is_empty(){
-        return Err((StatusCode::BAD_REQUEST, Json("O ID do usuário não pode estar vazio.".to_string())))
-    }
+    let idusuario = busca_usuario_email(State(pool.clone()), Query(EmailInput{email: input.email.trim().to_string()})).await?.1.0;
 
-    let conn = &mut cria_conn()?;
+    let conn = &obtem_conn(&pool).await?;
 
-    match verifica_codigo_recuperacao_db(conn, idusuario, codigodigitado).await{
-        Ok(id) => {
-            return Ok((StatusCode::OK, Json(id)))
+    match verifica_codigo_recuperacao_db(conn, idusuario, codigodigitado, FinalidadeCodigo::Recuperacao.chave()).await{
+        Ok(idcodigo) => {
+            let tokenredefinicao = gera_token_redefinicao();
+            let dataexpiracao = chrono::Utc::now().naive_utc() + Duration::minutes(MINUTOS_VALIDADE_TOKEN);
+            cadastra_token_redefinicao(conn, idcodigo, hash_token_redefinicao(&tokenredefinicao), dataexpiracao).await?;
+            return Ok((StatusCode::OK, Json(TokenRedefinicaoReturn{tokenredefinicao})))
+        },
+        Err(ErroApi::Validacao{mensagem, ..}) => {
+            return Err(ErroApi::validacao_campo("codigodigitado", &mensagem))
         },
         Err(e) => {
-            return Err((StatusCode::INTERNAL_SERVER_ERROR, Json(e)))
+            return Err(e)
         }
     }
 }
@@ -53,7 +111,11 @@ pub async fn verifica_codigo_recuperacao(input: Json<CodigoRecuperacaoInput>)
         (
             status = 200, 
             description = "Dados válidos. E-mail enviado e registro salvo no banco.",
-            body = String
+            body = CodigoRecuperacaoReturn
+        ),
+        (
+            status = 403,
+            description = "O usuário errou o código muitas vezes e está bloqueado por alguns minutos."
         ),
         (
             status = 500,
@@ -66,8 +128,8 @@ pub async fn verifica_codigo_recuperacao(input: Json<CodigoRecuperacaoInput>)
     ),
     request_body = EmailInput    
 )]
-pub async fn envia_codigo_recuperacao(input: Json<EmailInput>)
-    -> Result<(StatusCode, Json<CodigoRecuperacaoReturn>), (StatusCode, Json<String>)>{
+pub async fn envia_codigo_recuperacao(State(pool): State<Pool>, input: Json<EmailInput>)
+    -> Result<(StatusCode, Json<CodigoRecuperacaoReturn>), ErroApi>{
     let email_clone = input.email.to_string();
     match valida_email(input).await{
         Ok(_) => {},
@@ -76,82 +138,91 @@ pub async fn envia_codigo_recuperacao(input: Json<EmailInput>)
         } 
     }
 
-    let idusuario = busca_usuario_email(Query(EmailInput{email: email_clone.clone()})).await?.1.0;
-    let usuario = busca_usuario_id(Query(IdInput{id: idusuario.clone()})).await?.1.0;
+    let idusuario = busca_usuario_email(State(pool.clone()), Query(EmailInput{email: email_clone.clone()})).await?.1.0;
+    let usuario = busca_dados_usuario(&pool, IdInput{id: idusuario.clone()}).await?.1.0;
     let origemconta = usuario.origemconta;
 
     if origemconta != "Sistema"{
-        return Err((StatusCode::BAD_REQUEST, 
-            Json("Não é possível solicitar a recuperação de senha de uma conta criada usando o Google.".to_string())))
+        return Err(ErroApi::validacao(&format!("A conta foi criada com {} e não tem senha para recuperar. Entre com {}.", origemconta, origemconta)))
     }
 
-    let codigoreturn = gera_codigo_recuperacao(email_clone.clone()).await?.1.0;
-    let res = envia_email_codigo(email_clone.clone(), "recuperação de senha", codigoreturn.codigo.clone()).await?;
-    println!("E-mail: {} | Código: {}", email_clone, res.1.0);
+    let codigogerado = gera_codigo_recuperacao(&pool, email_clone.clone(), &FinalidadeCodigo::Recuperacao).await?;
+    envia_email_codigo(email_clone, FinalidadeCodigo::Recuperacao, codigogerado.codigo).await?;
 
-    return Ok((StatusCode::OK, Json(codigoreturn)))
+    return Ok((StatusCode::OK, Json(CodigoRecuperacaoReturn{
+        idcodigo: codigogerado.idcodigo
+    })))
 }
 
 pub async fn deleta_codigo(id: String)
-    -> Result<String, String>{
+    -> Result<String, ErroApi>{
     // Só utilizar em testes
-    use crate::schema::codigos_recuperacao::dsl::*;
-
-    let conn = &mut cria_conn().unwrap();
-
-    let res: Result<CodigoRecuperacao, diesel::result::Error> = diesel::delete(codigos_recuperacao)
-        .filter(idcodigo.eq(id))
-        .get_result(conn);
-
-    match res{
-        Ok(codigoapagado) => {
-            return Ok(codigoapagado.idcodigo)
-        },
-        Err(e) => {
-            return Err(e.to_string())
+    let pool = cria_pool().unwrap();
+    let conn = &pool.get().await.unwrap();
+
+    executa(conn, move |conn| {
+        use crate::schema::codigos_recuperacao::dsl::*;
+
+        let res: Result<CodigoRecuperacao, diesel::result::Error> = diesel::delete(codigos_recuperacao)
+            .filter(idcodigo.eq(id))
+            .get_result(conn);
+
+        match res{
+            Ok(codigoapagado) => {
+                return Ok(codigoapagado.idcodigo)
+            },
+            Err(e) => {
+                return Err(e.into())
+            }
         }
-    }
+    }).await
 }
 
-fn gera_i32_aleatorio() -> i32{
-    let mut rng = rand::thread_rng();
-    let random: i32 = rng.gen_range(1000..=9999);
-    random
+/// Código numérico de `DIGITOS_CODIGO` dígitos, sorteado pelo gerador do sistema operacional.
+pub fn gera_codigo_aleatorio() -> String{
+    let limite = 10_u32.pow(DIGITOS_CODIGO);
+    let numero = OsRng.gen_range(0..limite);
+    format!("{:0largura$}", numero, largura = DIGITOS_CODIGO as usize)
 }
 
-pub async fn gera_codigo_recuperacao(email: String)
-    -> Result<(StatusCode, Json<CodigoRecuperacaoReturn>), (StatusCode, Json<String>)>{
-    let email_ = email.clone();
-    let input = Query(EmailInput{email: email_.clone()});
-    let idusuario = busca_usuario_email(input).await?.1.0;
+/// Gera e cadastra um novo código para o usuário, invalidando os anteriores. O código em texto
+/// puro só existe no retorno, para ser enviado por e-mail, e só vale para a `finalidade`.
+pub async fn gera_codigo_recuperacao(pool: &Pool, email: String, finalidade: &FinalidadeCodigo)
+    -> Result<CodigoGerado, ErroApi>{
+    let input = Query(EmailInput{email: email.clone()});
+    let idusuario = busca_usuario_email(State(pool.clone()), input).await?.1.0;
+
+    let conn = &obtem_conn(pool).await?;
+    verifica_bloqueio_codigo(conn, idusuario.clone()).await?;
 
     let datacriacao = chrono::Utc::now().naive_utc();
-    let dia = Days::new(1);
-    let dataexpiracao = chrono::Utc::now().checked_add_days(dia).unwrap().naive_utc();
-    let idcodigo = gera_hash(&email_);
+    let dataexpiracao = datacriacao + Duration::minutes(MINUTOS_VALIDADE_CODIGO);
+    let idcodigo = gera_id();
 
-    let codigo = gera_i32_aleatorio().to_string();
+    let codigo = gera_codigo_aleatorio();
 
     let codigorecuperacao = CodigoRecuperacao{
-        codigo: codigo.clone(),
+        hashcodigo: gera_hash_senha(&codigo)?,
         datacriacao,
         dataexpiracao,
         status: "Não utilizado".to_string(),
         idusuario,
-        idcodigo: idcodigo.clone(),
+        idcodigo,
+        tentativas: 0,
+        dataultimatentativa: None,
+        hashtoken: None,
+        finalidade: finalidade.chave()
     };
 
-    let conn = &mut cria_conn()?;
-
     match cadastra_codigo_recuperacao_db(conn, codigorecuperacao).await{
-        Ok(codigo) => {
-            return Ok((StatusCode::OK, Json(CodigoRecuperacaoReturn{
+        Ok(idcodigo) => {
+            return Ok(CodigoGerado{
                 idcodigo,
                 codigo
-            })))
+            })
         },
         Err(e) => {
-            return Err((StatusCode::INTERNAL_SERVER_ERROR, Json(e)))
+            return Err(e)
         }
     };
-}
\ No newline at end of file
+}
@@ -1,10 +1,11 @@
-use axum::{extract::Query, Json};
+use axum::{extract::{Query, State}, Json};
 use hyper::StatusCode;
 use serde::{Deserialize, Serialize};
 
-use crate::models::{self, contas_bancarias::ContaBancaria};
+use crate::{erros::ErroApi, models::{self, contas_bancarias::ContaBancaria}};
 
-use super::{cria_conn, gera_hash, usuarios::IdInput};
+use super::{obtem_conn, sessoes::{verifica_usuario, verifica_usuario_ou_admin, UsuarioAutenticado}, usuarios::IdInput, Pool};
+use crate::ids::gera_id;
 
 #[derive(Serialize, Deserialize)]
 pub struct ContaBancariaInput{
@@ -14,15 +15,16 @@ pub struct ContaBancariaInput{
     pub nomebanco: String
 }
 
-pub async fn cadastra_conta_bancaria(input: Json<ContaBancariaInput>)
-    -> Result<(StatusCode, Json<String>), (StatusCode, Json<String>)>{
+pub async fn cadastra_conta_bancaria(State(pool): State<Pool>, usuario: UsuarioAutenticado, input: Json<ContaBancariaInput>)
+    -> Result<(StatusCode, Json<String>), ErroApi>{
     if input.idusuario.trim().is_empty() || input.nomebanco.trim().is_empty()
       || input.numeroagencia.trim().is_empty() || input.numeroconta.trim().is_empty(){
-      return Err((StatusCode::BAD_REQUEST, Json("Um ou mais campos estão vazios.".to_string())))
+      return Err(ErroApi::validacao("Um ou mais campos estão vazios."))
     }
+    verifica_usuario(&usuario, &input.idusuario)?;
 
     let conta: ContaBancaria = {
-      let idconta = gera_hash(&input.idusuario);
+      let idconta = gera_id();
       let idusuario = input.idusuario.clone();
       let numeroconta = input.numeroconta.clone();
       let numeroagencia = input.numeroagencia.clone();
@@ -37,53 +39,70 @@ pub async fn cadastra_conta_bancaria(input: Json<ContaBancariaInput>)
       }
     };
 
-    let conn = &mut cria_conn()?;
+    let conn = &obtem_conn(&pool).await?;
 
     match models::contas_bancarias::cadastra_conta_bancaria(conn, conta).await{
       Ok(id) => {
         return Ok((StatusCode::OK, Json(id)))
       },
       Err(e) => {
-        return Err((StatusCode::INTERNAL_SERVER_ERROR, Json(e)))
+        return Err(e)
       }
     }
 }
 
-pub async fn busca_conta_bancaria_idusuario(Query(id): Query<IdInput>)
-    -> Result<(StatusCode, Json<ContaBancaria>), (StatusCode, Json<String>)>{
+pub async fn busca_conta_bancaria_idusuario(State(pool): State<Pool>, usuario: UsuarioAutenticado, Query(id): Query<IdInput>)
+    -> Result<(StatusCode, Json<ContaBancaria>), ErroApi>{
     if id.id.trim().is_empty(){
-      return Err((StatusCode::BAD_REQUEST, Json("Um ou mais campos estão vazios.".to_string())))
+      return Err(ErroApi::validacao("Um ou mais campos estão vazios."))
+    }
+    verifica_usuario_ou_admin(&usuario, &id.id)?;
+
+    busca_conta_bancaria_usuario(&pool, id).await
+}
+
+/// Busca a conta bancária de qualquer usuário. Uso interno, sem verificação de sessão.
+pub async fn busca_conta_bancaria_usuario(pool: &Pool, id: IdInput)
+    -> Result<(StatusCode, Json<ContaBancaria>), ErroApi>{
+    if id.id.trim().is_empty(){
+      return Err(ErroApi::validacao("Um ou mais campos estão vazios."))
     }
 
     let id = id.id.trim().to_string();
-    let conn = &mut cria_conn()?;
+    let conn = &obtem_conn(pool).await?;
 
     match models::contas_bancarias::busca_conta_bancaria_idusuario(conn, id).await{
       Ok(conta) => {
         return Ok((StatusCode::OK, Json(conta)))
       },
       Err(e) => {
-        return Err((StatusCode::INTERNAL_SERVER_ERROR, Json(e)))
+        return Err(e)
       }
     }
 }
 
-pub async fn atualiza_conta_bancaria(input: Json<ContaBancaria>)
-    ->Result<(StatusCode, Json<ContaBancaria>), (StatusCode, Json<String>)>{
+pub async fn atualiza_conta_bancaria(State(pool): State<Pool>, usuario: UsuarioAutenticado, input: Json<ContaBancaria>)
+    ->Result<(StatusCode, Json<ContaBancaria>), ErroApi>{
     if input.idconta.trim().is_empty() || input.idusuario.trim().is_empty() 
       || input.nomebanco.trim().is_empty() || input.numeroagencia.trim().is_empty()
       || input.numeroconta.trim().is_empty(){
-        return Err((StatusCode::BAD_REQUEST, Json("Um ou mais campos estão vazios.".to_string())))
+        return Err(ErroApi::validacao("Um ou mais campos estão vazios."))
+    }
+    verifica_usuario(&usuario, &input.idusuario)?;
+
+    let conta_atual = busca_conta_bancaria_usuario(&pool, IdInput{id: input.idusuario.clone()}).await?.1.0;
+    if conta_atual.idconta != input.idconta{
+        return Err(ErroApi::Proibido("Essa conta bancária pertence a outro usuário.".to_string()))
     }
 
-    let mut conn = &mut cria_conn()?;
+    let conn = &obtem_conn(&pool).await?;
 
     match models::contas_bancarias::atualiza_conta_bancaria(conn, input.0).await{
         Ok(conta) => {
           return Ok((StatusCode::OK, Json(conta)))
         },
         Err(e) => {
-          return Err((StatusCode::INTERNAL_SERVER_ERROR, Json(e)))
+          return Err(e)
         }
     }
 }
\ No newline at end of file
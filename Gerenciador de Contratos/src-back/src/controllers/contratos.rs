@@ -1,73 +1,81 @@
-use axum::{extract::Query, Json};
-use chrono::NaiveDateTime;
+use std::str::FromStr;
+
+use axum::{extract::{Query, State}, http::header::{CONTENT_DISPOSITION, CONTENT_TYPE, ETAG}, response::{IntoResponse, Response}, Json};
+use chrono::{NaiveDateTime, TimeDelta};
 use hyper::StatusCode;
 use serde::{Deserialize, Serialize};
-
-use crate::models::{self, contratos::Contrato, locatarios::busca_locatario_idlocatario};
-
-use super::{contas_bancarias::busca_conta_bancaria_idusuario, cria_conn, enderecos::{busca_endereco_id, busca_endereco_idusuario}, formata_cnpj, formata_cpf, gera_hash, locadoras::busca_locadora_idusuario, locatarios::busca_locatario_idusuario, maquinas::busca_maquina_id, solicitacoes_contratos::busca_solicitacao_idsolicitacao, usuarios::{busca_usuario_id, formata_documento, IdInput, UserId}};
-
-pub struct ContratoInput{
-    pub idlocatario: String,
-    pub idlocador: String,
-    pub idenderecolocatario: String,
-    pub idenderecolocador: String,
-    pub idenderecoretirada: String,
-    pub idmaquina: String,
-    pub idsolicitacaocontrato: String,
+use sha2::{Digest, Sha256};
+
+use crate::{dinheiro::{formata_reais, Dinheiro}, emails::ConteudoEmail, erros::ErroApi, models::{self, contratos::{Contrato, StatusContrato}, documentos_contratos::DocumentoContrato, executa, locatarios::busca_locatario_idlocatario, reservas_maquinas::{busca_reserva_idsolicitacao, calcula_fim_locacao}, solicitacoes_contratos::SolicitacaoContrato}, pdf::{gera_pdf, BlocoPdf}};
+
+use super::{contas_bancarias::busca_conta_bancaria_usuario, obtem_conn, enderecos::{busca_endereco, busca_endereco_usuario}, envia_emails::{renderiza_email, EmailContratoGerado, EmailContratoTerminando}, formata_cnpj, formata_cpf, maquinas::{busca_maquina_id, calcula_valor_aluguel, CalculoValorAluguel}, notificacoes::{cadastra_notificacao, NotificacaoInput}, sessoes::{verifica_usuario_ou_admin, UsuarioAutenticado}, usuarios::{busca_dados_usuario, formata_documento, IdInput, UserId}, Pool};
+use crate::ids::gera_id;
+
+/// Monta o contrato de uma solicitação com os endereços e a conta bancária atuais das partes.
+/// Não grava nada: o contrato é salvo na mesma transação da aprovação, em
+/// `models::solicitacoes_contratos::aprova_solicitacao`.
+pub async fn monta_contrato(pool: &Pool, solicitacao: &SolicitacaoContrato) -> Result<Contrato, ErroApi>{
+    let idenderecolocatario = busca_endereco_usuario(pool, UserId{idusuario: solicitacao.idlocatario.clone()}).await?.1.0.idendereco;
+    // A máquina é retirada no endereço do locador, que também define o foro.
+    let enderecolocador = busca_endereco_usuario(pool, UserId{idusuario: solicitacao.idlocador.clone()}).await?.1.0;
+    let contabancaria = busca_conta_bancaria_usuario(pool, IdInput{id: solicitacao.idlocador.clone()}).await?.1.0;
+
+    return Ok(Contrato{
+        idcontrato: gera_id(),
+        idlocatario: solicitacao.idlocatario.clone(),
+        idlocador: solicitacao.idlocador.clone(),
+        idenderecolocatario,
+        idenderecolocador: enderecolocador.idendereco.clone(),
+        idenderecoretirada: enderecolocador.idendereco,
+        idmaquina: solicitacao.idmaquina.clone(),
+        idsolicitacaocontrato: solicitacao.idsolicitacao.clone(),
+        prazolocacao: solicitacao.prazolocacao,
+        medidatempolocacao: solicitacao.medidatempolocacao,
+        valorlocacao: solicitacao.valorsolicitacao,
+        idcontabancarialocador: contabancaria.idconta,
+        cidadeforo: enderecolocador.cidade,
+        datacontrato: chrono::Utc::now().naive_utc(),
+        statuscontrato: StatusContrato::Ativo,
+        motivoencerramento: None,
+        dataencerramento: None,
+        dataavisotermino: None,
+        datadevolucao: None,
+        dataavisoatraso: None
+    })
 }
 
-pub async fn cadastra_contrato(input: Json<ContratoInput>)
-    -> Result<(StatusCode, Json<String>), (StatusCode, Json<String>)>{
-    if input.idenderecolocador.trim().is_empty() || input.idenderecolocatario.trim().is_empty()
-      || input.idenderecoretirada.trim().is_empty() || input.idlocador.trim().is_empty()
-      || input.idlocatario.trim().is_empty() || input.idmaquina.trim().is_empty()
-      || input.idsolicitacaocontrato.trim().is_empty() {
-      return Err((StatusCode::BAD_REQUEST, Json("Um ou mais campos estão vazios.".to_string())))    
-    }
+/// Gera o PDF de um contrato recém-gravado e avisa as partes. Roda depois que a transação
+/// da aprovação termina, então uma falha aqui não desfaz o contrato.
+pub async fn notifica_contrato_gerado(pool: &Pool, idcontrato: String) -> Result<(), ErroApi>{
+    let conn = &obtem_conn(pool).await?;
+    let contrato = models::contratos::busca_contrato_id(conn, idcontrato.clone()).await?;
 
-    let idcontrato = gera_hash(&input.idsolicitacaocontrato);
-    let idsolicitacao = input.idsolicitacaocontrato.to_string();
-
-    let solicitacao = busca_solicitacao_idsolicitacao(Query(IdInput{id: idsolicitacao})).await?.1.0;
-    let medidatempo = solicitacao.medidatempolocacao;
-    let prazolocacao = solicitacao.prazolocacao;
-    let valorsolicitacao = solicitacao.valorsolicitacao;
-
-    let enderecoretirada = busca_endereco_id(Query(input.idenderecoretirada.to_string())).await?.1.0;
-    let cidadeforo = enderecoretirada.cidade;
-
-    let contabancaria = busca_conta_bancaria_idusuario(Query(IdInput{id: input.idlocador.to_string()})).await?.1.0;
-    let datacontrato = chrono::Utc::now().naive_utc();
-
-    let contrato = Contrato{
-        idcontrato,
-        idlocatario: input.idlocatario.to_string(),
-        idlocador: input.idlocador.to_string(),
-        idenderecolocatario: input.idenderecolocatario.to_string(),
-        idenderecolocador: input.idenderecolocador.to_string(),
-        idenderecoretirada: input.idenderecoretirada.to_string(),
-        idmaquina: input.idmaquina.to_string(),
-        idsolicitacaocontrato: input.idsolicitacaocontrato.to_string(),
-        prazolocacao: prazolocacao,
-        medidatempolocacao: medidatempo,
-        valorlocacao: valorsolicitacao,
-        idcontabancarialocador: contabancaria.idconta,
-        cidadeforo,
-        datacontrato,
-        statuscontrato: "Ativo".to_string(),
-    };
-
-    let conn = &mut cria_conn()?;
- 
-    match models::contratos::cadastra_contrato(conn, contrato).await{
-      Ok(idcontrato) => {
-        return Ok((StatusCode::OK, Json(idcontrato)))
+    // Se o PDF falhar aqui, ele é gerado na primeira vez que for pedido.
+    let pdf = match gera_documento_contrato(pool, contrato.clone()).await{
+      Ok(documento) => {
+        Some(documento.documento)
       },
       Err(e) => {
-        return Err((StatusCode::INTERNAL_SERVER_ERROR, Json(e)))
+        tracing::error!("Erro ao gerar o PDF do contrato {}: {}", idcontrato, e);
+        None
       }
-    }  
+    };
+
+    // Sem o PDF, o e-mail vai sem anexo, e o aviso não pode dizer que o contrato foi enviado.
+    let mensagem = match pdf{
+      Some(_) => "O contrato de locação foi gerado e enviado para o seu e-mail. Clique aqui para ver seus contratos.",
+      None => "O contrato de locação foi gerado e está disponível para download no MaqExpress. Clique aqui para ver seus contratos."
+    };
+    let maquina = busca_maquina_id(State(pool.clone()), Query(IdInput{id: contrato.idmaquina.clone()})).await?.1.0;
+    let email = renderiza_email(&EmailContratoGerado{
+      idcontrato: contrato.idcontrato.clone(),
+      nomemaquina: maquina.nome,
+      prazolocacao: contrato.prazolocacao,
+      medidatempolocacao: contrato.medidatempolocacao.to_string(),
+      valorlocacao: contrato.valorlocacao,
+      pdf
+    })?;
+    notifica_partes_contrato(pool, &contrato, "Um contrato foi gerado!", mensagem, Some(email)).await
 }
 
 #[derive(Serialize, Deserialize)]
@@ -108,7 +116,7 @@ pub struct ContratoPDF{
 
     pub nomemaquina: String,
     pub numeroseriemaquina: String,
-    pub valoraluguelmaquina: f64,
+    pub valoraluguelmaquina: Dinheiro,
 
     pub numerocontabanco: String,
     pub numeroagenciabanco: String,
@@ -117,32 +125,133 @@ pub struct ContratoPDF{
     pub medidatempolocacao: String,
 
     pub prazolocacao: f64,
-    pub valorlocacao: f64,
+    pub valorlocacao: Dinheiro,
+    pub cidadeforo: String,
     
     pub datacontrato: String,
+    /// Início e fim do período reservado, no mesmo formato de `datacontrato`.
+    pub datainicio: String,
+    pub datafim: String,
+}
+
+impl ContratoPDF{
+    /// Valores de cada campo do modelo do contrato, já formatados para exibição.
+    pub fn campos(&self) -> Vec<(&'static str, String)>{
+        let formata = |texto: &str, formato: &str| match NaiveDateTime::parse_from_str(texto, "%Y-%m-%d %H:%M:%S%.f"){
+            Ok(data) => data.format(formato).to_string(),
+            Err(_) => texto.to_string()
+        };
+        let datacontrato = formata(&self.datacontrato, "%d/%m/%Y");
+        vec![
+            ("idcontrato", self.idcontrato.clone()),
+            ("nomelocatario", self.nomelocatario.clone()),
+            ("documentolocatario", self.documentolocatario.clone()),
+            ("tipodocumentolocatario", self.tipodocumentolocatario.clone()),
+            ("nomelocador", self.nomelocador.clone()),
+            ("documentolocador", self.documentolocador.clone()),
+            ("tipodocumentolocador", self.tipodocumentolocador.clone()),
+            ("estadoenderecolocatario", self.estadoenderecolocatario.clone()),
+            ("cidadeenderecolocatario", self.cidadeenderecolocatario.clone()),
+            ("cependerecolocatario", self.cependerecolocatario.clone()),
+            ("bairroenderecolocatario", self.bairroenderecolocatario.clone()),
+            ("logradouroenderecolocatario", self.logradouroenderecolocatario.clone()),
+            ("numeroenderecolocatario", self.numeroenderecolocatario.clone()),
+            ("complementoenderecolocatario", self.complementoenderecolocatario.clone()),
+            ("estadoenderecolocador", self.estadoenderecolocador.clone()),
+            ("cidadeenderecolocador", self.cidadeenderecolocador.clone()),
+            ("cependerecolocador", self.cependerecolocador.clone()),
+            ("bairroenderecolocador", self.bairroenderecolocador.clone()),
+            ("logradouroenderecolocador", self.logradouroenderecolocador.clone()),
+            ("numeroenderecolocador", self.numeroenderecolocador.clone()),
+            ("complementoenderecolocador", self.complementoenderecolocador.clone()),
+            ("estadoenderecoretirada", self.estadoenderecoretirada.clone()),
+            ("cidadeenderecoretirada", self.cidadeenderecoretirada.clone()),
+            ("cependerecoretirada", self.cependerecoretirada.clone()),
+            ("bairroenderecoretirada", self.bairroenderecoretirada.clone()),
+            ("logradouroenderecoretirada", self.logradouroenderecoretirada.clone()),
+            ("numeroenderecoretirada", self.numeroenderecoretirada.clone()),
+            ("complementoenderecoretirada", self.complementoenderecoretirada.clone()),
+            ("nomemaquina", self.nomemaquina.clone()),
+            ("numeroseriemaquina", self.numeroseriemaquina.clone()),
+            ("valoraluguelmaquina", formata_reais(self.valoraluguelmaquina)),
+            ("numerocontabanco", self.numerocontabanco.clone()),
+            ("numeroagenciabanco", self.numeroagenciabanco.clone()),
+            ("nomebanco", self.nomebanco.clone()),
+            ("medidatempolocacao", self.medidatempolocacao.to_lowercase()),
+            ("prazolocacao", self.prazolocacao.to_string()),
+            ("valorlocacao", formata_reais(self.valorlocacao)),
+            ("cidadeforo", self.cidadeforo.clone()),
+            ("datacontrato", datacontrato),
+            ("datainicio", formata(&self.datainicio, "%d/%m/%Y às %H:%M")),
+            ("datafim", formata(&self.datafim, "%d/%m/%Y às %H:%M")),
+        ]
+    }
+}
+
+/// Versão do modelo usada nos contratos novos. Ao alterar o texto do contrato, crie um
+/// arquivo novo em `templates/` e uma versão nova aqui; os PDFs já emitidos não mudam.
+pub const VERSAO_MODELO_CONTRATO: &str = "v2";
+
+pub fn modelo_contrato(versao: &str) -> Option<&'static str>{
+    match versao{
+        "v1" => Some(include_str!("../templates/contrato_locacao_v1.txt")),
+        "v2" => Some(include_str!("../templates/contrato_locacao_v2.txt")),
+        _ => None
+    }
 }
 
-pub async fn busca_contrato_id(Query(id): Query<IdInput>)
-    -> Result<(StatusCode, Json<Contrato>), (StatusCode, Json<String>)>{
+/// Preenche os campos `{{campo}}` do modelo e converte o texto em blocos do PDF.
+/// Linhas com `# ` viram o título, `## ` os títulos das cláusulas e linhas vazias, espaços.
+pub fn renderiza_modelo(modelo: &str, campos: &[(&str, String)]) -> Result<Vec<BlocoPdf>, ErroApi>{
+    let mut texto = modelo.to_string();
+    for (campo, valor) in campos{
+        texto = texto.replace(&format!("{{{{{}}}}}", campo), valor.trim());
+    }
+    if let Some(inicio) = texto.find("{{"){
+        let resto = &texto[inicio..];
+        let fim = resto.find("}}").map(|f| f + 2).unwrap_or(resto.len());
+        return Err(ErroApi::Interno(format!("Campo sem valor no modelo do contrato: {}", &resto[..fim])))
+    }
+    // Complementos vazios deixam vírgulas duplicadas no meio do endereço.
+    let texto = texto.replace(", , ", ", ");
+
+    let mut blocos = Vec::new();
+    for linha in texto.lines(){
+        let linha = linha.trim();
+        if linha.is_empty(){
+            blocos.push(BlocoPdf::Espaco);
+        } else if let Some(titulo) = linha.strip_prefix("## "){
+            blocos.push(BlocoPdf::Secao(titulo.to_string()));
+        } else if let Some(titulo) = linha.strip_prefix("# "){
+            blocos.push(BlocoPdf::Titulo(titulo.to_string()));
+        } else {
+            blocos.push(BlocoPdf::Paragrafo(linha.to_string()));
+        }
+    }
+    return Ok(blocos)
+}
+
+pub async fn busca_contrato_id(State(pool): State<Pool>, Query(id): Query<IdInput>)
+    -> Result<(StatusCode, Json<Contrato>), ErroApi>{
     if id.id.trim().is_empty(){
-      return Err((StatusCode::BAD_REQUEST, Json("Um ou mais campos estão vazios.".to_string())))    
+      return Err(ErroApi::validacao("Um ou mais campos estão vazios."))    
     }
 
     let id = id.id.trim().to_string();
 
-    let conn = &mut cria_conn()?;
+    let conn = &obtem_conn(&pool).await?;
 
     match models::contratos::busca_contrato_id(conn, id).await{
       Ok(contrato) => {
         return Ok((StatusCode::OK, Json(contrato)))
       },
       Err(e) => {
-        return Err((StatusCode::INTERNAL_SERVER_ERROR, Json(e)))
+        return Err(e)
       }
     }
 }
 
-pub fn tipo_documento(documento_: &str) -> Result<String, (StatusCode, Json<String>)>{
+pub fn tipo_documento(documento_: &str) -> Result<String, ErroApi>{
   match formata_cpf(documento_){
       Ok(cpf) => {
           return Ok("CPF".to_string())
@@ -155,44 +264,71 @@ pub fn tipo_documento(documento_: &str) -> Result<String, (StatusCode, Json<Stri
           return Ok("CNPJ".to_string())
       },
       Err(_) => {
-          return Err((StatusCode::BAD_REQUEST, Json("O documento não é válido.".to_string())))
+          return Err(ErroApi::validacao("O documento não é válido."))
       }
   }
 }
 
-pub async fn gera_contrato_idsolicitacao(Query(id): Query<IdInput>)
-    -> Result<(StatusCode, Json<ContratoPDF>), (StatusCode, Json<String>)>{
+fn e_parte_contrato(usuario: &UsuarioAutenticado, contrato: &Contrato) -> bool{
+    usuario.idusuario == contrato.idlocador || usuario.idusuario == contrato.idlocatario
+}
+
+/// Só o locador, o locatário e os administradores podem acessar os dados de um contrato.
+pub fn verifica_parte_contrato(usuario: &UsuarioAutenticado, contrato: &Contrato) -> Result<(), ErroApi>{
+    if !e_parte_contrato(usuario, contrato) && !usuario.e_admin(){
+      return Err(ErroApi::Proibido("Você não faz parte desse contrato.".to_string()))
+    }
+    return Ok(())
+}
+
+pub async fn gera_contrato_idsolicitacao(State(pool): State<Pool>, usuario: UsuarioAutenticado, Query(id): Query<IdInput>)
+    -> Result<(StatusCode, Json<ContratoPDF>), ErroApi>{
     if id.id.trim().is_empty(){
-      return Err((StatusCode::BAD_REQUEST, Json("Um ou mais campos estão vazios.".to_string())))    
+      return Err(ErroApi::validacao("Um ou mais campos estão vazios."))    
     }
 
     let id = id.id.trim().to_string();
 
-    let conn = &mut cria_conn()?;
+    let conn = &obtem_conn(&pool).await?;
 
     let contrato = match models::contratos::busca_contrato_idsolicitacao(conn, id).await{
       Ok(contrato) => {
         contrato
       },
       Err(e) => {
-        return Err((StatusCode::INTERNAL_SERVER_ERROR, Json(e)))
+        return Err(e)
       }
     };
+    verifica_parte_contrato(&usuario, &contrato)?;
 
-    let locatario = busca_usuario_id(Query(IdInput{id: contrato.idlocatario})).await?.1.0;
-    let locador = busca_usuario_id(Query(IdInput{id: contrato.idlocador})).await?.1.0;
+    let contratopdf = monta_contrato_pdf(&pool, contrato).await?;
+    return Ok((StatusCode::OK, Json(contratopdf)))
+}
+
+/// Reúne os dados das partes, da máquina e da conta bancária usados no documento do contrato.
+/// O período é o da reserva, que já inclui as renovações.
+pub async fn monta_contrato_pdf(pool: &Pool, contrato: Contrato) -> Result<ContratoPDF, ErroApi>{
+    let conn = &obtem_conn(pool).await?;
+    let idsolicitacao = contrato.idsolicitacaocontrato.clone();
+    let (datainicio, datafim) = match executa(conn, move |conn| busca_reserva_idsolicitacao(conn, &idsolicitacao)).await?{
+        Some(reserva) => (reserva.datainicio, reserva.datafim),
+        None => (contrato.datacontrato,
+            calcula_fim_locacao(contrato.datacontrato, contrato.prazolocacao, contrato.medidatempolocacao)?)
+    };
+
+    let locatario = busca_dados_usuario(pool, IdInput{id: contrato.idlocatario}).await?.1.0;
+    let locador = busca_dados_usuario(&pool, IdInput{id: contrato.idlocador}).await?.1.0;
 
     let tipodocumentolocatario = tipo_documento(&locatario.documento)?;
     let tipodocumentolocador = tipo_documento(&locador.documento)?;
 
-    let enderecolocatario = busca_endereco_idusuario(Query(UserId{idusuario: locatario.idusuario})).await?.1.0;
-    let enderecolocador = busca_endereco_idusuario(Query(UserId{idusuario: locador.idusuario.clone()})).await?.1.0;
+    let enderecolocatario = busca_endereco(pool, contrato.idenderecolocatario).await?.1.0;
+    let enderecolocador = busca_endereco(pool, contrato.idenderecolocador).await?.1.0;
+    let enderecoretirada = busca_endereco(pool, contrato.idenderecoretirada).await?.1.0;
 
-    let maquina = busca_maquina_id(Query(IdInput{id: contrato.idmaquina})).await?.1.0;
+    let maquina = busca_maquina_id(State(pool.clone()), Query(IdInput{id: contrato.idmaquina})).await?.1.0;
 
-    let contabancaria = busca_conta_bancaria_idusuario(Query(IdInput{id: locador.idusuario})).await?.1.0;
-
-    let solicitacao = busca_solicitacao_idsolicitacao(Query(IdInput{id: contrato.idsolicitacaocontrato})).await?.1.0;
+    let contabancaria = busca_conta_bancaria_usuario(pool, IdInput{id: locador.idusuario}).await?.1.0;
 
     let contratopdf = ContratoPDF{
         idcontrato: contrato.idcontrato,
@@ -213,21 +349,21 @@ pub async fn gera_contrato_idsolicitacao(Query(id): Query<IdInput>)
         numeroenderecolocatario: enderecolocatario.numero,
         complementoenderecolocatario: enderecolocatario.complemento,
 
-        estadoenderecolocador: enderecolocador.estado.clone(),
-        cidadeenderecolocador: enderecolocador.cidade.clone(),
-        cependerecolocador: enderecolocador.cep.clone(),
-        bairroenderecolocador: enderecolocador.bairro.clone(),
-        logradouroenderecolocador: enderecolocador.logradouro.clone(),
-        numeroenderecolocador: enderecolocador.numero.clone(),
-        complementoenderecolocador: enderecolocador.complemento.clone(),
-
-        estadoenderecoretirada: enderecolocador.estado,
-        cidadeenderecoretirada: enderecolocador.cidade,
-        cependerecoretirada: enderecolocador.cep,
-        bairroenderecoretirada: enderecolocador.bairro,
-        logradouroenderecoretirada: enderecolocador.logradouro,
-        numeroenderecoretirada: enderecolocador.numero,
-        complementoenderecoretirada: enderecolocador.complemento,
+        estadoenderecolocador: enderecolocador.estado,
+        cidadeenderecolocador: enderecolocador.cidade,
+        cependerecolocador: enderecolocador.cep,
+        bairroenderecolocador: enderecolocador.bairro,
+        logradouroenderecolocador: enderecolocador.logradouro,
+        numeroenderecolocador: enderecolocador.numero,
+        complementoenderecolocador: enderecolocador.complemento,
+
+        estadoenderecoretirada: enderecoretirada.estado,
+        cidadeenderecoretirada: enderecoretirada.cidade,
+        cependerecoretirada: enderecoretirada.cep,
+        bairroenderecoretirada: enderecoretirada.bairro,
+        logradouroenderecoretirada: enderecoretirada.logradouro,
+        numeroenderecoretirada: enderecoretirada.numero,
+        complementoenderecoretirada: enderecoretirada.complemento,
 
         nomemaquina: maquina.nome,
         numeroseriemaquina: maquina.numeroserie,
@@ -237,32 +373,321 @@ pub async fn gera_contrato_idsolicitacao(Query(id): Query<IdInput>)
         numeroagenciabanco: contabancaria.numeroagencia,
         nomebanco: contabancaria.nomebanco,
 
-        medidatempolocacao: solicitacao.medidatempolocacao,
-        prazolocacao: solicitacao.prazolocacao,
-        valorlocacao: solicitacao.valorsolicitacao,
+        medidatempolocacao: contrato.medidatempolocacao.to_string(),
+        prazolocacao: contrato.prazolocacao,
+        valorlocacao: contrato.valorlocacao,
+        cidadeforo: contrato.cidadeforo,
         datacontrato: contrato.datacontrato.to_string(),
+        datainicio: datainicio.to_string(),
+        datafim: datafim.to_string(),
     };
 
-    return Ok((StatusCode::OK, Json(contratopdf)))
+    return Ok(contratopdf)
+}
 
+/// Gera o PDF do contrato com o modelo vigente e o salva junto ao contrato.
+pub async fn gera_documento_contrato(pool: &Pool, contrato: Contrato) -> Result<DocumentoContrato, ErroApi>{
+    let idcontrato = contrato.idcontrato.clone();
+    let contratopdf = monta_contrato_pdf(pool, contrato).await?;
+
+    let modelo = match modelo_contrato(VERSAO_MODELO_CONTRATO){
+      Some(modelo) => {
+        modelo
+      },
+      None => {
+        return Err(ErroApi::Interno(format!("Modelo de contrato {} não encontrado.", VERSAO_MODELO_CONTRATO)))
+      }
+    };
+    let blocos = renderiza_modelo(modelo, &contratopdf.campos())?;
+    let documento = gera_pdf(
+      "Contrato de Locação de Máquina",
+      &format!("MaqExpress - Contrato {} - Modelo {}", idcontrato, VERSAO_MODELO_CONTRATO),
+      &blocos
+    );
+    let hashdocumento = format!("{:x}", Sha256::digest(&documento));
+
+    let conn = &obtem_conn(pool).await?;
+    models::documentos_contratos::cadastra_documento_contrato(conn, DocumentoContrato{
+      idcontrato,
+      versaomodelo: VERSAO_MODELO_CONTRATO.to_string(),
+      hashdocumento,
+      documento,
+      datageracao: chrono::Utc::now().naive_utc()
+    }).await
 }
 
-pub async fn busca_contrato_idsolicitacao(Query(id): Query<IdInput>)
-    -> Result<(StatusCode, Json<Contrato>), (StatusCode, Json<String>)>{
+/// Devolve o PDF do contrato de uma solicitação. O documento é o mesmo para o locador e o
+/// locatário; o hash SHA-256 do arquivo vai no cabeçalho `ETag`.
+pub async fn busca_pdf_contrato_idsolicitacao(State(pool): State<Pool>, usuario: UsuarioAutenticado, Query(id): Query<IdInput>)
+    -> Result<Response, ErroApi>{
     if id.id.trim().is_empty(){
-      return Err((StatusCode::BAD_REQUEST, Json("Um ou mais campos estão vazios.".to_string())))
+      return Err(ErroApi::validacao("Um ou mais campos estão vazios."))
     }
 
     let id = id.id.trim().to_string();
 
-    let conn = &mut cria_conn()?;
+    let conn = &obtem_conn(&pool).await?;
+
+    let contrato = models::contratos::busca_contrato_idsolicitacao(conn, id).await?;
+    verifica_parte_contrato(&usuario, &contrato)?;
+
+    let documento = match models::documentos_contratos::busca_documento_contrato(conn, contrato.idcontrato.clone()).await{
+      Ok(documento) => {
+        documento
+      },
+      Err(ErroApi::NaoEncontrado(_)) => {
+        gera_documento_contrato(&pool, contrato).await?
+      },
+      Err(e) => {
+        return Err(e)
+      }
+    };
+
+    let nomearquivo = format!("inline; filename=\"contrato-{}.pdf\"", &documento.idcontrato[..documento.idcontrato.len().min(12)]);
+    return Ok((
+      StatusCode::OK,
+      [
+        (CONTENT_TYPE, "application/pdf".to_string()),
+        (CONTENT_DISPOSITION, nomearquivo),
+        (ETAG, format!("\"{}\"", documento.hashdocumento))
+      ],
+      documento.documento
+    ).into_response())
+}
+
+pub async fn busca_contrato_idsolicitacao(State(pool): State<Pool>, usuario: UsuarioAutenticado, Query(id): Query<IdInput>)
+    -> Result<(StatusCode, Json<Contrato>), ErroApi>{
+    if id.id.trim().is_empty(){
+      return Err(ErroApi::validacao("Um ou mais campos estão vazios."))
+    }
+
+    let id = id.id.trim().to_string();
+
+    let conn = &obtem_conn(&pool).await?;
 
     match models::contratos::busca_contrato_idsolicitacao(conn, id).await{
       Ok(contrato) => {
+        verifica_parte_contrato(&usuario, &contrato)?;
         return Ok((StatusCode::OK, Json(contrato)))
       },
       Err(e) => {
-        return Err((StatusCode::INTERNAL_SERVER_ERROR, Json(e)))
+        return Err(e)
       }
     }
-}
\ No newline at end of file
+}
+
+/// Avisa o locador e o locatário de uma mudança no contrato. Se houver `email`, ele é enviado
+/// para os dois.
+pub async fn notifica_partes_contrato(pool: &Pool, contrato: &Contrato, titulo: &str, mensagem: &str, email: Option<ConteudoEmail>)
+    -> Result<(), ErroApi>{
+    for idusuario in [&contrato.idlocador, &contrato.idlocatario]{
+      cadastra_notificacao(State(pool.clone()), Json(NotificacaoInput{
+        idusuario: idusuario.clone(),
+        titulo: titulo.to_string(),
+        mensagem: mensagem.to_string(),
+        onclick: "/contract-request".to_string(),
+        email: email.clone()
+      })).await?.1.0;
+    }
+    return Ok(())
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct RescisaoContratoInput{
+    pub id: String,
+    pub motivo: String
+}
+
+/// Encerra o contrato antes do fim do prazo. Qualquer uma das partes, ou um administrador,
+/// pode rescindir, desde que informe o motivo. A máquina fica livre a partir da rescisão.
+pub async fn rescinde_contrato(State(pool): State<Pool>, usuario: UsuarioAutenticado, input: Json<RescisaoContratoInput>)
+    -> Result<(StatusCode, Json<Contrato>), ErroApi>{
+    if input.id.trim().is_empty(){
+      return Err(ErroApi::validacao("Um ou mais campos estão vazios."))
+    }
+    if input.motivo.trim().is_empty(){
+      return Err(ErroApi::validacao_campo("motivo", "Informe o motivo da rescisão."))
+    }
+
+    let conn = &obtem_conn(&pool).await?;
+
+    let contrato = models::contratos::busca_contrato_id(conn, input.id.trim().to_string()).await?;
+    verifica_parte_contrato(&usuario, &contrato)?;
+
+    let contrato = match models::contratos::encerra_contrato(conn, contrato.idcontrato, StatusContrato::Rescindido,
+      Some(input.motivo.trim().to_string())).await{
+      Ok(contrato) => {
+        contrato
+      },
+      Err(e) => {
+        return Err(e)
+      }
+    };
+
+    let quem = if usuario.idusuario == contrato.idlocador { "O locador" }
+      else if usuario.idusuario == contrato.idlocatario { "O locatário" }
+      else { "A administração do MaqExpress" };
+    notifica_partes_contrato(&pool, &contrato, "Um contrato foi rescindido.",
+      &format!("{} rescindiu o contrato. Motivo: {}. Clique aqui para ver seus contratos.", quem, input.motivo.trim()), None).await?;
+
+    return Ok((StatusCode::OK, Json(contrato)))
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct RenovacaoContratoInput{
+    pub id: String,
+    /// Prazo somado ao contrato, na mesma medida de tempo dele.
+    pub prazoadicional: f64
+}
+
+/// Estende um contrato ativo a pedido do locatário. O valor do prazo adicional é calculado como o
+/// de uma locação nova da mesma máquina e somado ao valor do contrato.
+pub async fn renova_contrato(State(pool): State<Pool>, usuario: UsuarioAutenticado, input: Json<RenovacaoContratoInput>)
+    -> Result<(StatusCode, Json<Contrato>), ErroApi>{
+    if input.id.trim().is_empty(){
+      return Err(ErroApi::validacao("Um ou mais campos estão vazios."))
+    }
+    if !input.prazoadicional.is_finite() || input.prazoadicional <= 0.0{
+      return Err(ErroApi::validacao_campo("prazoadicional", "O prazo adicional deve ser maior que zero."))
+    }
+
+    let conn = &obtem_conn(&pool).await?;
+
+    let contrato = models::contratos::busca_contrato_id(conn, input.id.trim().to_string()).await?;
+    // A renovação é cobrada do locatário, então só ele pode pedi-la.
+    if usuario.idusuario != contrato.idlocatario{
+      return Err(ErroApi::Proibido("Só o locatário pode pedir a renovação do contrato.".to_string()))
+    }
+
+    let valoradicional = calcula_valor_aluguel(State(pool.clone()), Json(CalculoValorAluguel{
+      medida_prazo: contrato.medidatempolocacao,
+      prazo: input.prazoadicional,
+      idmaquina: contrato.idmaquina.clone()
+    })).await?.1.0;
+
+    let contrato = match models::contratos::renova_contrato(conn, contrato.idcontrato, input.prazoadicional, valoradicional).await{
+      Ok(contrato) => {
+        contrato
+      },
+      Err(e) => {
+        return Err(e)
+      }
+    };
+
+    notifica_partes_contrato(&pool, &contrato, "Um contrato foi renovado!",
+      &format!("O contrato foi estendido por mais {} {}, e o valor total passou a ser {}. Clique aqui para ver seus contratos.",
+        input.prazoadicional, contrato.medidatempolocacao.texto().to_lowercase(), formata_reais(contrato.valorlocacao)), None).await?;
+
+    return Ok((StatusCode::OK, Json(contrato)))
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct ContratosUsuarioInput{
+    pub id: String,
+    /// "Ativo", "Atrasado", "Concluído" ou "Rescindido". Sem o filtro, todos os contratos são listados.
+    pub status: Option<String>
+}
+
+async fn busca_contratos_usuario(pool: &Pool, usuario: &UsuarioAutenticado, input: ContratosUsuarioInput, comolocador: bool)
+    -> Result<Vec<Contrato>, ErroApi>{
+    if input.id.trim().is_empty(){
+      return Err(ErroApi::validacao("Um ou mais campos estão vazios."))
+    }
+    verifica_usuario_ou_admin(usuario, &input.id)?;
+
+    let status = match input.status.as_deref().map(str::trim){
+      Some("") | None => None,
+      Some(status) => Some(StatusContrato::from_str(status)?)
+    };
+
+    let conn = &obtem_conn(pool).await?;
+
+    models::contratos::busca_contratos_usuario(conn, input.id.trim().to_string(), comolocador, status).await
+}
+
+pub async fn busca_contratos_idlocador(State(pool): State<Pool>, usuario: UsuarioAutenticado, Query(input): Query<ContratosUsuarioInput>)
+    -> Result<(StatusCode, Json<Vec<Contrato>>), ErroApi>{
+    let contratos = busca_contratos_usuario(&pool, &usuario, input, true).await?;
+    return Ok((StatusCode::OK, Json(contratos)))
+}
+
+pub async fn busca_contratos_idlocatario(State(pool): State<Pool>, usuario: UsuarioAutenticado, Query(input): Query<ContratosUsuarioInput>)
+    -> Result<(StatusCode, Json<Vec<Contrato>>), ErroApi>{
+    let contratos = busca_contratos_usuario(&pool, &usuario, input, false).await?;
+    return Ok((StatusCode::OK, Json(contratos)))
+}
+
+/// Encerra o período dos contratos ativos que chegaram ao fim: os que já tiveram a máquina
+/// devolvida são concluídos, e os outros ficam atrasados até `registra_devolucao_contrato` os
+/// concluir. As partes são avisadas nos dois casos. Um contrato com erro fica no log e é tentado
+/// de novo na próxima rodada, sem impedir os outros. Roda periodicamente a partir de `main.rs` e
+/// devolve quantos foram encerrados.
+pub async fn encerra_contratos_terminados(pool: &Pool) -> Result<usize, ErroApi>{
+    let conn = &obtem_conn(pool).await?;
+
+    let terminados = models::contratos::busca_contratos_terminados(conn, chrono::Utc::now().naive_utc()).await?;
+    let mut encerrados = 0;
+    for contrato in &terminados{
+      match encerra_periodo_contrato(pool, contrato).await{
+        Ok(()) => {
+          encerrados += 1;
+        },
+        Err(e) => {
+          tracing::error!("Erro ao encerrar o período do contrato {}: {}", contrato.idcontrato, e);
+        }
+      }
+    }
+    return Ok(encerrados)
+}
+
+async fn encerra_periodo_contrato(pool: &Pool, contrato: &Contrato) -> Result<(), ErroApi>{
+    let conn = &obtem_conn(pool).await?;
+    let contrato = models::contratos::encerra_periodo_contrato(conn, contrato.idcontrato.clone()).await?;
+    match contrato.statuscontrato{
+      StatusContrato::Concluido => {
+        notifica_partes_contrato(pool, &contrato, "Contrato concluído!",
+          "O prazo do contrato terminou, e a máquina já tinha sido devolvida. Clique aqui para ver seus contratos.", None).await
+      },
+      _ => {
+        notifica_partes_contrato(pool, &contrato, "A máquina ainda não foi devolvida.",
+          "O prazo do contrato terminou, mas a devolução da máquina ainda não foi registrada. O contrato fica atrasado até ela ser registrada. Clique aqui para ver seus contratos.", None).await
+      }
+    }
+}
+
+/// Com quantos dias de antecedência as partes são avisadas do fim do contrato.
+pub const DIAS_AVISO_TERMINO_CONTRATO: i64 = 3;
+
+/// Avisa as partes dos contratos que terminam nos próximos dias. Cada contrato é avisado uma
+/// vez por período; um contrato com erro fica no log e é tentado de novo na próxima rodada.
+/// Roda periodicamente a partir de `main.rs` e devolve quantos foram avisados.
+pub async fn avisa_contratos_terminando(pool: &Pool) -> Result<usize, ErroApi>{
+    let conn = &obtem_conn(pool).await?;
+    let agora = chrono::Utc::now().naive_utc();
+
+    let terminando = models::contratos::busca_contratos_terminando(conn, agora, agora + TimeDelta::days(DIAS_AVISO_TERMINO_CONTRATO)).await?;
+    let mut avisados = 0;
+    for (contrato, datafim) in &terminando{
+      match avisa_contrato_terminando(pool, contrato, *datafim).await{
+        Ok(()) => {
+          avisados += 1;
+        },
+        Err(e) => {
+          tracing::error!("Erro ao avisar o fim do contrato {}: {}", contrato.idcontrato, e);
+        }
+      }
+    }
+    return Ok(avisados)
+}
+
+async fn avisa_contrato_terminando(pool: &Pool, contrato: &Contrato, datafim: NaiveDateTime) -> Result<(), ErroApi>{
+    let conn = &obtem_conn(pool).await?;
+    let maquina = busca_maquina_id(State(pool.clone()), Query(IdInput{id: contrato.idmaquina.clone()})).await?.1.0;
+    let email = renderiza_email(&EmailContratoTerminando{
+      nomemaquina: maquina.nome,
+      datafim
+    })?;
+    notifica_partes_contrato(pool, contrato, "Um contrato está perto do fim.",
+      &format!("O contrato termina em {}. Clique aqui para ver seus contratos.", datafim.format("%d/%m/%Y")), Some(email)).await?;
+    models::contratos::marca_aviso_termino(conn, contrato.idcontrato.clone()).await
+}
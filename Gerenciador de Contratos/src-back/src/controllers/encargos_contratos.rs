@@ -0,0 +1,165 @@
+use axum::{extract::{Query, State}, http::StatusCode, Json};
+use chrono::NaiveDateTime;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use crate::{dinheiro::formata_reais, erros::ErroApi, models::{self, contratos::{Contrato, StatusContrato}, encargos_contratos::{EncargoContrato, RegrasAtraso}, faturas::Fatura, precos_maquinas::MedidaTempo}};
+
+use super::{contratos::verifica_parte_contrato, locadoras::{PERCENTUAL_JUROS_MENSAL_PADRAO, PERCENTUAL_MULTA_PADRAO}, notificacoes::{cadastra_notificacao, NotificacaoInput}, obtem_conn, precos_maquinas::busca_tabela_precos, sessoes::UsuarioAutenticado, usuarios::IdInput, Pool};
+
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct DevolucaoContratoInput{
+    /// ID do contrato.
+    pub id: String,
+    /// Quando a máquina voltou. Sem ela, vale o momento do registro.
+    #[schema(value_type = Option<String>)]
+    pub datadevolucao: Option<NaiveDateTime>
+}
+
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct DevolucaoContratoReturn{
+    pub idcontrato: String,
+    pub statuscontrato: StatusContrato,
+    #[schema(value_type = String)]
+    pub datadevolucao: NaiveDateTime,
+    /// Vazio quando a devolução foi feita no prazo.
+    pub encargos: Vec<EncargoContrato>,
+    /// Fatura à vista com a soma dos encargos.
+    pub fatura: Option<Fatura>
+}
+
+/// Regras de atraso da locadora do locador. Locadores sem locadora cadastrada seguem as padrão.
+async fn busca_regras_atraso(pool: &Pool, contrato: &Contrato) -> Result<RegrasAtraso, ErroApi>{
+    let tarifahora = busca_tabela_precos(pool, &contrato.idmaquina).await?.tarifa(MedidaTempo::Horas)?;
+    let conn = &obtem_conn(pool).await?;
+    let (percentualmulta, percentualjurosmensal) = match models::locadoras::busca_locadora_idusuario(conn, contrato.idlocador.clone()).await{
+        Ok(locadora) => {
+            (locadora.percentualmulta, locadora.percentualjurosmensal)
+        },
+        Err(ErroApi::NaoEncontrado(_)) => {
+            (PERCENTUAL_MULTA_PADRAO, PERCENTUAL_JUROS_MENSAL_PADRAO)
+        },
+        Err(e) => {
+            return Err(e)
+        }
+    };
+    return Ok(RegrasAtraso{
+        tarifahora,
+        percentualmulta,
+        percentualjurosmensal
+    })
+}
+
+#[utoipa::path(
+    put,
+    tag = "Faturas",
+    path = "/registra_devolucao_contrato",
+    description = "Registra a volta da máquina ao locador. Conclui o contrato atrasado ou que já passou do
+    fim do período; um contrato devolvido antes do fim segue ativo e é concluído quando o período
+    terminar. Se a devolução passou do prazo, lança o tempo extra, a multa e os juros de mora da locadora, gera
+    uma fatura à vista com eles e avisa o locatário.",
+    responses(
+        (
+            status = 200,
+            description = "Devolução registrada, com os encargos lançados.",
+            body = DevolucaoContratoReturn
+        ),
+        (
+            status = 400,
+            description = "A data da devolução é futura ou anterior ao contrato."
+        ),
+        (
+            status = 403,
+            description = "O usuário autenticado não é o locador do contrato."
+        ),
+        (
+            status = 404,
+            description = "O contrato não existe."
+        ),
+        (
+            status = 409,
+            description = "A devolução já foi registrada."
+        ),
+    ),
+    request_body = DevolucaoContratoInput
+)]
+pub async fn registra_devolucao_contrato(State(pool): State<Pool>, usuario: UsuarioAutenticado, input: Json<DevolucaoContratoInput>)
+    -> Result<(StatusCode, Json<DevolucaoContratoReturn>), ErroApi>{
+    if input.id.trim().is_empty(){
+        return Err(ErroApi::validacao("Um ou mais campos estão vazios."))
+    }
+    let agora = chrono::Utc::now().naive_utc();
+    let devolucao = input.datadevolucao.unwrap_or(agora);
+    if devolucao > agora{
+        return Err(ErroApi::validacao_campo("datadevolucao", "A devolução não pode estar no futuro."))
+    }
+
+    let conn = &obtem_conn(&pool).await?;
+    let contrato = models::contratos::busca_contrato_id(conn, input.id.trim().to_string()).await?;
+    // Quem recebe a máquina de volta é o locador.
+    if usuario.idusuario != contrato.idlocador && !usuario.e_admin(){
+        return Err(ErroApi::Proibido("Só o locador do contrato pode registrar a devolução.".to_string()))
+    }
+
+    let regras = busca_regras_atraso(&pool, &contrato).await?;
+    let (contrato, encargos, fatura) = models::contratos::registra_devolucao_contrato(conn, contrato.idcontrato, devolucao, regras).await?;
+
+    let mensagem = match &fatura{
+        Some(fatura) => format!("A máquina foi devolvida depois do prazo, e os encargos de atraso somam {}. A fatura vence em {}. Clique aqui para ver seus contratos.",
+            formata_reais(fatura.valor), fatura.datavencimento.format("%d/%m/%Y")),
+        None => "O locador registrou a devolução da máquina dentro do prazo. Clique aqui para ver seus contratos.".to_string()
+    };
+    cadastra_notificacao(State(pool.clone()), Json(NotificacaoInput{
+        idusuario: contrato.idlocatario.clone(),
+        titulo: "Devolução registrada.".to_string(),
+        mensagem,
+        onclick: "/contract-request".to_string(),
+        email: None
+    })).await?;
+
+    return Ok((StatusCode::OK, Json(DevolucaoContratoReturn{
+        idcontrato: contrato.idcontrato,
+        statuscontrato: contrato.statuscontrato,
+        datadevolucao: devolucao,
+        encargos,
+        fatura
+    })))
+}
+
+#[utoipa::path(
+    get,
+    tag = "Faturas",
+    path = "/busca_encargos_idcontrato/{id}",
+    description = "Lista os encargos cobrados além do valor do contrato, como os de uma devolução atrasada.",
+    responses(
+        (
+            status = 200,
+            description = "Encargos do contrato.",
+            body = Vec<EncargoContrato>
+        ),
+        (
+            status = 403,
+            description = "O usuário autenticado não faz parte do contrato."
+        ),
+        (
+            status = 404,
+            description = "O contrato não existe."
+        ),
+    ),
+    params(
+        ("id" = String, Path, description = "ID do contrato"),
+    )
+)]
+pub async fn busca_encargos_idcontrato(State(pool): State<Pool>, usuario: UsuarioAutenticado, Query(input): Query<IdInput>)
+    -> Result<(StatusCode, Json<Vec<EncargoContrato>>), ErroApi>{
+    if input.id.trim().is_empty(){
+        return Err(ErroApi::validacao("Um ou mais campos estão vazios."))
+    }
+
+    let conn = &obtem_conn(&pool).await?;
+    let contrato = models::contratos::busca_contrato_id(conn, input.id.trim().to_string()).await?;
+    verifica_parte_contrato(&usuario, &contrato)?;
+
+    let encargos = models::encargos_contratos::busca_encargos_idcontrato(conn, contrato.idcontrato).await?;
+    return Ok((StatusCode::OK, Json(encargos)))
+}
@@ -1,12 +1,13 @@
-use axum::{extract::Query, Json};
+use axum::{extract::{Query, State}, Json};
 use hyper::StatusCode;
 use serde::{Deserialize, Serialize};
 use tracing::span::Id;
 use utoipa::ToSchema;
 
-use crate::{controllers::usuarios::{busca_usuario_id, IdInput}, models::{self, enderecos::Endereco}};
+use crate::{controllers::usuarios::{busca_dados_usuario, IdInput}, erros::ErroApi, models::{self, enderecos::Endereco}};
 
-use super::{cria_conn, enderecos_usuarios::busca_enderecousuario_idusuario, gera_hash, usuarios::UserId};
+use super::{obtem_conn, enderecos_usuarios::busca_enderecousuario_idusuario, sessoes::{verifica_usuario, verifica_usuario_ou_admin, UsuarioAutenticado}, usuarios::UserId, Pool};
+use crate::ids::gera_id;
 
 #[derive(Serialize, Deserialize, ToSchema)]
 pub struct EnderecoUsuarioInput{
@@ -56,14 +57,15 @@ pub struct EnderecoInput{
     request_body = EnderecoUsuarioInput    
 )]
 
-pub async fn cadastra_endereco_usuario(input: Json<EnderecoUsuarioInput>)
-    -> Result<(StatusCode, Json<Endereco>), (StatusCode, Json<String>)>{
+pub async fn cadastra_endereco_usuario(State(pool): State<Pool>, usuario: UsuarioAutenticado, input: Json<EnderecoUsuarioInput>)
+    -> Result<(StatusCode, Json<Endereco>), ErroApi>{
     if input.bairro.trim().is_empty() || input.cep.trim().is_empty() || input.cidade.trim().is_empty()
         || input.estado.trim().is_empty() || input.logradouro.trim().is_empty() 
         || input.numero.trim().is_empty() || input.pais.trim().is_empty()
         || input.idusuario.trim().is_empty(){
-        return Err((StatusCode::BAD_REQUEST, Json("Um ou mais campos estão vazios.".to_string())))
+        return Err(ErroApi::validacao("Um ou mais campos estão vazios."))
     }
+    verifica_usuario(&usuario, &input.idusuario)?;
     let complemento = input.complemento.clone();
     let complemento = match complemento{
         Some(complemento) => {
@@ -75,7 +77,7 @@ pub async fn cadastra_endereco_usuario(input: Json<EnderecoUsuarioInput>)
     };
 
     let endereco = Endereco{
-        idendereco: gera_hash(&input.logradouro),
+        idendereco: gera_id(),
         pais: input.pais.to_string(),
         estado: input.estado.to_string(),
         cidade: input.cidade.to_string(),
@@ -86,19 +88,19 @@ pub async fn cadastra_endereco_usuario(input: Json<EnderecoUsuarioInput>)
         complemento,
     };
 
-    let idusuario = busca_usuario_id(Query(IdInput{id: input.idusuario.clone()})).await?.1.idusuario.clone();
-    let conn = &mut cria_conn()?;
+    let idusuario = busca_dados_usuario(&pool, IdInput{id: input.idusuario.clone()}).await?.1.idusuario.clone();
+    let conn = &obtem_conn(&pool).await?;
 
     let endereco = match models::enderecos::cadastra_endereco(conn, endereco).await{
         Ok(endereco) => {
             endereco
         },
         Err(e) => {
-            return Err((StatusCode::INTERNAL_SERVER_ERROR, Json(e)))
+            return Err(e)
         }
     };
 
-    match crate::controllers::enderecos_usuarios::cadastra_endereco_usuario(Json(crate::controllers::enderecos_usuarios::EnderecoUsuarioInput{
+    match crate::controllers::enderecos_usuarios::cadastra_endereco_usuario(State(pool.clone()), Json(crate::controllers::enderecos_usuarios::EnderecoUsuarioInput{
         idendereco: endereco.idendereco.clone(),
         idusuario
     })).await{
@@ -123,8 +125,8 @@ pub async fn cadastra_endereco_usuario(input: Json<EnderecoUsuarioInput>)
             body = Endereco
         ),
         (
-            status = 500,
-            description = "ID inválido. Erro ao buscar o endereço."
+            status = 404,
+            description = "O ID inserido não pertence a um endereço registrado no sistema."
         ),
         (
             status = 400,
@@ -135,22 +137,38 @@ pub async fn cadastra_endereco_usuario(input: Json<EnderecoUsuarioInput>)
         ("idendereco" = String, Path, description = "ID do endereço"),
     )  
 )]
-pub async fn busca_endereco_id(Query(params): Query<String>)
-    -> Result<(StatusCode, Json<Endereco>), (StatusCode, Json<String>)>{
+pub async fn busca_endereco_id(State(pool): State<Pool>, usuario: UsuarioAutenticado, Query(params): Query<String>)
+    -> Result<(StatusCode, Json<Endereco>), ErroApi>{
+    if params.trim().is_empty(){
+        return Err(ErroApi::validacao("Um ou mais campos estão vazios."))
+    }
+
+    if !usuario.e_admin(){
+        let conn = &obtem_conn(&pool).await?;
+        let enderecousuario = models::enderecos_usuarios::busca_enderecousuario_idendereco(conn, params.trim().to_string()).await?;
+        verifica_usuario(&usuario, &enderecousuario.idusuario)?;
+    }
+
+    busca_endereco(&pool, params).await
+}
+
+/// Busca qualquer endereço pelo ID. Uso interno, sem verificação de sessão.
+pub async fn busca_endereco(pool: &Pool, params: String)
+    -> Result<(StatusCode, Json<Endereco>), ErroApi>{
     if params.trim().is_empty(){
-        return Err((StatusCode::BAD_REQUEST, Json("Um ou mais campos estão vazios.".to_string())))
+        return Err(ErroApi::validacao("Um ou mais campos estão vazios."))
     }
 
-    let id = params.to_string();
+    let id = params.trim().to_string();
 
-    let conn = &mut cria_conn()?;
+    let conn = &obtem_conn(pool).await?;
 
     match models::enderecos::busca_endereco_id(conn, id).await{
         Ok(endereco) => {
             return Ok((StatusCode::OK, Json(endereco)))
         },
         Err(e) => {
-            return Err((StatusCode::INTERNAL_SERVER_ERROR, Json(e)))
+            return Err(e)
         }
     }
 }
@@ -167,8 +185,8 @@ pub async fn busca_endereco_id(Query(params): Query<String>)
             body = Endereco
         ),
         (
-            status = 500,
-            description = "ID inválido. Erro ao buscar o endereço."
+            status = 404,
+            description = "O ID inserido não pertence a um endereço registrado no sistema."
         ),
         (
             status = 400,
@@ -179,37 +197,57 @@ pub async fn busca_endereco_id(Query(params): Query<String>)
         ("idusuario" = UserId, Path, description = "ID do usuário"),
     )  
 )]
-pub async fn busca_endereco_idusuario(Query(params): Query<UserId>)
-    -> Result<(StatusCode, Json<Endereco>), (StatusCode, Json<String>)>{
+pub async fn busca_endereco_idusuario(State(pool): State<Pool>, usuario: UsuarioAutenticado, Query(params): Query<UserId>)
+    -> Result<(StatusCode, Json<Endereco>), ErroApi>{
     if params.idusuario.trim().is_empty(){
-        return Err((StatusCode::BAD_REQUEST, Json("Um ou mais campos estão vazios.".to_string())))
+        return Err(ErroApi::validacao("Um ou mais campos estão vazios."))
     }
+    verifica_usuario_ou_admin(&usuario, &params.idusuario)?;
 
-    let idendereco = busca_enderecousuario_idusuario(axum::extract::Query(params)).await?.1.idendereco.to_string();
+    busca_endereco_usuario(&pool, params).await
+}
 
-    let endereco = busca_endereco_id(Query(idendereco)).await;
-    endereco
+/// Busca o endereço de qualquer usuário. Uso interno, sem verificação de sessão.
+pub async fn busca_endereco_usuario(pool: &Pool, params: UserId)
+    -> Result<(StatusCode, Json<Endereco>), ErroApi>{
+    if params.idusuario.trim().is_empty(){
+        return Err(ErroApi::validacao("Um ou mais campos estão vazios."))
+    }
+
+    let idendereco = busca_enderecousuario_idusuario(State(pool.clone()), axum::extract::Query(params)).await?.1.idendereco.to_string();
+
+    busca_endereco(pool, idendereco).await
 }
 
-pub async fn atualiza_endereco(endereco: Json<Endereco>)
-    -> Result<(StatusCode, Json<Endereco>), (StatusCode, Json<String>)>{
+pub async fn atualiza_endereco(State(pool): State<Pool>, usuario: UsuarioAutenticado, endereco: Json<Endereco>)
+    -> Result<(StatusCode, Json<Endereco>), ErroApi>{
     if endereco.bairro.trim().is_empty() || endereco.cep.trim().is_empty()
         || endereco.cidade.trim().is_empty() || endereco.idendereco.trim().is_empty()
         || endereco.estado.trim().is_empty() || endereco.logradouro.trim().is_empty()
         || endereco.numero.trim().is_empty() || endereco.pais.trim().is_empty(){
-        return Err((StatusCode::BAD_REQUEST, Json("Um ou mais campos estão vazios.".to_string())))
+        return Err(ErroApi::validacao("Um ou mais campos estão vazios."))
     }
 
     let endereco = endereco.0;
 
-    let conn = &mut cria_conn()?;
+    let conn = &obtem_conn(&pool).await?;
+
+    let idusuario = match models::enderecos_usuarios::busca_enderecousuario_idendereco(conn, endereco.idendereco.clone()).await{
+        Ok(enderecousuario) => {
+            enderecousuario.idusuario
+        },
+        Err(e) => {
+            return Err(e)
+        }
+    };
+    verifica_usuario(&usuario, &idusuario)?;
 
     match models::enderecos::atualiza_endereco(conn, endereco).await{
         Ok(endereco) => {
             return Ok((StatusCode::OK, Json(endereco)))
         },
         Err(e) => {
-            return Err((StatusCode::INTERNAL_SERVER_ERROR, Json(e)))
+            return Err(e)
         }
     }
 }
\ No newline at end of file
@@ -1,9 +1,10 @@
-use axum::{extract::Query, Json};
+use axum::{extract::{Query, State}, Json};
 use hyper::StatusCode;
 
-use crate::models::{self, enderecos_usuarios::EnderecoUsuario};
+use crate::{erros::ErroApi, models::{self, enderecos_usuarios::EnderecoUsuario}};
 
-use super::{cria_conn, gera_hash, usuarios::{IdInput, UserId}};
+use super::{obtem_conn, usuarios::{IdInput, UserId}, Pool};
+use crate::ids::gera_id;
 
 pub struct EnderecoUsuarioInput{
     pub idendereco: String,
@@ -13,23 +14,23 @@ pub struct EnderecoUsuarioInput{
 impl From<EnderecoUsuarioInput> for EnderecoUsuario{
     fn from(endereco: EnderecoUsuarioInput) -> Self {
         Self{
-            idenderecousuario: gera_hash(&endereco.idendereco),
+            idenderecousuario: gera_id(),
             idendereco: endereco.idendereco,
             idusuario: endereco.idusuario            
         }
     }
 }
 
-pub async fn cadastra_endereco_usuario(input: Json<EnderecoUsuarioInput>)
-    -> Result<(StatusCode, Json<String>), (StatusCode, Json<String>)>{
+pub async fn cadastra_endereco_usuario(State(pool): State<Pool>, input: Json<EnderecoUsuarioInput>)
+    -> Result<(StatusCode, Json<String>), ErroApi>{
     if input.idendereco.trim().is_empty() || input.idusuario.trim().is_empty(){
-        return Err((StatusCode::BAD_REQUEST, Json("Um ou mais campos estão vazios.".to_string())))
+        return Err(ErroApi::validacao("Um ou mais campos estão vazios."))
     }
 
-    let conn = &mut cria_conn()?;
+    let conn = &obtem_conn(&pool).await?;
 
     let enderecousuario = EnderecoUsuario{
-        idenderecousuario: gera_hash(&input.idendereco),
+        idenderecousuario: gera_id(),
         idendereco: input.idendereco.clone(),
         idusuario: input.idusuario.clone(),
     };
@@ -40,26 +41,26 @@ pub async fn cadastra_endereco_usuario(input: Json<EnderecoUsuarioInput>)
             return Ok((StatusCode::OK, Json(id)))
         },
         Err(e) => {
-            return Err((StatusCode::INTERNAL_SERVER_ERROR, Json(e)));
+            return Err(e);
         }
     }
 }
 
-pub async fn busca_enderecousuario_idusuario(Query(id): Query<UserId>)
-    -> Result<(StatusCode, Json<EnderecoUsuario>), (StatusCode, Json<String>)>{
+pub async fn busca_enderecousuario_idusuario(State(pool): State<Pool>, Query(id): Query<UserId>)
+    -> Result<(StatusCode, Json<EnderecoUsuario>), ErroApi>{
     if id.idusuario.trim().is_empty(){
-        return Err((StatusCode::BAD_REQUEST, Json("Um ou mais campos estão vazios.".to_string())))
+        return Err(ErroApi::validacao("Um ou mais campos estão vazios."))
     }
     let idusuario = id.idusuario.to_string();
 
-    let conn = &mut cria_conn()?;
+    let conn = &obtem_conn(&pool).await?;
 
     match models::enderecos_usuarios::busca_enderecousuario_idusuario(conn, idusuario).await{
         Ok(enderecosusuario) => {
             return Ok((StatusCode::OK, Json(enderecosusuario)))
         },
         Err(e) => {
-            return Err((StatusCode::INTERNAL_SERVER_ERROR, Json(e)))
+            return Err(e)
         }
     }
 }
\ No newline at end of file
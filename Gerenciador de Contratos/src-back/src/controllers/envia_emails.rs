@@ -1,132 +1,307 @@
-use axum::{http::StatusCode, Json};
-use dotenv::dotenv;
-use std::env;
-use lettre::{message::header::ContentType, transport::smtp::authentication::{Credentials, Mechanism}, Message, SmtpTransport, Transport};
-
-pub fn verifica_credenciais_email() -> Result<(String, String), String>{
-    dotenv().ok();
-    let smtp_username = match
-        env::var("smtp_username") {
-        Ok(username) => {username},
-        Err(e) => {
-            return Err(format!("smtp_username não definido no arquivo .env: {}", e))
+use axum::http::StatusCode;
+use chrono::NaiveDateTime;
+
+use crate::{dinheiro::{formata_reais, Dinheiro}, emails::{envia_email, Anexo, ConteudoEmail}, erros::ErroApi};
+
+use super::escapa_html;
+
+/// Layout comum a todos os e-mails em HTML. O conteúdo de cada modelo entra em `{{conteudo}}`.
+const LAYOUT_EMAIL: &str = include_str!("../templates/emails/layout.html");
+
+/// Versões em HTML e em texto puro de cada modelo de `templates/emails/`.
+pub fn modelo_email(nome: &str) -> Option<(&'static str, &'static str)>{
+    match nome{
+        "codigo" => Some((include_str!("../templates/emails/codigo.html"), include_str!("../templates/emails/codigo.txt"))),
+        "nova_solicitacao" => Some((include_str!("../templates/emails/nova_solicitacao.html"), include_str!("../templates/emails/nova_solicitacao.txt"))),
+        "resposta_solicitacao" => Some((include_str!("../templates/emails/resposta_solicitacao.html"), include_str!("../templates/emails/resposta_solicitacao.txt"))),
+        "contrato_gerado" => Some((include_str!("../templates/emails/contrato_gerado.html"), include_str!("../templates/emails/contrato_gerado.txt"))),
+        "contrato_terminando" => Some((include_str!("../templates/emails/contrato_terminando.html"), include_str!("../templates/emails/contrato_terminando.txt"))),
+        "fatura_paga" => Some((include_str!("../templates/emails/fatura_paga.html"), include_str!("../templates/emails/fatura_paga.txt"))),
+        _ => None
+    }
+}
+
+/// Os dados de um modelo de e-mail. Cada modelo tem a sua struct, com os campos que o
+/// texto usa.
+pub trait ModeloEmail{
+    /// Nome dos arquivos do modelo em `templates/emails/`, sem a extensão.
+    const NOME: &'static str;
+
+    fn titulo(&self) -> String;
+
+    /// Valores dos campos `{{campo}}` do modelo, sem escapar. O HTML é escapado ao preencher.
+    fn campos(&self) -> Vec<(&'static str, String)>;
+
+    fn anexos(&self) -> Vec<Anexo>{
+        Vec::new()
+    }
+}
+
+fn preenche_modelo(modelo: &str, campos: &[(&str, String)], html: bool) -> Result<String, ErroApi>{
+    let mut texto = modelo.to_string();
+    for (campo, valor) in campos{
+        let valor = if html { escapa_html(valor) } else { valor.clone() };
+        texto = texto.replace(&format!("{{{{{}}}}}", campo), &valor);
+    }
+    if let Some(inicio) = texto.find("{{"){
+        let resto = &texto[inicio..];
+        let fim = resto.find("}}").map(|f| f + 2).unwrap_or(resto.len());
+        return Err(ErroApi::Interno(format!("Campo sem valor no modelo de e-mail: {}", &resto[..fim])))
+    }
+    return Ok(texto)
+}
+
+/// Preenche as versões em HTML e em texto puro do modelo.
+pub fn renderiza_email<M: ModeloEmail>(modelo: &M) -> Result<ConteudoEmail, ErroApi>{
+    let (html, texto) = match modelo_email(M::NOME){
+        Some(modelo) => {
+            modelo
+        },
+        None => {
+            return Err(ErroApi::Interno(format!("Modelo de e-mail {} não encontrado.", M::NOME)))
         }
     };
-    let smtp_password = match env::var("smtp_password"){
-        Ok(password) => {password},
-        Err(e) => {
-            return Err(format!("smtp_password não definido no arquivo .env: {}", e))
+    let titulo = modelo.titulo();
+    let campos = modelo.campos();
+
+    let conteudo = preenche_modelo(html, &campos, true)?;
+    let html = LAYOUT_EMAIL
+        .replace("{{titulo}}", &escapa_html(&titulo))
+        .replace("{{conteudo}}", conteudo.trim_end());
+
+    return Ok(ConteudoEmail{
+        assunto: format!("MaqExpress | {}", titulo),
+        html,
+        texto: preenche_modelo(texto, &campos, false)?,
+        anexos: modelo.anexos()
+    })
+}
+
+fn formata_data(data: NaiveDateTime) -> String{
+    data.format("%d/%m/%Y às %H:%M").to_string()
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum FinalidadeCodigo{
+    Ativacao,
+    Recuperacao,
+    /// Vínculo com uma conta do provedor de login com esse nome.
+    VinculoProvedor(String),
+    DesvinculoProvedor(String)
+}
+
+impl FinalidadeCodigo{
+    /// Como a finalidade é gravada com o código. O código só é aceito na operação para a qual
+    /// foi enviado.
+    pub fn chave(&self) -> String{
+        match self{
+            FinalidadeCodigo::Ativacao => "Ativação".to_string(),
+            FinalidadeCodigo::Recuperacao => "Recuperação".to_string(),
+            FinalidadeCodigo::VinculoProvedor(provedor) => format!("Vínculo {}", provedor),
+            FinalidadeCodigo::DesvinculoProvedor(provedor) => format!("Desvínculo {}", provedor)
         }
-    };
-    let credenciais = (smtp_username, smtp_password);
-    Ok(credenciais)
+    }
 }
 
-pub async fn envia_email_codigo(email: String, assunto: &str, codigo: String) 
-    -> Result<(StatusCode, Json<String>), (StatusCode, Json<String>)>{
-    // carregando as credenciais SMTP
-    let credenciais = match verifica_credenciais_email(){
-        Ok(credenciais) => {
-            credenciais
-        },
-        Err(e) => {
-            return Err((StatusCode::SERVICE_UNAVAILABLE, Json(e)))
+pub struct EmailCodigo{
+    pub finalidade: FinalidadeCodigo,
+    pub codigo: String
+}
+
+impl ModeloEmail for EmailCodigo{
+    const NOME: &'static str = "codigo";
+
+    fn titulo(&self) -> String{
+        match &self.finalidade{
+            FinalidadeCodigo::Ativacao => "Ativação de Conta".to_string(),
+            FinalidadeCodigo::Recuperacao => "Recuperação de Senha".to_string(),
+            FinalidadeCodigo::VinculoProvedor(_) => "Vínculo de Conta".to_string(),
+            FinalidadeCodigo::DesvinculoProvedor(_) => "Desvínculo de Conta".to_string()
         }
-    };
+    }
 
-    let smtp_username = credenciais.0;
-    let smtp_password = credenciais.1;
+    fn campos(&self) -> Vec<(&'static str, String)>{
+        let mensagem = match &self.finalidade{
+            FinalidadeCodigo::Ativacao => "Bem-Vindo(a) ao MaqExpress! Use o código abaixo para ativar sua conta:".to_string(),
+            FinalidadeCodigo::Recuperacao => "Você solicitou a redefinição da sua senha. Use o código abaixo para redefinir sua senha:".to_string(),
+            FinalidadeCodigo::VinculoProvedor(provedor) => format!("Alguém tentou entrar na sua conta usando uma conta {}. Se foi você, use o código abaixo para vincular as duas contas:", provedor),
+            FinalidadeCodigo::DesvinculoProvedor(provedor) => format!("Você solicitou o desvínculo da sua conta {}. Use o código abaixo para confirmar:", provedor)
+        };
+        vec![
+            ("mensagem", mensagem),
+            ("codigo", self.codigo.clone()),
+        ]
+    }
+}
 
-    let assunto_titulo;
-    let assunto_corpo;
-    match assunto{
-        "recuperação de senha" => {
-            assunto_titulo = "Recuperação de Senha";
-            assunto_corpo = "Você solicitou a redefinição da sua senha. Use o código abaixo para redefinir sua senha:"
-        },
-        "ativação de conta" => {
-            assunto_titulo = "Ativação de Conta";
-            assunto_corpo = "Bem-Vindo(a) ao MaqExpress! Use o código abaixo para ativar sua conta:"
-        },
-        _ => {
-            return Err((StatusCode::BAD_REQUEST, Json("Assunto inválido.".to_string())))
+/// Enviado ao locador quando alguém solicita uma das suas máquinas.
+pub struct EmailNovaSolicitacao{
+    pub nomelocatario: String,
+    pub nomemaquina: String,
+    pub prazolocacao: f64,
+    pub medidatempolocacao: String,
+    pub datainicio: NaiveDateTime,
+    pub valorsolicitacao: Dinheiro,
+    pub diasexpiracao: i64
+}
+
+impl ModeloEmail for EmailNovaSolicitacao{
+    const NOME: &'static str = "nova_solicitacao";
+
+    fn titulo(&self) -> String{
+        "Nova Solicitação de Aluguel".to_string()
+    }
+
+    fn campos(&self) -> Vec<(&'static str, String)>{
+        vec![
+            ("nomelocatario", self.nomelocatario.clone()),
+            ("nomemaquina", self.nomemaquina.clone()),
+            ("prazolocacao", self.prazolocacao.to_string()),
+            ("medidatempolocacao", self.medidatempolocacao.to_lowercase()),
+            ("datainicio", formata_data(self.datainicio)),
+            ("valorsolicitacao", formata_reais(self.valorsolicitacao)),
+            ("diasexpiracao", self.diasexpiracao.to_string()),
+        ]
+    }
+}
+
+/// Enviado ao locatário quando o locador aprova ou recusa a solicitação.
+pub struct EmailRespostaSolicitacao{
+    pub nomemaquina: String,
+    pub aprovada: bool
+}
+
+impl ModeloEmail for EmailRespostaSolicitacao{
+    const NOME: &'static str = "resposta_solicitacao";
+
+    fn titulo(&self) -> String{
+        match self.aprovada{
+            true => "Solicitação Aprovada".to_string(),
+            false => "Solicitação Recusada".to_string()
         }
     }
 
-    // o servidor SMTP e porta
-    let smtp_server = "smtp.gmail.com";
-    //let smtp_port = 587;
-    // credenciais de autenticação SMTP
-    let smtp_credentials = Credentials::new(
-        smtp_username.to_string(), smtp_password.to_string()
-    );
-
-    // transporte SMTP
-    let smtp_transport = SmtpTransport::starttls_relay(&smtp_server)
-        .unwrap()
-        .credentials(smtp_credentials)
-        .authentication(vec![Mechanism::Plain])
-        .build();
-
-    let email = Message::builder()
-        .from("gerenciadordecontratosgdc@gmail.com".parse().unwrap())
-        .to(email.parse().unwrap())
-        .subject(format!("MaqExpress | {}", assunto_titulo))
-        .header(ContentType::parse("text/html").unwrap()) // Define o tipo de conteúdo como HTML
-        .body(
-            format!(
-                r#"
-                <!DOCTYPE html>
-                <html>
-                <head>
-                    <style>
-                        body {{
-                            font-family: Arial, sans-serif;
-                            margin: 20px;
-                        }}
-                        .container {{
-                            padding: 20px;
-                            border: 1px solid #ddd;
-                            border-radius: 5px;
-                            background-color: #f9f9f9;
-                        }}
-                        .header {{
-                            font-size: 24px;
-                            font-weight: bold;
-                            color: #333;
-                        }}
-                        .code {{
-                            font-size: 18px;
-                            font-weight: bold;
-                            color:rgb(2, 119, 74);
-                            padding: 10px;
-                            border: 1px solidrgb(3, 102, 64);
-                            border-radius: 5px;
-                            display: inline-block;
-                        }}
-                    </style>
-                </head>
-                <body>
-                    <div class="container">
-                        <div class="header">{assunto_titulo}</div>
-                        <p>Olá,</p>
-                        <p>{assunto_corpo}</p>
-                        <div class="code">{codigo}</div>
-                        <p>Se você não solicitou isso, por favor ignore este e-mail.</p>
-                        <p>Atenciosamente,<br>Equipe do MaqExpress </p>
-                    </div>
-                </body>
-                </html>
-                "#,
-                codigo = codigo, assunto_corpo = assunto_corpo
-            )
-        )
-        .unwrap();
-
-    // enviar o e-mail usando o transporte SMTP
-    match smtp_transport.send(&email) {
-        Ok(_) => println!("E-mail enviado com sucesso!"),
-        Err(err) => eprintln!("Erro ao enviar e-mail: {:?}", err),
-    }
-    return Ok((StatusCode::CREATED, Json(codigo)))
-}
\ No newline at end of file
+    fn campos(&self) -> Vec<(&'static str, String)>{
+        let (resposta, proximopasso) = match self.aprovada{
+            true => ("aprovada", "O contrato de locação será gerado em seguida, e você o receberá por e-mail."),
+            false => ("recusada", "Você pode procurar outras máquinas disponíveis no catálogo do MaqExpress.")
+        };
+        vec![
+            ("nomemaquina", self.nomemaquina.clone()),
+            ("resposta", resposta.to_string()),
+            ("proximopasso", proximopasso.to_string()),
+        ]
+    }
+}
+
+/// Enviado às duas partes quando o contrato é gerado, com o PDF em anexo se ele já existir.
+pub struct EmailContratoGerado{
+    pub idcontrato: String,
+    pub nomemaquina: String,
+    pub prazolocacao: f64,
+    pub medidatempolocacao: String,
+    pub valorlocacao: Dinheiro,
+    pub pdf: Option<Vec<u8>>
+}
+
+impl ModeloEmail for EmailContratoGerado{
+    const NOME: &'static str = "contrato_gerado";
+
+    fn titulo(&self) -> String{
+        "Contrato Gerado".to_string()
+    }
+
+    fn campos(&self) -> Vec<(&'static str, String)>{
+        let documento = match self.pdf{
+            Some(_) => "O contrato segue em anexo, em PDF. Ele também fica disponível no MaqExpress.",
+            None => "O PDF do contrato fica disponível no MaqExpress."
+        };
+        vec![
+            ("nomemaquina", self.nomemaquina.clone()),
+            ("prazolocacao", self.prazolocacao.to_string()),
+            ("medidatempolocacao", self.medidatempolocacao.to_lowercase()),
+            ("valorlocacao", formata_reais(self.valorlocacao)),
+            ("documento", documento.to_string()),
+        ]
+    }
+
+    fn anexos(&self) -> Vec<Anexo>{
+        match &self.pdf{
+            Some(pdf) => vec![Anexo{
+                nome: format!("contrato-{}.pdf", &self.idcontrato[..self.idcontrato.len().min(12)]),
+                tipo: "application/pdf".to_string(),
+                conteudo: pdf.clone()
+            }],
+            None => Vec::new()
+        }
+    }
+}
+
+/// Enviado às duas partes alguns dias antes do fim do contrato.
+pub struct EmailContratoTerminando{
+    pub nomemaquina: String,
+    pub datafim: NaiveDateTime
+}
+
+impl ModeloEmail for EmailContratoTerminando{
+    const NOME: &'static str = "contrato_terminando";
+
+    fn titulo(&self) -> String{
+        "Contrato Perto do Fim".to_string()
+    }
+
+    fn campos(&self) -> Vec<(&'static str, String)>{
+        vec![
+            ("nomemaquina", self.nomemaquina.clone()),
+            ("datafim", formata_data(self.datafim)),
+        ]
+    }
+}
+
+/// Enviado às duas partes quando uma fatura é quitada, com o recibo em anexo.
+pub struct EmailFaturaPaga{
+    pub idfatura: String,
+    pub nomemaquina: String,
+    pub numeroparcela: i32,
+    pub valor: Dinheiro,
+    pub recibo: Vec<u8>
+}
+
+impl ModeloEmail for EmailFaturaPaga{
+    const NOME: &'static str = "fatura_paga";
+
+    fn titulo(&self) -> String{
+        "Fatura Paga".to_string()
+    }
+
+    fn campos(&self) -> Vec<(&'static str, String)>{
+        vec![
+            ("nomemaquina", self.nomemaquina.clone()),
+            ("numeroparcela", self.numeroparcela.to_string()),
+            ("valor", formata_reais(self.valor)),
+        ]
+    }
+
+    fn anexos(&self) -> Vec<Anexo>{
+        vec![Anexo{
+            nome: format!("recibo-{}.pdf", &self.idfatura[..self.idfatura.len().min(12)]),
+            tipo: "application/pdf".to_string(),
+            conteudo: self.recibo.clone()
+        }]
+    }
+}
+
+pub async fn envia_email_codigo(email: String, finalidade: FinalidadeCodigo, codigo: String)
+    -> Result<StatusCode, ErroApi>{
+    let conteudo = renderiza_email(&EmailCodigo{finalidade, codigo})?;
+
+    // O transporte é escolhido no `.env`; veja `crate::emails`.
+    match envia_email(conteudo.para(&email)).await{
+        Ok(_) => {
+            return Ok(StatusCode::CREATED)
+        },
+        Err(e) => {
+            return Err(e)
+        }
+    }
+}
@@ -0,0 +1,583 @@
+use std::str::FromStr;
+
+use axum::{extract::{Query, State}, http::{header::{CONTENT_DISPOSITION, CONTENT_TYPE, ETAG}, StatusCode}, response::{IntoResponse, Response}, Json};
+use chrono::{Months, NaiveDateTime, TimeDelta};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use utoipa::ToSchema;
+
+use crate::{dinheiro::{formata_reais, Dinheiro}, erros::ErroApi, ids::gera_id, models::{self, contratos::{Contrato, StatusContrato}, faturas::{Fatura, StatusFatura, DIAS_VENCIMENTO_FATURA}, recibos_faturas::ReciboFatura, reservas_maquinas::{busca_reserva_idsolicitacao, calcula_fim_locacao}}, pdf::gera_pdf};
+
+use super::{contratos::{notifica_partes_contrato, renderiza_modelo, tipo_documento, verifica_parte_contrato}, envia_emails::{renderiza_email, EmailFaturaPaga}, obtem_conn, sessoes::{verifica_usuario_ou_admin, UsuarioAutenticado}, usuarios::IdInput, Pool};
+
+/// Limite de parcelas por geração, nos planos mensal e personalizado.
+pub const MAXIMO_PARCELAS: usize = 120;
+
+#[derive(Serialize, Deserialize, ToSchema, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum PlanoFaturamento{
+    /// Uma parcela com o valor todo.
+    AVista,
+    /// Uma parcela por mês do prazo do contrato, com o valor dividido igualmente.
+    Mensal,
+    /// As parcelas informadas em `parcelas`.
+    Personalizado
+}
+
+#[derive(Serialize, Deserialize, ToSchema, Debug, Clone, PartialEq)]
+pub struct ParcelaFatura{
+    pub valor: Dinheiro,
+    #[schema(value_type = String)]
+    pub datavencimento: NaiveDateTime
+}
+
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct FaturasContratoInput{
+    pub idcontrato: String,
+    /// "a_vista", "mensal" ou "personalizado".
+    pub plano: PlanoFaturamento,
+    /// Só no plano personalizado. A soma das parcelas deve ser o valor a faturar.
+    #[serde(default)]
+    pub parcelas: Vec<ParcelaFatura>
+}
+
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct FaturasUsuarioInput{
+    pub id: String,
+    /// "Pendente", "Vencida" ou "Paga". Sem o filtro, todas as faturas são listadas.
+    pub status: Option<String>
+}
+
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct PagamentoFaturaInput{
+    pub idfatura: String,
+    /// Valor recebido. Pode ser menor que o valor em aberto, para pagamentos parciais.
+    pub valor: Dinheiro
+}
+
+/// Monta as parcelas de `valor` conforme o plano. `inicio` e `fim` delimitam o período
+/// cobrado: no plano mensal, cada mês entre eles gera uma parcela, que vence
+/// `DIAS_VENCIMENTO_FATURA` dias depois do início do mês.
+pub fn calcula_parcelas(plano: PlanoFaturamento, total: Dinheiro, inicio: NaiveDateTime, fim: NaiveDateTime, parcelas: &[ParcelaFatura])
+    -> Result<Vec<ParcelaFatura>, ErroApi>{
+    if !total.e_positivo(){
+        return Err(ErroApi::validacao_campo("valor", "O valor a faturar deve ser maior que zero."))
+    }
+    let prazo = TimeDelta::days(DIAS_VENCIMENTO_FATURA);
+
+    match plano{
+        PlanoFaturamento::AVista => {
+            return Ok(vec![ParcelaFatura{
+                valor: total,
+                datavencimento: inicio + prazo
+            }])
+        },
+        PlanoFaturamento::Mensal => {
+            let mut meses = Vec::new();
+            while let Some(mes) = inicio.checked_add_months(Months::new(meses.len() as u32)){
+                if mes >= fim && !meses.is_empty(){
+                    break
+                }
+                if meses.len() == MAXIMO_PARCELAS{
+                    return Err(ErroApi::validacao_campo("plano", "O contrato é longo demais para o plano mensal."))
+                }
+                meses.push(mes);
+            }
+            let valores = total.divide(meses.len());
+            return Ok(meses.into_iter().zip(valores).map(|(mes, valor)| ParcelaFatura{
+                valor,
+                datavencimento: mes + prazo
+            }).collect())
+        },
+        PlanoFaturamento::Personalizado => {
+            if parcelas.is_empty() || parcelas.len() > MAXIMO_PARCELAS{
+                return Err(ErroApi::validacao_campo("parcelas", &format!("Informe de 1 a {} parcelas.", MAXIMO_PARCELAS)))
+            }
+            if parcelas.iter().any(|parcela| !parcela.valor.e_positivo()){
+                return Err(ErroApi::validacao_campo("parcelas", "O valor de cada parcela deve ser maior que zero."))
+            }
+            let soma: Dinheiro = parcelas.iter().map(|parcela| parcela.valor).sum();
+            if soma != total{
+                return Err(ErroApi::validacao_campo("parcelas",
+                    &format!("A soma das parcelas deve ser {}.", formata_reais(total))))
+            }
+            let mut parcelas = parcelas.to_vec();
+            parcelas.sort_by_key(|parcela| parcela.datavencimento);
+            return Ok(parcelas)
+        }
+    }
+}
+
+/// Só o locador e os administradores lançam as faturas e os pagamentos de um contrato.
+fn verifica_locador_contrato(usuario: &UsuarioAutenticado, contrato: &Contrato) -> Result<(), ErroApi>{
+    if usuario.idusuario != contrato.idlocador && !usuario.e_admin(){
+        return Err(ErroApi::Proibido("Só o locador do contrato pode alterar as faturas dele.".to_string()))
+    }
+    return Ok(())
+}
+
+#[utoipa::path(
+    post,
+    tag = "Faturas",
+    path = "/gera_faturas_contrato",
+    description = "Gera as faturas do valor do contrato que ainda não foi faturado: à vista, mensais ou
+    com as parcelas informadas. Depois de uma renovação, gera as faturas do valor adicional.",
+    responses(
+        (
+            status = 201,
+            description = "Faturas geradas.",
+            body = Vec<Fatura>
+        ),
+        (
+            status = 400,
+            description = "As parcelas não somam o valor a faturar OU são parcelas demais."
+        ),
+        (
+            status = 403,
+            description = "O usuário autenticado não é o locador do contrato."
+        ),
+        (
+            status = 409,
+            description = "O contrato foi rescindido OU já foi todo faturado."
+        ),
+    ),
+    request_body = FaturasContratoInput
+)]
+pub async fn gera_faturas_contrato(State(pool): State<Pool>, usuario: UsuarioAutenticado, input: Json<FaturasContratoInput>)
+    -> Result<(StatusCode, Json<Vec<Fatura>>), ErroApi>{
+    if input.idcontrato.trim().is_empty(){
+        return Err(ErroApi::validacao("Um ou mais campos estão vazios."))
+    }
+
+    let conn = &obtem_conn(&pool).await?;
+    let contrato = models::contratos::busca_contrato_id(conn, input.idcontrato.trim().to_string()).await?;
+    verifica_locador_contrato(&usuario, &contrato)?;
+
+    // O restante a faturar e as parcelas são calculados com o contrato travado, para que duas
+    // gerações ao mesmo tempo não faturem o mesmo valor.
+    let plano = input.plano;
+    let personalizadas = input.parcelas.clone();
+    let agora = chrono::Utc::now().naive_utc();
+    let (faturas, restante) = models::faturas::cadastra_faturas_contrato(conn, contrato.idcontrato.clone(),
+        move |conn, contrato, existentes, restante| {
+        // Um contrato concluído ainda pode ser faturado: o valor e os encargos de atraso continuam devidos.
+        if contrato.statuscontrato == StatusContrato::Rescindido{
+            return Err(ErroApi::Conflito(format!("O contrato já está '{}'.", contrato.statuscontrato)))
+        }
+
+        // O período cobrado é o da reserva, que já inclui as renovações. As primeiras faturas contam
+        // do início dela; as de uma renovação, de agora.
+        let (iniciolocacao, fim) = match busca_reserva_idsolicitacao(conn, &contrato.idsolicitacaocontrato)?{
+            Some(reserva) => (reserva.datainicio, reserva.datafim),
+            None => (contrato.datacontrato,
+                calcula_fim_locacao(contrato.datacontrato, contrato.prazolocacao, contrato.medidatempolocacao)?)
+        };
+        let inicio = if existentes.is_empty() { iniciolocacao } else { agora.max(iniciolocacao) };
+        let parcelas = calcula_parcelas(plano, restante, inicio, fim.max(inicio), &personalizadas)?;
+
+        return Ok(parcelas.into_iter().enumerate().map(|(i, parcela)| Fatura{
+            idfatura: gera_id(),
+            idcontrato: contrato.idcontrato.clone(),
+            numeroparcela: (existentes.len() + i + 1) as i32,
+            valor: parcela.valor,
+            valorpago: Dinheiro::ZERO,
+            datavencimento: parcela.datavencimento,
+            status: StatusFatura::Pendente,
+            datacriacao: agora,
+            datapagamento: None
+        }).collect())
+    }).await?;
+
+    notifica_partes_contrato(&pool, &contrato, "Faturas geradas!",
+        &format!("O locador gerou {} fatura(s) para o contrato, somando {}. Clique aqui para ver seus contratos.",
+            faturas.len(), formata_reais(restante)), None).await?;
+
+    return Ok((StatusCode::CREATED, Json(faturas)))
+}
+
+#[utoipa::path(
+    get,
+    tag = "Faturas",
+    path = "/busca_faturas_idcontrato/{id}",
+    description = "Lista as faturas do contrato, na ordem das parcelas.",
+    responses(
+        (
+            status = 200,
+            description = "Faturas do contrato.",
+            body = Vec<Fatura>
+        ),
+        (
+            status = 403,
+            description = "O usuário autenticado não faz parte do contrato."
+        ),
+        (
+            status = 404,
+            description = "O contrato não existe."
+        ),
+    ),
+    params(
+        ("id" = String, Path, description = "ID do contrato"),
+    )
+)]
+pub async fn busca_faturas_idcontrato(State(pool): State<Pool>, usuario: UsuarioAutenticado, Query(input): Query<IdInput>)
+    -> Result<(StatusCode, Json<Vec<Fatura>>), ErroApi>{
+    if input.id.trim().is_empty(){
+        return Err(ErroApi::validacao("Um ou mais campos estão vazios."))
+    }
+
+    let conn = &obtem_conn(&pool).await?;
+    let contrato = models::contratos::busca_contrato_id(conn, input.id.trim().to_string()).await?;
+    verifica_parte_contrato(&usuario, &contrato)?;
+
+    let faturas = models::faturas::busca_faturas_idcontrato(conn, contrato.idcontrato).await?;
+    return Ok((StatusCode::OK, Json(faturas)))
+}
+
+async fn busca_faturas_usuario(pool: &Pool, usuario: &UsuarioAutenticado, input: FaturasUsuarioInput, comolocador: bool)
+    -> Result<Vec<Fatura>, ErroApi>{
+    if input.id.trim().is_empty(){
+        return Err(ErroApi::validacao("Um ou mais campos estão vazios."))
+    }
+    verifica_usuario_ou_admin(usuario, &input.id)?;
+
+    let status = match input.status.as_deref().map(str::trim){
+        Some("") | None => None,
+        Some(status) => Some(StatusFatura::from_str(status)?)
+    };
+
+    let conn = &obtem_conn(pool).await?;
+    models::faturas::busca_faturas_usuario(conn, input.id.trim().to_string(), comolocador, status).await
+}
+
+#[utoipa::path(
+    get,
+    tag = "Faturas",
+    path = "/busca_faturas_idlocador/{id}",
+    description = "Lista as faturas a receber do locador, das que vencem primeiro para as que vencem depois.",
+    responses(
+        (
+            status = 200,
+            description = "Faturas dos contratos do locador.",
+            body = Vec<Fatura>
+        ),
+        (
+            status = 400,
+            description = "Status de fatura inválido."
+        ),
+        (
+            status = 403,
+            description = "O usuário autenticado não é o dono da conta nem administrador."
+        ),
+    ),
+    params(
+        ("id" = String, Path, description = "ID do locador"),
+        ("status" = Option<String>, Query, description = "\"Pendente\", \"Vencida\" ou \"Paga\""),
+    )
+)]
+pub async fn busca_faturas_idlocador(State(pool): State<Pool>, usuario: UsuarioAutenticado, Query(input): Query<FaturasUsuarioInput>)
+    -> Result<(StatusCode, Json<Vec<Fatura>>), ErroApi>{
+    let faturas = busca_faturas_usuario(&pool, &usuario, input, true).await?;
+    return Ok((StatusCode::OK, Json(faturas)))
+}
+
+#[utoipa::path(
+    get,
+    tag = "Faturas",
+    path = "/busca_faturas_idlocatario/{id}",
+    description = "Lista as faturas a pagar do locatário, das que vencem primeiro para as que vencem depois.",
+    responses(
+        (
+            status = 200,
+            description = "Faturas dos contratos do locatário.",
+            body = Vec<Fatura>
+        ),
+        (
+            status = 400,
+            description = "Status de fatura inválido."
+        ),
+        (
+            status = 403,
+            description = "O usuário autenticado não é o dono da conta nem administrador."
+        ),
+    ),
+    params(
+        ("id" = String, Path, description = "ID do locatário"),
+        ("status" = Option<String>, Query, description = "\"Pendente\", \"Vencida\" ou \"Paga\""),
+    )
+)]
+pub async fn busca_faturas_idlocatario(State(pool): State<Pool>, usuario: UsuarioAutenticado, Query(input): Query<FaturasUsuarioInput>)
+    -> Result<(StatusCode, Json<Vec<Fatura>>), ErroApi>{
+    let faturas = busca_faturas_usuario(&pool, &usuario, input, false).await?;
+    return Ok((StatusCode::OK, Json(faturas)))
+}
+
+/// Dados preenchidos no modelo do recibo.
+pub struct ReciboPDF{
+    pub idfatura: String,
+    pub idcontrato: String,
+    pub numeroparcela: i32,
+    pub nomelocatario: String,
+    pub documentolocatario: String,
+    pub tipodocumentolocatario: String,
+    pub nomelocador: String,
+    pub documentolocador: String,
+    pub tipodocumentolocador: String,
+    pub nomemaquina: String,
+    pub valorpago: Dinheiro,
+    pub datavencimento: NaiveDateTime,
+    pub datapagamento: NaiveDateTime,
+    pub cidadeforo: String
+}
+
+impl ReciboPDF{
+    pub fn campos(&self) -> Vec<(&'static str, String)>{
+        vec![
+            ("numerorecibo", self.idfatura.clone()),
+            ("idcontrato", self.idcontrato.clone()),
+            ("numeroparcela", self.numeroparcela.to_string()),
+            ("nomelocatario", self.nomelocatario.clone()),
+            ("documentolocatario", self.documentolocatario.clone()),
+            ("tipodocumentolocatario", self.tipodocumentolocatario.clone()),
+            ("nomelocador", self.nomelocador.clone()),
+            ("documentolocador", self.documentolocador.clone()),
+            ("tipodocumentolocador", self.tipodocumentolocador.clone()),
+            ("nomemaquina", self.nomemaquina.clone()),
+            ("valorpago", formata_reais(self.valorpago)),
+            ("datavencimento", self.datavencimento.format("%d/%m/%Y").to_string()),
+            ("datapagamento", self.datapagamento.format("%d/%m/%Y").to_string()),
+            ("cidadeforo", self.cidadeforo.clone()),
+        ]
+    }
+}
+
+/// Versão do modelo usada nos recibos novos; veja `VERSAO_MODELO_CONTRATO`.
+pub const VERSAO_MODELO_RECIBO: &str = "v1";
+
+pub fn modelo_recibo(versao: &str) -> Option<&'static str>{
+    match versao{
+        "v1" => Some(include_str!("../templates/recibo_fatura_v1.txt")),
+        _ => None
+    }
+}
+
+/// Gera o PDF do recibo de uma fatura paga e o salva junto à fatura.
+pub async fn gera_recibo_fatura(pool: &Pool, fatura: &Fatura, contrato: &Contrato) -> Result<ReciboFatura, ErroApi>{
+    let datapagamento = match fatura.datapagamento{
+        Some(data) if fatura.status == StatusFatura::Paga => {
+            data
+        },
+        _ => {
+            return Err(ErroApi::Conflito("A fatura ainda não foi paga.".to_string()))
+        }
+    };
+
+    let conn = &obtem_conn(pool).await?;
+    let locatario = models::usuarios::busca_usuario_id(conn, contrato.idlocatario.clone()).await?;
+    let locador = models::usuarios::busca_usuario_id(conn, contrato.idlocador.clone()).await?;
+    let maquina = models::maquinas::busca_maquina_id(conn, contrato.idmaquina.clone()).await?;
+
+    let recibo = ReciboPDF{
+        idfatura: fatura.idfatura.clone(),
+        idcontrato: contrato.idcontrato.clone(),
+        numeroparcela: fatura.numeroparcela,
+        tipodocumentolocatario: tipo_documento(&locatario.documento)?,
+        nomelocatario: locatario.nome,
+        documentolocatario: locatario.documento,
+        tipodocumentolocador: tipo_documento(&locador.documento)?,
+        nomelocador: locador.nome,
+        documentolocador: locador.documento,
+        nomemaquina: maquina.nome,
+        valorpago: fatura.valorpago,
+        datavencimento: fatura.datavencimento,
+        datapagamento,
+        cidadeforo: contrato.cidadeforo.clone()
+    };
+
+    let modelo = match modelo_recibo(VERSAO_MODELO_RECIBO){
+        Some(modelo) => {
+            modelo
+        },
+        None => {
+            return Err(ErroApi::Interno(format!("Modelo de recibo {} não encontrado.", VERSAO_MODELO_RECIBO)))
+        }
+    };
+    let blocos = renderiza_modelo(modelo, &recibo.campos())?;
+    let documento = gera_pdf(
+        "Recibo de Pagamento",
+        &format!("MaqExpress - Recibo {} - Modelo {}", fatura.idfatura, VERSAO_MODELO_RECIBO),
+        &blocos
+    );
+    let hashrecibo = format!("{:x}", Sha256::digest(&documento));
+
+    models::recibos_faturas::cadastra_recibo_fatura(conn, ReciboFatura{
+        idfatura: fatura.idfatura.clone(),
+        versaomodelo: VERSAO_MODELO_RECIBO.to_string(),
+        hashrecibo,
+        documento,
+        datageracao: chrono::Utc::now().naive_utc()
+    }).await
+}
+
+/// Gera o recibo da fatura quitada e o envia por e-mail às partes, pelo registro manual ou pelo PIX.
+pub async fn notifica_fatura_paga(pool: &Pool, contrato: &Contrato, fatura: &Fatura) -> Result<(), ErroApi>{
+    let conn = &obtem_conn(pool).await?;
+    let recibo = gera_recibo_fatura(pool, fatura, contrato).await?;
+    let maquina = models::maquinas::busca_maquina_id(conn, contrato.idmaquina.clone()).await?;
+    let email = renderiza_email(&EmailFaturaPaga{
+        idfatura: fatura.idfatura.clone(),
+        nomemaquina: maquina.nome,
+        numeroparcela: fatura.numeroparcela,
+        valor: fatura.valor,
+        recibo: recibo.documento
+    })?;
+    notifica_partes_contrato(pool, contrato, "Fatura paga!",
+        &format!("A parcela {} do contrato, de {}, foi paga, e o recibo foi enviado para o seu e-mail. Clique aqui para ver seus contratos.",
+            fatura.numeroparcela, formata_reais(fatura.valor)), Some(email)).await
+}
+
+#[utoipa::path(
+    patch,
+    tag = "Faturas",
+    path = "/registra_pagamento_fatura",
+    description = "Registra um pagamento recebido pelo locador. As cobranças PIX pendentes da fatura são
+    canceladas. Quando a fatura é quitada, o recibo é gerado e enviado por e-mail às partes.",
+    responses(
+        (
+            status = 200,
+            description = "Pagamento registrado.",
+            body = Fatura
+        ),
+        (
+            status = 400,
+            description = "O valor é zero ou maior que o valor em aberto da fatura."
+        ),
+        (
+            status = 403,
+            description = "O usuário autenticado não é o locador do contrato."
+        ),
+        (
+            status = 404,
+            description = "A fatura não existe."
+        ),
+        (
+            status = 409,
+            description = "A fatura já foi paga."
+        ),
+    ),
+    request_body = PagamentoFaturaInput
+)]
+pub async fn registra_pagamento_fatura(State(pool): State<Pool>, usuario: UsuarioAutenticado, input: Json<PagamentoFaturaInput>)
+    -> Result<(StatusCode, Json<Fatura>), ErroApi>{
+    if input.idfatura.trim().is_empty(){
+        return Err(ErroApi::validacao("Um ou mais campos estão vazios."))
+    }
+    if !input.valor.e_positivo(){
+        return Err(ErroApi::validacao_campo("valor", "O valor pago deve ser maior que zero."))
+    }
+
+    let conn = &obtem_conn(&pool).await?;
+    let fatura = models::faturas::busca_fatura_id(conn, input.idfatura.trim().to_string()).await?;
+    let contrato = models::contratos::busca_contrato_id(conn, fatura.idcontrato.clone()).await?;
+    verifica_locador_contrato(&usuario, &contrato)?;
+
+    let fatura = models::faturas::registra_pagamento_fatura(conn, fatura.idfatura, input.valor,
+        chrono::Utc::now().naive_utc()).await?;
+
+    if fatura.status != StatusFatura::Paga{
+        notifica_partes_contrato(&pool, &contrato, "Pagamento parcial registrado.",
+            &format!("O locador registrou o pagamento de {} da parcela {}. Faltam {}. Clique aqui para ver seus contratos.",
+                formata_reais(input.valor), fatura.numeroparcela, formata_reais(fatura.valor - fatura.valorpago)), None).await?;
+        return Ok((StatusCode::OK, Json(fatura)))
+    }
+
+    notifica_fatura_paga(&pool, &contrato, &fatura).await?;
+
+    return Ok((StatusCode::OK, Json(fatura)))
+}
+
+#[utoipa::path(
+    get,
+    tag = "Faturas",
+    path = "/busca_recibo_fatura/{id}",
+    description = "Devolve o PDF do recibo de uma fatura paga. O hash SHA-256 do arquivo vai no cabeçalho `ETag`.",
+    responses(
+        (
+            status = 200,
+            description = "O recibo, em PDF.",
+            body = Vec<u8>,
+            content_type = "application/pdf"
+        ),
+        (
+            status = 403,
+            description = "O usuário autenticado não faz parte do contrato."
+        ),
+        (
+            status = 404,
+            description = "A fatura não existe."
+        ),
+        (
+            status = 409,
+            description = "A fatura ainda não foi paga."
+        ),
+    ),
+    params(
+        ("id" = String, Path, description = "ID da fatura"),
+    )
+)]
+pub async fn busca_recibo_fatura(State(pool): State<Pool>, usuario: UsuarioAutenticado, Query(input): Query<IdInput>)
+    -> Result<Response, ErroApi>{
+    if input.id.trim().is_empty(){
+        return Err(ErroApi::validacao("Um ou mais campos estão vazios."))
+    }
+
+    let conn = &obtem_conn(&pool).await?;
+    let fatura = models::faturas::busca_fatura_id(conn, input.id.trim().to_string()).await?;
+    let contrato = models::contratos::busca_contrato_id(conn, fatura.idcontrato.clone()).await?;
+    verifica_parte_contrato(&usuario, &contrato)?;
+
+    let recibo = match models::recibos_faturas::busca_recibo_fatura(conn, fatura.idfatura.clone()).await{
+        Ok(recibo) => {
+            recibo
+        },
+        Err(ErroApi::NaoEncontrado(_)) => {
+            gera_recibo_fatura(&pool, &fatura, &contrato).await?
+        },
+        Err(e) => {
+            return Err(e)
+        }
+    };
+
+    let nomearquivo = format!("inline; filename=\"recibo-{}.pdf\"", &recibo.idfatura[..recibo.idfatura.len().min(12)]);
+    return Ok((
+        StatusCode::OK,
+        [
+            (CONTENT_TYPE, "application/pdf".to_string()),
+            (CONTENT_DISPOSITION, nomearquivo),
+            (ETAG, format!("\"{}\"", recibo.hashrecibo))
+        ],
+        recibo.documento
+    ).into_response())
+}
+
+/// Marca como vencidas as faturas que passaram do vencimento sem ser pagas e avisa as partes.
+/// Um aviso que falha fica no log e não impede os outros. Roda periodicamente a partir de
+/// `main.rs` e devolve quantas faturas venceram.
+pub async fn vence_faturas(pool: &Pool) -> Result<usize, ErroApi>{
+    let conn = &obtem_conn(pool).await?;
+
+    let vencidas = models::faturas::marca_faturas_vencidas(conn, chrono::Utc::now().naive_utc()).await?;
+    for fatura in &vencidas{
+        if let Err(e) = avisa_fatura_vencida(pool, fatura).await{
+            tracing::error!("Erro ao avisar o vencimento da fatura {}: {}", fatura.idfatura, e);
+        }
+    }
+    return Ok(vencidas.len())
+}
+
+async fn avisa_fatura_vencida(pool: &Pool, fatura: &Fatura) -> Result<(), ErroApi>{
+    let conn = &obtem_conn(pool).await?;
+    let contrato = models::contratos::busca_contrato_id(conn, fatura.idcontrato.clone()).await?;
+    notifica_partes_contrato(pool, &contrato, "Uma fatura venceu.",
+        &format!("A parcela {} do contrato, de {}, venceu em {} sem ser paga. Clique aqui para ver seus contratos.",
+            fatura.numeroparcela, formata_reais(fatura.valor - fatura.valorpago), fatura.datavencimento.format("%d/%m/%Y")), None).await
+}
@@ -1,10 +1,10 @@
-use axum::Json;
+use axum::{extract::State, Json};
 use hyper::StatusCode;
 use serde::{Deserialize, Serialize};
 
-use crate::models::{self, imagens_maquinas::{self}};
+use crate::{erros::ErroApi, models::{self, imagens_maquinas::{self}}};
 
-use super::cria_conn;
+use super::{maquinas_usuarios::verifica_dono_maquina, obtem_conn, sessoes::UsuarioAutenticado, Pool};
 
 #[derive(Serialize, Deserialize)]
 pub struct ImagemMaquinaInput{
@@ -12,12 +12,13 @@ pub struct ImagemMaquinaInput{
     pub idmaquina: String
 }
 
-pub async fn cadastra_imagem_maquina(input: Json<ImagemMaquinaInput>)
-    -> Result<(StatusCode, Json<String>), (StatusCode, Json<String>)>{
+pub async fn cadastra_imagem_maquina(State(pool): State<Pool>, usuario: UsuarioAutenticado, input: Json<ImagemMaquinaInput>)
+    -> Result<(StatusCode, Json<String>), ErroApi>{
+    verifica_dono_maquina(&pool, &usuario, &input.idmaquina).await?;
     let idimagem = input.idimagem.to_string();
     let idmaquina = input.idmaquina.to_string();
 
-    let conn = &mut cria_conn()?;
+    let conn = &obtem_conn(&pool).await?;
 
     match models::imagens_maquinas::cadastra_imagem_maquina(conn, idmaquina, idimagem).await{
         Ok(_) => {
@@ -25,17 +26,17 @@ pub async fn cadastra_imagem_maquina(input: Json<ImagemMaquinaInput>)
         },
         Err(e) => {
             println!("Erro [controller]: {}", e);
-            return Err((StatusCode::INTERNAL_SERVER_ERROR, Json(e)))
+            return Err(e)
         }
     }
 }
 
-pub async fn recupera_imagem_maquina(input: Json<String>)
-    -> Result<Json<String>, (StatusCode, Json<String>)> {
+pub async fn recupera_imagem_maquina(State(pool): State<Pool>, input: Json<String>)
+    -> Result<Json<String>, ErroApi> {
 
     let idmaquina = input.0.to_string();
 
-    let conn = &mut cria_conn()?;
+    let conn = &obtem_conn(&pool).await?;
 
     match imagens_maquinas::recupera_imagem_maquina(conn, idmaquina).await {
         Ok(img) => {
@@ -43,23 +44,23 @@ pub async fn recupera_imagem_maquina(input: Json<String>)
         },
         Err(e) => {
             println!("Erro [controller]: {}", e);
-            Err((StatusCode::INTERNAL_SERVER_ERROR, Json(e)))
+            Err(e)
         }
     }
 }
 
-pub async fn recupera_imagens_maquina(input: Json<String>)
-    -> Result<(StatusCode, Json<Vec<String>>), (StatusCode, Json<String>)>{
+pub async fn recupera_imagens_maquina(State(pool): State<Pool>, input: Json<String>)
+    -> Result<(StatusCode, Json<Vec<String>>), ErroApi>{
     let idmaquina = input.0.to_string();
 
-    let conn = &mut cria_conn()?;
+    let conn = &obtem_conn(&pool).await?;
 
     match models::imagens_maquinas::recupera_imagens_maquina(conn, Json(idmaquina)).await{
         Ok(ids) => {
             return Ok((StatusCode::OK, Json(ids)))
         },
         Err(e) => {
-            return Err((StatusCode::INTERNAL_SERVER_ERROR, Json(e)))
+            return Err(e)
         }
     }
 }
\ No newline at end of file
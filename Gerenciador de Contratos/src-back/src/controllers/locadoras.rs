@@ -1,10 +1,11 @@
-use axum::{extract::Query, Json};
+use axum::{extract::{Query, State}, Json};
 use hyper::StatusCode;
 use serde::{Deserialize, Serialize};
 
-use crate::models::{self, locadoras::Locadora};
+use crate::{dinheiro::Percentual, erros::ErroApi, models::{self, locadoras::Locadora, papeis_usuarios::{atribui_papel, Papel}}};
 
-use super::{cria_conn, gera_hash, usuarios::IdInput};
+use super::{obtem_conn, sessoes::{verifica_usuario, verifica_usuario_ou_admin, UsuarioAutenticado}, usuarios::IdInput, Pool};
+use crate::ids::gera_id;
 
 
 #[derive(Serialize, Deserialize)]
@@ -14,17 +15,22 @@ pub struct LocadoraInput{
   pub idconta: String
 }
 
-pub async fn cadastra_locadora(input: Json<LocadoraInput>)
-    -> Result<(StatusCode, Json<String>), (StatusCode, Json<String>)>{
+/// Regras de atraso das locadoras novas, até que o locador as troque.
+pub const PERCENTUAL_MULTA_PADRAO: Percentual = Percentual::de_inteiro(2);
+pub const PERCENTUAL_JUROS_MENSAL_PADRAO: Percentual = Percentual::de_inteiro(1);
+
+pub async fn cadastra_locadora(State(pool): State<Pool>, usuario: UsuarioAutenticado, input: Json<LocadoraInput>)
+    -> Result<(StatusCode, Json<String>), ErroApi>{
     if input.idusuario.trim().is_empty() || input.idendereco.trim().is_empty()
       || input.idconta.trim().is_empty(){
-      return Err((StatusCode::BAD_REQUEST, Json("Um ou mais campos estão vazios.".to_string())));
+      return Err(ErroApi::validacao("Um ou mais campos estão vazios."));
     }
+    verifica_usuario(&usuario, &input.idusuario)?;
 
-    let conn = &mut cria_conn()?;
+    let conn = &obtem_conn(&pool).await?;
 
     let locadora: Locadora = {
-      let idlocadora = gera_hash(&input.idusuario);
+      let idlocadora = gera_id();
       let idusuario = input.idusuario.clone();
       let idendereco = input.idendereco.clone();
       let idconta = input.idconta.clone();
@@ -33,27 +39,31 @@ pub async fn cadastra_locadora(input: Json<LocadoraInput>)
         idlocadora,
         idusuario,
         idendereco,
-        idconta
+        idconta,
+        percentualmulta: PERCENTUAL_MULTA_PADRAO,
+        percentualjurosmensal: PERCENTUAL_JUROS_MENSAL_PADRAO
       }
     };
 
     match models::locadoras::cadastra_locadora(conn, locadora).await{
       Ok(id) => {
+        atribui_papel(conn, input.idusuario.trim().to_string(), Papel::Locador).await?;
         return Ok((StatusCode::OK, Json(id)))
       },
       Err(e) => {
-        return Err((StatusCode::INTERNAL_SERVER_ERROR, Json(e)))
+        return Err(e)
       }
     }
 }
 
-pub async fn busca_locadora_idusuario(Query(id): Query<IdInput>)
-    -> Result<(StatusCode, Json<Locadora>), (StatusCode, Json<String>)>{
+pub async fn busca_locadora_idusuario(State(pool): State<Pool>, usuario: UsuarioAutenticado, Query(id): Query<IdInput>)
+    -> Result<(StatusCode, Json<Locadora>), ErroApi>{
     if id.id.trim().is_empty(){
-      return Err((StatusCode::BAD_REQUEST, Json("Um ou mais campos estão vazios.".to_string())));
+      return Err(ErroApi::validacao("Um ou mais campos estão vazios."));
     }
+    verifica_usuario_ou_admin(&usuario, id.id.trim())?;
 
-    let conn = &mut cria_conn()?;
+    let conn = &obtem_conn(&pool).await?;
     let idusuario = id.id.trim().to_string();
     
     match models::locadoras::busca_locadora_idusuario(conn, idusuario).await{
@@ -61,7 +71,36 @@ pub async fn busca_locadora_idusuario(Query(id): Query<IdInput>)
         return Ok((StatusCode::OK, Json(locatario)))
       },
       Err(e) => {
-        return Err((StatusCode::INTERNAL_SERVER_ERROR, Json(e)))
+        return Err(e)
+      }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct RegrasAtrasoInput{
+  pub idusuario: String,
+  /// Porcentagem do valor do contrato cobrada uma vez quando a máquina volta atrasada.
+  pub percentualmulta: Percentual,
+  /// Porcentagem do valor do contrato por mês de atraso, cobrada por dia.
+  pub percentualjurosmensal: Percentual
+}
+
+/// Troca a multa e os juros que a locadora cobra nas devoluções atrasadas. Vale para as
+/// devoluções registradas depois da troca.
+pub async fn atualiza_regras_atraso_locadora(State(pool): State<Pool>, usuario: UsuarioAutenticado, input: Json<RegrasAtrasoInput>)
+    -> Result<(StatusCode, Json<Locadora>), ErroApi>{
+    if input.idusuario.trim().is_empty(){
+      return Err(ErroApi::validacao("Um ou mais campos estão vazios."));
+    }
+    verifica_usuario(&usuario, &input.idusuario)?;
+    for (campo, valor) in [("percentualmulta", input.percentualmulta), ("percentualjurosmensal", input.percentualjurosmensal)]{
+      if !(Percentual::ZERO..=Percentual::CEM).contains(&valor){
+        return Err(ErroApi::validacao_campo(campo, "O percentual deve estar entre 0 e 100."))
       }
     }
-}
\ No newline at end of file
+
+    let conn = &obtem_conn(&pool).await?;
+    let locadora = models::locadoras::atualiza_regras_atraso(conn, input.idusuario.trim().to_string(),
+      input.percentualmulta, input.percentualjurosmensal).await?;
+    return Ok((StatusCode::OK, Json(locadora)))
+}
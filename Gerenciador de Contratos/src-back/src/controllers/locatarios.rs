@@ -1,10 +1,11 @@
-use axum::{extract::Query, Json};
+use axum::{extract::{Query, State}, Json};
 use hyper::StatusCode;
 use serde::{Deserialize, Serialize};
 
-use crate::models::{self, locatarios::Locatario};
+use crate::{erros::ErroApi, models::{self, locatarios::Locatario, papeis_usuarios::{atribui_papel, Papel}}};
 
-use super::{cria_conn, gera_hash, usuarios::{busca_usuario_id, IdInput}};
+use super::{obtem_conn, sessoes::{verifica_usuario, verifica_usuario_ou_admin, UsuarioAutenticado}, usuarios::{busca_dados_usuario, IdInput}, Pool};
+use crate::ids::gera_id;
 
 
 #[derive(Serialize, Deserialize)]
@@ -13,13 +14,14 @@ pub struct LocatarioInput{
   pub idendereco: String
 }
 
-pub async fn cadastra_locatario(input: Json<LocatarioInput>)
-  -> Result<(StatusCode, Json<String>), (StatusCode, Json<String>)>{
+pub async fn cadastra_locatario(State(pool): State<Pool>, usuario: UsuarioAutenticado, input: Json<LocatarioInput>)
+  -> Result<(StatusCode, Json<String>), ErroApi>{
   if input.idusuario.trim().is_empty() || input.idendereco.trim().is_empty(){
-    return Err((StatusCode::BAD_REQUEST, Json("Um ou mais campos estão vazios.".to_string())));
+    return Err(ErroApi::validacao("Um ou mais campos estão vazios."));
   }
+  verifica_usuario(&usuario, &input.idusuario)?;
 
-  let res = busca_usuario_id(Query(IdInput{id: input.idusuario.clone()})).await;
+  let res = busca_dados_usuario(&pool, IdInput{id: input.idusuario.clone()}).await;
   match res{
     Ok(res) => {
       ()
@@ -29,10 +31,10 @@ pub async fn cadastra_locatario(input: Json<LocatarioInput>)
     }
   }
 
-  let conn = &mut cria_conn()?;
+  let conn = &obtem_conn(&pool).await?;
 
   let locatario: Locatario = {
-    let idlocatario = gera_hash(&input.idusuario);
+    let idlocatario = gera_id();
     let idusuario = input.idusuario.clone();
     let idendereco = input.idendereco.clone();
 
@@ -45,21 +47,23 @@ pub async fn cadastra_locatario(input: Json<LocatarioInput>)
 
   match models::locatarios::cadastra_locatario(conn, locatario).await{
     Ok(id) => {
+      atribui_papel(conn, input.idusuario.trim().to_string(), Papel::Locatario).await?;
       return Ok((StatusCode::OK, Json(id)))
     },
     Err(e) => {
-      return Err((StatusCode::INTERNAL_SERVER_ERROR, Json(e)))
+      return Err(e)
     }
   }
 }
 
-pub async fn busca_locatario_idusuario(Query(id): Query<IdInput>)
-  -> Result<(StatusCode, Json<Locatario>), (StatusCode, Json<String>)>{
+pub async fn busca_locatario_idusuario(State(pool): State<Pool>, usuario: UsuarioAutenticado, Query(id): Query<IdInput>)
+  -> Result<(StatusCode, Json<Locatario>), ErroApi>{
   if id.id.trim().is_empty(){
-    return Err((StatusCode::BAD_REQUEST, Json("Um ou mais campos estão vazios.".to_string())));
+    return Err(ErroApi::validacao("Um ou mais campos estão vazios."));
   }
+  verifica_usuario_ou_admin(&usuario, id.id.trim())?;
 
-  let conn = &mut cria_conn()?;
+  let conn = &obtem_conn(&pool).await?;
   let idusuario = id.id.trim().to_string();
   
   match models::locatarios::busca_locatario_idusuario(conn, idusuario).await{
@@ -67,7 +71,7 @@ pub async fn busca_locatario_idusuario(Query(id): Query<IdInput>)
       return Ok((StatusCode::OK, Json(locatario)))
     },
     Err(e) => {
-      return Err((StatusCode::INTERNAL_SERVER_ERROR, Json(e)))
+      return Err(e)
     }
   }
 }
\ No newline at end of file
@@ -1,15 +1,15 @@
 use std::str::FromStr;
 
-use axum::{extract::Query, http::StatusCode, Json};
+use axum::{extract::{Query, State}, http::StatusCode, Json};
 use chrono::NaiveDateTime;
 use diesel::{ExpressionMethods, RunQueryDsl};
-use rand::random;
 use serde::{Deserialize, Serialize};
 use utoipa::ToSchema;
 
-use crate::{controllers::{maquinas_usuarios::{cadastra_maquina_usuario, MaquinaUsuarioInput}, usuarios::{busca_usuario_id, IdInput}}, models::{self, maquinas::Maquina, str_to_f64_bigdecimal}};
+use crate::{controllers::{maquinas_usuarios::{cadastra_maquina_usuario, verifica_dono_maquina, MaquinaUsuarioInput}, usuarios::{busca_dados_usuario, IdInput}}, dinheiro::Dinheiro, erros::ErroApi, models::{self, executa, maquinas::{FiltroMaquinas, Maquina, OrdenacaoMaquinas, FIM_DESTAQUE, INICIO_DESTAQUE}, precos_maquinas::MedidaTempo}};
 
-use super::{cria_conn, gera_hash};
+use super::{cria_pool, escapa_html, obtem_conn, precos_maquinas::orca_aluguel, sessoes::{verifica_usuario, UsuarioAutenticado}, Pool};
+use crate::ids::{gera_id, normaliza_id_publico};
 
 #[derive(Serialize, Deserialize)]
 pub struct MaquinaInput{
@@ -17,7 +17,7 @@ pub struct MaquinaInput{
 
     pub nome: String,
     pub numeroserie: String,
-    pub valoraluguel: f64,
+    pub valoraluguel: Dinheiro,
     pub disponivelaluguel: String,
     pub status: String,
     pub categoria: String,
@@ -31,7 +31,7 @@ pub struct MaquinaReturn{
     pub nome: String,
     pub numeroserie: String,
     pub categoria: String,
-    pub valoraluguel: f64,
+    pub valoraluguel: Dinheiro,
     pub disponivelaluguel: String,
     pub status: String,
     pub datacadastro: String,
@@ -75,31 +75,31 @@ impl From<Maquina> for MaquinaReturn {
     }
 }
 
-pub async fn cadastra_maquina(input: Json<MaquinaInput>)
-    -> Result<(StatusCode, Json<models::maquinas::IdsMaquina>), (StatusCode, Json<String>)>{
+pub async fn cadastra_maquina(State(pool): State<Pool>, usuario: UsuarioAutenticado, input: Json<MaquinaInput>)
+    -> Result<(StatusCode, Json<models::maquinas::IdsMaquina>), ErroApi>{
     if input.nome.trim().is_empty() || input.numeroserie.trim().is_empty()
         || input.valoraluguel.to_string().trim().is_empty()
         || input.disponivelaluguel.trim().is_empty() || input.status.trim().is_empty()
         || input.idusuario.trim().is_empty(){
-        return Err((StatusCode::BAD_REQUEST, 
-            Json("Um ou mais campos estão vazios.".to_string())))
+        return Err(ErroApi::validacao("Um ou mais campos estão vazios."))
     }
 
-    assert!(busca_usuario_id(Query(IdInput{id: input.idusuario.clone()})).await.is_ok());
+    verifica_usuario(&usuario, &input.idusuario)?;
+    busca_dados_usuario(&pool, IdInput{id: input.idusuario.clone()}).await?;
 
-    if input.valoraluguel <= 1.0 {
-        return Err((StatusCode::BAD_REQUEST,
-            Json("O valor do aluguel não pode ser menor que R$ 1,00.".to_string())))
+    if input.valoraluguel <= Dinheiro::de_reais(1) {
+        return Err(ErroApi::validacao("O valor do aluguel não pode ser menor que R$ 1,00."))
     }
-    let id: u32 = random();
+    let conn = &obtem_conn(&pool).await?;
+    let idpublico = models::maquinas::gera_idpublico_maquina(conn).await?;
     let datacadastro = chrono::Utc::now().naive_utc();
     let dataatualizacao = chrono::Utc::now().naive_utc();
     let maquina = Maquina{
-        idmaquina: gera_hash(&input.numeroserie),
-        idpublico: id.to_string(),
+        idmaquina: gera_id(),
+        idpublico,
         nome: input.nome.to_string(),
         numeroserie: input.numeroserie.to_string(),
-        valoraluguel: str_to_f64_bigdecimal(&input.valoraluguel.to_string()),
+        valoraluguel: input.valoraluguel,
         disponivelaluguel: input.disponivelaluguel.to_string(),
         status: input.status.to_string(),
         datacadastro,
@@ -107,17 +107,16 @@ pub async fn cadastra_maquina(input: Json<MaquinaInput>)
         descricao: input.descricao.to_string(),
         categoria: input.categoria.to_string()        
     };
-    let conn = &mut cria_conn()?;
     let idsmaquina = match models::maquinas::cadastra_maquina(conn, maquina).await{
         Ok(ids) => {
             ids
         },
         Err(e) => {
-            return Err((StatusCode::INTERNAL_SERVER_ERROR, Json(e)))
+            return Err(e)
         }
     };
 
-    match cadastra_maquina_usuario(Json(MaquinaUsuarioInput{
+    match cadastra_maquina_usuario(State(pool.clone()), Json(MaquinaUsuarioInput{
         idmaquina: idsmaquina.idmaquina.clone(),
         idusuario: input.idusuario.trim().to_string()
     })).await{
@@ -131,54 +130,57 @@ pub async fn cadastra_maquina(input: Json<MaquinaInput>)
 }
 
 pub async fn deleta_maquina_id(id: String)
-    -> Result<String, String>{
+    -> Result<String, ErroApi>{
     // Só utilizar em testes
-    use crate::schema::maquinas::dsl::*;
-
-    let conn = &mut cria_conn().unwrap();
-
-    let res: Result<Maquina, diesel::result::Error> = diesel::delete(maquinas)
-        .filter(idmaquina.eq(id))
-        .get_result(conn);
-
-    match res{
-        Ok(maquinaapagada) => {
-            return Ok(maquinaapagada.idmaquina)
-        },
-        Err(e) => {
-            return Err(e.to_string())
+    let pool = cria_pool().unwrap();
+    let conn = &pool.get().await.unwrap();
+
+    executa(conn, move |conn| {
+        use crate::schema::maquinas::dsl::*;
+
+        let res: Result<Maquina, diesel::result::Error> = diesel::delete(maquinas)
+            .filter(idmaquina.eq(id))
+            .get_result(conn);
+
+        match res{
+            Ok(maquinaapagada) => {
+                return Ok(maquinaapagada.idmaquina)
+            },
+            Err(e) => {
+                return Err(e.into())
+            }
         }
-    }
+    }).await
 }
 
 // tonic / src-java / proto / grpc
-pub async fn lista_todas_maquinas()
-    -> Result<(StatusCode, Json<Vec<Maquina>>), (StatusCode, Json<String>)>{
-    let conn = &mut cria_conn()?;
+pub async fn lista_todas_maquinas(State(pool): State<Pool>)
+    -> Result<(StatusCode, Json<Vec<Maquina>>), ErroApi>{
+    let conn = &obtem_conn(&pool).await?;
     
     match models::maquinas::lista_todas_maquinas(conn).await{
         Ok(maquinas) => {
             return Ok((StatusCode::OK, Json(maquinas)))
         },
         Err(e) => {
-            return Err((StatusCode::INTERNAL_SERVER_ERROR, Json(e)))
+            return Err(e)
         }
     };
 }
 
-pub async fn busca_maquina_id(Query(input): Query<IdInput>)
-    -> Result<(StatusCode, Json<Maquina>), (StatusCode, Json<String>)>{
+pub async fn busca_maquina_id(State(pool): State<Pool>, Query(input): Query<IdInput>)
+    -> Result<(StatusCode, Json<Maquina>), ErroApi>{
     if input.id.trim().is_empty(){
-        return Err((StatusCode::BAD_REQUEST, Json("Um ou mais campos estão vazios.".to_string())))
+        return Err(ErroApi::validacao("Um ou mais campos estão vazios."))
     }
-    let conn = &mut cria_conn()?;
+    let conn = &obtem_conn(&pool).await?;
     let id = input.id.trim().to_string();
     match models::maquinas::busca_maquina_id(conn, id).await{
         Ok(maq) => {
             return Ok((StatusCode::OK, Json(maq)))
         },
         Err(e) => {
-            return Err((StatusCode::INTERNAL_SERVER_ERROR, Json(e)))
+            return Err(e)
         }
     }
 }
@@ -207,19 +209,19 @@ pub async fn busca_maquina_id(Query(input): Query<IdInput>)
         ("id" = String, Path, description = "ID da máquina"),
     )
 )]
-pub async fn busca_maquina_idpublico(Query(params): Query<IdInput>)
-    -> Result<(StatusCode, Json<MaquinaReturn>), (StatusCode, Json<String>)>{
+pub async fn busca_maquina_idpublico(State(pool): State<Pool>, Query(params): Query<IdInput>)
+    -> Result<(StatusCode, Json<MaquinaReturn>), ErroApi>{
     if params.id.trim().is_empty(){
-        return Err((StatusCode::BAD_REQUEST, Json("Um ou mais campos estão vazios.".to_string())))
+        return Err(ErroApi::validacao("Um ou mais campos estão vazios."))
     }
-    let conn = &mut cria_conn()?;
-    let idpublico = params.id.trim().to_string();
+    let conn = &obtem_conn(&pool).await?;
+    let idpublico = normaliza_id_publico(&params.id);
     match models::maquinas::busca_maquina_idpublico(conn, idpublico).await{
         Ok(maq) => {
             return Ok((StatusCode::OK, Json(MaquinaReturn::from(maq))))
         },
         Err(e) => {
-            return Err((StatusCode::INTERNAL_SERVER_ERROR, Json(e)))
+            return Err(e)
         }
     }
 }
@@ -238,6 +240,10 @@ pub async fn busca_maquina_idpublico(Query(params): Query<IdInput>)
             status = 400,
             description = "Parâmetro inválido ou ausente."
         ),
+        (
+            status = 403,
+            description = "A máquina é de outro usuário."
+        ),
         (
             status = 500,
             description = "Erro interno."
@@ -245,89 +251,256 @@ pub async fn busca_maquina_idpublico(Query(params): Query<IdInput>)
     ),
     request_body = MaquinaReturn
 )]
-pub async fn atualiza_maquina(maquina: Json<MaquinaReturn>)
-    -> Result<StatusCode, (StatusCode, Json<String>)>{
+pub async fn atualiza_maquina(State(pool): State<Pool>, usuario: UsuarioAutenticado, maquina: Json<MaquinaReturn>)
+    -> Result<StatusCode, ErroApi>{
     if maquina.categoria.trim().is_empty() || maquina.dataatualizacao.trim().is_empty()
         || maquina.datacadastro.trim().is_empty() || maquina.descricao.trim().is_empty()
         || maquina.disponivelaluguel.trim().is_empty() || maquina.idmaquina.trim().is_empty()
         || maquina.idpublico.trim().is_empty() || maquina.nome.trim().is_empty()
         || maquina.numeroserie.trim().is_empty() || maquina.status.trim().is_empty(){
-        return Err((StatusCode::BAD_REQUEST, Json("Um ou mais campos estão vazios.".to_string())))
+        return Err(ErroApi::validacao("Um ou mais campos estão vazios."))
     }
     
+    verifica_dono_maquina(&pool, &usuario, &maquina.idmaquina).await?;
     let maquina = maquina.0;
 
-    let conn = &mut cria_conn()?;
+    let conn = &obtem_conn(&pool).await?;
 
     match models::maquinas::atualiza_maquina(conn, Maquina::from(maquina)).await{
         Ok(_maquina) => {
             return Ok(StatusCode::OK)
         },
         Err(e) => {
-            return Err((StatusCode::INTERNAL_SERVER_ERROR, Json(e)))
+            return Err(e)
         }
     }
 }
 
-pub async fn pesquisa_maquina(pesquisa: Json<String>)
-    -> Result<(StatusCode, Json<Vec<Maquina>>), (StatusCode, Json<String>)>{
-    if pesquisa.0.trim().is_empty(){
-        return Err((StatusCode::BAD_REQUEST, Json("Um ou mais campos estão vazios.".to_string())));
+/// Tamanho de página usado quando a pesquisa não informa um.
+pub const TAMANHO_PAGINA_PADRAO: i64 = 20;
+pub const TAMANHO_PAGINA_MAXIMO: i64 = 100;
+
+#[derive(Serialize, Deserialize, ToSchema, Default)]
+pub struct PesquisaMaquinaInput{
+    pub texto: Option<String>,
+    pub categoria: Option<String>,
+    pub valor_minimo: Option<Dinheiro>,
+    pub valor_maximo: Option<Dinheiro>,
+    pub disponivelaluguel: Option<String>,
+    /// Cidade do endereço do dono da máquina.
+    pub cidade: Option<String>,
+    pub ordenacao: Option<OrdenacaoMaquinas>,
+    /// Começa em 1.
+    pub pagina: Option<i64>,
+    pub tamanho_pagina: Option<i64>
+}
+
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct ResultadoPesquisaMaquinas{
+    pub maquinas: Vec<MaquinaReturn>,
+    pub total: i64,
+    pub pagina: i64,
+    pub tamanho_pagina: i64,
+    pub total_paginas: i64
+}
+
+fn texto_opcional(texto: &Option<String>) -> Option<String>{
+    match texto{
+        Some(t) if !t.trim().is_empty() => Some(t.trim().to_string()),
+        _ => None
+    }
+}
+
+/// Aplica os valores padrão da paginação e devolve `(pagina, tamanho_pagina)`.
+pub fn valida_paginacao(pagina: Option<i64>, tamanho_pagina: Option<i64>) -> Result<(i64, i64), ErroApi>{
+    let pagina = pagina.unwrap_or(1);
+    if pagina < 1{
+        return Err(ErroApi::validacao_campo("pagina", "A página deve ser maior que zero."))
     }
+    let tamanho_pagina = tamanho_pagina.unwrap_or(TAMANHO_PAGINA_PADRAO);
+    if !(1..=TAMANHO_PAGINA_MAXIMO).contains(&tamanho_pagina){
+        return Err(ErroApi::validacao_campo("tamanho_pagina",
+            &format!("O tamanho da página deve estar entre 1 e {}.", TAMANHO_PAGINA_MAXIMO)))
+    }
+    return Ok((pagina, tamanho_pagina))
+}
 
-    let pesquisa = pesquisa.0.to_string();
+/// Valida a pesquisa e a converte nos filtros usados pelo banco.
+pub fn normaliza_pesquisa(input: &PesquisaMaquinaInput) -> Result<FiltroMaquinas, ErroApi>{
+    let (pagina, tamanho_pagina) = valida_paginacao(input.pagina, input.tamanho_pagina)?;
+    for (campo, valor) in [("valor_minimo", input.valor_minimo), ("valor_maximo", input.valor_maximo)]{
+        if let Some(valor) = valor{
+            if valor < Dinheiro::ZERO{
+                return Err(ErroApi::validacao_campo(campo, "O valor não pode ser negativo."))
+            }
+        }
+    }
+    if let (Some(minimo), Some(maximo)) = (input.valor_minimo, input.valor_maximo){
+        if minimo > maximo{
+            return Err(ErroApi::validacao_campo("valor_minimo", "O valor mínimo é maior que o valor máximo."))
+        }
+    }
 
-    let conn = &mut cria_conn()?;
+    return Ok(FiltroMaquinas{
+        texto: texto_opcional(&input.texto),
+        categoria: texto_opcional(&input.categoria),
+        valor_minimo: input.valor_minimo,
+        valor_maximo: input.valor_maximo,
+        disponivelaluguel: texto_opcional(&input.disponivelaluguel),
+        cidade: texto_opcional(&input.cidade),
+        ordenacao: input.ordenacao.unwrap_or_default(),
+        limite: tamanho_pagina,
+        deslocamento: (pagina - 1) * tamanho_pagina
+    })
+}
 
-    match models::maquinas::pesquisa_maquina(conn, pesquisa).await{
-        Ok(maquinas) => {
-            return Ok((StatusCode::OK, Json(maquinas)))
+#[utoipa::path(
+    post,
+    tag = "Máquina",
+    path = "/pesquisa_maquina",
+    description = "Pesquisa máquinas ativas por texto, categoria, faixa de preço, disponibilidade e cidade do dono, com paginação.",
+    responses(
+        (
+            status = 200,
+            description = "Página de resultados e total de máquinas encontradas.",
+            body = ResultadoPesquisaMaquinas
+        ),
+        (
+            status = 400,
+            description = "Algum dos filtros é inválido."
+        ),
+    ),
+    request_body = PesquisaMaquinaInput
+)]
+pub async fn pesquisa_maquina(State(pool): State<Pool>, Json(input): Json<PesquisaMaquinaInput>)
+    -> Result<(StatusCode, Json<ResultadoPesquisaMaquinas>), ErroApi>{
+    let filtro = normaliza_pesquisa(&input)?;
+    let tamanho_pagina = filtro.limite;
+    let pagina = filtro.deslocamento / tamanho_pagina + 1;
+
+    let conn = &obtem_conn(&pool).await?;
+
+    match models::maquinas::pesquisa_maquina(conn, filtro).await{
+        Ok((maquinas, total)) => {
+            return Ok((StatusCode::OK, Json(ResultadoPesquisaMaquinas{
+                maquinas: maquinas.into_iter().map(MaquinaReturn::from).collect(),
+                total,
+                pagina,
+                tamanho_pagina,
+                total_paginas: (total + tamanho_pagina - 1) / tamanho_pagina
+            })))
         },
         Err(e) => {
-            return Err((StatusCode::INTERNAL_SERVER_ERROR, Json(e)))
+            return Err(e)
         }
     }
 }
 
-#[derive(Serialize, Deserialize)]
-pub struct CalculoValorAluguel{
-    pub medida_prazo: String,
-    pub prazo: f64,
-    pub idmaquina: String
+pub const TAMANHO_MAXIMO_BUSCA_TEXTUAL: usize = 200;
+
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct BuscaTextualInput{
+    pub texto: String,
+    /// Começa em 1.
+    pub pagina: Option<i64>,
+    pub tamanho_pagina: Option<i64>
 }
 
-pub async fn calcula_valor_aluguel(input: Json<CalculoValorAluguel>)
-    -> Result<(StatusCode, Json<f64>), (StatusCode, Json<String>)>{
-    if input.medida_prazo.trim().is_empty() || input.prazo.to_string().trim().is_empty()
-        || input.idmaquina.trim().is_empty(){
-        return Err((StatusCode::BAD_REQUEST, Json("Um ou mais campos estão vazios.".to_string())))
-    }
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct ResultadoBuscaTextualReturn{
+    pub maquina: MaquinaReturn,
+    pub relevancia: f32,
+    /// Nome da máquina, em HTML, com os termos encontrados dentro de `<mark>`.
+    pub trechonome: String,
+    /// Trechos da descrição, em HTML, com os termos encontrados dentro de `<mark>`.
+    pub trechodescricao: String
+}
 
-    let valores = input.0;
-    let medida_prazo = valores.medida_prazo.to_string();
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct ResultadoBuscaTextualMaquinas{
+    pub resultados: Vec<ResultadoBuscaTextualReturn>,
+    pub total: i64,
+    pub pagina: i64,
+    pub tamanho_pagina: i64,
+    pub total_paginas: i64
+}
 
-    let maquina = busca_maquina_id(Query(IdInput{id: valores.idmaquina.to_string()})).await?.1.0;
-    
-    let valor_por_hora = maquina.valoraluguel / 720.0;
-    let valor_taxa = maquina.valoraluguel * 0.05;
+/// Escapa o HTML do trecho devolvido pelo banco e só então troca os marcadores de destaque
+/// por `<mark>`.
+pub fn destaca_trecho(trecho: &str) -> String{
+    escapa_html(trecho)
+        .replace(INICIO_DESTAQUE, "<mark>")
+        .replace(FIM_DESTAQUE, "</mark>")
+}
 
-    let valor_aluguel = match medida_prazo.as_str() {
-        "Horas" => {
-            (valor_por_hora * valores.prazo) + valor_taxa
-        },
-        "Dias" => {
-            (valor_por_hora * 24.0 * valores.prazo) + valor_taxa
-        },
-        "Semanas" => {
-            (valor_por_hora * 24.0 * (30. / 4.) * valores.prazo) + valor_taxa
-        },
-        "Meses" => {
-            (valor_por_hora * 24.0 * 30. * valores.prazo) + valor_taxa
+#[utoipa::path(
+    post,
+    tag = "Máquina",
+    path = "/busca_textual_maquina",
+    description = "Busca textual nas máquinas ativas, sem diferenciar acentos e considerando o radical das palavras. Os resultados vêm ordenados pela relevância e com os termos encontrados destacados.",
+    responses(
+        (
+            status = 200,
+            description = "Página de resultados e total de máquinas encontradas.",
+            body = ResultadoBuscaTextualMaquinas
+        ),
+        (
+            status = 400,
+            description = "Texto ou paginação inválidos."
+        ),
+    ),
+    request_body = BuscaTextualInput
+)]
+pub async fn busca_textual_maquina(State(pool): State<Pool>, Json(input): Json<BuscaTextualInput>)
+    -> Result<(StatusCode, Json<ResultadoBuscaTextualMaquinas>), ErroApi>{
+    let texto = input.texto.trim().to_string();
+    if texto.is_empty(){
+        return Err(ErroApi::validacao_campo("texto", "Informe o texto da busca."))
+    }
+    if texto.chars().count() > TAMANHO_MAXIMO_BUSCA_TEXTUAL{
+        return Err(ErroApi::validacao_campo("texto",
+            &format!("O texto da busca deve ter no máximo {} caracteres.", TAMANHO_MAXIMO_BUSCA_TEXTUAL)))
+    }
+    let (pagina, tamanho_pagina) = valida_paginacao(input.pagina, input.tamanho_pagina)?;
+
+    let conn = &obtem_conn(&pool).await?;
+
+    match models::maquinas::busca_textual_maquinas(conn, texto, tamanho_pagina, (pagina - 1) * tamanho_pagina).await{
+        Ok((resultados, total)) => {
+            let resultados = resultados.into_iter().map(|r| ResultadoBuscaTextualReturn{
+                trechonome: destaca_trecho(&r.trechonome),
+                trechodescricao: destaca_trecho(&r.trechodescricao),
+                relevancia: r.relevancia,
+                maquina: MaquinaReturn::from(r.maquina)
+            }).collect();
+            return Ok((StatusCode::OK, Json(ResultadoBuscaTextualMaquinas{
+                resultados,
+                total,
+                pagina,
+                tamanho_pagina,
+                total_paginas: (total + tamanho_pagina - 1) / tamanho_pagina
+            })))
         },
-        _ => {
-            return Err((StatusCode::BAD_REQUEST, Json("Medida de tempo inválida.".to_string())))
+        Err(e) => {
+            return Err(e)
         }
-    };
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct CalculoValorAluguel{
+    pub medida_prazo: MedidaTempo,
+    pub prazo: f64,
+    pub idmaquina: String
+}
 
-    return Ok((StatusCode::OK, Json(valor_aluguel)))
-}
\ No newline at end of file
+/// Total do orçamento da locação; os itens estão em `/orca_aluguel_maquina`.
+pub async fn calcula_valor_aluguel(State(pool): State<Pool>, input: Json<CalculoValorAluguel>)
+    -> Result<(StatusCode, Json<Dinheiro>), ErroApi>{
+    if input.idmaquina.trim().is_empty(){
+        return Err(ErroApi::validacao("Um ou mais campos estão vazios."))
+    }
+
+    let orcamento = orca_aluguel(&pool, &input.idmaquina, input.medida_prazo, input.prazo).await?;
+    return Ok((StatusCode::OK, Json(orcamento.total)))
+}
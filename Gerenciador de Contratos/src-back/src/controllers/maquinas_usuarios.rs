@@ -1,76 +1,93 @@
-use axum::{extract::Query, Json};
+use axum::{extract::{Query, State}, Json};
 use hyper::StatusCode;
 
-use crate::models::{self, maquinas_usuarios::MaquinaUsuario};
+use crate::{erros::ErroApi, models::{self, maquinas_usuarios::MaquinaUsuario}};
 
-use super::{cria_conn, gera_hash, maquinas::busca_maquina_id, usuarios::{busca_usuario_id, IdInput, UsuarioReturn}};
+use super::{obtem_conn, maquinas::busca_maquina_id, sessoes::{verifica_usuario_ou_admin, UsuarioAutenticado}, usuarios::{busca_dados_usuario, IdInput, PerfilUsuarioReturn}, Pool};
+use crate::ids::gera_id;
 
 pub struct MaquinaUsuarioInput{
     pub idmaquina: String,
     pub idusuario: String
 }
 
-pub async fn cadastra_maquina_usuario(input: Json<MaquinaUsuarioInput>)
-    -> Result<(StatusCode, Json<String>), (StatusCode, Json<String>)>{
+pub async fn cadastra_maquina_usuario(State(pool): State<Pool>, input: Json<MaquinaUsuarioInput>)
+    -> Result<(StatusCode, Json<String>), ErroApi>{
     if input.idmaquina.trim().is_empty() || input.idusuario.trim().is_empty(){
-        return Err((StatusCode::BAD_REQUEST, Json("Um ou mais campos estão vazios.".to_string())))
+        return Err(ErroApi::validacao("Um ou mais campos estão vazios."))
     }
     let maquinausuario = MaquinaUsuario{
         idmaquina: input.idmaquina.trim().to_string(),
         idusuario: input.idusuario.trim().to_string(),
-        idmaquinausuario: gera_hash(&input.idusuario)
+        idmaquinausuario: gera_id()
     };
-    let conn = &mut cria_conn()?;
+    let conn = &obtem_conn(&pool).await?;
     match models::maquinas_usuarios::cadastra_maquina_usuario(conn, maquinausuario).await{
         Ok(id) => {
             return Ok((StatusCode::OK, Json(id)))
         },
         Err(e) => {
-            return Err((StatusCode::INTERNAL_SERVER_ERROR, Json(e)))
+            return Err(e)
         }
     }
 }
 
-pub async fn busca_maquinas_usuario_idusuario(Query(input): Query<IdInput>)
-    -> Result<(StatusCode, Json<Vec<models::maquinas::Maquina>>), (StatusCode, Json<String>)>{
+/// Garante que a máquina é do usuário autenticado. Os administradores podem moderar
+/// qualquer máquina.
+pub async fn verifica_dono_maquina(pool: &Pool, usuario: &UsuarioAutenticado, idmaquina: &str)
+    -> Result<(), ErroApi>{
+    if usuario.e_admin(){
+        return Ok(())
+    }
+    let conn = &obtem_conn(pool).await?;
+    let iddono = models::maquinas_usuarios::busca_idusuario_idmaquina(conn, idmaquina.trim().to_string()).await?;
+    if iddono != usuario.idusuario{
+        return Err(ErroApi::Proibido("Você não é o dono dessa máquina.".to_string()))
+    }
+    return Ok(())
+}
+
+pub async fn busca_maquinas_usuario_idusuario(State(pool): State<Pool>, usuario: UsuarioAutenticado, Query(input): Query<IdInput>)
+    -> Result<(StatusCode, Json<Vec<models::maquinas::Maquina>>), ErroApi>{
     if input.id.trim().is_empty(){
-        return Err((StatusCode::BAD_REQUEST, Json("Um ou mais campos estão vazios.".to_string())))
+        return Err(ErroApi::validacao("Um ou mais campos estão vazios."))
     }
-    let conn = &mut cria_conn()?;
+    verifica_usuario_ou_admin(&usuario, input.id.trim())?;
+    let conn = &obtem_conn(&pool).await?;
     let id = input.id.trim().to_string();
     let maqs = match models::maquinas_usuarios::busca_maquinas_usuario_idusuario(conn, id).await{
         Ok(maqs) => {
             maqs
         },
         Err(e) => {
-            return Err((StatusCode::INTERNAL_SERVER_ERROR, Json(e)))
+            return Err(e)
         }
     };
     let mut maquinas = vec![];
     for maq in maqs{
-        maquinas.push(busca_maquina_id(Query(IdInput{id: maq.idmaquina})).await?.1.0);
+        maquinas.push(busca_maquina_id(State(pool.clone()), Query(IdInput{id: maq.idmaquina})).await?.1.0);
     }
     return Ok((StatusCode::OK, Json(maquinas)))
 }
 
-pub async fn busca_usuario_idmaquina(Query(id): Query<IdInput>)
-    -> Result<(StatusCode, Json<UsuarioReturn>), (StatusCode, Json<String>)>{
+pub async fn busca_usuario_idmaquina(State(pool): State<Pool>, Query(id): Query<IdInput>)
+    -> Result<(StatusCode, Json<PerfilUsuarioReturn>), ErroApi>{
     if id.id.trim().is_empty(){
-        return Err((StatusCode::BAD_REQUEST, Json("Um ou mais campos estão vazios.".to_string())))
+        return Err(ErroApi::validacao("Um ou mais campos estão vazios."))
     }
     let id = id.id.trim().to_string();
 
-    let conn = &mut cria_conn()?;
+    let conn = &obtem_conn(&pool).await?;
 
     let idusuario = match models::maquinas_usuarios::busca_idusuario_idmaquina(conn, id).await{
         Ok(id) => {
             id
         },
         Err(e) => {
-            return Err((StatusCode::INTERNAL_SERVER_ERROR, Json(e)))
+            return Err(e)
         }
     };
 
-    let usuario = busca_usuario_id(Query(IdInput{id: idusuario})).await?;
-    return Ok(usuario)
+    let usuario = busca_dados_usuario(&pool, IdInput{id: idusuario}).await?.1.0;
+    return Ok((StatusCode::OK, Json(PerfilUsuarioReturn::from(usuario))))
 }
\ No newline at end of file
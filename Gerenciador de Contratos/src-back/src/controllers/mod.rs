@@ -1,9 +1,10 @@
-use axum::{http::StatusCode, Json};
-use diesel::prelude::*;
+use deadpool_diesel::postgres::{Manager, Runtime};
 use dotenvy::dotenv;
 use pwhash::bcrypt;
 use std::env;
 
+use crate::{erros::ErroApi, models::Conexao};
+
 pub mod codigos_recuperacao;
 pub mod envia_emails;
 pub mod usuarios;
@@ -18,10 +19,21 @@ pub mod locatarios;
 pub mod locadoras;
 pub mod contas_bancarias;
 pub mod solicitacoes_contratos;
+pub mod reservas_maquinas;
 pub mod contratos;
 pub mod notificacoes;
+pub mod sessoes;
+pub mod papeis_usuarios;
+pub mod chaves_pix;
+pub mod cobrancas_pix;
+pub mod faturas;
+pub mod encargos_contratos;
+pub mod precos_maquinas;
+
+/// Pool de conexões compartilhado entre os handlers, criado uma única vez em `main.rs`.
+pub type Pool = deadpool_diesel::postgres::Pool;
 
-pub fn cria_conn() -> Result<PgConnection, (StatusCode, Json<String>)> {
+pub fn cria_pool() -> Result<Pool, String> {
     dotenv().ok();
 
     let database_url = match env::var("DATABASE_URL"){
@@ -29,16 +41,27 @@ pub fn cria_conn() -> Result<PgConnection, (StatusCode, Json<String>)> {
             url
         },
         Err(e) => {
-            return Err((StatusCode::SERVICE_UNAVAILABLE, Json(e.to_string())))
+            return Err(e.to_string())
         }
     };
-    let conn = PgConnection::establish(&database_url);
-    match conn{
+    let manager = Manager::new(database_url, Runtime::Tokio1);
+    match Pool::builder(manager).build(){
+        Ok(pool) => {
+            return Ok(pool)
+        },
+        Err(e) => {
+            return Err(e.to_string())
+        }
+    }
+}
+
+pub async fn obtem_conn(pool: &Pool) -> Result<Conexao, ErroApi> {
+    match pool.get().await{
         Ok(conn) => {
             return Ok(conn)
         },
         Err(e) => {
-            return Err((StatusCode::SERVICE_UNAVAILABLE, Json(e.to_string())))
+            return Err(e.into())
         }
     }
 }
@@ -85,4 +108,14 @@ pub fn formata_cpf(cpf: &str) -> Result<String, String>{
         cpffinal.push(u);
     }
     return Ok(cpffinal);
-}
\ No newline at end of file
+}
+
+/// Escapa os caracteres especiais do HTML, para exibir textos dos usuários com segurança.
+pub fn escapa_html(texto: &str) -> String{
+    texto
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&#39;")
+}
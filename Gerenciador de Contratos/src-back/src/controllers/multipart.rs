@@ -1,9 +1,9 @@
-use axum::{body::Bytes, http::StatusCode, Json};
+use axum::{body::Bytes, extract::State, http::StatusCode, Json};
 use axum_typed_multipart::{FieldData, TryFromMultipart, TypedMultipart};
 use serde::{Deserialize, Serialize};
 use tokio::fs;
 
-use crate::{controllers::cria_conn, models::imagens::atualiza_link_imagem};
+use crate::{controllers::{obtem_conn, Pool}, erros::ErroApi, models::imagens::atualiza_link_imagem};
 
 #[derive(TryFromMultipart)]
 pub struct Img {
@@ -17,8 +17,8 @@ pub struct ImgOutput {
     pub link: String,
 }
 
-pub async fn cadastra_imagem(data: TypedMultipart<Img>) 
-    -> Result<(StatusCode, Json<ImgOutput>), (StatusCode, Json<String>)> {
+pub async fn cadastra_imagem(State(pool): State<Pool>, data: TypedMultipart<Img>) 
+    -> Result<(StatusCode, Json<ImgOutput>), ErroApi> {
     
     let nomearquivo = data.file.metadata.file_name.clone().unwrap_or_else(|| "file_name_undefined".to_string());
     println!("Recebendo arquivo: {}", nomearquivo);
@@ -28,31 +28,25 @@ pub async fn cadastra_imagem(data: TypedMultipart<Img>)
     // Converte os bytes para Vec<u8>
     let bin = data.file.contents.to_vec();
 
-    let conn = &mut cria_conn()?;
+    let conn = &obtem_conn(&pool).await?;
 
     // Insere os dados da imagem no banco de dados
     match crate::models::imagens::cadastra_imagem(conn, nomearquivo.clone()).await {
         Ok(idimagem) => {
-            let nome_hash = format!("{}_{}", &idimagem[..20], nomearquivo);
+            let nome_hash = format!("{}_{}", idimagem, nomearquivo);
             let file_path = format!("./images/{}", &nome_hash);
             println!("Salvando imagem em: {}", file_path);
 
             if let Err(e) = fs::write(&file_path, &data.file.contents).await {
                 eprintln!("Erro ao salvar o arquivo: {}", e);
-                return Err((
-                    StatusCode::INTERNAL_SERVER_ERROR,
-                    Json(format!("Erro ao salvar o arquivo: {}", e)),
-                ));
+                return Err(ErroApi::Interno(format!("Erro ao salvar o arquivo: {}", e)));
             }
 
             // Atualiza o link da imagem no banco de dados
             let link = format!("/images/{}", &nome_hash);
             if let Err(e) = atualiza_link_imagem(conn, idimagem.clone(), link.clone()).await {
                 eprintln!("Erro ao atualizar o link da imagem: {}", e);
-                return Err((
-                    StatusCode::INTERNAL_SERVER_ERROR,
-                    Json(format!("Erro ao atualizar o link no banco: {}", e)),
-                ));
+                return Err(ErroApi::Interno(format!("Erro ao atualizar o link no banco: {}", e)));
             }
             
             Ok((
@@ -65,10 +59,7 @@ pub async fn cadastra_imagem(data: TypedMultipart<Img>)
         }
         Err(e) => {
             eprintln!("Erro ao inserir a imagem no banco de dados: {}", e);
-            Err((
-                StatusCode::INTERNAL_SERVER_ERROR,
-                Json(format!("Erro ao inserir a imagem no banco: {}", e)),
-            ))
+            Err(ErroApi::Interno(format!("Erro ao inserir a imagem no banco: {}", e)))
         }
     }
 }
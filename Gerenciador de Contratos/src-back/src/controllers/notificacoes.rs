@@ -1,10 +1,11 @@
-use axum::{extract::Query, Json};
+use axum::{extract::{Query, State}, Json};
 use hyper::StatusCode;
 use serde::{Deserialize, Serialize};
 
-use crate::models::{self, notificacoes::{Notificacao, NotificaoStatusInput}};
+use crate::{emails::{envia_email, ConteudoEmail}, erros::ErroApi, models::{self, notificacoes::{Notificacao, NotificaoStatusInput}}};
 
-use super::{cria_conn, gera_hash, usuarios::IdInput};
+use super::{obtem_conn, sessoes::{verifica_usuario, verifica_usuario_ou_admin, UsuarioAutenticado}, usuarios::IdInput, Pool};
+use crate::ids::gera_id;
 
 #[derive(Serialize, Deserialize)]
 pub struct NotificacaoInput{
@@ -12,16 +13,21 @@ pub struct NotificacaoInput{
     pub titulo: String,
     pub mensagem: String,
     pub onclick: String,
+    /// E-mail enviado ao usuário junto com a notificação. Não faz parte do corpo da requisição.
+    #[serde(skip)]
+    pub email: Option<ConteudoEmail>
 }
 
-pub async fn cadastra_notificacao(input: Json<NotificacaoInput>)
-    -> Result<(StatusCode, Json<String>), (StatusCode, Json<String>)>{
+/// Cria uma notificação no feed de um usuário. Só para uso interno: não tem rota, para que
+/// ninguém publique no feed de outra conta.
+pub async fn cadastra_notificacao(State(pool): State<Pool>, input: Json<NotificacaoInput>)
+    -> Result<(StatusCode, Json<String>), ErroApi>{
     if input.idusuario.trim().is_empty() || input.mensagem.trim().is_empty()
       || input.onclick.is_empty() || input.titulo.trim().is_empty(){
-      return Err((StatusCode::BAD_REQUEST, Json("Um ou mais campos estão vazios.".to_string())))
+      return Err(ErroApi::validacao("Um ou mais campos estão vazios."))
     }
   
-    let idnotificacao = gera_hash(&input.idusuario);
+    let idnotificacao = gera_id();
     let datacriacao = chrono::Utc::now().naive_utc();
 
     let notificacao = Notificacao{
@@ -34,45 +40,56 @@ pub async fn cadastra_notificacao(input: Json<NotificacaoInput>)
       datacriacao
     };
 
-    let conn = &mut cria_conn()?;
+    let conn = &obtem_conn(&pool).await?;
 
-    match models::notificacoes::cadastra_notificacao(conn, notificacao).await{
+    let notificacao = match models::notificacoes::cadastra_notificacao(conn, notificacao).await{
       Ok(notificacao) => {
-        return Ok((StatusCode::OK, Json(notificacao.idnotificacao)))
+        notificacao
       },
       Err(e) => {
-        return Err((StatusCode::INTERNAL_SERVER_ERROR, Json(e)))
+        return Err(e)
+      }
+    };
+
+    // A notificação já foi gravada, então uma falha no e-mail não vira erro para quem a criou.
+    if let Some(conteudo) = input.0.email{
+      let envio = match models::usuarios::busca_email_usuario(conn, notificacao.idusuario.clone()).await{
+        Ok(email) => envia_email(conteudo.para(&email)).await,
+        Err(e) => Err(e)
+      };
+      if let Err(e) = envio{
+        tracing::warn!("Erro ao enviar o e-mail da notificação {}: {}", notificacao.idnotificacao, e);
       }
     }
+
+    return Ok((StatusCode::OK, Json(notificacao.idnotificacao)))
 }
 
-pub async fn busca_notificacoes_idusuario(Query(id): Query<IdInput>)
-    -> Result<(StatusCode, Json<Vec<Notificacao>>), (StatusCode, Json<String>)>{
+pub async fn busca_notificacoes_idusuario(State(pool): State<Pool>, usuario: UsuarioAutenticado, Query(id): Query<IdInput>)
+    -> Result<(StatusCode, Json<Vec<Notificacao>>), ErroApi>{
     if id.id.trim().is_empty(){
-      return Err((StatusCode::BAD_REQUEST, Json("Um ou mais campos estão vazios.".to_string())))
+      return Err(ErroApi::validacao("Um ou mais campos estão vazios."))
     }
+    verifica_usuario_ou_admin(&usuario, &id.id)?;
 
     let id = id.id.trim().to_string();
 
-    let conn = &mut cria_conn()?;
+    let conn = &obtem_conn(&pool).await?;
 
     match models::notificacoes::busca_notificacoes_idusuario(conn, id).await{
       Ok(res) => {
-        if res.is_empty(){
-          return Err((StatusCode::INTERNAL_SERVER_ERROR, Json("Esse usuário não possui notificações.".to_string())))
-        }
         return Ok((StatusCode::OK, Json(res)))
       },
       Err(e) => {
-        return Err((StatusCode::INTERNAL_SERVER_ERROR, Json(e)))
+        return Err(e)
       }
     }
 }
 
-pub async fn atualiza_status_notificacao(input: Json<NotificaoStatusInput>)
-    -> Result<(StatusCode, Json<String>), (StatusCode, Json<String>)>{
+pub async fn atualiza_status_notificacao(State(pool): State<Pool>, usuario: UsuarioAutenticado, input: Json<NotificaoStatusInput>)
+    -> Result<(StatusCode, Json<String>), ErroApi>{
     if input.id.trim().is_empty() || input.novostatus.trim().is_empty(){
-        return Err((StatusCode::BAD_REQUEST, Json("Um ou mais campos estão vazios.".to_string())))
+        return Err(ErroApi::validacao("Um ou mais campos estão vazios."))
     }
 
     let id = input.id.trim().to_string();
@@ -83,14 +100,23 @@ pub async fn atualiza_status_notificacao(input: Json<NotificaoStatusInput>)
       novostatus
     };
 
-    let conn = &mut cria_conn()?;
+    let conn = &obtem_conn(&pool).await?;
+
+    match models::notificacoes::busca_notificacao_id(conn, atualizacao.id.clone()).await{
+      Ok(notificacao) => {
+        verifica_usuario(&usuario, &notificacao.idusuario)?;
+      },
+      Err(e) => {
+        return Err(e)
+      }
+    }
 
     match models::notificacoes::atualiza_status_notificacao(conn, atualizacao).await{
       Ok(id) => {
         return Ok((StatusCode::OK, Json(id)))
       },
       Err(e) => {
-        return Err((StatusCode::INTERNAL_SERVER_ERROR, Json(e)))
+        return Err(e)
       }
     }
 }
\ No newline at end of file
@@ -1,252 +1,432 @@
-use axum::{extract::{Query, State}, Json};
+// Login com provedores externos (Google, Microsoft, Keycloak, GitHub...).
+//
+// Os provedores e a troca do código pela conta do usuário ficam em `crate::oidc`. Aqui a
+// conta do provedor vira uma conta da API: ela é encontrada pelo vínculo em
+// `contas_externas`; uma conta com o mesmo e-mail, criada com senha ou por outro provedor,
+// só é vinculada depois do código enviado para esse e-mail. Depois, a API devolve os
+// próprios tokens de sessão.
+
+use axum::{extract::{Path, Query, State}, Json};
 use hyper::StatusCode;
-use rand::Rng;
-use reqwest::{Client, Url};
+use rand::RngCore;
 use serde::{Deserialize, Serialize};
-use std::{env, sync::Arc};
-
-use crate::{controllers::usuarios::UserId, models::{self, usuarios::Usuario}};
-
-use super::{codigos_recuperacao::gera_codigo_recuperacao, cria_conn, envia_emails::envia_email_codigo, gera_hash, usuarios::{busca_usuario_email_oauth, valida_email, EmailInput}};
-
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct Config {
-    pub google_oauth_client_id: String,
-    pub google_oauth_client_secret: String,
-    pub google_oauth_redirect_url: String,
-}
-
-impl Config {
-    pub fn init() -> Arc<Self> {
-        use dotenv::dotenv;
-        dotenv().ok();
-        let google_oauth_client_id = match env::var("GOOGLE_OAUTH_CLIENT_ID"){
-            Ok(google_oauth_client_id) => {
-                google_oauth_client_id
-            },
-            Err(e) => {
-                println!("{:?}", e);
-                e.to_string()
-            }
-        };
-
-        let google_oauth_client_secret = match env::var("GOOGLE_OAUTH_CLIENT_SECRET"){
-            Ok(google_oauth_client_secret) => {
-                google_oauth_client_secret
-            },
-            Err(e) => {
-                println!("{:?}", e);
-                e.to_string()
-            }
-        };
-
-        let google_oauth_redirect_url = match env::var("GOOGLE_OAUTH_REDIRECT_URL"){
-            Ok(google_oauth_redirect_url) => {
-                google_oauth_redirect_url
-            },
-            Err(e) => {
-                println!("{:?}", e);
-                e.to_string()
-            }
-        };
+use utoipa::ToSchema;
+use std::sync::Arc;
 
-         Arc::new(Config {
-            google_oauth_client_id,
-            google_oauth_client_secret,
-            google_oauth_redirect_url
-        })
-    }
-}
+use crate::{controllers::usuarios::UserId, erros::ErroApi, ids::gera_id, oidc::{IdentidadeExterna, InicioAutorizacao, ProvedorOidc, ProvedorPublico, ProvedoresOidc}, senhas::gera_hash_senha, models::{self, codigos_recuperacao::verifica_codigo_recuperacao_db, usuarios::{StatusUsuario, Usuario}}};
 
-#[derive(Deserialize)]
-pub struct OAuthResponse {
-    pub access_token: String,
-    pub id_token: String,
-}
-
-#[derive(Deserialize, Debug, Serialize)]
-pub struct GoogleUserResult {
-    pub id: Option<String>,
-    pub email: Option<String>,
-    pub verified_email: bool,
-    pub name: Option<String>,
-    pub picture: Option<String>,
-    pub given_name: Option<String>,
-    pub family_name: Option<String>,
-    pub locale: Option<String>,
-}
+use super::{codigos_recuperacao::gera_codigo_recuperacao, obtem_conn, envia_emails::{envia_email_codigo, FinalidadeCodigo}, sessoes::{gera_tokens_sessao, verifica_usuario, TokensSessao, UsuarioAutenticado}, usuarios::{busca_usuario_email, valida_email, EmailInput}, Pool};
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, ToSchema)]
 pub struct AuthCodePayload {
     pub code: String,
+    /// O `state` devolvido por `/auth/{provedor}/inicia`.
+    pub estado: String,
 }
 
-/// Solicita um token ao Google OAuth2.
-pub async fn request_token(
-    config: &Config,
-    authorization_code: &str,
-) -> Result<OAuthResponse, (StatusCode, Json<String>)> {
-    let client = Client::new();
-
-    let params = [
-        ("grant_type", "authorization_code"),
-        ("redirect_uri", &config.google_oauth_redirect_url),
-        ("client_id", &config.google_oauth_client_id),
-        ("client_secret", &config.google_oauth_client_secret),
-        ("code", authorization_code),
-    ];
-
-    let response = client
-        .post("https://oauth2.googleapis.com/token")
-        .form(&params)
-        .send()
-        .await
-        .map_err(|e| (StatusCode::BAD_REQUEST, Json(e.to_string())))?;
-
-    if response.status().is_success() {
-        response
-            .json::<OAuthResponse>()
-            .await
-            .map_err(|e| (StatusCode::BAD_REQUEST, Json(format!("Failed to parse token response: {}", e))))
-    } else {
-        let error_message = response.text().await.unwrap_or_default();
-        Err((StatusCode::BAD_REQUEST, Json(error_message)))
-    }
+#[utoipa::path(
+    get,
+    tag = "Usuário",
+    path = "/auth/provedores",
+    description = "Lista os provedores de login externos configurados.",
+    responses(
+        (
+            status = 200,
+            description = "Provedores configurados.",
+            body = Vec<ProvedorPublico>
+        ),
+    )
+)]
+pub async fn lista_provedores(State(provedores): State<Arc<ProvedoresOidc>>)
+    -> Result<(StatusCode, Json<Vec<ProvedorPublico>>), ErroApi>{
+    return Ok((StatusCode::OK, Json(provedores.lista())))
 }
 
-/// Obtém informações do usuário usando o token.
-pub async fn get_google_user(
-    access_token: &str,
-) -> Result<Json<GoogleUserResult>, (StatusCode, Json<String>)> {
-    let client = Client::new();
-    let mut url = Url::parse("https://www.googleapis.com/oauth2/v1/userinfo").unwrap();
-    url.query_pairs_mut()
-        .append_pair("access_token", access_token);
-
-    let response = client
-        .get(url)
-        .send()
-        .await
-        .map_err(|e| (StatusCode::BAD_REQUEST, Json(e.to_string())))?;
-
-    if !response.status().is_success() {
-        let error_message = response.text().await.unwrap_or_default();
-        return Err((StatusCode::BAD_REQUEST, Json(error_message)))
-    }
-    let response = response
-            .json()
-            .await;
-            
-    let res: GoogleUserResult = response.unwrap();
-    return Ok(Json(res))
+#[utoipa::path(
+    get,
+    tag = "Usuário",
+    path = "/auth/{provedor}/inicia",
+    description = "Inicia o login com um provedor externo: devolve o endereço para onde o usuário deve ser enviado e o `state` que volta com o código de autorização.",
+    params(
+        ("provedor" = String, Path, description = "Chave do provedor, como `google`.")
+    ),
+    responses(
+        (
+            status = 200,
+            description = "Endereço de autorização gerado. Ele vale por 10 minutos.",
+            body = InicioAutorizacao
+        ),
+        (
+            status = 404,
+            description = "O provedor não está configurado."
+        ),
+        (
+            status = 502,
+            description = "Erro ao buscar a configuração do provedor."
+        ),
+    )
+)]
+pub async fn inicia_login_provedor(State(provedores): State<Arc<ProvedoresOidc>>, Path(provedor): Path<String>)
+    -> Result<(StatusCode, Json<InicioAutorizacao>), ErroApi>{
+    let inicio = provedores.busca(&provedor)?.inicia_autorizacao(&provedores.fluxos).await?;
+    return Ok((StatusCode::OK, Json(inicio)))
 }
 
-/// Handler para o endpoint de autenticação.
-pub async fn google_oauth_handler(
-    State(config): State<Arc<Config>>,
+#[utoipa::path(
+    post,
+    tag = "Usuário",
+    path = "/auth/{provedor}",
+    description = "Inicia uma sessão com a conta de um provedor externo, com o código de autorização e o `state` do login começado em `/auth/{provedor}/inicia`. Cria a conta no primeiro acesso. Se já existir uma conta com o mesmo e-mail, envia um código para o e-mail; o vínculo é confirmado em `/confirma_vinculo_provedor`.",
+    params(
+        ("provedor" = String, Path, description = "Chave do provedor, como `google`.")
+    ),
+    responses(
+        (
+            status = 200,
+            description = "Conta do provedor conferida. Tokens da sessão gerados.",
+            body = TokensSessao
+        ),
+        (
+            status = 401,
+            description = "O provedor recusou o código OU o id_token é inválido OU o e-mail não foi verificado OU o `state` expirou, já foi usado ou não foi gerado em `/auth/{provedor}/inicia`."
+        ),
+        (
+            status = 400,
+            description = "O código ou o `state` está vazio."
+        ),
+        (
+            status = 403,
+            description = "A conta está suspensa."
+        ),
+        (
+            status = 404,
+            description = "O provedor não está configurado."
+        ),
+        (
+            status = 409,
+            description = "Já existe uma conta com esse e-mail (vinculo_pendente). Um código foi enviado para confirmar o vínculo."
+        ),
+    ),
+    request_body = AuthCodePayload
+)]
+pub async fn entra_com_provedor(
+    State(pool): State<Pool>,
+    State(provedores): State<Arc<ProvedoresOidc>>,
+    Path(provedor): Path<String>,
     Json(payload): Json<AuthCodePayload>,
-) -> Result<Json<GoogleUserResult>, (StatusCode, Json<String>)> {
-    // Solicita o token ao Google OAuth2
-    let tokens = request_token(&config, &payload.code).await?;
-
-    // Obtém informações do usuário
-    let user_info = get_google_user(&tokens.access_token).await?;
-
-    let fotoid = user_info.picture.clone().unwrap();
-    println!("{}", fotoid);
-    
-    let res = cadastra_usuario_oauth(CredenciaisUsuarioGoogle{
-        email: user_info.email.clone(),
-        name: user_info.name.clone()
-    }).await?;
-    println!("{:?}", res);
+) -> Result<(StatusCode, Json<TokensSessao>), ErroApi> {
+    let provedor = provedores.busca(&provedor)?;
+    if payload.code.trim().is_empty(){
+        return Err(ErroApi::validacao_campo("code", "O código de autorização não pode estar vazio."))
+    }
 
-    Ok(Json(user_info.0))
+    // Só são aceitos códigos de logins começados em `/auth/{provedor}/inicia`, com PKCE e nonce.
+    if payload.estado.trim().is_empty(){
+        return Err(ErroApi::validacao_campo("estado", "O state não pode estar vazio. Comece o login em /auth/{provedor}/inicia."))
+    }
+    let fluxo = provedores.fluxos.consome(payload.estado.trim(), &provedor.configuracao.chave)?;
+    let tokens = provedor.troca_codigo(payload.code.trim(), &fluxo.verificador).await?;
+    let identidade = provedor.identidade(&tokens, &fluxo.nonce).await?;
+    let idusuario = entra_com_identidade(&pool, provedor, identidade).await?;
+
+    let tokens = gera_tokens_sessao(&idusuario)?;
+    return Ok((StatusCode::OK, Json(tokens)))
 }
 
-pub struct CredenciaisUsuarioGoogle{
-    pub email: Option<String>,
-    pub name: Option<String>
+fn verifica_conta_suspensa(status: StatusUsuario) -> Result<(), ErroApi>{
+    if status == StatusUsuario::Suspenso{
+        return Err(ErroApi::ContaSuspensa("A conta está suspensa.".to_string()))
+    }
+    return Ok(())
 }
 
-pub async fn cadastra_usuario_oauth(usuario: CredenciaisUsuarioGoogle)
-    -> Result<(StatusCode, Json<UserId>), (StatusCode, Json<String>)>{
-    match valida_usuario_oauth(&usuario).await{
-        Ok(_) => {},
-        Err(e) => {
-            println!("{:?}", e);
-            return Err((StatusCode::BAD_REQUEST, Json(e)))
-        }
+/// Encontra ou cria a conta do usuário do provedor e devolve o seu ID.
+pub async fn entra_com_identidade(pool: &Pool, provedor: &ProvedorOidc, identidade: IdentidadeExterna) -> Result<String, ErroApi>{
+    let chave = provedor.configuracao.chave.clone();
+    let nome = provedor.configuracao.nome.clone();
+    let conn = &obtem_conn(pool).await?;
+    if let Some(conta) = models::contas_externas::busca_conta_externa(conn, chave.clone(), identidade.sub.clone()).await?{
+        let usuario = models::usuarios::busca_usuario_id(conn, conta.idusuario).await?;
+        verifica_conta_suspensa(usuario.status)?;
+        return Ok(usuario.idusuario)
     }
 
-    let email_clone = usuario.email.clone().unwrap();
-    let nome_clone = usuario.name.clone().unwrap_or(email_clone.clone());
-    let senha = gera_hash(&email_clone);
+    let email = identidade.email.trim().to_string();
+    match models::usuarios::busca_usuario_email(conn, email.clone()).await{
+        Ok(idusuario) => {
+            let usuario = models::usuarios::busca_usuario_id(conn, idusuario.clone()).await?;
+            verifica_conta_suspensa(usuario.status)?;
 
-    let erro = match busca_usuario_email_oauth(Query(EmailInput{email: email_clone.clone()})).await{
-        Ok(id) => {
-            return Ok((StatusCode::OK, Json(UserId{idusuario: id.1.to_string()})))
+            let vinculada = models::contas_externas::busca_conta_externa_usuario(conn, idusuario.clone(), chave.clone()).await?;
+            if vinculada.as_ref().is_some_and(|conta| !conta.pendente){
+                return Err(ErroApi::Conflito(format!("A conta com esse e-mail já está vinculada a outra conta {}.", nome)))
+            }
+
+            // Contas criadas pelo provedor antes de guardarmos o vínculo.
+            if usuario.origemconta == nome{
+                models::contas_externas::vincula_conta_externa(conn, idusuario.clone(), chave, identidade.sub, false).await?;
+                return Ok(idusuario)
+            }
+
+            // Quem controla o e-mail no provedor não é, necessariamente, o dono da conta.
+            models::contas_externas::vincula_conta_externa(conn, idusuario, chave, identidade.sub, true).await?;
+            let finalidade = FinalidadeCodigo::VinculoProvedor(nome);
+            let codigo = gera_codigo_recuperacao(pool, email.clone(), &finalidade).await?.codigo;
+            envia_email_codigo(email, finalidade, codigo).await?;
+            return Err(ErroApi::VinculoPendente("Já existe uma conta com esse e-mail. Use o código enviado para o e-mail para vincular as contas.".to_string()))
         },
+        Err(ErroApi::NaoEncontrado(_)) => {},
         Err(e) => {
-            e
+            return Err(e)
         }
-    };
-
-    if erro.1.0 == "Esse e-mail pertence a outro usuário.".to_string(){
-        return Err(erro)
     }
 
-    let idusuario = gera_hash(&email_clone);
+    let res = cadastra_usuario_oauth(pool, provedor, CredenciaisUsuarioExterno{
+        email,
+        name: identidade.nome,
+        sub: identidade.sub
+    }).await?;
+    return Ok(res.1.0.idusuario)
+}
+
+pub struct CredenciaisUsuarioExterno{
+    pub email: String,
+    pub name: Option<String>,
+    pub sub: String
+}
+
+/// Hash de um segredo aleatório descartado, para que nenhuma senha funcione na conta.
+fn hash_senha_descartavel() -> Result<String, ErroApi>{
+    let mut segredo = [0_u8; 32];
+    rand::rngs::OsRng.fill_bytes(&mut segredo);
+    let segredo: String = segredo.iter().map(|byte| format!("{:02x}", byte)).collect();
+    return gera_hash_senha(&segredo)
+}
+
+/// Cria uma conta já ativa e vinculada: o provedor verificou o e-mail.
+pub async fn cadastra_usuario_oauth(pool: &Pool, provedor: &ProvedorOidc, usuario: CredenciaisUsuarioExterno)
+    -> Result<(StatusCode, Json<UserId>), ErroApi>{
+    valida_usuario_oauth(&usuario).await?;
+
+    let nome = usuario.name.clone().unwrap_or(usuario.email.clone());
+    // Contas de provedores externos não entram com senha.
+    let senha = hash_senha_descartavel()?;
+
+    let conn = &obtem_conn(pool).await?;
+    let idusuario = gera_id();
     let idusuario_clone = idusuario.clone();
+    let idpublico = models::usuarios::gera_idpublico_usuario(conn).await?;
     let now = chrono::Utc::now().naive_utc();
-    let usuario = Usuario{
-        nome: nome_clone,
-        email: email_clone.clone(),
+    let novo = Usuario{
+        nome,
+        email: usuario.email,
         senha,
         documento: "Não definido".to_string(),
         datacadastro: now,
         idusuario,
-        origemconta: "Google".to_string(),
-        status: "Ativo".to_string(),
-        idpublico: rand::thread_rng().gen_range(111111..999999).to_string(),
+        origemconta: provedor.configuracao.nome.clone(),
+        status: StatusUsuario::Ativo,
+        idpublico
     };
 
-    let conn = &mut cria_conn()?;
-
-    match models::usuarios::cadastra_usuario(conn, usuario).await{
+    models::usuarios::cadastra_usuario(conn, novo).await?;
+    match models::contas_externas::vincula_conta_externa(conn, idusuario_clone.clone(), provedor.configuracao.chave.clone(), usuario.sub, false).await{
         Ok(_) => {
-            
-        },
-        Err(e) => {
-            return Err((StatusCode::INTERNAL_SERVER_ERROR, Json(e)))
-        }
-    }
-
-    let codigo = gera_codigo_recuperacao(email_clone.clone()).await?.1.0.codigo;
-    match envia_email_codigo(email_clone, "ativação de conta", codigo).await{
-        Ok(codigoativacao) => {
-            println!("Código ativação: {:?}", codigoativacao);
             return Ok((StatusCode::OK, Json(UserId{idusuario: idusuario_clone})))
         },
         Err(e) => {
             return Err(e)
         }
     }
-
 }
 
-pub async fn valida_usuario_oauth(usuario: &CredenciaisUsuarioGoogle) -> Result<(), String>{
+pub async fn valida_usuario_oauth(usuario: &CredenciaisUsuarioExterno) -> Result<(), ErroApi>{
     let nome = usuario.name.clone().unwrap_or("Usuário".to_string());
     if nome.trim().is_empty(){
-        return Err("Erro ao validar o nome.".to_string())
+        return Err(ErroApi::validacao_campo("nome", "Erro ao validar o nome."))
     }
 
-    let email = usuario.email.clone().unwrap();
-    match valida_email(Json(EmailInput{email: email.clone()})).await{
+    match valida_email(Json(EmailInput{email: usuario.email.clone()})).await{
         Ok(_) => {},
         Err(e) => {
-            return Err(e.1.0)
+            return Err(e)
         }
     }
     return Ok(())
 }
+
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct ConfirmaVinculoInput{
+    pub email: String,
+    pub provedor: String,
+    pub codigo: String
+}
+
+#[utoipa::path(
+    post,
+    tag = "Usuário",
+    path = "/confirma_vinculo_provedor",
+    description = "Confirma, com o código enviado para o e-mail, o vínculo da conta com a conta do provedor usada em `/auth/{provedor}`, e inicia uma sessão. Uma conta ainda não ativada é ativada e perde a senha cadastrada, que pode ser definida de novo pela recuperação de senha.",
+    responses(
+        (
+            status = 200,
+            description = "Código válido. As contas foram vinculadas e os tokens da sessão gerados.",
+            body = TokensSessao
+        ),
+        (
+            status = 404,
+            description = "O e-mail inserido não está registrado no sistema OU o provedor não está configurado."
+        ),
+        (
+            status = 409,
+            description = "Não há vínculo pendente com esse provedor nessa conta."
+        ),
+        (
+            status = 403,
+            description = "A conta está suspensa OU o usuário errou o código muitas vezes e está bloqueado por alguns minutos."
+        ),
+        (
+            status = 400,
+            description = "O código está incorreto ou expirou, ou algum dos campos inseridos é inválido."
+        ),
+    ),
+    request_body = ConfirmaVinculoInput
+)]
+pub async fn confirma_vinculo_provedor(State(pool): State<Pool>, State(provedores): State<Arc<ProvedoresOidc>>, input: Json<ConfirmaVinculoInput>)
+    -> Result<(StatusCode, Json<TokensSessao>), ErroApi>{
+    let codigo = input.codigo.trim().to_string();
+    if codigo.is_empty(){
+        return Err(ErroApi::validacao_campo("codigo", "O código não pode estar vazio."))
+    }
+    let configuracao = &provedores.busca(&input.provedor)?.configuracao;
+    let provedor = configuracao.chave.clone();
+
+    let idusuario = busca_usuario_email(State(pool.clone()), Query(EmailInput{email: input.email.trim().to_string()})).await?.1.0;
+
+    let conn = &obtem_conn(&pool).await?;
+    let usuario = models::usuarios::busca_usuario_id(conn, idusuario.clone()).await?;
+    verifica_conta_suspensa(usuario.status)?;
+    let pendente = models::contas_externas::busca_conta_externa_usuario(conn, idusuario.clone(), provedor.clone()).await?;
+    if !pendente.is_some_and(|conta| conta.pendente){
+        return Err(ErroApi::Conflito("Não há vínculo pendente com esse provedor nessa conta.".to_string()))
+    }
+
+    verifica_codigo_recuperacao_db(conn, idusuario.clone(), codigo, FinalidadeCodigo::VinculoProvedor(configuracao.nome.clone()).chave()).await?;
+    models::contas_externas::confirma_conta_externa(conn, idusuario.clone(), provedor).await?;
+    // O código provou que o usuário controla o e-mail, o que também ativa a conta. A senha de
+    // uma conta pendente foi escolhida por quem a cadastrou, que pode não ser o dono do e-mail,
+    // então ela é descartada; o dono define outra pela recuperação de senha.
+    if usuario.status == StatusUsuario::Pendente{
+        models::usuarios::atualiza_senha_usuario(conn, usuario.email.clone(), hash_senha_descartavel()?).await?;
+        models::usuarios::atualiza_status_usuario(conn, idusuario.clone(), StatusUsuario::Ativo).await?;
+    }
+
+    let tokens = gera_tokens_sessao(&idusuario)?;
+    return Ok((StatusCode::OK, Json(tokens)))
+}
+
+/// A conta criada pelo provedor não tem senha, então não pode perder o vínculo com ele.
+async fn verifica_desvinculo(pool: &Pool, provedores: &ProvedoresOidc, idusuario: &str, provedor: &str) -> Result<Usuario, ErroApi>{
+    let provedor = provedores.busca(provedor)?;
+    let conn = &obtem_conn(pool).await?;
+    let usuario = models::usuarios::busca_usuario_id(conn, idusuario.to_string()).await?;
+
+    let vinculada = models::contas_externas::busca_conta_externa_usuario(conn, idusuario.to_string(), provedor.configuracao.chave.clone()).await?;
+    if vinculada.is_none_or(|conta| conta.pendente){
+        return Err(ErroApi::Conflito("A conta não está vinculada a esse provedor.".to_string()))
+    }
+    if usuario.origemconta == provedor.configuracao.nome{
+        return Err(ErroApi::Conflito(format!("A conta foi criada com {} e não tem senha, então não pode ser desvinculada.", provedor.configuracao.nome)))
+    }
+    return Ok(usuario)
+}
+
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct SolicitaDesvinculoInput{
+    pub idusuario: String,
+    pub provedor: String
+}
+
+#[utoipa::path(
+    post,
+    tag = "Usuário",
+    path = "/solicita_desvinculo_provedor",
+    description = "Envia para o e-mail do usuário o código que confirma o desvínculo da conta do provedor.",
+    responses(
+        (
+            status = 200,
+            description = "O código foi enviado.",
+        ),
+        (
+            status = 409,
+            description = "A conta não está vinculada ao provedor OU foi criada por ele."
+        ),
+        (
+            status = 403,
+            description = "O usuário autenticado não é o dono da conta."
+        ),
+    ),
+    request_body = SolicitaDesvinculoInput
+)]
+pub async fn solicita_desvinculo_provedor(State(pool): State<Pool>, State(provedores): State<Arc<ProvedoresOidc>>, usuario: UsuarioAutenticado, input: Json<SolicitaDesvinculoInput>)
+    -> Result<StatusCode, ErroApi>{
+    let idusuario = input.idusuario.trim().to_string();
+    verifica_usuario(&usuario, &idusuario)?;
+    let usuario = verifica_desvinculo(&pool, &provedores, &idusuario, &input.provedor).await?;
+    let nome = provedores.busca(&input.provedor)?.configuracao.nome.clone();
+
+    let finalidade = FinalidadeCodigo::DesvinculoProvedor(nome);
+    let codigo = gera_codigo_recuperacao(&pool, usuario.email.clone(), &finalidade).await?.codigo;
+    envia_email_codigo(usuario.email, finalidade, codigo).await?;
+    return Ok(StatusCode::OK)
+}
+
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct DesvinculaProvedorInput{
+    pub idusuario: String,
+    pub provedor: String,
+    pub codigo: String
+}
+
+#[utoipa::path(
+    post,
+    tag = "Usuário",
+    path = "/desvincula_provedor",
+    description = "Desvincula a conta do provedor com o código enviado por `/solicita_desvinculo_provedor`.",
+    responses(
+        (
+            status = 200,
+            description = "Código válido. A conta do provedor foi desvinculada.",
+        ),
+        (
+            status = 409,
+            description = "A conta não está vinculada ao provedor OU foi criada por ele."
+        ),
+        (
+            status = 403,
+            description = "O usuário autenticado não é o dono da conta OU errou o código muitas vezes."
+        ),
+        (
+            status = 400,
+            description = "O código está incorreto ou expirou."
+        ),
+    ),
+    request_body = DesvinculaProvedorInput
+)]
+pub async fn desvincula_provedor(State(pool): State<Pool>, State(provedores): State<Arc<ProvedoresOidc>>, usuario: UsuarioAutenticado, input: Json<DesvinculaProvedorInput>)
+    -> Result<StatusCode, ErroApi>{
+    let idusuario = input.idusuario.trim().to_string();
+    verifica_usuario(&usuario, &idusuario)?;
+    let codigo = input.codigo.trim().to_string();
+    if codigo.is_empty(){
+        return Err(ErroApi::validacao_campo("codigo", "O código não pode estar vazio."))
+    }
+    verifica_desvinculo(&pool, &provedores, &idusuario, &input.provedor).await?;
+    let configuracao = &provedores.busca(&input.provedor)?.configuracao;
+    let chave = configuracao.chave.clone();
+
+    let conn = &obtem_conn(&pool).await?;
+    verifica_codigo_recuperacao_db(conn, idusuario.clone(), codigo, FinalidadeCodigo::DesvinculoProvedor(configuracao.nome.clone()).chave()).await?;
+    models::contas_externas::desvincula_conta_externa(conn, idusuario, chave).await?;
+    return Ok(StatusCode::OK)
+}
@@ -0,0 +1,128 @@
+use std::str::FromStr;
+
+use axum::{extract::{Query, State}, http::StatusCode, Json};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use crate::{erros::ErroApi, models::{self, papeis_usuarios::Papel}};
+
+use super::{obtem_conn, sessoes::{verifica_usuario_ou_admin, UsuarioAutenticado}, usuarios::IdInput, Pool};
+
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct PapelUsuarioInput{
+    pub idusuario: String,
+    /// "locador", "locatario" ou "admin".
+    pub papel: String
+}
+
+#[utoipa::path(
+    get,
+    tag = "Usuário",
+    path = "/busca_papeis_usuario/{id}",
+    description = "Lista os papéis do usuário.",
+    responses(
+        (
+            status = 200,
+            description = "Papéis do usuário.",
+            body = Vec<Papel>
+        ),
+        (
+            status = 403,
+            description = "O usuário autenticado não é o dono da conta nem administrador."
+        ),
+    ),
+    params(
+        ("id" = String, Path, description = "ID do usuário"),
+    )
+)]
+pub async fn busca_papeis_usuario(State(pool): State<Pool>, usuario: UsuarioAutenticado, Query(input): Query<IdInput>)
+    -> Result<(StatusCode, Json<Vec<Papel>>), ErroApi>{
+    if input.id.trim().is_empty(){
+        return Err(ErroApi::validacao("Um ou mais campos estão vazios."))
+    }
+    verifica_usuario_ou_admin(&usuario, &input.id)?;
+
+    let conn = &obtem_conn(&pool).await?;
+    let papeis = models::papeis_usuarios::busca_papeis_usuario(conn, input.id.trim().to_string()).await?;
+    return Ok((StatusCode::OK, Json(papeis)))
+}
+
+fn le_papel_usuario(input: &PapelUsuarioInput) -> Result<(String, Papel), ErroApi>{
+    if input.idusuario.trim().is_empty() || input.papel.trim().is_empty(){
+        return Err(ErroApi::validacao("Um ou mais campos estão vazios."))
+    }
+    return Ok((input.idusuario.trim().to_string(), Papel::from_str(&input.papel)?))
+}
+
+#[utoipa::path(
+    post,
+    tag = "Usuário",
+    path = "/atribui_papel_usuario",
+    description = "Dá um papel ao usuário. Só para administradores.",
+    responses(
+        (
+            status = 200,
+            description = "O usuário tem o papel."
+        ),
+        (
+            status = 400,
+            description = "Parâmetro inválido ou ausente."
+        ),
+        (
+            status = 403,
+            description = "O usuário autenticado não é administrador."
+        ),
+        (
+            status = 404,
+            description = "O usuário não existe."
+        ),
+    ),
+    request_body = PapelUsuarioInput
+)]
+pub async fn atribui_papel_usuario(State(pool): State<Pool>, input: Json<PapelUsuarioInput>)
+    -> Result<StatusCode, ErroApi>{
+    let (idusuario, papel) = le_papel_usuario(&input)?;
+
+    let conn = &obtem_conn(&pool).await?;
+    models::usuarios::busca_usuario_id(conn, idusuario.clone()).await?;
+    models::papeis_usuarios::atribui_papel(conn, idusuario, papel).await?;
+    return Ok(StatusCode::OK)
+}
+
+#[utoipa::path(
+    post,
+    tag = "Usuário",
+    path = "/remove_papel_usuario",
+    description = "Tira um papel do usuário. Só para administradores, que não podem tirar o próprio papel de admin.",
+    responses(
+        (
+            status = 200,
+            description = "O papel foi removido."
+        ),
+        (
+            status = 400,
+            description = "Parâmetro inválido ou ausente."
+        ),
+        (
+            status = 403,
+            description = "O usuário autenticado não é administrador."
+        ),
+        (
+            status = 409,
+            description = "O usuário não tem o papel OU o admin tentou tirar o próprio papel de admin."
+        ),
+    ),
+    request_body = PapelUsuarioInput
+)]
+pub async fn remove_papel_usuario(State(pool): State<Pool>, usuario: UsuarioAutenticado, input: Json<PapelUsuarioInput>)
+    -> Result<StatusCode, ErroApi>{
+    let (idusuario, papel) = le_papel_usuario(&input)?;
+    // Evita que o sistema fique sem nenhum administrador por engano.
+    if idusuario == usuario.idusuario && papel == Papel::Admin{
+        return Err(ErroApi::Conflito("Você não pode tirar o seu próprio papel de admin.".to_string()))
+    }
+
+    let conn = &obtem_conn(&pool).await?;
+    models::papeis_usuarios::remove_papel(conn, idusuario, papel).await?;
+    return Ok(StatusCode::OK)
+}
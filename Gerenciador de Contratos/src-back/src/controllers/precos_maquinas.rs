@@ -0,0 +1,252 @@
+use std::collections::HashSet;
+
+use axum::{extract::{Query, State}, http::StatusCode, Json};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use crate::{dinheiro::{Dinheiro, Percentual}, erros::ErroApi, models::{self, precos_maquinas::{DescontoMaquina, MedidaTempo, PrecoMaquina}}, precos::{calcula_orcamento, ConfiguracaoPrecos, OrcamentoAluguel, TabelaPrecos}};
+
+use super::{maquinas_usuarios::verifica_dono_maquina, obtem_conn, sessoes::UsuarioAutenticado, usuarios::IdInput, Pool};
+
+/// Tabela de preços da máquina, com o valor mensal dela.
+pub async fn busca_tabela_precos(pool: &Pool, idmaquina: &str) -> Result<TabelaPrecos, ErroApi>{
+    let conn = &obtem_conn(pool).await?;
+    let maquina = models::maquinas::busca_maquina_id(conn, idmaquina.trim().to_string()).await?;
+    let (precos, descontos) = models::precos_maquinas::busca_tabela_precos(conn, maquina.idmaquina).await?;
+    return Ok(TabelaPrecos{
+        valoraluguel: maquina.valoraluguel,
+        precos,
+        descontos
+    })
+}
+
+/// Orça a locação com a tabela da máquina e a taxa configurada no `.env`.
+pub async fn orca_aluguel(pool: &Pool, idmaquina: &str, medida: MedidaTempo, prazo: f64) -> Result<OrcamentoAluguel, ErroApi>{
+    let tabela = busca_tabela_precos(pool, idmaquina).await?;
+    calcula_orcamento(&tabela, medida, prazo, &ConfiguracaoPrecos::de_ambiente()?)
+}
+
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct TarifaMaquinaReturn{
+    pub medida: MedidaTempo,
+    /// Valor de uma unidade da medida.
+    pub valorunidade: Dinheiro,
+    pub prazominimo: f64,
+    /// Falso quando a tarifa sai do valor mensal da máquina.
+    pub propria: bool
+}
+
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct PrecosMaquinaReturn{
+    pub idmaquina: String,
+    /// Uma tarifa por medida de tempo.
+    pub tarifas: Vec<TarifaMaquinaReturn>,
+    pub descontos: Vec<DescontoMaquina>,
+    /// Percentual da taxa da plataforma, somada a cada locação.
+    pub percentualtaxa: Percentual,
+    pub taxaminima: Dinheiro
+}
+
+fn precos_maquina_return(idmaquina: String, tabela: &TabelaPrecos, configuracao: &ConfiguracaoPrecos)
+    -> Result<PrecosMaquinaReturn, ErroApi>{
+    let tarifas = MedidaTempo::TODAS.into_iter().map(|medida| Ok(TarifaMaquinaReturn{
+        medida,
+        valorunidade: tabela.tarifa(medida)?.valor(1.0)?,
+        prazominimo: tabela.prazo_minimo(medida),
+        propria: tabela.preco(medida).is_some()
+    })).collect::<Result<Vec<_>, ErroApi>>()?;
+    Ok(PrecosMaquinaReturn{
+        idmaquina,
+        tarifas,
+        descontos: tabela.descontos.clone(),
+        percentualtaxa: configuracao.percentual_taxa,
+        taxaminima: configuracao.taxa_minima
+    })
+}
+
+#[utoipa::path(
+    get,
+    tag = "Máquina",
+    path = "/busca_precos_maquina/{id}",
+    description = "Mostra a tarifa da máquina em cada medida de tempo, com os prazos mínimos, as faixas
+    de desconto e a taxa da plataforma.",
+    responses(
+        (
+            status = 200,
+            description = "Preços da máquina.",
+            body = PrecosMaquinaReturn
+        ),
+        (
+            status = 404,
+            description = "A máquina não existe."
+        ),
+    ),
+    params(
+        ("id" = String, Path, description = "ID da máquina"),
+    )
+)]
+pub async fn busca_precos_maquina(State(pool): State<Pool>, Query(input): Query<IdInput>)
+    -> Result<(StatusCode, Json<PrecosMaquinaReturn>), ErroApi>{
+    if input.id.trim().is_empty(){
+        return Err(ErroApi::validacao("Um ou mais campos estão vazios."))
+    }
+
+    let tabela = busca_tabela_precos(&pool, &input.id).await?;
+    let precos = precos_maquina_return(input.id.trim().to_string(), &tabela, &ConfiguracaoPrecos::de_ambiente()?)?;
+    return Ok((StatusCode::OK, Json(precos)))
+}
+
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct TarifaMaquinaInput{
+    pub medida: MedidaTempo,
+    /// Valor de uma unidade da medida.
+    pub valor: Dinheiro,
+    /// Menor prazo aceito nessa medida. Sem ele, não há mínimo.
+    pub prazominimo: Option<f64>
+}
+
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct DescontoMaquinaInput{
+    pub diasminimos: i32,
+    pub percentualdesconto: Percentual
+}
+
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct PrecosMaquinaInput{
+    pub idmaquina: String,
+    /// As medidas sem tarifa saem do valor mensal da máquina.
+    pub tarifas: Vec<TarifaMaquinaInput>,
+    pub descontos: Vec<DescontoMaquinaInput>
+}
+
+/// Confere a tabela enviada e a converte nas linhas do banco.
+pub fn valida_precos_maquina(input: &PrecosMaquinaInput) -> Result<(Vec<PrecoMaquina>, Vec<DescontoMaquina>), ErroApi>{
+    let idmaquina = input.idmaquina.trim().to_string();
+
+    let mut medidas = HashSet::new();
+    let mut precos = vec![];
+    for tarifa in &input.tarifas{
+        if !medidas.insert(tarifa.medida){
+            return Err(ErroApi::validacao_campo("tarifas", &format!("A medida {} aparece mais de uma vez.", tarifa.medida)))
+        }
+        if !tarifa.valor.e_positivo(){
+            return Err(ErroApi::validacao_campo("tarifas", "O valor das tarifas deve ser maior que zero."))
+        }
+        let prazominimo = tarifa.prazominimo.unwrap_or(0.0);
+        if !prazominimo.is_finite() || prazominimo < 0.0{
+            return Err(ErroApi::validacao_campo("tarifas", "O prazo mínimo não pode ser negativo."))
+        }
+        precos.push(PrecoMaquina{
+            idmaquina: idmaquina.clone(),
+            medida: tarifa.medida,
+            valor: tarifa.valor,
+            prazominimo
+        });
+    }
+
+    let mut faixas = HashSet::new();
+    let mut descontos = vec![];
+    for desconto in &input.descontos{
+        if desconto.diasminimos < 1{
+            return Err(ErroApi::validacao_campo("descontos", "As faixas de desconto começam em pelo menos um dia."))
+        }
+        if !faixas.insert(desconto.diasminimos){
+            return Err(ErroApi::validacao_campo("descontos", &format!("A faixa de {} dia(s) aparece mais de uma vez.", desconto.diasminimos)))
+        }
+        if !desconto.percentualdesconto.e_positivo() || desconto.percentualdesconto >= Percentual::CEM{
+            return Err(ErroApi::validacao_campo("descontos", "O desconto deve ser maior que 0% e menor que 100%."))
+        }
+        descontos.push(DescontoMaquina{
+            idmaquina: idmaquina.clone(),
+            diasminimos: desconto.diasminimos,
+            percentualdesconto: desconto.percentualdesconto
+        });
+    }
+    descontos.sort_by_key(|desconto| desconto.diasminimos);
+    return Ok((precos, descontos))
+}
+
+#[utoipa::path(
+    put,
+    tag = "Máquina",
+    path = "/atualiza_precos_maquina",
+    description = "Troca a tabela de preços da máquina: as tarifas por medida de tempo, com os prazos
+    mínimos, e as faixas de desconto das locações longas. Vale para os orçamentos e solicitações
+    feitos depois da troca.",
+    responses(
+        (
+            status = 200,
+            description = "Tabela atualizada.",
+            body = PrecosMaquinaReturn
+        ),
+        (
+            status = 400,
+            description = "Tarifas ou descontos inválidos."
+        ),
+        (
+            status = 403,
+            description = "A máquina é de outro usuário."
+        ),
+        (
+            status = 404,
+            description = "A máquina não existe."
+        ),
+    ),
+    request_body = PrecosMaquinaInput
+)]
+pub async fn atualiza_precos_maquina(State(pool): State<Pool>, usuario: UsuarioAutenticado, input: Json<PrecosMaquinaInput>)
+    -> Result<(StatusCode, Json<PrecosMaquinaReturn>), ErroApi>{
+    if input.idmaquina.trim().is_empty(){
+        return Err(ErroApi::validacao("Um ou mais campos estão vazios."))
+    }
+    let (precos, descontos) = valida_precos_maquina(&input)?;
+    verifica_dono_maquina(&pool, &usuario, &input.idmaquina).await?;
+
+    let conn = &obtem_conn(&pool).await?;
+    models::precos_maquinas::substitui_tabela_precos(conn, input.idmaquina.trim().to_string(), precos, descontos).await?;
+
+    let tabela = busca_tabela_precos(&pool, &input.idmaquina).await?;
+    let precos = precos_maquina_return(input.idmaquina.trim().to_string(), &tabela, &ConfiguracaoPrecos::de_ambiente()?)?;
+    return Ok((StatusCode::OK, Json(precos)))
+}
+
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct OrcamentoAluguelInput{
+    pub idmaquina: String,
+    pub medida: MedidaTempo,
+    pub prazo: f64
+}
+
+#[utoipa::path(
+    post,
+    tag = "Máquina",
+    path = "/orca_aluguel_maquina",
+    description = "Calcula o valor de uma locação, item a item: o aluguel pela tarifa da medida, o
+    desconto da faixa alcançada pelo prazo e a taxa da plataforma. É o mesmo valor exigido na
+    solicitação da locação.",
+    responses(
+        (
+            status = 200,
+            description = "Orçamento da locação.",
+            body = OrcamentoAluguel
+        ),
+        (
+            status = 400,
+            description = "Prazo inválido ou menor que o mínimo da máquina."
+        ),
+        (
+            status = 404,
+            description = "A máquina não existe."
+        ),
+    ),
+    request_body = OrcamentoAluguelInput
+)]
+pub async fn orca_aluguel_maquina(State(pool): State<Pool>, input: Json<OrcamentoAluguelInput>)
+    -> Result<(StatusCode, Json<OrcamentoAluguel>), ErroApi>{
+    if input.idmaquina.trim().is_empty(){
+        return Err(ErroApi::validacao("Um ou mais campos estão vazios."))
+    }
+
+    let orcamento = orca_aluguel(&pool, &input.idmaquina, input.medida, input.prazo).await?;
+    return Ok((StatusCode::OK, Json(orcamento)))
+}
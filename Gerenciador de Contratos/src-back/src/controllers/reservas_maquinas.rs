@@ -0,0 +1,76 @@
+use axum::{extract::{Query, State}, Json};
+use chrono::NaiveDateTime;
+use hyper::StatusCode;
+use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
+
+use crate::{erros::ErroApi, models};
+
+use super::{obtem_conn, Pool};
+
+/// Maior intervalo aceito na consulta do calendário de uma máquina.
+pub const DIAS_MAXIMOS_CALENDARIO: i64 = 400;
+
+#[derive(Serialize, Deserialize, IntoParams)]
+pub struct CalendarioMaquinaInput{
+    pub idmaquina: String,
+    #[param(value_type = String, example = "2026-10-01T00:00:00")]
+    pub inicio: NaiveDateTime,
+    #[param(value_type = String, example = "2026-11-01T00:00:00")]
+    pub fim: NaiveDateTime
+}
+
+#[derive(Serialize, Deserialize, ToSchema, Debug, PartialEq)]
+pub struct PeriodoReservado{
+    #[schema(value_type = String)]
+    pub datainicio: NaiveDateTime,
+    #[schema(value_type = String)]
+    pub datafim: NaiveDateTime
+}
+
+#[utoipa::path(
+    get,
+    tag = "Máquina",
+    path = "/busca_calendario_maquina/",
+    description = "Lista os períodos em que a máquina está ocupada por uma solicitação aprovada ou por um contrato ativo, dentro do intervalo informado.",
+    responses(
+        (
+            status = 200,
+            description = "Períodos ocupados, em ordem cronológica.",
+            body = Vec<PeriodoReservado>
+        ),
+        (
+            status = 400,
+            description = "Parâmetro inválido ou ausente."
+        ),
+    ),
+    params(CalendarioMaquinaInput)
+)]
+pub async fn busca_calendario_maquina(State(pool): State<Pool>, Query(input): Query<CalendarioMaquinaInput>)
+    -> Result<(StatusCode, Json<Vec<PeriodoReservado>>), ErroApi>{
+    if input.idmaquina.trim().is_empty(){
+        return Err(ErroApi::validacao("Um ou mais campos estão vazios."))
+    }
+    if input.fim <= input.inicio{
+        return Err(ErroApi::validacao_campo("fim", "O fim do intervalo deve ser posterior ao início."))
+    }
+    if (input.fim - input.inicio).num_days() > DIAS_MAXIMOS_CALENDARIO{
+        return Err(ErroApi::validacao_campo("fim",
+            &format!("O intervalo pode ter no máximo {} dias.", DIAS_MAXIMOS_CALENDARIO)))
+    }
+
+    let conn = &obtem_conn(&pool).await?;
+
+    match models::reservas_maquinas::busca_reservas_ocupadas(conn, input.idmaquina.trim().to_string(), input.inicio, input.fim).await{
+        Ok(reservas) => {
+            let periodos = reservas.into_iter().map(|reserva| PeriodoReservado{
+                datainicio: reserva.datainicio,
+                datafim: reserva.datafim
+            }).collect();
+            return Ok((StatusCode::OK, Json(periodos)))
+        },
+        Err(e) => {
+            return Err(e)
+        }
+    }
+}
@@ -0,0 +1,309 @@
+use axum::{extract::{FromRequestParts, Request, State}, http::{header::AUTHORIZATION, request::Parts, HeaderMap, StatusCode}, middleware::Next, response::Response, Json};
+use dotenvy::dotenv;
+use jsonwebtoken::{decode, encode, DecodingKey, EncodingKey, Header, Validation};
+use serde::{Deserialize, Serialize};
+use std::env;
+use utoipa::ToSchema;
+
+use crate::{erros::ErroApi, ids::{e_id_legado, gera_id}, models::{self, ids_legados::busca_ids_novos, papeis_usuarios::{busca_papeis_usuario, Papel, Permissao}, tokens_renovacao::consome_token_renovacao, usuarios::StatusUsuario}};
+
+use super::{obtem_conn, Pool};
+
+/// Tempo de vida do token de acesso, em segundos (15 minutos).
+pub const DURACAO_TOKEN_ACESSO: i64 = 15 * 60;
+/// Tempo de vida do token de renovação, em segundos (7 dias).
+pub const DURACAO_TOKEN_RENOVACAO: i64 = 7 * 24 * 60 * 60;
+
+const TIPO_TOKEN_ACESSO: &str = "acesso";
+const TIPO_TOKEN_RENOVACAO: &str = "renovacao";
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ClaimsSessao{
+    pub sub: String,
+    pub tipo: String,
+    /// Identifica o token. O de renovação é registrado quando usado, para valer uma vez só.
+    /// Tokens emitidos antes do campo chegam com ele vazio.
+    #[serde(default)]
+    pub jti: String,
+    pub iat: i64,
+    pub exp: i64
+}
+
+#[derive(Serialize, Deserialize, ToSchema, Debug)]
+pub struct TokensSessao{
+    pub idusuario: String,
+    pub token_acesso: String,
+    pub token_renovacao: String,
+    pub expira_em: i64
+}
+
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct RenovaSessaoInput{
+    pub token_renovacao: String
+}
+
+/// Usuário dono do token de acesso enviado no cabeçalho `Authorization`.
+#[derive(Clone, Debug)]
+pub struct UsuarioAutenticado{
+    pub idusuario: String,
+    /// Carregados do banco por `exige_autenticacao`. Fora das rotas protegidas, a lista
+    /// fica vazia.
+    pub papeis: Vec<Papel>
+}
+
+impl UsuarioAutenticado{
+    pub fn pode(&self, permissao: Permissao) -> bool{
+        self.papeis.iter().any(|papel| papel.permissoes().contains(&permissao))
+    }
+
+    pub fn e_admin(&self) -> bool{
+        self.pode(Permissao::Modera)
+    }
+}
+
+fn chave_jwt() -> Result<String, ErroApi>{
+    dotenv().ok();
+    match env::var("JWT_SECRET"){
+        Ok(chave) => {
+            if chave.trim().is_empty(){
+                return Err(ErroApi::Interno("JWT_SECRET está vazio no arquivo .env.".to_string()))
+            }
+            return Ok(chave)
+        },
+        Err(e) => {
+            return Err(ErroApi::Interno(format!("JWT_SECRET não definido no arquivo .env: {}", e)))
+        }
+    }
+}
+
+fn gera_token(idusuario: &str, tipo: &str, duracao: i64, chave: &str)
+    -> Result<(String, i64), ErroApi>{
+    let iat = chrono::Utc::now().timestamp();
+    let exp = iat + duracao;
+    let claims = ClaimsSessao{
+        sub: idusuario.to_string(),
+        tipo: tipo.to_string(),
+        jti: gera_id(),
+        iat,
+        exp
+    };
+
+    match encode(&Header::default(), &claims, &EncodingKey::from_secret(chave.as_bytes())){
+        Ok(token) => {
+            return Ok((token, exp))
+        },
+        Err(e) => {
+            return Err(ErroApi::Interno(e.to_string()))
+        }
+    }
+}
+
+/// Gera o par de tokens (acesso + renovação) de uma sessão do usuário.
+pub fn gera_tokens_sessao(idusuario: &str) -> Result<TokensSessao, ErroApi>{
+    let chave = chave_jwt()?;
+    let (token_acesso, expira_em) = gera_token(idusuario, TIPO_TOKEN_ACESSO, DURACAO_TOKEN_ACESSO, &chave)?;
+    let (token_renovacao, _) = gera_token(idusuario, TIPO_TOKEN_RENOVACAO, DURACAO_TOKEN_RENOVACAO, &chave)?;
+
+    return Ok(TokensSessao{
+        idusuario: idusuario.to_string(),
+        token_acesso,
+        token_renovacao,
+        expira_em
+    })
+}
+
+/// Valida a assinatura, a expiração e o tipo de um token de sessão.
+pub fn valida_token(token: &str, tipo: &str) -> Result<ClaimsSessao, ErroApi>{
+    let chave = chave_jwt()?;
+
+    let claims = match decode::<ClaimsSessao>(token, &DecodingKey::from_secret(chave.as_bytes()), &Validation::default()){
+        Ok(dados) => {
+            dados.claims
+        },
+        Err(e) => {
+            return Err(ErroApi::NaoAutorizado(format!("Token inválido: {}", e)))
+        }
+    };
+
+    if claims.tipo != tipo{
+        return Err(ErroApi::NaoAutorizado("O tipo do token é inválido.".to_string()))
+    }
+    return Ok(claims)
+}
+
+pub fn extrai_token_bearer(headers: &HeaderMap) -> Result<String, ErroApi>{
+    let cabecalho = match headers.get(AUTHORIZATION){
+        Some(cabecalho) => {
+            cabecalho
+        },
+        None => {
+            return Err(ErroApi::NaoAutorizado("O token de acesso não foi enviado.".to_string()))
+        }
+    };
+
+    let cabecalho = match cabecalho.to_str(){
+        Ok(cabecalho) => {
+            cabecalho
+        },
+        Err(_) => {
+            return Err(ErroApi::NaoAutorizado("O cabeçalho de autorização é inválido.".to_string()))
+        }
+    };
+
+    match cabecalho.strip_prefix("Bearer "){
+        Some(token) if !token.trim().is_empty() => {
+            return Ok(token.trim().to_string())
+        },
+        _ => {
+            return Err(ErroApi::NaoAutorizado("O cabeçalho de autorização é inválido.".to_string()))
+        }
+    }
+}
+
+/// Os tokens emitidos antes da troca das chaves por UUIDv7 trazem o ID antigo do usuário.
+/// Eles são recusados, e o cliente renova a sessão, que passa a usar o ID novo.
+fn usuario_do_token(claims: ClaimsSessao) -> Result<UsuarioAutenticado, ErroApi>{
+    if e_id_legado(&claims.sub){
+        return Err(ErroApi::NaoAutorizado("A sessão usa um ID antigo. Renove a sessão.".to_string()))
+    }
+    return Ok(UsuarioAutenticado{
+        idusuario: claims.sub,
+        papeis: vec![]
+    })
+}
+
+/// Middleware das rotas protegidas: rejeita a requisição sem um token de acesso válido
+/// e disponibiliza o `UsuarioAutenticado`, com os papéis dele, para os handlers.
+pub async fn exige_autenticacao(State(pool): State<Pool>, mut req: Request, next: Next)
+    -> Result<Response, ErroApi>{
+    let token = extrai_token_bearer(req.headers())?;
+    let claims = valida_token(&token, TIPO_TOKEN_ACESSO)?;
+
+    let mut usuario = usuario_do_token(claims)?;
+    let conn = &obtem_conn(&pool).await?;
+    usuario.papeis = busca_papeis_usuario(conn, usuario.idusuario.clone()).await?;
+
+    req.extensions_mut().insert(usuario);
+    return Ok(next.run(req).await)
+}
+
+/// Guarda de rota: só deixa passar quem tem a permissão. Roda depois de `exige_autenticacao`.
+pub async fn exige_permissao(State(permissao): State<Permissao>, usuario: UsuarioAutenticado, req: Request, next: Next)
+    -> Result<Response, ErroApi>{
+    if !usuario.pode(permissao){
+        return Err(ErroApi::Proibido("Você não tem permissão para fazer isso.".to_string()))
+    }
+    return Ok(next.run(req).await)
+}
+
+impl<S> FromRequestParts<S> for UsuarioAutenticado
+where
+    S: Send + Sync,
+{
+    type Rejection = ErroApi;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection>{
+        if let Some(usuario) = parts.extensions.get::<UsuarioAutenticado>(){
+            return Ok(usuario.clone())
+        }
+
+        let token = extrai_token_bearer(&parts.headers)?;
+        let claims = valida_token(&token, TIPO_TOKEN_ACESSO)?;
+        return usuario_do_token(claims)
+    }
+}
+
+/// Garante que o usuário autenticado só age sobre os próprios dados.
+pub fn verifica_usuario(usuario: &UsuarioAutenticado, idusuario: &str) -> Result<(), ErroApi>{
+    if usuario.idusuario != idusuario.trim(){
+        return Err(ErroApi::Proibido("Você não tem permissão para acessar os dados de outro usuário.".to_string()))
+    }
+    return Ok(())
+}
+
+/// Como `verifica_usuario`, mas também libera os administradores. Para consultas e moderação.
+pub fn verifica_usuario_ou_admin(usuario: &UsuarioAutenticado, idusuario: &str) -> Result<(), ErroApi>{
+    if usuario.e_admin(){
+        return Ok(())
+    }
+    return verifica_usuario(usuario, idusuario)
+}
+
+#[utoipa::path(
+    post,
+    tag = "Sessão",
+    path = "/renova_sessao",
+    description = "Gera um novo par de tokens a partir de um token de renovação válido. Cada token de renovação vale uma vez só: o par novo traz outro.",
+    responses(
+        (
+            status = 200,
+            description = "Token de renovação válido. Novos tokens gerados.",
+            body = TokensSessao
+        ),
+        (
+            status = 401,
+            description = "O token de renovação é inválido, expirou ou já foi usado, ou o usuário da sessão não existe mais."
+        ),
+        (
+            status = 403,
+            description = "A conta está suspensa ou ainda não foi ativada."
+        ),
+        (
+            status = 429,
+            description = "Muitas tentativas. Tente de novo depois do tempo indicado."
+        ),
+    ),
+    request_body = RenovaSessaoInput
+)]
+pub async fn renova_sessao(State(pool): State<Pool>, input: Json<RenovaSessaoInput>)
+    -> Result<(StatusCode, Json<TokensSessao>), ErroApi>{
+    if input.token_renovacao.trim().is_empty(){
+        return Err(ErroApi::validacao("Um ou mais campos estão vazios."))
+    }
+
+    let claims = valida_token(input.token_renovacao.trim(), TIPO_TOKEN_RENOVACAO)?;
+    if claims.jti.is_empty(){
+        return Err(ErroApi::NaoAutorizado("O token de renovação é antigo. Entre de novo.".to_string()))
+    }
+    let expiracao = match chrono::DateTime::from_timestamp(claims.exp, 0){
+        Some(expiracao) => expiracao.naive_utc(),
+        None => {
+            return Err(ErroApi::NaoAutorizado("O token de renovação é inválido.".to_string()))
+        }
+    };
+
+    let conn = &obtem_conn(&pool).await?;
+    let mut idusuario = claims.sub;
+    if e_id_legado(&idusuario){
+        idusuario = match busca_ids_novos(conn, vec![idusuario.clone()]).await?.remove(&idusuario){
+            Some(id) => id,
+            None => {
+                return Err(ErroApi::NaoAutorizado("O usuário da sessão não existe.".to_string()))
+            }
+        };
+    }
+
+    // A sessão não sobrevive à conta: usuários apagados, suspensos ou pendentes não renovam.
+    let usuario = match models::usuarios::busca_usuario_id(conn, idusuario.clone()).await{
+        Ok(usuario) => usuario,
+        Err(ErroApi::NaoEncontrado(_)) => {
+            return Err(ErroApi::NaoAutorizado("O usuário da sessão não existe.".to_string()))
+        },
+        Err(e) => {
+            return Err(e)
+        }
+    };
+    match usuario.status{
+        StatusUsuario::Ativo => {},
+        StatusUsuario::Pendente => {
+            return Err(ErroApi::ContaNaoAtivada("A conta ainda não foi ativada.".to_string()))
+        },
+        StatusUsuario::Suspenso => {
+            return Err(ErroApi::ContaSuspensa("A conta está suspensa.".to_string()))
+        }
+    }
+
+    consome_token_renovacao(conn, claims.jti, expiracao).await?;
+    let tokens = gera_tokens_sessao(&idusuario)?;
+    return Ok((StatusCode::OK, Json(tokens)))
+}
@@ -1,41 +1,66 @@
-use axum::{extract::Query, Json};
+use std::str::FromStr;
+
+use axum::{extract::{Query, State}, Json};
+use chrono::{NaiveDateTime, TimeDelta};
 use hyper::StatusCode;
 use serde::{Deserialize, Serialize};
 
-use crate::{controllers::{contratos::{cadastra_contrato, ContratoInput}, enderecos::busca_endereco_idusuario, notificacoes::{cadastra_notificacao, NotificacaoInput}, usuarios::UserId}, models::{self, solicitacoes_contratos::SolicitacaoContrato}};
+use crate::{controllers::{contratos::{monta_contrato, notifica_contrato_gerado}, envia_emails::{renderiza_email, EmailNovaSolicitacao, EmailRespostaSolicitacao}, maquinas::busca_maquina_id, notificacoes::{cadastra_notificacao, NotificacaoInput}}, dinheiro::{formata_reais, Dinheiro}, erros::ErroApi, models::{self, precos_maquinas::MedidaTempo, reservas_maquinas::{calcula_fim_locacao, ReservaMaquina}, solicitacoes_contratos::{HistoricoSolicitacao, SolicitacaoContrato, StatusSolicitacao}}};
 
-use super::{cria_conn, gera_hash, usuarios::IdInput};
+use super::{obtem_conn, precos_maquinas::orca_aluguel, sessoes::{verifica_usuario, verifica_usuario_ou_admin, UsuarioAutenticado}, usuarios::{busca_dados_usuario, IdInput}, Pool};
+use crate::ids::gera_id;
 
 #[derive(Serialize, Deserialize)]
 pub struct SolicitacaoContratoInput{
     pub idlocador: String,
     pub idlocatario: String,
     pub idmaquina: String,
-    pub medidatempolocacao: String,
+    pub medidatempolocacao: MedidaTempo,
     pub origemsolicitacao: String,
-    pub valorsolicitacao: f64,
+    /// Deve ser o total do orçamento da locação (`/orca_aluguel_maquina`).
+    pub valorsolicitacao: Dinheiro,
     pub prazolocacao: f64,
+    /// Início da locação. Se não for informado, a locação começa no momento da solicitação.
+    pub datainicio: Option<NaiveDateTime>
 }
 
-pub async fn cadastra_solicitacao_contrato(input: Json<SolicitacaoContratoInput>)
-    -> Result<(StatusCode, Json<String>), (StatusCode, Json<String>)>{
+pub async fn cadastra_solicitacao_contrato(State(pool): State<Pool>, usuario: UsuarioAutenticado, input: Json<SolicitacaoContratoInput>)
+    -> Result<(StatusCode, Json<String>), ErroApi>{
     if input.idlocador.trim().is_empty() || input.idlocatario.trim().is_empty() || 
       input.idmaquina.trim().is_empty() || input.origemsolicitacao.trim().is_empty()
-      || input.prazolocacao.to_string().trim().is_empty() || input.medidatempolocacao.trim().is_empty(){
-      return Err((StatusCode::BAD_REQUEST, Json("Um ou mais campos estão vazios.".to_string())))
+      || input.prazolocacao.to_string().trim().is_empty(){
+      return Err(ErroApi::validacao("Um ou mais campos estão vazios."))
+    }
+    verifica_usuario(&usuario, &input.idlocatario)?;
+    if input.idlocador.trim() == input.idlocatario.trim(){
+      return Err(ErroApi::validacao_campo("idlocatario", "Você não pode alugar a sua própria máquina."))
     }
 
     let idlocador = input.idlocador.trim().to_string();
     let idlocatario = input.idlocatario.trim().to_string();
     let idmaquina = input.idmaquina.trim().to_string();
     let origemsolicitacao = input.origemsolicitacao.trim().to_string();
-    let statussolicitacao = "Aguardando aprovação".to_string();
-    let medidatempolocacao = input.medidatempolocacao.to_string();
+    let statussolicitacao = StatusSolicitacao::AguardandoAprovacao;
+    let medidatempolocacao = input.medidatempolocacao;
     let prazolocacao = input.prazolocacao;
     let valorsolicitacao = input.valorsolicitacao;
 
-    let idsolicitacao = gera_hash(&idmaquina);
+    let idsolicitacao = gera_id();
     let datasolicitacao = chrono::Utc::now().naive_utc();
+
+    // Alguns minutos de tolerância para o relógio do cliente.
+    let datainicio = input.datainicio.unwrap_or(datasolicitacao);
+    if datainicio < datasolicitacao - TimeDelta::minutes(5){
+      return Err(ErroApi::validacao_campo("datainicio", "A locação não pode começar no passado."))
+    }
+    let datafim = calcula_fim_locacao(datainicio, prazolocacao, medidatempolocacao)?;
+    let reserva = ReservaMaquina{
+      idreserva: gera_id(),
+      idmaquina: idmaquina.clone(),
+      idsolicitacao: idsolicitacao.clone(),
+      datainicio,
+      datafim
+    };
     let solicitacao = SolicitacaoContrato{
       idsolicitacao,
       idlocador,
@@ -49,78 +74,102 @@ pub async fn cadastra_solicitacao_contrato(input: Json<SolicitacaoContratoInput>
       datasolicitacao
     };
 
-    if valorsolicitacao < 1.{
-      return Err((StatusCode::BAD_REQUEST, Json("O valor do aluguel não é válido.".to_string())))
+    let conn = &obtem_conn(&pool).await?;
+    // Só o dono da máquina pode aprovar a solicitação, então ele precisa ser o locador dela.
+    let iddono = models::maquinas_usuarios::busca_idusuario_idmaquina(conn, solicitacao.idmaquina.clone()).await?;
+    if iddono != solicitacao.idlocador{
+      return Err(ErroApi::validacao_campo("idlocador", "O locador informado não é o dono da máquina."))
     }
 
-    let conn = &mut cria_conn()?;
-    let id = match models::solicitacoes_contratos::cadastra_solicitacao_contrato(conn, solicitacao.clone()).await{
+    // O valor vem do cliente, então é conferido com o orçamento da tabela de preços da máquina.
+    let orcamento = orca_aluguel(&pool, &solicitacao.idmaquina, medidatempolocacao, prazolocacao).await?;
+    if valorsolicitacao != orcamento.total{
+      return Err(ErroApi::validacao_campo("valorsolicitacao",
+        &format!("O valor do aluguel não confere com o preço da máquina, que é {}.", formata_reais(orcamento.total))))
+    }
+
+    let id = match models::solicitacoes_contratos::cadastra_solicitacao_contrato(conn, solicitacao.clone(), reserva).await{
       Ok(id) => {
         id
       },
       Err(e) => {
-        return Err((StatusCode::INTERNAL_SERVER_ERROR, Json(e)))
+        return Err(e)
       }
     };
 
-    cadastra_notificacao(Json(NotificacaoInput{
+    let locatario = busca_dados_usuario(&pool, IdInput{id: solicitacao.idlocatario.clone()}).await?.1.0;
+    let maquina = busca_maquina_id(State(pool.clone()), Query(IdInput{id: solicitacao.idmaquina.clone()})).await?.1.0;
+    let email = renderiza_email(&EmailNovaSolicitacao{
+      nomelocatario: locatario.nome,
+      nomemaquina: maquina.nome,
+      prazolocacao: solicitacao.prazolocacao,
+      medidatempolocacao: solicitacao.medidatempolocacao.to_string(),
+      datainicio,
+      valorsolicitacao: solicitacao.valorsolicitacao,
+      diasexpiracao: DIAS_EXPIRACAO_SOLICITACAO
+    })?;
+
+    cadastra_notificacao(State(pool.clone()), Json(NotificacaoInput{
       idusuario: solicitacao.idlocador.clone(),
       titulo: "Alguém solicitou uma máquina!".to_string(),
       mensagem: "Um cliente acabou de solicitar uma de suas máquinas! Clique aqui para ver suas solicitações.".to_string(),
       onclick: "/contract-request".to_string(),
+      email: Some(email)
     })).await?.1.0;
 
-    cadastra_notificacao(Json(NotificacaoInput{
+    cadastra_notificacao(State(pool.clone()), Json(NotificacaoInput{
       idusuario: solicitacao.idlocatario.clone(),
       titulo: "Você solicitou uma máquina!".to_string(),
       mensagem: "Enviamos uma notificação para o dono da máquina que você solicitou. Ele receberá a notificação em breve, e poderá decidir se aprova ou recusa sua solicitação. Clique nessa mensagem para acompanhar suas solicitações.".to_string(),
       onclick: "/contract-request".to_string(),
+      email: None
     })).await?.1.0;
 
     return Ok((StatusCode::OK, Json(id)))
 }
 
-pub async fn busca_solicitacao_idsolicitacao(Query(id): Query<IdInput>)
-    -> Result<(StatusCode, Json<SolicitacaoContrato>), (StatusCode, Json<String>)>{
+/// Só o locador, o locatário e os administradores veem a solicitação.
+pub async fn busca_solicitacao_idsolicitacao(State(pool): State<Pool>, usuario: UsuarioAutenticado, Query(id): Query<IdInput>)
+    -> Result<(StatusCode, Json<SolicitacaoContrato>), ErroApi>{
     if id.id.trim().is_empty(){
-      return Err((StatusCode::BAD_REQUEST, Json("Um ou mais campos estão vazios.".to_string())))
+      return Err(ErroApi::validacao("Um ou mais campos estão vazios."))
     }
     let id = id.id.trim().to_string();
 
-    let conn = &mut cria_conn()?;
+    let conn = &obtem_conn(&pool).await?;
 
     match models::solicitacoes_contratos::busca_solicitacao_idsolicitacao(conn, id).await{
       Ok(solicitacao) => {
+        if usuario.idusuario != solicitacao.idlocador && usuario.idusuario != solicitacao.idlocatario && !usuario.e_admin(){
+          return Err(ErroApi::Proibido("Você não faz parte dessa solicitação.".to_string()))
+        }
         return Ok((StatusCode::OK, Json(solicitacao)))
       },
       Err(e) => {
-        return Err((StatusCode::INTERNAL_SERVER_ERROR, Json(e)))
+        return Err(e)
       }
     }
 }
 
-pub async fn busca_solicitacoes_idlocador(Query(id): Query<IdInput>)
-    -> Result<(StatusCode, Json<Vec<SolicitacaoContrato>>), (StatusCode, Json<String>)>{
+pub async fn busca_solicitacoes_idlocador(State(pool): State<Pool>, usuario: UsuarioAutenticado, Query(id): Query<IdInput>)
+    -> Result<(StatusCode, Json<Vec<SolicitacaoContrato>>), ErroApi>{
     if id.id.trim().is_empty(){
-      return Err((StatusCode::BAD_REQUEST, Json("Um ou mais campos estão vazios.".to_string())))
+      return Err(ErroApi::validacao("Um ou mais campos estão vazios."))
     }
+    verifica_usuario_ou_admin(&usuario, &id.id)?;
     let id = id.id.trim().to_string();
 
-    let conn = &mut cria_conn()?;
+    let conn = &obtem_conn(&pool).await?;
 
     let solicitacoes = match models::solicitacoes_contratos::busca_solicitacoes_idlocador(conn, id).await{
       Ok(solicitacoes) => {
         solicitacoes
       },
       Err(e) => {
-        return Err((StatusCode::INTERNAL_SERVER_ERROR, Json(e)))
+        return Err(e)
       }
     };
 
-    if solicitacoes.is_empty(){
-      return Err((StatusCode::INTERNAL_SERVER_ERROR, Json("Você não possui solicitações de contratos.".to_string())))
-    }
-
     return Ok((StatusCode::OK, Json(solicitacoes)))
 }
 
@@ -130,78 +179,145 @@ pub struct StatusSolicitacaoInput{
     pub status: String
 }
 
-pub async fn atualiza_status_solicitacao(input: Json<StatusSolicitacaoInput>)
-    -> Result<(StatusCode, Json<SolicitacaoContrato>), (StatusCode, Json<String>)>{
+pub async fn atualiza_status_solicitacao(State(pool): State<Pool>, usuario: UsuarioAutenticado, input: Json<StatusSolicitacaoInput>)
+    -> Result<(StatusCode, Json<SolicitacaoContrato>), ErroApi>{
     if input.id.trim().is_empty() || input.status.trim().is_empty(){
-        return Err((StatusCode::BAD_REQUEST, Json("Um ou mais campos estão vazios.".to_string())))
+        return Err(ErroApi::validacao("Um ou mais campos estão vazios."))
     }
+    let novostatus = StatusSolicitacao::from_str(&input.status)?;
 
-    let conn = &mut cria_conn()?;
+    let conn = &obtem_conn(&pool).await?;
 
+    // O model confere se a transição é permitida e se o usuário tem o papel exigido.
     let id = input.id.trim().to_string();
-    let novostatus = input.status.to_string();
-    let solicitacao = match models::solicitacoes_contratos::atualiza_status_solicitacao(conn, id, novostatus).await{
+    let mut idcontrato = None;
+    let res = if novostatus == StatusSolicitacao::Aprovada{
+      let atual = models::solicitacoes_contratos::busca_solicitacao_idsolicitacao(conn, id).await?;
+      verifica_usuario_ou_admin(&usuario, &atual.idlocador)?;
+      let contrato = monta_contrato(&pool, &atual).await?;
+      idcontrato = Some(contrato.idcontrato.clone());
+      models::solicitacoes_contratos::aprova_solicitacao(conn, contrato, usuario.idusuario.clone(), usuario.e_admin()).await
+    } else if usuario.e_admin(){
+      models::solicitacoes_contratos::modera_status_solicitacao(conn, id, novostatus, usuario.idusuario.clone()).await
+    } else {
+      models::solicitacoes_contratos::atualiza_status_solicitacao(conn, id, novostatus, Some(usuario.idusuario.clone())).await
+    };
+    let solicitacao = match res{
         Ok(solicitacao) => {
             solicitacao
         },
         Err(e) => {
-          return Err((StatusCode::INTERNAL_SERVER_ERROR, Json(e)))
+          return Err(e)
         }
     };
 
-    let idlocatario = busca_solicitacao_idsolicitacao(Query(IdInput { id: {input.id.clone()} })).await?.1.0.idlocatario;
+    // A mudança já foi gravada, então uma falha nos avisos não desfaz nada nem vira erro.
+    let moderada = usuario.idusuario != solicitacao.idlocador && usuario.idusuario != solicitacao.idlocatario;
+    if let Err(e) = notifica_status_solicitacao(&pool, &solicitacao, moderada).await{
+      tracing::warn!("Erro ao avisar a mudança de status da solicitação {}: {}", solicitacao.idsolicitacao, e);
+    }
+
+    if let Some(idcontrato) = idcontrato{
+      if let Err(e) = notifica_contrato_gerado(&pool, idcontrato.clone()).await{
+        tracing::warn!("Erro ao avisar as partes do contrato {}: {}", idcontrato, e);
+      }
+    }
+
+    return Ok((StatusCode::OK, Json(solicitacao)));
+}
 
-    let idnotificacao = cadastra_notificacao(
+/// Avisa a outra parte que o status da solicitação mudou. Quando a mudança foi feita por um
+/// administrador de fora da solicitação, quem recebe o aviso é o locatário.
+async fn notifica_status_solicitacao(pool: &Pool, solicitacao: &SolicitacaoContrato, moderada: bool)
+    -> Result<(), ErroApi>{
+    let (idnotificado, quem) = match solicitacao.statussolicitacao{
+      _ if moderada => (solicitacao.idlocatario.clone(), "A administração do MaqExpress"),
+      StatusSolicitacao::Cancelada => (solicitacao.idlocador.clone(), "O cliente"),
+      _ => (solicitacao.idlocatario.clone(), "Uma locadora")
+    };
+    let email = match solicitacao.statussolicitacao{
+      StatusSolicitacao::Aprovada | StatusSolicitacao::Recusada => {
+        let maquina = busca_maquina_id(State(pool.clone()), Query(IdInput{id: solicitacao.idmaquina.clone()})).await?.1.0;
+        Some(renderiza_email(&EmailRespostaSolicitacao{
+          nomemaquina: maquina.nome,
+          aprovada: solicitacao.statussolicitacao == StatusSolicitacao::Aprovada
+        })?)
+      },
+      _ => None
+    };
+    cadastra_notificacao(State(pool.clone()),
       Json(NotificacaoInput { 
-        idusuario: idlocatario, 
-        titulo: "Sua solicitação de aluguel foi atualizada!".to_string(), 
-        mensagem: format!("Uma locadora atualizou o status de sua solicitação para '{}'. Clique aqui para ver suas solicitações.", solicitacao.statussolicitacao.clone()), 
-        onclick: ("/contract-request".to_string()) })
-    ).await?.1.0;
-
-    if solicitacao.statussolicitacao != "Solicitação aprovada"{
-      return Ok((StatusCode::OK, Json(solicitacao)))
+        idusuario: idnotificado, 
+        titulo: "Uma solicitação de aluguel foi atualizada!".to_string(), 
+        mensagem: format!("{} atualizou o status da solicitação para '{}'. Clique aqui para ver suas solicitações.", quem, solicitacao.statussolicitacao), 
+        onclick: ("/contract-request".to_string()),
+        email })
+    ).await?;
+    return Ok(())
+}
+
+/// Dias que o locador tem para responder uma solicitação antes que ela expire.
+pub const DIAS_EXPIRACAO_SOLICITACAO: i64 = 7;
+
+/// Expira as solicitações sem resposta e avisa os locatários. Roda periodicamente a partir
+/// de `main.rs`.
+pub async fn expira_solicitacoes(pool: &Pool) -> Result<usize, ErroApi>{
+    let conn = &obtem_conn(pool).await?;
+    let limite = chrono::Utc::now().naive_utc() - TimeDelta::days(DIAS_EXPIRACAO_SOLICITACAO);
+
+    let expiradas = models::solicitacoes_contratos::expira_solicitacoes_pendentes(conn, limite).await?;
+    for solicitacao in &expiradas{
+      cadastra_notificacao(State(pool.clone()), Json(NotificacaoInput{
+        idusuario: solicitacao.idlocatario.clone(),
+        titulo: "Sua solicitação de aluguel expirou.".to_string(),
+        mensagem: "O dono da máquina não respondeu a tempo, então sua solicitação expirou. Clique aqui para ver suas solicitações.".to_string(),
+        onclick: "/contract-request".to_string(),
+        email: None
+      })).await?.1.0;
     }
+    return Ok(expiradas.len())
+}
 
-    let idenderecolocatario = busca_endereco_idusuario(Query(UserId{idusuario: solicitacao.idlocatario.clone()})).await?.1.0.idendereco;
-    let idenderecolocador = busca_endereco_idusuario(Query(UserId{idusuario: solicitacao.idlocador.clone()})).await?.1.0.idendereco;
-
-    let idcontrato = cadastra_contrato(Json(ContratoInput{
-        idlocatario: solicitacao.idlocatario.clone(),
-        idlocador: solicitacao.idlocador.clone(),
-        idenderecolocatario,
-        idenderecolocador: idenderecolocador.clone(),
-        idenderecoretirada: idenderecolocador,
-        idmaquina: solicitacao.idmaquina.clone(),
-        idsolicitacaocontrato: solicitacao.idsolicitacao.clone(),
-    })).await?.1.0;
+/// Lista as mudanças de status da solicitação, da mais antiga para a mais recente.
+pub async fn busca_historico_solicitacao(State(pool): State<Pool>, usuario: UsuarioAutenticado, Query(id): Query<IdInput>)
+    -> Result<(StatusCode, Json<Vec<HistoricoSolicitacao>>), ErroApi>{
+    if id.id.trim().is_empty(){
+      return Err(ErroApi::validacao("Um ou mais campos estão vazios."))
+    }
+    let id = id.id.trim().to_string();
 
-    println!("Contrato registrado!");
+    busca_solicitacao_idsolicitacao(State(pool.clone()), usuario, Query(IdInput{id: id.clone()})).await?;
 
-    return Ok((StatusCode::OK, Json(solicitacao)));
+    let conn = &obtem_conn(&pool).await?;
+
+    match models::solicitacoes_contratos::busca_historico_solicitacao(conn, id).await{
+      Ok(historico) => {
+        return Ok((StatusCode::OK, Json(historico)))
+      },
+      Err(e) => {
+        return Err(e)
+      }
+    }
 }
 
-pub async fn busca_solicitacoes_idlocatario(Query(id): Query<IdInput>)
-    -> Result<(StatusCode, Json<Vec<SolicitacaoContrato>>), (StatusCode, Json<String>)>{
+pub async fn busca_solicitacoes_idlocatario(State(pool): State<Pool>, usuario: UsuarioAutenticado, Query(id): Query<IdInput>)
+    -> Result<(StatusCode, Json<Vec<SolicitacaoContrato>>), ErroApi>{
     if id.id.trim().is_empty(){
-      return Err((StatusCode::BAD_REQUEST, Json("Um ou mais campos estão vazios.".to_string())))
+      return Err(ErroApi::validacao("Um ou mais campos estão vazios."))
     }
+    verifica_usuario_ou_admin(&usuario, &id.id)?;
     let id = id.id.trim().to_string();
 
-    let conn = &mut cria_conn()?;
+    let conn = &obtem_conn(&pool).await?;
 
     let solicitacoes = match models::solicitacoes_contratos::busca_solicitacoes_idlocatario(conn, id).await{
       Ok(solicitacoes) => {
         solicitacoes
       },
       Err(e) => {
-        return Err((StatusCode::INTERNAL_SERVER_ERROR, Json(e)))
+        return Err(e)
       }
     };
 
-    if solicitacoes.is_empty(){
-      return Err((StatusCode::INTERNAL_SERVER_ERROR, Json("Você não emitiu solicitações de contratos.".to_string())))
-    }
-
     return Ok((StatusCode::OK, Json(solicitacoes)))
 }
\ No newline at end of file
@@ -1,20 +1,16 @@
-use axum::{extract::Query, http::StatusCode, Json};
-use pwhash::unix::verify;
-use rand::Rng;
-use reqwest::Response;
+use axum::{extract::{Query, State}, http::StatusCode, Json};
 use serde::{Deserialize, Serialize};
 use utoipa::{IntoParams, ToSchema};
 use validator::Validate;
 
-use crate::models::{self, usuarios::Usuario};
+use crate::{erros::ErroApi, ids::gera_id, senhas::{configuracao_senhas, gera_hash_senha, verifica_senha, VerificacaoSenha}, models::{self, codigos_recuperacao::{busca_data_ultimo_codigo, redefine_senha_token, verifica_codigo_recuperacao_db}, usuarios::{StatusUsuario, Usuario}}};
 
-use super::{codigos_recuperacao::gera_codigo_recuperacao, cria_conn, envia_emails::envia_email_codigo, formata_cnpj, formata_cpf, gera_hash, notificacoes::{cadastra_notificacao, NotificacaoInput}};
+use super::{codigos_recuperacao::{gera_codigo_recuperacao, hash_token_redefinicao}, obtem_conn, envia_emails::{envia_email_codigo, FinalidadeCodigo}, formata_cnpj, formata_cpf, notificacoes::{cadastra_notificacao, NotificacaoInput}, sessoes::{gera_tokens_sessao, verifica_usuario, verifica_usuario_ou_admin, TokensSessao, UsuarioAutenticado}, Pool};
 
 #[derive(Serialize, Deserialize, ToSchema)]
 pub struct UsuarioReturn{
     pub nome: String,
     pub email: String,
-    pub senha: String,
     pub documento: String,
     pub datacadastro: String,
     pub idusuario: String,
@@ -26,7 +22,6 @@ impl From<Usuario> for UsuarioReturn {
         Self {       
             nome: usuario.nome,
             email: usuario.email,
-            senha: usuario.senha,
             documento: usuario.documento,
             datacadastro: usuario.datacadastro.to_string(),
             idusuario: usuario.idusuario,
@@ -35,6 +30,23 @@ impl From<Usuario> for UsuarioReturn {
     }
 }
 
+/// Só os dados que podem ser mostrados para outros usuários, como o nome do
+/// dono de uma máquina ou de quem fez uma solicitação.
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct PerfilUsuarioReturn{
+    pub idusuario: String,
+    pub nome: String
+}
+
+impl From<UsuarioReturn> for PerfilUsuarioReturn {
+    fn from(usuario: UsuarioReturn) -> Self {
+        Self {
+            idusuario: usuario.idusuario,
+            nome: usuario.nome
+        }
+    }
+}
+
 #[derive(Deserialize, Serialize, Clone, ToSchema)]
 pub struct UsuarioInput{
     pub nome: String,
@@ -65,6 +77,10 @@ pub struct EmailInput{
             status = 500,
             description = "Erro ao cadastrar o usuário."
         ),
+        (
+            status = 409,
+            description = "Já existe um usuário cadastrado com esse e-mail."
+        ),
         (
             status = 400,
             description = "Algum dos campos inseridos está incorreto."
@@ -73,35 +89,38 @@ pub struct EmailInput{
     request_body = UsuarioInput    
 )]
 
-pub async fn cadastra_usuario(usuario: Json<UsuarioInput>)
-    -> Result<(StatusCode, Json<UserId>), (StatusCode, Json<String>)>{
+pub async fn cadastra_usuario(State(pool): State<Pool>, usuario: Json<UsuarioInput>)
+    -> Result<(StatusCode, Json<UserId>), ErroApi>{
     match valida_usuario(&usuario.0).await{
         Ok(_) => {},
         Err(e) => {
-            println!("{:?}", e);
-            return Err((StatusCode::BAD_REQUEST, Json(e)))
+            tracing::debug!("Cadastro de usuário recusado: {}", e);
+            return Err(e)
         }
     }
 
-    match busca_usuario_email(Query(EmailInput{email: usuario.email.clone()})).await{
+    match busca_usuario_email(State(pool.clone()), Query(EmailInput{email: usuario.email.clone()})).await{
         Ok(_) => {
-            return Err((StatusCode::BAD_REQUEST, Json("Já existe um usuário cadastrado com esse e-mail.".to_string())))
+            return Err(ErroApi::Conflito("Já existe um usuário cadastrado com esse e-mail.".to_string()))
         },
+        Err(ErroApi::NaoEncontrado(_)) => {},
         Err(e) => {
-            println!("{:?}", e);
+            return Err(e)
         }
     }
 
     let email_clone = usuario.email.to_string();
-    let senha = gera_hash(&usuario.senha);
+    let senha = gera_hash_senha(&usuario.senha)?;
 
-    let idusuario = gera_hash(&usuario.email);
+    let conn = &obtem_conn(&pool).await?;
+    let idusuario = gera_id();
     let idusuario_clone = idusuario.clone();
+    let idpublico = models::usuarios::gera_idpublico_usuario(conn).await?;
     let now = chrono::Utc::now().naive_utc();
     let documento = match formata_documento(&usuario.documento){
         Ok(doc) => {doc},
         Err(e) => {
-            return Err((StatusCode::BAD_REQUEST, Json(format!("Documento inválido: {}", e))))
+            return Err(ErroApi::validacao_campo("documento", &format!("Documento inválido: {}", e)))
         }
     };
     let usuario = Usuario{
@@ -112,39 +131,40 @@ pub async fn cadastra_usuario(usuario: Json<UsuarioInput>)
         datacadastro: now,
         idusuario,
         origemconta: "Sistema".to_string(),
-        idpublico: rand::thread_rng().gen_range(111111..999999).to_string(),
-        status: "Ativo".to_string()
+        idpublico,
+        status: StatusUsuario::Pendente
     };
 
-    let conn = &mut cria_conn()?;
-
     match models::usuarios::cadastra_usuario(conn, usuario).await{
         Ok(_) => {
             
         },
         Err(e) => {
-            return Err((StatusCode::INTERNAL_SERVER_ERROR, Json(e)))
+            return Err(e)
         }
     }
     
-    cadastra_notificacao(Json(NotificacaoInput{
+    // O usuário já está gravado. Se o aviso ou o e-mail falharem, o cadastro vale do mesmo
+    // jeito, e o código de ativação pode ser pedido de novo em /reenvia_codigo_ativacao.
+    if let Err(e) = cadastra_notificacao(State(pool.clone()), Json(NotificacaoInput{
         idusuario: idusuario_clone.clone(),
         titulo: "Bem-Vindo!".to_string(),
         mensagem: "Você realizou seu cadastro na MaqExpress! Clique nessa mensagem para ver as máquinas disponíveis no catálogo.".to_string(),
         onclick: "/machine".to_string(),
-    })).await?.1.0;
+        email: None
+    })).await{
+        tracing::warn!("Erro ao criar a notificação de boas-vindas do usuário {}: {}", idusuario_clone, e);
+    }
 
-    let codigo = gera_codigo_recuperacao(email_clone.clone()).await?.1.0.codigo;
-    match envia_email_codigo(email_clone, "ativação de conta", codigo).await{
-        Ok(codigoativacao) => {
-            println!("Código de ativação: {}", codigoativacao.1.0);
-            return Ok((StatusCode::OK, Json(UserId{idusuario: idusuario_clone})))
-        },
-        Err(e) => {
-            return Err(e)
-        }
+    let envio = match gera_codigo_recuperacao(&pool, email_clone.clone(), &FinalidadeCodigo::Ativacao).await{
+        Ok(codigo) => envia_email_codigo(email_clone, FinalidadeCodigo::Ativacao, codigo.codigo).await,
+        Err(e) => Err(e)
+    };
+    if let Err(e) = envio{
+        tracing::warn!("Erro ao enviar o código de ativação do usuário {}: {}", idusuario_clone, e);
     }
 
+    return Ok((StatusCode::OK, Json(UserId{idusuario: idusuario_clone})))
 }
 
 #[derive(Serialize, Deserialize, ToSchema)]
@@ -162,16 +182,20 @@ pub struct UserId{
     post,
     tag = "Usuário",
     path = "/realiza_login",
-    description = "Valida o login de um usuário.",
+    description = "Valida o login de um usuário e inicia uma sessão.",
     responses(
         (
             status = 200, 
-            description = "Credenciais verificadas e válidas. Login pode ser realizado.",
-            body = UserId       
+            description = "Credenciais verificadas e válidas. Tokens da sessão gerados.",
+            body = TokensSessao       
         ),
         (
-            status = 500,
-            description = "O e-mail inserido não está registrado no sistema."
+            status = 401,
+            description = "O e-mail inserido não está registrado no sistema OU a senha está incorreta."
+        ),
+        (
+            status = 403,
+            description = "A conta ainda não foi ativada (conta_nao_ativada) OU está suspensa (conta_suspensa)."
         ),
         (
             status = 400,
@@ -181,13 +205,182 @@ pub struct UserId{
     request_body = CredenciaisUsuario    
 )]
 
-pub async fn realiza_login(input: Json<CredenciaisUsuario>)
-    -> Result<(StatusCode, Json<UserId>), (StatusCode, Json<String>)>{
+pub async fn realiza_login(State(pool): State<Pool>, input: Json<CredenciaisUsuario>)
+    -> Result<(StatusCode, Json<TokensSessao>), ErroApi>{
+    let email = input.email.trim().to_string();
+    let senha = input.senha.clone();
+    let (idusuario, verificacao) = confere_credenciais(&pool, input.0).await?;
+
+    // O status só é conferido depois da senha, para não revelar a situação de contas alheias.
+    let conn = &obtem_conn(&pool).await?;
+    let usuario = models::usuarios::busca_usuario_id(conn, idusuario.clone()).await?;
+    match usuario.status{
+        StatusUsuario::Ativo => {},
+        StatusUsuario::Pendente => {
+            return Err(ErroApi::ContaNaoAtivada("A conta ainda não foi ativada. Use o código enviado para o seu e-mail.".to_string()))
+        },
+        StatusUsuario::Suspenso => {
+            return Err(ErroApi::ContaSuspensa("A conta está suspensa.".to_string()))
+        }
+    }
+
+    // Hashes bcrypt ou com parâmetros antigos são refeitos agora que a senha está em mãos.
+    // Uma falha aqui não impede o login; o hash é refeito no próximo.
+    if verificacao == VerificacaoSenha::PrecisaAtualizar{
+        let res = match gera_hash_senha(&senha){
+            Ok(hash) => models::usuarios::atualiza_senha_usuario(conn, email, hash).await,
+            Err(e) => Err(e)
+        };
+        if let Err(e) = res{
+            tracing::warn!("Erro ao atualizar o hash da senha do usuário {}: {}", idusuario, e);
+        }
+    }
+
+    let tokens = gera_tokens_sessao(&idusuario)?;
+    return Ok((StatusCode::OK, Json(tokens)))
+}
+
+/// Tempo mínimo entre dois envios do código de ativação para a mesma conta.
+pub const SEGUNDOS_REENVIO_CODIGO_ATIVACAO: i64 = 60;
+
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct AtivaContaInput{
+    pub email: String,
+    pub codigo: String
+}
+
+#[utoipa::path(
+    post,
+    tag = "Usuário",
+    path = "/ativa_conta",
+    description = "Ativa a conta de um usuário com o código enviado para o seu e-mail no cadastro.",
+    responses(
+        (
+            status = 200, 
+            description = "Código válido. A conta foi ativada.",
+            body = UserId
+        ),
+        (
+            status = 404,
+            description = "O e-mail inserido não está registrado no sistema."
+        ),
+        (
+            status = 409,
+            description = "A conta já está ativa ou está suspensa."
+        ),
+        (
+            status = 403,
+            description = "O usuário errou o código muitas vezes e está bloqueado por alguns minutos."
+        ),
+        (
+            status = 400,
+            description = "O código está incorreto ou expirou, ou algum dos campos inseridos é inválido."
+        ),
+    ),
+    request_body = AtivaContaInput    
+)]
+
+pub async fn ativa_conta(State(pool): State<Pool>, input: Json<AtivaContaInput>)
+    -> Result<(StatusCode, Json<UserId>), ErroApi>{
+    let codigo = input.codigo.trim().to_string();
+    if codigo.is_empty(){
+        return Err(ErroApi::validacao_campo("codigo", "O código não pode estar vazio."))
+    }
+
+    let idusuario = busca_usuario_email(State(pool.clone()), Query(EmailInput{email: input.email.trim().to_string()})).await?.1.0;
+
+    let conn = &obtem_conn(&pool).await?;
+    let usuario = models::usuarios::busca_usuario_id(conn, idusuario.clone()).await?;
+    verifica_conta_pendente(usuario.status)?;
+
+    verifica_codigo_recuperacao_db(conn, idusuario.clone(), codigo, FinalidadeCodigo::Ativacao.chave()).await?;
+
+    match models::usuarios::atualiza_status_usuario(conn, idusuario.clone(), StatusUsuario::Ativo).await{
+        Ok(_) => {
+            return Ok((StatusCode::OK, Json(UserId{idusuario})))
+        },
+        Err(e) => {
+            return Err(e)
+        }
+    }
+}
+
+#[utoipa::path(
+    post,
+    tag = "Usuário",
+    path = "/reenvia_codigo_ativacao",
+    description = "Envia um novo código de ativação para o e-mail de uma conta pendente.",
+    responses(
+        (
+            status = 200, 
+            description = "Um novo código foi enviado.",
+        ),
+        (
+            status = 404,
+            description = "O e-mail inserido não está registrado no sistema."
+        ),
+        (
+            status = 409,
+            description = "A conta já está ativa ou está suspensa OU o último código foi enviado há menos de um minuto."
+        ),
+        (
+            status = 400,
+            description = "O e-mail é inválido."
+        ),
+    ),
+    request_body = EmailInput    
+)]
+
+pub async fn reenvia_codigo_ativacao(State(pool): State<Pool>, input: Json<EmailInput>)
+    -> Result<StatusCode, ErroApi>{
+    let email = input.email.trim().to_string();
+    let idusuario = busca_usuario_email(State(pool.clone()), Query(EmailInput{email: email.clone()})).await?.1.0;
+
+    let conn = &obtem_conn(&pool).await?;
+    let usuario = models::usuarios::busca_usuario_id(conn, idusuario.clone()).await?;
+    verifica_conta_pendente(usuario.status)?;
+
+    if let Some(ultimoenvio) = busca_data_ultimo_codigo(conn, idusuario).await?{
+        let espera = SEGUNDOS_REENVIO_CODIGO_ATIVACAO - (chrono::Utc::now().naive_utc() - ultimoenvio).num_seconds();
+        if espera > 0{
+            return Err(ErroApi::Conflito(format!("Aguarde {} segundos para pedir um novo código.", espera)))
+        }
+    }
+
+    let codigo = gera_codigo_recuperacao(&pool, email.clone(), &FinalidadeCodigo::Ativacao).await?.codigo;
+    envia_email_codigo(email, FinalidadeCodigo::Ativacao, codigo).await?;
+    return Ok(StatusCode::OK)
+}
+
+fn verifica_conta_pendente(status: StatusUsuario) -> Result<(), ErroApi>{
+    match status{
+        StatusUsuario::Pendente => {
+            return Ok(())
+        },
+        StatusUsuario::Ativo => {
+            return Err(ErroApi::Conflito("A conta já está ativa.".to_string()))
+        },
+        StatusUsuario::Suspenso => {
+            return Err(ErroApi::ContaSuspensa("A conta está suspensa.".to_string()))
+        }
+    }
+}
+
+/// Confere e-mail e senha, retornando o ID do usuário. Não inicia uma sessão.
+pub async fn verifica_credenciais(pool: &Pool, input: CredenciaisUsuario)
+    -> Result<String, ErroApi>{
+    let (idusuario, _) = confere_credenciais(pool, input).await?;
+    return Ok(idusuario)
+}
+
+/// Como `verifica_credenciais`, mas também diz se o hash da senha precisa ser refeito.
+async fn confere_credenciais(pool: &Pool, input: CredenciaisUsuario)
+    -> Result<(String, VerificacaoSenha), ErroApi>{
     let email = input.email.to_string();
     let senha: String = input.senha.to_string();
 
     if senha.trim().is_empty(){
-        return Err((StatusCode::BAD_REQUEST, Json("A senha está vazia.".to_string())))
+        return Err(ErroApi::validacao_campo("senha", "A senha está vazia."))
     }
     
     match valida_email(Json(EmailInput{
@@ -199,30 +392,32 @@ pub async fn realiza_login(input: Json<CredenciaisUsuario>)
         }
     }
 
-    let hash_senha = match busca_senha_usuario(Json(EmailInput{
+    let hash_senha = match busca_senha_usuario(State(pool.clone()), Json(EmailInput{
         email: email.clone()
     })).await{
         Ok(hash) => {hash},
+        Err(ErroApi::NaoEncontrado(_)) => {
+            return Err(ErroApi::NaoAutorizado("Erro no login.".to_string()))
+        },
         Err(e) => {
             return Err(e)
         }
     };
     let hash_senha = hash_senha.1.to_string();
     
-    let id = busca_usuario_email(Query(EmailInput{
+    let id = busca_usuario_email(State(pool.clone()), Query(EmailInput{
         email
     })).await?.1.0;
 
-    if verify(senha, &hash_senha){
-        return Ok((StatusCode::OK, Json(UserId{
-            idusuario: id
-        })))
+    let verificacao = verifica_senha(&senha, &hash_senha)?;
+    if verificacao.correta(){
+        return Ok((id, verificacao))
     }
-    return Err((StatusCode::BAD_REQUEST, Json("Erro no login.".to_string())))
+    return Err(ErroApi::NaoAutorizado("Erro no login.".to_string()))
 }
 
-pub async fn busca_senha_usuario(email: Json<EmailInput>) 
-    -> Result<(StatusCode, Json<String>), (StatusCode, Json<String>)>{
+pub async fn busca_senha_usuario(State(pool): State<Pool>, email: Json<EmailInput>) 
+    -> Result<(StatusCode, Json<String>), ErroApi>{
     let email_clone = email.email.clone();
     match valida_email(email).await{
         Ok(_) => {},
@@ -231,14 +426,14 @@ pub async fn busca_senha_usuario(email: Json<EmailInput>)
         }
     }
 
-    let conn = &mut cria_conn()?;
+    let conn = &obtem_conn(&pool).await?;
 
     match models::usuarios::busca_senha_usuario(conn, email_clone).await{
         Ok(hash) => {
             return Ok((StatusCode::OK, Json(hash)))
         },
         Err(e) => {
-            return Err((StatusCode::INTERNAL_SERVER_ERROR, Json(e)))
+            return Err(e)
         }
     }
 }
@@ -256,7 +451,7 @@ pub async fn busca_senha_usuario(email: Json<EmailInput>)
             body = String       
         ),
         (
-            status = 500,
+            status = 404,
             description = "O e-mail inserido não está registrado no sistema."
         ),
         (
@@ -268,54 +463,48 @@ pub async fn busca_senha_usuario(email: Json<EmailInput>)
         ("idusuario" = UserId, Path, description = "ID do usuário"),
     )
 )]
-pub async fn busca_email_usuario(Query(params): Query<UserId>) -> Result<(StatusCode, Json<String>), (StatusCode, Json<String>)>{
+pub async fn busca_email_usuario(State(pool): State<Pool>, usuario: UsuarioAutenticado, Query(params): Query<UserId>) -> Result<(StatusCode, Json<String>), ErroApi>{
     let id = params.idusuario.trim().to_string();
     if id.trim().is_empty(){
-        return Err((StatusCode::BAD_REQUEST, Json("O ID está vazio.".to_string())))
+        return Err(ErroApi::validacao("O ID está vazio."))
     }
+    verifica_usuario_ou_admin(&usuario, &id)?;
     
-    let conn = &mut cria_conn()?;
+    let conn = &obtem_conn(&pool).await?;
 
     let resultado_busca = models::usuarios::busca_email_usuario(conn, id).await;
     match resultado_busca{
         Ok(email) => {
             return Ok((StatusCode::OK, Json(email)))
     }, Err(e) => {
-        return Err((StatusCode::BAD_REQUEST, Json(e.to_string())));
+        return Err(e);
     }
     }
 }
 
+/// Confere a senha com a política configurada; veja `crate::senhas`.
 pub fn valida_senha(senha: &str) -> Result<(), String>{
-    if senha.trim().is_empty(){
-        return Err("A senha está vazia.".to_string())
-    }
-    if senha.len() < 8{
-        return Err("A senha é muito curta.".to_string())
-    }
-    if !senha.chars().any(|c| c.is_ascii_digit()){
-        return Err("A senha deve conter ao menos um número".to_string())
-    }
-    if !senha.chars().any(|c| c.is_ascii_punctuation()){
-        return Err("A senha deve conter ao menos um símbolo".to_string())
-    }
-    if !senha.chars().any(|c| c.is_uppercase()){
-        return Err("A senha deve conter ao menos uma letra maiúscula.".to_string())
+    match configuracao_senhas(){
+        Ok(configuracao) => {
+            return configuracao.politica.valida(senha)
+        },
+        Err(e) => {
+            return Err(e.to_string())
+        }
     }
-    return Ok(())
 }
 
-pub async fn valida_usuario(usuario: &UsuarioInput) -> Result<(), String>{
+pub async fn valida_usuario(usuario: &UsuarioInput) -> Result<(), ErroApi>{
     let nome = usuario.nome.to_string();
     if nome.trim().is_empty(){
-        return Err("Erro ao validar o nome.".to_string())
+        return Err(ErroApi::validacao_campo("nome", "Erro ao validar o nome."))
     }
 
     let email = usuario.email.to_string();
     match valida_email(Json(EmailInput{email: email.clone()})).await{
         Ok(_) => {},
         Err(e) => {
-            return Err(e.1.0)
+            return Err(e)
         }
     }
 
@@ -324,7 +513,7 @@ pub async fn valida_usuario(usuario: &UsuarioInput) -> Result<(), String>{
         Ok(()) => {
         },
         Err(e) => {
-            return Err(e)
+            return Err(ErroApi::validacao_campo("senha", &e))
         }
     }
 
@@ -334,7 +523,7 @@ pub async fn valida_usuario(usuario: &UsuarioInput) -> Result<(), String>{
             
         },
         Err(e) => {
-            return Err(e)
+            return Err(ErroApi::validacao_campo("documento", &e))
         }
     };
     return Ok(())
@@ -359,9 +548,13 @@ pub struct AtualizaEmailInput{
             body = String       
         ),
         (
-            status = 500,
+            status = 401,
             description = "O e-mail inserido não está registrado no sistema OU a senha está incorreta."
         ),
+        (
+            status = 409,
+            description = "O novo e-mail pertence a outro usuário."
+        ),
         (
             status = 400,
             description = "Algum dos campos inseridos está incorreto."
@@ -369,7 +562,7 @@ pub struct AtualizaEmailInput{
     ),
     request_body = AtualizaEmailInput
 )]
-pub async fn atualiza_email_usuario(input: Json<AtualizaEmailInput>) -> Result<(StatusCode, Json<String>), (StatusCode, Json<String>)>{
+pub async fn atualiza_email_usuario(State(pool): State<Pool>, usuario: UsuarioAutenticado, input: Json<AtualizaEmailInput>) -> Result<(StatusCode, Json<String>), ErroApi>{
     let email_antigo = input.email_antigo.to_string();
     let email_novo = input.email_novo.to_string();
 
@@ -395,52 +588,45 @@ pub async fn atualiza_email_usuario(input: Json<AtualizaEmailInput>) -> Result<(
         }
     }
 
-    match realiza_login(Json(CredenciaisUsuario{
+    match verifica_credenciais(&pool, CredenciaisUsuario{
         email: email_antigo.clone(),
         senha: input.senha.to_string()
-    })).await{
-        Ok(_) => {},
-        Err(e) => {
-            return Err(e)
-        }
-    }
-    
-    match busca_usuario_email(Query(EmailInput{
-        email: email_antigo.clone()
-    })).await{
-        Ok(_) => {
+    }).await{
+        Ok(idusuario) => {
+            verifica_usuario(&usuario, &idusuario)?;
         },
         Err(e) => {
             return Err(e)
         }
     }
     
-    match busca_usuario_email(Query(EmailInput{
+    match busca_usuario_email(State(pool.clone()), Query(EmailInput{
         email: email_novo.clone()
     })).await{
         Ok(_) => {
-            return Err((StatusCode::BAD_REQUEST, Json("Esse e-mail pertence a outro usuário.".to_string())))
+            return Err(ErroApi::Conflito("Esse e-mail pertence a outro usuário.".to_string()))
         },
         Err(_) => {
             
         }
     }
 
-    let conn = &mut cria_conn()?;
+    let conn = &obtem_conn(&pool).await?;
 
     match models::usuarios::atualiza_email_usuario(conn, email_antigo, email_novo).await{
         Ok(email_atualizado) => {
             return Ok((StatusCode::OK, Json(email_atualizado)))
         },
         Err(e) =>{
-            return Err((StatusCode::BAD_REQUEST, Json(e)))
+            return Err(e)
         }
     }
 }
 
 #[derive(Serialize, Deserialize, ToSchema)]
 pub struct RecuperaSenhaInput{
-    pub idusuario: String,
+    /// Token devolvido por `/verifica_codigo_recuperacao`.
+    pub tokenredefinicao: String,
     pub senha_nova: String
 }
 
@@ -449,46 +635,44 @@ pub struct RecuperaSenhaInput{
     patch,
     tag = "Usuário",
     path = "/redefine_senha_usuario",
-    description = "Redefine a senha de um usuário que não está logado.",
+    description = "Redefine a senha de um usuário que não está logado, com o token devolvido por
+    `/verifica_codigo_recuperacao`. Cada token redefine a senha uma vez só.",
     responses(
         (
             status = 200, 
-            description = "O e-mail foi encontrado e a senha é segura. A senha foi atualizada com sucesso.",
-        ),
-        (
-            status = 500,
-            description = "O e-mail inserido não está registrado no sistema."
+            description = "O token é válido e a senha é segura. A senha foi atualizada com sucesso.",
         ),
         (
             status = 400,
-            description = "Algum dos campos inseridos está incorreto."
+            description = "O token é inválido, expirou ou já foi usado, ou a senha não é segura."
         ),
     ),
     request_body = RecuperaSenhaInput    
 )]
 
-pub async fn redefine_senha_usuario(input: Json<RecuperaSenhaInput>)
-    -> Result<StatusCode, (StatusCode, Json<String>)>{
-    let id = input.idusuario.to_string();
-    let email = busca_usuario_id(Query(IdInput{id})).await?.1.email.to_string();
+pub async fn redefine_senha_usuario(State(pool): State<Pool>, input: Json<RecuperaSenhaInput>)
+    -> Result<StatusCode, ErroApi>{
+    if input.tokenredefinicao.trim().is_empty(){
+        return Err(ErroApi::validacao_campo("tokenredefinicao", "O token de redefinição não pode estar vazio."))
+    }
 
     let senha_nova = input.senha_nova.to_string();
     match valida_senha(&senha_nova){
         Ok(_) => {},
         Err(e) => {
-            return Err((StatusCode::BAD_REQUEST, Json(e)))
+            return Err(ErroApi::validacao_campo("senha_nova", &e))
         }
     }
-    let senha_nova = gera_hash(&senha_nova);
+    let senha_nova = gera_hash_senha(&senha_nova)?;
 
-    let conn = &mut cria_conn()?;
+    let conn = &obtem_conn(&pool).await?;
 
-    match models::usuarios::atualiza_senha_usuario(conn, email, senha_nova).await{
+    match redefine_senha_token(conn, hash_token_redefinicao(&input.tokenredefinicao), senha_nova).await{
         Ok(_) => {
             return Ok(StatusCode::OK)
         },
         Err(e) => {
-            return Err((StatusCode::INTERNAL_SERVER_ERROR, Json(e)))
+            return Err(e)
         }
     }
 }
@@ -511,7 +695,7 @@ pub struct AtualizaSenhaInput{
             description = "O e-mail foi encontrado e a senha é segura. A senha foi atualizada com sucesso.",
         ),
         (
-            status = 500,
+            status = 404,
             description = "O e-mail inserido não está registrado no sistema."
         ),
         (
@@ -522,14 +706,15 @@ pub struct AtualizaSenhaInput{
     request_body = RecuperaSenhaInput    
 )]
 
-pub async fn atualiza_senha_usuario(input: Json<AtualizaSenhaInput>)
-    -> Result<StatusCode, (StatusCode, Json<String>)>{
+pub async fn atualiza_senha_usuario(State(pool): State<Pool>, usuario: UsuarioAutenticado, input: Json<AtualizaSenhaInput>)
+    -> Result<StatusCode, ErroApi>{
     let id = input.idusuario.to_string();
-    let email = busca_usuario_id(Query(IdInput{id})).await?.1.email.to_string();
-    match realiza_login(Json(CredenciaisUsuario{
+    verifica_usuario(&usuario, &id)?;
+    let email = busca_dados_usuario(&pool, IdInput{id}).await?.1.email.to_string();
+    match verifica_credenciais(&pool, CredenciaisUsuario{
         email: email.clone(),
         senha: input.senha_antiga.to_string()
-    })).await{
+    }).await{
         Ok(_) => {},
         Err(e) => {
             return Err(e)
@@ -540,49 +725,27 @@ pub async fn atualiza_senha_usuario(input: Json<AtualizaSenhaInput>)
     match valida_senha(&senha_nova){
         Ok(_) => {},
         Err(e) => {
-            return Err((StatusCode::BAD_REQUEST, Json(e)))
+            return Err(ErroApi::validacao_campo("senha_nova", &e))
         }
     }
-    let senha_nova = gera_hash(&senha_nova);
+    let senha_nova = gera_hash_senha(&senha_nova)?;
 
-    let conn = &mut cria_conn()?;
+    let conn = &obtem_conn(&pool).await?;
 
     match models::usuarios::atualiza_senha_usuario(conn, email, senha_nova).await{
         Ok(_) => {
             return Ok(StatusCode::OK)
         },
         Err(e) => {
-            return Err((StatusCode::INTERNAL_SERVER_ERROR, Json(e)))
+            return Err(e)
         }
     }
 }
 
 
-#[utoipa::path(
-    get,
-    tag = "Usuário",
-    path = "/busca_usuario_email/{email}",
-    description = "Busca o ID de um usuário a partir do e-mail.",
-    responses(
-        (
-            status = 200, 
-            description = "E-mail válido. Usuário encontrado. ID retornado.",
-            body = UserId       
-        ),
-        (
-            status = 404,
-            description = "O e-mail inserido não está registrado no sistema."
-        ),
-        (
-            status = 400,
-            description = "Parâmetro inválido ou ausente."
-        ),
-    ),
-    params(
-        ("email" = String, Path, description = "E-mail do usuário"),
-    )
-)]
-pub async fn busca_usuario_email(Query(params): Query<EmailInput>) -> Result<(StatusCode, Json<String>), (StatusCode, Json<String>)>{
+/// ID do usuário dono do e-mail. Só para uso interno: não tem rota, para que ninguém descubra
+/// o ID de outra conta pelo e-mail.
+pub async fn busca_usuario_email(State(pool): State<Pool>, Query(params): Query<EmailInput>) -> Result<(StatusCode, Json<String>), ErroApi>{
     match valida_email(Json(EmailInput{
         email: params.email.clone()
     })).await{
@@ -594,7 +757,7 @@ pub async fn busca_usuario_email(Query(params): Query<EmailInput>) -> Result<(St
 
     let email = params.email.to_string();
     
-    let conn = &mut cria_conn()?;
+    let conn = &obtem_conn(&pool).await?;
 
     let res = models::usuarios::busca_usuario_email(conn, email).await;
     match res{
@@ -602,12 +765,12 @@ pub async fn busca_usuario_email(Query(params): Query<EmailInput>) -> Result<(St
             return Ok((StatusCode::OK, Json(idusuario)))
         },
         Err(e) => {
-            return Err((StatusCode::BAD_REQUEST, Json(e)))
+            return Err(e)
         }
     }
 }
 
-pub async fn busca_usuario_email_oauth(Query(params): Query<EmailInput>) -> Result<(StatusCode, Json<String>), (StatusCode, Json<String>)>{
+pub async fn busca_usuario_email_oauth(State(pool): State<Pool>, Query(params): Query<EmailInput>) -> Result<(StatusCode, Json<String>), ErroApi>{
     match valida_email(Json(EmailInput{
         email: params.email.clone()
     })).await{
@@ -619,30 +782,23 @@ pub async fn busca_usuario_email_oauth(Query(params): Query<EmailInput>) -> Resu
 
     let email = params.email.to_string();
     
-    let conn = &mut cria_conn()?;
+    let conn = &obtem_conn(&pool).await?;
 
     let res = models::usuarios::busca_usuario_email_oauth(conn, email.clone()).await;
-    let res = match res{
+    match res{
         Ok(idusuario) => {
-            println!("Usuário Oauth encontrado");
+            tracing::debug!("Usuário OAuth encontrado: {}", idusuario);
             return Ok((StatusCode::OK, Json(idusuario)))
         },
+        Err(ErroApi::NaoEncontrado(_)) => {},
         Err(e) => {
-            e      
+            return Err(e)
         }
     };
 
-    if res != "Record not found".to_string(){
-        println!("{:?}", res);
-        println!("E != RECORD NOT FOUND");
-    }
-
-    println!("!RECORD NOT FOUND");
-
-    
-    match busca_usuario_email(Query(EmailInput{email})).await{
+    match busca_usuario_email(State(pool.clone()), Query(EmailInput{email})).await{
         Ok(_) => {
-            return Err((StatusCode::BAD_REQUEST, Json("Esse e-mail pertence a outro usuário.".to_string())))
+            return Err(ErroApi::Conflito("Esse e-mail pertence a outro usuário.".to_string()))
         },
         Err(e) => {
             return Err(e)
@@ -678,23 +834,64 @@ pub struct IdInput{
         ("id" = String, Path, description = "ID do usuário"),
     )
 )]
-pub async fn busca_usuario_id(Query(params): Query<IdInput>)
-    -> Result<(StatusCode, Json<UsuarioReturn>), (StatusCode, Json<String>)>{
+pub async fn busca_usuario_id(State(pool): State<Pool>, usuario: UsuarioAutenticado, Query(params): Query<IdInput>)
+    -> Result<(StatusCode, Json<UsuarioReturn>), ErroApi>{
     if params.id.trim().is_empty(){
-        return Err((StatusCode::BAD_REQUEST, Json("Um ou mais campos estão vazios.".to_string())))
+        return Err(ErroApi::validacao("Um ou mais campos estão vazios."))
     }
-    let id = params.id.to_string();
-    let conn = &mut cria_conn()?;
+    verifica_usuario_ou_admin(&usuario, params.id.trim())?;
+
+    busca_dados_usuario(&pool, params).await
+}
+
+/// Busca os dados de qualquer usuário. Uso interno, sem verificação de sessão.
+pub async fn busca_dados_usuario(pool: &Pool, params: IdInput)
+    -> Result<(StatusCode, Json<UsuarioReturn>), ErroApi>{
+    if params.id.trim().is_empty(){
+        return Err(ErroApi::validacao("Um ou mais campos estão vazios."))
+    }
+    let id = params.id.trim().to_string();
+    let conn = &obtem_conn(pool).await?;
     match models::usuarios::busca_usuario_id(conn, id).await{
         Ok(usuario) => {
             return Ok((StatusCode::OK, Json(UsuarioReturn::from(usuario))))
         },
         Err(e) => {
-            return Err((StatusCode::INTERNAL_SERVER_ERROR, Json(e)))
+            return Err(e)
         }
     }
 }
 
+#[utoipa::path(
+    get,
+    tag = "Usuário",
+    path = "/busca_perfil_usuario/{id}",
+    description = "Busca o perfil público (ID e nome) de qualquer usuário.",
+    responses(
+        (
+            status = 200, 
+            description = "ID válido. Usuário encontrado.",
+            body = PerfilUsuarioReturn
+        ),
+        (
+            status = 404,
+            description = "O ID inserido não pertence a um usuário registrado no sistema."
+        ),
+        (
+            status = 400,
+            description = "Parâmetro inválido ou ausente."
+        ),
+    ),
+    params(
+        ("id" = String, Path, description = "ID do usuário"),
+    )
+)]
+pub async fn busca_perfil_usuario(State(pool): State<Pool>, Query(params): Query<IdInput>)
+    -> Result<(StatusCode, Json<PerfilUsuarioReturn>), ErroApi>{
+    let usuario = busca_dados_usuario(&pool, params).await?.1.0;
+    return Ok((StatusCode::OK, Json(PerfilUsuarioReturn::from(usuario))))
+}
+
 #[derive(Serialize, Deserialize, ToSchema)]
 pub struct AtualizaUsuarioInput{
     pub email_antigo: String,
@@ -716,8 +913,12 @@ pub struct AtualizaUsuarioInput{
             body = UserId       
         ),
         (
-            status = 404,
-            description = "O e-mail inserido não está registrado no sistema.\nOU a senha está incorreta.\nOU o documento pertence a outra pessoa.\nOU o e-mail pertence a outra pessoa."
+            status = 401,
+            description = "O e-mail inserido não está registrado no sistema OU a senha está incorreta."
+        ),
+        (
+            status = 409,
+            description = "O documento ou o e-mail pertence a outra pessoa."
         ),
         (
             status = 400,
@@ -726,34 +927,35 @@ pub struct AtualizaUsuarioInput{
     ),
     request_body = AtualizaUsuarioInput
 )]
-pub async fn atualiza_usuario(input: Json<AtualizaUsuarioInput>)
-    -> Result<(StatusCode, Json<UserId>), (StatusCode, Json<String>)>{
+pub async fn atualiza_usuario(State(pool): State<Pool>, usuario: UsuarioAutenticado, input: Json<AtualizaUsuarioInput>)
+    -> Result<(StatusCode, Json<UserId>), ErroApi>{
     if input.nome_novo.trim().is_empty() || input.documento_novo.trim().is_empty()
         || input.email_antigo.trim().is_empty() || input.senha.trim().is_empty()
         || input.email_novo.trim().is_empty(){
-        return Err((StatusCode::BAD_REQUEST, Json("Um ou mais campos estão vazios.".to_string())))
+        return Err(ErroApi::validacao("Um ou mais campos estão vazios."))
     }
     let _res1 = valida_email(Json(EmailInput{
         email: input.email_antigo.clone()
     })).await?;
-    let _res2 = realiza_login(Json(CredenciaisUsuario{
+    let idusuario = verifica_credenciais(&pool, CredenciaisUsuario{
         email: input.email_antigo.to_string(),
         senha: input.senha.to_string()
-    })).await?;
+    }).await?;
+    verifica_usuario(&usuario, &idusuario)?;
 
     let query: Query<EmailInput> = Query::from(axum::extract::Query(EmailInput{
         email: input.email_novo.clone()
     }));
-    match busca_usuario_email(query).await{
+    match busca_usuario_email(State(pool.clone()), query).await{
         Ok(_) => {
             if input.email_antigo != input.email_novo{
-                return Err((StatusCode::BAD_REQUEST, Json("Esse e-mail já pertence a outro usuário.".to_string())))
+                return Err(ErroApi::Conflito("Esse e-mail já pertence a outro usuário.".to_string()))
             }
         },
         Err(_) => {}
     }
 
-    let conn = &mut cria_conn()?;
+    let conn = &obtem_conn(&pool).await?;
     match crate::models::usuarios::atualiza_usuario(conn, input.0).await{
         Ok(idusuario) => {
             return Ok((StatusCode::OK, Json(UserId{
@@ -761,7 +963,7 @@ pub async fn atualiza_usuario(input: Json<AtualizaUsuarioInput>)
             })))
         },
         Err(e) => {
-            return Err((StatusCode::INTERNAL_SERVER_ERROR, Json(e)))
+            return Err(e)
         }
     }
 }
@@ -783,7 +985,7 @@ pub struct DocumentoInput{
             body = UsuarioReturn
         ),
         (
-            status = 500,
+            status = 404,
             description = "O documento inserido não pertence a um usuário registrado no sistema."
         ),
         (
@@ -795,19 +997,20 @@ pub struct DocumentoInput{
         ("documento" = DocumentoInput, Path, description = "Documento do usuário"),
     )
 )]
-pub async fn busca_usuario_documento(Query(input): Query<DocumentoInput>)
-    -> Result<(StatusCode, Json<UsuarioReturn>), (StatusCode, Json<String>)>{
+pub async fn busca_usuario_documento(State(pool): State<Pool>, usuario: UsuarioAutenticado, Query(input): Query<DocumentoInput>)
+    -> Result<(StatusCode, Json<UsuarioReturn>), ErroApi>{
     if input.documento.trim().is_empty(){
-        return Err((StatusCode::BAD_REQUEST, Json("Um ou mais campos estão vazios.".to_string())))
+        return Err(ErroApi::validacao("Um ou mais campos estão vazios."))
     }
     let documento = input.documento.trim().to_string();
-    let conn = &mut cria_conn()?;
+    let conn = &obtem_conn(&pool).await?;
     match models::usuarios::busca_usuario_documento(conn, documento).await{
-        Ok(usuario) => {
-            return Ok((StatusCode::OK, Json(UsuarioReturn::from(usuario))))
+        Ok(encontrado) => {
+            verifica_usuario_ou_admin(&usuario, &encontrado.idusuario)?;
+            return Ok((StatusCode::OK, Json(UsuarioReturn::from(encontrado))))
         },
         Err(e) => {
-            return Err((StatusCode::INTERNAL_SERVER_ERROR, Json(e)))
+            return Err(e)
         }
     }
 }
@@ -823,7 +1026,7 @@ pub async fn busca_usuario_documento(Query(input): Query<DocumentoInput>)
             description = "ID válido. Usuário deletado.",
         ),
         (
-            status = 500,
+            status = 404,
             description = "O ID inserido não pertence a um usuário registrado no sistema."
         ),
         (
@@ -835,29 +1038,30 @@ pub async fn busca_usuario_documento(Query(input): Query<DocumentoInput>)
         ("id" = String, Path, description = "ID do usuário"),
     )
 )]
-pub async fn deleta_usuario(Query(input): Query<IdInput>)
-    -> Result<StatusCode, (StatusCode, Json<String>)>{
+pub async fn deleta_usuario(usuario: UsuarioAutenticado, Query(input): Query<IdInput>)
+    -> Result<StatusCode, ErroApi>{
     if input.id.trim().is_empty(){
-        return Err((StatusCode::BAD_REQUEST, Json("Um ou mais campos estão vazios.".to_string())))
+        return Err(ErroApi::validacao("Um ou mais campos estão vazios."))
     }
     let id = input.id.trim().to_string();
+    verifica_usuario_ou_admin(&usuario, &id)?;
     match models::usuarios::deleta_usuario(id).await{
         Ok(_) => {
             return Ok(StatusCode::OK)
         },
         Err(e) => {
-            return Err((StatusCode::INTERNAL_SERVER_ERROR, Json(e)))
+            return Err(e)
         }
     }
 }
 
-pub async fn valida_email(input: Json<EmailInput>) -> Result<(StatusCode, Json<String>), (StatusCode, Json<String>)> {
+pub async fn valida_email(input: Json<EmailInput>) -> Result<(StatusCode, Json<String>), ErroApi> {
     match input.validate(){
          Ok(_) => {
              return Ok((StatusCode::OK, Json(input.0.email)))
          },
          Err(e) => {
-             return Err((StatusCode::BAD_REQUEST, Json(e.to_string())))
+             return Err(ErroApi::validacao_campo("email", &e.to_string()))
          }
      }
 }
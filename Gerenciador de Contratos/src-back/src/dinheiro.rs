@@ -0,0 +1,411 @@
+// Valores em reais. Um `Dinheiro` guarda um número inteiro de centavos, então somas e
+// comparações são exatas. No banco, ele é gravado nas colunas `NUMERIC(14, 2)`; no JSON, sai
+// como texto com duas casas ("1234.50"), para não passar por ponto flutuante, e entra como
+// texto ou número. Valores negativos são recusados na entrada, assim como os que não cabem na
+// coluna (`Dinheiro::MAXIMO`); com isso, somar e subtrair valores lidos da entrada ou do banco
+// não estoura o `i64`.
+//
+// Toda conta que pode gerar frações de centavo (valor por hora, percentuais, divisões) passa
+// por `multiplica`, que arredonda o resultado uma única vez, no fim: meio centavo ou mais vai
+// para o centavo de cima (longe do zero), e menos que isso é descartado. Valores recebidos com
+// mais de duas casas seguem a mesma regra. Os percentuais são um `Percentual`, guardado em
+// centésimos de ponto, e também nunca passam por ponto flutuante.
+
+use std::{fmt, iter::Sum, ops::{Add, AddAssign, Neg, Sub, SubAssign}, str::FromStr};
+
+use bigdecimal::{BigDecimal, RoundingMode, ToPrimitive};
+use diesel::{deserialize::{FromSql, FromSqlRow}, expression::AsExpression, pg::{Pg, PgValue}, serialize::{Output, ToSql}, sql_types::Numeric};
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+use utoipa::{openapi::{schema::{ObjectBuilder, SchemaFormat, Type}, RefOr, Schema}, PartialSchema, ToSchema};
+
+use crate::erros::ErroApi;
+
+#[derive(AsExpression, FromSqlRow, Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[diesel(sql_type = Numeric)]
+pub struct Dinheiro(i64);
+
+impl Dinheiro{
+    pub const ZERO: Dinheiro = Dinheiro(0);
+    /// O maior valor que cabe em `NUMERIC(14, 2)`: R$ 999.999.999.999,99.
+    pub const MAXIMO: Dinheiro = Dinheiro(99_999_999_999_999);
+
+    pub const fn de_centavos(centavos: i64) -> Dinheiro{
+        Dinheiro(centavos)
+    }
+
+    pub const fn de_reais(reais: i64) -> Dinheiro{
+        Dinheiro(reais * 100)
+    }
+
+    pub fn centavos(self) -> i64{
+        self.0
+    }
+
+    pub fn e_positivo(self) -> bool{
+        self.0 > 0
+    }
+
+    /// O valor vezes `numerador / denominador`, arredondado no centavo. Falha se o denominador
+    /// for zero ou se o resultado não couber em um `Dinheiro`.
+    pub fn multiplica(self, numerador: i64, denominador: i64) -> Result<Dinheiro, ErroApi>{
+        if denominador == 0{
+            return Err(ErroApi::Interno("Divisão de valor por zero.".to_string()))
+        }
+        let produto = self.0 as i128 * numerador as i128;
+        let denominador = denominador as i128;
+        let quociente = produto / denominador;
+        let resto = produto % denominador;
+        let arredonda = 2 * resto.abs() >= denominador.abs();
+        let sinal = if (produto < 0) != (denominador < 0) { -1 } else { 1 };
+        match i64::try_from(quociente + if arredonda { sinal } else { 0 }){
+            Ok(centavos) => {
+                return Ok(Dinheiro(centavos))
+            },
+            Err(_) => {
+                return Err(ErroApi::validacao("O valor calculado é grande demais."))
+            }
+        }
+    }
+
+    /// `percentual` do valor.
+    pub fn percentual(self, percentual: Percentual) -> Result<Dinheiro, ErroApi>{
+        self.percentual_proporcional(percentual, 1, 1)
+    }
+
+    /// `percentual` do valor, vezes `numerador / denominador`, com um só arredondamento no fim.
+    pub fn percentual_proporcional(self, percentual: Percentual, numerador: i64, denominador: i64) -> Result<Dinheiro, ErroApi>{
+        match (percentual.0.checked_mul(numerador), 10_000i64.checked_mul(denominador)){
+            (Some(numerador), Some(denominador)) => {
+                return self.multiplica(numerador, denominador)
+            },
+            _ => {
+                return Err(ErroApi::validacao("O valor calculado é grande demais."))
+            }
+        }
+    }
+
+    /// Divide o valor em `partes` partes que somam o valor original. Os centavos que sobram da
+    /// divisão ficam nas primeiras partes.
+    pub fn divide(self, partes: usize) -> Vec<Dinheiro>{
+        if partes == 0{
+            return vec![]
+        }
+        let base = self.0 / partes as i64;
+        let sobra = (self.0 % partes as i64) as usize;
+        (0..partes).map(|i| Dinheiro(base + if i < sobra { 1 } else { 0 })).collect()
+    }
+
+    fn de_bigdecimal(valor: &BigDecimal) -> Option<Dinheiro>{
+        (valor * BigDecimal::from(100)).with_scale_round(0, RoundingMode::HalfUp).to_i64().map(Dinheiro)
+    }
+}
+
+/// Formata um valor em reais: 1234,5 vira "R$ 1.234,50".
+pub fn formata_reais(valor: Dinheiro) -> String{
+    let centavos = valor.centavos().unsigned_abs();
+    let inteiro = (centavos / 100).to_string();
+    let mut milhares = String::new();
+    for (i, c) in inteiro.chars().enumerate(){
+        if i > 0 && (inteiro.len() - i).is_multiple_of(3){
+            milhares.push('.');
+        }
+        milhares.push(c);
+    }
+    let sinal = if valor < Dinheiro::ZERO { "-" } else { "" };
+    format!("{}R$ {},{:02}", sinal, milhares, centavos % 100)
+}
+
+/// "1234.50": o formato das colunas `NUMERIC` e do campo de valor do BR Code.
+impl fmt::Display for Dinheiro{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result{
+        let sinal = if self.0 < 0 { "-" } else { "" };
+        write!(f, "{}{}.{:02}", sinal, self.0.unsigned_abs() / 100, self.0.unsigned_abs() % 100)
+    }
+}
+
+/// Lê valores como "1234.5", "-3" ou "0.125", com ponto decimal. Recusa valores que, em módulo,
+/// passam de `Dinheiro::MAXIMO`.
+impl FromStr for Dinheiro{
+    type Err = ErroApi;
+
+    fn from_str(texto: &str) -> Result<Self, Self::Err>{
+        let invalido = || ErroApi::validacao_campo("valor", "Valor em reais inválido.");
+        let valor = BigDecimal::from_str(texto.trim()).map_err(|_| invalido())?;
+        let valor = Dinheiro::de_bigdecimal(&valor).ok_or_else(invalido)?;
+        if valor.0.unsigned_abs() > Dinheiro::MAXIMO.0.unsigned_abs(){
+            return Err(ErroApi::validacao_campo("valor", "Valor em reais grande demais."))
+        }
+        return Ok(valor)
+    }
+}
+
+impl Add for Dinheiro{
+    type Output = Dinheiro;
+
+    fn add(self, outro: Dinheiro) -> Dinheiro{
+        Dinheiro(self.0 + outro.0)
+    }
+}
+
+impl AddAssign for Dinheiro{
+    fn add_assign(&mut self, outro: Dinheiro){
+        self.0 += outro.0;
+    }
+}
+
+impl Sub for Dinheiro{
+    type Output = Dinheiro;
+
+    fn sub(self, outro: Dinheiro) -> Dinheiro{
+        Dinheiro(self.0 - outro.0)
+    }
+}
+
+impl SubAssign for Dinheiro{
+    fn sub_assign(&mut self, outro: Dinheiro){
+        self.0 -= outro.0;
+    }
+}
+
+impl Neg for Dinheiro{
+    type Output = Dinheiro;
+
+    fn neg(self) -> Dinheiro{
+        Dinheiro(-self.0)
+    }
+}
+
+impl Sum for Dinheiro{
+    fn sum<I: Iterator<Item = Dinheiro>>(valores: I) -> Dinheiro{
+        valores.fold(Dinheiro::ZERO, Add::add)
+    }
+}
+
+impl<'a> Sum<&'a Dinheiro> for Dinheiro{
+    fn sum<I: Iterator<Item = &'a Dinheiro>>(valores: I) -> Dinheiro{
+        valores.copied().sum()
+    }
+}
+
+impl ToSql<Numeric, Pg> for Dinheiro{
+    fn to_sql<'b>(&'b self, out: &mut Output<'b, '_, Pg>) -> diesel::serialize::Result{
+        let valor = BigDecimal::new(self.0.into(), 2);
+        <BigDecimal as ToSql<Numeric, Pg>>::to_sql(&valor, &mut out.reborrow())
+    }
+}
+
+impl FromSql<Numeric, Pg> for Dinheiro{
+    fn from_sql(bytes: PgValue<'_>) -> diesel::deserialize::Result<Self>{
+        let valor = <BigDecimal as FromSql<Numeric, Pg>>::from_sql(bytes)?;
+        match Dinheiro::de_bigdecimal(&valor){
+            Some(dinheiro) => {
+                return Ok(dinheiro)
+            },
+            None => {
+                return Err(format!("Valor fora do limite de Dinheiro: {}", valor).into())
+            }
+        }
+    }
+}
+
+impl Serialize for Dinheiro{
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error>{
+        serializer.collect_str(self)
+    }
+}
+
+fn nao_negativo<T: PartialOrd + Default, E: de::Error>(valor: T, mensagem: &str) -> Result<T, E>{
+    if valor < T::default(){
+        return Err(E::custom(mensagem))
+    }
+    return Ok(valor)
+}
+
+struct VisitanteDinheiro;
+
+impl<'de> de::Visitor<'de> for VisitanteDinheiro{
+    type Value = Dinheiro;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result{
+        write!(f, "um valor em reais")
+    }
+
+    fn visit_i64<E: de::Error>(self, valor: i64) -> Result<Dinheiro, E>{
+        let valor = valor.checked_mul(100).map(Dinheiro).ok_or_else(|| E::custom("Valor em reais grande demais."))?;
+        if valor > Dinheiro::MAXIMO{
+            return Err(E::custom("Valor em reais grande demais."))
+        }
+        nao_negativo(valor, "Valor em reais negativo.")
+    }
+
+    fn visit_u64<E: de::Error>(self, valor: u64) -> Result<Dinheiro, E>{
+        let valor = i64::try_from(valor).map_err(|_| E::custom("Valor em reais grande demais."))?;
+        self.visit_i64(valor)
+    }
+
+    // Parte do texto do número, e não do f64, para que "1.005" seja lido como 1,005 e
+    // arredondado para 1,01.
+    fn visit_f64<E: de::Error>(self, valor: f64) -> Result<Dinheiro, E>{
+        if !valor.is_finite(){
+            return Err(E::custom("Valor em reais inválido."))
+        }
+        self.visit_str(&valor.to_string())
+    }
+
+    fn visit_str<E: de::Error>(self, texto: &str) -> Result<Dinheiro, E>{
+        let valor = Dinheiro::from_str(texto).map_err(|e| match e{
+            ErroApi::Validacao{mensagem, ..} => E::custom(mensagem),
+            _ => E::custom("Valor em reais inválido.")
+        })?;
+        nao_negativo(valor, "Valor em reais negativo.")
+    }
+}
+
+impl<'de> Deserialize<'de> for Dinheiro{
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Dinheiro, D::Error>{
+        deserializer.deserialize_any(VisitanteDinheiro)
+    }
+}
+
+impl PartialSchema for Dinheiro{
+    fn schema() -> RefOr<Schema>{
+        RefOr::T(Schema::Object(ObjectBuilder::new()
+            .schema_type(Type::String)
+            .format(Some(SchemaFormat::Custom("decimal".to_string())))
+            .description(Some("Valor em reais, com duas casas decimais, de no máximo 999999999999.99. Na entrada, também é aceito como número, e nunca negativo."))
+            .examples(["1234.50"])
+            .build()))
+    }
+}
+
+impl ToSchema for Dinheiro{}
+
+/// Percentual guardado em centésimos de ponto: 2,55% é `Percentual(255)`. No banco, fica nas
+/// colunas `NUMERIC(5, 2)`. Textos e números com mais de duas casas são arredondados como os
+/// valores em reais.
+#[derive(AsExpression, FromSqlRow, Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[diesel(sql_type = Numeric)]
+pub struct Percentual(i64);
+
+impl Percentual{
+    pub const ZERO: Percentual = Percentual(0);
+    pub const CEM: Percentual = Percentual(10_000);
+
+    pub const fn de_pontos_base(pontos: i64) -> Percentual{
+        Percentual(pontos)
+    }
+
+    pub const fn de_inteiro(percentual: i64) -> Percentual{
+        Percentual(percentual * 100)
+    }
+
+    pub fn pontos_base(self) -> i64{
+        self.0
+    }
+
+    pub fn e_positivo(self) -> bool{
+        self.0 > 0
+    }
+}
+
+/// "2.55", "2.5" ou "2", sem zeros sobrando, para caber nas mensagens.
+impl fmt::Display for Percentual{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result{
+        let sinal = if self.0 < 0 { "-" } else { "" };
+        let (inteiro, fracao) = (self.0.unsigned_abs() / 100, self.0.unsigned_abs() % 100);
+        match fracao{
+            0 => write!(f, "{}{}", sinal, inteiro),
+            _ if fracao % 10 == 0 => write!(f, "{}{}.{}", sinal, inteiro, fracao / 10),
+            _ => write!(f, "{}{}.{:02}", sinal, inteiro, fracao)
+        }
+    }
+}
+
+/// Lê percentuais como "2.55" ou "5", com ponto decimal e sem o "%".
+impl FromStr for Percentual{
+    type Err = ErroApi;
+
+    fn from_str(texto: &str) -> Result<Self, Self::Err>{
+        let invalido = || ErroApi::validacao_campo("percentual", "Percentual inválido.");
+        let valor = BigDecimal::from_str(texto.trim()).map_err(|_| invalido())?;
+        (valor * BigDecimal::from(100)).with_scale_round(0, RoundingMode::HalfUp).to_i64().map(Percentual).ok_or_else(invalido)
+    }
+}
+
+impl ToSql<Numeric, Pg> for Percentual{
+    fn to_sql<'b>(&'b self, out: &mut Output<'b, '_, Pg>) -> diesel::serialize::Result{
+        let valor = BigDecimal::new(self.0.into(), 2);
+        <BigDecimal as ToSql<Numeric, Pg>>::to_sql(&valor, &mut out.reborrow())
+    }
+}
+
+impl FromSql<Numeric, Pg> for Percentual{
+    fn from_sql(bytes: PgValue<'_>) -> diesel::deserialize::Result<Self>{
+        let valor = <BigDecimal as FromSql<Numeric, Pg>>::from_sql(bytes)?;
+        match (valor.clone() * BigDecimal::from(100)).with_scale_round(0, RoundingMode::HalfUp).to_i64(){
+            Some(pontos) => {
+                return Ok(Percentual(pontos))
+            },
+            None => {
+                return Err(format!("Percentual fora do limite: {}", valor).into())
+            }
+        }
+    }
+}
+
+impl Serialize for Percentual{
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error>{
+        serializer.collect_str(self)
+    }
+}
+
+struct VisitantePercentual;
+
+impl<'de> de::Visitor<'de> for VisitantePercentual{
+    type Value = Percentual;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result{
+        write!(f, "um percentual")
+    }
+
+    fn visit_i64<E: de::Error>(self, valor: i64) -> Result<Percentual, E>{
+        let valor = valor.checked_mul(100).map(Percentual).ok_or_else(|| E::custom("Percentual grande demais."))?;
+        nao_negativo(valor, "Percentual negativo.")
+    }
+
+    fn visit_u64<E: de::Error>(self, valor: u64) -> Result<Percentual, E>{
+        let valor = i64::try_from(valor).map_err(|_| E::custom("Percentual grande demais."))?;
+        self.visit_i64(valor)
+    }
+
+    fn visit_f64<E: de::Error>(self, valor: f64) -> Result<Percentual, E>{
+        if !valor.is_finite(){
+            return Err(E::custom("Percentual inválido."))
+        }
+        self.visit_str(&valor.to_string())
+    }
+
+    fn visit_str<E: de::Error>(self, texto: &str) -> Result<Percentual, E>{
+        let valor = Percentual::from_str(texto).map_err(|_| E::custom("Percentual inválido."))?;
+        nao_negativo(valor, "Percentual negativo.")
+    }
+}
+
+impl<'de> Deserialize<'de> for Percentual{
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Percentual, D::Error>{
+        deserializer.deserialize_any(VisitantePercentual)
+    }
+}
+
+impl PartialSchema for Percentual{
+    fn schema() -> RefOr<Schema>{
+        RefOr::T(Schema::Object(ObjectBuilder::new()
+            .schema_type(Type::String)
+            .format(Some(SchemaFormat::Custom("decimal".to_string())))
+            .description(Some("Percentual com até duas casas decimais, sem o \"%\". Na entrada, também é aceito como número, e nunca negativo."))
+            .examples(["2.55"])
+            .build()))
+    }
+}
+
+impl ToSchema for Percentual{}
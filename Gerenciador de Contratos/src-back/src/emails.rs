@@ -0,0 +1,286 @@
+// Envio de e-mails. O transporte é escolhido pelo `.env` com `EMAIL_TRANSPORTE`:
+// - `smtp` (padrão): servidor SMTP definido por `SMTP_HOST`, `SMTP_PORTA` e `SMTP_TLS`;
+// - `arquivo`: grava cada mensagem como um `.eml` na pasta `EMAIL_PASTA`, para desenvolvimento;
+// - `memoria`: guarda as mensagens na memória do processo. É o transporte usado nos testes.
+
+use std::{env, path::PathBuf, sync::{Arc, Mutex, OnceLock}};
+
+use dotenv::dotenv;
+use lettre::{message::{header::ContentType, Attachment, MultiPart}, transport::smtp::{authentication::Credentials, client::{Tls, TlsParameters}}, Message, SmtpTransport, Transport};
+
+use crate::erros::ErroApi;
+
+pub const REMETENTE_PADRAO: &str = "MaqExpress <gerenciadordecontratosgdc@gmail.com>";
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Anexo{
+    pub nome: String,
+    /// Tipo MIME do arquivo, como `application/pdf`.
+    pub tipo: String,
+    pub conteudo: Vec<u8>
+}
+
+/// Uma mensagem pronta para envio. O corpo vai em HTML e em texto puro, para os leitores
+/// de e-mail que não exibem HTML.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Email{
+    pub destinatario: String,
+    pub assunto: String,
+    pub html: String,
+    pub texto: String,
+    pub anexos: Vec<Anexo>
+}
+
+/// O conteúdo de um e-mail, ainda sem destinatário. É o resultado de um modelo de
+/// `controllers::envia_emails` já preenchido.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConteudoEmail{
+    pub assunto: String,
+    pub html: String,
+    pub texto: String,
+    pub anexos: Vec<Anexo>
+}
+
+impl ConteudoEmail{
+    pub fn para(self, destinatario: &str) -> Email{
+        Email{
+            destinatario: destinatario.to_string(),
+            assunto: self.assunto,
+            html: self.html,
+            texto: self.texto,
+            anexos: self.anexos
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SegurancaSmtp{
+    /// Conexão sem criptografia que passa a usar TLS com o comando STARTTLS (porta 587).
+    Starttls,
+    /// TLS desde o início da conexão (porta 465).
+    Tls,
+    /// Sem criptografia. Só para servidores locais, como o MailHog.
+    Nenhuma
+}
+
+#[derive(Debug, Clone)]
+pub enum TransporteEmail{
+    Smtp{
+        host: String,
+        porta: u16,
+        seguranca: SegurancaSmtp,
+        usuario: Option<String>,
+        senha: Option<String>,
+        remetente: String
+    },
+    Arquivo{
+        pasta: PathBuf,
+        remetente: String
+    },
+    Memoria(Arc<Mutex<Vec<Email>>>)
+}
+
+impl TransporteEmail{
+    pub fn memoria() -> Self{
+        TransporteEmail::Memoria(Arc::new(Mutex::new(Vec::new())))
+    }
+
+    /// Monta o transporte a partir das variáveis de configuração. `variavel` devolve o valor
+    /// de uma variável, ou `None` se ela não estiver definida.
+    pub fn de_variaveis(variavel: impl Fn(&str) -> Option<String>) -> Result<Self, ErroApi>{
+        let remetente = variavel("EMAIL_REMETENTE").unwrap_or(REMETENTE_PADRAO.to_string());
+        if remetente.parse::<lettre::message::Mailbox>().is_err(){
+            return Err(ErroApi::Interno(format!("EMAIL_REMETENTE inválido: {}", remetente)))
+        }
+
+        match variavel("EMAIL_TRANSPORTE").unwrap_or("smtp".to_string()).trim(){
+            "smtp" => {
+                let seguranca = match variavel("SMTP_TLS").unwrap_or("starttls".to_string()).trim(){
+                    "starttls" => SegurancaSmtp::Starttls,
+                    "tls" => SegurancaSmtp::Tls,
+                    "nenhum" => SegurancaSmtp::Nenhuma,
+                    outro => {
+                        return Err(ErroApi::Interno(format!("SMTP_TLS inválido: {}. Use starttls, tls ou nenhum.", outro)))
+                    }
+                };
+                let porta_padrao = match seguranca{
+                    SegurancaSmtp::Starttls => 587,
+                    SegurancaSmtp::Tls => 465,
+                    SegurancaSmtp::Nenhuma => 25
+                };
+                let porta = match variavel("SMTP_PORTA"){
+                    Some(porta) => match porta.trim().parse::<u16>(){
+                        Ok(porta) => porta,
+                        Err(_) => {
+                            return Err(ErroApi::Interno(format!("SMTP_PORTA inválida: {}", porta)))
+                        }
+                    },
+                    None => porta_padrao
+                };
+                return Ok(TransporteEmail::Smtp{
+                    host: variavel("SMTP_HOST").unwrap_or("smtp.gmail.com".to_string()),
+                    porta,
+                    seguranca,
+                    usuario: variavel("smtp_username"),
+                    senha: variavel("smtp_password"),
+                    remetente
+                })
+            },
+            "arquivo" => {
+                return Ok(TransporteEmail::Arquivo{
+                    pasta: PathBuf::from(variavel("EMAIL_PASTA").unwrap_or("emails".to_string())),
+                    remetente
+                })
+            },
+            "memoria" => {
+                return Ok(TransporteEmail::memoria())
+            },
+            outro => {
+                return Err(ErroApi::Interno(format!("EMAIL_TRANSPORTE inválido: {}. Use smtp, arquivo ou memoria.", outro)))
+            }
+        }
+    }
+
+    /// Mensagens guardadas pelo transporte em memória. Nos outros transportes, a lista é vazia.
+    pub fn enviados(&self) -> Vec<Email>{
+        match self{
+            TransporteEmail::Memoria(enviados) => enviados.lock().unwrap().clone(),
+            _ => Vec::new()
+        }
+    }
+
+    pub async fn envia(&self, email: Email) -> Result<(), ErroApi>{
+        let remetente = match self{
+            TransporteEmail::Smtp{remetente, ..} | TransporteEmail::Arquivo{remetente, ..} => remetente.clone(),
+            TransporteEmail::Memoria(enviados) => {
+                valida_destinatario(&email.destinatario)?;
+                enviados.lock().unwrap().push(email);
+                return Ok(())
+            }
+        };
+        let mensagem = monta_mensagem(&remetente, &email)?;
+
+        let transporte = self.clone();
+        // O lettre envia de forma bloqueante, então o envio sai do executor assíncrono.
+        let res = tokio::task::spawn_blocking(move || transporte.envia_mensagem(&mensagem)).await;
+        match res{
+            Ok(res) => {
+                return res
+            },
+            Err(e) => {
+                return Err(ErroApi::Interno(format!("Erro ao enviar o e-mail: {}", e)))
+            }
+        }
+    }
+
+    fn envia_mensagem(&self, mensagem: &Message) -> Result<(), ErroApi>{
+        match self{
+            TransporteEmail::Smtp{host, porta, seguranca, usuario, senha, ..} => {
+                let parametros = || TlsParameters::new(host.clone())
+                    .map_err(|e| ErroApi::Interno(format!("Erro ao configurar o TLS do SMTP: {}", e)));
+                let tls = match seguranca{
+                    SegurancaSmtp::Starttls => Tls::Required(parametros()?),
+                    SegurancaSmtp::Tls => Tls::Wrapper(parametros()?),
+                    SegurancaSmtp::Nenhuma => Tls::None
+                };
+                let mut construtor = SmtpTransport::builder_dangerous(host.as_str()).port(*porta).tls(tls);
+                if let (Some(usuario), Some(senha)) = (usuario, senha){
+                    construtor = construtor.credentials(Credentials::new(usuario.clone(), senha.clone()));
+                }
+
+                match construtor.build().send(mensagem){
+                    Ok(_) => {
+                        return Ok(())
+                    },
+                    Err(e) => {
+                        return Err(ErroApi::ServicoExterno(format!("Erro ao enviar o e-mail: {}", e)))
+                    }
+                }
+            },
+            TransporteEmail::Arquivo{pasta, ..} => {
+                let arquivo = pasta.join(format!("{}-{:08x}.eml",
+                    chrono::Utc::now().format("%Y%m%d%H%M%S%f"), rand::random::<u32>()));
+                let res = std::fs::create_dir_all(pasta)
+                    .and_then(|_| std::fs::write(&arquivo, mensagem.formatted()));
+                match res{
+                    Ok(_) => {
+                        return Ok(())
+                    },
+                    Err(e) => {
+                        return Err(ErroApi::Interno(format!("Erro ao gravar o e-mail em {}: {}", arquivo.display(), e)))
+                    }
+                }
+            },
+            TransporteEmail::Memoria(_) => {
+                return Ok(())
+            }
+        }
+    }
+}
+
+fn valida_destinatario(destinatario: &str) -> Result<lettre::message::Mailbox, ErroApi>{
+    match destinatario.parse(){
+        Ok(destinatario) => {
+            return Ok(destinatario)
+        },
+        Err(_) => {
+            return Err(ErroApi::validacao_campo("email", "O e-mail do destinatário é inválido."))
+        }
+    }
+}
+
+fn monta_mensagem(remetente: &str, email: &Email) -> Result<Message, ErroApi>{
+    let remetente = match remetente.parse(){
+        Ok(remetente) => remetente,
+        Err(e) => {
+            return Err(ErroApi::Interno(format!("Remetente de e-mail inválido: {}", e)))
+        }
+    };
+    let mut corpo = MultiPart::alternative_plain_html(email.texto.clone(), email.html.clone());
+    if !email.anexos.is_empty(){
+        corpo = MultiPart::mixed().multipart(corpo);
+        for anexo in &email.anexos{
+            let tipo = match ContentType::parse(&anexo.tipo){
+                Ok(tipo) => tipo,
+                Err(_) => {
+                    return Err(ErroApi::Interno(format!("Tipo de anexo inválido: {}", anexo.tipo)))
+                }
+            };
+            corpo = corpo.singlepart(Attachment::new(anexo.nome.clone()).body(anexo.conteudo.clone(), tipo));
+        }
+    }
+    let mensagem = Message::builder()
+        .from(remetente)
+        .to(valida_destinatario(&email.destinatario)?)
+        .subject(email.assunto.clone())
+        .multipart(corpo);
+    match mensagem{
+        Ok(mensagem) => {
+            return Ok(mensagem)
+        },
+        Err(e) => {
+            return Err(ErroApi::Interno(format!("Erro ao montar o e-mail: {}", e)))
+        }
+    }
+}
+
+/// Transporte usado pela API, lido do `.env` na primeira chamada. Nos testes é sempre o
+/// transporte em memória, para que nenhum e-mail saia da máquina.
+pub fn transporte_email() -> Result<&'static TransporteEmail, ErroApi>{
+    static TRANSPORTE: OnceLock<TransporteEmail> = OnceLock::new();
+    if let Some(transporte) = TRANSPORTE.get(){
+        return Ok(transporte)
+    }
+
+    let transporte = if cfg!(test){
+        TransporteEmail::memoria()
+    } else {
+        dotenv().ok();
+        TransporteEmail::de_variaveis(|nome| env::var(nome).ok())?
+    };
+    return Ok(TRANSPORTE.get_or_init(|| transporte))
+}
+
+pub async fn envia_email(email: Email) -> Result<(), ErroApi>{
+    transporte_email()?.envia(email).await
+}
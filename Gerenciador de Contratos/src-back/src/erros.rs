@@ -0,0 +1,180 @@
+use axum::{http::{header::RETRY_AFTER, StatusCode}, response::{IntoResponse, Response}, Json};
+use diesel::result::{DatabaseErrorKind, Error as DieselError};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+/// Erro padrão da API. Cada variante corresponde a um status HTTP e a um código fixo
+/// devolvido no corpo da resposta.
+#[derive(Debug, thiserror::Error)]
+pub enum ErroApi{
+    #[error("{0}")]
+    NaoEncontrado(String),
+    #[error("{mensagem}")]
+    Validacao{
+        mensagem: String,
+        campo: Option<String>
+    },
+    #[error("{0}")]
+    Conflito(String),
+    #[error("{0}")]
+    NaoAutorizado(String),
+    #[error("{0}")]
+    Proibido(String),
+    /// Login de uma conta que ainda não confirmou o código de ativação.
+    #[error("{0}")]
+    ContaNaoAtivada(String),
+    #[error("{0}")]
+    ContaSuspensa(String),
+    /// Login com o Google em uma conta criada com senha. O vínculo só vale depois do código
+    /// enviado para o e-mail da conta.
+    #[error("{0}")]
+    VinculoPendente(String),
+    /// Requisições demais em pouco tempo. `segundos` vai no cabeçalho `Retry-After`.
+    #[error("{mensagem}")]
+    LimiteExcedido{
+        mensagem: String,
+        segundos: u64
+    },
+    /// Falha em um serviço do qual a API depende (Google, servidor de e-mail etc.).
+    #[error("{0}")]
+    ServicoExterno(String),
+    /// O banco de dados ou outro recurso essencial está temporariamente fora do ar.
+    #[error("{0}")]
+    Indisponivel(String),
+    #[error("{0}")]
+    Interno(String)
+}
+
+/// Corpo JSON devolvido em todas as respostas de erro.
+#[derive(Serialize, Deserialize, ToSchema, Debug)]
+pub struct CorpoErro{
+    pub code: String,
+    pub message: String,
+    pub field: Option<String>
+}
+
+impl ErroApi{
+    pub fn validacao(mensagem: &str) -> Self{
+        ErroApi::Validacao{
+            mensagem: mensagem.to_string(),
+            campo: None
+        }
+    }
+
+    pub fn validacao_campo(campo: &str, mensagem: &str) -> Self{
+        ErroApi::Validacao{
+            mensagem: mensagem.to_string(),
+            campo: Some(campo.to_string())
+        }
+    }
+
+    pub fn status(&self) -> StatusCode{
+        match self{
+            ErroApi::NaoEncontrado(_) => StatusCode::NOT_FOUND,
+            ErroApi::Validacao{..} => StatusCode::BAD_REQUEST,
+            ErroApi::Conflito(_) => StatusCode::CONFLICT,
+            ErroApi::NaoAutorizado(_) => StatusCode::UNAUTHORIZED,
+            ErroApi::Proibido(_) => StatusCode::FORBIDDEN,
+            ErroApi::ContaNaoAtivada(_) => StatusCode::FORBIDDEN,
+            ErroApi::ContaSuspensa(_) => StatusCode::FORBIDDEN,
+            ErroApi::VinculoPendente(_) => StatusCode::CONFLICT,
+            ErroApi::LimiteExcedido{..} => StatusCode::TOO_MANY_REQUESTS,
+            ErroApi::ServicoExterno(_) => StatusCode::BAD_GATEWAY,
+            ErroApi::Indisponivel(_) => StatusCode::SERVICE_UNAVAILABLE,
+            ErroApi::Interno(_) => StatusCode::INTERNAL_SERVER_ERROR
+        }
+    }
+
+    pub fn codigo(&self) -> &'static str{
+        match self{
+            ErroApi::NaoEncontrado(_) => "nao_encontrado",
+            ErroApi::Validacao{..} => "validacao",
+            ErroApi::Conflito(_) => "conflito",
+            ErroApi::NaoAutorizado(_) => "nao_autorizado",
+            ErroApi::Proibido(_) => "proibido",
+            ErroApi::ContaNaoAtivada(_) => "conta_nao_ativada",
+            ErroApi::ContaSuspensa(_) => "conta_suspensa",
+            ErroApi::VinculoPendente(_) => "vinculo_pendente",
+            ErroApi::LimiteExcedido{..} => "limite_excedido",
+            ErroApi::ServicoExterno(_) => "servico_externo",
+            ErroApi::Indisponivel(_) => "indisponivel",
+            ErroApi::Interno(_) => "interno"
+        }
+    }
+
+    pub fn corpo(&self) -> CorpoErro{
+        let field = match self{
+            ErroApi::Validacao{campo, ..} => campo.clone(),
+            _ => None
+        };
+        CorpoErro{
+            code: self.codigo().to_string(),
+            message: self.to_string(),
+            field
+        }
+    }
+}
+
+impl IntoResponse for ErroApi{
+    fn into_response(self) -> Response{
+        if let ErroApi::Interno(e) = &self{
+            tracing::error!("Erro interno: {}", e);
+        }
+        if let ErroApi::LimiteExcedido{segundos, ..} = &self{
+            let segundos = segundos.to_string();
+            return (self.status(), [(RETRY_AFTER, segundos)], Json(self.corpo())).into_response()
+        }
+        (self.status(), Json(self.corpo())).into_response()
+    }
+}
+
+impl From<DieselError> for ErroApi{
+    fn from(e: DieselError) -> Self{
+        match e{
+            DieselError::NotFound => {
+                ErroApi::NaoEncontrado("Registro não encontrado.".to_string())
+            },
+            // A mensagem do banco cita as restrições e os valores, então fica só no log.
+            DieselError::DatabaseError(DatabaseErrorKind::UniqueViolation, info) => {
+                tracing::warn!("Registro duplicado em {}: {}", info.table_name().unwrap_or("?"), info.message());
+                ErroApi::Conflito("Já existe um registro com esses dados.".to_string())
+            },
+            DieselError::DatabaseError(DatabaseErrorKind::ForeignKeyViolation, info) => {
+                tracing::warn!("Referência inválida em {}: {}", info.table_name().unwrap_or("?"), info.message());
+                ErroApi::Validacao{
+                    mensagem: "O registro referenciado não existe ou ainda está em uso.".to_string(),
+                    campo: info.column_name().map(|c| c.to_string())
+                }
+            },
+            DieselError::DatabaseError(DatabaseErrorKind::NotNullViolation, info) => {
+                tracing::warn!("Campo obrigatório vazio em {}: {}", info.table_name().unwrap_or("?"), info.message());
+                ErroApi::Validacao{
+                    mensagem: "Um campo obrigatório não foi informado.".to_string(),
+                    campo: info.column_name().map(|c| c.to_string())
+                }
+            },
+            DieselError::DatabaseError(DatabaseErrorKind::CheckViolation, info) => {
+                tracing::warn!("Valor recusado em {}: {}", info.table_name().unwrap_or("?"), info.message());
+                ErroApi::Validacao{
+                    mensagem: "Um ou mais campos têm valores inválidos.".to_string(),
+                    campo: info.column_name().map(|c| c.to_string())
+                }
+            },
+            e => {
+                ErroApi::Interno(e.to_string())
+            }
+        }
+    }
+}
+
+impl From<deadpool_diesel::PoolError> for ErroApi{
+    fn from(e: deadpool_diesel::PoolError) -> Self{
+        ErroApi::Indisponivel(e.to_string())
+    }
+}
+
+impl From<deadpool_diesel::InteractError> for ErroApi{
+    fn from(e: deadpool_diesel::InteractError) -> Self{
+        ErroApi::Interno(e.to_string())
+    }
+}
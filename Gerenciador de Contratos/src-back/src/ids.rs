@@ -0,0 +1,193 @@
+// Geração de identificadores.
+//
+// As chaves primárias são UUIDv7: começam pelo horário de criação, então ficam em ordem
+// no índice e não revelam nada do registro. Os IDs públicos (`idpublico` de usuários e
+// máquinas), que aparecem para o usuário e nos links, têm 8 caracteres do alfabeto
+// Crockford base32, sem letras que se confundem com números.
+//
+// Antes, as chaves eram hashes bcrypt (`$2b$12$...`). A migração `gerar_ids_uuid` trocou
+// essas chaves por UUIDv7 e guardou as antigas em `ids_legados`; o middleware
+// `resolve_ids_legados` troca os IDs antigos que os clientes ainda enviarem pelos novos.
+
+use std::collections::HashMap;
+
+use axum::{body::{to_bytes, Body}, extract::{Request, State}, http::{header::CONTENT_TYPE, Uri}, middleware::Next, response::Response};
+use rand::{rngs::OsRng, Rng};
+use uuid::Uuid;
+
+use crate::{controllers::{obtem_conn, Pool}, erros::ErroApi, models::ids_legados::busca_ids_novos};
+
+/// Alfabeto Crockford base32: sem I, L, O e U.
+const ALFABETO_ID_PUBLICO: &[u8] = b"0123456789ABCDEFGHJKMNPQRSTVWXYZ";
+pub const TAMANHO_ID_PUBLICO: usize = 8;
+/// Quantos IDs públicos sorteados já em uso são aceitos antes de desistir.
+const TENTATIVAS_ID_PUBLICO: usize = 5;
+/// Os corpos JSON maiores que isso não são lidos para trocar IDs antigos.
+const TAMANHO_MAXIMO_CORPO: usize = 2 * 1024 * 1024;
+
+/// Nova chave primária: um UUIDv7 no formato com hífens.
+pub fn gera_id() -> String{
+    Uuid::now_v7().to_string()
+}
+
+pub fn gera_id_publico() -> String{
+    (0..TAMANHO_ID_PUBLICO)
+        .map(|_| ALFABETO_ID_PUBLICO[OsRng.gen_range(0..ALFABETO_ID_PUBLICO.len())] as char)
+        .collect()
+}
+
+/// Sorteia IDs públicos até achar um que `existe` diga estar livre. A coluna também tem
+/// um índice único, que barra duas requisições que sorteiem o mesmo ID ao mesmo tempo.
+pub fn gera_id_publico_unico(mut existe: impl FnMut(&str) -> Result<bool, ErroApi>) -> Result<String, ErroApi>{
+    for _ in 0..TENTATIVAS_ID_PUBLICO{
+        let id = gera_id_publico();
+        if !existe(&id)?{
+            return Ok(id)
+        }
+    }
+    return Err(ErroApi::Interno("Não foi possível gerar um ID público livre.".to_string()))
+}
+
+/// Deixa o ID público digitado pelo usuário no formato guardado: maiúsculo, com I e L
+/// lidos como 1 e O lido como 0. Os IDs numéricos antigos não mudam.
+pub fn normaliza_id_publico(id: &str) -> String{
+    id.trim()
+        .chars()
+        .map(|c| match c.to_ascii_uppercase(){
+            'I' | 'L' => '1',
+            'O' => '0',
+            c => c
+        })
+        .collect()
+}
+
+/// Se o ID é uma chave antiga, gerada com bcrypt: `$2a$`, `$2b$` ou `$2y$`, o custo e
+/// 53 caracteres de sal e hash.
+pub fn e_id_legado(id: &str) -> bool{
+    let bytes = id.as_bytes();
+    bytes.len() == 60
+        && bytes.starts_with(b"$2")
+        && matches!(bytes[2], b'a' | b'b' | b'y')
+        && bytes[3] == b'$'
+        && bytes[4].is_ascii_digit() && bytes[5].is_ascii_digit()
+        && bytes[6] == b'$'
+        && bytes[7..].iter().all(|b| b.is_ascii_alphanumeric() || *b == b'.' || *b == b'/')
+}
+
+fn decodifica_componente(texto: &str) -> Option<String>{
+    let bytes = texto.as_bytes();
+    let mut decodificado = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len(){
+        match bytes[i]{
+            b'%' => {
+                let hexa = std::str::from_utf8(bytes.get(i + 1..i + 3)?).ok()?;
+                decodificado.push(u8::from_str_radix(hexa, 16).ok()?);
+                i += 3;
+            },
+            b'+' => {
+                decodificado.push(b' ');
+                i += 1;
+            },
+            byte => {
+                decodificado.push(byte);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8(decodificado).ok()
+}
+
+/// IDs antigos nos valores da query string. Devolve o valor como veio na URL e decodificado.
+pub fn ids_legados_query(query: &str) -> Vec<(String, String)>{
+    query.split('&')
+        .filter_map(|par| par.split_once('=').map(|(_, valor)| valor))
+        .filter_map(|valor| Some((valor.to_string(), decodifica_componente(valor)?)))
+        .filter(|(_, id)| e_id_legado(id))
+        .collect()
+}
+
+/// IDs antigos nas strings de um corpo JSON. Os hashes bcrypt não têm aspas nem barras
+/// invertidas, então basta olhar o texto entre cada par de aspas.
+pub fn ids_legados_json(corpo: &str) -> Vec<String>{
+    corpo.split('"')
+        .filter(|trecho| e_id_legado(trecho))
+        .map(str::to_string)
+        .collect()
+}
+
+/// Troca os IDs antigos do corpo JSON pelos novos do mapa.
+pub fn troca_ids_json(corpo: &str, ids: &HashMap<String, String>) -> String{
+    let mut corpo = corpo.to_string();
+    for (legado, novo) in ids{
+        corpo = corpo.replace(&format!("\"{}\"", legado), &format!("\"{}\"", novo));
+    }
+    return corpo
+}
+
+fn troca_ids_uri(uri: &Uri, encontrados: &[(String, String)], ids: &HashMap<String, String>) -> Option<Uri>{
+    let mut query = uri.query()?.to_string();
+    for (original, legado) in encontrados{
+        if let Some(novo) = ids.get(legado){
+            query = query.replace(&format!("={}", original), &format!("={}", novo));
+        }
+    }
+    format!("{}?{}", uri.path(), query).parse().ok()
+}
+
+async fn mapa_ids_legados(pool: &Pool, mut legados: Vec<String>) -> Result<HashMap<String, String>, ErroApi>{
+    legados.sort();
+    legados.dedup();
+    if legados.is_empty(){
+        return Ok(HashMap::new())
+    }
+    let conn = &obtem_conn(pool).await?;
+    return busca_ids_novos(conn, legados).await
+}
+
+/// Middleware que troca os IDs antigos da query string e do corpo JSON pelos UUIDv7 que
+/// os substituíram. IDs antigos sem correspondência seguem como vieram e dão `404` no handler.
+pub async fn resolve_ids_legados(State(pool): State<Pool>, mut req: Request, next: Next)
+    -> Result<Response, ErroApi>{
+    let na_query = req.uri().query().map(ids_legados_query).unwrap_or_default();
+    let json = req.headers().get(CONTENT_TYPE)
+        .and_then(|tipo| tipo.to_str().ok())
+        .is_some_and(|tipo| tipo.starts_with("application/json"));
+
+    // Outros corpos, como os uploads de imagem, seguem sem ser lidos.
+    if !json{
+        if na_query.is_empty(){
+            return Ok(next.run(req).await)
+        }
+        let legados = na_query.iter().map(|(_, id)| id.clone()).collect();
+        let ids = mapa_ids_legados(&pool, legados).await?;
+        if let Some(uri) = troca_ids_uri(req.uri(), &na_query, &ids){
+            *req.uri_mut() = uri;
+        }
+        return Ok(next.run(req).await)
+    }
+
+    let (mut partes, corpo) = req.into_parts();
+    let corpo = match to_bytes(corpo, TAMANHO_MAXIMO_CORPO).await{
+        Ok(corpo) => corpo,
+        Err(_) => {
+            return Err(ErroApi::validacao("O corpo da requisição é grande demais."))
+        }
+    };
+    let texto = std::str::from_utf8(&corpo).ok();
+    let no_corpo = texto.map(ids_legados_json).unwrap_or_default();
+    if na_query.is_empty() && no_corpo.is_empty(){
+        return Ok(next.run(Request::from_parts(partes, Body::from(corpo))).await)
+    }
+
+    let legados = na_query.iter().map(|(_, id)| id.clone()).chain(no_corpo).collect();
+    let ids = mapa_ids_legados(&pool, legados).await?;
+    if let Some(uri) = troca_ids_uri(&partes.uri, &na_query, &ids){
+        partes.uri = uri;
+    }
+    let corpo = match texto{
+        Some(texto) => Body::from(troca_ids_json(texto, &ids)),
+        None => Body::from(corpo)
+    };
+    return Ok(next.run(Request::from_parts(partes, corpo)).await)
+}
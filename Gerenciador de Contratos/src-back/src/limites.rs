@@ -0,0 +1,422 @@
+// Limite de requisições das rotas de autenticação (login, cadastro e códigos por e-mail).
+// `CamadaLimite` é uma camada do tower que conta as requisições por IP e por conta (o e-mail
+// ou o ID do usuário enviado no corpo) e responde 429 com `Retry-After` quando o limite
+// estoura. Nas rotas de senha e código, as tentativas erradas também contam: depois de
+// algumas, cada nova tentativa precisa esperar um tempo que dobra a cada erro, e depois de
+// mais algumas a conta (ou o IP) fica bloqueada por um tempo.
+//
+// Os contadores ficam em um `ArmazenamentoLimites`. O padrão é `ArmazenamentoMemoria`, que
+// vale só para um processo; com mais de uma instância da API, implemente o trait sobre um
+// armazenamento compartilhado.
+
+use std::{collections::HashMap, env, future::Future, net::SocketAddr, pin::Pin, sync::{Arc, Mutex}, task::{Context, Poll}, time::{Duration, Instant}};
+
+use axum::{body::{to_bytes, Body}, extract::{ConnectInfo, Request}, http::StatusCode, response::{IntoResponse, Response}, Json};
+use dotenv::dotenv;
+use serde::Deserialize;
+use tower::{Layer, Service};
+
+use crate::erros::ErroApi;
+
+/// Maior corpo aceito nas rotas limitadas. Todas recebem JSONs pequenos.
+const TAMANHO_MAXIMO_CORPO: usize = 64 * 1024;
+
+/// Acima dessa quantidade de chaves, o armazenamento em memória descarta as que já venceram.
+const CHAVES_ANTES_LIMPEZA: usize = 10_000;
+
+/// Tentativas erradas seguidas de uma chave.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Falhas{
+    pub quantidade: u32,
+    pub ultima: Instant
+}
+
+pub trait ArmazenamentoLimites: Send + Sync{
+    /// Soma uma requisição ao contador da chave. Devolve o total na janela atual e quanto falta
+    /// para ela acabar.
+    fn conta_requisicao(&self, chave: &str, janela: Duration, agora: Instant) -> (u32, Duration);
+
+    fn busca_falhas(&self, chave: &str) -> Option<Falhas>;
+
+    /// Soma uma falha à chave. Se a última falha tiver mais de `validade`, a contagem recomeça.
+    fn registra_falha(&self, chave: &str, validade: Duration, agora: Instant) -> Falhas;
+
+    fn limpa_falhas(&self, chave: &str);
+}
+
+#[derive(Default)]
+pub struct ArmazenamentoMemoria{
+    /// Fim da janela atual e total de requisições nela.
+    contadores: Mutex<HashMap<String, (Instant, u32)>>,
+    falhas: Mutex<HashMap<String, (Falhas, Duration)>>
+}
+
+impl ArmazenamentoLimites for ArmazenamentoMemoria{
+    fn conta_requisicao(&self, chave: &str, janela: Duration, agora: Instant) -> (u32, Duration){
+        let mut contadores = self.contadores.lock().unwrap();
+        if contadores.len() > CHAVES_ANTES_LIMPEZA{
+            contadores.retain(|_, (fim, _)| *fim > agora);
+        }
+
+        let contador = contadores.entry(chave.to_string()).or_insert((agora + janela, 0));
+        if contador.0 <= agora{
+            *contador = (agora + janela, 0);
+        }
+        contador.1 += 1;
+        return (contador.1, contador.0.saturating_duration_since(agora))
+    }
+
+    fn busca_falhas(&self, chave: &str) -> Option<Falhas>{
+        self.falhas.lock().unwrap().get(chave).map(|(falhas, _)| *falhas)
+    }
+
+    fn registra_falha(&self, chave: &str, validade: Duration, agora: Instant) -> Falhas{
+        let mut todas = self.falhas.lock().unwrap();
+        if todas.len() > CHAVES_ANTES_LIMPEZA{
+            todas.retain(|_, (falhas, validade)| agora.saturating_duration_since(falhas.ultima) < *validade);
+        }
+
+        let (falhas, _) = todas.entry(chave.to_string())
+            .or_insert((Falhas{quantidade: 0, ultima: agora}, validade));
+        if agora.saturating_duration_since(falhas.ultima) >= validade{
+            falhas.quantidade = 0;
+        }
+        falhas.quantidade += 1;
+        falhas.ultima = agora;
+        return *falhas
+    }
+
+    fn limpa_falhas(&self, chave: &str){
+        self.falhas.lock().unwrap().remove(chave);
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConfiguracaoLimites{
+    /// Requisições aceitas de um mesmo IP em cada `janela_ip`, por rota.
+    pub requisicoes_ip: u32,
+    pub janela_ip: Duration,
+    /// Requisições aceitas para uma mesma conta em cada `janela_conta`, por rota.
+    pub requisicoes_conta: u32,
+    pub janela_conta: Duration,
+    /// A partir de quantas falhas seguidas cada nova tentativa precisa esperar. A espera começa
+    /// em `atraso_inicial` e dobra a cada falha.
+    pub falhas_atraso: u32,
+    pub atraso_inicial: Duration,
+    /// Quantas falhas seguidas bloqueiam a conta (ou o IP) por `duracao_bloqueio`.
+    pub falhas_bloqueio: u32,
+    pub duracao_bloqueio: Duration,
+    /// Usa o primeiro IP do `X-Forwarded-For`. Só deve ser ligado atrás de um proxy confiável.
+    pub confia_proxy: bool
+}
+
+impl Default for ConfiguracaoLimites{
+    fn default() -> Self{
+        ConfiguracaoLimites{
+            requisicoes_ip: 20,
+            janela_ip: Duration::from_secs(60),
+            requisicoes_conta: 10,
+            janela_conta: Duration::from_secs(60),
+            falhas_atraso: 3,
+            atraso_inicial: Duration::from_secs(1),
+            falhas_bloqueio: 10,
+            duracao_bloqueio: Duration::from_secs(15 * 60),
+            confia_proxy: false
+        }
+    }
+}
+
+fn le_numero(variavel: &impl Fn(&str) -> Option<String>, nome: &str, padrao: u64) -> Result<u64, ErroApi>{
+    match variavel(nome){
+        Some(valor) => match valor.trim().parse::<u64>(){
+            Ok(numero) if numero > 0 => {
+                return Ok(numero)
+            },
+            _ => {
+                return Err(ErroApi::Interno(format!("{} inválido: {}. Use um número inteiro positivo.", nome, valor)))
+            }
+        },
+        None => {
+            return Ok(padrao)
+        }
+    }
+}
+
+impl ConfiguracaoLimites{
+    /// Lê a configuração das variáveis `LIMITE_*`. Janelas e durações são em segundos; as
+    /// variáveis ausentes ficam com o valor padrão.
+    pub fn de_variaveis(variavel: impl Fn(&str) -> Option<String>) -> Result<Self, ErroApi>{
+        let padrao = ConfiguracaoLimites::default();
+        let segundos = |nome: &str, padrao: Duration| -> Result<Duration, ErroApi>{
+            Ok(Duration::from_secs(le_numero(&variavel, nome, padrao.as_secs())?))
+        };
+        let quantidade = |nome: &str, padrao: u32| -> Result<u32, ErroApi>{
+            Ok(le_numero(&variavel, nome, padrao as u64)?.min(u32::MAX as u64) as u32)
+        };
+
+        let confia_proxy = match variavel("LIMITE_CONFIA_PROXY").as_deref().map(str::trim){
+            None | Some("false") => false,
+            Some("true") => true,
+            Some(outro) => {
+                return Err(ErroApi::Interno(format!("LIMITE_CONFIA_PROXY inválido: {}. Use true ou false.", outro)))
+            }
+        };
+
+        return Ok(ConfiguracaoLimites{
+            requisicoes_ip: quantidade("LIMITE_IP_REQUISICOES", padrao.requisicoes_ip)?,
+            janela_ip: segundos("LIMITE_IP_JANELA", padrao.janela_ip)?,
+            requisicoes_conta: quantidade("LIMITE_CONTA_REQUISICOES", padrao.requisicoes_conta)?,
+            janela_conta: segundos("LIMITE_CONTA_JANELA", padrao.janela_conta)?,
+            falhas_atraso: quantidade("LIMITE_FALHAS_ATRASO", padrao.falhas_atraso)?,
+            atraso_inicial: segundos("LIMITE_ATRASO_INICIAL", padrao.atraso_inicial)?,
+            falhas_bloqueio: quantidade("LIMITE_FALHAS_BLOQUEIO", padrao.falhas_bloqueio)?,
+            duracao_bloqueio: segundos("LIMITE_DURACAO_BLOQUEIO", padrao.duracao_bloqueio)?,
+            confia_proxy
+        })
+    }
+}
+
+fn segundos_espera(espera: Duration) -> u64{
+    espera.as_secs() + if espera.subsec_nanos() > 0 { 1 } else { 0 }
+}
+
+fn limite_excedido(mensagem: &str, espera: Duration) -> ErroApi{
+    ErroApi::LimiteExcedido{
+        mensagem: mensagem.to_string(),
+        segundos: segundos_espera(espera).max(1)
+    }
+}
+
+/// Aplica a `ConfiguracaoLimites` sobre um armazenamento. É barato de clonar: os clones
+/// compartilham os contadores.
+#[derive(Clone)]
+pub struct Limitador{
+    armazenamento: Arc<dyn ArmazenamentoLimites>,
+    configuracao: ConfiguracaoLimites
+}
+
+impl Limitador{
+    pub fn new(armazenamento: Arc<dyn ArmazenamentoLimites>, configuracao: ConfiguracaoLimites) -> Self{
+        Limitador{
+            armazenamento,
+            configuracao
+        }
+    }
+
+    pub fn memoria(configuracao: ConfiguracaoLimites) -> Self{
+        Limitador::new(Arc::new(ArmazenamentoMemoria::default()), configuracao)
+    }
+
+    /// Limitador usado pela API, com a configuração do `.env` e os contadores em memória.
+    pub fn de_ambiente() -> Result<Self, ErroApi>{
+        dotenv().ok();
+        let configuracao = ConfiguracaoLimites::de_variaveis(|nome| env::var(nome).ok())?;
+        return Ok(Limitador::memoria(configuracao))
+    }
+
+    pub fn configuracao(&self) -> &ConfiguracaoLimites{
+        &self.configuracao
+    }
+
+    fn chaves_falhas(escopo: &str, ip: &str, conta: Option<&str>) -> Vec<String>{
+        let mut chaves = vec![format!("{}:ip:{}", escopo, ip)];
+        if let Some(conta) = conta{
+            chaves.push(format!("{}:conta:{}", escopo, conta));
+        }
+        chaves
+    }
+
+    /// Quanto a chave ainda precisa esperar por causa das falhas, se precisar.
+    fn espera_falhas(&self, falhas: Falhas, agora: Instant) -> Option<(Duration, bool)>{
+        let configuracao = &self.configuracao;
+        let bloqueada = falhas.quantidade >= configuracao.falhas_bloqueio;
+        let espera = if bloqueada{
+            configuracao.duracao_bloqueio
+        } else if falhas.quantidade >= configuracao.falhas_atraso{
+            let expoente = (falhas.quantidade - configuracao.falhas_atraso).min(20);
+            configuracao.atraso_inicial.saturating_mul(2_u32.pow(expoente)).min(configuracao.duracao_bloqueio)
+        } else {
+            return None
+        };
+
+        let passado = agora.saturating_duration_since(falhas.ultima);
+        match espera.checked_sub(passado){
+            Some(restante) if !restante.is_zero() => Some((restante, bloqueada)),
+            _ => None
+        }
+    }
+
+    /// Confere se a requisição pode seguir e, se puder, a soma aos contadores do IP e da conta.
+    pub fn verifica(&self, escopo: &str, ip: &str, conta: Option<&str>, agora: Instant) -> Result<(), ErroApi>{
+        for chave in Limitador::chaves_falhas(escopo, ip, conta){
+            let falhas = match self.armazenamento.busca_falhas(&chave){
+                Some(falhas) => falhas,
+                None => continue
+            };
+            match self.espera_falhas(falhas, agora){
+                Some((espera, true)) => {
+                    return Err(limite_excedido(&format!("Muitas tentativas sem sucesso. Tente novamente em {} minutos.",
+                        segundos_espera(espera).div_ceil(60)), espera))
+                },
+                Some((espera, false)) => {
+                    return Err(limite_excedido(&format!("Aguarde {} segundos antes de tentar novamente.",
+                        segundos_espera(espera)), espera))
+                },
+                None => {}
+            }
+        }
+
+        let configuracao = &self.configuracao;
+        let (total, restante) = self.armazenamento.conta_requisicao(&format!("{}:ip:{}", escopo, ip), configuracao.janela_ip, agora);
+        if total > configuracao.requisicoes_ip{
+            return Err(limite_excedido("Muitas requisições deste endereço. Tente novamente mais tarde.", restante))
+        }
+        if let Some(conta) = conta{
+            let (total, restante) = self.armazenamento.conta_requisicao(&format!("{}:conta:{}", escopo, conta), configuracao.janela_conta, agora);
+            if total > configuracao.requisicoes_conta{
+                return Err(limite_excedido("Muitas requisições para esta conta. Tente novamente mais tarde.", restante))
+            }
+        }
+        return Ok(())
+    }
+
+    /// Registra uma tentativa errada para o IP e para a conta.
+    pub fn registra_falha(&self, escopo: &str, ip: &str, conta: Option<&str>, agora: Instant){
+        for chave in Limitador::chaves_falhas(escopo, ip, conta){
+            self.armazenamento.registra_falha(&chave, self.configuracao.duracao_bloqueio, agora);
+        }
+    }
+
+    /// Zera as falhas da conta. As do IP só vencem com o tempo, para que o acerto em uma conta
+    /// não libere tentativas em outras.
+    pub fn registra_sucesso(&self, escopo: &str, conta: Option<&str>){
+        if let Some(conta) = conta{
+            self.armazenamento.limpa_falhas(&format!("{}:conta:{}", escopo, conta));
+        }
+    }
+}
+
+/// Campos do corpo que identificam a conta.
+#[derive(Deserialize)]
+struct ContaRequisicao{
+    email: Option<String>,
+    idusuario: Option<String>
+}
+
+fn extrai_conta(corpo: &[u8]) -> Option<String>{
+    let conta = Json::<ContaRequisicao>::from_bytes(corpo).ok()?.0;
+    conta.email.or(conta.idusuario)
+        .map(|conta| conta.trim().to_lowercase())
+        .filter(|conta| !conta.is_empty())
+}
+
+fn extrai_ip(req: &Request, confia_proxy: bool) -> String{
+    if confia_proxy{
+        let encaminhado = req.headers().get("x-forwarded-for")
+            .and_then(|valor| valor.to_str().ok())
+            .and_then(|valor| valor.split(',').next())
+            .map(str::trim)
+            .filter(|ip| !ip.is_empty());
+        if let Some(ip) = encaminhado{
+            return ip.to_string()
+        }
+    }
+    match req.extensions().get::<ConnectInfo<SocketAddr>>(){
+        Some(ConnectInfo(endereco)) => endereco.ip().to_string(),
+        None => "desconhecido".to_string()
+    }
+}
+
+/// Camada do tower que aplica o `Limitador` a uma rota. O `escopo` separa os contadores de
+/// cada rota.
+#[derive(Clone)]
+pub struct CamadaLimite{
+    limitador: Limitador,
+    escopo: &'static str,
+    conta_falhas: bool
+}
+
+impl CamadaLimite{
+    pub fn new(limitador: Limitador, escopo: &'static str) -> Self{
+        CamadaLimite{
+            limitador,
+            escopo,
+            conta_falhas: false
+        }
+    }
+
+    /// Conta as respostas 400 e 401 como tentativas erradas, com espera progressiva e
+    /// bloqueio. Uma resposta de sucesso zera as falhas da conta.
+    pub fn com_falhas(mut self) -> Self{
+        self.conta_falhas = true;
+        self
+    }
+}
+
+impl<S> Layer<S> for CamadaLimite{
+    type Service = ServicoLimite<S>;
+
+    fn layer(&self, interno: S) -> Self::Service{
+        ServicoLimite{
+            interno,
+            camada: self.clone()
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct ServicoLimite<S>{
+    interno: S,
+    camada: CamadaLimite
+}
+
+impl<S> Service<Request> for ServicoLimite<S>
+where
+    S: Service<Request, Response = Response> + Clone + Send + 'static,
+    S::Future: Send + 'static,
+{
+    type Response = Response;
+    type Error = S::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Response, S::Error>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>>{
+        self.interno.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: Request) -> Self::Future{
+        // O serviço que passou pelo `poll_ready` atende esta requisição; o clone fica para a próxima.
+        let clone = self.interno.clone();
+        let mut interno = std::mem::replace(&mut self.interno, clone);
+        let camada = self.camada.clone();
+
+        Box::pin(async move {
+            let ip = extrai_ip(&req, camada.limitador.configuracao().confia_proxy);
+            let (partes, corpo) = req.into_parts();
+            let corpo = match to_bytes(corpo, TAMANHO_MAXIMO_CORPO).await{
+                Ok(corpo) => corpo,
+                Err(_) => {
+                    return Ok(ErroApi::validacao("O corpo da requisição é grande demais.").into_response())
+                }
+            };
+            let conta = extrai_conta(&corpo);
+
+            if let Err(e) = camada.limitador.verifica(camada.escopo, &ip, conta.as_deref(), Instant::now()){
+                return Ok(e.into_response())
+            }
+
+            let resposta = interno.call(Request::from_parts(partes, Body::from(corpo))).await?;
+            if camada.conta_falhas{
+                match resposta.status(){
+                    StatusCode::BAD_REQUEST | StatusCode::UNAUTHORIZED => {
+                        camada.limitador.registra_falha(camada.escopo, &ip, conta.as_deref(), Instant::now());
+                    },
+                    status if status.is_success() => {
+                        camada.limitador.registra_sucesso(camada.escopo, conta.as_deref());
+                    },
+                    _ => {}
+                }
+            }
+            return Ok(resposta)
+        })
+    }
+}
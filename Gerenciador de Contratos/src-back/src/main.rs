@@ -1,4 +1,14 @@
 pub mod controllers;
+pub mod emails;
+pub mod dinheiro;
+pub mod erros;
+pub mod ids;
+pub mod limites;
+pub mod oidc;
+pub mod pix;
+pub mod precos;
+pub mod senhas;
+pub mod pdf;
 pub mod models;
 pub mod tests;
 pub mod routes;
@@ -7,7 +17,42 @@ pub mod schema;
 #[tokio::main]
  async fn main() {
      tracing_subscriber::fmt::init();
-     let app = crate::routes::cria_rotas();
+     let pool = crate::controllers::cria_pool().expect("Erro ao criar o pool de conexões com o banco de dados");
+
+     // As migrações pendentes são aplicadas em toda inicialização.
+     // Com `migra`, o servidor só aplica as migrações e encerra.
+     let conn = crate::controllers::obtem_conn(&pool).await.expect("Erro ao conectar ao banco de dados");
+     let versoes = crate::models::migracoes::aplica_migracoes(&conn).await.expect("Erro ao aplicar as migrações");
+     for versao in versoes{
+         tracing::info!("Migração aplicada: {}", versao);
+     }
+     drop(conn);
+     if std::env::args().nth(1).as_deref() == Some("migra"){
+         return
+     }
+
+     let pool_expiracao = pool.clone();
+     tokio::spawn(async move {
+         let mut intervalo = tokio::time::interval(std::time::Duration::from_secs(60 * 60));
+         loop {
+             intervalo.tick().await;
+             if let Err(e) = crate::controllers::solicitacoes_contratos::expira_solicitacoes(&pool_expiracao).await{
+                 tracing::error!("Erro ao expirar as solicitações: {}", e);
+             }
+             if let Err(e) = crate::controllers::contratos::encerra_contratos_terminados(&pool_expiracao).await{
+                 tracing::error!("Erro ao encerrar os contratos terminados: {}", e);
+             }
+             if let Err(e) = crate::controllers::contratos::avisa_contratos_terminando(&pool_expiracao).await{
+                 tracing::error!("Erro ao avisar os contratos perto do fim: {}", e);
+             }
+             if let Err(e) = crate::controllers::faturas::vence_faturas(&pool_expiracao).await{
+                 tracing::error!("Erro ao marcar as faturas vencidas: {}", e);
+             }
+         }
+     });
+
+     let app = crate::routes::cria_rotas(pool);
      let listener = tokio::net::TcpListener::bind("0.0.0.0:3003").await.unwrap();
-     axum::serve(listener, app).await.unwrap();
+     // O endereço de quem conecta é usado pelo limite de requisições das rotas de autenticação.
+     axum::serve(listener, app.into_make_service_with_connect_info::<std::net::SocketAddr>()).await.unwrap();
  }
\ No newline at end of file
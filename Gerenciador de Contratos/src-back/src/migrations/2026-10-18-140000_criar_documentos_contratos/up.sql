@@ -0,0 +1,8 @@
+-- Your SQL goes here
+CREATE TABLE documentos_contratos(
+    idcontrato VARCHAR(64) PRIMARY KEY REFERENCES contratos(idcontrato) ON DELETE CASCADE,
+    versaomodelo VARCHAR(16) NOT NULL,
+    hashdocumento VARCHAR(64) NOT NULL,
+    documento BYTEA NOT NULL,
+    datageracao TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
\ No newline at end of file
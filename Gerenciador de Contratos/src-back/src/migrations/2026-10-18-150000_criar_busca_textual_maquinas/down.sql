@@ -0,0 +1,4 @@
+-- This file should undo anything in `up.sql`
+DROP INDEX IF EXISTS maquinas_documento_busca_idx;
+DROP FUNCTION IF EXISTS documento_busca_maquina(VARCHAR, VARCHAR, VARCHAR);
+DROP TEXT SEARCH CONFIGURATION IF EXISTS portugues_sem_acento;
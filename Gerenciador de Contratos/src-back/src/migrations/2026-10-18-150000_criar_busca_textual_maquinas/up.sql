@@ -0,0 +1,21 @@
+-- Your SQL goes here
+CREATE EXTENSION IF NOT EXISTS unaccent;
+
+-- Configuração em português que ignora acentos: "máquina" e "maquina" geram o mesmo lexema.
+CREATE TEXT SEARCH CONFIGURATION portugues_sem_acento (COPY = pg_catalog.portuguese);
+ALTER TEXT SEARCH CONFIGURATION portugues_sem_acento
+    ALTER MAPPING FOR hword, hword_part, word WITH unaccent, portuguese_stem;
+
+-- Documento de busca de uma máquina. O nome também entra sem espaços, para que
+-- "retro escavadeira" e "retroescavadeira" se encontrem.
+CREATE FUNCTION documento_busca_maquina(nome VARCHAR, categoria VARCHAR, descricao VARCHAR)
+RETURNS tsvector
+LANGUAGE SQL IMMUTABLE PARALLEL SAFE AS $$
+    SELECT setweight(to_tsvector('public.portugues_sem_acento', coalesce(nome, '')), 'A')
+        || setweight(to_tsvector('public.portugues_sem_acento', replace(coalesce(nome, ''), ' ', '')), 'A')
+        || setweight(to_tsvector('public.portugues_sem_acento', coalesce(categoria, '')), 'B')
+        || setweight(to_tsvector('public.portugues_sem_acento', coalesce(descricao, '')), 'C')
+$$;
+
+CREATE INDEX maquinas_documento_busca_idx ON maquinas
+    USING GIN (documento_busca_maquina(nome, categoria, descricao));
@@ -0,0 +1,23 @@
+-- Your SQL goes here
+CREATE TABLE reservas_maquinas(
+    idreserva VARCHAR(64) PRIMARY KEY,
+    idmaquina VARCHAR(64) NOT NULL,
+    idsolicitacao VARCHAR(64) NOT NULL UNIQUE REFERENCES solicitacoes_contratos(idsolicitacao) ON DELETE CASCADE,
+    datainicio TIMESTAMP NOT NULL,
+    datafim TIMESTAMP NOT NULL,
+    CONSTRAINT periodo_valido CHECK (datafim > datainicio)
+);
+
+CREATE INDEX reservas_maquinas_periodo_idx ON reservas_maquinas (idmaquina, datainicio, datafim);
+
+-- As solicitações já existentes passam a reservar a máquina a partir da data da solicitação.
+INSERT INTO reservas_maquinas (idreserva, idmaquina, idsolicitacao, datainicio, datafim)
+SELECT md5('reserva' || idsolicitacao), idmaquina, idsolicitacao, datasolicitacao,
+    datasolicitacao + prazolocacao * CASE medidatempolocacao
+        WHEN 'Horas' THEN INTERVAL '1 hour'
+        WHEN 'Dias' THEN INTERVAL '1 day'
+        WHEN 'Semanas' THEN INTERVAL '1 week'
+        WHEN 'Meses' THEN INTERVAL '1 month'
+    END
+FROM solicitacoes_contratos
+WHERE prazolocacao > 0 AND medidatempolocacao IN ('Horas', 'Dias', 'Semanas', 'Meses');
@@ -0,0 +1,3 @@
+-- This file should undo anything in `up.sql`
+DROP TABLE historico_solicitacoes;
+ALTER TABLE solicitacoes_contratos DROP CONSTRAINT statussolicitacao_valido;
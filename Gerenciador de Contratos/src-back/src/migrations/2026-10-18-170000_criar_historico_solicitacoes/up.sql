@@ -0,0 +1,26 @@
+-- Your SQL goes here
+-- Até aqui o status era texto livre. Valores fora do ciclo de vida passam a contar como cancelados.
+UPDATE solicitacoes_contratos SET statussolicitacao = 'Solicitação cancelada'
+WHERE statussolicitacao NOT IN ('Aguardando aprovação', 'Solicitação aprovada', 'Solicitação recusada',
+    'Solicitação cancelada', 'Solicitação expirada');
+
+ALTER TABLE solicitacoes_contratos ADD CONSTRAINT statussolicitacao_valido CHECK (statussolicitacao IN (
+    'Aguardando aprovação', 'Solicitação aprovada', 'Solicitação recusada',
+    'Solicitação cancelada', 'Solicitação expirada'));
+
+CREATE TABLE historico_solicitacoes(
+    idhistorico VARCHAR(64) PRIMARY KEY,
+    idsolicitacao VARCHAR(64) NOT NULL REFERENCES solicitacoes_contratos(idsolicitacao) ON DELETE CASCADE,
+    statusanterior VARCHAR(64),
+    statusnovo VARCHAR(64) NOT NULL,
+    papelator VARCHAR(16) NOT NULL,
+    idator VARCHAR(64),
+    datatransicao TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX historico_solicitacoes_idsolicitacao_idx ON historico_solicitacoes (idsolicitacao, datatransicao);
+
+-- O status atual das solicitações existentes vira o primeiro registro do histórico.
+INSERT INTO historico_solicitacoes (idhistorico, idsolicitacao, statusanterior, statusnovo, papelator, idator, datatransicao)
+SELECT md5('historico' || idsolicitacao), idsolicitacao, NULL, statussolicitacao, 'sistema', NULL, datasolicitacao
+FROM solicitacoes_contratos;
@@ -0,0 +1,6 @@
+-- This file should undo anything in `up.sql`
+DROP INDEX contratos_idlocatario_idx;
+DROP INDEX contratos_idlocador_idx;
+ALTER TABLE contratos DROP CONSTRAINT statuscontrato_valido;
+ALTER TABLE contratos DROP COLUMN dataencerramento;
+ALTER TABLE contratos DROP COLUMN motivoencerramento;
@@ -0,0 +1,11 @@
+-- Your SQL goes here
+ALTER TABLE contratos ADD COLUMN motivoencerramento TEXT;
+ALTER TABLE contratos ADD COLUMN dataencerramento TIMESTAMP;
+
+UPDATE contratos SET statuscontrato = 'Ativo'
+WHERE statuscontrato NOT IN ('Ativo', 'Concluído', 'Rescindido');
+
+ALTER TABLE contratos ADD CONSTRAINT statuscontrato_valido CHECK (statuscontrato IN ('Ativo', 'Concluído', 'Rescindido'));
+
+CREATE INDEX contratos_idlocador_idx ON contratos (idlocador, statuscontrato);
+CREATE INDEX contratos_idlocatario_idx ON contratos (idlocatario, statuscontrato);
@@ -0,0 +1,3 @@
+-- Your SQL goes here
+-- Quando as partes foram avisadas de que o contrato está perto do fim. Volta a ser nulo na renovação.
+ALTER TABLE contratos ADD COLUMN dataavisotermino TIMESTAMP;
@@ -0,0 +1,2 @@
+-- This file should undo anything in `up.sql`
+ALTER TABLE usuarios DROP CONSTRAINT status_usuario_valido;
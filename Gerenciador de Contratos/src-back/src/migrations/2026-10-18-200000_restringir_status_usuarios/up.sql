@@ -0,0 +1,6 @@
+-- Your SQL goes here
+-- Contas criadas antes da ativação por código continuam ativas.
+UPDATE usuarios SET status = 'Ativo'
+WHERE status NOT IN ('Pendente', 'Ativo', 'Suspenso');
+
+ALTER TABLE usuarios ADD CONSTRAINT status_usuario_valido CHECK (status IN ('Pendente', 'Ativo', 'Suspenso'));
@@ -0,0 +1,9 @@
+-- This file should undo anything in `up.sql`
+DROP INDEX idx_codigos_recuperacao_idusuario;
+ALTER TABLE codigos_recuperacao DROP COLUMN dataultimatentativa;
+ALTER TABLE codigos_recuperacao DROP COLUMN tentativas;
+
+-- Os hashes não cabem na coluna antiga.
+DELETE FROM codigos_recuperacao;
+ALTER TABLE codigos_recuperacao ALTER COLUMN hashcodigo TYPE VARCHAR(6);
+ALTER TABLE codigos_recuperacao RENAME COLUMN hashcodigo TO codigo;
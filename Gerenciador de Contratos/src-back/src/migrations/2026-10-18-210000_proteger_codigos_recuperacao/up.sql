@@ -0,0 +1,11 @@
+-- Your SQL goes here
+-- Os códigos passam a ser guardados só como hash. Os que estavam em texto puro deixam de valer.
+UPDATE codigos_recuperacao SET status = 'Invalidado' WHERE status = 'Não utilizado';
+ALTER TABLE codigos_recuperacao RENAME COLUMN codigo TO hashcodigo;
+ALTER TABLE codigos_recuperacao ALTER COLUMN hashcodigo TYPE VARCHAR(128);
+
+-- Tentativas erradas, usadas para bloquear o usuário por um tempo.
+ALTER TABLE codigos_recuperacao ADD COLUMN tentativas INTEGER NOT NULL DEFAULT 0;
+ALTER TABLE codigos_recuperacao ADD COLUMN dataultimatentativa TIMESTAMP;
+
+CREATE INDEX idx_codigos_recuperacao_idusuario ON codigos_recuperacao (idusuario, status);
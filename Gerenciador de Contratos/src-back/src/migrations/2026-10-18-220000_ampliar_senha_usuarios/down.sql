@@ -0,0 +1,4 @@
+-- This file should undo anything in `up.sql`
+-- Os hashes Argon2id não cabem na coluna antiga; essas contas precisam redefinir a senha.
+UPDATE usuarios SET senha = '!' WHERE LENGTH(senha) > 64;
+ALTER TABLE usuarios ALTER COLUMN senha TYPE VARCHAR(64);
@@ -0,0 +1,7 @@
+-- Your SQL goes here
+-- Hashes Argon2id no formato PHC passam de 64 caracteres.
+ALTER TABLE usuarios ALTER COLUMN senha TYPE VARCHAR(255);
+
+-- As contas do Google guardavam o hash do próprio e-mail como senha, o que permitia entrar
+-- com o e-mail no lugar da senha. Um valor que não é hash de nada impede o login por senha.
+UPDATE usuarios SET senha = '!' WHERE origemconta = 'Google';
@@ -0,0 +1,35 @@
+-- This file should undo anything in `up.sql`
+DROP INDEX idx_maquinas_idpublico;
+DROP INDEX idx_usuarios_idpublico;
+
+-- Volta as chaves trocadas para os hashes antigos. Os registros criados depois da migração
+-- continuam com UUIDv7.
+ALTER TABLE codigos_recuperacao DROP CONSTRAINT fk_usuarios;
+ALTER TABLE documentos_contratos DROP CONSTRAINT documentos_contratos_idcontrato_fkey;
+ALTER TABLE reservas_maquinas DROP CONSTRAINT reservas_maquinas_idsolicitacao_fkey;
+ALTER TABLE historico_solicitacoes DROP CONSTRAINT historico_solicitacoes_idsolicitacao_fkey;
+
+DO $$
+DECLARE
+    coluna RECORD;
+BEGIN
+    FOR coluna IN
+        SELECT table_name, column_name FROM information_schema.columns
+        WHERE table_schema = current_schema()
+            AND column_name LIKE 'id%'
+            AND data_type = 'character varying'
+            AND table_name <> 'ids_legados'
+    LOOP
+        EXECUTE format(
+            'UPDATE %1$I SET %2$I = ids_legados.idlegado FROM ids_legados WHERE %1$I.%2$I = ids_legados.idnovo',
+            coluna.table_name, coluna.column_name
+        );
+    END LOOP;
+END $$;
+
+ALTER TABLE codigos_recuperacao ADD CONSTRAINT fk_usuarios FOREIGN KEY (idusuario) REFERENCES usuarios(idusuario) ON DELETE CASCADE;
+ALTER TABLE documentos_contratos ADD CONSTRAINT documentos_contratos_idcontrato_fkey FOREIGN KEY (idcontrato) REFERENCES contratos(idcontrato) ON DELETE CASCADE;
+ALTER TABLE reservas_maquinas ADD CONSTRAINT reservas_maquinas_idsolicitacao_fkey FOREIGN KEY (idsolicitacao) REFERENCES solicitacoes_contratos(idsolicitacao) ON DELETE CASCADE;
+ALTER TABLE historico_solicitacoes ADD CONSTRAINT historico_solicitacoes_idsolicitacao_fkey FOREIGN KEY (idsolicitacao) REFERENCES solicitacoes_contratos(idsolicitacao) ON DELETE CASCADE;
+
+DROP TABLE ids_legados;
@@ -0,0 +1,95 @@
+-- Your SQL goes here
+-- As chaves geradas com bcrypt ($2b$12$...) viram UUIDv7. As antigas ficam em `ids_legados`,
+-- para que os IDs que os clientes ainda guardam continuem funcionando.
+CREATE TABLE ids_legados (
+    idlegado VARCHAR(64) PRIMARY KEY,
+    idnovo VARCHAR(64) NOT NULL UNIQUE,
+    tabela VARCHAR(64) NOT NULL
+);
+
+-- UUIDv7 com o horário de criação do registro, para manter a ordem dos registros antigos.
+CREATE FUNCTION uuid_v7_migracao(momento TIMESTAMP) RETURNS VARCHAR AS $$
+    SELECT encode(
+        set_bit(set_bit(
+            overlay(uuid_send(gen_random_uuid())
+                PLACING substring(int8send(floor(extract(epoch FROM momento) * 1000)::BIGINT) FROM 3)
+                FROM 1 FOR 6),
+        52, 1), 53, 1),
+    'hex')::UUID::VARCHAR
+$$ LANGUAGE SQL VOLATILE;
+
+INSERT INTO ids_legados (idlegado, idnovo, tabela)
+    SELECT idusuario, uuid_v7_migracao(datacadastro), 'usuarios' FROM usuarios WHERE idusuario LIKE '$2%'
+    UNION ALL SELECT idcodigo, uuid_v7_migracao(datacriacao), 'codigos_recuperacao' FROM codigos_recuperacao WHERE idcodigo LIKE '$2%'
+    UNION ALL SELECT idmaquina, uuid_v7_migracao(datacadastro), 'maquinas' FROM maquinas WHERE idmaquina LIKE '$2%'
+    UNION ALL SELECT idimagem, uuid_v7_migracao(NOW()::TIMESTAMP), 'imagens' FROM imagens WHERE idimagem LIKE '$2%'
+    UNION ALL SELECT idimagemmaquina, uuid_v7_migracao(NOW()::TIMESTAMP), 'imagens_maquinas' FROM imagens_maquinas WHERE idimagemmaquina LIKE '$2%'
+    UNION ALL SELECT idmaquinausuario, uuid_v7_migracao(NOW()::TIMESTAMP), 'maquinas_usuarios' FROM maquinas_usuarios WHERE idmaquinausuario LIKE '$2%'
+    UNION ALL SELECT idendereco, uuid_v7_migracao(NOW()::TIMESTAMP), 'enderecos' FROM enderecos WHERE idendereco LIKE '$2%'
+    UNION ALL SELECT idenderecousuario, uuid_v7_migracao(NOW()::TIMESTAMP), 'enderecos_usuarios' FROM enderecos_usuarios WHERE idenderecousuario LIKE '$2%'
+    UNION ALL SELECT idlocadora, uuid_v7_migracao(NOW()::TIMESTAMP), 'locadoras' FROM locadoras WHERE idlocadora LIKE '$2%'
+    UNION ALL SELECT idlocatario, uuid_v7_migracao(NOW()::TIMESTAMP), 'locatarios' FROM locatarios WHERE idlocatario LIKE '$2%'
+    UNION ALL SELECT idconta, uuid_v7_migracao(NOW()::TIMESTAMP), 'contas_bancarias' FROM contas_bancarias WHERE idconta LIKE '$2%'
+    UNION ALL SELECT idsolicitacao, uuid_v7_migracao(datasolicitacao), 'solicitacoes_contratos' FROM solicitacoes_contratos WHERE idsolicitacao LIKE '$2%'
+    UNION ALL SELECT idreserva, uuid_v7_migracao(datainicio), 'reservas_maquinas' FROM reservas_maquinas WHERE idreserva LIKE '$2%'
+    UNION ALL SELECT idcontrato, uuid_v7_migracao(datacontrato), 'contratos' FROM contratos WHERE idcontrato LIKE '$2%'
+    UNION ALL SELECT idnotificacao, uuid_v7_migracao(datacriacao), 'notificacoes' FROM notificacoes WHERE idnotificacao LIKE '$2%'
+ON CONFLICT DO NOTHING;
+
+DROP FUNCTION uuid_v7_migracao(TIMESTAMP);
+
+-- As chaves estrangeiras voltam depois que as duas pontas estiverem trocadas.
+ALTER TABLE codigos_recuperacao DROP CONSTRAINT fk_usuarios;
+ALTER TABLE documentos_contratos DROP CONSTRAINT documentos_contratos_idcontrato_fkey;
+ALTER TABLE reservas_maquinas DROP CONSTRAINT reservas_maquinas_idsolicitacao_fkey;
+ALTER TABLE historico_solicitacoes DROP CONSTRAINT historico_solicitacoes_idsolicitacao_fkey;
+
+-- Cada hash bcrypt é único, então a troca vale para toda coluna de ID, seja a chave da
+-- tabela ou uma referência a outra.
+DO $$
+DECLARE
+    coluna RECORD;
+BEGIN
+    FOR coluna IN
+        SELECT table_name, column_name FROM information_schema.columns
+        WHERE table_schema = current_schema()
+            AND column_name LIKE 'id%'
+            AND data_type = 'character varying'
+            AND table_name <> 'ids_legados'
+    LOOP
+        EXECUTE format(
+            'UPDATE %1$I SET %2$I = ids_legados.idnovo FROM ids_legados WHERE %1$I.%2$I = ids_legados.idlegado',
+            coluna.table_name, coluna.column_name
+        );
+    END LOOP;
+END $$;
+
+ALTER TABLE codigos_recuperacao ADD CONSTRAINT fk_usuarios FOREIGN KEY (idusuario) REFERENCES usuarios(idusuario) ON DELETE CASCADE;
+ALTER TABLE documentos_contratos ADD CONSTRAINT documentos_contratos_idcontrato_fkey FOREIGN KEY (idcontrato) REFERENCES contratos(idcontrato) ON DELETE CASCADE;
+ALTER TABLE reservas_maquinas ADD CONSTRAINT reservas_maquinas_idsolicitacao_fkey FOREIGN KEY (idsolicitacao) REFERENCES solicitacoes_contratos(idsolicitacao) ON DELETE CASCADE;
+ALTER TABLE historico_solicitacoes ADD CONSTRAINT historico_solicitacoes_idsolicitacao_fkey FOREIGN KEY (idsolicitacao) REFERENCES solicitacoes_contratos(idsolicitacao) ON DELETE CASCADE;
+
+-- Os IDs públicos antigos eram números sorteados e podiam se repetir. O registro mais
+-- antigo fica com o número; os outros recebem um ID novo, no formato de `crate::ids`.
+CREATE FUNCTION id_publico_migracao() RETURNS VARCHAR AS $$
+    SELECT string_agg(substr('0123456789ABCDEFGHJKMNPQRSTVWXYZ', floor(random() * 32)::INT + 1, 1), '')
+    FROM generate_series(1, 8)
+$$ LANGUAGE SQL VOLATILE;
+
+UPDATE usuarios SET idpublico = id_publico_migracao()
+WHERE idusuario IN (
+    SELECT idusuario FROM (
+        SELECT idusuario, ROW_NUMBER() OVER (PARTITION BY idpublico ORDER BY datacadastro, idusuario) AS ordem FROM usuarios
+    ) repetidos WHERE ordem > 1
+);
+UPDATE maquinas SET idpublico = id_publico_migracao()
+WHERE idmaquina IN (
+    SELECT idmaquina FROM (
+        SELECT idmaquina, ROW_NUMBER() OVER (PARTITION BY idpublico ORDER BY datacadastro, idmaquina) AS ordem FROM maquinas
+    ) repetidos WHERE ordem > 1
+);
+
+DROP FUNCTION id_publico_migracao();
+
+CREATE UNIQUE INDEX idx_usuarios_idpublico ON usuarios (idpublico);
+CREATE UNIQUE INDEX idx_maquinas_idpublico ON maquinas (idpublico);
@@ -0,0 +1,5 @@
+-- This file should undo anything in `up.sql`
+DROP INDEX IF EXISTS idx_usuarios_idgoogle;
+
+ALTER TABLE usuarios DROP COLUMN idgooglependente;
+ALTER TABLE usuarios DROP COLUMN idgoogle;
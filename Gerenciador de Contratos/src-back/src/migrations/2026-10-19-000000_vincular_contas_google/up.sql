@@ -0,0 +1,7 @@
+-- Your SQL goes here
+-- `idgoogle` é o `sub` do id_token do Google vinculado à conta. `idgooglependente` guarda o
+-- `sub` de um vínculo que ainda espera o código enviado por e-mail.
+ALTER TABLE usuarios ADD COLUMN idgoogle VARCHAR(255);
+ALTER TABLE usuarios ADD COLUMN idgooglependente VARCHAR(255);
+
+CREATE UNIQUE INDEX idx_usuarios_idgoogle ON usuarios (idgoogle);
@@ -0,0 +1,15 @@
+-- This file should undo anything in `up.sql`
+ALTER TABLE usuarios ADD COLUMN idgoogle VARCHAR(255);
+ALTER TABLE usuarios ADD COLUMN idgooglependente VARCHAR(255);
+CREATE UNIQUE INDEX idx_usuarios_idgoogle ON usuarios (idgoogle);
+
+-- Só os vínculos com o Google cabem nas colunas antigas.
+UPDATE usuarios u SET idgoogle = c.sub
+FROM contas_externas c
+WHERE c.idusuario = u.idusuario AND c.provedor = 'google' AND NOT c.pendente;
+
+UPDATE usuarios u SET idgooglependente = c.sub
+FROM contas_externas c
+WHERE c.idusuario = u.idusuario AND c.provedor = 'google' AND c.pendente;
+
+DROP TABLE contas_externas;
@@ -0,0 +1,26 @@
+-- Your SQL goes here
+-- Contas de provedores de login externos (Google, Microsoft, Keycloak...) vinculadas a cada
+-- usuário. `sub` é o ID da conta no provedor. Um vínculo `pendente` espera o código
+-- enviado para o e-mail do usuário.
+CREATE TABLE contas_externas(
+    idcontaexterna VARCHAR(64) PRIMARY KEY,
+    idusuario VARCHAR(64) NOT NULL REFERENCES usuarios(idusuario) ON DELETE CASCADE,
+    provedor VARCHAR(64) NOT NULL,
+    sub VARCHAR(255) NOT NULL,
+    pendente BOOLEAN NOT NULL DEFAULT FALSE,
+    datavinculo TIMESTAMP NOT NULL
+);
+
+-- Cada usuário tem no máximo uma conta de cada provedor, e cada conta confirmada pertence
+-- a um só usuário.
+CREATE UNIQUE INDEX idx_contas_externas_usuario ON contas_externas (idusuario, provedor);
+CREATE UNIQUE INDEX idx_contas_externas_sub ON contas_externas (provedor, sub) WHERE NOT pendente;
+
+INSERT INTO contas_externas (idcontaexterna, idusuario, provedor, sub, pendente, datavinculo)
+SELECT gen_random_uuid()::TEXT, idusuario, 'google', COALESCE(idgoogle, idgooglependente), idgoogle IS NULL, NOW()
+FROM usuarios
+WHERE idgoogle IS NOT NULL OR idgooglependente IS NOT NULL;
+
+DROP INDEX idx_usuarios_idgoogle;
+ALTER TABLE usuarios DROP COLUMN idgooglependente;
+ALTER TABLE usuarios DROP COLUMN idgoogle;
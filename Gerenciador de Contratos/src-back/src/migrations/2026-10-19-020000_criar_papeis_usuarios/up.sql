@@ -0,0 +1,19 @@
+-- Your SQL goes here
+-- Papéis de cada usuário. As permissões de cada papel ficam no código
+-- (`models::papeis_usuarios::Papel::permissoes`).
+CREATE TABLE papeis_usuarios(
+    idusuario VARCHAR(64) NOT NULL REFERENCES usuarios(idusuario) ON DELETE CASCADE,
+    papel VARCHAR(16) NOT NULL,
+    dataatribuicao TIMESTAMP NOT NULL,
+    PRIMARY KEY (idusuario, papel),
+    CONSTRAINT papel_valido CHECK (papel IN ('locador', 'locatario', 'admin'))
+);
+
+-- Quem já cadastrou uma locadora ou um locatário recebe o papel correspondente.
+INSERT INTO papeis_usuarios (idusuario, papel, dataatribuicao)
+SELECT DISTINCT locadoras.idusuario, 'locador', NOW()
+FROM locadoras JOIN usuarios ON usuarios.idusuario = locadoras.idusuario;
+
+INSERT INTO papeis_usuarios (idusuario, papel, dataatribuicao)
+SELECT DISTINCT locatarios.idusuario, 'locatario', NOW()
+FROM locatarios JOIN usuarios ON usuarios.idusuario = locatarios.idusuario;
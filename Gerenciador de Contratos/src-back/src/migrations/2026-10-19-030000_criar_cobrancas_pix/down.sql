@@ -0,0 +1,3 @@
+-- This file should undo anything in `up.sql`
+DROP TABLE cobrancas_pix;
+DROP TABLE chaves_pix;
@@ -0,0 +1,32 @@
+-- Your SQL goes here
+-- Chaves PIX em que os locadores recebem os pagamentos. A chave fica no formato do DICT
+-- (veja `crate::pix::normaliza_chave_pix`).
+CREATE TABLE chaves_pix(
+    idchavepix VARCHAR(64) PRIMARY KEY,
+    idusuario VARCHAR(64) NOT NULL REFERENCES usuarios(idusuario) ON DELETE CASCADE,
+    tipo VARCHAR(16) NOT NULL,
+    chave VARCHAR(77) NOT NULL UNIQUE,
+    datacadastro TIMESTAMP NOT NULL,
+    CONSTRAINT tipo_chave_pix_valido CHECK (tipo IN ('cpf', 'cnpj', 'email', 'telefone', 'aleatoria'))
+);
+
+CREATE INDEX chaves_pix_idusuario ON chaves_pix (idusuario);
+
+-- Cobranças PIX dos contratos. A chave é copiada da chave do locador, para que a cobrança
+-- continue valendo se ela for removida.
+CREATE TABLE cobrancas_pix(
+    idcobranca VARCHAR(64) PRIMARY KEY,
+    idcontrato VARCHAR(64) NOT NULL REFERENCES contratos(idcontrato) ON DELETE CASCADE,
+    chave VARCHAR(77) NOT NULL,
+    txid VARCHAR(35) NOT NULL UNIQUE,
+    valor FLOAT NOT NULL,
+    dinamica BOOLEAN NOT NULL,
+    payload TEXT NOT NULL,
+    status VARCHAR(16) NOT NULL DEFAULT 'Pendente',
+    datacriacao TIMESTAMP NOT NULL,
+    datapagamento TIMESTAMP,
+    idpagamento VARCHAR(64),
+    CONSTRAINT status_cobranca_valido CHECK (status IN ('Pendente', 'Paga'))
+);
+
+CREATE INDEX cobrancas_pix_idcontrato ON cobrancas_pix (idcontrato);
@@ -0,0 +1,28 @@
+-- Your SQL goes here
+-- Parcelas em que o valor de um contrato é cobrado. `valorpago` acumula os pagamentos
+-- parciais; a fatura fica paga quando ele chega ao valor da parcela.
+CREATE TABLE faturas(
+    idfatura VARCHAR(64) PRIMARY KEY,
+    idcontrato VARCHAR(64) NOT NULL REFERENCES contratos(idcontrato) ON DELETE CASCADE,
+    numeroparcela INTEGER NOT NULL,
+    valor FLOAT NOT NULL,
+    valorpago FLOAT NOT NULL DEFAULT 0,
+    datavencimento TIMESTAMP NOT NULL,
+    status VARCHAR(16) NOT NULL DEFAULT 'Pendente',
+    datacriacao TIMESTAMP NOT NULL,
+    datapagamento TIMESTAMP,
+    CONSTRAINT parcela_fatura_unica UNIQUE (idcontrato, numeroparcela),
+    CONSTRAINT valor_fatura_positivo CHECK (valor > 0 AND valorpago >= 0),
+    CONSTRAINT status_fatura_valido CHECK (status IN ('Pendente', 'Vencida', 'Paga'))
+);
+
+CREATE INDEX faturas_status_vencimento ON faturas (status, datavencimento);
+
+-- Recibo de uma fatura paga, gerado uma única vez, como os PDFs dos contratos.
+CREATE TABLE recibos_faturas(
+    idfatura VARCHAR(64) PRIMARY KEY REFERENCES faturas(idfatura) ON DELETE CASCADE,
+    versaomodelo VARCHAR(16) NOT NULL,
+    hashrecibo VARCHAR(64) NOT NULL,
+    documento BYTEA NOT NULL,
+    datageracao TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
@@ -0,0 +1,9 @@
+-- This file should undo anything in `up.sql`
+DROP TABLE encargos_contratos;
+
+ALTER TABLE contratos DROP COLUMN datadevolucao;
+
+ALTER TABLE locadoras
+    DROP CONSTRAINT percentuais_atraso_validos,
+    DROP COLUMN percentualjurosmensal,
+    DROP COLUMN percentualmulta;
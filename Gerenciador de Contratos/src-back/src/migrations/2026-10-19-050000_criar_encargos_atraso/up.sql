@@ -0,0 +1,23 @@
+-- Your SQL goes here
+-- Regras de atraso de cada locadora, em porcentagem: a multa incide uma vez sobre o valor do
+-- contrato, e os juros de mora, por mês de atraso, contados por dia.
+ALTER TABLE locadoras
+    ADD COLUMN percentualmulta FLOAT NOT NULL DEFAULT 2,
+    ADD COLUMN percentualjurosmensal FLOAT NOT NULL DEFAULT 1,
+    ADD CONSTRAINT percentuais_atraso_validos CHECK (percentualmulta BETWEEN 0 AND 100 AND percentualjurosmensal BETWEEN 0 AND 100);
+
+ALTER TABLE contratos ADD COLUMN datadevolucao TIMESTAMP;
+
+-- Cobranças que se somam ao valor do contrato, como as de uma devolução atrasada.
+CREATE TABLE encargos_contratos(
+    idencargo VARCHAR(64) PRIMARY KEY,
+    idcontrato VARCHAR(64) NOT NULL REFERENCES contratos(idcontrato) ON DELETE CASCADE,
+    tipo VARCHAR(16) NOT NULL,
+    descricao TEXT NOT NULL,
+    valor FLOAT NOT NULL,
+    datacriacao TIMESTAMP NOT NULL,
+    CONSTRAINT tipo_encargo_valido CHECK (tipo IN ('TempoExtra', 'Multa', 'Juros')),
+    CONSTRAINT valor_encargo_positivo CHECK (valor > 0)
+);
+
+CREATE INDEX encargos_contratos_idcontrato ON encargos_contratos (idcontrato);
@@ -0,0 +1,9 @@
+-- This file should undo anything in `up.sql`
+ALTER TABLE encargos_contratos ALTER COLUMN valor TYPE FLOAT;
+ALTER TABLE faturas
+    ALTER COLUMN valorpago TYPE FLOAT,
+    ALTER COLUMN valor TYPE FLOAT;
+ALTER TABLE cobrancas_pix ALTER COLUMN valor TYPE FLOAT;
+ALTER TABLE contratos ALTER COLUMN valorlocacao TYPE FLOAT;
+ALTER TABLE solicitacoes_contratos ALTER COLUMN valorsolicitacao TYPE FLOAT;
+ALTER TABLE maquinas ALTER COLUMN valoraluguel TYPE FLOAT;
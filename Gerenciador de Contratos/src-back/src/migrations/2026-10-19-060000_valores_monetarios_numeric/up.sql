@@ -0,0 +1,11 @@
+-- Your SQL goes here
+-- Valores em reais passam a ser exatos, com duas casas. Os valores antigos são arredondados
+-- para o centavo mais próximo.
+ALTER TABLE maquinas ALTER COLUMN valoraluguel TYPE NUMERIC(14, 2) USING ROUND(valoraluguel::NUMERIC, 2);
+ALTER TABLE solicitacoes_contratos ALTER COLUMN valorsolicitacao TYPE NUMERIC(14, 2) USING ROUND(valorsolicitacao::NUMERIC, 2);
+ALTER TABLE contratos ALTER COLUMN valorlocacao TYPE NUMERIC(14, 2) USING ROUND(valorlocacao::NUMERIC, 2);
+ALTER TABLE cobrancas_pix ALTER COLUMN valor TYPE NUMERIC(14, 2) USING ROUND(valor::NUMERIC, 2);
+ALTER TABLE faturas
+    ALTER COLUMN valor TYPE NUMERIC(14, 2) USING ROUND(valor::NUMERIC, 2),
+    ALTER COLUMN valorpago TYPE NUMERIC(14, 2) USING ROUND(valorpago::NUMERIC, 2);
+ALTER TABLE encargos_contratos ALTER COLUMN valor TYPE NUMERIC(14, 2) USING ROUND(valor::NUMERIC, 2);
@@ -0,0 +1,6 @@
+-- This file should undo anything in `up.sql`
+ALTER TABLE contratos DROP CONSTRAINT medida_contrato_valida;
+ALTER TABLE solicitacoes_contratos DROP CONSTRAINT medida_solicitacao_valida;
+
+DROP TABLE descontos_maquinas;
+DROP TABLE precos_maquinas;
@@ -0,0 +1,29 @@
+-- Your SQL goes here
+-- Tarifas próprias de cada máquina, por medida de tempo. As medidas sem tarifa continuam
+-- saindo do valor mensal da máquina.
+CREATE TABLE precos_maquinas(
+    idmaquina VARCHAR(64) NOT NULL REFERENCES maquinas(idmaquina) ON DELETE CASCADE,
+    medida VARCHAR(16) NOT NULL,
+    valor NUMERIC(14, 2) NOT NULL,
+    prazominimo FLOAT NOT NULL DEFAULT 0,
+    PRIMARY KEY (idmaquina, medida),
+    CONSTRAINT medida_preco_valida CHECK (medida IN ('Horas', 'Dias', 'Semanas', 'Meses')),
+    CONSTRAINT valor_preco_positivo CHECK (valor > 0),
+    CONSTRAINT prazo_minimo_valido CHECK (prazominimo >= 0)
+);
+
+-- Descontos das locações longas: a maior faixa alcançada pelo prazo vale para a locação toda.
+CREATE TABLE descontos_maquinas(
+    idmaquina VARCHAR(64) NOT NULL REFERENCES maquinas(idmaquina) ON DELETE CASCADE,
+    diasminimos INTEGER NOT NULL,
+    percentualdesconto FLOAT NOT NULL,
+    PRIMARY KEY (idmaquina, diasminimos),
+    CONSTRAINT dias_desconto_positivos CHECK (diasminimos > 0),
+    CONSTRAINT percentual_desconto_valido CHECK (percentualdesconto > 0 AND percentualdesconto < 100)
+);
+
+-- A medida do prazo passa a ser lida como enum, então só as quatro medidas do front-end valem.
+ALTER TABLE solicitacoes_contratos ADD CONSTRAINT medida_solicitacao_valida
+    CHECK (medidatempolocacao IN ('Horas', 'Dias', 'Semanas', 'Meses'));
+ALTER TABLE contratos ADD CONSTRAINT medida_contrato_valida
+    CHECK (medidatempolocacao IN ('Horas', 'Dias', 'Semanas', 'Meses'));
@@ -0,0 +1,3 @@
+-- This file should undo anything in `up.sql`
+DROP INDEX idx_codigos_recuperacao_hashtoken;
+ALTER TABLE codigos_recuperacao DROP COLUMN hashtoken;
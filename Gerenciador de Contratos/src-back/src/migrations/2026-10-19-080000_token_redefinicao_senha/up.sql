@@ -0,0 +1,5 @@
+-- Your SQL goes here
+-- Token entregue ao conferir um código de recuperação. Só o hash SHA-256 é guardado, e a
+-- redefinição da senha o consome.
+ALTER TABLE codigos_recuperacao ADD COLUMN hashtoken VARCHAR(64);
+CREATE UNIQUE INDEX idx_codigos_recuperacao_hashtoken ON codigos_recuperacao (hashtoken);
@@ -0,0 +1,3 @@
+-- Your SQL goes here
+-- Quando as partes foram avisadas de que a máquina não voltou no fim do contrato. Volta a ser nulo na renovação.
+ALTER TABLE contratos ADD COLUMN dataavisoatraso TIMESTAMP;
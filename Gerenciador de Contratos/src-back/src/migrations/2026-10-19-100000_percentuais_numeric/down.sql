@@ -0,0 +1,5 @@
+-- This file should undo anything in `up.sql`
+ALTER TABLE descontos_maquinas ALTER COLUMN percentualdesconto TYPE FLOAT;
+ALTER TABLE locadoras
+    ALTER COLUMN percentualjurosmensal TYPE FLOAT,
+    ALTER COLUMN percentualmulta TYPE FLOAT;
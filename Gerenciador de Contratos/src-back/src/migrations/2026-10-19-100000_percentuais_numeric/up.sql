@@ -0,0 +1,7 @@
+-- Your SQL goes here
+-- Percentuais passam a ser exatos, com duas casas, como os valores em reais. As faixas
+-- aceitas continuam nas restrições de cada tabela.
+ALTER TABLE locadoras
+    ALTER COLUMN percentualmulta TYPE NUMERIC(5, 2) USING ROUND(percentualmulta::NUMERIC, 2),
+    ALTER COLUMN percentualjurosmensal TYPE NUMERIC(5, 2) USING ROUND(percentualjurosmensal::NUMERIC, 2);
+ALTER TABLE descontos_maquinas ALTER COLUMN percentualdesconto TYPE NUMERIC(5, 2) USING ROUND(percentualdesconto::NUMERIC, 2);
@@ -0,0 +1,8 @@
+-- Your SQL goes here
+-- Tokens de renovação já trocados por um par novo. Cada token renova a sessão uma vez só;
+-- os registros saem depois que o token expira.
+CREATE TABLE tokens_renovacao_usados(
+    idtoken VARCHAR(64) PRIMARY KEY,
+    dataexpiracao TIMESTAMP NOT NULL
+);
+CREATE INDEX idx_tokens_renovacao_usados_dataexpiracao ON tokens_renovacao_usados (dataexpiracao);
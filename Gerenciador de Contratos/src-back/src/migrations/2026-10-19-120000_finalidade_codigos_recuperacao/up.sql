@@ -0,0 +1,6 @@
+-- Your SQL goes here
+-- Para que o código foi enviado (ativação, recuperação de senha, vínculo ou desvínculo de um
+-- provedor). Um código só é aceito na operação da sua finalidade. Os códigos já enviados
+-- ficam sem finalidade e deixam de valer.
+ALTER TABLE codigos_recuperacao ADD COLUMN finalidade VARCHAR(128) NOT NULL DEFAULT '';
+ALTER TABLE codigos_recuperacao ALTER COLUMN finalidade DROP DEFAULT;
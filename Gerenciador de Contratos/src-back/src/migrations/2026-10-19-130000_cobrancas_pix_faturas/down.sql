@@ -0,0 +1,3 @@
+-- This file should undo anything in `up.sql`
+DROP INDEX cobrancas_pix_idfatura;
+ALTER TABLE cobrancas_pix DROP COLUMN idfatura;
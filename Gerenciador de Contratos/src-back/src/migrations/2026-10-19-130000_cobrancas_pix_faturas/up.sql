@@ -0,0 +1,6 @@
+-- Your SQL goes here
+-- Cada cobrança PIX passa a cobrar uma fatura, e o pagamento dela é somado ao valor pago da
+-- fatura. As cobranças do valor todo do contrato, geradas antes das faturas, ficam sem fatura.
+ALTER TABLE cobrancas_pix ADD COLUMN idfatura VARCHAR(64) REFERENCES faturas(idfatura) ON DELETE CASCADE;
+
+CREATE INDEX cobrancas_pix_idfatura ON cobrancas_pix (idfatura);
@@ -0,0 +1,4 @@
+-- This file should undo anything in `up.sql`
+DELETE FROM cobrancas_pix WHERE status = 'Cancelada';
+ALTER TABLE cobrancas_pix DROP CONSTRAINT status_cobranca_valido;
+ALTER TABLE cobrancas_pix ADD CONSTRAINT status_cobranca_valido CHECK (status IN ('Pendente', 'Paga'));
@@ -0,0 +1,6 @@
+-- Your SQL goes here
+-- Uma cobrança pendente é cancelada quando outra a substitui (o valor em aberto, a chave ou o
+-- tipo mudaram) ou quando a fatura recebe um pagamento fora do PIX. Cobranças canceladas não
+-- aceitam pagamento.
+ALTER TABLE cobrancas_pix DROP CONSTRAINT status_cobranca_valido;
+ALTER TABLE cobrancas_pix ADD CONSTRAINT status_cobranca_valido CHECK (status IN ('Pendente', 'Paga', 'Cancelada'));
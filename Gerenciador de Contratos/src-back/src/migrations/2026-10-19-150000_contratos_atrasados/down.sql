@@ -0,0 +1,5 @@
+-- This file should undo anything in `up.sql`
+UPDATE contratos SET statuscontrato = 'Ativo' WHERE statuscontrato = 'Atrasado';
+
+ALTER TABLE contratos DROP CONSTRAINT statuscontrato_valido;
+ALTER TABLE contratos ADD CONSTRAINT statuscontrato_valido CHECK (statuscontrato IN ('Ativo', 'Concluído', 'Rescindido'));
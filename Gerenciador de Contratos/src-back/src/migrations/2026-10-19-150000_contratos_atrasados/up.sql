@@ -0,0 +1,8 @@
+-- Your SQL goes here
+-- Contratos cujo período terminou sem a devolução da máquina ficam atrasados até ela ser
+-- registrada. Os que já tinham recebido o aviso de atraso passam a esse status.
+ALTER TABLE contratos DROP CONSTRAINT statuscontrato_valido;
+ALTER TABLE contratos ADD CONSTRAINT statuscontrato_valido CHECK (statuscontrato IN ('Ativo', 'Atrasado', 'Concluído', 'Rescindido'));
+
+UPDATE contratos SET statuscontrato = 'Atrasado'
+WHERE statuscontrato = 'Ativo' AND datadevolucao IS NULL AND dataavisoatraso IS NOT NULL;
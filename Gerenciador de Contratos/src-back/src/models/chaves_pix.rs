@@ -0,0 +1,156 @@
+use std::{fmt, str::FromStr};
+
+use chrono::NaiveDateTime;
+use diesel::{deserialize::FromSqlRow, expression::AsExpression, prelude::{Insertable, Queryable}, sql_types::Varchar, ExpressionMethods, QueryDsl, RunQueryDsl, Selectable};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use super::{executa, Conexao};
+use crate::erros::ErroApi;
+
+#[derive(Serialize, Deserialize, ToSchema, AsExpression, FromSqlRow, Debug, Clone, Copy, PartialEq, Eq)]
+#[diesel(sql_type = Varchar)]
+#[serde(rename_all = "snake_case")]
+pub enum TipoChavePix{
+    Cpf,
+    Cnpj,
+    Email,
+    Telefone,
+    /// Chave aleatória (EVP), um UUID gerado pelo banco.
+    Aleatoria
+}
+
+impl TipoChavePix{
+    pub fn texto(&self) -> &'static str{
+        match self{
+            TipoChavePix::Cpf => "cpf",
+            TipoChavePix::Cnpj => "cnpj",
+            TipoChavePix::Email => "email",
+            TipoChavePix::Telefone => "telefone",
+            TipoChavePix::Aleatoria => "aleatoria"
+        }
+    }
+}
+
+impl fmt::Display for TipoChavePix{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result{
+        write!(f, "{}", self.texto())
+    }
+}
+
+impl FromStr for TipoChavePix{
+    type Err = ErroApi;
+
+    fn from_str(texto: &str) -> Result<Self, Self::Err>{
+        let todos = [TipoChavePix::Cpf, TipoChavePix::Cnpj, TipoChavePix::Email, TipoChavePix::Telefone, TipoChavePix::Aleatoria];
+        match todos.into_iter().find(|tipo| tipo.texto() == texto.trim()){
+            Some(tipo) => {
+                return Ok(tipo)
+            },
+            None => {
+                return Err(ErroApi::validacao_campo("tipo", "Tipo de chave PIX inválido."))
+            }
+        }
+    }
+}
+
+enum_texto_sql!(TipoChavePix);
+
+#[derive(Queryable, Selectable, Insertable, Serialize, Deserialize, ToSchema, Debug, Clone)]
+#[diesel(table_name = crate::schema::chaves_pix)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct ChavePix{
+    pub idchavepix: String,
+    pub idusuario: String,
+    pub tipo: TipoChavePix,
+    pub chave: String,
+    #[schema(value_type = String)]
+    pub datacadastro: NaiveDateTime
+}
+
+pub async fn cadastra_chave_pix(conn: &Conexao, chavepix: ChavePix)
+    -> Result<ChavePix, ErroApi>{
+    executa(conn, move |conn| {
+        use crate::schema::chaves_pix::dsl::*;
+
+        let res = diesel::insert_into(chaves_pix)
+            .values(&chavepix)
+            .get_result::<ChavePix>(conn);
+        match res{
+            Ok(chavepix) => {
+                return Ok(chavepix)
+            },
+            Err(diesel::result::Error::DatabaseError(diesel::result::DatabaseErrorKind::UniqueViolation, _)) => {
+                return Err(ErroApi::Conflito("Essa chave PIX já está cadastrada.".to_string()))
+            },
+            Err(e) => {
+                return Err(e.into())
+            }
+        }
+    }).await
+}
+
+pub async fn busca_chave_pix_id(conn: &Conexao, id: String)
+    -> Result<ChavePix, ErroApi>{
+    executa(conn, move |conn| {
+        use crate::schema::chaves_pix::dsl::*;
+
+        let res = chaves_pix
+            .filter(idchavepix.eq(id))
+            .first::<ChavePix>(conn);
+        match res{
+            Ok(chavepix) => {
+                return Ok(chavepix)
+            },
+            Err(diesel::result::Error::NotFound) => {
+                return Err(ErroApi::NaoEncontrado("Chave PIX não encontrada.".to_string()))
+            },
+            Err(e) => {
+                return Err(e.into())
+            }
+        }
+    }).await
+}
+
+/// As chaves do usuário, da mais antiga para a mais nova.
+pub async fn busca_chaves_pix_idusuario(conn: &Conexao, id: String)
+    -> Result<Vec<ChavePix>, ErroApi>{
+    executa(conn, move |conn| {
+        use crate::schema::chaves_pix::dsl::*;
+
+        let res = chaves_pix
+            .filter(idusuario.eq(id))
+            .order_by(datacadastro.asc())
+            .load::<ChavePix>(conn);
+        match res{
+            Ok(chaves) => {
+                return Ok(chaves)
+            },
+            Err(e) => {
+                return Err(e.into())
+            }
+        }
+    }).await
+}
+
+pub async fn deleta_chave_pix(conn: &Conexao, id: String)
+    -> Result<(), ErroApi>{
+    executa(conn, move |conn| {
+        use crate::schema::chaves_pix::dsl::*;
+
+        let res = diesel::delete(chaves_pix)
+            .filter(idchavepix.eq(id))
+            .execute(conn);
+        match res{
+            Ok(0) => {
+                return Err(ErroApi::NaoEncontrado("Chave PIX não encontrada.".to_string()))
+            },
+            Ok(_) => {
+                return Ok(())
+            },
+            Err(e) => {
+                return Err(e.into())
+            }
+        }
+    }).await
+}
@@ -0,0 +1,233 @@
+use std::{fmt, str::FromStr};
+
+use chrono::NaiveDateTime;
+use diesel::{deserialize::FromSqlRow, expression::AsExpression, prelude::{Insertable, Queryable}, sql_types::Varchar, Connection, ExpressionMethods, PgConnection, QueryDsl, RunQueryDsl, Selectable};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use super::{executa, faturas::{soma_pagamento_fatura, Fatura}, Conexao};
+use crate::{dinheiro::Dinheiro, erros::ErroApi};
+
+#[derive(Serialize, Deserialize, ToSchema, AsExpression, FromSqlRow, Debug, Clone, Copy, PartialEq, Eq)]
+#[diesel(sql_type = Varchar)]
+pub enum StatusCobranca{
+    Pendente,
+    Paga,
+    /// Substituída por uma cobrança mais nova da fatura, ou a fatura recebeu um pagamento fora do PIX.
+    Cancelada
+}
+
+impl StatusCobranca{
+    pub fn texto(&self) -> &'static str{
+        match self{
+            StatusCobranca::Pendente => "Pendente",
+            StatusCobranca::Paga => "Paga",
+            StatusCobranca::Cancelada => "Cancelada"
+        }
+    }
+}
+
+impl fmt::Display for StatusCobranca{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result{
+        write!(f, "{}", self.texto())
+    }
+}
+
+impl FromStr for StatusCobranca{
+    type Err = ErroApi;
+
+    fn from_str(texto: &str) -> Result<Self, Self::Err>{
+        let todos = [StatusCobranca::Pendente, StatusCobranca::Paga, StatusCobranca::Cancelada];
+        match todos.into_iter().find(|status| status.texto() == texto.trim()){
+            Some(status) => {
+                return Ok(status)
+            },
+            None => {
+                return Err(ErroApi::validacao_campo("status", "Status de cobrança inválido."))
+            }
+        }
+    }
+}
+
+enum_texto_sql!(StatusCobranca);
+
+#[derive(Queryable, Selectable, Insertable, Serialize, Deserialize, ToSchema, Debug, Clone)]
+#[diesel(table_name = crate::schema::cobrancas_pix)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct CobrancaPix{
+    pub idcobranca: String,
+    pub idcontrato: String,
+    /// Chave PIX do locador quando a cobrança foi gerada.
+    pub chave: String,
+    pub txid: String,
+    pub valor: Dinheiro,
+    pub dinamica: bool,
+    /// O "copia e cola" da cobrança, o mesmo texto do QR Code.
+    pub payload: String,
+    pub status: StatusCobranca,
+    #[schema(value_type = String)]
+    pub datacriacao: NaiveDateTime,
+    #[schema(value_type = Option<String>)]
+    pub datapagamento: Option<NaiveDateTime>,
+    /// ID do pagamento no provedor (o `endToEndId` do PIX).
+    pub idpagamento: Option<String>,
+    /// Fatura cobrada. As cobranças do valor todo do contrato, anteriores às faturas, não têm.
+    pub idfatura: Option<String>
+}
+
+/// Salva a cobrança e cancela as outras cobranças pendentes da mesma fatura, que ficam
+/// substituídas por ela. A fatura fica travada, para que duas cobranças geradas ao mesmo tempo
+/// não fiquem pendentes juntas.
+pub async fn cadastra_cobranca_pix(conn: &Conexao, cobranca: CobrancaPix)
+    -> Result<CobrancaPix, ErroApi>{
+    executa(conn, move |conn| {
+        use crate::schema::cobrancas_pix::dsl::*;
+        use crate::schema::faturas;
+
+        conn.transaction(|conn| {
+          if let Some(id) = &cobranca.idfatura{
+              faturas::table
+                  .filter(faturas::idfatura.eq(id))
+                  .select(faturas::idfatura)
+                  .for_update()
+                  .first::<String>(conn)?;
+              cancela_cobrancas_pendentes(conn, id)?;
+          }
+
+          let res = diesel::insert_into(cobrancas_pix)
+              .values(&cobranca)
+              .get_result::<CobrancaPix>(conn);
+          match res{
+              Ok(cobranca) => {
+                  return Ok(cobranca)
+              },
+              Err(e) => {
+                  return Err(e.into())
+              }
+          }
+        })
+    }).await
+}
+
+/// Cancela as cobranças pendentes da fatura. Roda dentro da transação de quem chama.
+pub fn cancela_cobrancas_pendentes(conn: &mut PgConnection, id: &str) -> Result<usize, ErroApi>{
+    use crate::schema::cobrancas_pix::dsl::*;
+
+    let res = diesel::update(cobrancas_pix)
+        .filter(idfatura.eq(id))
+        .filter(status.eq(StatusCobranca::Pendente))
+        .set(status.eq(StatusCobranca::Cancelada))
+        .execute(conn);
+    match res{
+        Ok(canceladas) => {
+            return Ok(canceladas)
+        },
+        Err(e) => {
+            return Err(e.into())
+        }
+    }
+}
+
+/// As cobranças do contrato, da mais antiga para a mais nova.
+pub async fn busca_cobrancas_idcontrato(conn: &Conexao, id: String)
+    -> Result<Vec<CobrancaPix>, ErroApi>{
+    executa(conn, move |conn| {
+        use crate::schema::cobrancas_pix::dsl::*;
+
+        let res = cobrancas_pix
+            .filter(idcontrato.eq(id))
+            .order_by(datacriacao.asc())
+            .load::<CobrancaPix>(conn);
+        match res{
+            Ok(cobrancas) => {
+                return Ok(cobrancas)
+            },
+            Err(e) => {
+                return Err(e.into())
+            }
+        }
+    }).await
+}
+
+/// As cobranças da fatura, da mais antiga para a mais nova.
+pub async fn busca_cobrancas_idfatura(conn: &Conexao, id: String)
+    -> Result<Vec<CobrancaPix>, ErroApi>{
+    executa(conn, move |conn| {
+        use crate::schema::cobrancas_pix::dsl::*;
+
+        let res = cobrancas_pix
+            .filter(idfatura.eq(id))
+            .order_by(datacriacao.asc())
+            .load::<CobrancaPix>(conn);
+        match res{
+            Ok(cobrancas) => {
+                return Ok(cobrancas)
+            },
+            Err(e) => {
+                return Err(e.into())
+            }
+        }
+    }).await
+}
+
+pub async fn busca_cobranca_txid(conn: &Conexao, txid_: String)
+    -> Result<CobrancaPix, ErroApi>{
+    executa(conn, move |conn| {
+        use crate::schema::cobrancas_pix::dsl::*;
+
+        let res = cobrancas_pix
+            .filter(txid.eq(txid_))
+            .first::<CobrancaPix>(conn);
+        match res{
+            Ok(cobranca) => {
+                return Ok(cobranca)
+            },
+            Err(diesel::result::Error::NotFound) => {
+                return Err(ErroApi::NaoEncontrado("Cobrança PIX não encontrada.".to_string()))
+            },
+            Err(e) => {
+                return Err(e.into())
+            }
+        }
+    }).await
+}
+
+/// Marca a cobrança pendente como paga e, se ela cobra uma fatura, soma o valor ao que foi pago
+/// dela, tudo na mesma transação. Só uma confirmação vale, mesmo que cheguem duas ao mesmo tempo,
+/// e uma cobrança cancelada não é paga.
+pub async fn confirma_pagamento_cobranca(conn: &Conexao, txid_: String, idpagamento_: String)
+    -> Result<(CobrancaPix, Option<Fatura>), ErroApi>{
+    executa(conn, move |conn| {
+        use crate::schema::cobrancas_pix::dsl::*;
+
+        conn.transaction(|conn| {
+          let agora = chrono::Utc::now().naive_utc();
+          let res = diesel::update(cobrancas_pix)
+              .filter(txid.eq(txid_))
+              .filter(status.eq(StatusCobranca::Pendente))
+              .set((status.eq(StatusCobranca::Paga), datapagamento.eq(agora), idpagamento.eq(idpagamento_)))
+              .get_result::<CobrancaPix>(conn);
+          let cobranca = match res{
+              Ok(cobranca) => {
+                  cobranca
+              },
+              Err(diesel::result::Error::NotFound) => {
+                  return Err(ErroApi::Conflito("A cobrança já foi paga ou foi substituída por outra.".to_string()))
+              },
+              Err(e) => {
+                  return Err(e.into())
+              }
+          };
+
+          let fatura = match &cobranca.idfatura{
+              Some(id) => {
+                  Some(soma_pagamento_fatura(conn, id, cobranca.valor, agora)?)
+              },
+              None => {
+                  None
+              }
+          };
+          return Ok((cobranca, fatura))
+        })
+    }).await
+}
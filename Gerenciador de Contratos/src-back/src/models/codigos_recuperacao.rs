@@ -1,65 +1,268 @@
-use chrono::NaiveDateTime;
-use diesel::{prelude::{Insertable, Queryable}, ExpressionMethods, PgConnection, QueryDsl, RunQueryDsl, Selectable, SelectableHelper};
+use chrono::{Duration, NaiveDateTime};
+use diesel::{prelude::{Insertable, Queryable}, Connection, ExpressionMethods, OptionalExtension, PgConnection, QueryDsl, RunQueryDsl, Selectable};
 use serde::{Deserialize, Serialize};
 
+use super::{executa, Conexao};
+use crate::{erros::ErroApi, senhas::configuracao_senhas};
+
+/// Quantas tentativas erradas o usuário pode fazer antes de ser bloqueado.
+pub const MAX_TENTATIVAS_CODIGO: i32 = 5;
+/// Por quanto tempo o usuário fica sem poder conferir ou pedir códigos depois de errar
+/// `MAX_TENTATIVAS_CODIGO` vezes.
+pub const MINUTOS_BLOQUEIO_CODIGO: i64 = 15;
+
 #[derive(Queryable, Selectable, Insertable, Serialize, Deserialize)]
 #[diesel(table_name = crate::schema::codigos_recuperacao)]
 #[diesel(check_for_backend(diesel::pg::Pg))]
 pub struct CodigoRecuperacao{
-    pub codigo: String,
+    /// Hash do código enviado por e-mail. O código em si nunca é guardado.
+    pub hashcodigo: String,
     pub datacriacao: NaiveDateTime,
     pub dataexpiracao: NaiveDateTime,
     pub status: String,
     pub idusuario: String,
-    pub idcodigo: String
+    pub idcodigo: String,
+    pub tentativas: i32,
+    pub dataultimatentativa: Option<NaiveDateTime>,
+    /// Hash SHA-256 do token de redefinição de senha, depois que o código é conferido.
+    pub hashtoken: Option<String>,
+    /// Para que o código foi enviado; veja `FinalidadeCodigo::chave`.
+    pub finalidade: String
+}
+
+/// Cadastra o código e invalida os códigos e tokens anteriores do usuário que ainda não foram
+/// usados.
+pub async fn cadastra_codigo_recuperacao_db(conn: &Conexao, dados: CodigoRecuperacao)
+    -> Result<String, ErroApi>{
+    executa(conn, move |conn| {
+        use crate::schema::codigos_recuperacao::dsl::*;
+
+        let res: Result<CodigoRecuperacao, diesel::result::Error> = conn.transaction(|conn| {
+            diesel::update(codigos_recuperacao)
+                .filter(idusuario.eq(dados.idusuario.clone()))
+                .filter(status.eq_any(["Não utilizado", "Verificado"]))
+                .set(status.eq("Invalidado"))
+                .execute(conn)?;
+
+            diesel::insert_into(codigos_recuperacao)
+                .values(dados)
+                .get_result(conn)
+        });
+
+        match res{
+            Ok(codigorecuperacao) => {
+                return Ok(codigorecuperacao.idcodigo)
+            },
+            Err(e) => {
+                return Err(e.into())
+            }
+        }
+    }).await
 }
 
-pub async fn cadastra_codigo_recuperacao_db(conn: &mut PgConnection, dados: CodigoRecuperacao)
-    -> Result<String, String>{
+/// Tentativas erradas do usuário nos últimos `MINUTOS_BLOQUEIO_CODIGO` minutos.
+fn conta_tentativas_recentes(conn: &mut PgConnection, idusuario_: &str, agora: NaiveDateTime)
+    -> Result<i64, diesel::result::Error>{
     use crate::schema::codigos_recuperacao::dsl::*;
 
-    let res: Result<CodigoRecuperacao, diesel::result::Error> = diesel::insert_into(codigos_recuperacao)
-        .values(dados)
-        .get_result(conn);
+    let total: Option<i64> = codigos_recuperacao
+        .filter(idusuario.eq(idusuario_))
+        .filter(dataultimatentativa.ge(agora - Duration::minutes(MINUTOS_BLOQUEIO_CODIGO)))
+        .select(diesel::dsl::sum(tentativas))
+        .first(conn)?;
+    return Ok(total.unwrap_or(0))
+}
+
+fn erro_bloqueio() -> ErroApi{
+    ErroApi::Proibido(format!("Muitas tentativas com código incorreto. Tente novamente em {} minutos.", MINUTOS_BLOQUEIO_CODIGO))
+}
 
-    match res{
-        Ok(codigorecuperacao) => {
-            return Ok(codigorecuperacao.codigo)
-        },
-        Err(e) => {
-            return Err(e.to_string())
+/// Confere se o usuário pode receber um novo código, isto é, se não está bloqueado.
+pub async fn verifica_bloqueio_codigo(conn: &Conexao, idusuario_: String) -> Result<(), ErroApi>{
+    executa(conn, move |conn| {
+        let agora = chrono::Utc::now().naive_utc();
+        match conta_tentativas_recentes(conn, &idusuario_, agora){
+            Ok(total) if total >= MAX_TENTATIVAS_CODIGO as i64 => {
+                return Err(erro_bloqueio())
+            },
+            Ok(_) => {
+                return Ok(())
+            },
+            Err(e) => {
+                return Err(e.into())
+            }
         }
-    }
+    }).await
+}
+
+enum ConferenciaCodigo{
+    Valido(String),
+    Invalido,
+    Bloqueado
 }
 
+/// Confere o código digitado com o código vigente do usuário e o marca como utilizado.
+/// Só vale o código enviado com a `finalidade` da operação: um código de vínculo, por
+/// exemplo, não redefine a senha.
+/// Cada erro conta uma tentativa; ao chegar em `MAX_TENTATIVAS_CODIGO` o código é bloqueado
+/// e o usuário precisa esperar para tentar de novo.
+pub async fn verifica_codigo_recuperacao_db(conn: &Conexao, idusuario_: String, codigo_: String, finalidade_: String) -> Result<String, ErroApi>{
+    executa(conn, move |conn| {
+        use crate::schema::codigos_recuperacao::dsl::*;
 
-pub async fn verifica_codigo_recuperacao_db(conn: &mut PgConnection, idusuario_: String, codigo_: String) -> Result<String, String>{
-    use crate::schema::codigos_recuperacao::dsl::*;
+        let senhas = configuracao_senhas()?;
+        let agora = chrono::Utc::now().naive_utc();
+        // As tentativas erradas precisam ser gravadas, então a transação termina com `Ok` nos
+        // três casos e o erro é montado depois.
+        let res: Result<ConferenciaCodigo, diesel::result::Error> = conn.transaction(|conn| {
+            let vigente: Option<CodigoRecuperacao> = codigos_recuperacao
+                .filter(idusuario.eq(idusuario_.clone()))
+                .filter(status.eq("Não utilizado"))
+                .filter(finalidade.eq(&finalidade_))
+                .filter(dataexpiracao.gt(agora))
+                .order_by(datacriacao.desc())
+                .for_update()
+                .first(conn)
+                .optional()?;
+
+            if conta_tentativas_recentes(conn, &idusuario_, agora)? >= MAX_TENTATIVAS_CODIGO as i64{
+                return Ok(ConferenciaCodigo::Bloqueado)
+            }
+
+            let vigente = match vigente{
+                Some(vigente) => vigente,
+                None => {
+                    return Ok(ConferenciaCodigo::Invalido)
+                }
+            };
 
-    let res: Result<CodigoRecuperacao, diesel::result::Error> = codigos_recuperacao
-        .filter(idusuario.eq(idusuario_.clone()))
-        .filter(codigo.eq(codigo_))
-        .filter(status.eq("Não utilizado"))
-        .first(conn);
-    let idcodigo_ = match res{
-        Ok(codigorecuperacao) => {
-            codigorecuperacao.idcodigo
-        },
-        Err(e) => {
-            return Err(e.to_string())
+            if senhas.verifica(&codigo_, &vigente.hashcodigo).correta(){
+                diesel::update(codigos_recuperacao.find(vigente.idcodigo.clone()))
+                    .set(status.eq("Utilizado"))
+                    .execute(conn)?;
+                return Ok(ConferenciaCodigo::Valido(vigente.idcodigo))
+            }
+
+            let novastentativas = vigente.tentativas + 1;
+            let novostatus = if novastentativas >= MAX_TENTATIVAS_CODIGO { "Bloqueado" } else { "Não utilizado" };
+            diesel::update(codigos_recuperacao.find(vigente.idcodigo))
+                .set((
+                    tentativas.eq(novastentativas),
+                    dataultimatentativa.eq(agora),
+                    status.eq(novostatus)
+                ))
+                .execute(conn)?;
+            return Ok(ConferenciaCodigo::Invalido)
+        });
+
+        match res{
+            Ok(ConferenciaCodigo::Valido(id)) => {
+                return Ok(id)
+            },
+            Ok(ConferenciaCodigo::Invalido) => {
+                return Err(ErroApi::validacao_campo("codigo", "Código inválido ou expirado."))
+            },
+            Ok(ConferenciaCodigo::Bloqueado) => {
+                return Err(erro_bloqueio())
+            },
+            Err(e) => {
+                return Err(e.into())
+            }
+        }
+    }).await
+}
+
+/// Quando o último código do usuário foi criado, se ele tiver algum.
+pub async fn busca_data_ultimo_codigo(conn: &Conexao, idusuario_: String) -> Result<Option<NaiveDateTime>, ErroApi>{
+    executa(conn, move |conn| {
+        use crate::schema::codigos_recuperacao::dsl::*;
+
+        let res: Result<Option<NaiveDateTime>, diesel::result::Error> = codigos_recuperacao
+            .filter(idusuario.eq(idusuario_))
+            .select(diesel::dsl::max(datacriacao))
+            .first(conn);
+        match res{
+            Ok(data) => {
+                return Ok(data)
+            },
+            Err(e) => {
+                return Err(e.into())
+            }
+        }
+    }).await
+}
+
+/// Troca um código já conferido pelo token de redefinição de senha, que vale até
+/// `dataexpiracao_`.
+pub async fn cadastra_token_redefinicao(conn: &Conexao, idcodigo_: String, hashtoken_: String, dataexpiracao_: NaiveDateTime)
+    -> Result<(), ErroApi>{
+    executa(conn, move |conn| {
+        use crate::schema::codigos_recuperacao::dsl::*;
+
+        let res = diesel::update(codigos_recuperacao.find(idcodigo_))
+            .filter(status.eq("Utilizado"))
+            .set((
+                status.eq("Verificado"),
+                hashtoken.eq(Some(hashtoken_)),
+                dataexpiracao.eq(dataexpiracao_)
+            ))
+            .execute(conn);
+        match res{
+            Ok(1) => {
+                return Ok(())
+            },
+            Ok(_) => {
+                return Err(ErroApi::validacao_campo("codigo", "Código inválido ou expirado."))
+            },
+            Err(e) => {
+                return Err(e.into())
+            }
         }
-    };
-
-    let res: Result<CodigoRecuperacao, diesel::result::Error> = diesel::update(codigos_recuperacao.find(idcodigo_))
-        .set(status.eq("Utilizado"))
-        .returning(CodigoRecuperacao::as_returning())
-        .get_result(conn);
-    match res{
-        Ok(res) => {
-            return Ok(res.codigo)
-        },
-        Err(e) => {
-            return Err(e.to_string())
+    }).await
+}
+
+/// Troca a senha do dono do token e consome o token, na mesma transação: cada token redefine
+/// a senha uma vez só. Retorna o ID do usuário.
+pub async fn redefine_senha_token(conn: &Conexao, hashtoken_: String, senha_nova: String) -> Result<String, ErroApi>{
+    executa(conn, move |conn| {
+        use crate::schema::codigos_recuperacao::dsl::*;
+        use crate::schema::usuarios;
+
+        let agora = chrono::Utc::now().naive_utc();
+        let res: Result<Option<String>, diesel::result::Error> = conn.transaction(|conn| {
+            let vigente: Option<CodigoRecuperacao> = codigos_recuperacao
+                .filter(hashtoken.eq(&hashtoken_))
+                .filter(status.eq("Verificado"))
+                .filter(dataexpiracao.gt(agora))
+                .for_update()
+                .first(conn)
+                .optional()?;
+            let vigente = match vigente{
+                Some(vigente) => vigente,
+                None => {
+                    return Ok(None)
+                }
+            };
+
+            diesel::update(codigos_recuperacao.find(&vigente.idcodigo))
+                .set(status.eq("Utilizado"))
+                .execute(conn)?;
+            diesel::update(usuarios::table.find(&vigente.idusuario))
+                .set(usuarios::senha.eq(senha_nova))
+                .execute(conn)?;
+            Ok(Some(vigente.idusuario))
+        });
+
+        match res{
+            Ok(Some(id)) => {
+                return Ok(id)
+            },
+            Ok(None) => {
+                return Err(ErroApi::validacao_campo("tokenredefinicao", "Token de redefinição inválido ou expirado."))
+            },
+            Err(e) => {
+                return Err(e.into())
+            }
         }
-    }
-}
\ No newline at end of file
+    }).await
+}
@@ -1,7 +1,9 @@
-use diesel::{prelude::{Insertable, Queryable}, query_dsl::methods::FilterDsl, ExpressionMethods, PgConnection, RunQueryDsl, Selectable};
+use diesel::{prelude::{Insertable, Queryable}, query_dsl::methods::FilterDsl, ExpressionMethods, RunQueryDsl, Selectable};
 use serde::{Deserialize, Serialize};
 
-use crate::controllers::cria_conn;
+use super::{executa, Conexao};
+use crate::erros::ErroApi;
+use crate::controllers::cria_pool;
 
 #[derive(Queryable, Selectable, Insertable, Serialize, Deserialize, Debug)]
 #[diesel(table_name = crate::schema::contas_bancarias)]
@@ -14,80 +16,89 @@ pub struct ContaBancaria{
     pub nomebanco: String
 }
 
-pub async fn cadastra_conta_bancaria(conn: &mut PgConnection, conta: ContaBancaria)
-    -> Result<String, String>{
-    use crate::schema::contas_bancarias::dsl::*;
+pub async fn cadastra_conta_bancaria(conn: &Conexao, conta: ContaBancaria)
+    -> Result<String, ErroApi>{
+    executa(conn, move |conn| {
+        use crate::schema::contas_bancarias::dsl::*;
 
-    let res: Result<ContaBancaria, diesel::result::Error> = diesel::insert_into(contas_bancarias)
-      .values(conta)
-      .get_result(conn);
+        let res: Result<ContaBancaria, diesel::result::Error> = diesel::insert_into(contas_bancarias)
+          .values(conta)
+          .get_result(conn);
 
-    match res{
-      Ok(conta) => {
-        return Ok(conta.idconta)
-      },
-      Err(e) => {
-        return Err(e.to_string());
-      }
-    }
+        match res{
+          Ok(conta) => {
+            return Ok(conta.idconta)
+          },
+          Err(e) => {
+            return Err(e.into());
+          }
+        }
+    }).await
 }
 
-pub async fn busca_conta_bancaria_idusuario(conn: &mut PgConnection, id: String)
-    -> Result<ContaBancaria, String>{
-    use crate::schema::contas_bancarias::dsl::*;
+pub async fn busca_conta_bancaria_idusuario(conn: &Conexao, id: String)
+    -> Result<ContaBancaria, ErroApi>{
+    executa(conn, move |conn| {
+        use crate::schema::contas_bancarias::dsl::*;
 
-    let res: Result<ContaBancaria, diesel::result::Error> = contas_bancarias.filter(idusuario.eq(id))
-      .get_result(conn);
+        let res: Result<ContaBancaria, diesel::result::Error> = contas_bancarias.filter(idusuario.eq(id))
+          .get_result(conn);
 
-    match res{
-      Ok(conta) => {
-        return Ok(conta)
-      },
-      Err(e) => {
-        return Err(e.to_string())
-      }
-    }
+        match res{
+          Ok(conta) => {
+            return Ok(conta)
+          },
+          Err(e) => {
+            return Err(e.into())
+          }
+        }
+    }).await
 }
 
 pub async fn deleta_conta_bancaria(id: String)
-    -> Result<(), String>{
-    use crate::schema::contas_bancarias::dsl::*;
+    -> Result<(), ErroApi>{
+    let pool = cria_pool().unwrap();
+    let conn = &pool.get().await.unwrap();
 
-    let conn = &mut cria_conn().unwrap();
+    executa(conn, move |conn| {
+        use crate::schema::contas_bancarias::dsl::*;
 
-    let res = diesel::delete(contas_bancarias)
-      .filter(idconta.eq(id))
-      .execute(conn);
-    
-    match res{
-      Ok(_) => {
-        return Ok(())
-      },
-      Err(e) => {
-        return Err(e.to_string())
-      }
-    }
+        let res = diesel::delete(contas_bancarias)
+          .filter(idconta.eq(id))
+          .execute(conn);
+
+        match res{
+          Ok(_) => {
+            return Ok(())
+          },
+          Err(e) => {
+            return Err(e.into())
+          }
+        }
+    }).await
 }
 
-pub async fn atualiza_conta_bancaria(conn: &mut PgConnection, conta: ContaBancaria)
-    -> Result<ContaBancaria, String>{
-    use crate::schema::contas_bancarias::dsl::*;
+pub async fn atualiza_conta_bancaria(conn: &Conexao, conta: ContaBancaria)
+    -> Result<ContaBancaria, ErroApi>{
+    executa(conn, move |conn| {
+        use crate::schema::contas_bancarias::dsl::*;
 
-    let res: Result<ContaBancaria, diesel::result::Error> = diesel::update(contas_bancarias)
-      .filter(idconta.eq(conta.idconta))
-      .set((
-        nomebanco.eq(conta.nomebanco),
-        numeroagencia.eq(conta.numeroagencia),
-        numeroconta.eq(conta.numeroconta)
-      ))
-      .get_result(conn);
+        let res: Result<ContaBancaria, diesel::result::Error> = diesel::update(contas_bancarias)
+          .filter(idconta.eq(conta.idconta))
+          .set((
+            nomebanco.eq(conta.nomebanco),
+            numeroagencia.eq(conta.numeroagencia),
+            numeroconta.eq(conta.numeroconta)
+          ))
+          .get_result(conn);
 
-    match res{
-      Ok(conta) => {
-        return Ok(conta)
-      },
-      Err(e) => {
-        return Err(e.to_string())
-      }
-    }
+        match res{
+          Ok(conta) => {
+            return Ok(conta)
+          },
+          Err(e) => {
+            return Err(e.into())
+          }
+        }
+    }).await
 }
\ No newline at end of file
@@ -0,0 +1,169 @@
+use chrono::NaiveDateTime;
+use diesel::{prelude::{Insertable, Queryable}, upsert::excluded, ExpressionMethods, OptionalExtension, QueryDsl, RunQueryDsl, Selectable};
+use serde::{Deserialize, Serialize};
+
+use super::{executa, Conexao};
+use crate::{erros::ErroApi, ids::gera_id};
+
+/// Conta de um provedor de login externo vinculada a um usuário.
+#[derive(Queryable, Selectable, Insertable, Serialize, Deserialize, Debug, Clone)]
+#[diesel(table_name = crate::schema::contas_externas)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct ContaExterna{
+    pub idcontaexterna: String,
+    pub idusuario: String,
+    /// Chave do provedor na configuração, como `google`.
+    pub provedor: String,
+    /// ID da conta no provedor.
+    pub sub: String,
+    /// O vínculo espera o código enviado para o e-mail do usuário.
+    pub pendente: bool,
+    pub datavinculo: NaiveDateTime
+}
+
+/// A conta confirmada do provedor com esse `sub`.
+pub async fn busca_conta_externa(conn: &Conexao, provedor_: String, sub_: String)
+    -> Result<Option<ContaExterna>, ErroApi>{
+    executa(conn, move |conn| {
+        use crate::schema::contas_externas::dsl::*;
+
+        let res = contas_externas
+            .filter(provedor.eq(provedor_))
+            .filter(sub.eq(sub_))
+            .filter(pendente.eq(false))
+            .first::<ContaExterna>(conn)
+            .optional();
+        match res{
+            Ok(conta) => {
+                return Ok(conta)
+            },
+            Err(e) => {
+                return Err(e.into())
+            }
+        }
+    }).await
+}
+
+/// A conta do provedor vinculada ao usuário, confirmada ou pendente.
+pub async fn busca_conta_externa_usuario(conn: &Conexao, id: String, provedor_: String)
+    -> Result<Option<ContaExterna>, ErroApi>{
+    executa(conn, move |conn| {
+        use crate::schema::contas_externas::dsl::*;
+
+        let res = contas_externas
+            .filter(idusuario.eq(id))
+            .filter(provedor.eq(provedor_))
+            .first::<ContaExterna>(conn)
+            .optional();
+        match res{
+            Ok(conta) => {
+                return Ok(conta)
+            },
+            Err(e) => {
+                return Err(e.into())
+            }
+        }
+    }).await
+}
+
+pub async fn busca_contas_externas_usuario(conn: &Conexao, id: String)
+    -> Result<Vec<ContaExterna>, ErroApi>{
+    executa(conn, move |conn| {
+        use crate::schema::contas_externas::dsl::*;
+
+        let res = contas_externas
+            .filter(idusuario.eq(id))
+            .order_by(datavinculo.asc())
+            .load::<ContaExterna>(conn);
+        match res{
+            Ok(contas) => {
+                return Ok(contas)
+            },
+            Err(e) => {
+                return Err(e.into())
+            }
+        }
+    }).await
+}
+
+/// Vincula a conta do provedor ao usuário, trocando o vínculo anterior com o mesmo provedor.
+/// Com `pendente`, o vínculo só vale depois de `confirma_conta_externa`.
+pub async fn vincula_conta_externa(conn: &Conexao, id: String, provedor_: String, sub_: String, pendente_: bool)
+    -> Result<(), ErroApi>{
+    executa(conn, move |conn| {
+        use crate::schema::contas_externas::dsl::*;
+
+        let conta = ContaExterna{
+            idcontaexterna: gera_id(),
+            idusuario: id,
+            provedor: provedor_,
+            sub: sub_,
+            pendente: pendente_,
+            datavinculo: chrono::Utc::now().naive_utc()
+        };
+        let res = diesel::insert_into(contas_externas)
+            .values(&conta)
+            .on_conflict((idusuario, provedor))
+            .do_update()
+            .set((sub.eq(excluded(sub)), pendente.eq(excluded(pendente)), datavinculo.eq(excluded(datavinculo))))
+            .execute(conn);
+        match res{
+            Ok(_) => {
+                return Ok(())
+            },
+            Err(e) => {
+                return Err(e.into())
+            }
+        }
+    }).await
+}
+
+/// Confirma o vínculo pendente com o provedor e devolve o `sub` vinculado.
+pub async fn confirma_conta_externa(conn: &Conexao, id: String, provedor_: String)
+    -> Result<String, ErroApi>{
+    executa(conn, move |conn| {
+        use crate::schema::contas_externas::dsl::*;
+
+        let res = diesel::update(contas_externas)
+            .filter(idusuario.eq(id))
+            .filter(provedor.eq(provedor_))
+            .filter(pendente.eq(true))
+            .set((pendente.eq(false), datavinculo.eq(chrono::Utc::now().naive_utc())))
+            .returning(sub)
+            .get_result::<String>(conn);
+        match res{
+            Ok(vinculado) => {
+                return Ok(vinculado)
+            },
+            Err(diesel::result::Error::NotFound) => {
+                return Err(ErroApi::Conflito("Não há vínculo pendente com esse provedor nessa conta.".to_string()))
+            },
+            Err(e) => {
+                return Err(e.into())
+            }
+        }
+    }).await
+}
+
+pub async fn desvincula_conta_externa(conn: &Conexao, id: String, provedor_: String)
+    -> Result<(), ErroApi>{
+    executa(conn, move |conn| {
+        use crate::schema::contas_externas::dsl::*;
+
+        let res = diesel::delete(contas_externas)
+            .filter(idusuario.eq(id))
+            .filter(provedor.eq(provedor_))
+            .execute(conn);
+        match res{
+            Ok(0) => {
+                return Err(ErroApi::Conflito("A conta não está vinculada a esse provedor.".to_string()))
+            },
+            Ok(_) => {
+                return Ok(())
+            },
+            Err(e) => {
+                return Err(e.into())
+            }
+        }
+    }).await
+}
@@ -1,8 +1,63 @@
+use std::{fmt, str::FromStr};
+
 use chrono::NaiveDateTime;
-use diesel::{prelude::{Insertable, Queryable}, query_dsl::methods::FilterDsl, ExpressionMethods, PgConnection, RunQueryDsl, Selectable};
+use diesel::{deserialize::FromSqlRow, expression::AsExpression, prelude::{Insertable, Queryable}, query_dsl::methods::FilterDsl, sql_types::Varchar, Connection, ExpressionMethods, JoinOnDsl, PgConnection, RunQueryDsl, Selectable, SelectableHelper};
 use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use super::{encargos_contratos::{calcula_encargos_atraso, EncargoContrato, RegrasAtraso}, executa, faturas::{Fatura, StatusFatura, DIAS_VENCIMENTO_FATURA}, precos_maquinas::MedidaTempo, reservas_maquinas::{busca_reserva_idsolicitacao, calcula_fim_locacao, verifica_periodo_livre}, Conexao};
+use crate::{dinheiro::Dinheiro, erros::ErroApi, ids::gera_id};
+
+/// Ciclo de vida de um contrato. O contrato nasce ativo. Quando o período termina, ele é
+/// concluído, se a máquina já voltou, ou fica atrasado até a devolução ser registrada, que o
+/// conclui. Uma das partes pode rescindi-lo antes do fim.
+#[derive(Serialize, Deserialize, ToSchema, AsExpression, FromSqlRow, Debug, Clone, Copy, PartialEq, Eq)]
+#[diesel(sql_type = Varchar)]
+pub enum StatusContrato{
+    Ativo,
+    /// O período terminou sem que a máquina fosse devolvida.
+    Atrasado,
+    #[serde(rename = "Concluído")]
+    Concluido,
+    Rescindido
+}
+
+impl StatusContrato{
+    pub fn texto(&self) -> &'static str{
+        match self{
+            StatusContrato::Ativo => "Ativo",
+            StatusContrato::Atrasado => "Atrasado",
+            StatusContrato::Concluido => "Concluído",
+            StatusContrato::Rescindido => "Rescindido"
+        }
+    }
+}
+
+impl fmt::Display for StatusContrato{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result{
+        write!(f, "{}", self.texto())
+    }
+}
 
-#[derive(Queryable, Selectable, Insertable, Serialize, Deserialize, Debug)]
+impl FromStr for StatusContrato{
+    type Err = ErroApi;
+
+    fn from_str(texto: &str) -> Result<Self, Self::Err>{
+        let todos = [StatusContrato::Ativo, StatusContrato::Atrasado, StatusContrato::Concluido, StatusContrato::Rescindido];
+        match todos.into_iter().find(|status| status.texto() == texto.trim()){
+            Some(status) => {
+                return Ok(status)
+            },
+            None => {
+                return Err(ErroApi::validacao_campo("status", "Status de contrato inválido."))
+            }
+        }
+    }
+}
+
+enum_texto_sql!(StatusContrato);
+
+#[derive(Queryable, Selectable, Insertable, Serialize, Deserialize, Debug, Clone)]
 #[diesel(table_name = crate::schema::contratos)]
 #[diesel(check_for_backend(diesel::pg::Pg))] 
 pub struct Contrato{ 
@@ -15,16 +70,30 @@ pub struct Contrato{
     pub idmaquina: String,
     pub idsolicitacaocontrato: String,
     pub idcontabancarialocador: String,
-    pub medidatempolocacao: String,
+    pub medidatempolocacao: MedidaTempo,
     pub cidadeforo: String,
-    pub statuscontrato: String,
+    pub statuscontrato: StatusContrato,
     pub prazolocacao: f64,
-    pub valorlocacao: f64,
+    pub valorlocacao: Dinheiro,
     pub datacontrato: NaiveDateTime,
+    pub motivoencerramento: Option<String>,
+    pub dataencerramento: Option<NaiveDateTime>,
+    pub dataavisotermino: Option<NaiveDateTime>,
+    /// Quando a máquina voltou ao locador; veja `calcula_encargos_atraso`.
+    pub datadevolucao: Option<NaiveDateTime>,
+    /// Quando o período acabou sem a devolução da máquina e o contrato ficou atrasado.
+    pub dataavisoatraso: Option<NaiveDateTime>
 }
 
-pub async fn cadastra_contrato(conn: &mut PgConnection, contrato: Contrato)
-    -> Result<String, String>{
+pub async fn cadastra_contrato(conn: &Conexao, contrato: Contrato)
+    -> Result<String, ErroApi>{
+    executa(conn, move |conn| {
+        insere_contrato(conn, contrato)
+    }).await
+}
+
+/// Grava o contrato na conexão recebida, para ser usado dentro de uma transação maior.
+pub fn insere_contrato(conn: &mut PgConnection, contrato: Contrato) -> Result<String, ErroApi>{
     use crate::schema::contratos::dsl::*;
 
     let res: Result<Contrato, diesel::result::Error> = diesel::insert_into(contratos)
@@ -36,42 +105,432 @@ pub async fn cadastra_contrato(conn: &mut PgConnection, contrato: Contrato)
         return Ok(contrato.idcontrato)
       },
       Err(e) => {
-        return Err(e.to_string())
+        return Err(e.into())
       }
     }
 }
 
-pub async fn busca_contrato_id(conn: &mut PgConnection, id: String)
-    -> Result<Contrato, String>{
+pub async fn busca_contrato_id(conn: &Conexao, id: String)
+    -> Result<Contrato, ErroApi>{
+    executa(conn, move |conn| {
+        use crate::schema::contratos::dsl::*;
+
+        let res: Result<Contrato, diesel::result::Error> = contratos.filter(idcontrato.eq(id))
+          .get_result(conn);
+
+        match res{
+          Ok(contrato) => {
+            return Ok(contrato)
+          },
+          Err(e) => {
+            return Err(e.into())
+          }
+        }
+    }).await
+}
+
+
+pub async fn busca_contrato_idsolicitacao(conn: &Conexao, id: String)
+    -> Result<Contrato, ErroApi>{
+    executa(conn, move |conn| {
+        use crate::schema::contratos::dsl::*;
+
+        let res: Result<Contrato, diesel::result::Error> = contratos.filter(idsolicitacaocontrato.eq(id))
+          .get_result(conn);
+
+        match res{
+          Ok(contrato) => {
+            return Ok(contrato)
+          },
+          Err(e) => {
+            return Err(e.into())
+          }
+        }
+    }).await
+}
+
+/// Lê o contrato travando a linha até o fim da transação. Falha com `Conflito` se ele já
+/// não estiver ativo.
+fn busca_contrato_ativo(conn: &mut PgConnection, id: &str) -> Result<Contrato, ErroApi>{
     use crate::schema::contratos::dsl::*;
 
-    let res: Result<Contrato, diesel::result::Error> = contratos.filter(idcontrato.eq(id))
+    let res: Result<Contrato, diesel::result::Error> = diesel::QueryDsl::for_update(contratos
+      .filter(idcontrato.eq(id)))
       .get_result(conn);
-
     match res{
-      Ok(contrato) => {
+      Ok(contrato) if contrato.statuscontrato == StatusContrato::Ativo => {
         return Ok(contrato)
       },
+      Ok(contrato) => {
+        return Err(ErroApi::Conflito(format!("O contrato já está '{}'.", contrato.statuscontrato)))
+      },
       Err(e) => {
-        return Err(e.to_string())
+        return Err(e.into())
       }
     }
 }
 
+/// Encerra um contrato ativo como concluído ou rescindido.
+pub async fn encerra_contrato(conn: &Conexao, id: String, novostatus: StatusContrato, motivo: Option<String>)
+    -> Result<Contrato, ErroApi>{
+    executa(conn, move |conn| {
+        use crate::schema::contratos::dsl::*;
 
-pub async fn busca_contrato_idsolicitacao(conn: &mut PgConnection, id: String)
-    -> Result<Contrato, String>{
-    use crate::schema::contratos::dsl::*;
+        if novostatus == StatusContrato::Ativo{
+          return Err(ErroApi::validacao_campo("status", "O contrato só pode ser encerrado como concluído ou rescindido."))
+        }
 
-    let res: Result<Contrato, diesel::result::Error> = contratos.filter(idsolicitacaocontrato.eq(id))
-      .get_result(conn);
+        conn.transaction(|conn| {
+          busca_contrato_ativo(conn, &id)?;
 
-    match res{
-      Ok(contrato) => {
-        return Ok(contrato)
+          let res: Result<Contrato, diesel::result::Error> = diesel::update(contratos)
+            .filter(idcontrato.eq(&id))
+            .set((
+              statuscontrato.eq(novostatus),
+              motivoencerramento.eq(motivo),
+              dataencerramento.eq(chrono::Utc::now().naive_utc())
+            ))
+            .get_result(conn);
+
+          match res{
+            Ok(contrato) => {
+              return Ok(contrato)
+            },
+            Err(e) => {
+              return Err(e.into())
+            }
+          }
+        })
+    }).await
+}
+
+/// Soma `prazoadicional` ao prazo do contrato e `valoradicional` ao valor. O período reservado
+/// da máquina é estendido na mesma medida, se estiver livre. O PDF salvo é descartado, para
+/// ser gerado de novo com os valores atualizados, e os avisos de término e de atraso voltam a
+/// ser enviados.
+pub async fn renova_contrato(conn: &Conexao, id: String, prazoadicional: f64, valoradicional: Dinheiro)
+    -> Result<Contrato, ErroApi>{
+    executa(conn, move |conn| {
+        use crate::schema::contratos::dsl::*;
+        use crate::schema::{documentos_contratos, reservas_maquinas};
+
+        conn.transaction(|conn| {
+          let contrato = busca_contrato_ativo(conn, &id)?;
+
+          if let Some(mut reserva) = busca_reserva_idsolicitacao(conn, &contrato.idsolicitacaocontrato)?{
+            let novofim = calcula_fim_locacao(reserva.datafim, prazoadicional, contrato.medidatempolocacao)?;
+            reserva.datafim = novofim;
+            verifica_periodo_livre(conn, &reserva)?;
+
+            let res = diesel::update(reservas_maquinas::table)
+              .filter(reservas_maquinas::idreserva.eq(&reserva.idreserva))
+              .set(reservas_maquinas::datafim.eq(novofim))
+              .execute(conn);
+            if let Err(e) = res{
+              return Err(e.into())
+            }
+          }
+
+          let res = diesel::delete(documentos_contratos::table)
+            .filter(documentos_contratos::idcontrato.eq(&id))
+            .execute(conn);
+          if let Err(e) = res{
+            return Err(e.into())
+          }
+
+          let res: Result<Contrato, diesel::result::Error> = diesel::update(contratos)
+            .filter(idcontrato.eq(&id))
+            .set((
+              prazolocacao.eq(contrato.prazolocacao + prazoadicional),
+              valorlocacao.eq(contrato.valorlocacao + valoradicional),
+              dataavisotermino.eq(None::<NaiveDateTime>),
+              dataavisoatraso.eq(None::<NaiveDateTime>)
+            ))
+            .get_result(conn);
+
+          match res{
+            Ok(contrato) => {
+              return Ok(contrato)
+            },
+            Err(e) => {
+              return Err(e.into())
+            }
+          }
+        })
+    }).await
+}
+
+/// Quando a máquina deveria voltar: no fim do período reservado ou, se o contrato foi
+/// rescindido, na data da rescisão.
+fn fim_previsto_contrato(conn: &mut PgConnection, contrato: &Contrato) -> Result<NaiveDateTime, ErroApi>{
+    if contrato.statuscontrato == StatusContrato::Rescindido{
+      if let Some(encerramento) = contrato.dataencerramento{
+        return Ok(encerramento)
+      }
+    }
+    match busca_reserva_idsolicitacao(conn, &contrato.idsolicitacaocontrato)?{
+      Some(reserva) => {
+        return Ok(reserva.datafim)
       },
-      Err(e) => {
-        return Err(e.to_string())
+      None => {
+        return calcula_fim_locacao(contrato.datacontrato, contrato.prazolocacao, contrato.medidatempolocacao)
       }
     }
-}
\ No newline at end of file
+}
+
+/// Registra a volta da máquina ao locador. Um contrato atrasado, ou ativo com o período já
+/// terminado, é concluído; um devolvido antes do fim segue ativo até `encerra_periodo_contrato`
+/// concluí-lo. Se a devolução passou do prazo, os encargos de atraso são lançados e cobrados numa
+/// fatura à vista, com o número da próxima parcela do contrato.
+pub async fn registra_devolucao_contrato(conn: &Conexao, id: String, devolucao: NaiveDateTime, regras: RegrasAtraso)
+    -> Result<(Contrato, Vec<EncargoContrato>, Option<Fatura>), ErroApi>{
+    executa(conn, move |conn| {
+        use crate::schema::contratos::dsl::*;
+        use crate::schema::{encargos_contratos, faturas};
+
+        conn.transaction(|conn| {
+          let res: Result<Contrato, diesel::result::Error> = diesel::QueryDsl::for_update(contratos
+            .filter(idcontrato.eq(&id)))
+            .get_result(conn);
+          let contrato = match res{
+            Ok(contrato) => {
+              contrato
+            },
+            Err(diesel::result::Error::NotFound) => {
+              return Err(ErroApi::NaoEncontrado("Contrato não encontrado.".to_string()))
+            },
+            Err(e) => {
+              return Err(e.into())
+            }
+          };
+          if contrato.datadevolucao.is_some(){
+            return Err(ErroApi::Conflito("A devolução da máquina já foi registrada.".to_string()))
+          }
+          if devolucao < contrato.datacontrato{
+            return Err(ErroApi::validacao_campo("datadevolucao", "A devolução não pode ser anterior ao contrato."))
+          }
+
+          let agora = chrono::Utc::now().naive_utc();
+          let fim = fim_previsto_contrato(conn, &contrato)?;
+          let encargos = calcula_encargos_atraso(&contrato, fim, devolucao, &regras, agora)?;
+
+          let conclui = match contrato.statuscontrato{
+            StatusContrato::Atrasado => true,
+            StatusContrato::Ativo => agora >= fim,
+            _ => false
+          };
+          let res: Result<Contrato, diesel::result::Error> = match conclui{
+            true => diesel::update(contratos)
+              .filter(idcontrato.eq(&id))
+              .set((
+                datadevolucao.eq(devolucao),
+                statuscontrato.eq(StatusContrato::Concluido),
+                dataencerramento.eq(agora)
+              ))
+              .get_result(conn),
+            false => diesel::update(contratos)
+              .filter(idcontrato.eq(&id))
+              .set(datadevolucao.eq(devolucao))
+              .get_result(conn)
+          };
+          let contrato = match res{
+            Ok(contrato) => {
+              contrato
+            },
+            Err(e) => {
+              return Err(e.into())
+            }
+          };
+          if encargos.is_empty(){
+            return Ok((contrato, encargos, None))
+          }
+
+          let res = diesel::insert_into(encargos_contratos::table)
+            .values(&encargos)
+            .execute(conn);
+          if let Err(e) = res{
+            return Err(e.into())
+          }
+
+          let res: Result<Option<i32>, diesel::result::Error> = diesel::QueryDsl::select(faturas::table
+            .filter(faturas::idcontrato.eq(&id)), diesel::dsl::max(faturas::numeroparcela))
+            .get_result(conn);
+          let ultimaparcela = match res{
+            Ok(ultimaparcela) => {
+              ultimaparcela.unwrap_or(0)
+            },
+            Err(e) => {
+              return Err(e.into())
+            }
+          };
+          let fatura = Fatura{
+            idfatura: gera_id(),
+            idcontrato: id.clone(),
+            numeroparcela: ultimaparcela + 1,
+            valor: encargos.iter().map(|encargo| encargo.valor).sum(),
+            valorpago: Dinheiro::ZERO,
+            datavencimento: agora + chrono::TimeDelta::days(DIAS_VENCIMENTO_FATURA),
+            status: StatusFatura::Pendente,
+            datacriacao: agora,
+            datapagamento: None
+          };
+          let res: Result<Fatura, diesel::result::Error> = diesel::insert_into(faturas::table)
+            .values(fatura)
+            .get_result(conn);
+          match res{
+            Ok(fatura) => {
+              return Ok((contrato, encargos, Some(fatura)))
+            },
+            Err(e) => {
+              return Err(e.into())
+            }
+          }
+        })
+    }).await
+}
+
+/// Contratos em que o usuário é locador ou locatário, dos mais recentes para os mais antigos.
+pub async fn busca_contratos_usuario(conn: &Conexao, idusuario: String, comolocador: bool, status: Option<StatusContrato>)
+    -> Result<Vec<Contrato>, ErroApi>{
+    executa(conn, move |conn| {
+        use crate::schema::contratos::dsl::*;
+
+        let mut consulta = diesel::QueryDsl::into_boxed(contratos);
+        consulta = match comolocador{
+          true => consulta.filter(idlocador.eq(idusuario)),
+          false => consulta.filter(idlocatario.eq(idusuario))
+        };
+        if let Some(status) = status{
+          consulta = consulta.filter(statuscontrato.eq(status));
+        }
+
+        let res: Result<Vec<Contrato>, diesel::result::Error> = diesel::QueryDsl::order_by(consulta, datacontrato.desc())
+          .get_results(conn);
+
+        match res{
+          Ok(contratos_) => {
+            return Ok(contratos_)
+          },
+          Err(e) => {
+            return Err(e.into())
+          }
+        }
+    }).await
+}
+
+/// Contratos ativos cujo período reservado já terminou; veja `encerra_periodo_contrato`.
+pub async fn busca_contratos_terminados(conn: &Conexao, agora: NaiveDateTime)
+    -> Result<Vec<Contrato>, ErroApi>{
+    executa(conn, move |conn| {
+        use crate::schema::contratos::dsl::*;
+        use crate::schema::reservas_maquinas;
+
+        let reservas_terminadas = diesel::QueryDsl::select(reservas_maquinas::table
+          .filter(reservas_maquinas::datafim.le(agora)), reservas_maquinas::idsolicitacao);
+        let res: Result<Vec<Contrato>, diesel::result::Error> = contratos
+          .filter(statuscontrato.eq(StatusContrato::Ativo))
+          .filter(idsolicitacaocontrato.eq_any(reservas_terminadas))
+          .get_results(conn);
+
+        match res{
+          Ok(contratos_) => {
+            return Ok(contratos_)
+          },
+          Err(e) => {
+            return Err(e.into())
+          }
+        }
+    }).await
+}
+
+/// Contratos ativos que terminam até `limite` e cujas partes ainda não foram avisadas, com a
+/// data de término de cada um.
+pub async fn busca_contratos_terminando(conn: &Conexao, agora: NaiveDateTime, limite: NaiveDateTime)
+    -> Result<Vec<(Contrato, NaiveDateTime)>, ErroApi>{
+    executa(conn, move |conn| {
+        use crate::schema::contratos::dsl::*;
+        use crate::schema::reservas_maquinas;
+
+        let res: Result<Vec<(Contrato, NaiveDateTime)>, diesel::result::Error> = diesel::QueryDsl::select(diesel::QueryDsl::inner_join(contratos,
+          reservas_maquinas::table.on(reservas_maquinas::idsolicitacao.eq(idsolicitacaocontrato)))
+          .filter(statuscontrato.eq(StatusContrato::Ativo))
+          .filter(dataavisotermino.is_null())
+          .filter(reservas_maquinas::datafim.gt(agora))
+          .filter(reservas_maquinas::datafim.le(limite)), (Contrato::as_select(), reservas_maquinas::datafim))
+          .get_results(conn);
+
+        match res{
+          Ok(contratos_) => {
+            return Ok(contratos_)
+          },
+          Err(e) => {
+            return Err(e.into())
+          }
+        }
+    }).await
+}
+
+pub async fn marca_aviso_termino(conn: &Conexao, id: String)
+    -> Result<(), ErroApi>{
+    executa(conn, move |conn| {
+        use crate::schema::contratos::dsl::*;
+
+        let res = diesel::update(contratos)
+          .filter(idcontrato.eq(id))
+          .set(dataavisotermino.eq(chrono::Utc::now().naive_utc()))
+          .execute(conn);
+
+        match res{
+          Ok(_) => {
+            return Ok(())
+          },
+          Err(e) => {
+            return Err(e.into())
+          }
+        }
+    }).await
+}
+
+/// Encerra o período de um contrato ativo: ele é concluído se a máquina já foi devolvida, ou
+/// fica atrasado até a devolução. Falha com `Conflito` se o período ainda não terminou, como
+/// depois de uma renovação.
+pub async fn encerra_periodo_contrato(conn: &Conexao, id: String)
+    -> Result<Contrato, ErroApi>{
+    executa(conn, move |conn| {
+        use crate::schema::contratos::dsl::*;
+
+        conn.transaction(|conn| {
+          let contrato = busca_contrato_ativo(conn, &id)?;
+          let agora = chrono::Utc::now().naive_utc();
+          if fim_previsto_contrato(conn, &contrato)? > agora{
+            return Err(ErroApi::Conflito("O período do contrato ainda não terminou.".to_string()))
+          }
+
+          let res: Result<Contrato, diesel::result::Error> = match contrato.datadevolucao{
+            Some(_) => diesel::update(contratos)
+              .filter(idcontrato.eq(&id))
+              .set((
+                statuscontrato.eq(StatusContrato::Concluido),
+                dataencerramento.eq(agora)
+              ))
+              .get_result(conn),
+            None => diesel::update(contratos)
+              .filter(idcontrato.eq(&id))
+              .set((
+                statuscontrato.eq(StatusContrato::Atrasado),
+                dataavisoatraso.eq(agora)
+              ))
+              .get_result(conn)
+          };
+
+          match res{
+            Ok(contrato) => {
+              return Ok(contrato)
+            },
+            Err(e) => {
+              return Err(e.into())
+            }
+          }
+        })
+    }).await
+}
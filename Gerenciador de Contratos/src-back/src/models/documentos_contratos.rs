@@ -0,0 +1,66 @@
+use chrono::NaiveDateTime;
+use diesel::{prelude::{Insertable, Queryable}, ExpressionMethods, QueryDsl, RunQueryDsl, Selectable};
+
+use super::{executa, Conexao};
+use crate::erros::ErroApi;
+
+/// PDF de um contrato, gerado uma única vez e compartilhado pelo locador e pelo locatário.
+#[derive(Queryable, Selectable, Insertable, Debug)]
+#[diesel(table_name = crate::schema::documentos_contratos)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct DocumentoContrato{
+    pub idcontrato: String,
+    pub versaomodelo: String,
+    pub hashdocumento: String,
+    pub documento: Vec<u8>,
+    pub datageracao: NaiveDateTime
+}
+
+/// Salva o documento, caso o contrato ainda não tenha um, e devolve o documento salvo.
+/// Se outra requisição salvou um documento antes, é ele que volta, e não o recebido.
+pub async fn cadastra_documento_contrato(conn: &Conexao, novo: DocumentoContrato)
+    -> Result<DocumentoContrato, ErroApi>{
+    executa(conn, move |conn| {
+        use crate::schema::documentos_contratos::dsl::*;
+
+        let id = novo.idcontrato.clone();
+        let res = diesel::insert_into(documentos_contratos)
+            .values(novo)
+            .on_conflict_do_nothing()
+            .execute(conn);
+        if let Err(e) = res{
+            return Err(e.into())
+        }
+
+        let res: Result<DocumentoContrato, diesel::result::Error> = documentos_contratos
+            .filter(idcontrato.eq(id))
+            .get_result(conn);
+        match res{
+            Ok(salvo) => {
+                return Ok(salvo)
+            },
+            Err(e) => {
+                return Err(e.into())
+            }
+        }
+    }).await
+}
+
+pub async fn busca_documento_contrato(conn: &Conexao, id: String)
+    -> Result<DocumentoContrato, ErroApi>{
+    executa(conn, move |conn| {
+        use crate::schema::documentos_contratos::dsl::*;
+
+        let res: Result<DocumentoContrato, diesel::result::Error> = documentos_contratos
+            .filter(idcontrato.eq(id))
+            .get_result(conn);
+        match res{
+            Ok(salvo) => {
+                return Ok(salvo)
+            },
+            Err(e) => {
+                return Err(e.into())
+            }
+        }
+    }).await
+}
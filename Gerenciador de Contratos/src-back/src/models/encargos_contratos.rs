@@ -0,0 +1,132 @@
+use std::{fmt, str::FromStr};
+
+use chrono::NaiveDateTime;
+use diesel::{deserialize::FromSqlRow, expression::AsExpression, prelude::{Insertable, Queryable}, sql_types::Varchar, ExpressionMethods, QueryDsl, RunQueryDsl, Selectable};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use super::{contratos::Contrato, executa, Conexao};
+use crate::{dinheiro::{formata_reais, Dinheiro, Percentual}, erros::ErroApi, ids::gera_id, precos::Tarifa};
+
+/// Motivo de um encargo. Os três são lançados juntos quando a máquina volta atrasada.
+#[derive(Serialize, Deserialize, ToSchema, AsExpression, FromSqlRow, Debug, Clone, Copy, PartialEq, Eq)]
+#[diesel(sql_type = Varchar)]
+pub enum TipoEncargo{
+    /// Horas de uso além do prazo, pelo valor da máquina.
+    TempoExtra,
+    Multa,
+    /// Juros de mora, proporcionais aos dias de atraso.
+    Juros
+}
+
+impl TipoEncargo{
+    pub fn texto(&self) -> &'static str{
+        match self{
+            TipoEncargo::TempoExtra => "TempoExtra",
+            TipoEncargo::Multa => "Multa",
+            TipoEncargo::Juros => "Juros"
+        }
+    }
+}
+
+impl fmt::Display for TipoEncargo{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result{
+        write!(f, "{}", self.texto())
+    }
+}
+
+impl FromStr for TipoEncargo{
+    type Err = ErroApi;
+
+    fn from_str(texto: &str) -> Result<Self, Self::Err>{
+        let todos = [TipoEncargo::TempoExtra, TipoEncargo::Multa, TipoEncargo::Juros];
+        match todos.into_iter().find(|tipo| tipo.texto() == texto.trim()){
+            Some(tipo) => {
+                return Ok(tipo)
+            },
+            None => {
+                return Err(ErroApi::validacao_campo("tipo", "Tipo de encargo inválido."))
+            }
+        }
+    }
+}
+
+enum_texto_sql!(TipoEncargo);
+
+/// Valor cobrado além do valor do contrato. É faturado junto com os outros encargos da
+/// mesma devolução.
+#[derive(Queryable, Selectable, Insertable, Serialize, Deserialize, ToSchema, Debug, Clone, PartialEq)]
+#[diesel(table_name = crate::schema::encargos_contratos)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct EncargoContrato{
+    pub idencargo: String,
+    pub idcontrato: String,
+    pub tipo: TipoEncargo,
+    pub descricao: String,
+    pub valor: Dinheiro,
+    #[schema(value_type = String)]
+    pub datacriacao: NaiveDateTime
+}
+
+/// Valores usados no cálculo dos encargos de uma devolução atrasada.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RegrasAtraso{
+    /// Tarifa por hora da máquina, da tabela de preços dela.
+    pub tarifahora: Tarifa,
+    pub percentualmulta: Percentual,
+    pub percentualjurosmensal: Percentual
+}
+
+/// Encargos de uma devolução feita depois de `fim`. Cada hora começada conta como hora de uso,
+/// e cada dia começado, como dia de atraso. A multa incide uma vez sobre o valor do contrato, e
+/// os juros, por dia, a um trinta avos da taxa mensal. Encargos que dão zero não são lançados.
+pub fn calcula_encargos_atraso(contrato: &Contrato, fim: NaiveDateTime, devolucao: NaiveDateTime, regras: &RegrasAtraso, agora: NaiveDateTime)
+    -> Result<Vec<EncargoContrato>, ErroApi>{
+    if devolucao <= fim{
+        return Ok(vec![])
+    }
+    let segundos = (devolucao - fim).num_seconds();
+    let horas = (segundos + 3599) / 3600;
+    let dias = (horas + 23) / 24;
+
+    let encargos = [
+        (TipoEncargo::TempoExtra, regras.tarifahora.valor(horas as f64)?,
+            format!("{} hora(s) de uso além do prazo, a {} por hora.", horas, formata_reais(regras.tarifahora.valor(1.0)?))),
+        (TipoEncargo::Multa, contrato.valorlocacao.percentual(regras.percentualmulta)?,
+            format!("Multa de {}% sobre o valor do contrato.", regras.percentualmulta)),
+        (TipoEncargo::Juros, contrato.valorlocacao.percentual_proporcional(regras.percentualjurosmensal, dias, 30)?,
+            format!("Juros de mora de {}% ao mês por {} dia(s) de atraso.", regras.percentualjurosmensal, dias))
+    ];
+    return Ok(encargos.into_iter()
+        .filter(|(_, valor, _)| valor.e_positivo())
+        .map(|(tipo, valor, descricao)| EncargoContrato{
+            idencargo: gera_id(),
+            idcontrato: contrato.idcontrato.clone(),
+            tipo,
+            descricao,
+            valor,
+            datacriacao: agora
+        })
+        .collect())
+}
+
+/// Os encargos do contrato, dos mais antigos para os mais recentes.
+pub async fn busca_encargos_idcontrato(conn: &Conexao, id: String)
+    -> Result<Vec<EncargoContrato>, ErroApi>{
+    executa(conn, move |conn| {
+        use crate::schema::encargos_contratos::dsl::*;
+
+        let res: Result<Vec<EncargoContrato>, diesel::result::Error> = encargos_contratos
+          .filter(idcontrato.eq(id))
+          .order_by(datacriacao.asc())
+          .get_results(conn);
+        match res{
+          Ok(encargos) => {
+            return Ok(encargos)
+          },
+          Err(e) => {
+            return Err(e.into())
+          }
+        }
+    }).await
+}
@@ -1,9 +1,12 @@
 use chrono::NaiveDateTime;
-use diesel::{prelude::{Insertable, Queryable}, ExpressionMethods, PgConnection, QueryDsl, RunQueryDsl, Selectable, SelectableHelper};
+use diesel::{prelude::{Insertable, Queryable}, ExpressionMethods, QueryDsl, RunQueryDsl, Selectable, SelectableHelper};
 use serde::{Deserialize, Serialize};
+
+use super::{executa, Conexao};
+use crate::erros::ErroApi;
 use utoipa::ToSchema;
 
-use crate::controllers::cria_conn;
+use crate::controllers::cria_pool;
 
 #[derive(Queryable, Selectable, Insertable, Serialize, Deserialize, ToSchema)]
 #[diesel(table_name = crate::schema::enderecos)]
@@ -20,85 +23,94 @@ pub struct Endereco{
     pub complemento: String
 }
 
-pub async fn cadastra_endereco(conn: &mut PgConnection, endereco: Endereco)
-    -> Result<Endereco, String>{
-    use crate::schema::enderecos::dsl::*;
+pub async fn cadastra_endereco(conn: &Conexao, endereco: Endereco)
+    -> Result<Endereco, ErroApi>{
+    executa(conn, move |conn| {
+        use crate::schema::enderecos::dsl::*;
 
-    let res: Result<Endereco, diesel::result::Error> = diesel::insert_into(enderecos)
-        .values(endereco)
-        .get_result(conn);
+        let res: Result<Endereco, diesel::result::Error> = diesel::insert_into(enderecos)
+            .values(endereco)
+            .get_result(conn);
 
-    match res{
-        Ok(endereco) => {
-            return Ok(endereco)
-        },
-        Err(e) => {
-            return Err(e.to_string())
+        match res{
+            Ok(endereco) => {
+                return Ok(endereco)
+            },
+            Err(e) => {
+                return Err(e.into())
+            }
         }
-    }
+    }).await
 }
 
-pub async fn busca_endereco_id(conn: &mut PgConnection, id: String)
-    -> Result<Endereco, String>{
-    use crate::schema::enderecos::dsl::*;
+pub async fn busca_endereco_id(conn: &Conexao, id: String)
+    -> Result<Endereco, ErroApi>{
+    executa(conn, move |conn| {
+        use crate::schema::enderecos::dsl::*;
 
-    let res: Result<Endereco, diesel::result::Error> = enderecos.filter(idendereco.eq(id))
-        .get_result(conn);
+        let res: Result<Endereco, diesel::result::Error> = enderecos.filter(idendereco.eq(id))
+            .get_result(conn);
 
-    match res{
-        Ok(endereco) => {
-            return Ok(endereco)
-        },
-        Err(e) => {
-            return Err(e.to_string())
+        match res{
+            Ok(endereco) => {
+                return Ok(endereco)
+            },
+            Err(e) => {
+                return Err(e.into())
+            }
         }
-    }
+    }).await
 }
 
 pub async fn deleta_endereco(id: String)
-    -> Result<String, String>{
-    // Só utilizar em testes
-    use crate::schema::enderecos::dsl::*;
-
-    let conn = &mut cria_conn().unwrap();
-
-    let res: Result<Endereco, diesel::result::Error> = diesel::delete(enderecos)
-        .filter(idendereco.eq(id))
-        .get_result(conn);
-
-    match res{
-        Ok(enderecoapagado) => {
-            return Ok(enderecoapagado.idendereco)
-        },
-        Err(e) => {
-            return Err(e.to_string())
+    -> Result<String, ErroApi>{
+    let pool = cria_pool().unwrap();
+    let conn = &pool.get().await.unwrap();
+
+    executa(conn, move |conn| {
+        // Só utilizar em testes
+        use crate::schema::enderecos::dsl::*;
+
+        let res: Result<Endereco, diesel::result::Error> = diesel::delete(enderecos)
+            .filter(idendereco.eq(id))
+            .get_result(conn);
+
+        match res{
+            Ok(enderecoapagado) => {
+                return Ok(enderecoapagado.idendereco)
+            },
+            Err(e) => {
+                return Err(e.into())
+            }
         }
-    }
+    }).await
 }
 
-pub async fn atualiza_endereco(conn: &mut PgConnection, endereco: Endereco)
-    -> Result<Endereco, String>{
-    use crate::schema::enderecos::dsl::*;
-
-    let res: Result<Endereco, diesel::result::Error> = diesel::update(enderecos.filter(idendereco.eq(endereco.idendereco)))
-        .set((
-            pais.eq(endereco.pais),
-            estado.eq(endereco.estado),
-            cidade.eq(endereco.cidade),
-            cep.eq(endereco.cep),
-            bairro.eq(endereco.bairro),
-            logradouro.eq(endereco.logradouro),
-            numero.eq(endereco.numero),
-            complemento.eq(endereco.complemento)
-        ))
-        .get_result(conn);
-
-    match res{
-        Ok(endereco) => {
-            return Ok(endereco)
-        },
-        Err(e) => {
-            return Err(e.to_string())
+pub async fn atualiza_endereco(conn: &Conexao, endereco: Endereco)
+    -> Result<Endereco, ErroApi>{
+    executa(conn, move |conn| {
+        use crate::schema::enderecos::dsl::*;
+
+        let res: Result<Endereco, diesel::result::Error> = diesel::update(enderecos.filter(idendereco.eq(endereco.idendereco)))
+            .set((
+                pais.eq(endereco.pais),
+                estado.eq(endereco.estado),
+                cidade.eq(endereco.cidade),
+                cep.eq(endereco.cep),
+                bairro.eq(endereco.bairro),
+                logradouro.eq(endereco.logradouro),
+                numero.eq(endereco.numero),
+                complemento.eq(endereco.complemento)
+            ))
+            .get_result(conn);
+
+        match res{
+            Ok(endereco) => {
+                return Ok(endereco)
+            },
+            Err(e) => {
+                return Err(e.into())
+            }
         }
-    }
+    }).await
 }
\ No newline at end of file
@@ -1,8 +1,10 @@
 use chrono::NaiveDateTime;
-use diesel::{prelude::{Insertable, Queryable}, ExpressionMethods, PgConnection, QueryDsl, RunQueryDsl, Selectable, SelectableHelper};
+use diesel::{prelude::{Insertable, Queryable}, ExpressionMethods, QueryDsl, RunQueryDsl, Selectable, SelectableHelper};
 use serde::{Deserialize, Serialize};
 
-use crate::controllers::cria_conn;
+use super::{executa, Conexao};
+use crate::erros::ErroApi;
+use crate::controllers::cria_pool;
 
 #[derive(Queryable, Selectable, Insertable, Serialize, Deserialize)]
 #[diesel(table_name = crate::schema::enderecos_usuarios)]
@@ -13,59 +15,86 @@ pub struct EnderecoUsuario{
     pub idusuario: String
 }
 
-pub async fn cadastra_endereco_usuario(conn: &mut PgConnection, dados: EnderecoUsuario)
-    -> Result<String, String>{
-    use crate::schema::enderecos_usuarios::dsl::*;
+pub async fn cadastra_endereco_usuario(conn: &Conexao, dados: EnderecoUsuario)
+    -> Result<String, ErroApi>{
+    executa(conn, move |conn| {
+        use crate::schema::enderecos_usuarios::dsl::*;
 
-    let res: Result<EnderecoUsuario, diesel::result::Error> = diesel::insert_into(enderecos_usuarios)
-        .values(dados)
-        .get_result(conn);
-    
-    match res{
-        Ok(dados) => {
-            return Ok(dados.idenderecousuario)
-        },
-        Err(e) => {
-            return Err(e.to_string())
+        let res: Result<EnderecoUsuario, diesel::result::Error> = diesel::insert_into(enderecos_usuarios)
+            .values(dados)
+            .get_result(conn);
+
+        match res{
+            Ok(dados) => {
+                return Ok(dados.idenderecousuario)
+            },
+            Err(e) => {
+                return Err(e.into())
+            }
+        }
+    }).await
+}
+
+pub async fn busca_enderecousuario_idusuario(conn: &Conexao, id: String)
+    -> Result<EnderecoUsuario, ErroApi>{
+    executa(conn, move |conn| {
+        use crate::schema::enderecos_usuarios::dsl::*;
+
+        let res: Result<EnderecoUsuario, diesel::result::Error> = enderecos_usuarios
+            .filter(idusuario.eq(id))
+            .get_result(conn);
+
+        match res{
+            Ok(endereco ) => {
+                return Ok(endereco)
+            },
+            Err(e) => {
+                return Err(e.into())
+            }
         }
-    }
+    }).await
 }
 
-pub async fn busca_enderecousuario_idusuario(conn: &mut PgConnection, id: String)
-    -> Result<EnderecoUsuario, String>{
-    use crate::schema::enderecos_usuarios::dsl::*;
+pub async fn busca_enderecousuario_idendereco(conn: &Conexao, id: String)
+    -> Result<EnderecoUsuario, ErroApi>{
+    executa(conn, move |conn| {
+        use crate::schema::enderecos_usuarios::dsl::*;
 
-    let res: Result<EnderecoUsuario, diesel::result::Error> = enderecos_usuarios
-        .filter(idusuario.eq(id))
-        .get_result(conn);
+        let res: Result<EnderecoUsuario, diesel::result::Error> = enderecos_usuarios
+            .filter(idendereco.eq(id))
+            .first(conn);
 
-    match res{
-        Ok(endereco ) => {
-            return Ok(endereco)
-        },
-        Err(e) => {
-            return Err(e.to_string())
+        match res{
+            Ok(endereco) => {
+                return Ok(endereco)
+            },
+            Err(e) => {
+                return Err(e.into())
+            }
         }
-    }
+    }).await
 }
 
 pub async fn deleta_endereco_usuario_idendereco(id: String)
-    -> Result<String, String>{
-    // Só utilizar em testes
-    use crate::schema::enderecos_usuarios::dsl::*;
+    -> Result<String, ErroApi>{
+    let pool = cria_pool().unwrap();
+    let conn = &pool.get().await.unwrap();
 
-    let conn = &mut cria_conn().unwrap();
+    executa(conn, move |conn| {
+        // Só utilizar em testes
+        use crate::schema::enderecos_usuarios::dsl::*;
 
-    let res: Result<EnderecoUsuario, diesel::result::Error> = diesel::delete(enderecos_usuarios)
-        .filter(idendereco.eq(id))
-        .get_result(conn);
+        let res: Result<EnderecoUsuario, diesel::result::Error> = diesel::delete(enderecos_usuarios)
+            .filter(idendereco.eq(id))
+            .get_result(conn);
 
-    match res{
-        Ok(enderecoapagado) => {
-            return Ok(enderecoapagado.idenderecousuario)
-        },
-        Err(e) => {
-            return Err(e.to_string())
+        match res{
+            Ok(enderecoapagado) => {
+                return Ok(enderecoapagado.idenderecousuario)
+            },
+            Err(e) => {
+                return Err(e.into())
+            }
         }
-    }
+    }).await
 }
\ No newline at end of file
@@ -0,0 +1,300 @@
+use std::{fmt, str::FromStr};
+
+use chrono::NaiveDateTime;
+use diesel::{deserialize::FromSqlRow, expression::AsExpression, prelude::{Insertable, Queryable}, sql_types::Varchar, Connection, ExpressionMethods, JoinOnDsl, PgConnection, QueryDsl, RunQueryDsl, Selectable, SelectableHelper};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use super::{cobrancas_pix::cancela_cobrancas_pendentes, contratos::Contrato, executa, Conexao};
+use crate::{dinheiro::Dinheiro, erros::ErroApi};
+
+/// Dias que o locatário tem para pagar cada parcela, contados do início do período cobrado.
+pub const DIAS_VENCIMENTO_FATURA: i64 = 5;
+
+/// A fatura nasce pendente, passa a vencida quando a data de vencimento chega sem que ela
+/// esteja quitada e termina paga. Uma fatura vencida ainda pode ser paga.
+#[derive(Serialize, Deserialize, ToSchema, AsExpression, FromSqlRow, Debug, Clone, Copy, PartialEq, Eq)]
+#[diesel(sql_type = Varchar)]
+pub enum StatusFatura{
+    Pendente,
+    Vencida,
+    Paga
+}
+
+impl StatusFatura{
+    pub fn texto(&self) -> &'static str{
+        match self{
+            StatusFatura::Pendente => "Pendente",
+            StatusFatura::Vencida => "Vencida",
+            StatusFatura::Paga => "Paga"
+        }
+    }
+}
+
+impl fmt::Display for StatusFatura{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result{
+        write!(f, "{}", self.texto())
+    }
+}
+
+impl FromStr for StatusFatura{
+    type Err = ErroApi;
+
+    fn from_str(texto: &str) -> Result<Self, Self::Err>{
+        let todos = [StatusFatura::Pendente, StatusFatura::Vencida, StatusFatura::Paga];
+        match todos.into_iter().find(|status| status.texto() == texto.trim()){
+            Some(status) => {
+                return Ok(status)
+            },
+            None => {
+                return Err(ErroApi::validacao_campo("status", "Status de fatura inválido."))
+            }
+        }
+    }
+}
+
+enum_texto_sql!(StatusFatura);
+
+#[derive(Queryable, Selectable, Insertable, Serialize, Deserialize, ToSchema, Debug, Clone)]
+#[diesel(table_name = crate::schema::faturas)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct Fatura{
+    pub idfatura: String,
+    pub idcontrato: String,
+    /// Começa em 1 e segue a ordem de vencimento das parcelas do contrato.
+    pub numeroparcela: i32,
+    pub valor: Dinheiro,
+    /// Soma dos pagamentos registrados, que podem ser parciais.
+    pub valorpago: Dinheiro,
+    #[schema(value_type = String)]
+    pub datavencimento: NaiveDateTime,
+    pub status: StatusFatura,
+    #[schema(value_type = String)]
+    pub datacriacao: NaiveDateTime,
+    /// Data do pagamento que quitou a fatura.
+    #[schema(value_type = Option<String>)]
+    pub datapagamento: Option<NaiveDateTime>
+}
+
+/// Gera e salva as faturas do que falta faturar do contrato. A linha do contrato fica travada
+/// do cálculo do valor restante até a gravação, então duas gerações ao mesmo tempo não faturam
+/// o mesmo valor. `monta` recebe o contrato, as faturas que ele já tem e o valor restante
+/// (valor da locação mais encargos, menos o já faturado) e devolve as faturas novas.
+pub async fn cadastra_faturas_contrato<F>(conn: &Conexao, id: String, monta: F)
+    -> Result<(Vec<Fatura>, Dinheiro), ErroApi>
+where
+    F: FnOnce(&mut PgConnection, &Contrato, &[Fatura], Dinheiro) -> Result<Vec<Fatura>, ErroApi> + Send + 'static,
+{
+    executa(conn, move |conn| {
+        use crate::schema::faturas::dsl::*;
+        use crate::schema::{contratos, encargos_contratos};
+
+        conn.transaction(|conn| {
+          let res: Result<Contrato, diesel::result::Error> = contratos::table
+            .filter(contratos::idcontrato.eq(&id))
+            .for_update()
+            .get_result(conn);
+          let contrato = match res{
+            Ok(contrato) => {
+              contrato
+            },
+            Err(diesel::result::Error::NotFound) => {
+              return Err(ErroApi::NaoEncontrado("Contrato não encontrado.".to_string()))
+            },
+            Err(e) => {
+              return Err(e.into())
+            }
+          };
+
+          let existentes: Vec<Fatura> = faturas
+            .filter(idcontrato.eq(&id))
+            .order_by(numeroparcela.asc())
+            .get_results(conn)?;
+          let encargos: Vec<Dinheiro> = encargos_contratos::table
+            .filter(encargos_contratos::idcontrato.eq(&id))
+            .select(encargos_contratos::valor)
+            .get_results(conn)?;
+          let faturado: Dinheiro = existentes.iter().map(|fatura| fatura.valor).sum();
+          let restante = contrato.valorlocacao + encargos.into_iter().sum::<Dinheiro>() - faturado;
+          if !restante.e_positivo(){
+            return Err(ErroApi::Conflito("As faturas do contrato já cobrem todo o valor dele.".to_string()))
+          }
+
+          let novas = monta(conn, &contrato, &existentes, restante)?;
+          let res: Result<Vec<Fatura>, diesel::result::Error> = diesel::insert_into(faturas)
+            .values(&novas)
+            .get_results(conn);
+          match res{
+            Ok(salvas) => {
+              return Ok((salvas, restante))
+            },
+            Err(e) => {
+              return Err(e.into())
+            }
+          }
+        })
+    }).await
+}
+
+pub async fn busca_fatura_id(conn: &Conexao, id: String)
+    -> Result<Fatura, ErroApi>{
+    executa(conn, move |conn| {
+        use crate::schema::faturas::dsl::*;
+
+        let res: Result<Fatura, diesel::result::Error> = faturas
+          .filter(idfatura.eq(id))
+          .get_result(conn);
+        match res{
+          Ok(fatura) => {
+            return Ok(fatura)
+          },
+          Err(diesel::result::Error::NotFound) => {
+            return Err(ErroApi::NaoEncontrado("Fatura não encontrada.".to_string()))
+          },
+          Err(e) => {
+            return Err(e.into())
+          }
+        }
+    }).await
+}
+
+/// As faturas do contrato, na ordem das parcelas.
+pub async fn busca_faturas_idcontrato(conn: &Conexao, id: String)
+    -> Result<Vec<Fatura>, ErroApi>{
+    executa(conn, move |conn| {
+        use crate::schema::faturas::dsl::*;
+
+        let res: Result<Vec<Fatura>, diesel::result::Error> = faturas
+          .filter(idcontrato.eq(id))
+          .order_by(numeroparcela.asc())
+          .get_results(conn);
+        match res{
+          Ok(faturas_) => {
+            return Ok(faturas_)
+          },
+          Err(e) => {
+            return Err(e.into())
+          }
+        }
+    }).await
+}
+
+/// Faturas dos contratos em que o usuário é locador ou locatário, das que vencem primeiro
+/// para as que vencem depois.
+pub async fn busca_faturas_usuario(conn: &Conexao, idusuario: String, comolocador: bool, filtro: Option<StatusFatura>)
+    -> Result<Vec<Fatura>, ErroApi>{
+    executa(conn, move |conn| {
+        use crate::schema::faturas::dsl::*;
+        use crate::schema::contratos;
+
+        let mut consulta = faturas
+          .inner_join(contratos::table.on(contratos::idcontrato.eq(idcontrato)))
+          .select(Fatura::as_select())
+          .into_boxed();
+        consulta = match comolocador{
+          true => consulta.filter(contratos::idlocador.eq(idusuario)),
+          false => consulta.filter(contratos::idlocatario.eq(idusuario))
+        };
+        if let Some(filtro) = filtro{
+          consulta = consulta.filter(status.eq(filtro));
+        }
+
+        let res: Result<Vec<Fatura>, diesel::result::Error> = consulta
+          .order_by((datavencimento.asc(), numeroparcela.asc()))
+          .get_results(conn);
+        match res{
+          Ok(faturas_) => {
+            return Ok(faturas_)
+          },
+          Err(e) => {
+            return Err(e.into())
+          }
+        }
+    }).await
+}
+
+/// Soma `valor` ao que já foi pago da fatura. Quando o total chega ao valor da parcela, ela
+/// fica paga. A linha fica travada durante a soma, para que dois pagamentos ao mesmo tempo
+/// não passem do valor da fatura. Roda dentro da transação de quem chama.
+pub fn soma_pagamento_fatura(conn: &mut PgConnection, id: &str, valor_: Dinheiro, agora: NaiveDateTime)
+    -> Result<Fatura, ErroApi>{
+    use crate::schema::faturas::dsl::*;
+
+    let res: Result<Fatura, diesel::result::Error> = faturas
+      .filter(idfatura.eq(id))
+      .for_update()
+      .get_result(conn);
+    let fatura = match res{
+      Ok(fatura) => {
+        fatura
+      },
+      Err(diesel::result::Error::NotFound) => {
+        return Err(ErroApi::NaoEncontrado("Fatura não encontrada.".to_string()))
+      },
+      Err(e) => {
+        return Err(e.into())
+      }
+    };
+    if fatura.status == StatusFatura::Paga{
+      return Err(ErroApi::Conflito("A fatura já foi paga.".to_string()))
+    }
+
+    let restante = fatura.valor - fatura.valorpago;
+    if valor_ > restante{
+      return Err(ErroApi::validacao_campo("valor", "O valor pago é maior que o valor em aberto da fatura."))
+    }
+    let pago = fatura.valorpago + valor_;
+    let quitada = valor_ == restante;
+
+    let res: Result<Fatura, diesel::result::Error> = diesel::update(faturas)
+      .filter(idfatura.eq(id))
+      .set((
+        valorpago.eq(pago),
+        status.eq(if quitada { StatusFatura::Paga } else { fatura.status }),
+        datapagamento.eq(if quitada { Some(agora) } else { None })
+      ))
+      .get_result(conn);
+    match res{
+      Ok(fatura) => {
+        return Ok(fatura)
+      },
+      Err(e) => {
+        return Err(e.into())
+      }
+    }
+}
+
+/// Registra um pagamento recebido fora do PIX; veja `soma_pagamento_fatura`. As cobranças PIX
+/// pendentes da fatura eram do valor antigo e são canceladas.
+pub async fn registra_pagamento_fatura(conn: &Conexao, id: String, valor_: Dinheiro, agora: NaiveDateTime)
+    -> Result<Fatura, ErroApi>{
+    executa(conn, move |conn| {
+        conn.transaction(|conn| {
+          let fatura = soma_pagamento_fatura(conn, &id, valor_, agora)?;
+          cancela_cobrancas_pendentes(conn, &id)?;
+          return Ok(fatura)
+        })
+    }).await
+}
+
+/// Marca como vencidas as faturas pendentes cujo vencimento já passou e as devolve.
+pub async fn marca_faturas_vencidas(conn: &Conexao, agora: NaiveDateTime)
+    -> Result<Vec<Fatura>, ErroApi>{
+    executa(conn, move |conn| {
+        use crate::schema::faturas::dsl::*;
+
+        let res: Result<Vec<Fatura>, diesel::result::Error> = diesel::update(faturas)
+          .filter(status.eq(StatusFatura::Pendente))
+          .filter(datavencimento.lt(agora))
+          .set(status.eq(StatusFatura::Vencida))
+          .get_results(conn);
+        match res{
+          Ok(vencidas) => {
+            return Ok(vencidas)
+          },
+          Err(e) => {
+            return Err(e.into())
+          }
+        }
+    }).await
+}
@@ -0,0 +1,29 @@
+use std::collections::HashMap;
+
+use diesel::{ExpressionMethods, QueryDsl, RunQueryDsl};
+
+use super::{executa, Conexao};
+use crate::erros::ErroApi;
+
+/// Os UUIDv7 que substituíram as chaves antigas recebidas, por chave antiga. As chaves
+/// sem correspondência ficam de fora.
+pub async fn busca_ids_novos(conn: &Conexao, legados: Vec<String>)
+    -> Result<HashMap<String, String>, ErroApi>{
+    executa(conn, move |conn| {
+        use crate::schema::ids_legados::dsl::*;
+
+        let res: Result<Vec<(String, String)>, diesel::result::Error> = ids_legados
+            .filter(idlegado.eq_any(legados))
+            .select((idlegado, idnovo))
+            .load(conn);
+
+        match res{
+            Ok(ids) => {
+                return Ok(ids.into_iter().collect())
+            },
+            Err(e) => {
+                return Err(e.into())
+            }
+        }
+    }).await
+}
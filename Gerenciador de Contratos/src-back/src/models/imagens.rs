@@ -2,7 +2,9 @@ use diesel::prelude::*;
 use hyper::HeaderMap;
 use serde::{Deserialize, Serialize};
 
-use crate::controllers::gera_hash;
+use super::{executa, Conexao};
+use crate::erros::ErroApi;
+use crate::ids::gera_id;
 use crate::schema::imagens::{self};
 use diesel::Queryable;
 
@@ -14,62 +16,68 @@ pub struct Imagem {
     pub link: String
 }
 
-pub async fn cadastra_imagem(conn: &mut PgConnection, nome: String) -> Result<String, String> {
-    use crate::schema::imagens;
-    let id = gera_hash(&nome);
-    let nova_imagem = Imagem {
-        idimagem: id.clone(),
-        nome,
-        link: "".to_string()
-    };
+pub async fn cadastra_imagem(conn: &Conexao, nome: String) -> Result<String, ErroApi> {
+    executa(conn, move |conn| {
+        use crate::schema::imagens;
+        let id = gera_id();
+        let nova_imagem = Imagem {
+            idimagem: id.clone(),
+            nome,
+            link: "".to_string()
+        };
 
-    let res = diesel::insert_into(imagens::table)
-        .values(&nova_imagem)
-        .execute(conn);
+        let res = diesel::insert_into(imagens::table)
+            .values(&nova_imagem)
+            .execute(conn);
 
-    match res {
-        Ok(_) => Ok(id),
-        Err(e) => Err(e.to_string()),
-    }
+        match res {
+            Ok(_) => Ok(id),
+            Err(e) => Err(e.into()),
+        }
+    }).await
 }
 
-pub async fn atualiza_link_imagem(conn: &mut PgConnection, id: String, novolink: String)
-    -> Result<String, String>{
-    use self::imagens::dsl::*;
+pub async fn atualiza_link_imagem(conn: &Conexao, id: String, novolink: String)
+    -> Result<String, ErroApi>{
+    executa(conn, move |conn| {
+        use self::imagens::dsl::*;
 
-    let res = diesel::update(imagens)
-        .filter(idimagem.eq(id))
-        .set(link.eq(novolink))
-        .execute(conn);
+        let res = diesel::update(imagens)
+            .filter(idimagem.eq(id))
+            .set(link.eq(novolink))
+            .execute(conn);
 
-    match res{
-        Ok(qtd) => {
-            println!("{} imagens atualizadas", qtd);
-            return Ok(qtd.to_string())
-        },
-        Err(e) => {
-            return Err(e.to_string())
+        match res{
+            Ok(qtd) => {
+                println!("{} imagens atualizadas", qtd);
+                return Ok(qtd.to_string())
+            },
+            Err(e) => {
+                return Err(e.into())
+            }
         }
-    }
+    }).await
 }
 
-pub async fn recupera_imagem(conn: &mut PgConnection, id: String) -> Result<(HeaderMap, String), String> {
-    use crate::schema::imagens::dsl::*;
+pub async fn recupera_imagem(conn: &Conexao, id: String) -> Result<(HeaderMap, String), ErroApi> {
+    executa(conn, move |conn| {
+        use crate::schema::imagens::dsl::*;
 
-    let imagem: Result<Imagem, diesel::result::Error> = imagens.filter(idimagem.eq(id)).first(conn);
+        let imagem: Result<Imagem, diesel::result::Error> = imagens.filter(idimagem.eq(id)).first(conn);
 
-    let imagem = match imagem{
-        Ok(imagem) => {imagem},
-        Err(e) => {
-            return Err(e.to_string())
-        }
-    };
+        let imagem = match imagem{
+            Ok(imagem) => {imagem},
+            Err(e) => {
+                return Err(e.into())
+            }
+        };
 
-    let mut headers = HeaderMap::new();
-    headers.insert(
-        axum::http::header::CONTENT_TYPE,
-        axum::http::header::HeaderValue::from_static("image/jpeg"),
-    );
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            axum::http::header::CONTENT_TYPE,
+            axum::http::header::HeaderValue::from_static("image/jpeg"),
+        );
 
-    return Ok((headers, imagem.link))
+        return Ok((headers, imagem.link))
+    }).await
 }
@@ -3,7 +3,9 @@ use diesel::prelude::*;
 use hyper::HeaderMap;
 use serde::{Deserialize, Serialize};
 
-use crate::controllers::gera_hash;
+use super::{executa, Conexao};
+use crate::erros::ErroApi;
+use crate::ids::gera_id;
 use crate::models::imagens::recupera_imagem;
 use crate::schema::imagens_maquinas::{self};
 use diesel::Queryable;
@@ -17,43 +19,49 @@ pub struct ImagemMaquina{
     pub idmaquina: String
 }
 
-pub async fn cadastra_imagem_maquina(conn: &mut PgConnection, idmaquina: String, idimagem: String)
-    -> Result<String, String>{
-    use crate::schema::imagens_maquinas;
-    let id = gera_hash(&idmaquina);
-    let nova_imagem = ImagemMaquina {
-        idimagemmaquina: id.clone(),
-        idimagem,
-        idmaquina
-    };
-
-    let res = diesel::insert_into(imagens_maquinas::table)
-        .values(&nova_imagem)
-        .execute(conn);
-
-    match res {
-        Ok(_) => Ok(id),
-        Err(e) => {
-            println!("Erro [BACK/DB]: {}", e);
-            return Err(e.to_string())
-        },
-    }
+pub async fn cadastra_imagem_maquina(conn: &Conexao, idmaquina: String, idimagem: String)
+    -> Result<String, ErroApi>{
+    executa(conn, move |conn| {
+        use crate::schema::imagens_maquinas;
+        let id = gera_id();
+        let nova_imagem = ImagemMaquina {
+            idimagemmaquina: id.clone(),
+            idimagem,
+            idmaquina
+        };
+
+        let res = diesel::insert_into(imagens_maquinas::table)
+            .values(&nova_imagem)
+            .execute(conn);
+
+        match res {
+            Ok(_) => Ok(id),
+            Err(e) => {
+                println!("Erro [BACK/DB]: {}", e);
+                return Err(e.into())
+            },
+        }
+    }).await
 }
 
-pub async fn recupera_imagem_maquina(conn: &mut PgConnection, id: String)
-    -> Result<(HeaderMap, String), String> {
-    use crate::schema::imagens_maquinas::dsl::*;
+pub async fn recupera_imagem_maquina(conn: &Conexao, id: String)
+    -> Result<(HeaderMap, String), ErroApi> {
+    let imagem = executa(conn, move |conn| {
+        use crate::schema::imagens_maquinas::dsl::*;
 
-    let imagem: Result<ImagemMaquina, diesel::result::Error> = imagens_maquinas
-        .filter(idmaquina.eq(id))
-        .first(conn);
+        let imagem: Result<ImagemMaquina, diesel::result::Error> = imagens_maquinas
+            .filter(idmaquina.eq(id))
+            .first(conn);
 
-    let imagem = match imagem{
-        Ok(imagem) => {imagem},
-        Err(e) => {
-            return Err(e.to_string())
+        match imagem{
+            Ok(imagem) => {
+                return Ok(imagem)
+            },
+            Err(e) => {
+                return Err(e.into())
+            }
         }
-    };
+    }).await?;
 
     let res = recupera_imagem(conn, imagem.idimagem).await;
 
@@ -68,19 +76,23 @@ pub async fn recupera_imagem_maquina(conn: &mut PgConnection, id: String)
     }
 }
 
-pub async fn recupera_imagens_maquina(conn: &mut PgConnection, id: Json<String>) -> Result<Vec<String>, String> {
-    use crate::schema::imagens_maquinas::dsl::*;
+pub async fn recupera_imagens_maquina(conn: &Conexao, id: Json<String>) -> Result<Vec<String>, ErroApi> {
+    let imagem = executa(conn, move |conn| {
+        use crate::schema::imagens_maquinas::dsl::*;
 
-    let imagem: Result<Vec<ImagemMaquina>, diesel::result::Error> = imagens_maquinas
-        .filter(idmaquina.eq(id.0))
-        .get_results(conn);
+        let imagem: Result<Vec<ImagemMaquina>, diesel::result::Error> = imagens_maquinas
+            .filter(idmaquina.eq(id.0))
+            .get_results(conn);
 
-    let imagem = match imagem{
-        Ok(imagem) => {imagem},
-        Err(e) => {
-            return Err(e.to_string())
+        match imagem{
+            Ok(imagem) => {
+                return Ok(imagem)
+            },
+            Err(e) => {
+                return Err(e.into())
+            }
         }
-    };
+    }).await?;
 
     let mut imagens = Vec::new();
     for img in imagem{
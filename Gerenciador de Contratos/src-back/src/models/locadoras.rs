@@ -1,7 +1,9 @@
-use diesel::{prelude::{Insertable, Queryable}, query_dsl::methods::FilterDsl, ExpressionMethods, PgConnection, RunQueryDsl, Selectable};
+use diesel::{prelude::{Insertable, Queryable}, query_dsl::methods::FilterDsl, ExpressionMethods, RunQueryDsl, Selectable};
 use serde::{Deserialize, Serialize};
 
-use crate::controllers::cria_conn;
+use super::{executa, Conexao};
+use crate::{dinheiro::Percentual, erros::ErroApi};
+use crate::controllers::cria_pool;
 
 #[derive(Queryable, Selectable, Insertable, Serialize, Deserialize, Debug)]
 #[diesel(table_name = crate::schema::locadoras)]
@@ -10,76 +12,113 @@ pub struct Locadora{
     pub idlocadora: String,
     pub idusuario: String,
     pub idendereco: String,
-    pub idconta: String
+    pub idconta: String,
+    /// Multa por devolução atrasada, em porcentagem do valor do contrato.
+    pub percentualmulta: Percentual,
+    /// Juros de mora por mês de atraso, em porcentagem do valor do contrato, cobrados por dia.
+    pub percentualjurosmensal: Percentual
 }
 
-pub async fn cadastra_locadora(conn: &mut PgConnection, locadora: Locadora)
-    -> Result<String, String>{
-    use crate::schema::locadoras::dsl::*;
-    let res: Result<Locadora, diesel::result::Error> = diesel::insert_into(locadoras)
-        .values(locadora)
-        .get_result(conn);
-    match res{
-        Ok(locadora) => {
-            return Ok(locadora.idlocadora)
-        },
-        Err(e) => {
-            return Err(e.to_string())
+pub async fn cadastra_locadora(conn: &Conexao, locadora: Locadora)
+    -> Result<String, ErroApi>{
+    executa(conn, move |conn| {
+        use crate::schema::locadoras::dsl::*;
+        let res: Result<Locadora, diesel::result::Error> = diesel::insert_into(locadoras)
+            .values(locadora)
+            .get_result(conn);
+        match res{
+            Ok(locadora) => {
+                return Ok(locadora.idlocadora)
+            },
+            Err(e) => {
+                return Err(e.into())
+            }
         }
-    }
+    }).await
 }
 
-pub async fn busca_locadora_idusuario(conn: &mut PgConnection, id: String)
-    -> Result<Locadora, String>{
-    use crate::schema::locadoras::dsl::*;
+pub async fn busca_locadora_idusuario(conn: &Conexao, id: String)
+    -> Result<Locadora, ErroApi>{
+    executa(conn, move |conn| {
+        use crate::schema::locadoras::dsl::*;
 
-    let res: Result<Locadora, diesel::result::Error> = locadoras.filter(idusuario.eq(id))
-        .first(conn);
-    
-    match res{
-        Ok(locadora) => {
-            return Ok(locadora)
-        },
-        Err(e) => {
-            return Err(e.to_string())
+        let res: Result<Locadora, diesel::result::Error> = locadoras.filter(idusuario.eq(id))
+            .first(conn);
+
+        match res{
+            Ok(locadora) => {
+                return Ok(locadora)
+            },
+            Err(e) => {
+                return Err(e.into())
+            }
         }
-    }
+    }).await
 }
 
-pub async fn busca_locadora_idlocadora(conn: &mut PgConnection, id: String) 
-    -> Result<Locadora, String>{
-    use crate::schema::locadoras::dsl::*;
-
-    let res: Result<Locadora, diesel::result::Error> = locadoras.filter(idlocadora.eq(id))
-      .get_result(conn);
-
-    match res{
-      Ok(locadora) => {
-        return Ok(locadora)
-      },
-      Err(e) => {
-        return Err(e.to_string())
-      }
-    }
+pub async fn busca_locadora_idlocadora(conn: &Conexao, id: String) 
+    -> Result<Locadora, ErroApi>{
+    executa(conn, move |conn| {
+        use crate::schema::locadoras::dsl::*;
+
+        let res: Result<Locadora, diesel::result::Error> = locadoras.filter(idlocadora.eq(id))
+          .get_result(conn);
+
+        match res{
+          Ok(locadora) => {
+            return Ok(locadora)
+          },
+          Err(e) => {
+            return Err(e.into())
+          }
+        }
+    }).await
 }
 
+/// Troca as regras de atraso da locadora do usuário.
+pub async fn atualiza_regras_atraso(conn: &Conexao, id: String, multa: Percentual, jurosmensal: Percentual)
+    -> Result<Locadora, ErroApi>{
+    executa(conn, move |conn| {
+        use crate::schema::locadoras::dsl::*;
+
+        let res: Result<Locadora, diesel::result::Error> = diesel::update(locadoras)
+          .filter(idusuario.eq(id))
+          .set((percentualmulta.eq(multa), percentualjurosmensal.eq(jurosmensal)))
+          .get_result(conn);
+
+        match res{
+          Ok(locadora) => {
+            return Ok(locadora)
+          },
+          Err(diesel::result::Error::NotFound) => {
+            return Err(ErroApi::NaoEncontrado("O usuário não tem uma locadora cadastrada.".to_string()))
+          },
+          Err(e) => {
+            return Err(e.into())
+          }
+        }
+    }).await
+}
 
 pub async fn deleta_locadora(id: String)
-    -> Result<(), String>{
-    use crate::schema::locadoras::dsl::*;
+    -> Result<(), ErroApi>{
+    let pool = cria_pool().unwrap();
+    let conn = &pool.get().await.unwrap();
 
-    let conn = &mut cria_conn().unwrap();
+    executa(conn, move |conn| {
+        use crate::schema::locadoras::dsl::*;
 
-    let res = diesel::delete(locadoras)
-        .filter(idlocadora.eq(id))
-        .execute(conn);
+        let res = diesel::delete(locadoras)
+            .filter(idlocadora.eq(id))
+            .execute(conn);
 
-    match res{
-        Ok(_) => {
-            Ok(())
-        },
-        Err(e) => {
-            return Err(e.to_string())
+        match res{
+            Ok(_) => {
+                Ok(())
+            },
+            Err(e) => {
+                return Err(e.into())
+            }
         }
-    }
+    }).await
 }
\ No newline at end of file
@@ -1,7 +1,9 @@
-use diesel::{prelude::{Insertable, Queryable}, query_dsl::methods::FilterDsl, ExpressionMethods, PgConnection, RunQueryDsl, Selectable};
+use diesel::{prelude::{Insertable, Queryable}, query_dsl::methods::FilterDsl, ExpressionMethods, RunQueryDsl, Selectable};
 use serde::{Deserialize, Serialize};
 
-use crate::controllers::cria_conn;
+use super::{executa, Conexao};
+use crate::erros::ErroApi;
+use crate::controllers::cria_pool;
 
 #[derive(Queryable, Selectable, Insertable, Serialize, Deserialize, Debug)]
 #[diesel(table_name = crate::schema::locatarios)]
@@ -12,72 +14,81 @@ pub struct Locatario{
     pub idendereco: String,
 }
 
-pub async fn cadastra_locatario(conn: &mut PgConnection, locatario: Locatario)
-    -> Result<String, String>{
-    use crate::schema::locatarios::dsl::*;
-    let res: Result<Locatario, diesel::result::Error> = diesel::insert_into(locatarios)
-        .values(locatario)
-        .get_result(conn);
-    match res{
-        Ok(locatario) => {
-            return Ok(locatario.idlocatario)
-        },
-        Err(e) => {
-            return Err(e.to_string())
+pub async fn cadastra_locatario(conn: &Conexao, locatario: Locatario)
+    -> Result<String, ErroApi>{
+    executa(conn, move |conn| {
+        use crate::schema::locatarios::dsl::*;
+        let res: Result<Locatario, diesel::result::Error> = diesel::insert_into(locatarios)
+            .values(locatario)
+            .get_result(conn);
+        match res{
+            Ok(locatario) => {
+                return Ok(locatario.idlocatario)
+            },
+            Err(e) => {
+                return Err(e.into())
+            }
         }
-    }
+    }).await
 }
 
-pub async fn busca_locatario_idusuario(conn: &mut PgConnection, id: String)
-    -> Result<Locatario, String>{
-    use crate::schema::locatarios::dsl::*;
+pub async fn busca_locatario_idusuario(conn: &Conexao, id: String)
+    -> Result<Locatario, ErroApi>{
+    executa(conn, move |conn| {
+        use crate::schema::locatarios::dsl::*;
 
-    let res: Result<Locatario, diesel::result::Error> = locatarios.filter(idusuario.eq(id))
-        .first(conn);
-    
-    match res{
-        Ok(locatario) => {
-            return Ok(locatario)
-        },
-        Err(e) => {
-            return Err(e.to_string())
+        let res: Result<Locatario, diesel::result::Error> = locatarios.filter(idusuario.eq(id))
+            .first(conn);
+
+        match res{
+            Ok(locatario) => {
+                return Ok(locatario)
+            },
+            Err(e) => {
+                return Err(e.into())
+            }
         }
-    }
+    }).await
 }
 
-pub async fn busca_locatario_idlocatario(conn: &mut PgConnection, id: String)
-     -> Result<Locatario, String>{
-    use crate::schema::locatarios::dsl::*;
+pub async fn busca_locatario_idlocatario(conn: &Conexao, id: String)
+     -> Result<Locatario, ErroApi>{
+    executa(conn, move |conn| {
+        use crate::schema::locatarios::dsl::*;
 
-    let res: Result<Locatario, diesel::result::Error> = locatarios.filter(idlocatario.eq(id))
-      .get_result(conn);
+        let res: Result<Locatario, diesel::result::Error> = locatarios.filter(idlocatario.eq(id))
+          .get_result(conn);
 
-    match res{
-      Ok(locatario) => {
-        return Ok(locatario)
-      },
-      Err(e) => {
-        return Err(e.to_string())
-      }
-    }
+        match res{
+          Ok(locatario) => {
+            return Ok(locatario)
+          },
+          Err(e) => {
+            return Err(e.into())
+          }
+        }
+    }).await
 }
 
 pub async fn deleta_locatario(id: String)
-    -> Result<(), String>{
-    use crate::schema::locatarios::dsl::*;
+    -> Result<(), ErroApi>{
+    let pool = cria_pool().unwrap();
+    let conn = &pool.get().await.unwrap();
 
-    let conn = &mut cria_conn().unwrap();
+    executa(conn, move |conn| {
+        use crate::schema::locatarios::dsl::*;
 
-    let res = diesel::delete(locatarios)
-        .filter(idlocatario.eq(id))
-        .execute(conn);
+        let res = diesel::delete(locatarios)
+            .filter(idlocatario.eq(id))
+            .execute(conn);
 
-    match res{
-        Ok(_) => {
-            Ok(())
-        },
-        Err(e) => {
-            return Err(e.to_string())
+        match res{
+            Ok(_) => {
+                Ok(())
+            },
+            Err(e) => {
+                return Err(e.into())
+            }
         }
-    }
+    }).await
 }
\ No newline at end of file
@@ -1,6 +1,10 @@
 use chrono::NaiveDateTime;
-use diesel::{prelude::{Insertable, Queryable}, BoolExpressionMethods, ExpressionMethods, PgConnection, PgTextExpressionMethods, QueryDsl, RunQueryDsl, Selectable};
+use diesel::{pg::Pg, prelude::{Insertable, Queryable, QueryableByName}, BoolExpressionMethods, ExpressionMethods, PgTextExpressionMethods, QueryDsl, RunQueryDsl, Selectable};
 use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use super::{executa, Conexao};
+use crate::{dinheiro::Dinheiro, erros::ErroApi, ids::gera_id_publico_unico};
 
 #[derive(Serialize, Deserialize)]
 pub struct IdsMaquina{
@@ -9,7 +13,7 @@ pub struct IdsMaquina{
 }
 
 
-#[derive(Queryable, Selectable, Insertable, Serialize, Deserialize, Debug)]
+#[derive(Queryable, QueryableByName, Selectable, Insertable, Serialize, Deserialize, Debug)]
 #[diesel(table_name = crate::schema::maquinas)]
 #[diesel(check_for_backend(diesel::pg::Pg))]
 pub struct Maquina{
@@ -18,7 +22,7 @@ pub struct Maquina{
     pub nome: String,
     pub numeroserie: String,
     pub categoria: String,
-    pub valoraluguel: f64,
+    pub valoraluguel: Dinheiro,
     pub disponivelaluguel: String,
     pub status: String,
     pub datacadastro: NaiveDateTime,
@@ -26,145 +30,365 @@ pub struct Maquina{
     pub descricao: String
 }
 
-pub async fn cadastra_maquina(conn: &mut PgConnection, maquina: Maquina)
-    -> Result<IdsMaquina, String>{
-    use crate::schema::maquinas::dsl::*;
+pub async fn cadastra_maquina(conn: &Conexao, maquina: Maquina)
+    -> Result<IdsMaquina, ErroApi>{
+    executa(conn, move |conn| {
+        use crate::schema::maquinas::dsl::*;
 
-    let res: Result<Maquina, diesel::result::Error> = diesel::insert_into(maquinas)
-        .values(maquina)
-        .get_result(conn);
-
-    match res{
-        Ok(maquina) => {
-            return Ok(IdsMaquina{
-                idmaquina: maquina.idmaquina,
-                idpublico: maquina.idpublico
-            })
-        },
-        Err(e) => {
-            return Err(e.to_string())
+        let res: Result<Maquina, diesel::result::Error> = diesel::insert_into(maquinas)
+            .values(maquina)
+            .get_result(conn);
+
+        match res{
+            Ok(maquina) => {
+                return Ok(IdsMaquina{
+                    idmaquina: maquina.idmaquina,
+                    idpublico: maquina.idpublico
+                })
+            },
+            Err(e) => {
+                return Err(e.into())
+            }
         }
-    }
+    }).await
 }
 
-pub async fn lista_todas_maquinas(conn: &mut PgConnection)
-    -> Result<Vec<Maquina>, String>{
-    use crate::schema::maquinas::dsl::*;
+pub async fn lista_todas_maquinas(conn: &Conexao)
+    -> Result<Vec<Maquina>, ErroApi>{
+    executa(conn, move |conn| {
+        use crate::schema::maquinas::dsl::*;
 
-    let res: Result<Vec<Maquina>, diesel::result::Error> = maquinas
-        .filter(status.eq("Ativo").and(disponivelaluguel.eq("Sim")))
-        .get_results(conn);
+        let res: Result<Vec<Maquina>, diesel::result::Error> = maquinas
+            .filter(status.eq("Ativo").and(disponivelaluguel.eq("Sim")))
+            .get_results(conn);
 
-    match res{
-        Ok(maqs) => {
-            if !maqs.is_empty(){
-                return Ok(maqs)
+        match res{
+            Ok(maqs) => {
+                if !maqs.is_empty(){
+                    return Ok(maqs)
+                }
+                return Err(ErroApi::NaoEncontrado("Não encontramos máquinas cadastradas no sistema.".to_string()))
+            },
+            Err(e) => {
+                return Err(e.into())
             }
-            return Err("Não encontramos máquinas cadastradas no sistema.".to_string())
-        },
-        Err(e) => {
-            return Err(e.to_string())
         }
-    }
+    }).await
 }
 
-pub async fn busca_maquina_id(conn: &mut PgConnection, id: String)
-    -> Result<Maquina, String>{
-    use crate::schema::maquinas::dsl::*;
+pub async fn busca_maquina_id(conn: &Conexao, id: String)
+    -> Result<Maquina, ErroApi>{
+    executa(conn, move |conn| {
+        use crate::schema::maquinas::dsl::*;
 
-    let res: Result<Maquina, diesel::result::Error> = maquinas.filter(idmaquina.eq(id))
-        .get_result(conn);
+        let res: Result<Maquina, diesel::result::Error> = maquinas.filter(idmaquina.eq(id))
+            .get_result(conn);
 
-    match res{
-        Ok(maquina) => {
-            return Ok(maquina)
-        },
-        Err(e) => {
-            return Err(e.to_string())
+        match res{
+            Ok(maquina) => {
+                return Ok(maquina)
+            },
+            Err(e) => {
+                return Err(e.into())
+            }
         }
-    }
+    }).await
 }
 
-pub async fn busca_maquina_idpublico(conn: &mut PgConnection, id: String)
-    -> Result<Maquina, String>{
-    use crate::schema::maquinas::dsl::*;
+/// Sorteia um `idpublico` que nenhuma máquina usa.
+pub async fn gera_idpublico_maquina(conn: &Conexao) -> Result<String, ErroApi>{
+    executa(conn, move |conn| {
+        use crate::schema::maquinas::dsl::*;
+
+        gera_id_publico_unico(|id| {
+            match diesel::select(diesel::dsl::exists(maquinas.filter(idpublico.eq(id)))).get_result::<bool>(conn){
+                Ok(existe) => {
+                    return Ok(existe)
+                },
+                Err(e) => {
+                    return Err(e.into())
+                }
+            }
+        })
+    }).await
+}
 
-    let res: Result<Maquina, diesel::result::Error> = maquinas.filter(idpublico.eq(id))
-        .get_result(conn);
+pub async fn busca_maquina_idpublico(conn: &Conexao, id: String)
+    -> Result<Maquina, ErroApi>{
+    executa(conn, move |conn| {
+        use crate::schema::maquinas::dsl::*;
 
-    match res{
-        Ok(maquina) => {
-            return Ok(maquina)
-        },
-        Err(e) => {
-            return Err(e.to_string())
+        let res: Result<Maquina, diesel::result::Error> = maquinas.filter(idpublico.eq(id))
+            .get_result(conn);
+
+        match res{
+            Ok(maquina) => {
+                return Ok(maquina)
+            },
+            Err(e) => {
+                return Err(e.into())
+            }
         }
-    }
+    }).await
+}
+
+/// Ordem dos resultados da pesquisa de máquinas.
+#[derive(Serialize, Deserialize, ToSchema, Clone, Copy, Debug, PartialEq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum OrdenacaoMaquinas{
+    #[default]
+    Relevancia,
+    MenorPreco,
+    MaiorPreco,
+    MaisRecentes
+}
+
+/// Filtros já validados da pesquisa de máquinas. Campos `None` não filtram.
+#[derive(Clone, Debug, Default)]
+pub struct FiltroMaquinas{
+    pub texto: Option<String>,
+    pub categoria: Option<String>,
+    pub valor_minimo: Option<Dinheiro>,
+    pub valor_maximo: Option<Dinheiro>,
+    pub disponivelaluguel: Option<String>,
+    pub cidade: Option<String>,
+    pub ordenacao: OrdenacaoMaquinas,
+    pub limite: i64,
+    pub deslocamento: i64
+}
+
+/// Escapa os curingas do LIKE para que o texto do usuário seja comparado literalmente.
+pub fn escapa_curingas(texto: &str) -> String{
+    texto.trim()
+        .replace('\\', "\\\\")
+        .replace('%', "\\%")
+        .replace('_', "\\_")
+}
+
+/// Padrão do ILIKE que encontra o texto em qualquer posição da coluna.
+pub fn padrao_ilike(texto: &str) -> String{
+    format!("%{}%", escapa_curingas(texto))
 }
 
-pub async fn pesquisa_maquina(conn: &mut PgConnection, pesquisa: String)
-    -> Result<Vec<Maquina>, String>{
+fn consulta_pesquisa(filtro: &FiltroMaquinas) -> crate::schema::maquinas::BoxedQuery<'static, Pg>{
     use crate::schema::maquinas::dsl::*;
-    use diesel::dsl::sql;
-    use diesel::sql_types::Integer;
-
-    let pesquisa = format!("%{}%", pesquisa);
-    
-    let match_count_sql = format!(
-        "CASE WHEN nome ILIKE '{}' THEN 1 ELSE 0 END + \
-         CASE WHEN numeroserie ILIKE '{}' THEN 1 ELSE 0 END + \
-         CASE WHEN categoria ILIKE '{}' THEN 1 ELSE 0 END + \
-         CASE WHEN descricao ILIKE '{}' THEN 1 ELSE 0 END",
-         pesquisa, pesquisa, pesquisa, pesquisa
-    );
-
-    let match_count_sql = sql::<Integer>(&match_count_sql);
-
-    let res: Result<Vec<Maquina>, diesel::result::Error> = maquinas
-        .filter(
-            nome.ilike(&pesquisa)
-                .or(categoria.ilike(&pesquisa))
-                .or(numeroserie.ilike(&pesquisa))
-                .or(descricao.ilike(&pesquisa))
-                .and(status.eq("ativo")),
-        )
-        .order_by(match_count_sql.desc())
-        .get_results(conn);
-
-    match res {
-        Ok(produtos_) => Ok(produtos_),
-        Err(e) => {
-            println!("{:?}", e);
-            Err(e.to_string())
-        }
+    use crate::schema::{enderecos, enderecos_usuarios, maquinas_usuarios};
+
+    let mut consulta = maquinas
+        .filter(status.eq("Ativo"))
+        .into_boxed();
+
+    if let Some(texto) = &filtro.texto{
+        let padrao = padrao_ilike(texto);
+        consulta = consulta.filter(
+            nome.ilike(padrao.clone())
+                .or(categoria.ilike(padrao.clone()))
+                .or(numeroserie.ilike(padrao.clone()))
+                .or(descricao.ilike(padrao))
+        );
+    }
+    if let Some(cat) = &filtro.categoria{
+        consulta = consulta.filter(categoria.ilike(escapa_curingas(cat)));
+    }
+    if let Some(minimo) = filtro.valor_minimo{
+        consulta = consulta.filter(valoraluguel.ge(minimo));
+    }
+    if let Some(maximo) = filtro.valor_maximo{
+        consulta = consulta.filter(valoraluguel.le(maximo));
+    }
+    if let Some(disponivel) = &filtro.disponivelaluguel{
+        consulta = consulta.filter(disponivelaluguel.eq(disponivel.clone()));
     }
+    if let Some(cidade) = &filtro.cidade{
+        // Cidade do endereço do dono da máquina: maquinas -> maquinas_usuarios -> enderecos_usuarios -> enderecos.
+        let enderecos_cidade = enderecos::table
+            .filter(enderecos::cidade.ilike(escapa_curingas(cidade)))
+            .select(enderecos::idendereco);
+        let donos = enderecos_usuarios::table
+            .filter(enderecos_usuarios::idendereco.eq_any(enderecos_cidade))
+            .select(enderecos_usuarios::idusuario);
+        let maquinas_donos = maquinas_usuarios::table
+            .filter(maquinas_usuarios::idusuario.eq_any(donos))
+            .select(maquinas_usuarios::idmaquina);
+        consulta = consulta.filter(idmaquina.eq_any(maquinas_donos));
+    }
+    consulta
 }
 
-pub async fn atualiza_maquina(conn: &mut PgConnection, maquina: Maquina)
-    -> Result<(), String>{
-    use crate::schema::maquinas::dsl::*;
+/// Pesquisa máquinas ativas com os filtros informados. Retorna a página pedida e o total
+/// de máquinas que atendem aos filtros.
+pub async fn pesquisa_maquina(conn: &Conexao, filtro: FiltroMaquinas)
+    -> Result<(Vec<Maquina>, i64), ErroApi>{
+    executa(conn, move |conn| {
+        use crate::schema::maquinas::dsl::*;
+        use diesel::dsl::sql;
+        use diesel::sql_types::{Integer, Text};
+
+        let total: i64 = match consulta_pesquisa(&filtro).count().get_result(conn){
+            Ok(total) => {
+                total
+            },
+            Err(e) => {
+                return Err(e.into())
+            }
+        };
 
-    let dataupdate = chrono::Local::now().naive_local();
-
-    let res = diesel::update(maquinas.filter(idmaquina.eq(&maquina.idmaquina)))
-        .set((
-            nome.eq(&maquina.nome),
-            numeroserie.eq(&maquina.numeroserie),
-            categoria.eq(&maquina.categoria),
-            valoraluguel.eq(&maquina.valoraluguel),
-            disponivelaluguel.eq(&maquina.disponivelaluguel),
-            status.eq(&maquina.status),
-            dataatualizacao.eq(dataupdate),
-            descricao.eq(&maquina.descricao)
-        ))
-        .execute(conn);
-
-    match res{
-        Ok(_) => {
-            return Ok(())
-        },
-        Err(e) => {
-            return Err(e.to_string())
+        let mut consulta = consulta_pesquisa(&filtro);
+        consulta = match (filtro.ordenacao, &filtro.texto){
+            (OrdenacaoMaquinas::Relevancia, Some(texto)) => {
+                // Cada coluna que contém o texto soma um ponto. O texto vai como parâmetro.
+                let padrao = padrao_ilike(texto);
+                let relevancia = sql::<Integer>("(CASE WHEN nome ILIKE ")
+                    .bind::<Text, _>(padrao.clone())
+                    .sql(" THEN 1 ELSE 0 END + CASE WHEN numeroserie ILIKE ")
+                    .bind::<Text, _>(padrao.clone())
+                    .sql(" THEN 1 ELSE 0 END + CASE WHEN categoria ILIKE ")
+                    .bind::<Text, _>(padrao.clone())
+                    .sql(" THEN 1 ELSE 0 END + CASE WHEN descricao ILIKE ")
+                    .bind::<Text, _>(padrao)
+                    .sql(" THEN 1 ELSE 0 END)");
+                consulta.order((relevancia.desc(), datacadastro.desc()))
+            },
+            (OrdenacaoMaquinas::Relevancia, None) | (OrdenacaoMaquinas::MaisRecentes, _) => {
+                consulta.order((datacadastro.desc(), idmaquina.asc()))
+            },
+            (OrdenacaoMaquinas::MenorPreco, _) => {
+                consulta.order((valoraluguel.asc(), idmaquina.asc()))
+            },
+            (OrdenacaoMaquinas::MaiorPreco, _) => {
+                consulta.order((valoraluguel.desc(), idmaquina.asc()))
+            }
+        };
+
+        let res: Result<Vec<Maquina>, diesel::result::Error> = consulta
+            .limit(filtro.limite)
+            .offset(filtro.deslocamento)
+            .get_results(conn);
+
+        match res {
+            Ok(produtos_) => Ok((produtos_, total)),
+            Err(e) => {
+                tracing::error!("Erro ao buscar máquinas: {}", e);
+                Err(e.into())
+            }
         }
-    }
+    }).await
+}
+
+/// Marcadores que o `ts_headline` coloca em volta dos termos encontrados. São trocados por
+/// `<mark>` depois que o trecho é escapado, para que o texto da máquina não vire HTML.
+pub const INICIO_DESTAQUE: &str = "\u{27EA}";
+pub const FIM_DESTAQUE: &str = "\u{27EB}";
+
+#[derive(QueryableByName, Debug)]
+pub struct ResultadoBuscaTextual{
+    #[diesel(embed)]
+    pub maquina: Maquina,
+    #[diesel(sql_type = diesel::sql_types::Float4)]
+    pub relevancia: f32,
+    #[diesel(sql_type = diesel::sql_types::Text)]
+    pub trechonome: String,
+    #[diesel(sql_type = diesel::sql_types::Text)]
+    pub trechodescricao: String
+}
+
+#[derive(QueryableByName)]
+struct ContagemBuscaTextual{
+    #[diesel(sql_type = diesel::sql_types::BigInt)]
+    total: i64
+}
+
+const CONSULTA_BUSCA_TEXTUAL: &str = "
+    WITH busca AS (
+        SELECT websearch_to_tsquery('public.portugues_sem_acento', $1)
+            || plainto_tsquery('public.portugues_sem_acento', $2) AS consulta
+    )";
+
+/// Busca textual em nome, categoria e descrição das máquinas ativas, usando o índice
+/// `maquinas_documento_busca_idx`. Ignora acentos, usa o radical das palavras em português
+/// e ordena pela relevância.
+pub async fn busca_textual_maquinas(conn: &Conexao, texto: String, limite: i64, deslocamento: i64)
+    -> Result<(Vec<ResultadoBuscaTextual>, i64), ErroApi>{
+    executa(conn, move |conn| {
+        use diesel::sql_types::{BigInt, Text};
+
+        let texto_junto: String = texto.split_whitespace().collect();
+        let opcoes_nome = format!("StartSel={}, StopSel={}, HighlightAll=true", INICIO_DESTAQUE, FIM_DESTAQUE);
+        let opcoes_descricao = format!("StartSel={}, StopSel={}, MaxWords=30, MinWords=10, MaxFragments=2", INICIO_DESTAQUE, FIM_DESTAQUE);
+
+        let res: Result<ContagemBuscaTextual, diesel::result::Error> = diesel::sql_query(format!("{}
+            SELECT COUNT(*) AS total
+            FROM maquinas, busca
+            WHERE status = 'Ativo'
+                AND documento_busca_maquina(nome, categoria, descricao) @@ busca.consulta", CONSULTA_BUSCA_TEXTUAL))
+            .bind::<Text, _>(texto.clone())
+            .bind::<Text, _>(texto_junto.clone())
+            .get_result(conn);
+        let total = match res{
+            Ok(contagem) => {
+                contagem.total
+            },
+            Err(e) => {
+                return Err(e.into())
+            }
+        };
+
+        let res: Result<Vec<ResultadoBuscaTextual>, diesel::result::Error> = diesel::sql_query(format!("{}
+            SELECT maquinas.*,
+                ts_rank_cd(documento_busca_maquina(nome, categoria, descricao), busca.consulta) AS relevancia,
+                ts_headline('public.portugues_sem_acento', nome, busca.consulta, $3) AS trechonome,
+                ts_headline('public.portugues_sem_acento', descricao, busca.consulta, $4) AS trechodescricao
+            FROM maquinas, busca
+            WHERE status = 'Ativo'
+                AND documento_busca_maquina(nome, categoria, descricao) @@ busca.consulta
+            ORDER BY relevancia DESC, datacadastro DESC, idmaquina
+            LIMIT $5 OFFSET $6", CONSULTA_BUSCA_TEXTUAL))
+            .bind::<Text, _>(texto)
+            .bind::<Text, _>(texto_junto)
+            .bind::<Text, _>(opcoes_nome)
+            .bind::<Text, _>(opcoes_descricao)
+            .bind::<BigInt, _>(limite)
+            .bind::<BigInt, _>(deslocamento)
+            .load(conn);
+
+        match res{
+            Ok(resultados) => {
+                return Ok((resultados, total))
+            },
+            Err(e) => {
+                return Err(e.into())
+            }
+        }
+    }).await
+}
+
+pub async fn atualiza_maquina(conn: &Conexao, maquina: Maquina)
+    -> Result<(), ErroApi>{
+    executa(conn, move |conn| {
+        use crate::schema::maquinas::dsl::*;
+
+        let dataupdate = chrono::Local::now().naive_local();
+
+        let res = diesel::update(maquinas.filter(idmaquina.eq(&maquina.idmaquina)))
+            .set((
+                nome.eq(&maquina.nome),
+                numeroserie.eq(&maquina.numeroserie),
+                categoria.eq(&maquina.categoria),
+                valoraluguel.eq(&maquina.valoraluguel),
+                disponivelaluguel.eq(&maquina.disponivelaluguel),
+                status.eq(&maquina.status),
+                dataatualizacao.eq(dataupdate),
+                descricao.eq(&maquina.descricao)
+            ))
+            .execute(conn);
+
+        match res{
+            Ok(_) => {
+                return Ok(())
+            },
+            Err(e) => {
+                return Err(e.into())
+            }
+        }
+    }).await
 }
\ No newline at end of file
@@ -1,6 +1,8 @@
-use diesel::{prelude::{Insertable, Queryable}, ExpressionMethods, PgConnection, QueryDsl, RunQueryDsl, Selectable};
+use diesel::{prelude::{Insertable, Queryable}, ExpressionMethods, QueryDsl, RunQueryDsl, Selectable};
 use serde::{Deserialize, Serialize};
 
+use super::{executa, Conexao};
+use crate::erros::ErroApi;
 use crate::schema::maquinas_usuarios;
 
 
@@ -13,55 +15,61 @@ pub struct MaquinaUsuario{
     pub idusuario: String
 }
 
-pub async fn cadastra_maquina_usuario(conn: &mut PgConnection, dados: MaquinaUsuario)
-    -> Result<String, String>{
-    let res: Result<MaquinaUsuario, diesel::result::Error> = diesel::insert_into(maquinas_usuarios::table)
-        .values(dados)
-        .get_result(conn);
-    match res{
-        Ok(maq) =>{
-            return Ok(maq.idmaquinausuario)
-        },
-        Err(e) => {
-            return Err(e.to_string())
+pub async fn cadastra_maquina_usuario(conn: &Conexao, dados: MaquinaUsuario)
+    -> Result<String, ErroApi>{
+    executa(conn, move |conn| {
+        let res: Result<MaquinaUsuario, diesel::result::Error> = diesel::insert_into(maquinas_usuarios::table)
+            .values(dados)
+            .get_result(conn);
+        match res{
+            Ok(maq) =>{
+                return Ok(maq.idmaquinausuario)
+            },
+            Err(e) => {
+                return Err(e.into())
+            }
         }
-    }
+    }).await
 }
 
-pub async fn busca_maquinas_usuario_idusuario(conn: &mut PgConnection, id: String)
-    -> Result<Vec<MaquinaUsuario>, String>{
-    use crate::schema::maquinas_usuarios::dsl::*;
+pub async fn busca_maquinas_usuario_idusuario(conn: &Conexao, id: String)
+    -> Result<Vec<MaquinaUsuario>, ErroApi>{
+    executa(conn, move |conn| {
+        use crate::schema::maquinas_usuarios::dsl::*;
 
-    let res: Result<Vec<MaquinaUsuario>, diesel::result::Error> = maquinas_usuarios.
-        filter(idusuario.eq(id))
-        .get_results(conn);
+        let res: Result<Vec<MaquinaUsuario>, diesel::result::Error> = maquinas_usuarios.
+            filter(idusuario.eq(id))
+            .get_results(conn);
 
-    match res{
-        Ok(maqs) => {
-            if !maqs.is_empty(){
-                return Ok(maqs)
+        match res{
+            Ok(maqs) => {
+                if !maqs.is_empty(){
+                    return Ok(maqs)
+                }
+                return Err(ErroApi::NaoEncontrado("Este usuário ainda não cadastrou uma máquina.".to_string()))
+            },
+            Err(e) => {
+                return Err(e.into())
             }
-            return Err("Este usuário ainda não cadastrou uma máquina.".to_string())
-        },
-        Err(e) => {
-            return Err(e.to_string())
         }
-    }
+    }).await
 }
 
-pub async fn busca_idusuario_idmaquina(conn: &mut PgConnection, id: String)
-    -> Result<String, String>{
-    use crate::schema::maquinas_usuarios::dsl::*;
+pub async fn busca_idusuario_idmaquina(conn: &Conexao, id: String)
+    -> Result<String, ErroApi>{
+    executa(conn, move |conn| {
+        use crate::schema::maquinas_usuarios::dsl::*;
 
-    let res: Result<MaquinaUsuario, diesel::result::Error> = maquinas_usuarios.filter(idmaquina.eq(id))
-        .first(conn);
+        let res: Result<MaquinaUsuario, diesel::result::Error> = maquinas_usuarios.filter(idmaquina.eq(id))
+            .first(conn);
 
-    match res{
-        Ok(info) => {
-            return Ok(info.idusuario)
-        },
-        Err(e) => {
-            return Err(e.to_string())
+        match res{
+            Ok(info) => {
+                return Ok(info.idusuario)
+            },
+            Err(e) => {
+                return Err(e.into())
+            }
         }
-    }
+    }).await
 }
\ No newline at end of file
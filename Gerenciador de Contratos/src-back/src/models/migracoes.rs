@@ -0,0 +1,34 @@
+use diesel_migrations::{embed_migrations, EmbeddedMigrations, MigrationHarness};
+
+use super::{executa, Conexao};
+use crate::erros::ErroApi;
+
+/// Migrações de `src/migrations`, embutidas no binário durante a compilação.
+pub const MIGRACOES: EmbeddedMigrations = embed_migrations!("src/migrations");
+
+/// Aplica as migrações pendentes e devolve as versões que foram aplicadas.
+pub async fn aplica_migracoes(conn: &Conexao) -> Result<Vec<String>, ErroApi>{
+    executa(conn, move |conn| {
+        match conn.run_pending_migrations(MIGRACOES){
+            Ok(versoes) => {
+                return Ok(versoes.iter().map(|versao| versao.to_string()).collect())
+            },
+            Err(e) => {
+                return Err(ErroApi::Interno(format!("Erro ao aplicar as migrações: {}", e)))
+            }
+        }
+    }).await
+}
+
+pub async fn possui_migracoes_pendentes(conn: &Conexao) -> Result<bool, ErroApi>{
+    executa(conn, move |conn| {
+        match conn.has_pending_migration(MIGRACOES){
+            Ok(pendentes) => {
+                return Ok(pendentes)
+            },
+            Err(e) => {
+                return Err(ErroApi::Interno(format!("Erro ao verificar as migrações: {}", e)))
+            }
+        }
+    }).await
+}
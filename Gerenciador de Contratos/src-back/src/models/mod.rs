@@ -1,4 +1,31 @@
-use bigdecimal::BigDecimal;
+use diesel::PgConnection;
+
+use crate::erros::ErroApi;
+
+/// Grava um enum numa coluna `Varchar` usando o texto de `texto()`, e o lê de volta com `FromStr`.
+macro_rules! enum_texto_sql{
+    ($tipo:ty) => {
+        impl diesel::serialize::ToSql<diesel::sql_types::Varchar, diesel::pg::Pg> for $tipo{
+            fn to_sql<'b>(&'b self, out: &mut diesel::serialize::Output<'b, '_, diesel::pg::Pg>) -> diesel::serialize::Result{
+                <str as diesel::serialize::ToSql<diesel::sql_types::Varchar, diesel::pg::Pg>>::to_sql(self.texto(), &mut out.reborrow())
+            }
+        }
+
+        impl diesel::deserialize::FromSql<diesel::sql_types::Varchar, diesel::pg::Pg> for $tipo{
+            fn from_sql(bytes: diesel::pg::PgValue<'_>) -> diesel::deserialize::Result<Self>{
+                let texto = <String as diesel::deserialize::FromSql<diesel::sql_types::Varchar, diesel::pg::Pg>>::from_sql(bytes)?;
+                match <$tipo as std::str::FromStr>::from_str(&texto){
+                    Ok(valor) => {
+                        return Ok(valor)
+                    },
+                    Err(_) => {
+                        return Err(format!("Valor desconhecido para {}: {}", stringify!($tipo), texto).into())
+                    }
+                }
+            }
+        }
+    };
+}
 
 pub mod usuarios;
 pub mod codigos_recuperacao;
@@ -12,12 +39,38 @@ pub mod locatarios;
 pub mod locadoras;
 pub mod contas_bancarias;
 pub mod solicitacoes_contratos;
+pub mod reservas_maquinas;
 pub mod contratos;
+pub mod documentos_contratos;
 pub mod notificacoes;
+pub mod ids_legados;
+pub mod contas_externas;
+pub mod papeis_usuarios;
+pub mod chaves_pix;
+pub mod cobrancas_pix;
+pub mod faturas;
+pub mod recibos_faturas;
+pub mod encargos_contratos;
+pub mod precos_maquinas;
+pub mod tokens_renovacao;
+pub mod migracoes;
+
+/// Conexão obtida do pool compartilhado da aplicação.
+pub type Conexao = deadpool_diesel::postgres::Object;
 
-pub fn str_to_f64_bigdecimal(price_str: &str) -> f64 {
-    let decimal = BigDecimal::parse_bytes(price_str.as_bytes(), 10).unwrap();
-    let d = decimal.to_string();
-    let decimal = d.parse().unwrap_or(0.0);
-    decimal
-}
\ No newline at end of file
+/// Executa uma operação do Diesel numa thread de bloqueio do pool,
+/// sem ocupar as threads de trabalho do tokio.
+pub async fn executa<T, F>(conn: &Conexao, operacao: F) -> Result<T, ErroApi>
+where
+    F: FnOnce(&mut PgConnection) -> Result<T, ErroApi> + Send + 'static,
+    T: Send + 'static,
+{
+    match conn.interact(operacao).await{
+        Ok(res) => {
+            return res
+        },
+        Err(e) => {
+            return Err(e.into())
+        }
+    }
+}
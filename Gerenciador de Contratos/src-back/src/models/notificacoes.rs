@@ -1,7 +1,10 @@
 use chrono::NaiveDateTime;
-use diesel::{prelude::{Insertable, Queryable}, ExpressionMethods, PgConnection, QueryDsl, RunQueryDsl, Selectable};
+use diesel::{prelude::{Insertable, Queryable}, ExpressionMethods, QueryDsl, RunQueryDsl, Selectable};
 use serde::{Deserialize, Serialize};
 
+use super::{executa, Conexao};
+use crate::erros::ErroApi;
+
 #[derive(Queryable, Selectable, Insertable, Serialize, Deserialize, Debug)]
 #[diesel(table_name = crate::schema::notificacoes)]
 #[diesel(check_for_backend(diesel::pg::Pg))]
@@ -15,41 +18,65 @@ pub struct Notificacao{
     pub datacriacao: NaiveDateTime
 }
 
-pub async fn cadastra_notificacao(conn: &mut PgConnection, notificacao: Notificacao)
-    -> Result<Notificacao, String>{
-    use crate::schema::notificacoes::dsl::*;
+pub async fn cadastra_notificacao(conn: &Conexao, notificacao: Notificacao)
+    -> Result<Notificacao, ErroApi>{
+    executa(conn, move |conn| {
+        use crate::schema::notificacoes::dsl::*;
+
+        let res: Result<Notificacao, diesel::result::Error> = diesel::insert_into(notificacoes)
+          .values(notificacao)
+          .get_result(conn);
+
+        match res{
+          Ok(notificacao) => {
+            return Ok(notificacao)
+          },
+          Err(e) => {
+            return Err(e.into())
+          }
+        }
+    }).await
+}
+
+pub async fn busca_notificacoes_idusuario(conn: &Conexao, id: String)
+    -> Result<Vec<Notificacao>, ErroApi>{
+    executa(conn, move |conn| {
+        use crate::schema::notificacoes::dsl::*;
 
-    let res: Result<Notificacao, diesel::result::Error> = diesel::insert_into(notificacoes)
-      .values(notificacao)
-      .get_result(conn);
+        let res: Result<Vec<Notificacao>, diesel::result::Error> = notificacoes
+          .filter(idusuario.eq(id))
+          .order_by(datacriacao.desc())
+          .get_results(conn);
 
-    match res{
-      Ok(notificacao) => {
-        return Ok(notificacao)
-      },
-      Err(e) => {
-        return Err(e.to_string())
-      }
-    }
+        match res{
+          Ok(res) => {
+            return Ok(res)
+          },
+          Err(e) => {
+            return Err(e.into())
+          }
+        }
+    }).await
 }
 
-pub async fn busca_notificacoes_idusuario(conn: &mut PgConnection, id: String)
-    -> Result<Vec<Notificacao>, String>{
-    use crate::schema::notificacoes::dsl::*;
+pub async fn busca_notificacao_id(conn: &Conexao, id: String)
+    -> Result<Notificacao, ErroApi>{
+    executa(conn, move |conn| {
+        use crate::schema::notificacoes::dsl::*;
 
-    let res: Result<Vec<Notificacao>, diesel::result::Error> = notificacoes
-      .filter(idusuario.eq(id))
-      .order_by(datacriacao.desc())
-      .get_results(conn);
+        let res: Result<Notificacao, diesel::result::Error> = notificacoes
+          .filter(idnotificacao.eq(id))
+          .first(conn);
 
-    match res{
-      Ok(res) => {
-        return Ok(res)
-      },
-      Err(e) => {
-        return Err(e.to_string())
-      }
-    }
+        match res{
+          Ok(notificacao) => {
+            return Ok(notificacao)
+          },
+          Err(e) => {
+            return Err(e.into())
+          }
+        }
+    }).await
 }
 
 #[derive(Serialize, Deserialize)]
@@ -58,21 +85,23 @@ pub struct NotificaoStatusInput{
     pub novostatus: String
 }
 
-pub async fn atualiza_status_notificacao(conn: &mut PgConnection, atualizacao: NotificaoStatusInput)
-    -> Result<String, String>{
-    use crate::schema::notificacoes::dsl::*;
+pub async fn atualiza_status_notificacao(conn: &Conexao, atualizacao: NotificaoStatusInput)
+    -> Result<String, ErroApi>{
+    executa(conn, move |conn| {
+        use crate::schema::notificacoes::dsl::*;
 
-    let res: Result<Notificacao, diesel::result::Error> = diesel::update(notificacoes)
-      .filter(idnotificacao.eq(atualizacao.id))
-      .set(status.eq(atualizacao.novostatus))
-      .get_result(conn);
+        let res: Result<Notificacao, diesel::result::Error> = diesel::update(notificacoes)
+          .filter(idnotificacao.eq(atualizacao.id))
+          .set(status.eq(atualizacao.novostatus))
+          .get_result(conn);
 
-    match res{
-      Ok(notificacao) => {
-        return Ok(notificacao.idnotificacao)
-      },
-      Err(e) => {
-        return Err(e.to_string())
-      }
-    }
+        match res{
+          Ok(notificacao) => {
+            return Ok(notificacao.idnotificacao)
+          },
+          Err(e) => {
+            return Err(e.into())
+          }
+        }
+    }).await
 }
\ No newline at end of file
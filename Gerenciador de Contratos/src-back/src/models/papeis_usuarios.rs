@@ -0,0 +1,145 @@
+use std::{fmt, str::FromStr};
+
+use diesel::{deserialize::FromSqlRow, expression::AsExpression, sql_types::Varchar, ExpressionMethods, QueryDsl, RunQueryDsl};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use super::{executa, Conexao};
+use crate::erros::ErroApi;
+
+/// Papel de um usuário no sistema. Um usuário pode ter vários.
+#[derive(Serialize, Deserialize, ToSchema, AsExpression, FromSqlRow, Debug, Clone, Copy, PartialEq, Eq)]
+#[diesel(sql_type = Varchar)]
+#[serde(rename_all = "snake_case")]
+pub enum Papel{
+    /// Cadastrou uma locadora e aluga as próprias máquinas.
+    Locador,
+    /// Cadastrou um locatário e pode pedir máquinas.
+    Locatario,
+    /// Vê e modera os dados de todos os usuários.
+    Admin
+}
+
+/// O que cada papel pode fazer. As rotas exigem permissões, e não papéis; veja
+/// `controllers::sessoes::exige_permissao`.
+#[derive(Serialize, Deserialize, ToSchema, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Permissao{
+    /// Cadastrar máquinas e atualizar as próprias.
+    GerenciaMaquinas,
+    /// Pedir o aluguel de uma máquina.
+    SolicitaLocacao,
+    /// Cadastrar chaves PIX para receber os pagamentos dos contratos.
+    RecebePagamentos,
+    /// Ver e moderar os dados de outros usuários.
+    Modera,
+    /// Dar e tirar papéis dos usuários.
+    GerenciaPapeis
+}
+
+impl Papel{
+    pub fn texto(&self) -> &'static str{
+        match self{
+            Papel::Locador => "locador",
+            Papel::Locatario => "locatario",
+            Papel::Admin => "admin"
+        }
+    }
+
+    pub fn permissoes(&self) -> &'static [Permissao]{
+        match self{
+            Papel::Locador => &[Permissao::GerenciaMaquinas, Permissao::RecebePagamentos],
+            Papel::Locatario => &[Permissao::SolicitaLocacao],
+            Papel::Admin => &[Permissao::GerenciaMaquinas, Permissao::SolicitaLocacao, Permissao::RecebePagamentos, Permissao::Modera, Permissao::GerenciaPapeis]
+        }
+    }
+}
+
+impl fmt::Display for Papel{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result{
+        write!(f, "{}", self.texto())
+    }
+}
+
+impl FromStr for Papel{
+    type Err = ErroApi;
+
+    fn from_str(texto: &str) -> Result<Self, Self::Err>{
+        let todos = [Papel::Locador, Papel::Locatario, Papel::Admin];
+        match todos.into_iter().find(|papel| papel.texto() == texto.trim()){
+            Some(papel) => {
+                return Ok(papel)
+            },
+            None => {
+                return Err(ErroApi::validacao_campo("papel", "Papel de usuário inválido."))
+            }
+        }
+    }
+}
+
+enum_texto_sql!(Papel);
+
+pub async fn busca_papeis_usuario(conn: &Conexao, id: String)
+    -> Result<Vec<Papel>, ErroApi>{
+    executa(conn, move |conn| {
+        use crate::schema::papeis_usuarios::dsl::*;
+
+        let res = papeis_usuarios
+            .filter(idusuario.eq(id))
+            .order_by(dataatribuicao.asc())
+            .select(papel)
+            .load::<Papel>(conn);
+        match res{
+            Ok(papeis) => {
+                return Ok(papeis)
+            },
+            Err(e) => {
+                return Err(e.into())
+            }
+        }
+    }).await
+}
+
+/// Dá o papel ao usuário. Não faz nada se ele já o tiver.
+pub async fn atribui_papel(conn: &Conexao, id: String, papel_: Papel)
+    -> Result<(), ErroApi>{
+    executa(conn, move |conn| {
+        use crate::schema::papeis_usuarios::dsl::*;
+
+        let res = diesel::insert_into(papeis_usuarios)
+            .values((idusuario.eq(id), papel.eq(papel_), dataatribuicao.eq(chrono::Utc::now().naive_utc())))
+            .on_conflict_do_nothing()
+            .execute(conn);
+        match res{
+            Ok(_) => {
+                return Ok(())
+            },
+            Err(e) => {
+                return Err(e.into())
+            }
+        }
+    }).await
+}
+
+pub async fn remove_papel(conn: &Conexao, id: String, papel_: Papel)
+    -> Result<(), ErroApi>{
+    executa(conn, move |conn| {
+        use crate::schema::papeis_usuarios::dsl::*;
+
+        let res = diesel::delete(papeis_usuarios)
+            .filter(idusuario.eq(id))
+            .filter(papel.eq(papel_))
+            .execute(conn);
+        match res{
+            Ok(0) => {
+                return Err(ErroApi::Conflito("O usuário não tem esse papel.".to_string()))
+            },
+            Ok(_) => {
+                return Ok(())
+            },
+            Err(e) => {
+                return Err(e.into())
+            }
+        }
+    }).await
+}
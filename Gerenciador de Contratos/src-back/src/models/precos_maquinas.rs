@@ -0,0 +1,159 @@
+use std::{fmt, str::FromStr};
+
+use diesel::{deserialize::FromSqlRow, expression::AsExpression, prelude::{Insertable, Queryable}, sql_types::Varchar, Connection, ExpressionMethods, QueryDsl, RunQueryDsl, Selectable};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use super::{executa, Conexao};
+use crate::{dinheiro::Dinheiro, erros::ErroApi};
+
+/// Unidade do prazo de uma locação. No banco e no JSON, é gravada com o texto exibido no
+/// front-end.
+#[derive(Serialize, Deserialize, ToSchema, AsExpression, FromSqlRow, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[diesel(sql_type = Varchar)]
+pub enum MedidaTempo{
+    Horas,
+    Dias,
+    Semanas,
+    Meses
+}
+
+impl MedidaTempo{
+    pub const TODAS: [MedidaTempo; 4] = [MedidaTempo::Horas, MedidaTempo::Dias, MedidaTempo::Semanas, MedidaTempo::Meses];
+
+    pub fn texto(&self) -> &'static str{
+        match self{
+            MedidaTempo::Horas => "Horas",
+            MedidaTempo::Dias => "Dias",
+            MedidaTempo::Semanas => "Semanas",
+            MedidaTempo::Meses => "Meses"
+        }
+    }
+
+    /// Horas em uma unidade da medida. A semana tem 7 dias e o mês, 30, tanto nos preços
+    /// quanto nas faixas de desconto.
+    pub fn horas(&self) -> i64{
+        match self{
+            MedidaTempo::Horas => 1,
+            MedidaTempo::Dias => 24,
+            MedidaTempo::Semanas => 7 * 24,
+            MedidaTempo::Meses => 30 * 24
+        }
+    }
+
+    /// O prazo convertido em dias, para comparar prazos de medidas diferentes.
+    pub fn em_dias(&self, prazo: f64) -> f64{
+        prazo * self.horas() as f64 / 24.0
+    }
+}
+
+impl fmt::Display for MedidaTempo{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result{
+        write!(f, "{}", self.texto())
+    }
+}
+
+impl FromStr for MedidaTempo{
+    type Err = ErroApi;
+
+    fn from_str(texto: &str) -> Result<Self, Self::Err>{
+        match MedidaTempo::TODAS.into_iter().find(|medida| medida.texto() == texto.trim()){
+            Some(medida) => {
+                return Ok(medida)
+            },
+            None => {
+                return Err(ErroApi::validacao_campo("medidatempolocacao", "Medida de tempo inválida."))
+            }
+        }
+    }
+}
+
+enum_texto_sql!(MedidaTempo);
+
+/// Tarifa própria da máquina em uma medida de tempo. As medidas sem tarifa saem do valor
+/// mensal da máquina; veja `crate::precos`.
+#[derive(Queryable, Selectable, Insertable, Serialize, Deserialize, ToSchema, Debug, Clone, PartialEq)]
+#[diesel(table_name = crate::schema::precos_maquinas)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct PrecoMaquina{
+    pub idmaquina: String,
+    pub medida: MedidaTempo,
+    /// Valor de uma unidade da medida.
+    pub valor: Dinheiro,
+    /// Menor prazo aceito nessa medida. Zero quando não há mínimo.
+    pub prazominimo: f64
+}
+
+/// Desconto dado às locações com pelo menos `diasminimos` dias, em qualquer medida.
+#[derive(Queryable, Selectable, Insertable, Serialize, Deserialize, ToSchema, Debug, Clone, PartialEq)]
+#[diesel(table_name = crate::schema::descontos_maquinas)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct DescontoMaquina{
+    pub idmaquina: String,
+    pub diasminimos: i32,
+    pub percentualdesconto: Percentual
+}
+
+/// As tarifas e as faixas de desconto da máquina, com as faixas da menor para a maior.
+pub async fn busca_tabela_precos(conn: &Conexao, id: String)
+    -> Result<(Vec<PrecoMaquina>, Vec<DescontoMaquina>), ErroApi>{
+    executa(conn, move |conn| {
+        use crate::schema::{descontos_maquinas, precos_maquinas};
+
+        let res: Result<(Vec<PrecoMaquina>, Vec<DescontoMaquina>), diesel::result::Error> = conn.transaction(|conn| {
+          let precos = precos_maquinas::table
+            .filter(precos_maquinas::idmaquina.eq(&id))
+            .get_results(conn)?;
+          let descontos = descontos_maquinas::table
+            .filter(descontos_maquinas::idmaquina.eq(&id))
+            .order_by(descontos_maquinas::diasminimos.asc())
+            .get_results(conn)?;
+          Ok((precos, descontos))
+        });
+        match res{
+          Ok(tabela) => {
+            return Ok(tabela)
+          },
+          Err(e) => {
+            return Err(e.into())
+          }
+        }
+    }).await
+}
+
+/// Troca toda a tabela de preços da máquina pela nova, numa transação só.
+pub async fn substitui_tabela_precos(conn: &Conexao, id: String, precos: Vec<PrecoMaquina>, descontos: Vec<DescontoMaquina>)
+    -> Result<(), ErroApi>{
+    executa(conn, move |conn| {
+        use crate::schema::{descontos_maquinas, precos_maquinas};
+
+        let res: Result<(), diesel::result::Error> = conn.transaction(|conn| {
+          diesel::delete(precos_maquinas::table.filter(precos_maquinas::idmaquina.eq(&id)))
+            .execute(conn)?;
+          diesel::delete(descontos_maquinas::table.filter(descontos_maquinas::idmaquina.eq(&id)))
+            .execute(conn)?;
+          if !precos.is_empty(){
+            diesel::insert_into(precos_maquinas::table)
+              .values(&precos)
+              .execute(conn)?;
+          }
+          if !descontos.is_empty(){
+            diesel::insert_into(descontos_maquinas::table)
+              .values(&descontos)
+              .execute(conn)?;
+          }
+          Ok(())
+        });
+        match res{
+          Ok(()) => {
+            return Ok(())
+          },
+          Err(diesel::result::Error::DatabaseError(diesel::result::DatabaseErrorKind::ForeignKeyViolation, _)) => {
+            return Err(ErroApi::NaoEncontrado("Máquina não encontrada.".to_string()))
+          },
+          Err(e) => {
+            return Err(e.into())
+          }
+        }
+    }).await
+}
@@ -0,0 +1,65 @@
+use chrono::NaiveDateTime;
+use diesel::{prelude::{Insertable, Queryable}, ExpressionMethods, QueryDsl, RunQueryDsl, Selectable};
+
+use super::{executa, Conexao};
+use crate::erros::ErroApi;
+
+/// PDF do recibo de uma fatura paga, gerado uma única vez.
+#[derive(Queryable, Selectable, Insertable, Debug)]
+#[diesel(table_name = crate::schema::recibos_faturas)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct ReciboFatura{
+    pub idfatura: String,
+    pub versaomodelo: String,
+    pub hashrecibo: String,
+    pub documento: Vec<u8>,
+    pub datageracao: NaiveDateTime
+}
+
+/// Salva o recibo, caso a fatura ainda não tenha um, e devolve o recibo salvo.
+pub async fn cadastra_recibo_fatura(conn: &Conexao, novo: ReciboFatura)
+    -> Result<ReciboFatura, ErroApi>{
+    executa(conn, move |conn| {
+        use crate::schema::recibos_faturas::dsl::*;
+
+        let id = novo.idfatura.clone();
+        let res = diesel::insert_into(recibos_faturas)
+            .values(novo)
+            .on_conflict_do_nothing()
+            .execute(conn);
+        if let Err(e) = res{
+            return Err(e.into())
+        }
+
+        let res: Result<ReciboFatura, diesel::result::Error> = recibos_faturas
+            .filter(idfatura.eq(id))
+            .get_result(conn);
+        match res{
+            Ok(salvo) => {
+                return Ok(salvo)
+            },
+            Err(e) => {
+                return Err(e.into())
+            }
+        }
+    }).await
+}
+
+pub async fn busca_recibo_fatura(conn: &Conexao, id: String)
+    -> Result<ReciboFatura, ErroApi>{
+    executa(conn, move |conn| {
+        use crate::schema::recibos_faturas::dsl::*;
+
+        let res: Result<ReciboFatura, diesel::result::Error> = recibos_faturas
+            .filter(idfatura.eq(id))
+            .get_result(conn);
+        match res{
+            Ok(salvo) => {
+                return Ok(salvo)
+            },
+            Err(e) => {
+                return Err(e.into())
+            }
+        }
+    }).await
+}
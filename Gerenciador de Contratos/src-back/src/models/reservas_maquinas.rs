@@ -0,0 +1,190 @@
+use chrono::{Months, NaiveDateTime, TimeDelta};
+use diesel::{pg::Pg, prelude::{Insertable, Queryable}, BoolExpressionMethods, ExpressionMethods, PgConnection, QueryDsl, RunQueryDsl, Selectable, SelectableHelper};
+use serde::{Deserialize, Serialize};
+
+use super::{contratos::StatusContrato, executa, precos_maquinas::MedidaTempo, solicitacoes_contratos::StatusSolicitacao, Conexao};
+use crate::erros::ErroApi;
+
+/// Calcula o fim da locação a partir do início, do prazo e da medida de tempo.
+pub fn calcula_fim_locacao(inicio: NaiveDateTime, prazo: f64, medida: MedidaTempo) -> Result<NaiveDateTime, ErroApi>{
+    if !prazo.is_finite() || prazo <= 0.0{
+        return Err(ErroApi::validacao_campo("prazolocacao", "O prazo da locação deve ser maior que zero."))
+    }
+    let segundos_unidade = match medida{
+        MedidaTempo::Horas => 3_600.0,
+        MedidaTempo::Dias => 86_400.0,
+        MedidaTempo::Semanas => 604_800.0,
+        MedidaTempo::Meses => {
+            // Meses têm tamanhos diferentes, então o prazo é somado no calendário.
+            if prazo.fract() != 0.0{
+                return Err(ErroApi::validacao_campo("prazolocacao", "O prazo em meses deve ser um número inteiro."))
+            }
+            match inicio.checked_add_months(Months::new(prazo as u32)){
+                Some(fim) => {
+                    return Ok(fim)
+                },
+                None => {
+                    return Err(ErroApi::validacao_campo("prazolocacao", "O prazo da locação é grande demais."))
+                }
+            }
+        }
+    };
+
+    let fim = TimeDelta::try_seconds((prazo * segundos_unidade).round() as i64)
+        .and_then(|duracao| inicio.checked_add_signed(duracao));
+    match fim{
+        Some(fim) => {
+            return Ok(fim)
+        },
+        None => {
+            return Err(ErroApi::validacao_campo("prazolocacao", "O prazo da locação é grande demais."))
+        }
+    }
+}
+
+/// Período em que uma solicitação pretende usar a máquina. Existe uma reserva por solicitação,
+/// mas ela só bloqueia a máquina enquanto a solicitação estiver aprovada e o contrato, ativo.
+#[derive(Queryable, Selectable, Insertable, Serialize, Deserialize, Debug, Clone)]
+#[diesel(table_name = crate::schema::reservas_maquinas)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct ReservaMaquina{
+    pub idreserva: String,
+    pub idmaquina: String,
+    pub idsolicitacao: String,
+    pub datainicio: NaiveDateTime,
+    pub datafim: NaiveDateTime
+}
+
+/// Reservas que ocupam a máquina em algum momento entre `inicio` e `fim`.
+fn consulta_reservas_ocupadas(maquina: String, inicio: NaiveDateTime, fim: NaiveDateTime)
+    -> crate::schema::reservas_maquinas::BoxedQuery<'static, Pg>{
+    use crate::schema::reservas_maquinas::dsl::*;
+    use crate::schema::{contratos, solicitacoes_contratos};
+
+    // A aprovação gera o contrato. Depois que ele é concluído ou rescindido, o período fica livre.
+    let em_curso = [StatusContrato::Ativo, StatusContrato::Atrasado];
+    let solicitacoes_aprovadas = solicitacoes_contratos::table
+        .filter(solicitacoes_contratos::statussolicitacao.eq(StatusSolicitacao::Aprovada))
+        .select(solicitacoes_contratos::idsolicitacao);
+    let contratos_encerrados = contratos::table
+        .filter(contratos::statuscontrato.ne_all(em_curso))
+        .select(contratos::idsolicitacaocontrato);
+    // Se o período acabou e o contrato está atrasado (ou ainda ativo, antes de
+    // `encerra_periodo_contrato` passar por ele), a máquina pode não ter voltado, e a reserva
+    // ocupa qualquer período até a devolução ser registrada.
+    let contratos_ativos = contratos::table
+        .filter(contratos::statuscontrato.eq_any(em_curso))
+        .select(contratos::idsolicitacaocontrato);
+    let agora = chrono::Utc::now().naive_utc();
+
+    reservas_maquinas
+        .filter(idmaquina.eq(maquina))
+        .filter(datainicio.lt(fim).and(datafim.gt(inicio))
+            .or(datafim.le(agora).and(idsolicitacao.eq_any(contratos_ativos))))
+        .filter(idsolicitacao.eq_any(solicitacoes_aprovadas))
+        .filter(idsolicitacao.ne_all(contratos_encerrados))
+        .into_boxed()
+}
+
+/// Trava a máquina até o fim da transação, para que duas solicitações não reservem o mesmo
+/// período ao mesmo tempo.
+fn trava_maquina(conn: &mut PgConnection, id: &str) -> Result<(), ErroApi>{
+    use crate::schema::maquinas::dsl::*;
+
+    let res: Result<String, diesel::result::Error> = maquinas
+        .filter(idmaquina.eq(id))
+        .select(idmaquina)
+        .for_update()
+        .get_result(conn);
+    match res{
+        Ok(_) => {
+            return Ok(())
+        },
+        Err(diesel::result::Error::NotFound) => {
+            return Err(ErroApi::NaoEncontrado("Máquina não encontrada.".to_string()))
+        },
+        Err(e) => {
+            return Err(e.into())
+        }
+    }
+}
+
+/// Confere, dentro de uma transação que já travou a máquina, se o período está livre.
+/// A reserva da própria solicitação, se houver, é ignorada.
+pub fn verifica_periodo_livre(conn: &mut PgConnection, reserva: &ReservaMaquina) -> Result<(), ErroApi>{
+    use crate::schema::reservas_maquinas::dsl::*;
+
+    trava_maquina(conn, &reserva.idmaquina)?;
+
+    let res: Result<i64, diesel::result::Error> = consulta_reservas_ocupadas(reserva.idmaquina.clone(), reserva.datainicio, reserva.datafim)
+        .filter(idsolicitacao.ne(reserva.idsolicitacao.clone()))
+        .count()
+        .get_result(conn);
+    match res{
+        Ok(0) => {
+            return Ok(())
+        },
+        Ok(_) => {
+            return Err(ErroApi::Conflito("A máquina já está reservada nesse período.".to_string()))
+        },
+        Err(e) => {
+            return Err(e.into())
+        }
+    }
+}
+
+pub fn cadastra_reserva(conn: &mut PgConnection, reserva: ReservaMaquina) -> Result<(), ErroApi>{
+    use crate::schema::reservas_maquinas::dsl::*;
+
+    let res = diesel::insert_into(reservas_maquinas)
+        .values(reserva)
+        .execute(conn);
+    match res{
+        Ok(_) => {
+            return Ok(())
+        },
+        Err(e) => {
+            return Err(e.into())
+        }
+    }
+}
+
+pub fn busca_reserva_idsolicitacao(conn: &mut PgConnection, id: &str) -> Result<Option<ReservaMaquina>, ErroApi>{
+    use crate::schema::reservas_maquinas::dsl::*;
+
+    let res: Result<ReservaMaquina, diesel::result::Error> = reservas_maquinas
+        .filter(idsolicitacao.eq(id))
+        .get_result(conn);
+    match res{
+        Ok(reserva) => {
+            return Ok(Some(reserva))
+        },
+        Err(diesel::result::Error::NotFound) => {
+            return Ok(None)
+        },
+        Err(e) => {
+            return Err(e.into())
+        }
+    }
+}
+
+/// Reservas que ocupam a máquina entre `inicio` e `fim`, em ordem cronológica.
+pub async fn busca_reservas_ocupadas(conn: &Conexao, maquina: String, inicio: NaiveDateTime, fim: NaiveDateTime)
+    -> Result<Vec<ReservaMaquina>, ErroApi>{
+    executa(conn, move |conn| {
+        use crate::schema::reservas_maquinas::dsl::*;
+
+        let res: Result<Vec<ReservaMaquina>, diesel::result::Error> = consulta_reservas_ocupadas(maquina, inicio, fim)
+            .order(datainicio.asc())
+            .select(ReservaMaquina::as_select())
+            .get_results(conn);
+        match res{
+            Ok(reservas) => {
+                return Ok(reservas)
+            },
+            Err(e) => {
+                return Err(e.into())
+            }
+        }
+    }).await
+}
@@ -1,6 +1,125 @@
+use std::{fmt, str::FromStr};
+
 use chrono::NaiveDateTime;
-use diesel::{prelude::{Insertable, Queryable}, query_dsl::methods::FilterDsl, ExpressionMethods, PgConnection, RunQueryDsl, Selectable};
+use diesel::{deserialize::FromSqlRow, expression::AsExpression, prelude::{Insertable, Queryable}, query_dsl::methods::FilterDsl, sql_types::Varchar, BoolExpressionMethods, Connection, ExpressionMethods, PgConnection, RunQueryDsl, Selectable};
 use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use super::{contratos::{insere_contrato, Contrato}, executa, precos_maquinas::MedidaTempo, reservas_maquinas::{busca_reserva_idsolicitacao, cadastra_reserva, verifica_periodo_livre, ReservaMaquina}, Conexao};
+use crate::{dinheiro::Dinheiro, erros::ErroApi, ids::gera_id};
+
+/// Ciclo de vida de uma solicitação. Toda solicitação nasce aguardando aprovação e passa
+/// uma única vez para um dos status finais. No banco e no JSON, cada status é gravado com
+/// o texto exibido no front-end.
+#[derive(Serialize, Deserialize, ToSchema, AsExpression, FromSqlRow, Debug, Clone, Copy, PartialEq, Eq)]
+#[diesel(sql_type = Varchar)]
+pub enum StatusSolicitacao{
+    #[serde(rename = "Aguardando aprovação")]
+    AguardandoAprovacao,
+    #[serde(rename = "Solicitação aprovada")]
+    Aprovada,
+    #[serde(rename = "Solicitação recusada")]
+    Recusada,
+    #[serde(rename = "Solicitação cancelada")]
+    Cancelada,
+    #[serde(rename = "Solicitação expirada")]
+    Expirada
+}
+
+/// Quem fez uma transição de status.
+#[derive(Serialize, Deserialize, ToSchema, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum PapelSolicitacao{
+    Locador,
+    Locatario,
+    /// Transições automáticas, como a expiração.
+    Sistema,
+    /// Administrador moderando uma solicitação da qual não faz parte.
+    Admin
+}
+
+impl StatusSolicitacao{
+    pub fn texto(&self) -> &'static str{
+        match self{
+            StatusSolicitacao::AguardandoAprovacao => "Aguardando aprovação",
+            StatusSolicitacao::Aprovada => "Solicitação aprovada",
+            StatusSolicitacao::Recusada => "Solicitação recusada",
+            StatusSolicitacao::Cancelada => "Solicitação cancelada",
+            StatusSolicitacao::Expirada => "Solicitação expirada"
+        }
+    }
+
+    /// Papel que pode levar a solicitação para este status.
+    pub fn papel_exigido(&self) -> Option<PapelSolicitacao>{
+        match self{
+            StatusSolicitacao::AguardandoAprovacao => None,
+            StatusSolicitacao::Aprovada | StatusSolicitacao::Recusada => Some(PapelSolicitacao::Locador),
+            StatusSolicitacao::Cancelada => Some(PapelSolicitacao::Locatario),
+            StatusSolicitacao::Expirada => Some(PapelSolicitacao::Sistema)
+        }
+    }
+
+    /// Confere se `papel` pode mudar a solicitação deste status para `novo`.
+    pub fn valida_transicao(&self, novo: StatusSolicitacao, papel: PapelSolicitacao) -> Result<(), ErroApi>{
+        if *self != StatusSolicitacao::AguardandoAprovacao || novo == StatusSolicitacao::AguardandoAprovacao{
+            return Err(ErroApi::Conflito(format!("Não é possível mudar a solicitação de '{}' para '{}'.", self, novo)))
+        }
+        // O admin pode recusar ou cancelar qualquer solicitação, mas não aprovar no lugar do locador.
+        if papel == PapelSolicitacao::Admin && matches!(novo, StatusSolicitacao::Recusada | StatusSolicitacao::Cancelada){
+            return Ok(())
+        }
+        if novo.papel_exigido() != Some(papel){
+            let mensagem = match novo.papel_exigido(){
+                Some(PapelSolicitacao::Locador) => "Só o locador pode aprovar ou recusar a solicitação.",
+                Some(PapelSolicitacao::Locatario) => "Só o locatário pode cancelar a solicitação.",
+                _ => "Esse status só pode ser definido pelo sistema."
+            };
+            return Err(ErroApi::Proibido(mensagem.to_string()))
+        }
+        return Ok(())
+    }
+}
+
+impl PapelSolicitacao{
+    pub fn texto(&self) -> &'static str{
+        match self{
+            PapelSolicitacao::Locador => "locador",
+            PapelSolicitacao::Locatario => "locatario",
+            PapelSolicitacao::Sistema => "sistema",
+            PapelSolicitacao::Admin => "admin"
+        }
+    }
+}
+
+impl fmt::Display for StatusSolicitacao{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result{
+        write!(f, "{}", self.texto())
+    }
+}
+
+impl FromStr for StatusSolicitacao{
+    type Err = ErroApi;
+
+    fn from_str(texto: &str) -> Result<Self, Self::Err>{
+        let todos = [
+            StatusSolicitacao::AguardandoAprovacao,
+            StatusSolicitacao::Aprovada,
+            StatusSolicitacao::Recusada,
+            StatusSolicitacao::Cancelada,
+            StatusSolicitacao::Expirada
+        ];
+        match todos.into_iter().find(|status| status.texto() == texto.trim()){
+            Some(status) => {
+                return Ok(status)
+            },
+            None => {
+                return Err(ErroApi::validacao_campo("status", "Status de solicitação inválido."))
+            }
+        }
+    }
+}
+
+enum_texto_sql!(StatusSolicitacao);
 
 #[derive(Queryable, Selectable, Insertable, Serialize, Deserialize, Debug, Clone)]
 #[diesel(table_name = crate::schema::solicitacoes_contratos)]
@@ -10,119 +129,318 @@ pub struct SolicitacaoContrato{
     pub idlocador: String, 
     pub idlocatario: String,
     pub idmaquina: String,
-    pub medidatempolocacao: String,
+    pub medidatempolocacao: MedidaTempo,
     pub origemsolicitacao: String,
-    pub statussolicitacao: String,
+    pub statussolicitacao: StatusSolicitacao,
     pub prazolocacao: f64,
-    pub valorsolicitacao: f64,
+    pub valorsolicitacao: Dinheiro,
     pub datasolicitacao: NaiveDateTime 
 }
 
-pub async fn cadastra_solicitacao_contrato(conn: &mut PgConnection, solicitacao: SolicitacaoContrato)
-    -> Result<String, String>{
-    use crate::schema::solicitacoes_contratos::dsl::*;
- 
-    let res: Result<SolicitacaoContrato, diesel::result::Error> = diesel::insert_into(solicitacoes_contratos)
-      .values(solicitacao)
-      .get_result(conn);
-    
-    match res{
-      Ok(solicitacao) => {
-        return Ok(solicitacao.idsolicitacao)
-      },
-      Err(e) => {
-        return Err(e.to_string())
-      }
-    }
+/// Registro de uma mudança de status. `idator` fica vazio nas transições do sistema.
+#[derive(Queryable, Selectable, Insertable, Serialize, Deserialize, Debug, Clone)]
+#[diesel(table_name = crate::schema::historico_solicitacoes)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct HistoricoSolicitacao{
+    pub idhistorico: String,
+    pub idsolicitacao: String,
+    pub statusanterior: Option<StatusSolicitacao>,
+    pub statusnovo: StatusSolicitacao,
+    pub papelator: String,
+    pub idator: Option<String>,
+    pub datatransicao: NaiveDateTime
 }
 
-pub async fn busca_solicitacoes_idlocador(conn: &mut PgConnection, id: String)
-    -> Result<Vec<SolicitacaoContrato>, String>{
-    use crate::schema::solicitacoes_contratos::dsl::*;
+fn registra_historico(conn: &mut PgConnection, id: &str, anterior: Option<StatusSolicitacao>, novo: StatusSolicitacao,
+    papel: PapelSolicitacao, ator: Option<String>) -> Result<(), ErroApi>{
+    use crate::schema::historico_solicitacoes::dsl::*;
 
-    let res: Result<Vec<SolicitacaoContrato>, diesel::result::Error> = diesel::QueryDsl::order_by(solicitacoes_contratos
-      .filter(idlocador.eq(id)), datasolicitacao.desc())
-      .get_results(conn);
-    
+    let historico = HistoricoSolicitacao{
+        idhistorico: gera_id(),
+        idsolicitacao: id.to_string(),
+        statusanterior: anterior,
+        statusnovo: novo,
+        papelator: papel.texto().to_string(),
+        idator: ator,
+        datatransicao: chrono::Utc::now().naive_utc()
+    };
+    let res = diesel::insert_into(historico_solicitacoes)
+        .values(historico)
+        .execute(conn);
     match res{
-      Ok(solicitacoes) => {
-        return Ok(solicitacoes)
-      },
-      Err(e) => {
-        return Err(e.to_string())
-      }
+        Ok(_) => {
+            return Ok(())
+        },
+        Err(e) => {
+            return Err(e.into())
+        }
     }
 }
 
-pub async fn busca_solicitacoes_idlocatario(conn: &mut PgConnection, id: String)
-    -> Result<Vec<SolicitacaoContrato>, String>{
-    use crate::schema::solicitacoes_contratos::dsl::*;
+/// Salva a solicitação junto com a reserva do período pedido. Falha com `Conflito` se a
+/// máquina já estiver ocupada nesse período.
+pub async fn cadastra_solicitacao_contrato(conn: &Conexao, solicitacao: SolicitacaoContrato, reserva: ReservaMaquina)
+    -> Result<String, ErroApi>{
+    executa(conn, move |conn| {
+        use crate::schema::solicitacoes_contratos::dsl::*;
 
-    let res: Result<Vec<SolicitacaoContrato>, diesel::result::Error> = diesel::QueryDsl::order_by(solicitacoes_contratos
-      .filter(idlocatario.eq(id)), datasolicitacao.desc())
-      .get_results(conn);
-    
-    match res{
-      Ok(solicitacoes) => {
-        return Ok(solicitacoes)
-      },
-      Err(e) => {
-        return Err(e.to_string())
-      }
-    }
+        let idlocatario_ator = solicitacao.idlocatario.clone();
+        conn.transaction(|conn| {
+          verifica_periodo_livre(conn, &reserva)?;
+
+          let res: Result<SolicitacaoContrato, diesel::result::Error> = diesel::insert_into(solicitacoes_contratos)
+            .values(solicitacao)
+            .get_result(conn);
+
+          let id = match res{
+            Ok(solicitacao) => {
+              solicitacao.idsolicitacao
+            },
+            Err(e) => {
+              return Err(e.into())
+            }
+          };
+
+          cadastra_reserva(conn, reserva)?;
+          registra_historico(conn, &id, None, StatusSolicitacao::AguardandoAprovacao,
+            PapelSolicitacao::Locatario, Some(idlocatario_ator))?;
+          return Ok(id)
+        })
+    }).await
+}
+
+pub async fn busca_solicitacoes_idlocador(conn: &Conexao, id: String)
+    -> Result<Vec<SolicitacaoContrato>, ErroApi>{
+    executa(conn, move |conn| {
+        use crate::schema::solicitacoes_contratos::dsl::*;
+
+        let res: Result<Vec<SolicitacaoContrato>, diesel::result::Error> = diesel::QueryDsl::order_by(solicitacoes_contratos
+          .filter(idlocador.eq(id)), datasolicitacao.desc())
+          .get_results(conn);
+
+        match res{
+          Ok(solicitacoes) => {
+            return Ok(solicitacoes)
+          },
+          Err(e) => {
+            return Err(e.into())
+          }
+        }
+    }).await
+}
+
+pub async fn busca_solicitacoes_idlocatario(conn: &Conexao, id: String)
+    -> Result<Vec<SolicitacaoContrato>, ErroApi>{
+    executa(conn, move |conn| {
+        use crate::schema::solicitacoes_contratos::dsl::*;
+
+        let res: Result<Vec<SolicitacaoContrato>, diesel::result::Error> = diesel::QueryDsl::order_by(solicitacoes_contratos
+          .filter(idlocatario.eq(id)), datasolicitacao.desc())
+          .get_results(conn);
+
+        match res{
+          Ok(solicitacoes) => {
+            return Ok(solicitacoes)
+          },
+          Err(e) => {
+            return Err(e.into())
+          }
+        }
+    }).await
 }
 
-pub async fn busca_solicitacoes_idmaquina(conn: &mut PgConnection, id: String)
-    -> Result<Vec<SolicitacaoContrato>, String>{
+pub async fn busca_solicitacoes_idmaquina(conn: &Conexao, id: String)
+    -> Result<Vec<SolicitacaoContrato>, ErroApi>{
+    executa(conn, move |conn| {
+        use crate::schema::solicitacoes_contratos::dsl::*;
+
+        let res: Result<Vec<SolicitacaoContrato>, diesel::result::Error> = diesel::QueryDsl::order_by(solicitacoes_contratos
+          .filter(idmaquina.eq(id)), datasolicitacao.desc())
+          .get_results(conn);
+
+        match res{
+          Ok(solicitacoes) => {
+            return Ok(solicitacoes)
+          },
+          Err(e) => {
+            return Err(e.into())
+          }
+        }
+    }).await
+}
+
+/// Muda o status da solicitação, conferindo a transição e o papel de quem a pede.
+/// `ator` é o usuário que fez a mudança, ou `None` para as transições do sistema.
+/// Ao aprovar, confere antes se o período reservado ainda está livre.
+pub async fn atualiza_status_solicitacao(conn: &Conexao, id: String, novostatus: StatusSolicitacao, ator: Option<String>)
+    -> Result<SolicitacaoContrato, ErroApi>{
+    executa(conn, move |conn| {
+        conn.transaction(|conn| {
+          transiciona_status(conn, &id, novostatus, ator, false)
+        })
+    }).await
+}
+
+/// Aprova a solicitação e grava o contrato dela na mesma transação: se o contrato não puder
+/// ser gravado, a solicitação continua aguardando aprovação. `admin` tem o mesmo papel que
+/// em `modera_status_solicitacao`.
+pub async fn aprova_solicitacao(conn: &Conexao, contrato: Contrato, ator: String, admin: bool)
+    -> Result<SolicitacaoContrato, ErroApi>{
+    executa(conn, move |conn| {
+        conn.transaction(|conn| {
+          let id = contrato.idsolicitacaocontrato.clone();
+          let solicitacao = transiciona_status(conn, &id, StatusSolicitacao::Aprovada, Some(ator), admin)?;
+          insere_contrato(conn, contrato)?;
+          return Ok(solicitacao)
+        })
+    }).await
+}
+
+/// Como `atualiza_status_solicitacao`, para um administrador. Se ele não fizer parte da
+/// solicitação, a mudança fica registrada com o papel `admin`.
+pub async fn modera_status_solicitacao(conn: &Conexao, id: String, novostatus: StatusSolicitacao, idadmin: String)
+    -> Result<SolicitacaoContrato, ErroApi>{
+    executa(conn, move |conn| {
+        conn.transaction(|conn| {
+          transiciona_status(conn, &id, novostatus, Some(idadmin), true)
+        })
+    }).await
+}
+
+fn transiciona_status(conn: &mut PgConnection, id: &str, novostatus: StatusSolicitacao, ator: Option<String>, admin: bool)
+    -> Result<SolicitacaoContrato, ErroApi>{
     use crate::schema::solicitacoes_contratos::dsl::*;
 
-    let res: Result<Vec<SolicitacaoContrato>, diesel::result::Error> = diesel::QueryDsl::order_by(solicitacoes_contratos
-      .filter(idmaquina.eq(id)), datasolicitacao.desc())
-      .get_results(conn);
-    
-    match res{
-      Ok(solicitacoes) => {
-        return Ok(solicitacoes)
+    // A linha fica travada até o fim da transação, então duas mudanças simultâneas não
+    // partem do mesmo status.
+    let res: Result<SolicitacaoContrato, diesel::result::Error> = diesel::QueryDsl::for_update(solicitacoes_contratos
+      .filter(idsolicitacao.eq(id)))
+      .get_result(conn);
+    let atual = match res{
+      Ok(solicitacao) => {
+        solicitacao
       },
       Err(e) => {
-        return Err(e.to_string())
+        return Err(e.into())
       }
-    }
-}
+    };
 
-pub async fn atualiza_status_solicitacao(conn: &mut PgConnection, id: String, novostatus: String)
-    -> Result<SolicitacaoContrato, String>{
-    use crate::schema::solicitacoes_contratos::dsl::*;
+    let papel = match &ator{
+      None => PapelSolicitacao::Sistema,
+      Some(idusuario) if novostatus.papel_exigido() == Some(PapelSolicitacao::Locador) && *idusuario == atual.idlocador => PapelSolicitacao::Locador,
+      Some(idusuario) if *idusuario == atual.idlocatario => PapelSolicitacao::Locatario,
+      Some(idusuario) if *idusuario == atual.idlocador => PapelSolicitacao::Locador,
+      Some(_) if admin => PapelSolicitacao::Admin,
+      Some(_) => {
+        return Err(ErroApi::Proibido("Você não faz parte dessa solicitação.".to_string()))
+      }
+    };
+    atual.statussolicitacao.valida_transicao(novostatus, papel)?;
+
+    if novostatus == StatusSolicitacao::Aprovada{
+      if let Some(reserva) = busca_reserva_idsolicitacao(conn, id)?{
+        verifica_periodo_livre(conn, &reserva)?;
+      }
+    }
 
     let res: Result<SolicitacaoContrato, diesel::result::Error> = diesel::update(solicitacoes_contratos)
       .filter(idsolicitacao.eq(id))
       .set(statussolicitacao.eq(novostatus))
       .get_result(conn);
-
-    match res{
+    let solicitacao = match res{
       Ok(solicitacao) => {
-        return Ok(solicitacao)
+        solicitacao
       },
       Err(e) => {
-        return Err(e.to_string())
+        return Err(e.into())
       }
-    }
+    };
+
+    registra_historico(conn, id, Some(atual.statussolicitacao), novostatus, papel, ator)?;
+    return Ok(solicitacao)
 }
 
-pub async fn busca_solicitacao_idsolicitacao(conn: &mut PgConnection, id: String)
-  -> Result<SolicitacaoContrato, String>{
-  use crate::schema::solicitacoes_contratos::dsl::*;
-
-  let res = solicitacoes_contratos
-    .filter(idsolicitacao.eq(id))
-    .get_result(conn);
-  
-  match res{
-    Ok(solicitacao) => {
-      return Ok(solicitacao)
-    },
-    Err(e) => {
-      return Err(e.to_string())
-    }
-  }
+/// Expira as solicitações que ainda aguardam aprovação quando o período reservado já
+/// começou ou quando foram feitas antes de `limite`. Devolve as solicitações expiradas.
+pub async fn expira_solicitacoes_pendentes(conn: &Conexao, limite: NaiveDateTime)
+    -> Result<Vec<SolicitacaoContrato>, ErroApi>{
+    executa(conn, move |conn| {
+        use crate::schema::solicitacoes_contratos::dsl::*;
+        use crate::schema::reservas_maquinas;
+
+        let agora = chrono::Utc::now().naive_utc();
+        let reservas_iniciadas = diesel::QueryDsl::select(reservas_maquinas::table
+          .filter(reservas_maquinas::datainicio.le(agora)), reservas_maquinas::idsolicitacao);
+        let res: Result<Vec<String>, diesel::result::Error> = diesel::QueryDsl::select(solicitacoes_contratos
+          .filter(statussolicitacao.eq(StatusSolicitacao::AguardandoAprovacao))
+          .filter(datasolicitacao.lt(limite).or(idsolicitacao.eq_any(reservas_iniciadas))), idsolicitacao)
+          .get_results(conn);
+        let ids = match res{
+          Ok(ids) => {
+            ids
+          },
+          Err(e) => {
+            return Err(e.into())
+          }
+        };
+
+        let mut expiradas = Vec::new();
+        for id in ids{
+          // Cada solicitação expira na sua própria transação. Se alguém mudou o status no
+          // meio do caminho, a transição é recusada e a solicitação fica como está.
+          let res = conn.transaction(|conn| {
+            transiciona_status(conn, &id, StatusSolicitacao::Expirada, None, false)
+          });
+          match res{
+            Ok(solicitacao) => {
+              expiradas.push(solicitacao)
+            },
+            Err(ErroApi::Conflito(_)) => {},
+            Err(e) => {
+              return Err(e)
+            }
+          }
+        }
+        return Ok(expiradas)
+    }).await
+}
+
+pub async fn busca_historico_solicitacao(conn: &Conexao, id: String)
+    -> Result<Vec<HistoricoSolicitacao>, ErroApi>{
+    executa(conn, move |conn| {
+        use crate::schema::historico_solicitacoes::dsl::*;
+
+        let res: Result<Vec<HistoricoSolicitacao>, diesel::result::Error> = diesel::QueryDsl::order_by(historico_solicitacoes
+          .filter(idsolicitacao.eq(id)), datatransicao.asc())
+          .get_results(conn);
+
+        match res{
+          Ok(historico) => {
+            return Ok(historico)
+          },
+          Err(e) => {
+            return Err(e.into())
+          }
+        }
+    }).await
+}
+
+pub async fn busca_solicitacao_idsolicitacao(conn: &Conexao, id: String)
+  -> Result<SolicitacaoContrato, ErroApi>{
+    executa(conn, move |conn| {
+      use crate::schema::solicitacoes_contratos::dsl::*;
+
+      let res = solicitacoes_contratos
+        .filter(idsolicitacao.eq(id))
+        .get_result(conn);
+
+      match res{
+        Ok(solicitacao) => {
+          return Ok(solicitacao)
+        },
+        Err(e) => {
+          return Err(e.into())
+        }
+      }
+    }).await
 }
\ No newline at end of file
@@ -0,0 +1,35 @@
+use chrono::NaiveDateTime;
+use diesel::{ExpressionMethods, QueryDsl, RunQueryDsl};
+
+use super::{executa, Conexao};
+use crate::erros::ErroApi;
+
+/// Registra o token de renovação como usado. Cada token renova a sessão uma vez só: se ele
+/// já foi usado, retorna `NaoAutorizado`. Os registros de tokens já expirados são apagados.
+pub async fn consome_token_renovacao(conn: &Conexao, idtoken_: String, dataexpiracao_: NaiveDateTime)
+    -> Result<(), ErroApi>{
+    executa(conn, move |conn| {
+        use crate::schema::tokens_renovacao_usados::dsl::*;
+
+        let agora = chrono::Utc::now().naive_utc();
+        if let Err(e) = diesel::delete(tokens_renovacao_usados.filter(dataexpiracao.lt(agora))).execute(conn){
+            return Err(e.into())
+        }
+
+        let res = diesel::insert_into(tokens_renovacao_usados)
+            .values((idtoken.eq(idtoken_), dataexpiracao.eq(dataexpiracao_)))
+            .on_conflict_do_nothing()
+            .execute(conn);
+        match res{
+            Ok(1) => {
+                return Ok(())
+            },
+            Ok(_) => {
+                return Err(ErroApi::NaoAutorizado("O token de renovação já foi usado. Entre de novo.".to_string()))
+            },
+            Err(e) => {
+                return Err(e.into())
+            }
+        }
+    }).await
+}
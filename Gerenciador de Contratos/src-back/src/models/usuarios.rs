@@ -1,7 +1,56 @@
+use std::{fmt, str::FromStr};
+
 use chrono::NaiveDateTime;
-use diesel::prelude::*;
+use diesel::{deserialize::FromSqlRow, expression::AsExpression, prelude::*};
 use serde::{Deserialize, Serialize};
-use crate::{controllers::{cria_conn, usuarios::AtualizaUsuarioInput}, schema::usuarios::{self, idusuario}};
+
+use super::{executa, Conexao};
+use crate::{erros::ErroApi, ids::gera_id_publico_unico};
+use crate::{controllers::{cria_pool, usuarios::AtualizaUsuarioInput}, schema::usuarios::{self, idusuario}};
+
+/// Situação da conta. Contas criadas com senha começam pendentes até o código de ativação
+/// enviado por e-mail ser confirmado; só contas ativas podem fazer login.
+#[derive(Serialize, Deserialize, AsExpression, FromSqlRow, Debug, Clone, Copy, PartialEq, Eq)]
+#[diesel(sql_type = diesel::sql_types::Varchar)]
+pub enum StatusUsuario{
+    Pendente,
+    Ativo,
+    Suspenso
+}
+
+impl StatusUsuario{
+    pub fn texto(&self) -> &'static str{
+        match self{
+            StatusUsuario::Pendente => "Pendente",
+            StatusUsuario::Ativo => "Ativo",
+            StatusUsuario::Suspenso => "Suspenso"
+        }
+    }
+}
+
+impl fmt::Display for StatusUsuario{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result{
+        write!(f, "{}", self.texto())
+    }
+}
+
+impl FromStr for StatusUsuario{
+    type Err = ErroApi;
+
+    fn from_str(texto: &str) -> Result<Self, Self::Err>{
+        let todos = [StatusUsuario::Pendente, StatusUsuario::Ativo, StatusUsuario::Suspenso];
+        match todos.into_iter().find(|status| status.texto() == texto.trim()){
+            Some(status) => {
+                return Ok(status)
+            },
+            None => {
+                return Err(ErroApi::validacao_campo("status", "Status de usuário inválido."))
+            }
+        }
+    }
+}
+
+enum_texto_sql!(StatusUsuario);
 
 #[derive(Queryable, Selectable, Insertable, Serialize, Deserialize, Debug)]
 #[diesel(table_name = crate::schema::usuarios)]
@@ -14,235 +63,297 @@ pub struct Usuario {
     pub datacadastro: NaiveDateTime,
     pub idusuario: String,
     pub origemconta: String,
-    pub status: String,
+    pub status: StatusUsuario,
     pub idpublico: String
 }
 
-pub async fn cadastra_usuario(conn: &mut PgConnection, usuario: Usuario) 
-    -> Result<(), String>{
-    let res: Result<Usuario, diesel::result::Error> = diesel::insert_into(usuarios::table)
-        .values(usuario)
-        .get_result(conn);
-    match res{
-        Ok(_res) => {
-            return Ok(())
-        },
-        Err(e) => {
-            return Err(e.to_string())
+/// Sorteia um `idpublico` que nenhum usuário usa.
+pub async fn gera_idpublico_usuario(conn: &Conexao) -> Result<String, ErroApi>{
+    executa(conn, move |conn| {
+        use crate::schema::usuarios::dsl::*;
+
+        gera_id_publico_unico(|id| {
+            match diesel::select(diesel::dsl::exists(usuarios.filter(idpublico.eq(id)))).get_result::<bool>(conn){
+                Ok(existe) => {
+                    return Ok(existe)
+                },
+                Err(e) => {
+                    return Err(e.into())
+                }
+            }
+        })
+    }).await
+}
+
+pub async fn cadastra_usuario(conn: &Conexao, usuario: Usuario) 
+    -> Result<(), ErroApi>{
+    executa(conn, move |conn| {
+        let res: Result<Usuario, diesel::result::Error> = diesel::insert_into(usuarios::table)
+            .values(usuario)
+            .get_result(conn);
+        match res{
+            Ok(_res) => {
+                return Ok(())
+            },
+            Err(e) => {
+                return Err(e.into())
+            }
         }
-    }
+    }).await
 }
 
 // Só utilizar em testes
 pub async fn deleta_usuario(id: String) 
-    -> Result<(), String>{
-
-    let conn = &mut cria_conn().unwrap();
-
-    let res: Result<Usuario, diesel::result::Error> = diesel::delete(usuarios::table)
-        .filter(idusuario.eq(id))
-        .get_result(conn);
-    match res{
-        Ok(_) => {
-            return Ok(())
-        },
-        Err(e) => {
-            return Err(e.to_string())
+    -> Result<(), ErroApi>{
+    let pool = cria_pool().unwrap();
+    let conn = &pool.get().await.unwrap();
+
+    executa(conn, move |conn| {
+        let res: Result<Usuario, diesel::result::Error> = diesel::delete(usuarios::table)
+            .filter(idusuario.eq(id))
+            .get_result(conn);
+        match res{
+            Ok(_) => {
+                return Ok(())
+            },
+            Err(e) => {
+                return Err(e.into())
+            }
         }
-    }
+    }).await
 }
 
-pub async fn busca_email_usuario(conn: &mut PgConnection, id: String) 
-    -> Result<String, String>{
-    use self::usuarios::dsl::*;
+pub async fn busca_email_usuario(conn: &Conexao, id: String) 
+    -> Result<String, ErroApi>{
+    executa(conn, move |conn| {
+        use self::usuarios::dsl::*;
 
-    let res = usuarios.filter(idusuario.eq(id)).select(Usuario::as_select()).first(conn);
-    match res{
-        Ok(usuario) => {
-            return Ok(usuario.email)
-        },
-        Err(e) => {
-            return Err(e.to_string())
+        let res = usuarios.filter(idusuario.eq(id)).select(Usuario::as_select()).first(conn);
+        match res{
+            Ok(usuario) => {
+                return Ok(usuario.email)
+            },
+            Err(e) => {
+                return Err(e.into())
+            }
         }
-    }
+    }).await
 }
 
-pub async fn atualiza_email_usuario(conn: &mut PgConnection, email_antigo: String, email_novo: String)
-     -> Result<String, String>{
-    use self::usuarios::dsl::*;
+pub async fn atualiza_email_usuario(conn: &Conexao, email_antigo: String, email_novo: String)
+     -> Result<String, ErroApi>{
+    executa(conn, move |conn| {
+        use self::usuarios::dsl::*;
 
-    let res = usuarios.filter(email.eq(email_antigo)).select(Usuario::as_select()).first(conn);
-    let id = match res{
-        Ok(usuario) => {
-            usuario.idusuario
-        },
-        Err(e) => {
-            return Err(e.to_string())
-        }
-    };
-
-    let usuario_atualizado = diesel::update(usuarios.find(id))
-        .set(email.eq(email_novo))
-        .returning(Usuario::as_returning())
-        .get_result(conn);
-
-    match usuario_atualizado{
-        Ok(usuario_atualizado) => {
-            return Ok(usuario_atualizado.idusuario)
-        },
-        Err(e) => {
-            return Err(e.to_string())
-        }
-    }
+        let res = usuarios.filter(email.eq(email_antigo)).select(Usuario::as_select()).first(conn);
+        let id = match res{
+            Ok(usuario) => {
+                usuario.idusuario
+            },
+            Err(e) => {
+                return Err(e.into())
+            }
+        };
+
+        let usuario_atualizado = diesel::update(usuarios.find(id))
+            .set(email.eq(email_novo))
+            .returning(Usuario::as_returning())
+            .get_result(conn);
 
+        match usuario_atualizado{
+            Ok(usuario_atualizado) => {
+                return Ok(usuario_atualizado.idusuario)
+            },
+            Err(e) => {
+                return Err(e.into())
+            }
+        }
+    }).await
 }
 
-pub async fn busca_usuario_email(conn: &mut PgConnection, email_: String)
-    -> Result<String, String>{
-    use self::usuarios::dsl::*;
+pub async fn busca_usuario_email(conn: &Conexao, email_: String)
+    -> Result<String, ErroApi>{
+    executa(conn, move |conn| {
+        use self::usuarios::dsl::*;
 
-    let res: Result<Usuario, diesel::result::Error> = usuarios.filter(email.eq(email_)).first(conn);
-    match res{
-        Ok(usuario) => {
-            return Ok(usuario.idusuario)
-        },
-        Err(e) => {
-            return Err(e.to_string())
+        let res: Result<Usuario, diesel::result::Error> = usuarios.filter(email.eq(email_)).first(conn);
+        match res{
+            Ok(usuario) => {
+                return Ok(usuario.idusuario)
+            },
+            Err(e) => {
+                return Err(e.into())
+            }
         }
-    }
+    }).await
 }
 
-pub async fn busca_usuario_email_oauth(conn: &mut PgConnection, email_: String)
-    -> Result<String, String>{
-    use self::usuarios::dsl::*;
+pub async fn busca_usuario_email_oauth(conn: &Conexao, email_: String)
+    -> Result<String, ErroApi>{
+    executa(conn, move |conn| {
+        use self::usuarios::dsl::*;
 
-    let res: Result<Usuario, diesel::result::Error> = usuarios.filter(email.eq(email_).and(origemconta.eq("Google"))).first(conn);
-    match res{
-        Ok(usuario) => {
-            return Ok(usuario.idusuario)
-        },
-        Err(e) => {
-            return Err(e.to_string())
+        let res: Result<Usuario, diesel::result::Error> = usuarios.filter(email.eq(email_).and(origemconta.eq("Google"))).first(conn);
+        match res{
+            Ok(usuario) => {
+                return Ok(usuario.idusuario)
+            },
+            Err(e) => {
+                return Err(e.into())
+            }
         }
-    }
+    }).await
 }
 
-pub async fn busca_senha_usuario(conn: &mut PgConnection, email_: String) 
-    -> Result<String, String>{
-    use self::usuarios::dsl::*;
+pub async fn busca_senha_usuario(conn: &Conexao, email_: String) 
+    -> Result<String, ErroApi>{
+    executa(conn, move |conn| {
+        use self::usuarios::dsl::*;
 
-    let res: Result<Usuario, diesel::result::Error> = usuarios.filter(email.eq(email_)).first(conn);
-    match res{
-        Ok(usuario) => {
-            return Ok(usuario.senha)
-        },
-        Err(e) => {
-            return Err(e.to_string())
+        let res: Result<Usuario, diesel::result::Error> = usuarios.filter(email.eq(email_)).first(conn);
+        match res{
+            Ok(usuario) => {
+                return Ok(usuario.senha)
+            },
+            Err(e) => {
+                return Err(e.into())
+            }
         }
-    }
+    }).await
 }
 
-pub async fn atualiza_senha_usuario(conn: &mut PgConnection, email_: String, senha_nova: String) 
-    -> Result<String, String>{
-    use self::usuarios::dsl::*;
+pub async fn atualiza_senha_usuario(conn: &Conexao, email_: String, senha_nova: String) 
+    -> Result<String, ErroApi>{
+    executa(conn, move |conn| {
+        use self::usuarios::dsl::*;
 
-    let res = usuarios.filter(email.eq(email_)).select(Usuario::as_select()).first(conn);
-    let id = match res{
-        Ok(usuario) => {
-            usuario.idusuario
-        },
-        Err(e) => {
-           return Err(e.to_string())
-        }
-    };
-   
-    let usuario_atualizado = diesel::update(usuarios.find(id))
-        .set(senha.eq(senha_nova))
-        .returning(Usuario::as_returning())
-        .get_result(conn);
-   
-    match usuario_atualizado{
-        Ok(usuario_atualizado) => {
-           return Ok(usuario_atualizado.idusuario)
-        },
-        Err(e) => {
-            return Err(e.to_string())
-        }
-   }
-}
-pub async fn busca_usuario_id(conn: &mut PgConnection, id: String)
-    -> Result<Usuario, String>{
-    use self::usuarios::dsl::*;
-
-    let res: Result<Usuario, diesel::result::Error> = usuarios.filter(idusuario.eq(id)).first(conn);
-    match res{
-        Ok(usuario) => {
-            return Ok(usuario)
-        },
-        Err(e) => {
-            return Err(e.to_string())
+        let res = usuarios.filter(email.eq(email_)).select(Usuario::as_select()).first(conn);
+        let id = match res{
+            Ok(usuario) => {
+                usuario.idusuario
+            },
+            Err(e) => {
+               return Err(e.into())
+            }
+        };
+
+        let usuario_atualizado = diesel::update(usuarios.find(id))
+            .set(senha.eq(senha_nova))
+            .returning(Usuario::as_returning())
+            .get_result(conn);
+
+        match usuario_atualizado{
+            Ok(usuario_atualizado) => {
+               return Ok(usuario_atualizado.idusuario)
+            },
+            Err(e) => {
+                return Err(e.into())
+            }
+       }
+    }).await
+}
+pub async fn busca_usuario_id(conn: &Conexao, id: String)
+    -> Result<Usuario, ErroApi>{
+    executa(conn, move |conn| {
+        use self::usuarios::dsl::*;
+
+        let res: Result<Usuario, diesel::result::Error> = usuarios.filter(idusuario.eq(id)).first(conn);
+        match res{
+            Ok(usuario) => {
+                return Ok(usuario)
+            },
+            Err(e) => {
+                return Err(e.into())
+            }
         }
-    }
+    }).await
 }
 
-pub async fn atualiza_usuario(conn: &mut PgConnection, usuario: AtualizaUsuarioInput)
-    -> Result<String, String>{
-    use self::usuarios::dsl::*;
+pub async fn atualiza_usuario(conn: &Conexao, usuario: AtualizaUsuarioInput)
+    -> Result<String, ErroApi>{
+    executa(conn, move |conn| {
+        use self::usuarios::dsl::*;
 
-    let res: Result<Usuario, diesel::result::Error> = usuarios
-        .filter(email.eq(usuario.email_antigo))
-        .get_result(conn);
+        let res: Result<Usuario, diesel::result::Error> = usuarios
+            .filter(email.eq(usuario.email_antigo))
+            .get_result(conn);
 
-    let usuario_banco = match res{
-        Ok(usuario) => {
-            usuario
-        },
-        Err(e) => {
-            return Err(e.to_string())
+        let usuario_banco = match res{
+            Ok(usuario) => {
+                usuario
+            },
+            Err(e) => {
+                return Err(e.into())
+            }
+        };
+
+        let res: Result<Usuario, diesel::result::Error> = usuarios.filter(documento.eq(usuario.documento_novo.clone()))
+            .get_result(conn);
+
+        match res{
+            Ok(user) => {
+                if user.documento == usuario.documento_novo && usuario_banco.idusuario != user.idusuario{
+                    return Err(ErroApi::Conflito("Esse documento já pertence a outra pessoa.".to_string()))
+                }
+            },
+            Err(_) => {}
         }
-    };
 
-    let res: Result<Usuario, diesel::result::Error> = usuarios.filter(documento.eq(usuario.documento_novo.clone()))
-        .get_result(conn);
+        let res: Result<Usuario, diesel::result::Error> = diesel::update(usuarios)
+            .filter(idusuario.eq(usuario_banco.idusuario))
+            .set((
+                email.eq(usuario.email_novo),
+                documento.eq(usuario.documento_novo),
+                nome.eq(usuario.nome_novo)
+            )).get_result(conn);
 
-    match res{
-        Ok(user) => {
-            if user.documento == usuario.documento_novo && usuario_banco.idusuario != user.idusuario{
-                return Err("Esse documento já pertence a outra pessoa.".to_string())
+        match res{
+            Ok(usuario ) =>{
+                return Ok(usuario.idusuario)
+            },
+            Err(e) => {
+                return Err(e.into())
             }
-        },
-        Err(_) => {}
-    }
+        }
+    }).await
+}
 
-    let res: Result<Usuario, diesel::result::Error> = diesel::update(usuarios)
-        .filter(idusuario.eq(usuario_banco.idusuario))
-        .set((
-            email.eq(usuario.email_novo),
-            documento.eq(usuario.documento_novo),
-            nome.eq(usuario.nome_novo)
-        )).get_result(conn);
-        
-    match res{
-        Ok(usuario ) =>{
-            return Ok(usuario.idusuario)
-        },
-        Err(e) => {
-            return Err(e.to_string())
+pub async fn busca_usuario_documento(conn: &Conexao, doc: String)
+    -> Result<Usuario, ErroApi>{
+    executa(conn, move |conn| {
+        use self::usuarios::dsl::*;
+
+        let res: Result<Usuario, diesel::result::Error> = usuarios.filter(documento.eq(doc)).first(conn);
+        match res{
+            Ok(usuario) => {
+                return Ok(usuario)
+            },
+            Err(e) => {
+                return Err(e.into())
+            }
         }
-    }
+    }).await
 }
 
-pub async fn busca_usuario_documento(conn: &mut PgConnection, doc: String)
-    -> Result<Usuario, String>{
-    use self::usuarios::dsl::*;
+pub async fn atualiza_status_usuario(conn: &Conexao, id: String, novostatus: StatusUsuario)
+    -> Result<(), ErroApi>{
+    executa(conn, move |conn| {
+        use self::usuarios::dsl::*;
 
-    let res: Result<Usuario, diesel::result::Error> = usuarios.filter(documento.eq(doc)).first(conn);
-    match res{
-        Ok(usuario) => {
-            return Ok(usuario)
-        },
-        Err(e) => {
-            return Err(e.to_string())
+        let res = diesel::update(usuarios)
+            .filter(idusuario.eq(id))
+            .set(status.eq(novostatus))
+            .execute(conn);
+        match res{
+            Ok(0) => {
+                return Err(ErroApi::NaoEncontrado("Usuário não encontrado.".to_string()))
+            },
+            Ok(_) => {
+                return Ok(())
+            },
+            Err(e) => {
+                return Err(e.into())
+            }
         }
-    }
-}
\ No newline at end of file
+    }).await
+}
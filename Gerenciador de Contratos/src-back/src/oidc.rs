@@ -0,0 +1,615 @@
+// Provedores de login externos (OpenID Connect e OAuth2).
+//
+// Os provedores vêm do `.env`, sem código novo para cada um. `OIDC_PROVEDORES` lista as
+// chaves (por exemplo, `microsoft,keycloak`), e cada chave tem as suas variáveis `OIDC_<CHAVE>_*`:
+// - `DISCOVERY_URL`: o documento `.well-known/openid-configuration`, de onde vêm os endereços;
+// - `CLIENT_ID`, `CLIENT_SECRET`, `REDIRECT_URL` e `ESCOPOS` (padrão `openid email profile`);
+// - `NOME`: nome do provedor guardado em `usuarios.origemconta` (padrão: a chave);
+// - `AUTHORIZATION_URL`, `TOKEN_URL`, `USERINFO_URL`, `JWKS_URL` e `EMISSOR`: para provedores
+//   sem discovery, como o GitHub, ou para trocar um endereço do discovery;
+// - `CAMPO_SUB`, `CAMPO_EMAIL` e `CAMPO_NOME`: campos com o ID, o e-mail e o nome da conta no
+//   id_token ou no userinfo (padrão `sub`, `email` e `name`);
+// - `CONFIA_EMAIL` (`true` ou `false`): aceita o e-mail sem `email_verified`. Só para
+//   provedores que entregam apenas e-mails verificados.
+//
+// O Google continua configurado pelas variáveis `GOOGLE_OAUTH_*` quando não está em
+// `OIDC_PROVEDORES`.
+//
+// O login usa o fluxo authorization code com PKCE. `inicia_autorizacao` gera o `state`, o
+// `nonce` e o `code_verifier`, que ficam na memória do servidor até o usuário voltar do
+// provedor; com mais de uma instância da API, o usuário precisa voltar para a mesma.
+
+use std::{collections::HashMap, env, sync::{Arc, Mutex}, time::{Duration, Instant}};
+
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use dotenv::dotenv;
+use jsonwebtoken::{decode, decode_header, jwk::JwkSet, Algorithm, DecodingKey, Validation};
+use rand::{rngs::OsRng, RngCore};
+use reqwest::{header::ACCEPT, Client, Url};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use utoipa::ToSchema;
+
+use crate::erros::ErroApi;
+
+const URL_AUTORIZACAO_GOOGLE: &str = "https://accounts.google.com/o/oauth2/v2/auth";
+const URL_TOKEN_GOOGLE: &str = "https://oauth2.googleapis.com/token";
+const URL_CHAVES_GOOGLE: &str = "https://www.googleapis.com/oauth2/v3/certs";
+/// Valores aceitos no `iss` do id_token do Google.
+const EMISSORES_GOOGLE: [&str; 2] = ["https://accounts.google.com", "accounts.google.com"];
+const ESCOPOS_PADRAO: &str = "openid email profile";
+
+/// Por quanto tempo o discovery e as chaves públicas de cada provedor ficam guardados. Um
+/// `kid` desconhecido força uma nova busca das chaves antes disso.
+const SEGUNDOS_CACHE_PROVEDOR: u64 = 60 * 60;
+/// Tempo que o usuário tem para voltar do provedor.
+const SEGUNDOS_VALIDADE_FLUXO: u64 = 10 * 60;
+/// Acima dessa quantidade de fluxos pendentes, os vencidos são descartados.
+const FLUXOS_ANTES_LIMPEZA: usize = 10_000;
+
+/// Algoritmos de assinatura aceitos no id_token. Os simétricos (HS*) ficam de fora: a
+/// chave seria o client secret, que não prova que o token veio do provedor.
+const ALGORITMOS_ACEITOS: [Algorithm; 9] = [
+    Algorithm::RS256, Algorithm::RS384, Algorithm::RS512,
+    Algorithm::PS256, Algorithm::PS384, Algorithm::PS512,
+    Algorithm::ES256, Algorithm::ES384, Algorithm::EdDSA
+];
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConfiguracaoProvedor{
+    /// Identifica o provedor nas rotas (`/auth/{chave}`) e em `contas_externas`.
+    pub chave: String,
+    /// Guardado em `usuarios.origemconta` nas contas criadas pelo provedor.
+    pub nome: String,
+    pub client_id: String,
+    pub client_secret: String,
+    pub redirect_url: String,
+    pub escopos: String,
+    pub discovery_url: Option<String>,
+    pub authorization_url: Option<String>,
+    pub token_url: Option<String>,
+    pub userinfo_url: Option<String>,
+    pub jwks_url: Option<String>,
+    /// Valores aceitos no `iss`. Vazio, vale o `issuer` do discovery.
+    pub emissores: Vec<String>,
+    pub campo_sub: String,
+    pub campo_email: String,
+    pub campo_nome: String,
+    pub confia_email: bool
+}
+
+impl ConfiguracaoProvedor{
+    /// O Google, configurado pelas variáveis `GOOGLE_OAUTH_*`. `None` sem `GOOGLE_OAUTH_CLIENT_ID`.
+    pub fn google(variavel: &impl Fn(&str) -> Option<String>) -> Option<Self>{
+        let client_id = le_texto(variavel, "GOOGLE_OAUTH_CLIENT_ID")?;
+        Some(ConfiguracaoProvedor{
+            chave: "google".to_string(),
+            nome: "Google".to_string(),
+            client_id,
+            client_secret: le_texto(variavel, "GOOGLE_OAUTH_CLIENT_SECRET").unwrap_or_default(),
+            redirect_url: le_texto(variavel, "GOOGLE_OAUTH_REDIRECT_URL").unwrap_or_default(),
+            escopos: ESCOPOS_PADRAO.to_string(),
+            discovery_url: None,
+            authorization_url: Some(URL_AUTORIZACAO_GOOGLE.to_string()),
+            token_url: Some(le_texto(variavel, "GOOGLE_OAUTH_TOKEN_URL").unwrap_or(URL_TOKEN_GOOGLE.to_string())),
+            userinfo_url: None,
+            jwks_url: Some(le_texto(variavel, "GOOGLE_OAUTH_CERTS_URL").unwrap_or(URL_CHAVES_GOOGLE.to_string())),
+            emissores: EMISSORES_GOOGLE.iter().map(|emissor| emissor.to_string()).collect(),
+            campo_sub: "sub".to_string(),
+            campo_email: "email".to_string(),
+            campo_nome: "name".to_string(),
+            confia_email: false
+        })
+    }
+
+    /// Lê as variáveis `OIDC_<CHAVE>_*` de um provedor.
+    pub fn de_variaveis(chave: &str, variavel: &impl Fn(&str) -> Option<String>) -> Result<Self, ErroApi>{
+        let prefixo = format!("OIDC_{}_", chave.to_uppercase().replace('-', "_"));
+        let le = |nome: &str| le_texto(variavel, &format!("{}{}", prefixo, nome));
+        let obrigatoria = |nome: &str| match le(nome){
+            Some(valor) => Ok(valor),
+            None => Err(ErroApi::Interno(format!("{}{} não definido para o provedor de login {}.", prefixo, nome, chave)))
+        };
+
+        let confia_email = match le("CONFIA_EMAIL").as_deref(){
+            None | Some("false") => false,
+            Some("true") => true,
+            Some(outro) => {
+                return Err(ErroApi::Interno(format!("{}CONFIA_EMAIL inválido: {}. Use true ou false.", prefixo, outro)))
+            }
+        };
+        let mut nome_padrao = chave.to_string();
+        if let Some(primeira) = nome_padrao.get_mut(0..1){
+            primeira.make_ascii_uppercase();
+        }
+
+        let configuracao = ConfiguracaoProvedor{
+            chave: chave.to_string(),
+            nome: le("NOME").unwrap_or(nome_padrao),
+            client_id: obrigatoria("CLIENT_ID")?,
+            client_secret: le("CLIENT_SECRET").unwrap_or_default(),
+            redirect_url: obrigatoria("REDIRECT_URL")?,
+            escopos: le("ESCOPOS").unwrap_or(ESCOPOS_PADRAO.to_string()),
+            discovery_url: le("DISCOVERY_URL"),
+            authorization_url: le("AUTHORIZATION_URL"),
+            token_url: le("TOKEN_URL"),
+            userinfo_url: le("USERINFO_URL"),
+            jwks_url: le("JWKS_URL"),
+            emissores: le("EMISSOR").into_iter().collect(),
+            campo_sub: le("CAMPO_SUB").unwrap_or("sub".to_string()),
+            campo_email: le("CAMPO_EMAIL").unwrap_or("email".to_string()),
+            campo_nome: le("CAMPO_NOME").unwrap_or("name".to_string()),
+            confia_email
+        };
+
+        if configuracao.discovery_url.is_none(){
+            obrigatoria("AUTHORIZATION_URL")?;
+            obrigatoria("TOKEN_URL")?;
+            if configuracao.jwks_url.is_none() && configuracao.userinfo_url.is_none(){
+                return Err(ErroApi::Interno(format!("Defina {}DISCOVERY_URL, {}JWKS_URL ou {}USERINFO_URL para o provedor de login {}.", prefixo, prefixo, prefixo, chave)))
+            }
+        }
+        return Ok(configuracao)
+    }
+}
+
+fn le_texto(variavel: &impl Fn(&str) -> Option<String>, nome: &str) -> Option<String>{
+    variavel(nome)
+        .map(|valor| valor.trim().to_string())
+        .filter(|valor| !valor.is_empty())
+}
+
+/// Endereços do provedor, da configuração ou do discovery.
+#[derive(Debug, Clone)]
+struct Enderecos{
+    autorizacao: String,
+    token: String,
+    userinfo: Option<String>,
+    jwks: Option<String>,
+    emissores: Vec<String>
+}
+
+#[derive(Deserialize)]
+struct DocumentoDiscovery{
+    issuer: String,
+    authorization_endpoint: String,
+    token_endpoint: String,
+    userinfo_endpoint: Option<String>,
+    jwks_uri: Option<String>
+}
+
+/// Resposta do endpoint de token. Provedores só OAuth2, como o GitHub, não enviam o id_token.
+#[derive(Deserialize, Debug)]
+pub struct TokensProvedor{
+    pub access_token: String,
+    pub id_token: Option<String>
+}
+
+/// A conta do usuário no provedor, já conferida.
+#[derive(Debug, Clone, PartialEq)]
+pub struct IdentidadeExterna{
+    pub sub: String,
+    pub email: String,
+    pub nome: Option<String>
+}
+
+/// Endereço para onde o front-end envia o usuário e o `state` que volta com o código.
+#[derive(Serialize, Deserialize, ToSchema, Debug)]
+pub struct InicioAutorizacao{
+    pub url: String,
+    pub estado: String
+}
+
+/// Provedor com a configuração e o que foi buscado dele (discovery e chaves públicas).
+#[derive(Debug)]
+pub struct ProvedorOidc{
+    pub configuracao: ConfiguracaoProvedor,
+    enderecos: Mutex<Option<(Instant, Enderecos)>>,
+    chaves: Mutex<Option<(Instant, JwkSet)>>
+}
+
+fn erro_provedor(nome: &str, e: impl std::fmt::Display) -> ErroApi{
+    ErroApi::ServicoExterno(format!("Erro na comunicação com {}: {}", nome, e))
+}
+
+fn texto_aleatorio() -> String{
+    let mut bytes = [0_u8; 32];
+    OsRng.fill_bytes(&mut bytes);
+    URL_SAFE_NO_PAD.encode(bytes)
+}
+
+/// `code_challenge` do PKCE (método `S256`) para o `code_verifier`.
+pub fn desafio_pkce(verificador: &str) -> String{
+    URL_SAFE_NO_PAD.encode(Sha256::digest(verificador.as_bytes()))
+}
+
+/// Campo de texto dos claims. IDs numéricos, como os do GitHub, viram texto.
+fn campo_texto(claims: &HashMap<String, Value>, campo: &str) -> Option<String>{
+    match claims.get(campo)?{
+        Value::String(texto) => Some(texto.trim().to_string()).filter(|texto| !texto.is_empty()),
+        Value::Number(numero) => Some(numero.to_string()),
+        _ => None
+    }
+}
+
+impl ProvedorOidc{
+    pub fn new(configuracao: ConfiguracaoProvedor) -> Self{
+        ProvedorOidc{
+            configuracao,
+            enderecos: Mutex::new(None),
+            chaves: Mutex::new(None)
+        }
+    }
+
+    async fn busca_json<T: serde::de::DeserializeOwned>(&self, requisicao: reqwest::RequestBuilder) -> Result<T, ErroApi>{
+        let nome = &self.configuracao.nome;
+        let resposta = match requisicao.header(ACCEPT, "application/json").send().await{
+            Ok(resposta) => resposta,
+            Err(e) => {
+                return Err(erro_provedor(nome, e))
+            }
+        };
+        if !resposta.status().is_success(){
+            return Err(erro_provedor(nome, resposta.status()))
+        }
+        match resposta.json::<T>().await{
+            Ok(json) => {
+                return Ok(json)
+            },
+            Err(e) => {
+                return Err(erro_provedor(nome, e))
+            }
+        }
+    }
+
+    async fn enderecos(&self) -> Result<Enderecos, ErroApi>{
+        let configuracao = &self.configuracao;
+        let discovery_url = match &configuracao.discovery_url{
+            Some(url) => url,
+            None => {
+                // Sem discovery, a configuração já foi conferida em `de_variaveis`.
+                return Ok(Enderecos{
+                    autorizacao: configuracao.authorization_url.clone().unwrap_or_default(),
+                    token: configuracao.token_url.clone().unwrap_or_default(),
+                    userinfo: configuracao.userinfo_url.clone(),
+                    jwks: configuracao.jwks_url.clone(),
+                    emissores: configuracao.emissores.clone()
+                })
+            }
+        };
+
+        if let Some((buscados, enderecos)) = self.enderecos.lock().unwrap().as_ref(){
+            if buscados.elapsed() < Duration::from_secs(SEGUNDOS_CACHE_PROVEDOR){
+                return Ok(enderecos.clone())
+            }
+        }
+        let documento: DocumentoDiscovery = self.busca_json(Client::new().get(discovery_url)).await?;
+        let enderecos = Enderecos{
+            autorizacao: configuracao.authorization_url.clone().unwrap_or(documento.authorization_endpoint),
+            token: configuracao.token_url.clone().unwrap_or(documento.token_endpoint),
+            userinfo: configuracao.userinfo_url.clone().or(documento.userinfo_endpoint),
+            jwks: configuracao.jwks_url.clone().or(documento.jwks_uri),
+            emissores: if configuracao.emissores.is_empty() { vec![documento.issuer] } else { configuracao.emissores.clone() }
+        };
+        *self.enderecos.lock().unwrap() = Some((Instant::now(), enderecos.clone()));
+        return Ok(enderecos)
+    }
+
+    /// Chaves públicas do provedor. `renova` ignora as chaves guardadas.
+    async fn chaves(&self, url: &str, renova: bool) -> Result<JwkSet, ErroApi>{
+        if !renova{
+            if let Some((buscadas, chaves)) = self.chaves.lock().unwrap().as_ref(){
+                if buscadas.elapsed() < Duration::from_secs(SEGUNDOS_CACHE_PROVEDOR){
+                    return Ok(chaves.clone())
+                }
+            }
+        }
+        let chaves: JwkSet = self.busca_json(Client::new().get(url)).await?;
+        *self.chaves.lock().unwrap() = Some((Instant::now(), chaves.clone()));
+        return Ok(chaves)
+    }
+
+    /// Monta o endereço de autorização e guarda o `state`, o `nonce` e o `code_verifier` do PKCE.
+    pub async fn inicia_autorizacao(&self, fluxos: &FluxosPendentes) -> Result<InicioAutorizacao, ErroApi>{
+        let enderecos = self.enderecos().await?;
+        let mut url = match Url::parse(&enderecos.autorizacao){
+            Ok(url) => url,
+            Err(e) => {
+                return Err(ErroApi::Interno(format!("Endereço de autorização inválido para {}: {}", self.configuracao.nome, e)))
+            }
+        };
+
+        let fluxo = FluxoPendente{
+            provedor: self.configuracao.chave.clone(),
+            verificador: texto_aleatorio(),
+            nonce: texto_aleatorio(),
+            criado: Instant::now()
+        };
+        let estado = texto_aleatorio();
+        url.query_pairs_mut()
+            .append_pair("response_type", "code")
+            .append_pair("client_id", &self.configuracao.client_id)
+            .append_pair("redirect_uri", &self.configuracao.redirect_url)
+            .append_pair("scope", &self.configuracao.escopos)
+            .append_pair("state", &estado)
+            .append_pair("nonce", &fluxo.nonce)
+            .append_pair("code_challenge", &desafio_pkce(&fluxo.verificador))
+            .append_pair("code_challenge_method", "S256");
+        fluxos.guarda(estado.clone(), fluxo);
+
+        return Ok(InicioAutorizacao{
+            url: url.to_string(),
+            estado
+        })
+    }
+
+    /// Troca o código de autorização pelos tokens. `verificador` é o `code_verifier` do PKCE
+    /// guardado em `inicia_autorizacao`.
+    pub async fn troca_codigo(&self, codigo: &str, verificador: &str) -> Result<TokensProvedor, ErroApi>{
+        let enderecos = self.enderecos().await?;
+        let configuracao = &self.configuracao;
+        let parametros = [
+            ("grant_type", "authorization_code"),
+            ("redirect_uri", configuracao.redirect_url.as_str()),
+            ("client_id", configuracao.client_id.as_str()),
+            ("client_secret", configuracao.client_secret.as_str()),
+            ("code", codigo),
+            ("code_verifier", verificador),
+        ];
+
+        let resposta = match Client::new().post(&enderecos.token).header(ACCEPT, "application/json").form(&parametros).send().await{
+            Ok(resposta) => resposta,
+            Err(e) => {
+                return Err(erro_provedor(&configuracao.nome, e))
+            }
+        };
+        if !resposta.status().is_success(){
+            let mensagem = resposta.text().await.unwrap_or_default();
+            return Err(ErroApi::NaoAutorizado(format!("{} recusou o código de autorização: {}", configuracao.nome, mensagem)))
+        }
+        match resposta.json::<TokensProvedor>().await{
+            Ok(tokens) => {
+                return Ok(tokens)
+            },
+            Err(e) => {
+                // O GitHub responde 200 com `error` no corpo quando recusa o código.
+                return Err(ErroApi::NaoAutorizado(format!("Resposta de token inválida de {}: {}", configuracao.nome, e)))
+            }
+        }
+    }
+
+    /// Confere a assinatura, o emissor, a audiência, a validade e o `nonce` do id_token e
+    /// devolve os claims.
+    pub async fn valida_id_token(&self, id_token: &str, nonce: Option<&str>) -> Result<HashMap<String, Value>, ErroApi>{
+        let nome = &self.configuracao.nome;
+        let invalido = |detalhe: &str| ErroApi::NaoAutorizado(format!("O id_token de {} é inválido: {}", nome, detalhe));
+
+        let cabecalho = match decode_header(id_token){
+            Ok(cabecalho) => cabecalho,
+            Err(e) => {
+                return Err(invalido(&e.to_string()))
+            }
+        };
+        if !ALGORITMOS_ACEITOS.contains(&cabecalho.alg){
+            return Err(invalido("algoritmo não aceito"))
+        }
+        let kid = match cabecalho.kid{
+            Some(kid) => kid,
+            None => {
+                return Err(invalido("sem kid"))
+            }
+        };
+
+        let enderecos = self.enderecos().await?;
+        let url_chaves = match &enderecos.jwks{
+            Some(url) => url,
+            None => {
+                return Err(ErroApi::Interno(format!("O provedor de login {} não tem endereço de chaves (JWKS_URL).", nome)))
+            }
+        };
+        let mut chaves = self.chaves(url_chaves, false).await?;
+        if chaves.find(&kid).is_none(){
+            chaves = self.chaves(url_chaves, true).await?;
+        }
+        let chave = match chaves.find(&kid).map(DecodingKey::from_jwk){
+            Some(Ok(chave)) => chave,
+            Some(Err(e)) => {
+                return Err(erro_provedor(nome, e))
+            },
+            None => {
+                return Err(invalido("chave desconhecida"))
+            }
+        };
+
+        let mut validacao = Validation::new(cabecalho.alg);
+        validacao.set_audience(&[&self.configuracao.client_id]);
+        validacao.set_issuer(&enderecos.emissores);
+        let claims = match decode::<HashMap<String, Value>>(id_token, &chave, &validacao){
+            Ok(dados) => dados.claims,
+            Err(e) => {
+                return Err(invalido(&e.to_string()))
+            }
+        };
+
+        if let Some(nonce) = nonce{
+            if campo_texto(&claims, "nonce").as_deref() != Some(nonce){
+                return Err(invalido("nonce diferente do enviado"))
+            }
+        }
+        return Ok(claims)
+    }
+
+    async fn busca_userinfo(&self, url: &str, access_token: &str) -> Result<HashMap<String, Value>, ErroApi>{
+        self.busca_json(Client::new().get(url).bearer_auth(access_token)).await
+    }
+
+    /// A conta do usuário no provedor: do id_token e, se faltar o e-mail ou o provedor não
+    /// enviar id_token, do userinfo.
+    pub async fn identidade(&self, tokens: &TokensProvedor, nonce: &str) -> Result<IdentidadeExterna, ErroApi>{
+        let configuracao = &self.configuracao;
+        let userinfo = self.enderecos().await?.userinfo;
+
+        let mut claims = match &tokens.id_token{
+            Some(id_token) => self.valida_id_token(id_token, Some(nonce)).await?,
+            None => HashMap::new()
+        };
+        if campo_texto(&claims, &configuracao.campo_email).is_none(){
+            let url = match &userinfo{
+                Some(url) => url,
+                None => {
+                    return Err(ErroApi::NaoAutorizado(format!("{} não informou o e-mail da conta.", configuracao.nome)))
+                }
+            };
+            let dados = self.busca_userinfo(url, &tokens.access_token).await?;
+            // O userinfo precisa ser da mesma conta do id_token.
+            if tokens.id_token.is_some() && campo_texto(&dados, &configuracao.campo_sub) != campo_texto(&claims, &configuracao.campo_sub){
+                return Err(ErroApi::NaoAutorizado(format!("O userinfo de {} é de outra conta.", configuracao.nome)))
+            }
+            for (campo, valor) in dados{
+                claims.entry(campo).or_insert(valor);
+            }
+        }
+
+        let sub = match campo_texto(&claims, &configuracao.campo_sub){
+            Some(sub) => sub,
+            None => {
+                return Err(ErroApi::NaoAutorizado(format!("{} não informou o ID da conta.", configuracao.nome)))
+            }
+        };
+        let email = match campo_texto(&claims, &configuracao.campo_email){
+            Some(email) => email,
+            None => {
+                return Err(ErroApi::NaoAutorizado(format!("{} não informou o e-mail da conta.", configuracao.nome)))
+            }
+        };
+        let verificado = match claims.get("email_verified"){
+            Some(Value::Bool(verificado)) => *verificado,
+            Some(Value::String(verificado)) => verificado == "true",
+            _ => configuracao.confia_email
+        };
+        if !verificado{
+            return Err(ErroApi::NaoAutorizado(format!("O e-mail da conta de {} não foi verificado.", configuracao.nome)))
+        }
+
+        return Ok(IdentidadeExterna{
+            sub,
+            email,
+            nome: campo_texto(&claims, &configuracao.campo_nome)
+        })
+    }
+}
+
+/// Login iniciado em `inicia_autorizacao`, esperando o usuário voltar do provedor.
+#[derive(Debug, Clone)]
+pub struct FluxoPendente{
+    pub provedor: String,
+    pub verificador: String,
+    pub nonce: String,
+    criado: Instant
+}
+
+/// Fluxos pendentes por `state`. Cada um só pode ser usado uma vez.
+#[derive(Default)]
+pub struct FluxosPendentes{
+    fluxos: Mutex<HashMap<String, FluxoPendente>>
+}
+
+impl FluxosPendentes{
+    fn guarda(&self, estado: String, fluxo: FluxoPendente){
+        let mut fluxos = self.fluxos.lock().unwrap();
+        if fluxos.len() > FLUXOS_ANTES_LIMPEZA{
+            fluxos.retain(|_, fluxo| fluxo.criado.elapsed() < Duration::from_secs(SEGUNDOS_VALIDADE_FLUXO));
+        }
+        fluxos.insert(estado, fluxo);
+    }
+
+    /// Tira o fluxo do `state`, que precisa ser do mesmo provedor e estar no prazo.
+    pub fn consome(&self, estado: &str, provedor: &str) -> Result<FluxoPendente, ErroApi>{
+        let fluxo = self.fluxos.lock().unwrap().remove(estado);
+        match fluxo{
+            Some(fluxo) if fluxo.provedor == provedor && fluxo.criado.elapsed() < Duration::from_secs(SEGUNDOS_VALIDADE_FLUXO) => {
+                return Ok(fluxo)
+            },
+            _ => {
+                return Err(ErroApi::NaoAutorizado("O login expirou ou já foi usado. Comece de novo.".to_string()))
+            }
+        }
+    }
+}
+
+/// Nome de um provedor para a tela de login.
+#[derive(Serialize, Deserialize, ToSchema, Debug, PartialEq)]
+pub struct ProvedorPublico{
+    pub chave: String,
+    pub nome: String
+}
+
+/// Os provedores configurados e os logins em andamento.
+pub struct ProvedoresOidc{
+    provedores: Vec<ProvedorOidc>,
+    pub fluxos: FluxosPendentes
+}
+
+impl std::fmt::Debug for ProvedoresOidc{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result{
+        f.debug_list().entries(self.provedores.iter().map(|provedor| &provedor.configuracao.chave)).finish()
+    }
+}
+
+impl ProvedoresOidc{
+    pub fn new(configuracoes: Vec<ConfiguracaoProvedor>) -> Self{
+        ProvedoresOidc{
+            provedores: configuracoes.into_iter().map(ProvedorOidc::new).collect(),
+            fluxos: FluxosPendentes::default()
+        }
+    }
+
+    pub fn de_variaveis(variavel: impl Fn(&str) -> Option<String>) -> Result<Self, ErroApi>{
+        let mut configuracoes = Vec::new();
+        let chaves = le_texto(&variavel, "OIDC_PROVEDORES").unwrap_or_default();
+        for chave in chaves.split(',').map(|chave| chave.trim().to_lowercase()).filter(|chave| !chave.is_empty()){
+            if !chave.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_'){
+                return Err(ErroApi::Interno(format!("Chave de provedor de login inválida em OIDC_PROVEDORES: {}", chave)))
+            }
+            if configuracoes.iter().any(|configuracao: &ConfiguracaoProvedor| configuracao.chave == chave){
+                return Err(ErroApi::Interno(format!("Provedor de login repetido em OIDC_PROVEDORES: {}", chave)))
+            }
+            configuracoes.push(ConfiguracaoProvedor::de_variaveis(&chave, &variavel)?);
+        }
+
+        if !configuracoes.iter().any(|configuracao| configuracao.chave == "google"){
+            if let Some(google) = ConfiguracaoProvedor::google(&variavel){
+                configuracoes.push(google);
+            }
+        }
+        return Ok(ProvedoresOidc::new(configuracoes))
+    }
+
+    /// Provedores usados pela API, com a configuração do `.env`.
+    pub fn de_ambiente() -> Result<Arc<Self>, ErroApi>{
+        dotenv().ok();
+        let provedores = ProvedoresOidc::de_variaveis(|nome| env::var(nome).ok())?;
+        return Ok(Arc::new(provedores))
+    }
+
+    pub fn busca(&self, chave: &str) -> Result<&ProvedorOidc, ErroApi>{
+        match self.provedores.iter().find(|provedor| provedor.configuracao.chave == chave.trim().to_lowercase()){
+            Some(provedor) => {
+                return Ok(provedor)
+            },
+            None => {
+                return Err(ErroApi::NaoEncontrado(format!("O provedor de login {} não está configurado.", chave)))
+            }
+        }
+    }
+
+    pub fn lista(&self) -> Vec<ProvedorPublico>{
+        self.provedores.iter()
+            .map(|provedor| ProvedorPublico{
+                chave: provedor.configuracao.chave.clone(),
+                nome: provedor.configuracao.nome.clone()
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,253 @@
+// Gerador mínimo de PDF (texto puro em A4), usado para emitir os contratos de locação.
+// Usa apenas as fontes padrão Helvetica e Helvetica-Bold, que todo leitor de PDF já possui,
+// então o arquivo gerado não depende de fontes embutidas.
+
+const LARGURA_PAGINA: f64 = 595.0;
+const ALTURA_PAGINA: f64 = 842.0;
+const MARGEM: f64 = 56.0;
+
+/// Um trecho do documento. Cada variante é diagramada com uma fonte e um espaçamento próprios.
+#[derive(Debug, Clone, PartialEq)]
+pub enum BlocoPdf{
+    Titulo(String),
+    Secao(String),
+    Paragrafo(String),
+    Espaco
+}
+
+#[derive(Clone, Copy)]
+enum Fonte{
+    Regular,
+    Negrito
+}
+
+impl Fonte{
+    fn nome(&self) -> &'static str{
+        match self{
+            Fonte::Regular => "F1",
+            Fonte::Negrito => "F2"
+        }
+    }
+}
+
+struct Linha{
+    texto: String,
+    fonte: Fonte,
+    tamanho: f64,
+    centralizada: bool,
+    y: f64
+}
+
+/// Largura de um caractere da Helvetica, em milésimos do tamanho da fonte.
+/// Letras acentuadas usam a largura da letra base.
+fn largura_caractere(c: char) -> f64{
+    const LARGURAS: [u16; 95] = [
+        278, 278, 355, 556, 556, 889, 667, 191, 333, 333, 389, 584, 278, 333, 278, 278,
+        556, 556, 556, 556, 556, 556, 556, 556, 556, 556, 278, 278, 584, 584, 584, 556,
+        1015, 667, 667, 722, 722, 667, 611, 778, 722, 278, 500, 667, 556, 833, 722, 778,
+        667, 778, 722, 667, 611, 722, 667, 944, 667, 667, 611, 278, 278, 278, 469, 556,
+        333, 556, 556, 500, 556, 556, 278, 556, 556, 222, 222, 500, 222, 833, 556, 556,
+        556, 556, 333, 500, 278, 556, 500, 722, 500, 500, 500, 334, 260, 334, 584
+    ];
+    let base = match c{
+        'á' | 'à' | 'â' | 'ã' | 'ä' => 'a',
+        'Á' | 'À' | 'Â' | 'Ã' | 'Ä' => 'A',
+        'é' | 'ê' | 'è' => 'e',
+        'É' | 'Ê' | 'È' => 'E',
+        'í' | 'î' | 'ì' => 'i',
+        'Í' | 'Î' | 'Ì' => 'I',
+        'ó' | 'ô' | 'õ' | 'ò' | 'ö' => 'o',
+        'Ó' | 'Ô' | 'Õ' | 'Ò' | 'Ö' => 'O',
+        'ú' | 'ü' | 'ù' => 'u',
+        'Ú' | 'Ü' | 'Ù' => 'U',
+        'ç' => 'c',
+        'Ç' => 'C',
+        'º' | 'ª' => 'o',
+        _ => c
+    };
+    let codigo = base as u32;
+    if (32..127).contains(&codigo){
+        return LARGURAS[(codigo - 32) as usize] as f64
+    }
+    556.0
+}
+
+fn largura_texto(texto: &str, tamanho: f64, fonte: Fonte) -> f64{
+    let soma: f64 = texto.chars().map(largura_caractere).sum();
+    let fator = match fonte{
+        // A versão em negrito é um pouco mais larga; a margem evita estourar a linha.
+        Fonte::Negrito => 1.06,
+        Fonte::Regular => 1.0
+    };
+    soma * tamanho / 1000.0 * fator
+}
+
+/// Quebra o texto em linhas que cabem na largura útil da página.
+fn quebra_linhas(texto: &str, tamanho: f64, fonte: Fonte, largura_maxima: f64) -> Vec<String>{
+    let mut linhas = Vec::new();
+    let mut atual = String::new();
+    for palavra in texto.split_whitespace(){
+        let candidata = if atual.is_empty(){
+            palavra.to_string()
+        } else {
+            format!("{} {}", atual, palavra)
+        };
+        if largura_texto(&candidata, tamanho, fonte) <= largura_maxima || atual.is_empty(){
+            atual = candidata;
+        } else {
+            linhas.push(atual);
+            atual = palavra.to_string();
+        }
+    }
+    if !atual.is_empty(){
+        linhas.push(atual);
+    }
+    linhas
+}
+
+/// Distribui os blocos em páginas, calculando a posição vertical de cada linha.
+fn diagrama(blocos: &[BlocoPdf]) -> Vec<Vec<Linha>>{
+    let largura_util = LARGURA_PAGINA - 2.0 * MARGEM;
+    let limite_inferior = MARGEM + 20.0;
+    let mut paginas: Vec<Vec<Linha>> = vec![Vec::new()];
+    let mut y = ALTURA_PAGINA - MARGEM;
+
+    for bloco in blocos{
+        let (texto, fonte, tamanho, entrelinha, espaco_depois, centralizada) = match bloco{
+            BlocoPdf::Titulo(t) => (t, Fonte::Negrito, 14.0, 18.0, 10.0, true),
+            BlocoPdf::Secao(t) => (t, Fonte::Negrito, 11.0, 15.0, 4.0, false),
+            BlocoPdf::Paragrafo(t) => (t, Fonte::Regular, 10.0, 14.0, 6.0, false),
+            BlocoPdf::Espaco => {
+                y -= 10.0;
+                continue
+            }
+        };
+
+        let linhas = quebra_linhas(texto, tamanho, fonte, largura_util);
+        // Títulos de seção não ficam sozinhos no fim da página.
+        let minimo = match bloco{
+            BlocoPdf::Secao(_) => entrelinha * 3.0,
+            _ => entrelinha
+        };
+        if y - minimo < limite_inferior{
+            paginas.push(Vec::new());
+            y = ALTURA_PAGINA - MARGEM;
+        }
+
+        for texto_linha in linhas{
+            if y - entrelinha < limite_inferior{
+                paginas.push(Vec::new());
+                y = ALTURA_PAGINA - MARGEM;
+            }
+            y -= entrelinha;
+            paginas.last_mut().unwrap().push(Linha{
+                texto: texto_linha,
+                fonte,
+                tamanho,
+                centralizada,
+                y
+            });
+        }
+        y -= espaco_depois;
+    }
+    paginas
+}
+
+/// Converte o texto para WinAnsiEncoding e escapa os caracteres especiais das strings do PDF.
+fn codifica_texto(texto: &str) -> Vec<u8>{
+    let mut bytes = Vec::new();
+    for c in texto.chars(){
+        let byte = match c{
+            '(' | ')' | '\\' => {
+                bytes.push(b'\\');
+                c as u8
+            },
+            '\u{20}'..='\u{7e}' => c as u8,
+            '\u{a0}'..='\u{ff}' => c as u32 as u8,
+            '–' => 0x96,
+            '—' => 0x97,
+            '“' => 0x93,
+            '”' => 0x94,
+            '‘' => 0x91,
+            '’' => 0x92,
+            '€' => 0x80,
+            _ => b'?'
+        };
+        bytes.push(byte);
+    }
+    bytes
+}
+
+fn conteudo_pagina(linhas: &[Linha], rodape: &str) -> Vec<u8>{
+    let mut conteudo = Vec::new();
+    let mut escreve = |fonte: Fonte, tamanho: f64, x: f64, y: f64, texto: &str| {
+        conteudo.extend_from_slice(format!("BT /{} {:.1} Tf {:.2} {:.2} Td (", fonte.nome(), tamanho, x, y).as_bytes());
+        conteudo.extend_from_slice(&codifica_texto(texto));
+        conteudo.extend_from_slice(b") Tj ET\n");
+    };
+
+    for linha in linhas{
+        let x = if linha.centralizada{
+            (LARGURA_PAGINA - largura_texto(&linha.texto, linha.tamanho, linha.fonte)) / 2.0
+        } else {
+            MARGEM
+        };
+        escreve(linha.fonte, linha.tamanho, x, linha.y, &linha.texto);
+    }
+    let x = (LARGURA_PAGINA - largura_texto(rodape, 8.0, Fonte::Regular)) / 2.0;
+    escreve(Fonte::Regular, 8.0, x, MARGEM / 2.0, rodape);
+    conteudo
+}
+
+/// Gera um PDF A4 com os blocos informados. O rodapé de cada página recebe o texto de
+/// `rodape` seguido da numeração. O resultado é determinístico: a mesma entrada gera
+/// sempre os mesmos bytes.
+pub fn gera_pdf(titulo: &str, rodape: &str, blocos: &[BlocoPdf]) -> Vec<u8>{
+    let paginas = diagrama(blocos);
+    let total = paginas.len();
+
+    // 1: catálogo, 2: páginas, 3 e 4: fontes, 5: informações; depois, página + conteúdo.
+    let mut objetos: Vec<Vec<u8>> = Vec::new();
+    let kids: Vec<String> = (0..total).map(|i| format!("{} 0 R", 6 + i * 2)).collect();
+    objetos.push(b"<< /Type /Catalog /Pages 2 0 R >>".to_vec());
+    objetos.push(format!("<< /Type /Pages /Kids [{}] /Count {} >>", kids.join(" "), total).into_bytes());
+    objetos.push(b"<< /Type /Font /Subtype /Type1 /BaseFont /Helvetica /Encoding /WinAnsiEncoding >>".to_vec());
+    objetos.push(b"<< /Type /Font /Subtype /Type1 /BaseFont /Helvetica-Bold /Encoding /WinAnsiEncoding >>".to_vec());
+    let mut info = b"<< /Producer (MaqExpress) /Title (".to_vec();
+    info.extend_from_slice(&codifica_texto(titulo));
+    info.extend_from_slice(b") >>");
+    objetos.push(info);
+
+    for (i, linhas) in paginas.iter().enumerate(){
+        let id_conteudo = 7 + i * 2;
+        objetos.push(format!(
+            "<< /Type /Page /Parent 2 0 R /MediaBox [0 0 {} {}] /Resources << /Font << /F1 3 0 R /F2 4 0 R >> >> /Contents {} 0 R >>",
+            LARGURA_PAGINA, ALTURA_PAGINA, id_conteudo
+        ).into_bytes());
+        let conteudo = conteudo_pagina(linhas, &format!("{} - Página {} de {}", rodape, i + 1, total));
+        let mut stream = format!("<< /Length {} >>\nstream\n", conteudo.len()).into_bytes();
+        stream.extend_from_slice(&conteudo);
+        stream.extend_from_slice(b"\nendstream");
+        objetos.push(stream);
+    }
+
+    let mut pdf = b"%PDF-1.4\n%\xe2\xe3\xcf\xd3\n".to_vec();
+    let mut posicoes = Vec::new();
+    for (i, objeto) in objetos.iter().enumerate(){
+        posicoes.push(pdf.len());
+        pdf.extend_from_slice(format!("{} 0 obj\n", i + 1).as_bytes());
+        pdf.extend_from_slice(objeto);
+        pdf.extend_from_slice(b"\nendobj\n");
+    }
+
+    let inicio_xref = pdf.len();
+    pdf.extend_from_slice(format!("xref\n0 {}\n0000000000 65535 f \n", objetos.len() + 1).as_bytes());
+    for posicao in posicoes{
+        pdf.extend_from_slice(format!("{:010} 00000 n \n", posicao).as_bytes());
+    }
+    pdf.extend_from_slice(format!(
+        "trailer\n<< /Size {} /Root 1 0 R /Info 5 0 R >>\nstartxref\n{}\n%%EOF\n",
+        objetos.len() + 1, inicio_xref
+    ).as_bytes());
+    pdf
+}
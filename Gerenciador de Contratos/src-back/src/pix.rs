@@ -0,0 +1,289 @@
+// Cobranças com PIX. O "copia e cola" (BR Code) é montado aqui, no formato EMV do Banco
+// Central: cada campo é o ID, o tamanho com dois dígitos e o valor, e o último campo, `63`,
+// é o CRC16 do payload inteiro. O mesmo texto vai no QR Code.
+//
+// A cobrança estática leva a chave PIX do locador e pode ser paga em qualquer banco. A
+// dinâmica leva só a URL onde o provedor de pagamentos publica os dados da cobrança, e o
+// provedor avisa a API quando ela é paga. Tudo é configurado pelo `.env`:
+// - `PIX_URL_COBRANCA`: a URL base das cobranças no provedor, sem o `https://`. O txid da
+//   cobrança vai no fim. Sem ela, só as cobranças estáticas ficam disponíveis;
+// - `PIX_TOKEN_CONFIRMACAO`: o token que o provedor manda no cabeçalho `Authorization` ao
+//   confirmar um pagamento em `/confirma_pagamento_pix`. Sem ele, as confirmações são recusadas.
+
+use std::env;
+
+use dotenv::dotenv;
+use qrcode::{render::svg, EcLevel, QrCode};
+use rand::{distributions::Alphanumeric, Rng};
+use sha2::{Digest, Sha256};
+use uuid::Uuid;
+use validator::ValidateEmail;
+
+use crate::{dinheiro::Dinheiro, erros::ErroApi, models::chaves_pix::TipoChavePix};
+
+/// Identificador do arranjo PIX no campo 26 do BR Code.
+const GUI_PIX: &str = "br.gov.bcb.pix";
+
+/// Tamanho máximo do nome do recebedor e da cidade no BR Code.
+const TAMANHO_NOME: usize = 25;
+const TAMANHO_CIDADE: usize = 15;
+
+/// Tamanho dos txids gerados. Nas cobranças dinâmicas, o Banco Central exige de 26 a 35
+/// caracteres; nas estáticas, no máximo 25.
+const TAMANHO_TXID_ESTATICA: usize = 25;
+const TAMANHO_TXID_DINAMICA: usize = 32;
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ConfiguracaoPix{
+    pub url_cobranca: Option<String>,
+    pub token_confirmacao: Option<String>
+}
+
+impl ConfiguracaoPix{
+    pub fn de_variaveis(variavel: impl Fn(&str) -> Option<String>) -> Self{
+        let le = |nome: &str| variavel(nome)
+            .map(|valor| valor.trim().to_string())
+            .filter(|valor| !valor.is_empty());
+
+        let url_cobranca = le("PIX_URL_COBRANCA").map(|url| {
+            let url = url.trim_start_matches("https://").trim_end_matches('/');
+            url.to_string()
+        });
+        return ConfiguracaoPix{
+            url_cobranca,
+            token_confirmacao: le("PIX_TOKEN_CONFIRMACAO")
+        }
+    }
+
+    pub fn de_ambiente() -> Self{
+        dotenv().ok();
+        ConfiguracaoPix::de_variaveis(|nome| env::var(nome).ok())
+    }
+
+    /// Confere o token enviado pelo provedor de pagamentos. Os hashes são comparados, e não os
+    /// tokens, para que o tempo da comparação não revele o token configurado.
+    pub fn verifica_token(&self, token: &str) -> Result<(), ErroApi>{
+        let configurado = match &self.token_confirmacao{
+            Some(configurado) => configurado,
+            None => {
+                return Err(ErroApi::Indisponivel("A confirmação de pagamentos PIX não está configurada.".to_string()))
+            }
+        };
+        if Sha256::digest(token.trim().as_bytes()) != Sha256::digest(configurado.as_bytes()){
+            return Err(ErroApi::NaoAutorizado("Token de confirmação inválido.".to_string()))
+        }
+        return Ok(())
+    }
+}
+
+fn digitos(texto: &str) -> Vec<u32>{
+    texto.chars().filter_map(|c| c.to_digit(10)).collect()
+}
+
+/// Dígito verificador do CPF e do CNPJ: a soma ponderada módulo 11.
+fn digito_verificador(numeros: &[u32], pesos: &[u32]) -> u32{
+    let soma: u32 = numeros.iter().zip(pesos).map(|(numero, peso)| numero * peso).sum();
+    let resto = soma % 11;
+    if resto < 2 { 0 } else { 11 - resto }
+}
+
+pub fn valida_cpf(cpf: &[u32]) -> bool{
+    if cpf.len() != 11 || cpf.iter().all(|d| *d == cpf[0]){
+        return false
+    }
+    let primeiro = digito_verificador(&cpf[..9], &[10, 9, 8, 7, 6, 5, 4, 3, 2]);
+    let segundo = digito_verificador(&cpf[..10], &[11, 10, 9, 8, 7, 6, 5, 4, 3, 2]);
+    return cpf[9] == primeiro && cpf[10] == segundo
+}
+
+pub fn valida_cnpj(cnpj: &[u32]) -> bool{
+    if cnpj.len() != 14 || cnpj.iter().all(|d| *d == cnpj[0]){
+        return false
+    }
+    let primeiro = digito_verificador(&cnpj[..12], &[5, 4, 3, 2, 9, 8, 7, 6, 5, 4, 3, 2]);
+    let segundo = digito_verificador(&cnpj[..13], &[6, 5, 4, 3, 2, 9, 8, 7, 6, 5, 4, 3, 2]);
+    return cnpj[12] == primeiro && cnpj[13] == segundo
+}
+
+/// Valida a chave conforme o tipo e a devolve no formato do DICT: CPF e CNPJ só com os números,
+/// e-mail em minúsculas, telefone como `+55DDNNNNNNNNN` e chave aleatória como UUID.
+pub fn normaliza_chave_pix(tipo: TipoChavePix, chave: &str) -> Result<String, ErroApi>{
+    let chave = chave.trim();
+    let invalida = |mensagem: &str| ErroApi::validacao_campo("chave", mensagem);
+    match tipo{
+        TipoChavePix::Cpf => {
+            let numeros = digitos(chave);
+            if !valida_cpf(&numeros){
+                return Err(invalida("CPF inválido."))
+            }
+            return Ok(numeros.iter().map(|d| d.to_string()).collect())
+        },
+        TipoChavePix::Cnpj => {
+            let numeros = digitos(chave);
+            if !valida_cnpj(&numeros){
+                return Err(invalida("CNPJ inválido."))
+            }
+            return Ok(numeros.iter().map(|d| d.to_string()).collect())
+        },
+        TipoChavePix::Email => {
+            let email = chave.to_lowercase();
+            if email.len() > 77 || !email.is_ascii() || !email.validate_email(){
+                return Err(invalida("E-mail inválido."))
+            }
+            return Ok(email)
+        },
+        TipoChavePix::Telefone => {
+            if chave.chars().any(|c| !c.is_ascii_digit() && !" +()-".contains(c)){
+                return Err(invalida("Telefone inválido."))
+            }
+            let mut numeros: String = chave.chars().filter(|c| c.is_ascii_digit()).collect();
+            if numeros.len() == 10 || numeros.len() == 11{
+                numeros.insert_str(0, "55");
+            }
+            // DDI 55, DDD sem zero e número de 8 ou 9 dígitos.
+            if !(numeros.len() == 12 || numeros.len() == 13) || !numeros.starts_with("55") || numeros[2..].starts_with('0'){
+                return Err(invalida("Telefone inválido. Use o DDD e o número, como +55 11 91234-5678."))
+            }
+            return Ok(format!("+{}", numeros))
+        },
+        TipoChavePix::Aleatoria => {
+            match Uuid::parse_str(chave){
+                Ok(uuid) if chave.len() == 36 => {
+                    return Ok(uuid.hyphenated().to_string())
+                },
+                _ => {
+                    return Err(invalida("Chave aleatória inválida."))
+                }
+            }
+        }
+    }
+}
+
+/// Tira os acentos e os caracteres fora do ASCII, que o BR Code não aceita, e corta o texto
+/// no tamanho máximo do campo.
+pub fn normaliza_texto_br_code(texto: &str, tamanho: usize) -> String{
+    let texto: String = texto.chars()
+        .filter_map(|c| match c{
+            'á' | 'à' | 'â' | 'ã' | 'ä' => Some('a'),
+            'Á' | 'À' | 'Â' | 'Ã' | 'Ä' => Some('A'),
+            'é' | 'è' | 'ê' | 'ë' => Some('e'),
+            'É' | 'È' | 'Ê' | 'Ë' => Some('E'),
+            'í' | 'ì' | 'î' | 'ï' => Some('i'),
+            'Í' | 'Ì' | 'Î' | 'Ï' => Some('I'),
+            'ó' | 'ò' | 'ô' | 'õ' | 'ö' => Some('o'),
+            'Ó' | 'Ò' | 'Ô' | 'Õ' | 'Ö' => Some('O'),
+            'ú' | 'ù' | 'û' | 'ü' => Some('u'),
+            'Ú' | 'Ù' | 'Û' | 'Ü' => Some('U'),
+            'ç' => Some('c'),
+            'Ç' => Some('C'),
+            'ñ' => Some('n'),
+            'Ñ' => Some('N'),
+            c if c.is_ascii() && !c.is_ascii_control() => Some(c),
+            c if c.is_whitespace() => Some(' '),
+            _ => None
+        })
+        .collect();
+    texto.split_whitespace().collect::<Vec<_>>().join(" ").chars().take(tamanho).collect::<String>().trim().to_string()
+}
+
+/// CRC16-CCITT (polinômio 0x1021, valor inicial 0xFFFF), usado no campo 63 do BR Code.
+pub fn crc16(dados: &[u8]) -> u16{
+    let mut crc: u16 = 0xFFFF;
+    for byte in dados{
+        crc ^= (*byte as u16) << 8;
+        for _ in 0..8{
+            crc = if crc & 0x8000 != 0 { (crc << 1) ^ 0x1021 } else { crc << 1 };
+        }
+    }
+    crc
+}
+
+/// Um campo EMV: ID, tamanho com dois dígitos e valor.
+fn campo(id: &str, valor: &str) -> Result<String, ErroApi>{
+    if valor.len() > 99{
+        return Err(ErroApi::Interno(format!("O campo {} do BR Code passou de 99 caracteres.", id)))
+    }
+    return Ok(format!("{}{:02}{}", id, valor.len(), valor))
+}
+
+/// Para onde o pagamento vai.
+#[derive(Debug, Clone, PartialEq)]
+pub enum DestinoPix{
+    /// Cobrança estática, com a chave do recebedor.
+    Chave(String),
+    /// Cobrança dinâmica, com a URL da cobrança no provedor, sem o `https://`.
+    Url(String)
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct DadosBrCode{
+    pub destino: DestinoPix,
+    /// Sem valor, quem paga informa o valor no banco.
+    pub valor: Option<Dinheiro>,
+    pub nome_recebedor: String,
+    pub cidade: String,
+    /// Sem txid, vai `***`, como pede o Banco Central.
+    pub txid: Option<String>
+}
+
+/// Monta o "copia e cola" da cobrança.
+pub fn gera_br_code(dados: &DadosBrCode) -> Result<String, ErroApi>{
+    let nome = normaliza_texto_br_code(&dados.nome_recebedor, TAMANHO_NOME);
+    let cidade = normaliza_texto_br_code(&dados.cidade, TAMANHO_CIDADE);
+    if nome.is_empty() || cidade.is_empty(){
+        return Err(ErroApi::validacao("O nome e a cidade do recebedor são obrigatórios na cobrança PIX."))
+    }
+
+    let (conta, iniciacao) = match &dados.destino{
+        DestinoPix::Chave(chave) => (campo("01", chave)?, None),
+        // A cobrança dinâmica só pode ser paga uma vez.
+        DestinoPix::Url(url) => (campo("25", url)?, Some(campo("01", "12")?))
+    };
+
+    let mut payload = campo("00", "01")?;
+    if let Some(iniciacao) = iniciacao{
+        payload.push_str(&iniciacao);
+    }
+    payload.push_str(&campo("26", &format!("{}{}", campo("00", GUI_PIX)?, conta))?);
+    payload.push_str(&campo("52", "0000")?);
+    payload.push_str(&campo("53", "986")?);
+    if let Some(valor) = dados.valor{
+        if !valor.e_positivo(){
+            return Err(ErroApi::validacao_campo("valor", "O valor da cobrança deve ser maior que zero."))
+        }
+        payload.push_str(&campo("54", &valor.to_string())?);
+    }
+    payload.push_str(&campo("58", "BR")?);
+    payload.push_str(&campo("59", &nome)?);
+    payload.push_str(&campo("60", &cidade)?);
+    payload.push_str(&campo("62", &campo("05", dados.txid.as_deref().unwrap_or("***"))?)?);
+
+    payload.push_str("6304");
+    let crc = crc16(payload.as_bytes());
+    payload.push_str(&format!("{:04X}", crc));
+    return Ok(payload)
+}
+
+/// Sorteia o txid de uma cobrança, com letras e números.
+pub fn gera_txid(dinamica: bool) -> String{
+    let tamanho = if dinamica { TAMANHO_TXID_DINAMICA } else { TAMANHO_TXID_ESTATICA };
+    rand::thread_rng()
+        .sample_iter(&Alphanumeric)
+        .take(tamanho)
+        .map(char::from)
+        .collect()
+}
+
+/// Desenha o QR Code do payload em SVG.
+pub fn gera_qrcode_svg(payload: &str) -> Result<String, ErroApi>{
+    match QrCode::with_error_correction_level(payload, EcLevel::M){
+        Ok(qrcode) => {
+            return Ok(qrcode.render::<svg::Color>()
+                .min_dimensions(256, 256)
+                .build())
+        },
+        Err(e) => {
+            return Err(ErroApi::Interno(format!("Erro ao gerar o QR Code: {}", e)))
+        }
+    }
+}
@@ -0,0 +1,217 @@
+// Preço das locações. Cada máquina pode ter uma tarifa por medida de tempo (hora, dia, semana
+// ou mês), com um prazo mínimo, e faixas de desconto para as locações longas. As medidas sem
+// tarifa própria saem do valor mensal da máquina (`valoraluguel`), na proporção das horas de
+// cada medida: o mês tem 30 dias, e a semana, 7 (veja `MedidaTempo::horas`).
+//
+// O orçamento lista o aluguel, o desconto da maior faixa alcançada pelo prazo e a taxa da
+// plataforma, que incide sobre o aluguel já com o desconto. A taxa é configurada pelo `.env`:
+// - `PRECO_TAXA_PERCENTUAL`: percentual da taxa (padrão: 5);
+// - `PRECO_TAXA_MINIMA`: menor taxa cobrada por locação, em reais (padrão: 0).
+
+use std::env;
+
+use dotenv::dotenv;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use crate::{dinheiro::{formata_reais, Dinheiro, Percentual}, erros::ErroApi, models::precos_maquinas::{DescontoMaquina, MedidaTempo, PrecoMaquina}};
+
+/// Maior prazo orçado, em dias.
+pub const DIAS_MAXIMOS_LOCACAO: f64 = 3650.0;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ConfiguracaoPrecos{
+    pub percentual_taxa: Percentual,
+    pub taxa_minima: Dinheiro
+}
+
+impl Default for ConfiguracaoPrecos{
+    fn default() -> Self{
+        ConfiguracaoPrecos{
+            percentual_taxa: Percentual::de_inteiro(5),
+            taxa_minima: Dinheiro::ZERO
+        }
+    }
+}
+
+impl ConfiguracaoPrecos{
+    /// Lê a configuração das variáveis `PRECO_*`. As ausentes ficam com o valor padrão.
+    pub fn de_variaveis(variavel: impl Fn(&str) -> Option<String>) -> Result<Self, ErroApi>{
+        let padrao = ConfiguracaoPrecos::default();
+        let percentual_taxa = match variavel("PRECO_TAXA_PERCENTUAL"){
+            Some(valor) => match valor.parse::<Percentual>(){
+                Ok(percentual) if (Percentual::ZERO..=Percentual::CEM).contains(&percentual) => percentual,
+                _ => {
+                    return Err(ErroApi::Interno(format!("PRECO_TAXA_PERCENTUAL inválido: {}. Use um número entre 0 e 100.", valor)))
+                }
+            },
+            None => padrao.percentual_taxa
+        };
+        let taxa_minima = match variavel("PRECO_TAXA_MINIMA"){
+            Some(valor) => match valor.parse::<Dinheiro>(){
+                Ok(taxa) if taxa >= Dinheiro::ZERO => taxa,
+                _ => {
+                    return Err(ErroApi::Interno(format!("PRECO_TAXA_MINIMA inválida: {}. Use um valor em reais, como 2.50.", valor)))
+                }
+            },
+            None => padrao.taxa_minima
+        };
+        return Ok(ConfiguracaoPrecos{
+            percentual_taxa,
+            taxa_minima
+        })
+    }
+
+    pub fn de_ambiente() -> Result<Self, ErroApi>{
+        dotenv().ok();
+        ConfiguracaoPrecos::de_variaveis(|nome| env::var(nome).ok())
+    }
+}
+
+/// Preço de `por` unidades de uma medida. As tarifas derivadas do valor mensal não são divididas
+/// antes: guardam o valor mensal vezes as horas da medida, por 720 unidades (as horas do mês),
+/// para que o aluguel seja arredondado uma vez só.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Tarifa{
+    pub valor: Dinheiro,
+    pub por: i64
+}
+
+impl Tarifa{
+    pub fn unitaria(valor: Dinheiro) -> Tarifa{
+        Tarifa{
+            valor,
+            por: 1
+        }
+    }
+
+    /// Valor de `quantidade` unidades, arredondado no centavo. A quantidade conta até o
+    /// milésimo.
+    pub fn valor(&self, quantidade: f64) -> Result<Dinheiro, ErroApi>{
+        self.valor.multiplica((quantidade * 1000.0).round() as i64, self.por * 1000)
+    }
+}
+
+/// Tudo o que define o preço de uma máquina.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TabelaPrecos{
+    pub valoraluguel: Dinheiro,
+    pub precos: Vec<PrecoMaquina>,
+    pub descontos: Vec<DescontoMaquina>
+}
+
+impl TabelaPrecos{
+    pub fn preco(&self, medida: MedidaTempo) -> Option<&PrecoMaquina>{
+        self.precos.iter().find(|preco| preco.medida == medida)
+    }
+
+    pub fn tarifa(&self, medida: MedidaTempo) -> Result<Tarifa, ErroApi>{
+        match self.preco(medida){
+            Some(preco) => Ok(Tarifa::unitaria(preco.valor)),
+            None => Ok(Tarifa{
+                valor: self.valoraluguel.multiplica(medida.horas(), 1)?,
+                por: MedidaTempo::Meses.horas()
+            })
+        }
+    }
+
+    pub fn prazo_minimo(&self, medida: MedidaTempo) -> f64{
+        self.preco(medida).map(|preco| preco.prazominimo).unwrap_or(0.0)
+    }
+
+    /// A maior faixa de desconto alcançada por uma locação de `dias` dias.
+    pub fn desconto(&self, dias: f64) -> Option<&DescontoMaquina>{
+        self.descontos.iter()
+            .filter(|desconto| desconto.diasminimos as f64 <= dias)
+            .max_by_key(|desconto| desconto.diasminimos)
+    }
+}
+
+#[derive(Serialize, Deserialize, ToSchema, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TipoItemOrcamento{
+    Aluguel,
+    /// Vem com o valor negativo.
+    Desconto,
+    Taxa
+}
+
+#[derive(Serialize, Deserialize, ToSchema, Debug, Clone, PartialEq)]
+pub struct ItemOrcamento{
+    pub tipo: TipoItemOrcamento,
+    pub descricao: String,
+    pub valor: Dinheiro
+}
+
+#[derive(Serialize, Deserialize, ToSchema, Debug, Clone, PartialEq)]
+pub struct OrcamentoAluguel{
+    pub medida: MedidaTempo,
+    pub prazo: f64,
+    /// Valor de uma unidade da medida, só para exibição: o aluguel é calculado sobre a tarifa
+    /// sem arredondar.
+    pub valorunidade: Dinheiro,
+    pub itens: Vec<ItemOrcamento>,
+    /// Soma dos itens.
+    pub total: Dinheiro
+}
+
+/// Orça uma locação de `prazo` unidades de `medida`.
+pub fn calcula_orcamento(tabela: &TabelaPrecos, medida: MedidaTempo, prazo: f64, configuracao: &ConfiguracaoPrecos)
+    -> Result<OrcamentoAluguel, ErroApi>{
+    if !prazo.is_finite() || prazo <= 0.0{
+        return Err(ErroApi::validacao_campo("prazolocacao", "O prazo da locação deve ser maior que zero."))
+    }
+    let dias = medida.em_dias(prazo);
+    if dias > DIAS_MAXIMOS_LOCACAO{
+        return Err(ErroApi::validacao_campo("prazolocacao", "O prazo da locação é grande demais."))
+    }
+    let unidade = medida.texto().to_lowercase();
+    let minimo = tabela.prazo_minimo(medida);
+    if prazo < minimo{
+        return Err(ErroApi::validacao_campo("prazolocacao",
+            &format!("O prazo mínimo dessa máquina em {} é {}.", unidade, minimo)))
+    }
+
+    let tarifa = tabela.tarifa(medida)?;
+    let valorunidade = tarifa.valor(1.0)?;
+    let aluguel = tarifa.valor(prazo)?;
+    let mut itens = vec![ItemOrcamento{
+        tipo: TipoItemOrcamento::Aluguel,
+        descricao: format!("{} {} a {} cada.", prazo, unidade, formata_reais(valorunidade)),
+        valor: aluguel
+    }];
+
+    let mut subtotal = aluguel;
+    if let Some(faixa) = tabela.desconto(dias){
+        let desconto = aluguel.percentual(faixa.percentualdesconto)?;
+        if desconto.e_positivo(){
+            itens.push(ItemOrcamento{
+                tipo: TipoItemOrcamento::Desconto,
+                descricao: format!("Desconto de {}% para locações a partir de {} dia(s).", faixa.percentualdesconto, faixa.diasminimos),
+                valor: -desconto
+            });
+            subtotal -= desconto;
+        }
+    }
+
+    let taxa = subtotal.percentual(configuracao.percentual_taxa)?;
+    let (taxa, descricao) = match taxa < configuracao.taxa_minima{
+        true => (configuracao.taxa_minima, "Taxa mínima da plataforma.".to_string()),
+        false => (taxa, format!("Taxa da plataforma de {}%.", configuracao.percentual_taxa))
+    };
+    if taxa.e_positivo(){
+        itens.push(ItemOrcamento{
+            tipo: TipoItemOrcamento::Taxa,
+            descricao,
+            valor: taxa
+        });
+    }
+
+    let total = itens.iter().map(|item| item.valor).sum();
+    return Ok(OrcamentoAluguel{
+        medida,
+        prazo,
+        valorunidade,
+        itens,
+        total
+    })
+}
@@ -1,40 +1,95 @@
+use std::sync::Arc;
+
 use axum::{
-    http::Method, routing::{delete, get, get_service, patch, post, put}, Router
+    extract::FromRef, http::{header::{ACCEPT, AUTHORIZATION, CONTENT_TYPE}, Method}, middleware, routing::{delete, get, get_service, patch, post, put}, Router
 };
 use tower_http::cors::{Any, CorsLayer};
 use tower_http::services::ServeDir;
-use utoipa::openapi::Contact;
-use crate::controllers::{codigos_recuperacao::{envia_codigo_recuperacao, verifica_codigo_recuperacao}, contas_bancarias::{atualiza_conta_bancaria, busca_conta_bancaria_idusuario, cadastra_conta_bancaria}, contratos::{busca_contrato_idsolicitacao, gera_contrato_idsolicitacao}, enderecos::{atualiza_endereco, busca_endereco_id, busca_endereco_idusuario, cadastra_endereco_usuario}, imagens_maquinas::{cadastra_imagem_maquina, recupera_imagem_maquina, recupera_imagens_maquina}, locadoras::{busca_locadora_idusuario, cadastra_locadora}, locatarios::{busca_locatario_idusuario, cadastra_locatario}, maquinas::{atualiza_maquina, busca_maquina_id, busca_maquina_idpublico, cadastra_maquina, calcula_valor_aluguel, lista_todas_maquinas, pesquisa_maquina}, maquinas_usuarios::{busca_maquinas_usuario_idusuario, busca_usuario_idmaquina}, multipart::cadastra_imagem, notificacoes::{atualiza_status_notificacao, busca_notificacoes_idusuario, cadastra_notificacao}, oauth::{google_oauth_handler, Config}, solicitacoes_contratos::{atualiza_status_solicitacao, busca_solicitacao_idsolicitacao, busca_solicitacoes_idlocador, busca_solicitacoes_idlocatario, cadastra_solicitacao_contrato}, usuarios::{self, atualiza_email_usuario, atualiza_senha_usuario, atualiza_usuario, busca_email_usuario, busca_usuario_documento, busca_usuario_id, cadastra_usuario, deleta_usuario, realiza_login, redefine_senha_usuario}};
-use crate::controllers::usuarios::busca_usuario_email;
-use crate::routes::usuarios::{__path_realiza_login, __path_cadastra_usuario, __path_busca_usuario_email, __path_atualiza_usuario, __path_atualiza_senha_usuario, __path_redefine_senha_usuario, __path_busca_email_usuario, __path_atualiza_email_usuario, __path_busca_usuario_id};
-use crate::controllers::codigos_recuperacao::__path_envia_codigo_recuperacao;
+use utoipa::openapi::{security::{HttpAuthScheme, HttpBuilder, SecurityScheme}, Contact};
+use crate::controllers::{chaves_pix::{busca_chaves_pix_idusuario, cadastra_chave_pix, deleta_chave_pix}, cobrancas_pix::{busca_cobrancas_pix_idcontrato, confirma_pagamento_pix, gera_cobranca_pix}, codigos_recuperacao::{envia_codigo_recuperacao, verifica_codigo_recuperacao}, contas_bancarias::{atualiza_conta_bancaria, busca_conta_bancaria_idusuario, cadastra_conta_bancaria}, contratos::{busca_contrato_idsolicitacao, busca_contratos_idlocador, busca_contratos_idlocatario, busca_pdf_contrato_idsolicitacao, gera_contrato_idsolicitacao, renova_contrato, rescinde_contrato}, encargos_contratos::{busca_encargos_idcontrato, registra_devolucao_contrato}, faturas::{busca_faturas_idcontrato, busca_faturas_idlocador, busca_faturas_idlocatario, busca_recibo_fatura, gera_faturas_contrato, registra_pagamento_fatura}, enderecos::{atualiza_endereco, busca_endereco_id, busca_endereco_idusuario, cadastra_endereco_usuario}, imagens_maquinas::{cadastra_imagem_maquina, recupera_imagem_maquina, recupera_imagens_maquina}, locadoras::{atualiza_regras_atraso_locadora, busca_locadora_idusuario, cadastra_locadora}, locatarios::{busca_locatario_idusuario, cadastra_locatario}, maquinas::{atualiza_maquina, busca_maquina_id, busca_maquina_idpublico, cadastra_maquina, calcula_valor_aluguel, lista_todas_maquinas, pesquisa_maquina, busca_textual_maquina}, maquinas_usuarios::{busca_maquinas_usuario_idusuario, busca_usuario_idmaquina}, multipart::cadastra_imagem, notificacoes::{atualiza_status_notificacao, busca_notificacoes_idusuario}, oauth::{confirma_vinculo_provedor, desvincula_provedor, entra_com_provedor, inicia_login_provedor, lista_provedores, solicita_desvinculo_provedor}, Pool, precos_maquinas::{atualiza_precos_maquina, busca_precos_maquina, orca_aluguel_maquina}, reservas_maquinas::busca_calendario_maquina, papeis_usuarios::{atribui_papel_usuario, busca_papeis_usuario, remove_papel_usuario}, sessoes::{exige_autenticacao, exige_permissao, renova_sessao}, solicitacoes_contratos::{atualiza_status_solicitacao, busca_historico_solicitacao, busca_solicitacao_idsolicitacao, busca_solicitacoes_idlocador, busca_solicitacoes_idlocatario, cadastra_solicitacao_contrato}, usuarios::{self, ativa_conta, atualiza_email_usuario, atualiza_senha_usuario, atualiza_usuario, busca_email_usuario, busca_perfil_usuario, busca_usuario_documento, busca_usuario_id, cadastra_usuario, deleta_usuario, realiza_login, redefine_senha_usuario, reenvia_codigo_ativacao}};
+use crate::ids::resolve_ids_legados;
+use crate::limites::{CamadaLimite, Limitador};
+use crate::models::papeis_usuarios::Permissao;
+use crate::oidc::ProvedoresOidc;
+use crate::routes::usuarios::{__path_realiza_login, __path_ativa_conta, __path_reenvia_codigo_ativacao, __path_cadastra_usuario, __path_atualiza_usuario, __path_atualiza_senha_usuario, __path_redefine_senha_usuario, __path_busca_email_usuario, __path_atualiza_email_usuario, __path_busca_usuario_id, __path_busca_perfil_usuario};
+use crate::controllers::codigos_recuperacao::{__path_envia_codigo_recuperacao, __path_verifica_codigo_recuperacao};
 use crate::controllers::enderecos::{__path_cadastra_endereco_usuario, __path_busca_endereco_id, __path_busca_endereco_idusuario};
-use crate::controllers::maquinas::{__path_busca_maquina_idpublico, __path_atualiza_maquina};
+use crate::controllers::maquinas::{__path_busca_maquina_idpublico, __path_atualiza_maquina, __path_pesquisa_maquina, __path_busca_textual_maquina};
+use crate::controllers::reservas_maquinas::__path_busca_calendario_maquina;
+use crate::controllers::precos_maquinas::{__path_busca_precos_maquina, __path_atualiza_precos_maquina, __path_orca_aluguel_maquina};
 use crate::controllers::usuarios::{__path_busca_usuario_documento, __path_deleta_usuario};
+use crate::controllers::sessoes::__path_renova_sessao;
+use crate::controllers::papeis_usuarios::{__path_busca_papeis_usuario, __path_atribui_papel_usuario, __path_remove_papel_usuario};
+use crate::controllers::chaves_pix::{__path_cadastra_chave_pix, __path_busca_chaves_pix_idusuario, __path_deleta_chave_pix};
+use crate::controllers::cobrancas_pix::{__path_gera_cobranca_pix, __path_busca_cobrancas_pix_idcontrato, __path_confirma_pagamento_pix};
+use crate::controllers::faturas::{__path_gera_faturas_contrato, __path_busca_faturas_idcontrato, __path_busca_faturas_idlocador, __path_busca_faturas_idlocatario, __path_registra_pagamento_fatura, __path_busca_recibo_fatura};
+use crate::controllers::encargos_contratos::{__path_registra_devolucao_contrato, __path_busca_encargos_idcontrato};
+use crate::controllers::oauth::{__path_lista_provedores, __path_inicia_login_provedor, __path_entra_com_provedor, __path_confirma_vinculo_provedor, __path_solicita_desvinculo_provedor, __path_desvincula_provedor};
 use utoipa_axum::routes;
 use utoipa_axum::router::OpenApiRouter;
 use utoipa_swagger_ui::SwaggerUi;
-pub fn cria_rotas() -> Router<>{
+
+/// Estado compartilhado pelos handlers: o pool de conexões e os provedores de login externos.
+#[derive(Clone, FromRef)]
+pub struct EstadoApp{
+    pub pool: Pool,
+    pub provedores: Arc<ProvedoresOidc>
+}
+
+pub fn cria_rotas(pool: Pool) -> Router<>{
     
-    let (_router, mut api) = OpenApiRouter::<()>::new()
+    let (_router, mut api) = OpenApiRouter::<EstadoApp>::new()
     
         .routes(routes!(realiza_login))
+        .routes(routes!(ativa_conta))
+        .routes(routes!(reenvia_codigo_ativacao))
+        .routes(routes!(renova_sessao))
+        .routes(routes!(lista_provedores))
+        .routes(routes!(inicia_login_provedor))
+        .routes(routes!(entra_com_provedor))
+        .routes(routes!(confirma_vinculo_provedor))
+        .routes(routes!(solicita_desvinculo_provedor))
+        .routes(routes!(desvincula_provedor))
         .routes(routes!(cadastra_usuario))
         .routes(routes!(envia_codigo_recuperacao))
-        .routes(routes!(busca_usuario_email))
+        .routes(routes!(verifica_codigo_recuperacao))
         .routes(routes!(atualiza_usuario))
         .routes(routes!(redefine_senha_usuario))
         .routes(routes!(atualiza_senha_usuario))
         .routes(routes!(busca_email_usuario))
         .routes(routes!(atualiza_email_usuario))
         .routes(routes!(busca_usuario_id))
+        .routes(routes!(busca_perfil_usuario))
         .routes(routes!(cadastra_endereco_usuario))
         .routes(routes!(busca_endereco_id))
         .routes(routes!(busca_endereco_idusuario))
         .routes(routes!(busca_maquina_idpublico))
         .routes(routes!(atualiza_maquina))
+        .routes(routes!(pesquisa_maquina))
+        .routes(routes!(busca_textual_maquina))
+        .routes(routes!(busca_calendario_maquina))
+        .routes(routes!(busca_precos_maquina))
+        .routes(routes!(atualiza_precos_maquina))
+        .routes(routes!(orca_aluguel_maquina))
         .routes(routes!(busca_usuario_documento))
         .routes(routes!(deleta_usuario))
+        .routes(routes!(busca_papeis_usuario))
+        .routes(routes!(atribui_papel_usuario))
+        .routes(routes!(remove_papel_usuario))
+        .routes(routes!(cadastra_chave_pix))
+        .routes(routes!(busca_chaves_pix_idusuario))
+        .routes(routes!(deleta_chave_pix))
+        .routes(routes!(gera_cobranca_pix))
+        .routes(routes!(busca_cobrancas_pix_idcontrato))
+        .routes(routes!(confirma_pagamento_pix))
+        .routes(routes!(gera_faturas_contrato))
+        .routes(routes!(busca_faturas_idcontrato))
+        .routes(routes!(busca_faturas_idlocador))
+        .routes(routes!(busca_faturas_idlocatario))
+        .routes(routes!(registra_pagamento_fatura))
+        .routes(routes!(busca_recibo_fatura))
+        .routes(routes!(registra_devolucao_contrato))
+        .routes(routes!(busca_encargos_idcontrato))
         .split_for_parts();
 
     api.info.description = Some("\nDocumentação para as rotas da API do sistema MaqExpress.\n
@@ -51,35 +106,41 @@ pub fn cria_rotas() -> Router<>{
     api.info.extensions = None;
     api.info.terms_of_service = None;
     api.external_docs = None;
-    let config = Config::init();
-
-    let app: Router<_> = Router::new()
-        .route("/cadastra_usuario", post(cadastra_usuario))
+    if let Some(componentes) = api.components.as_mut(){
+        componentes.add_security_scheme("token_acesso", SecurityScheme::Http(
+            HttpBuilder::new().scheme(HttpAuthScheme::Bearer).bearer_format("JWT").build()
+        ));
+    }
+    let estado = EstadoApp{
+        pool: pool.clone(),
+        provedores: ProvedoresOidc::de_ambiente().expect("Erro na configuração dos provedores de login")
+    };
+
+    // Guarda das rotas que exigem uma permissão, além do login; veja `Papel::permissoes`.
+    let exige = |permissao: Permissao| middleware::from_fn_with_state(permissao, exige_permissao);
+
+    // Rotas que exigem um token de acesso válido no cabeçalho `Authorization`.
+    let rotas_protegidas: Router<EstadoApp> = Router::new()
         .route("/busca_email_usuario", get(busca_email_usuario))
-        .route("/realiza_login", post(realiza_login))
         .route("/atualiza_senha_usuario", patch(atualiza_senha_usuario))
         .route("/atualiza_email_usuario", patch(atualiza_email_usuario))
-        .route("/busca_usuario_email/", get(busca_usuario_email))
         .route("/busca_usuario_id/", get(busca_usuario_id))
+        .route("/busca_perfil_usuario/", get(busca_perfil_usuario))
         .route("/atualiza_usuario", put(atualiza_usuario))
-        .route("/redefine_senha_usuario", patch(redefine_senha_usuario))
         .route("/deleta_usuario/", delete(deleta_usuario))
         .route("/busca_usuario_documento/", get(busca_usuario_documento))
+        .route("/solicita_desvinculo_provedor", post(solicita_desvinculo_provedor))
+        .route("/desvincula_provedor", post(desvincula_provedor))
+        .route("/busca_papeis_usuario/", get(busca_papeis_usuario))
+        .route("/atribui_papel_usuario", post(atribui_papel_usuario).layer(exige(Permissao::GerenciaPapeis)))
+        .route("/remove_papel_usuario", post(remove_papel_usuario).layer(exige(Permissao::GerenciaPapeis)))
 
-        .route("/verifica_codigo_recuperacao", post(verifica_codigo_recuperacao))
-        .route("/envia_codigo_recuperacao", post(envia_codigo_recuperacao))
-
-        .route("/cadastra_maquina", post(cadastra_maquina))
-        .route("/lista_todas_maquinas", get(lista_todas_maquinas))
-        .route("/busca_maquina_idpublico/", get(busca_maquina_idpublico))
-        .route("/pesquisa_maquina", post(pesquisa_maquina))
-        .route("/atualiza_maquina", put(atualiza_maquina))
-        .route("/busca_maquina_id/", get(busca_maquina_id))
+        .route("/cadastra_maquina", post(cadastra_maquina).layer(exige(Permissao::GerenciaMaquinas)))
+        .route("/atualiza_maquina", put(atualiza_maquina).layer(exige(Permissao::GerenciaMaquinas)))
+        .route("/atualiza_precos_maquina", put(atualiza_precos_maquina).layer(exige(Permissao::GerenciaMaquinas)))
 
-        .route("/cadastra_imagem", post(cadastra_imagem))
-        .route("/cadastra_imagem_maquina", post(cadastra_imagem_maquina))
-        .route("/recupera_imagem_maquina", post(recupera_imagem_maquina))
-        .route("/recupera_imagens_maquina", post(recupera_imagens_maquina))
+        .route("/cadastra_imagem", post(cadastra_imagem).layer(exige(Permissao::GerenciaMaquinas)))
+        .route("/cadastra_imagem_maquina", post(cadastra_imagem_maquina).layer(exige(Permissao::GerenciaMaquinas)))
 
         .route("/cadastra_endereco_usuario", post(cadastra_endereco_usuario))
         .route("/busca_endereco_id/", get(busca_endereco_id))
@@ -91,31 +152,89 @@ pub fn cria_rotas() -> Router<>{
 
         .route("/cadastra_locadora", post(cadastra_locadora))
         .route("/busca_locadora_idusuario/", get(busca_locadora_idusuario))
+        .route("/atualiza_regras_atraso_locadora", patch(atualiza_regras_atraso_locadora))
 
         .route("/cadastra_conta_bancaria", post(cadastra_conta_bancaria))
         .route("/busca_conta_bancaria_idusuario/", get(busca_conta_bancaria_idusuario))
         .route("/atualiza_conta_bancaria", put(atualiza_conta_bancaria))
         .route("/busca_usuario_idmaquina/", get(busca_usuario_idmaquina))
 
-        .route("/cadastra_solicitacao_contrato", post(cadastra_solicitacao_contrato))
+        .route("/cadastra_solicitacao_contrato", post(cadastra_solicitacao_contrato).layer(exige(Permissao::SolicitaLocacao)))
         .route("/busca_solicitacao_idsolicitacao/", get(busca_solicitacao_idsolicitacao))
         .route("/busca_solicitacoes_idlocador/", get(busca_solicitacoes_idlocador))
         .route("/atualiza_status_solicitacao", patch(atualiza_status_solicitacao))
+        .route("/busca_historico_solicitacao/", get(busca_historico_solicitacao))
         .route("/busca_solicitacoes_idlocatario/", get(busca_solicitacoes_idlocatario))
 
         .route("/gera_contrato_idsolicitacao/", get(gera_contrato_idsolicitacao))
         .route("/busca_contrato_idsolicitacao/", get(busca_contrato_idsolicitacao))
+        .route("/busca_pdf_contrato_idsolicitacao/", get(busca_pdf_contrato_idsolicitacao))
+        .route("/busca_contratos_idlocador/", get(busca_contratos_idlocador))
+        .route("/busca_contratos_idlocatario/", get(busca_contratos_idlocatario))
+        .route("/rescinde_contrato", put(rescinde_contrato))
+        .route("/renova_contrato", put(renova_contrato))
+
+        .route("/cadastra_chave_pix", post(cadastra_chave_pix).layer(exige(Permissao::RecebePagamentos)))
+        .route("/busca_chaves_pix_idusuario/", get(busca_chaves_pix_idusuario))
+        .route("/deleta_chave_pix/", delete(deleta_chave_pix))
+        .route("/gera_cobranca_pix", post(gera_cobranca_pix))
+        .route("/busca_cobrancas_pix_idcontrato/", get(busca_cobrancas_pix_idcontrato))
+
+        .route("/gera_faturas_contrato", post(gera_faturas_contrato))
+        .route("/busca_faturas_idcontrato/", get(busca_faturas_idcontrato))
+        .route("/busca_faturas_idlocador/", get(busca_faturas_idlocador))
+        .route("/busca_faturas_idlocatario/", get(busca_faturas_idlocatario))
+        .route("/registra_pagamento_fatura", patch(registra_pagamento_fatura))
+        .route("/busca_recibo_fatura/", get(busca_recibo_fatura))
+        .route("/registra_devolucao_contrato", put(registra_devolucao_contrato))
+        .route("/busca_encargos_idcontrato/", get(busca_encargos_idcontrato))
 
-        .route("/cadastra_notificacao",post(cadastra_notificacao))
         .route("/busca_notificacoes_idusuario/", get(busca_notificacoes_idusuario))
         .route("/atualiza_status_notificacao", patch(atualiza_status_notificacao))
 
+        .route("/busca_maquinas_usuario_idusuario/", get(busca_maquinas_usuario_idusuario))
+        .route_layer(middleware::from_fn_with_state(pool.clone(), exige_autenticacao));
+
+    // Rotas de autenticação com limite de requisições por IP e por conta; veja `crate::limites`.
+    let limitador = Limitador::de_ambiente().expect("Erro na configuração dos limites de requisições");
+    let limite = |escopo: &'static str| CamadaLimite::new(limitador.clone(), escopo);
+
+    let app: Router<_> = Router::new()
+        .route("/cadastra_usuario", post(cadastra_usuario).layer(limite("cadastra_usuario")))
+        .route("/realiza_login", post(realiza_login).layer(limite("realiza_login").com_falhas()))
+        .route("/ativa_conta", post(ativa_conta).layer(limite("ativa_conta").com_falhas()))
+        .route("/reenvia_codigo_ativacao", post(reenvia_codigo_ativacao).layer(limite("reenvia_codigo_ativacao")))
+        .route("/renova_sessao", post(renova_sessao).layer(limite("renova_sessao").com_falhas()))
+        .route("/redefine_senha_usuario", patch(redefine_senha_usuario).layer(limite("redefine_senha_usuario").com_falhas()))
+
+        .route("/verifica_codigo_recuperacao", post(verifica_codigo_recuperacao).layer(limite("verifica_codigo_recuperacao").com_falhas()))
+        .route("/envia_codigo_recuperacao", post(envia_codigo_recuperacao).layer(limite("envia_codigo_recuperacao")))
+
+        .route("/lista_todas_maquinas", get(lista_todas_maquinas))
+        .route("/busca_maquina_idpublico/", get(busca_maquina_idpublico))
+        .route("/pesquisa_maquina", post(pesquisa_maquina))
+        .route("/busca_textual_maquina", post(busca_textual_maquina))
+        .route("/busca_maquina_id/", get(busca_maquina_id))
+        .route("/busca_calendario_maquina/", get(busca_calendario_maquina))
+        .route("/busca_precos_maquina/", get(busca_precos_maquina))
+
+        .route("/recupera_imagem_maquina", post(recupera_imagem_maquina))
+        .route("/recupera_imagens_maquina", post(recupera_imagens_maquina))
+
         .route("/calcula_valor_aluguel", post(calcula_valor_aluguel))
+        .route("/orca_aluguel_maquina", post(orca_aluguel_maquina))
 
-        .route("/busca_maquinas_usuario_idusuario/", get(busca_maquinas_usuario_idusuario))
+        // Chamada pelo provedor de pagamentos, com o token próprio dele; veja `crate::pix`.
+        .route("/confirma_pagamento_pix", post(confirma_pagamento_pix))
 
-        .route("/auth/google", post(google_oauth_handler))
-        .with_state(config)
+        .route("/auth/provedores", get(lista_provedores))
+        .route("/auth/{provedor}/inicia", get(inicia_login_provedor).layer(limite("inicia_login_provedor")))
+        .route("/auth/{provedor}", post(entra_com_provedor).layer(limite("entra_com_provedor")))
+        .route("/confirma_vinculo_provedor", post(confirma_vinculo_provedor).layer(limite("confirma_vinculo_provedor").com_falhas()))
+        .merge(rotas_protegidas)
+        .with_state(estado)
+        // Troca as chaves antigas, geradas com bcrypt, pelos UUIDv7; veja `crate::ids`.
+        .layer(middleware::from_fn_with_state(pool, resolve_ids_legados))
 
 
         .nest_service("/images", get_service(ServeDir::new("./images")))
@@ -124,7 +243,7 @@ pub fn cria_rotas() -> Router<>{
             CorsLayer::new()
                 .allow_origin(Any)
                 .allow_methods(vec![Method::POST, Method::PUT, Method::PATCH, Method::DELETE, Method::GET]) 
-                .allow_headers(Any),
+                .allow_headers(vec![AUTHORIZATION, CONTENT_TYPE, ACCEPT]),
         )
         
         .merge(SwaggerUi::new("/swagger-ui").url("/api/openapi.json", api));
@@ -1,9 +1,47 @@
 // @generated automatically by Diesel CLI.
 
+diesel::table! {
+    chaves_pix (idchavepix) {
+        #[max_length = 64]
+        idchavepix -> Varchar,
+        #[max_length = 64]
+        idusuario -> Varchar,
+        #[max_length = 16]
+        tipo -> Varchar,
+        #[max_length = 77]
+        chave -> Varchar,
+        datacadastro -> Timestamp,
+    }
+}
+
+diesel::table! {
+    cobrancas_pix (idcobranca) {
+        #[max_length = 64]
+        idcobranca -> Varchar,
+        #[max_length = 64]
+        idcontrato -> Varchar,
+        #[max_length = 77]
+        chave -> Varchar,
+        #[max_length = 35]
+        txid -> Varchar,
+        valor -> Numeric,
+        dinamica -> Bool,
+        payload -> Text,
+        #[max_length = 16]
+        status -> Varchar,
+        datacriacao -> Timestamp,
+        datapagamento -> Nullable<Timestamp>,
+        #[max_length = 64]
+        idpagamento -> Nullable<Varchar>,
+        #[max_length = 64]
+        idfatura -> Nullable<Varchar>,
+    }
+}
+
 diesel::table! {
     codigos_recuperacao (idcodigo) {
-        #[max_length = 6]
-        codigo -> Varchar,
+        #[max_length = 128]
+        hashcodigo -> Varchar,
         datacriacao -> Timestamp,
         dataexpiracao -> Timestamp,
         #[max_length = 32]
@@ -12,6 +50,12 @@ diesel::table! {
         idusuario -> Varchar,
         #[max_length = 64]
         idcodigo -> Varchar,
+        tentativas -> Int4,
+        dataultimatentativa -> Nullable<Timestamp>,
+        #[max_length = 64]
+        hashtoken -> Nullable<Varchar>,
+        #[max_length = 128]
+        finalidade -> Varchar,
     }
 }
 
@@ -30,6 +74,21 @@ diesel::table! {
     }
 }
 
+diesel::table! {
+    contas_externas (idcontaexterna) {
+        #[max_length = 64]
+        idcontaexterna -> Varchar,
+        #[max_length = 64]
+        idusuario -> Varchar,
+        #[max_length = 64]
+        provedor -> Varchar,
+        #[max_length = 255]
+        sub -> Varchar,
+        pendente -> Bool,
+        datavinculo -> Timestamp,
+    }
+}
+
 diesel::table! {
     contratos (idcontrato) {
         #[max_length = 64]
@@ -57,8 +116,49 @@ diesel::table! {
         #[max_length = 64]
         statuscontrato -> Varchar,
         prazolocacao -> Float8,
-        valorlocacao -> Float8,
+        valorlocacao -> Numeric,
         datacontrato -> Timestamp,
+        motivoencerramento -> Nullable<Text>,
+        dataencerramento -> Nullable<Timestamp>,
+        dataavisotermino -> Nullable<Timestamp>,
+        datadevolucao -> Nullable<Timestamp>,
+        dataavisoatraso -> Nullable<Timestamp>,
+    }
+}
+
+diesel::table! {
+    descontos_maquinas (idmaquina, diasminimos) {
+        #[max_length = 64]
+        idmaquina -> Varchar,
+        diasminimos -> Int4,
+        percentualdesconto -> Numeric,
+    }
+}
+
+diesel::table! {
+    documentos_contratos (idcontrato) {
+        #[max_length = 64]
+        idcontrato -> Varchar,
+        #[max_length = 16]
+        versaomodelo -> Varchar,
+        #[max_length = 64]
+        hashdocumento -> Varchar,
+        documento -> Bytea,
+        datageracao -> Timestamp,
+    }
+}
+
+diesel::table! {
+    encargos_contratos (idencargo) {
+        #[max_length = 64]
+        idencargo -> Varchar,
+        #[max_length = 64]
+        idcontrato -> Varchar,
+        #[max_length = 16]
+        tipo -> Varchar,
+        descricao -> Text,
+        valor -> Numeric,
+        datacriacao -> Timestamp,
     }
 }
 
@@ -96,6 +196,52 @@ diesel::table! {
     }
 }
 
+diesel::table! {
+    faturas (idfatura) {
+        #[max_length = 64]
+        idfatura -> Varchar,
+        #[max_length = 64]
+        idcontrato -> Varchar,
+        numeroparcela -> Int4,
+        valor -> Numeric,
+        valorpago -> Numeric,
+        datavencimento -> Timestamp,
+        #[max_length = 16]
+        status -> Varchar,
+        datacriacao -> Timestamp,
+        datapagamento -> Nullable<Timestamp>,
+    }
+}
+
+diesel::table! {
+    historico_solicitacoes (idhistorico) {
+        #[max_length = 64]
+        idhistorico -> Varchar,
+        #[max_length = 64]
+        idsolicitacao -> Varchar,
+        #[max_length = 64]
+        statusanterior -> Nullable<Varchar>,
+        #[max_length = 64]
+        statusnovo -> Varchar,
+        #[max_length = 16]
+        papelator -> Varchar,
+        #[max_length = 64]
+        idator -> Nullable<Varchar>,
+        datatransicao -> Timestamp,
+    }
+}
+
+diesel::table! {
+    ids_legados (idlegado) {
+        #[max_length = 64]
+        idlegado -> Varchar,
+        #[max_length = 64]
+        idnovo -> Varchar,
+        #[max_length = 64]
+        tabela -> Varchar,
+    }
+}
+
 diesel::table! {
     imagens (idimagem) {
         #[max_length = 64]
@@ -128,6 +274,8 @@ diesel::table! {
         idendereco -> Varchar,
         #[max_length = 64]
         idconta -> Varchar,
+        percentualmulta -> Numeric,
+        percentualjurosmensal -> Numeric,
     }
 }
 
@@ -154,7 +302,7 @@ diesel::table! {
         numeroserie -> Varchar,
         #[max_length = 64]
         categoria -> Varchar,
-        valoraluguel -> Float8,
+        valoraluguel -> Numeric,
         #[max_length = 64]
         disponivelaluguel -> Varchar,
         #[max_length = 64]
@@ -193,6 +341,53 @@ diesel::table! {
     }
 }
 
+diesel::table! {
+    papeis_usuarios (idusuario, papel) {
+        #[max_length = 64]
+        idusuario -> Varchar,
+        #[max_length = 16]
+        papel -> Varchar,
+        dataatribuicao -> Timestamp,
+    }
+}
+
+diesel::table! {
+    precos_maquinas (idmaquina, medida) {
+        #[max_length = 64]
+        idmaquina -> Varchar,
+        #[max_length = 16]
+        medida -> Varchar,
+        valor -> Numeric,
+        prazominimo -> Float8,
+    }
+}
+
+diesel::table! {
+    recibos_faturas (idfatura) {
+        #[max_length = 64]
+        idfatura -> Varchar,
+        #[max_length = 16]
+        versaomodelo -> Varchar,
+        #[max_length = 64]
+        hashrecibo -> Varchar,
+        documento -> Bytea,
+        datageracao -> Timestamp,
+    }
+}
+
+diesel::table! {
+    reservas_maquinas (idreserva) {
+        #[max_length = 64]
+        idreserva -> Varchar,
+        #[max_length = 64]
+        idmaquina -> Varchar,
+        #[max_length = 64]
+        idsolicitacao -> Varchar,
+        datainicio -> Timestamp,
+        datafim -> Timestamp,
+    }
+}
+
 diesel::table! {
     solicitacoes_contratos (idsolicitacao) {
         #[max_length = 64]
@@ -210,18 +405,26 @@ diesel::table! {
         #[max_length = 64]
         statussolicitacao -> Varchar,
         prazolocacao -> Float8,
-        valorsolicitacao -> Float8,
+        valorsolicitacao -> Numeric,
         datasolicitacao -> Timestamp,
     }
 }
 
+diesel::table! {
+    tokens_renovacao_usados (idtoken) {
+        #[max_length = 64]
+        idtoken -> Varchar,
+        dataexpiracao -> Timestamp,
+    }
+}
+
 diesel::table! {
     usuarios (idusuario) {
         #[max_length = 64]
         nome -> Varchar,
         #[max_length = 64]
         email -> Varchar,
-        #[max_length = 64]
+        #[max_length = 255]
         senha -> Varchar,
         #[max_length = 64]
         documento -> Varchar,
@@ -237,14 +440,36 @@ diesel::table! {
     }
 }
 
+diesel::joinable!(chaves_pix -> usuarios (idusuario));
+diesel::joinable!(cobrancas_pix -> contratos (idcontrato));
+diesel::joinable!(cobrancas_pix -> faturas (idfatura));
 diesel::joinable!(codigos_recuperacao -> usuarios (idusuario));
+diesel::joinable!(contas_externas -> usuarios (idusuario));
+diesel::joinable!(descontos_maquinas -> maquinas (idmaquina));
+diesel::joinable!(documentos_contratos -> contratos (idcontrato));
+diesel::joinable!(encargos_contratos -> contratos (idcontrato));
+diesel::joinable!(faturas -> contratos (idcontrato));
+diesel::joinable!(historico_solicitacoes -> solicitacoes_contratos (idsolicitacao));
+diesel::joinable!(papeis_usuarios -> usuarios (idusuario));
+diesel::joinable!(precos_maquinas -> maquinas (idmaquina));
+diesel::joinable!(recibos_faturas -> faturas (idfatura));
+diesel::joinable!(reservas_maquinas -> solicitacoes_contratos (idsolicitacao));
 
 diesel::allow_tables_to_appear_in_same_query!(
+    chaves_pix,
+    cobrancas_pix,
     codigos_recuperacao,
     contas_bancarias,
+    contas_externas,
     contratos,
+    descontos_maquinas,
+    documentos_contratos,
+    encargos_contratos,
     enderecos,
     enderecos_usuarios,
+    faturas,
+    historico_solicitacoes,
+    ids_legados,
     imagens,
     imagens_maquinas,
     locadoras,
@@ -252,6 +477,11 @@ diesel::allow_tables_to_appear_in_same_query!(
     maquinas,
     maquinas_usuarios,
     notificacoes,
+    papeis_usuarios,
+    precos_maquinas,
+    recibos_faturas,
+    reservas_maquinas,
     solicitacoes_contratos,
+    tokens_renovacao_usados,
     usuarios,
 );
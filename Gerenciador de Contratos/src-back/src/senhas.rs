@@ -0,0 +1,285 @@
+// Hash e política de senhas. As senhas são guardadas com Argon2id; os hashes bcrypt
+// antigos continuam aceitos e são trocados por Argon2id no próximo login.
+//
+// Tudo é configurado pelo `.env`:
+// - `SENHA_ARGON2_MEMORIA` (KiB), `SENHA_ARGON2_ITERACOES` e `SENHA_ARGON2_PARALELISMO`;
+// - `SENHA_TAMANHO_MINIMO` e `SENHA_TAMANHO_MAXIMO`;
+// - `SENHA_EXIGE_MAIUSCULA`, `SENHA_EXIGE_MINUSCULA`, `SENHA_EXIGE_NUMERO` e `SENHA_EXIGE_SIMBOLO` (`true` ou `false`);
+// - `SENHA_LISTA_VAZADAS`: arquivo com uma senha vazada por linha, que não podem ser usadas.
+
+use std::{collections::HashSet, env, path::Path, sync::OnceLock};
+
+use argon2::{password_hash::{PasswordHash, PasswordHasher, PasswordVerifier, SaltString}, Algorithm, Argon2, Params, Version};
+use dotenv::dotenv;
+use rand::{rngs::OsRng, RngCore};
+
+use crate::erros::ErroApi;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ParametrosArgon2{
+    /// Memória usada por hash, em KiB.
+    pub memoria: u32,
+    pub iteracoes: u32,
+    pub paralelismo: u32
+}
+
+impl Default for ParametrosArgon2{
+    /// Os valores mínimos recomendados pela OWASP para o Argon2id.
+    fn default() -> Self{
+        ParametrosArgon2{
+            memoria: 19 * 1024,
+            iteracoes: 2,
+            paralelismo: 1
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct PoliticaSenha{
+    pub tamanho_minimo: usize,
+    pub tamanho_maximo: usize,
+    pub exige_maiuscula: bool,
+    pub exige_minuscula: bool,
+    pub exige_numero: bool,
+    pub exige_simbolo: bool,
+    /// Senhas vazadas, em letras minúsculas.
+    pub senhas_vazadas: HashSet<String>
+}
+
+impl Default for PoliticaSenha{
+    fn default() -> Self{
+        PoliticaSenha{
+            tamanho_minimo: 8,
+            tamanho_maximo: 128,
+            exige_maiuscula: true,
+            exige_minuscula: false,
+            exige_numero: true,
+            exige_simbolo: true,
+            senhas_vazadas: HashSet::new()
+        }
+    }
+}
+
+impl PoliticaSenha{
+    pub fn valida(&self, senha: &str) -> Result<(), String>{
+        if senha.trim().is_empty(){
+            return Err("A senha está vazia.".to_string())
+        }
+        let tamanho = senha.chars().count();
+        if tamanho < self.tamanho_minimo{
+            return Err("A senha é muito curta.".to_string())
+        }
+        if tamanho > self.tamanho_maximo{
+            return Err("A senha é muito longa.".to_string())
+        }
+        if self.exige_numero && !senha.chars().any(|c| c.is_ascii_digit()){
+            return Err("A senha deve conter ao menos um número".to_string())
+        }
+        if self.exige_simbolo && !senha.chars().any(|c| c.is_ascii_punctuation()){
+            return Err("A senha deve conter ao menos um símbolo".to_string())
+        }
+        if self.exige_maiuscula && !senha.chars().any(|c| c.is_uppercase()){
+            return Err("A senha deve conter ao menos uma letra maiúscula.".to_string())
+        }
+        if self.exige_minuscula && !senha.chars().any(|c| c.is_lowercase()){
+            return Err("A senha deve conter ao menos uma letra minúscula.".to_string())
+        }
+        if self.senhas_vazadas.contains(&senha.to_lowercase()){
+            return Err("Essa senha aparece em vazamentos de dados conhecidos. Escolha outra.".to_string())
+        }
+        return Ok(())
+    }
+
+    /// Lê o arquivo de senhas vazadas: uma senha por linha, ignorando linhas vazias e
+    /// comentários começados por `#`.
+    pub fn carrega_senhas_vazadas(arquivo: &Path) -> Result<HashSet<String>, ErroApi>{
+        match std::fs::read_to_string(arquivo){
+            Ok(conteudo) => {
+                return Ok(conteudo.lines()
+                    .map(str::trim)
+                    .filter(|linha| !linha.is_empty() && !linha.starts_with('#'))
+                    .map(str::to_lowercase)
+                    .collect())
+            },
+            Err(e) => {
+                return Err(ErroApi::Interno(format!("Erro ao ler a lista de senhas vazadas {}: {}", arquivo.display(), e)))
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct ConfiguracaoSenhas{
+    pub argon2: ParametrosArgon2,
+    pub politica: PoliticaSenha
+}
+
+fn le_numero(variavel: &impl Fn(&str) -> Option<String>, nome: &str, padrao: u32) -> Result<u32, ErroApi>{
+    match variavel(nome){
+        Some(valor) => match valor.trim().parse::<u32>(){
+            Ok(numero) => {
+                return Ok(numero)
+            },
+            Err(_) => {
+                return Err(ErroApi::Interno(format!("{} inválido: {}", nome, valor)))
+            }
+        },
+        None => {
+            return Ok(padrao)
+        }
+    }
+}
+
+fn le_booleano(variavel: &impl Fn(&str) -> Option<String>, nome: &str, padrao: bool) -> Result<bool, ErroApi>{
+    match variavel(nome).as_deref().map(str::trim){
+        None => Ok(padrao),
+        Some("true") => Ok(true),
+        Some("false") => Ok(false),
+        Some(outro) => Err(ErroApi::Interno(format!("{} inválido: {}. Use true ou false.", nome, outro)))
+    }
+}
+
+impl ConfiguracaoSenhas{
+    /// Monta a configuração a partir das variáveis `SENHA_*`. As ausentes ficam com o valor padrão.
+    pub fn de_variaveis(variavel: impl Fn(&str) -> Option<String>) -> Result<Self, ErroApi>{
+        let argon2 = ParametrosArgon2::default();
+        let argon2 = ParametrosArgon2{
+            memoria: le_numero(&variavel, "SENHA_ARGON2_MEMORIA", argon2.memoria)?,
+            iteracoes: le_numero(&variavel, "SENHA_ARGON2_ITERACOES", argon2.iteracoes)?,
+            paralelismo: le_numero(&variavel, "SENHA_ARGON2_PARALELISMO", argon2.paralelismo)?
+        };
+        // Confere os parâmetros agora, e não no primeiro cadastro.
+        if let Err(e) = Params::new(argon2.memoria, argon2.iteracoes, argon2.paralelismo, None){
+            return Err(ErroApi::Interno(format!("Parâmetros do Argon2 inválidos: {}", e)))
+        }
+
+        let padrao = PoliticaSenha::default();
+        let senhas_vazadas = match variavel("SENHA_LISTA_VAZADAS"){
+            Some(arquivo) => PoliticaSenha::carrega_senhas_vazadas(Path::new(arquivo.trim()))?,
+            None => HashSet::new()
+        };
+        let politica = PoliticaSenha{
+            tamanho_minimo: le_numero(&variavel, "SENHA_TAMANHO_MINIMO", padrao.tamanho_minimo as u32)? as usize,
+            tamanho_maximo: le_numero(&variavel, "SENHA_TAMANHO_MAXIMO", padrao.tamanho_maximo as u32)? as usize,
+            exige_maiuscula: le_booleano(&variavel, "SENHA_EXIGE_MAIUSCULA", padrao.exige_maiuscula)?,
+            exige_minuscula: le_booleano(&variavel, "SENHA_EXIGE_MINUSCULA", padrao.exige_minuscula)?,
+            exige_numero: le_booleano(&variavel, "SENHA_EXIGE_NUMERO", padrao.exige_numero)?,
+            exige_simbolo: le_booleano(&variavel, "SENHA_EXIGE_SIMBOLO", padrao.exige_simbolo)?,
+            senhas_vazadas
+        };
+        if politica.tamanho_minimo > politica.tamanho_maximo{
+            return Err(ErroApi::Interno("SENHA_TAMANHO_MINIMO é maior que SENHA_TAMANHO_MAXIMO.".to_string()))
+        }
+
+        return Ok(ConfiguracaoSenhas{
+            argon2,
+            politica
+        })
+    }
+
+    fn argon2(&self) -> Result<Argon2<'static>, ErroApi>{
+        match Params::new(self.argon2.memoria, self.argon2.iteracoes, self.argon2.paralelismo, None){
+            Ok(parametros) => {
+                return Ok(Argon2::new(Algorithm::Argon2id, Version::V0x13, parametros))
+            },
+            Err(e) => {
+                return Err(ErroApi::Interno(format!("Parâmetros do Argon2 inválidos: {}", e)))
+            }
+        }
+    }
+
+    /// Hash Argon2id da senha, no formato PHC (`$argon2id$v=19$m=...`), com um sal aleatório.
+    pub fn gera_hash(&self, senha: &str) -> Result<String, ErroApi>{
+        let mut bytes = [0_u8; 16];
+        OsRng.fill_bytes(&mut bytes);
+        let sal = match SaltString::encode_b64(&bytes){
+            Ok(sal) => sal,
+            Err(e) => {
+                return Err(ErroApi::Interno(format!("Erro ao gerar o sal da senha: {}", e)))
+            }
+        };
+        match self.argon2()?.hash_password(senha.as_bytes(), &sal){
+            Ok(hash) => {
+                return Ok(hash.to_string())
+            },
+            Err(e) => {
+                return Err(ErroApi::Interno(format!("Erro ao gerar o hash da senha: {}", e)))
+            }
+        }
+    }
+
+    /// Confere a senha com um hash Argon2id ou com um hash bcrypt antigo.
+    pub fn verifica(&self, senha: &str, hash: &str) -> VerificacaoSenha{
+        if !hash.starts_with("$argon2"){
+            return match pwhash::unix::verify(senha, hash){
+                true => VerificacaoSenha::PrecisaAtualizar,
+                false => VerificacaoSenha::Incorreta
+            }
+        }
+
+        let hash = match PasswordHash::new(hash){
+            Ok(hash) => hash,
+            Err(_) => {
+                return VerificacaoSenha::Incorreta
+            }
+        };
+        if Argon2::default().verify_password(senha.as_bytes(), &hash).is_err(){
+            return VerificacaoSenha::Incorreta
+        }
+
+        // Hashes de outra variante ou com outros parâmetros são refeitos com os atuais.
+        let atuais = (Some(self.argon2.memoria), Some(self.argon2.iteracoes), Some(self.argon2.paralelismo));
+        let params = &hash.params;
+        let do_hash = (params.get_decimal("m"), params.get_decimal("t"), params.get_decimal("p"));
+        if hash.algorithm.as_str() != "argon2id" || do_hash != atuais{
+            return VerificacaoSenha::PrecisaAtualizar
+        }
+        return VerificacaoSenha::Correta
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum VerificacaoSenha{
+    Incorreta,
+    Correta,
+    /// A senha está certa, mas o hash é bcrypt ou usa parâmetros antigos e deve ser refeito.
+    PrecisaAtualizar
+}
+
+impl VerificacaoSenha{
+    pub fn correta(&self) -> bool{
+        *self != VerificacaoSenha::Incorreta
+    }
+}
+
+/// Configuração usada pela API, lida do `.env` na primeira chamada. Nos testes, o Argon2
+/// usa parâmetros leves para não atrasar os testes que cadastram usuários.
+pub fn configuracao_senhas() -> Result<&'static ConfiguracaoSenhas, ErroApi>{
+    static CONFIGURACAO: OnceLock<ConfiguracaoSenhas> = OnceLock::new();
+    if let Some(configuracao) = CONFIGURACAO.get(){
+        return Ok(configuracao)
+    }
+
+    let configuracao = if cfg!(test){
+        ConfiguracaoSenhas{
+            argon2: ParametrosArgon2{
+                memoria: 1024,
+                iteracoes: 1,
+                paralelismo: 1
+            },
+            politica: PoliticaSenha::default()
+        }
+    } else {
+        dotenv().ok();
+        ConfiguracaoSenhas::de_variaveis(|nome| env::var(nome).ok())?
+    };
+    return Ok(CONFIGURACAO.get_or_init(|| configuracao))
+}
+
+pub fn gera_hash_senha(senha: &str) -> Result<String, ErroApi>{
+    configuracao_senhas()?.gera_hash(senha)
+}
+
+pub fn verifica_senha(senha: &str, hash: &str) -> Result<VerificacaoSenha, ErroApi>{
+    Ok(configuracao_senhas()?.verifica(senha, hash))
+}
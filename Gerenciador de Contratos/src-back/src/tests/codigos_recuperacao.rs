@@ -1,16 +1,19 @@
-use axum::Json;
+use std::collections::HashSet;
 
-use crate::{controllers::{codigos_recuperacao::{deleta_codigo, envia_codigo_recuperacao, verifica_codigo_recuperacao, CodigoRecuperacaoInput}, cria_conn, usuarios::{cadastra_usuario, realiza_login, CredenciaisUsuario, EmailInput}}, models::{codigos_recuperacao::cadastra_codigo_recuperacao_db, usuarios::deleta_usuario}, tests::usuarios::usuario_padrao};
+use axum::{extract::State, http::StatusCode, Json};
+use chrono::Duration;
+
+use crate::{controllers::{codigos_recuperacao::{deleta_codigo, envia_codigo_recuperacao, gera_codigo_aleatorio, gera_codigo_recuperacao, verifica_codigo_recuperacao, CodigoRecuperacaoInput}, envia_emails::FinalidadeCodigo, gera_hash, obtem_conn, usuarios::{cadastra_usuario, redefine_senha_usuario, verifica_credenciais, CredenciaisUsuario, EmailInput, RecuperaSenhaInput}}, erros::ErroApi, senhas::gera_hash_senha, models::{codigos_recuperacao::{cadastra_codigo_recuperacao_db, verifica_codigo_recuperacao_db, CodigoRecuperacao, MAX_TENTATIVAS_CODIGO}, usuarios::deleta_usuario}, tests::{pool_testes, usuarios::usuario_padrao}};
 
 #[tokio::test]
 async fn test_cadastra_codigo_ok(){
     let usuario = usuario_padrao("100");
     let email = usuario.email.clone();
 
-    let usuario = cadastra_usuario(Json(usuario)).await.unwrap().1;
+    let usuario = cadastra_usuario(State(pool_testes()), Json(usuario)).await.unwrap().1;
     let id = usuario.0.idusuario.to_string();
 
-    let idcodigo = envia_codigo_recuperacao(Json(EmailInput{email})).await.unwrap().1.0.idcodigo;
+    let idcodigo = envia_codigo_recuperacao(State(pool_testes()), Json(EmailInput{email})).await.unwrap().1.0.idcodigo;
 
     assert!(deleta_codigo(idcodigo).await.is_ok());
     assert!(deleta_usuario(id).await.is_ok());
@@ -20,12 +23,12 @@ async fn test_cadastra_codigo_ok(){
 async fn test_cadastra_codigo_err(){
     let usuario = usuario_padrao("101");
 
-    let usuario = cadastra_usuario(Json(usuario)).await.unwrap().1;
+    let usuario = cadastra_usuario(State(pool_testes()), Json(usuario)).await.unwrap().1;
     let id = usuario.0.idusuario.to_string();
 
     let email_invalido = "emailinvalido@gmail.com".to_string();
 
-    assert!(envia_codigo_recuperacao(Json(EmailInput{email: email_invalido})).await.is_err());
+    assert!(envia_codigo_recuperacao(State(pool_testes()), Json(EmailInput{email: email_invalido})).await.is_err());
     
     assert!(deleta_usuario(id).await.is_ok());
 }
@@ -34,17 +37,156 @@ async fn test_cadastra_codigo_err(){
 async fn test_verifica_codigo_err(){
     let usuario = usuario_padrao("102");
     let email = usuario.email.to_string();
+    let email_clone = email.clone();
 
-    let usuario = cadastra_usuario(Json(usuario)).await.unwrap().1;
+    let usuario = cadastra_usuario(State(pool_testes()), Json(usuario)).await.unwrap().1;
     let id = usuario.0.idusuario.to_string();
 
-    let idcodigo = envia_codigo_recuperacao(Json(EmailInput{email})).await.unwrap().1.0.idcodigo;
+    let idcodigo = envia_codigo_recuperacao(State(pool_testes()), Json(EmailInput{email})).await.unwrap().1.0.idcodigo;
 
-    assert!(verifica_codigo_recuperacao(Json(CodigoRecuperacaoInput{
-        idusuario: id.clone(),
+    assert!(verifica_codigo_recuperacao(State(pool_testes()), Json(CodigoRecuperacaoInput{
+        email: email_clone,
         codigodigitado: "CodInvalido".to_string()
     })).await.is_err());
 
     assert!(deleta_codigo(idcodigo).await.is_ok());
     assert!(deleta_usuario(id).await.is_ok());
-}
\ No newline at end of file
+}
+
+#[test]
+fn test_gera_codigo_aleatorio(){
+    let codigos: HashSet<String> = (0..50).map(|_| gera_codigo_aleatorio()).collect();
+    assert!(codigos.iter().all(|codigo| codigo.len() == 6 && codigo.chars().all(|c| c.is_ascii_digit())));
+    assert!(codigos.len() > 1);
+}
+
+#[tokio::test]
+async fn test_codigo_guardado_com_hash(){
+    let usuario = usuario_padrao("103");
+    let email = usuario.email.clone();
+
+    let id = cadastra_usuario(State(pool_testes()), Json(usuario)).await.unwrap().1.0.idusuario;
+    let finalidade = FinalidadeCodigo::Recuperacao;
+    let antigo = gera_codigo_recuperacao(&pool_testes(), email.clone(), &finalidade).await.unwrap();
+    let novo = gera_codigo_recuperacao(&pool_testes(), email.clone(), &finalidade).await.unwrap();
+
+    let conn = &obtem_conn(&pool_testes()).await.unwrap();
+    // O código novo invalida o anterior.
+    if antigo.codigo != novo.codigo{
+        assert!(verifica_codigo_recuperacao_db(conn, id.clone(), antigo.codigo, finalidade.chave()).await.is_err());
+    }
+    assert_eq!(verifica_codigo_recuperacao_db(conn, id.clone(), novo.codigo.clone(), finalidade.chave()).await.unwrap(), novo.idcodigo);
+    // Cada código só pode ser usado uma vez.
+    assert!(verifica_codigo_recuperacao_db(conn, id.clone(), novo.codigo, finalidade.chave()).await.is_err());
+
+    assert!(deleta_usuario(id).await.is_ok());
+}
+
+#[tokio::test]
+async fn test_codigo_expirado(){
+    let usuario = usuario_padrao("104");
+
+    let id = cadastra_usuario(State(pool_testes()), Json(usuario)).await.unwrap().1.0.idusuario;
+    let agora = chrono::Utc::now().naive_utc();
+    let conn = &obtem_conn(&pool_testes()).await.unwrap();
+    cadastra_codigo_recuperacao_db(conn, CodigoRecuperacao{
+        hashcodigo: gera_hash_senha("123456").unwrap(),
+        datacriacao: agora - Duration::hours(2),
+        dataexpiracao: agora - Duration::hours(1),
+        status: "Não utilizado".to_string(),
+        idusuario: id.clone(),
+        idcodigo: gera_hash("codigoexpirado104"),
+        tentativas: 0,
+        dataultimatentativa: None,
+        hashtoken: None,
+        finalidade: FinalidadeCodigo::Recuperacao.chave()
+    }).await.unwrap();
+
+    let erro = verifica_codigo_recuperacao_db(conn, id.clone(), "123456".to_string(), FinalidadeCodigo::Recuperacao.chave()).await.unwrap_err();
+    assert_eq!(erro.corpo().field, Some("codigo".to_string()));
+
+    assert!(deleta_usuario(id).await.is_ok());
+}
+
+#[tokio::test]
+async fn test_bloqueio_tentativas_codigo(){
+    let usuario = usuario_padrao("105");
+    let email = usuario.email.clone();
+
+    let id = cadastra_usuario(State(pool_testes()), Json(usuario)).await.unwrap().1.0.idusuario;
+    let codigo = gera_codigo_recuperacao(&pool_testes(), email.clone(), &FinalidadeCodigo::Recuperacao).await.unwrap().codigo;
+    let errado = if codigo == "000000" { "111111" } else { "000000" };
+
+    for _ in 0..MAX_TENTATIVAS_CODIGO{
+        let erro = verifica_codigo_recuperacao(State(pool_testes()), Json(CodigoRecuperacaoInput{
+            email: email.clone(),
+            codigodigitado: errado.to_string()
+        })).await.unwrap_err();
+        assert_eq!(erro.corpo().field, Some("codigodigitado".to_string()));
+    }
+
+    // Bloqueado: nem o código certo nem um código novo são aceitos.
+    let erro = verifica_codigo_recuperacao(State(pool_testes()), Json(CodigoRecuperacaoInput{
+        email: email.clone(),
+        codigodigitado: codigo
+    })).await.unwrap_err();
+    assert!(matches!(erro, ErroApi::Proibido(_)));
+    assert!(matches!(gera_codigo_recuperacao(&pool_testes(), email, &FinalidadeCodigo::Recuperacao).await, Err(ErroApi::Proibido(_))));
+
+    assert!(deleta_usuario(id).await.is_ok());
+}
+
+#[tokio::test]
+async fn test_redefine_senha_com_token(){
+    let usuario = usuario_padrao("106");
+    let email = usuario.email.clone();
+
+    let id = cadastra_usuario(State(pool_testes()), Json(usuario)).await.unwrap().1.0.idusuario;
+    let codigo = gera_codigo_recuperacao(&pool_testes(), email.clone(), &FinalidadeCodigo::Recuperacao).await.unwrap().codigo;
+
+    let redefine = |tokenredefinicao: &str| redefine_senha_usuario(State(pool_testes()), Json(RecuperaSenhaInput{
+        tokenredefinicao: tokenredefinicao.to_string(),
+        senha_nova: "Senha@Nova106".to_string()
+    }));
+    // Sem o token, nada muda.
+    assert!(matches!(redefine("tokeninventado").await, Err(ErroApi::Validacao{..})));
+
+    let token = verifica_codigo_recuperacao(State(pool_testes()), Json(CodigoRecuperacaoInput{
+        email: email.clone(),
+        codigodigitado: codigo
+    })).await.unwrap().1.0.tokenredefinicao;
+    assert_eq!(redefine(&token).await.unwrap(), StatusCode::OK);
+    assert!(verifica_credenciais(&pool_testes(), CredenciaisUsuario{
+        email: email.clone(),
+        senha: "Senha@Nova106".to_string()
+    }).await.is_ok());
+
+    // O token é consumido na redefinição.
+    assert!(matches!(redefine(&token).await, Err(ErroApi::Validacao{..})));
+
+    assert!(deleta_usuario(id).await.is_ok());
+}
+
+#[tokio::test]
+async fn test_codigo_de_outra_finalidade(){
+    let usuario = usuario_padrao("107");
+    let email = usuario.email.clone();
+
+    let id = cadastra_usuario(State(pool_testes()), Json(usuario)).await.unwrap().1.0.idusuario;
+    let vinculo = FinalidadeCodigo::VinculoProvedor("Google".to_string());
+    let codigo = gera_codigo_recuperacao(&pool_testes(), email.clone(), &vinculo).await.unwrap().codigo;
+
+    // Um código de vínculo não redefine a senha, nem ativa a conta ou desvincula o provedor.
+    let erro = verifica_codigo_recuperacao(State(pool_testes()), Json(CodigoRecuperacaoInput{
+        email: email.clone(),
+        codigodigitado: codigo.clone()
+    })).await.unwrap_err();
+    assert_eq!(erro.corpo().field, Some("codigodigitado".to_string()));
+    let conn = &obtem_conn(&pool_testes()).await.unwrap();
+    for outra in [FinalidadeCodigo::Ativacao, FinalidadeCodigo::DesvinculoProvedor("Google".to_string()), FinalidadeCodigo::VinculoProvedor("GitHub".to_string())]{
+        assert!(verifica_codigo_recuperacao_db(conn, id.clone(), codigo.clone(), outra.chave()).await.is_err());
+    }
+
+    assert!(verifica_codigo_recuperacao_db(conn, id.clone(), codigo, vinculo.chave()).await.is_ok());
+    assert!(deleta_usuario(id).await.is_ok());
+}
@@ -1,6 +1,6 @@
-use axum::{extract::Query, Json};
+use axum::{extract::{Query, State}, Json};
 
-use crate::{controllers::{contas_bancarias::{atualiza_conta_bancaria, busca_conta_bancaria_idusuario, cadastra_conta_bancaria, ContaBancariaInput}, usuarios::{cadastra_usuario, deleta_usuario, IdInput}}, models::contas_bancarias::deleta_conta_bancaria, tests::usuarios::usuario_padrao};
+use crate::{controllers::{contas_bancarias::{atualiza_conta_bancaria, busca_conta_bancaria_idusuario, cadastra_conta_bancaria, ContaBancariaInput}, usuarios::{cadastra_usuario, deleta_usuario, IdInput}}, models::contas_bancarias::deleta_conta_bancaria, tests::{pool_testes, sessoes::usuario_autenticado, usuarios::usuario_padrao}};
 
 
 pub fn conta_bancaria_padrao(idusuario: &str, numeroteste: &str) -> ContaBancariaInput{
@@ -19,14 +19,14 @@ pub fn conta_bancaria_padrao(idusuario: &str, numeroteste: &str) -> ContaBancari
 async fn test_cadastra_conta_bancaria_ok(){
   let usuario = usuario_padrao("600");
 
-  let usuario = cadastra_usuario(Json(usuario)).await.unwrap().1;
+  let usuario = cadastra_usuario(State(pool_testes()), Json(usuario)).await.unwrap().1;
   let idusuario = usuario.0.idusuario.to_string();
 
   let conta = conta_bancaria_padrao(&idusuario, "600");
 
-  let idconta = cadastra_conta_bancaria(Json(conta)).await.unwrap().1.0.to_string();  
+  let idconta = cadastra_conta_bancaria(State(pool_testes()), usuario_autenticado(&idusuario), Json(conta)).await.unwrap().1.0.to_string();  
 
-  assert!(deleta_usuario(Query(IdInput{id: idusuario})).await.is_ok());
+  assert!(deleta_usuario(usuario_autenticado(&idusuario), Query(IdInput{id: idusuario})).await.is_ok());
   assert!(deleta_conta_bancaria(idconta).await.is_ok());
 }
 
@@ -34,10 +34,10 @@ async fn test_cadastra_conta_bancaria_ok(){
 async fn test_cadastra_conta_bancaria_err(){
   let usuario = usuario_padrao("601");
 
-  let usuario = cadastra_usuario(Json(usuario)).await.unwrap().1;
+  let usuario = cadastra_usuario(State(pool_testes()), Json(usuario)).await.unwrap().1;
   let idusuario = usuario.0.idusuario.to_string();
 
-  assert!(cadastra_conta_bancaria(Json(
+  assert!(cadastra_conta_bancaria(State(pool_testes()), usuario_autenticado(&idusuario), Json(
     ContaBancariaInput{ 
       idusuario: idusuario.clone(),
       numeroconta: "".to_string(), 
@@ -45,7 +45,7 @@ async fn test_cadastra_conta_bancaria_err(){
       nomebanco: "".to_string() }
   )).await.is_err());
 
-  assert!(deleta_usuario(Query(IdInput{id: idusuario})).await.is_ok());
+  assert!(deleta_usuario(usuario_autenticado(&idusuario), Query(IdInput{id: idusuario})).await.is_ok());
 }
 
 
@@ -53,16 +53,16 @@ async fn test_cadastra_conta_bancaria_err(){
 async fn test_busca_conta_bancaria_idusuario_ok(){
     let usuario = usuario_padrao("602");
 
-    let usuario = cadastra_usuario(Json(usuario)).await.unwrap().1;
+    let usuario = cadastra_usuario(State(pool_testes()), Json(usuario)).await.unwrap().1;
     let idusuario = usuario.0.idusuario.to_string();
 
     let conta = conta_bancaria_padrao(&idusuario, "602");
 
-    let idconta = cadastra_conta_bancaria(Json(conta)).await.unwrap().1.0.to_string();  
+    let idconta = cadastra_conta_bancaria(State(pool_testes()), usuario_autenticado(&idusuario), Json(conta)).await.unwrap().1.0.to_string();  
 
-    assert!(busca_conta_bancaria_idusuario(Query(IdInput{id: idusuario.clone()})).await.is_ok());
+    assert!(busca_conta_bancaria_idusuario(State(pool_testes()), usuario_autenticado(&idusuario), Query(IdInput{id: idusuario.clone()})).await.is_ok());
 
-    assert!(deleta_usuario(Query(IdInput{id: idusuario})).await.is_ok());
+    assert!(deleta_usuario(usuario_autenticado(&idusuario), Query(IdInput{id: idusuario})).await.is_ok());
     assert!(deleta_conta_bancaria(idconta).await.is_ok());
 }
 
@@ -70,16 +70,16 @@ async fn test_busca_conta_bancaria_idusuario_ok(){
 async fn test_busca_conta_bancaria_idusuario_err(){
     let usuario = usuario_padrao("603");
 
-    let usuario = cadastra_usuario(Json(usuario)).await.unwrap().1;
+    let usuario = cadastra_usuario(State(pool_testes()), Json(usuario)).await.unwrap().1;
     let idusuario = usuario.0.idusuario.to_string();
 
     let conta = conta_bancaria_padrao(&idusuario, "603");
 
-    let idconta = cadastra_conta_bancaria(Json(conta)).await.unwrap().1.0.to_string();  
+    let idconta = cadastra_conta_bancaria(State(pool_testes()), usuario_autenticado(&idusuario), Json(conta)).await.unwrap().1.0.to_string();  
 
-    assert!(busca_conta_bancaria_idusuario(Query(IdInput{id: "ID inválido".to_string()})).await.is_err());
+    assert!(busca_conta_bancaria_idusuario(State(pool_testes()), usuario_autenticado(&idusuario), Query(IdInput{id: "ID inválido".to_string()})).await.is_err());
 
-    assert!(deleta_usuario(Query(IdInput{id: idusuario})).await.is_ok());
+    assert!(deleta_usuario(usuario_autenticado(&idusuario), Query(IdInput{id: idusuario})).await.is_ok());
     assert!(deleta_conta_bancaria(idconta).await.is_ok());
 }
 
@@ -87,19 +87,19 @@ async fn test_busca_conta_bancaria_idusuario_err(){
 async fn test_atualiza_conta_bancaria_ok(){
     let usuario = usuario_padrao("604");
 
-    let usuario = cadastra_usuario(Json(usuario)).await.unwrap().1;
+    let usuario = cadastra_usuario(State(pool_testes()), Json(usuario)).await.unwrap().1;
     let idusuario = usuario.0.idusuario.to_string();
 
     let conta = conta_bancaria_padrao(&idusuario, "604");
 
-    let idconta = cadastra_conta_bancaria(Json(conta)).await.unwrap().1.0.to_string();  
+    let idconta = cadastra_conta_bancaria(State(pool_testes()), usuario_autenticado(&idusuario), Json(conta)).await.unwrap().1.0.to_string();  
 
-    let mut conta_atualizada = busca_conta_bancaria_idusuario(Query(IdInput{id: idusuario.clone()})).await.unwrap().1.0;
+    let mut conta_atualizada = busca_conta_bancaria_idusuario(State(pool_testes()), usuario_autenticado(&idusuario), Query(IdInput{id: idusuario.clone()})).await.unwrap().1.0;
     conta_atualizada.nomebanco = "Banco Teste 604 Atualizado".to_string();
 
-    assert!(atualiza_conta_bancaria(Json(conta_atualizada)).await.is_ok());
+    assert!(atualiza_conta_bancaria(State(pool_testes()), usuario_autenticado(&idusuario), Json(conta_atualizada)).await.is_ok());
 
-    assert!(deleta_usuario(Query(IdInput{id: idusuario})).await.is_ok());
+    assert!(deleta_usuario(usuario_autenticado(&idusuario), Query(IdInput{id: idusuario})).await.is_ok());
     assert!(deleta_conta_bancaria(idconta).await.is_ok());
 }
 
@@ -107,18 +107,18 @@ async fn test_atualiza_conta_bancaria_ok(){
 async fn test_atualiza_conta_bancaria_err(){
     let usuario = usuario_padrao("605");
 
-    let usuario = cadastra_usuario(Json(usuario)).await.unwrap().1;
+    let usuario = cadastra_usuario(State(pool_testes()), Json(usuario)).await.unwrap().1;
     let idusuario = usuario.0.idusuario.to_string();
 
     let conta = conta_bancaria_padrao(&idusuario, "605");
 
-    let idconta = cadastra_conta_bancaria(Json(conta)).await.unwrap().1.0.to_string();  
+    let idconta = cadastra_conta_bancaria(State(pool_testes()), usuario_autenticado(&idusuario), Json(conta)).await.unwrap().1.0.to_string();  
 
-    let mut conta_atualizada = busca_conta_bancaria_idusuario(Query(IdInput{id: idusuario.clone()})).await.unwrap().1.0;
+    let mut conta_atualizada = busca_conta_bancaria_idusuario(State(pool_testes()), usuario_autenticado(&idusuario), Query(IdInput{id: idusuario.clone()})).await.unwrap().1.0;
     conta_atualizada.nomebanco = "".to_string();
 
-    assert!(atualiza_conta_bancaria(Json(conta_atualizada)).await.is_err());
+    assert!(atualiza_conta_bancaria(State(pool_testes()), usuario_autenticado(&idusuario), Json(conta_atualizada)).await.is_err());
 
-    assert!(deleta_usuario(Query(IdInput{id: idusuario})).await.is_ok());
+    assert!(deleta_usuario(usuario_autenticado(&idusuario), Query(IdInput{id: idusuario})).await.is_ok());
     assert!(deleta_conta_bancaria(idconta).await.is_ok());
 }
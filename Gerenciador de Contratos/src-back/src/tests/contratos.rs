@@ -0,0 +1,107 @@
+use std::str::FromStr;
+
+use axum::{extract::State, Json};
+use chrono::{NaiveDate, NaiveDateTime};
+use diesel::{ExpressionMethods, QueryDsl, RunQueryDsl};
+
+use crate::{controllers::{contratos::{renova_contrato, RenovacaoContratoInput}, maquinas::{cadastra_maquina, deleta_maquina_id}, obtem_conn, usuarios::cadastra_usuario}, dinheiro::Dinheiro, erros::ErroApi, models::{self, contratos::{Contrato, StatusContrato}, executa, precos_maquinas::MedidaTempo, solicitacoes_contratos::StatusSolicitacao, usuarios::deleta_usuario}, tests::{maquinas::{converte_tipo_maquina, maquina_padrao}, pool_testes, reservas_maquinas::solicitacao_reserva, sessoes::usuario_autenticado, usuarios::usuario_padrao}};
+
+fn data(dia: u32) -> NaiveDateTime{
+    NaiveDate::from_ymd_opt(2032, 3, dia).unwrap().and_hms_opt(8, 0, 0).unwrap()
+}
+
+pub fn contrato_padrao(idmaquina: &str, numeroteste: &str, prazo: f64) -> Contrato{
+    Contrato{
+        idcontrato: format!("idcontrato{}", numeroteste),
+        idlocatario: format!("idlocatario{}", numeroteste),
+        idlocador: format!("idlocador{}", numeroteste),
+        idenderecolocatario: format!("idenderecolocatario{}", numeroteste),
+        idenderecolocador: format!("idenderecolocador{}", numeroteste),
+        idenderecoretirada: format!("idenderecolocador{}", numeroteste),
+        idmaquina: idmaquina.to_string(),
+        idsolicitacaocontrato: format!("idsolicitacao{}", numeroteste),
+        idcontabancarialocador: format!("idconta{}", numeroteste),
+        medidatempolocacao: MedidaTempo::Dias,
+        cidadeforo: "Cidade".to_string(),
+        statuscontrato: StatusContrato::Ativo,
+        prazolocacao: prazo,
+        valorlocacao: Dinheiro::de_reais(100),
+        datacontrato: chrono::Utc::now().naive_utc(),
+        motivoencerramento: None,
+        dataencerramento: None,
+        dataavisotermino: None,
+        datadevolucao: None,
+        dataavisoatraso: None
+    }
+}
+
+#[test]
+fn test_status_contrato_texto(){
+    assert_eq!(StatusContrato::from_str("Concluído").unwrap(), StatusContrato::Concluido);
+    assert_eq!(StatusContrato::Rescindido.to_string(), "Rescindido");
+    assert_eq!(StatusContrato::from_str("Atrasado").unwrap(), StatusContrato::Atrasado);
+    assert!(StatusContrato::from_str("Concluido").is_err());
+}
+
+#[tokio::test]
+async fn test_ciclo_vida_contrato(){
+    let usuario = cadastra_usuario(State(pool_testes()), Json(usuario_padrao("970"))).await.unwrap().1;
+    let idusuario = usuario.0.idusuario.to_string();
+    let idmaquina = cadastra_maquina(State(pool_testes()), usuario_autenticado(&idusuario), Json(
+        converte_tipo_maquina(maquina_padrao("970").await, idusuario.clone()).await)).await.unwrap().1.0.idmaquina;
+
+    let conn = &obtem_conn(&pool_testes()).await.unwrap();
+
+    // Do dia 10 ao dia 15, aprovada e com contrato.
+    let (solicitacao, reserva) = solicitacao_reserva(&idmaquina, "970", data(10), 5.0);
+    let idaprovada = models::solicitacoes_contratos::cadastra_solicitacao_contrato(conn, solicitacao, reserva).await.unwrap();
+    models::solicitacoes_contratos::atualiza_status_solicitacao(conn, idaprovada.clone(), StatusSolicitacao::Aprovada,
+        Some("idlocador970".to_string())).await.unwrap();
+    let idcontrato = models::contratos::cadastra_contrato(conn, contrato_padrao(&idmaquina, "970", 5.0)).await.unwrap();
+
+    // Só o locatário pode pedir a renovação.
+    let res = renova_contrato(State(pool_testes()), usuario_autenticado("idlocador970"),
+        Json(RenovacaoContratoInput{id: idcontrato.clone(), prazoadicional: 1.0})).await;
+    assert!(matches!(res, Err(ErroApi::Proibido(_))));
+
+    // A renovação estende a reserva até o dia 17.
+    let contrato = models::contratos::renova_contrato(conn, idcontrato.clone(), 2.0, Dinheiro::de_reais(50)).await.unwrap();
+    assert_eq!(contrato.prazolocacao, 7.0);
+    assert_eq!(contrato.valorlocacao, Dinheiro::de_reais(150));
+
+    let (solicitacao, reserva) = solicitacao_reserva(&idmaquina, "971", data(16), 2.0);
+    let res = models::solicitacoes_contratos::cadastra_solicitacao_contrato(conn, solicitacao.clone(), reserva.clone()).await;
+    assert!(matches!(res, Err(ErroApi::Conflito(_))));
+
+    // Depois da rescisão, o período fica livre.
+    let contrato = models::contratos::encerra_contrato(conn, idcontrato.clone(), StatusContrato::Rescindido,
+        Some("Máquina com defeito".to_string())).await.unwrap();
+    assert_eq!(contrato.statuscontrato, StatusContrato::Rescindido);
+    assert!(contrato.dataencerramento.is_some());
+    assert!(models::solicitacoes_contratos::cadastra_solicitacao_contrato(conn, solicitacao, reserva).await.is_ok());
+
+    let res = models::contratos::encerra_contrato(conn, idcontrato.clone(), StatusContrato::Concluido, None).await;
+    assert!(matches!(res, Err(ErroApi::Conflito(_))));
+    let res = models::contratos::renova_contrato(conn, idcontrato.clone(), 1.0, Dinheiro::de_reais(10)).await;
+    assert!(matches!(res, Err(ErroApi::Conflito(_))));
+
+    let rescindidos = models::contratos::busca_contratos_usuario(conn, "idlocador970".to_string(), true,
+        Some(StatusContrato::Rescindido)).await.unwrap();
+    assert_eq!(rescindidos.len(), 1);
+    let ativos = models::contratos::busca_contratos_usuario(conn, "idlocatario970".to_string(), false,
+        Some(StatusContrato::Ativo)).await.unwrap();
+    assert!(ativos.is_empty());
+
+    executa(conn, move |conn| {
+        use crate::schema::{contratos, solicitacoes_contratos};
+
+        diesel::delete(contratos::table.filter(contratos::idcontrato.eq(idcontrato)))
+            .execute(conn)
+            .map_err(ErroApi::from)?;
+        diesel::delete(solicitacoes_contratos::table.filter(solicitacoes_contratos::idsolicitacao.eq_any(["idsolicitacao970", "idsolicitacao971"])))
+            .execute(conn)
+            .map_err(ErroApi::from)
+    }).await.unwrap();
+    assert!(deleta_usuario(idusuario).await.is_ok());
+    assert!(deleta_maquina_id(idmaquina).await.is_ok());
+}
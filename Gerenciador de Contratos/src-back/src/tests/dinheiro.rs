@@ -0,0 +1,111 @@
+use std::str::FromStr;
+
+use diesel::{dsl::sql, sql_types::Numeric, RunQueryDsl};
+
+use crate::{controllers::obtem_conn, dinheiro::{Dinheiro, Percentual}, erros::ErroApi, models::executa, tests::pool_testes};
+
+#[test]
+fn test_multiplica_arredonda_centavos(){
+    // R$ 10,00 / 3 = 3,333... e R$ 0,05 / 2 = 0,025, que sobe para 0,03.
+    assert_eq!(Dinheiro::de_reais(10).multiplica(1, 3).unwrap(), Dinheiro::de_centavos(333));
+    assert_eq!(Dinheiro::de_centavos(5).multiplica(1, 2).unwrap(), Dinheiro::de_centavos(3));
+    assert_eq!(Dinheiro::de_centavos(-5).multiplica(1, 2).unwrap(), Dinheiro::de_centavos(-3));
+    assert_eq!(Dinheiro::de_reais(900).percentual(Percentual::de_inteiro(2)).unwrap(), Dinheiro::de_reais(18));
+    assert_eq!(Dinheiro::de_centavos(1999).percentual(Percentual::de_pontos_base(255)).unwrap(), Dinheiro::de_centavos(51));
+    // Os juros de um dia são arredondados uma vez só, e não a taxa diária primeiro.
+    assert_eq!(Dinheiro::de_reais(1000).percentual_proporcional(Percentual::de_inteiro(1), 1, 30).unwrap(), Dinheiro::de_centavos(33));
+}
+
+#[test]
+fn test_multiplica_fora_do_limite(){
+    assert!(matches!(Dinheiro::de_reais(10).multiplica(1, 0), Err(ErroApi::Interno(_))));
+    assert!(matches!(Dinheiro::de_centavos(i64::MAX).multiplica(2, 1), Err(ErroApi::Validacao{..})));
+    assert!(Dinheiro::de_reais(1).percentual_proporcional(Percentual::de_inteiro(1), i64::MAX, 1).is_err());
+}
+
+#[test]
+fn test_divide_dinheiro(){
+    let centavos = |partes: Vec<Dinheiro>| partes.into_iter().map(Dinheiro::centavos).collect::<Vec<_>>();
+    assert_eq!(centavos(Dinheiro::de_reais(100).divide(3)), vec![3334, 3333, 3333]);
+    assert_eq!(centavos(Dinheiro::de_reais(5).divide(1)), vec![500]);
+    assert_eq!(centavos(Dinheiro::de_centavos(2).divide(4)), vec![1, 1, 0, 0]);
+    assert!(Dinheiro::de_reais(5).divide(0).is_empty());
+}
+
+#[test]
+fn test_dinheiro_texto(){
+    assert_eq!(Dinheiro::from_str("1234.5").unwrap(), Dinheiro::de_centavos(123450));
+    assert_eq!(Dinheiro::from_str(" 3 ").unwrap(), Dinheiro::de_reais(3));
+    assert_eq!(Dinheiro::from_str("1.005").unwrap(), Dinheiro::de_centavos(101));
+    assert_eq!(Dinheiro::from_str("-0.125").unwrap(), Dinheiro::de_centavos(-13));
+    assert!(Dinheiro::from_str("1,50").is_err());
+    assert!(Dinheiro::from_str("").is_err());
+
+    assert_eq!(Dinheiro::de_centavos(123450).to_string(), "1234.50");
+    assert_eq!(Dinheiro::de_centavos(-7).to_string(), "-0.07");
+}
+
+#[test]
+fn test_dinheiro_json(){
+    assert_eq!(serde_json::to_string(&Dinheiro::de_centavos(123450)).unwrap(), "\"1234.50\"");
+    assert_eq!(serde_json::to_string(&-Dinheiro::de_centavos(5)).unwrap(), "\"-0.05\"");
+    assert_eq!(serde_json::from_str::<Dinheiro>("1234.5").unwrap(), Dinheiro::de_centavos(123450));
+    assert_eq!(serde_json::from_str::<Dinheiro>("0.1").unwrap(), Dinheiro::de_centavos(10));
+    assert_eq!(serde_json::from_str::<Dinheiro>("1.005").unwrap(), Dinheiro::de_centavos(101));
+    assert_eq!(serde_json::from_str::<Dinheiro>("250").unwrap(), Dinheiro::de_reais(250));
+    assert_eq!(serde_json::from_str::<Dinheiro>("\"19.99\"").unwrap(), Dinheiro::de_centavos(1999));
+    assert!(serde_json::from_str::<Dinheiro>("\"abc\"").is_err());
+    assert!(serde_json::from_str::<Dinheiro>("true").is_err());
+    assert!(serde_json::from_str::<Dinheiro>("-1").is_err());
+    assert!(serde_json::from_str::<Dinheiro>("-0.5").is_err());
+    assert!(serde_json::from_str::<Dinheiro>("\"-19.99\"").is_err());
+
+    // O limite é o da coluna NUMERIC(14, 2).
+    assert_eq!(serde_json::from_str::<Dinheiro>("\"999999999999.99\"").unwrap(), Dinheiro::MAXIMO);
+    assert!(serde_json::from_str::<Dinheiro>("\"1000000000000\"").is_err());
+    assert!(serde_json::from_str::<Dinheiro>("1000000000000").is_err());
+    assert!(serde_json::from_str::<Dinheiro>("1e15").is_err());
+    assert!(serde_json::from_str::<Dinheiro>("92233720368547758").is_err());
+}
+
+#[test]
+fn test_percentual(){
+    assert_eq!(Percentual::from_str("2.55").unwrap(), Percentual::de_pontos_base(255));
+    assert_eq!(Percentual::from_str(" 5 ").unwrap(), Percentual::de_inteiro(5));
+    assert_eq!(Percentual::from_str("0.125").unwrap(), Percentual::de_pontos_base(13));
+    assert!(Percentual::from_str("5%").is_err());
+
+    assert_eq!(Percentual::de_pontos_base(255).to_string(), "2.55");
+    assert_eq!(Percentual::de_pontos_base(250).to_string(), "2.5");
+    assert_eq!(Percentual::de_inteiro(2).to_string(), "2");
+
+    assert_eq!(serde_json::to_string(&Percentual::de_pontos_base(150)).unwrap(), "\"1.5\"");
+    assert_eq!(serde_json::from_str::<Percentual>("1.5").unwrap(), Percentual::de_pontos_base(150));
+    assert_eq!(serde_json::from_str::<Percentual>("\"7.25\"").unwrap(), Percentual::de_pontos_base(725));
+    assert!(serde_json::from_str::<Percentual>("-2").is_err());
+}
+
+#[tokio::test]
+async fn test_dinheiro_numeric(){
+    let conn = &obtem_conn(&pool_testes()).await.unwrap();
+    let valores = executa(conn, |conn| {
+        diesel::select((sql::<Numeric>("1234.50::NUMERIC(14, 2)"), sql::<Numeric>("0.125::NUMERIC")))
+            .get_result::<(Dinheiro, Dinheiro)>(conn)
+            .map_err(Into::into)
+    }).await.unwrap();
+    assert_eq!(valores, (Dinheiro::de_centavos(123450), Dinheiro::de_centavos(13)));
+
+    let ida = executa(conn, |conn| {
+        diesel::select(sql::<Numeric>("").bind::<Numeric, _>(Dinheiro::de_centavos(-9905)))
+            .get_result::<Dinheiro>(conn)
+            .map_err(Into::into)
+    }).await.unwrap();
+    assert_eq!(ida, Dinheiro::de_centavos(-9905));
+
+    let percentuais = executa(conn, |conn| {
+        diesel::select((sql::<Numeric>("2.55::NUMERIC(5, 2)"), sql::<Numeric>("").bind::<Numeric, _>(Percentual::de_pontos_base(1050))))
+            .get_result::<(Percentual, Percentual)>(conn)
+            .map_err(Into::into)
+    }).await.unwrap();
+    assert_eq!(percentuais, (Percentual::de_pontos_base(255), Percentual::de_pontos_base(1050)));
+}
@@ -0,0 +1,187 @@
+use std::collections::HashMap;
+
+use axum::{extract::State, http::StatusCode, Json};
+use chrono::NaiveDate;
+
+use crate::{controllers::{codigos_recuperacao::{deleta_codigo, envia_codigo_recuperacao}, notificacoes::{cadastra_notificacao, NotificacaoInput}, obtem_conn, envia_emails::{envia_email_codigo, renderiza_email, EmailContratoGerado, EmailContratoTerminando, EmailNovaSolicitacao, EmailRespostaSolicitacao, FinalidadeCodigo}, usuarios::{cadastra_usuario, EmailInput}}, emails::{transporte_email, Anexo, ConteudoEmail, Email, SegurancaSmtp, TransporteEmail}, dinheiro::Dinheiro, erros::ErroApi, models::{executa, usuarios::deleta_usuario}, tests::{pool_testes, usuarios::usuario_padrao}};
+
+fn transporte(variaveis: &[(&str, &str)]) -> Result<TransporteEmail, ErroApi>{
+    let variaveis: HashMap<String, String> = variaveis.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect();
+    TransporteEmail::de_variaveis(|nome| variaveis.get(nome).cloned())
+}
+
+fn email_teste(destinatario: &str) -> Email{
+    Email{
+        destinatario: destinatario.to_string(),
+        assunto: "MaqExpress | Teste".to_string(),
+        html: "<p>Teste</p>".to_string(),
+        texto: "Teste".to_string(),
+        anexos: vec![Anexo{
+            nome: "teste.pdf".to_string(),
+            tipo: "application/pdf".to_string(),
+            conteudo: b"%PDF-1.4".to_vec()
+        }]
+    }
+}
+
+#[test]
+fn test_transporte_de_variaveis(){
+    match transporte(&[]).unwrap(){
+        TransporteEmail::Smtp{host, porta, seguranca, ..} => {
+            assert_eq!(host, "smtp.gmail.com");
+            assert_eq!(porta, 587);
+            assert_eq!(seguranca, SegurancaSmtp::Starttls);
+        },
+        outro => panic!("Transporte inesperado: {:?}", outro)
+    }
+    match transporte(&[("SMTP_HOST", "localhost"), ("SMTP_TLS", "tls")]).unwrap(){
+        TransporteEmail::Smtp{host, porta, ..} => {
+            assert_eq!(host, "localhost");
+            assert_eq!(porta, 465);
+        },
+        outro => panic!("Transporte inesperado: {:?}", outro)
+    }
+    assert!(matches!(transporte(&[("EMAIL_TRANSPORTE", "arquivo")]).unwrap(), TransporteEmail::Arquivo{..}));
+    assert!(matches!(transporte(&[("EMAIL_TRANSPORTE", "memoria")]).unwrap(), TransporteEmail::Memoria(_)));
+
+    assert!(transporte(&[("EMAIL_TRANSPORTE", "pombo")]).is_err());
+    assert!(transporte(&[("SMTP_PORTA", "porta")]).is_err());
+    assert!(transporte(&[("SMTP_TLS", "talvez")]).is_err());
+    assert!(transporte(&[("EMAIL_REMETENTE", "remetente")]).is_err());
+}
+
+#[tokio::test]
+async fn test_transporte_arquivo(){
+    let pasta = std::env::temp_dir().join(format!("maqexpress-emails-{:08x}", rand::random::<u32>()));
+    let transporte = TransporteEmail::Arquivo{pasta: pasta.clone(), remetente: "MaqExpress <teste@maqexpress.com>".to_string()};
+
+    transporte.envia(email_teste("testeunit990@gmail.com")).await.unwrap();
+
+    let arquivos: Vec<_> = std::fs::read_dir(&pasta).unwrap().map(|arquivo| arquivo.unwrap().path()).collect();
+    assert_eq!(arquivos.len(), 1);
+    let conteudo = std::fs::read_to_string(&arquivos[0]).unwrap();
+    assert!(conteudo.contains("To: testeunit990@gmail.com"));
+    assert!(conteudo.contains("Subject: MaqExpress | Teste"));
+    assert!(conteudo.contains("multipart/alternative"));
+    assert!(conteudo.contains("Content-Type: application/pdf"));
+
+    std::fs::remove_dir_all(pasta).unwrap();
+}
+
+#[tokio::test]
+async fn test_transporte_memoria(){
+    let transporte = TransporteEmail::memoria();
+
+    transporte.envia(email_teste("testeunit991@gmail.com")).await.unwrap();
+    assert_eq!(transporte.enviados(), vec![email_teste("testeunit991@gmail.com")]);
+
+    let res = transporte.envia(email_teste("destinatario")).await;
+    assert!(matches!(res, Err(ErroApi::Validacao{..})));
+    assert_eq!(transporte.enviados().len(), 1);
+}
+
+#[tokio::test]
+async fn test_envia_email_codigo(){
+    let status = envia_email_codigo("testeunit992@gmail.com".to_string(), FinalidadeCodigo::Ativacao, "ABC123".to_string()).await.unwrap();
+    assert_eq!(status, StatusCode::CREATED);
+
+    let enviados = transporte_email().unwrap().enviados();
+    let email = enviados.iter().find(|email| email.destinatario == "testeunit992@gmail.com").unwrap();
+    assert_eq!(email.assunto, "MaqExpress | Ativação de Conta");
+    assert!(email.html.contains("<div class=\"code\">ABC123</div>"));
+    assert!(email.texto.contains("ABC123"));
+}
+
+#[test]
+fn test_renderiza_email(){
+    let conteudo = renderiza_email(&EmailRespostaSolicitacao{
+        nomemaquina: "Trator <Grande> & Cia".to_string(),
+        aprovada: false
+    }).unwrap();
+    assert_eq!(conteudo.assunto, "MaqExpress | Solicitação Recusada");
+    assert!(conteudo.html.contains("Trator &lt;Grande&gt; &amp; Cia"));
+    assert!(conteudo.texto.contains("Trator <Grande> & Cia foi recusada."));
+    assert!(!conteudo.html.contains("{{") && !conteudo.texto.contains("{{"));
+    assert!(conteudo.anexos.is_empty());
+
+    let data = NaiveDate::from_ymd_opt(2031, 5, 2).unwrap().and_hms_opt(14, 30, 0).unwrap();
+    let conteudo = renderiza_email(&EmailNovaSolicitacao{
+        nomelocatario: "Usuario Teste".to_string(),
+        nomemaquina: "Trator".to_string(),
+        prazolocacao: 3.0,
+        medidatempolocacao: "Dias".to_string(),
+        datainicio: data,
+        valorsolicitacao: Dinheiro::de_centavos(123450),
+        diasexpiracao: 7
+    }).unwrap();
+    assert!(conteudo.texto.contains("por 3 dias, a partir de 02/05/2031 às 14:30, pelo valor de R$ 1.234,50."));
+
+    let conteudo = renderiza_email(&EmailContratoTerminando{nomemaquina: "Trator".to_string(), datafim: data}).unwrap();
+    assert!(conteudo.texto.contains("termina em 02/05/2031 às 14:30."));
+}
+
+#[test]
+fn test_email_contrato_gerado_anexo(){
+    let mut modelo = EmailContratoGerado{
+        idcontrato: "abcdefghijklmnopqrstuvwxyz".to_string(),
+        nomemaquina: "Trator".to_string(),
+        prazolocacao: 2.0,
+        medidatempolocacao: "Semanas".to_string(),
+        valorlocacao: Dinheiro::de_reais(500),
+        pdf: Some(b"%PDF-1.4".to_vec())
+    };
+    let conteudo = renderiza_email(&modelo).unwrap();
+    assert_eq!(conteudo.anexos.len(), 1);
+    assert_eq!(conteudo.anexos[0].nome, "contrato-abcdefghijkl.pdf");
+    assert!(conteudo.texto.contains("segue em anexo"));
+
+    modelo.pdf = None;
+    let conteudo = renderiza_email(&modelo).unwrap();
+    assert!(conteudo.anexos.is_empty());
+    assert!(!conteudo.texto.contains("segue em anexo"));
+}
+
+#[tokio::test]
+async fn test_email_recuperacao_enviado(){
+    let usuario = usuario_padrao("993");
+    let email = usuario.email.clone();
+
+    let idusuario = cadastra_usuario(State(pool_testes()), Json(usuario)).await.unwrap().1.0.idusuario;
+    let codigo = envia_codigo_recuperacao(State(pool_testes()), Json(EmailInput{email: email.clone()})).await.unwrap().1.0;
+
+    let enviados: Vec<Email> = transporte_email().unwrap().enviados().into_iter()
+        .filter(|enviado| enviado.destinatario == email)
+        .collect();
+    assert!(enviados.iter().any(|enviado| enviado.assunto == "MaqExpress | Ativação de Conta"));
+    assert!(enviados.iter().any(|enviado| enviado.assunto == "MaqExpress | Recuperação de Senha"));
+
+    assert!(deleta_codigo(codigo.idcodigo).await.is_ok());
+    assert!(deleta_usuario(idusuario).await.is_ok());
+}
+
+#[tokio::test]
+async fn test_notificacao_sem_email(){
+    // O usuário não existe, então o e-mail não tem para onde ir, mas a notificação fica gravada.
+    let id = cadastra_notificacao(State(pool_testes()), Json(NotificacaoInput{
+        idusuario: "usuarioinexistente".to_string(),
+        titulo: "Título".to_string(),
+        mensagem: "Mensagem".to_string(),
+        onclick: "/".to_string(),
+        email: Some(ConteudoEmail{
+            assunto: "Assunto".to_string(),
+            html: "<p>Texto</p>".to_string(),
+            texto: "Texto".to_string(),
+            anexos: vec![]
+        })
+    })).await.unwrap().1.0;
+
+    let conn = &obtem_conn(&pool_testes()).await.unwrap();
+    executa(conn, move |conn| {
+        use diesel::{ExpressionMethods, QueryDsl, RunQueryDsl};
+        use crate::schema::notificacoes::dsl::*;
+
+        diesel::delete(notificacoes.filter(idnotificacao.eq(id)))
+            .execute(conn)
+            .map_err(ErroApi::from)
+    }).await.unwrap();
+}
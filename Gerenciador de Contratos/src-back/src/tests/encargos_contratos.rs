@@ -0,0 +1,132 @@
+use axum::{extract::{Query, State}, Json};
+use chrono::{NaiveDate, NaiveDateTime, TimeDelta};
+use diesel::{ExpressionMethods, QueryDsl, RunQueryDsl};
+
+use crate::{controllers::{encargos_contratos::{busca_encargos_idcontrato, registra_devolucao_contrato, DevolucaoContratoInput}, maquinas::{cadastra_maquina, deleta_maquina_id}, obtem_conn, usuarios::{cadastra_usuario, IdInput}}, dinheiro::{Dinheiro, Percentual}, erros::ErroApi, models::{self, contratos::{Contrato, StatusContrato}, encargos_contratos::{calcula_encargos_atraso, RegrasAtraso, TipoEncargo}, executa, precos_maquinas::MedidaTempo, usuarios::deleta_usuario}, precos::Tarifa, tests::{maquinas::{converte_tipo_maquina, maquina_padrao}, pool_testes, sessoes::usuario_autenticado, usuarios::usuario_padrao}};
+
+fn contrato_encargos(idlocador: &str, idlocatario: &str, idmaquina: &str, datacontrato: NaiveDateTime) -> Contrato{
+    Contrato{
+        idcontrato: "idcontrato991".to_string(),
+        idlocatario: idlocatario.to_string(),
+        idlocador: idlocador.to_string(),
+        idenderecolocatario: "idenderecolocatario991".to_string(),
+        idenderecolocador: "idenderecolocador991".to_string(),
+        idenderecoretirada: "idenderecolocador991".to_string(),
+        idmaquina: idmaquina.to_string(),
+        idsolicitacaocontrato: "idsolicitacao991".to_string(),
+        idcontabancarialocador: "idconta991".to_string(),
+        medidatempolocacao: MedidaTempo::Dias,
+        cidadeforo: "Cidade".to_string(),
+        statuscontrato: StatusContrato::Ativo,
+        prazolocacao: 3.0,
+        valorlocacao: Dinheiro::de_reais(900),
+        datacontrato,
+        motivoencerramento: None,
+        dataencerramento: None,
+        dataavisotermino: None,
+        datadevolucao: None,
+        dataavisoatraso: None
+    }
+}
+
+#[test]
+fn test_calcula_encargos_atraso(){
+    let fim = NaiveDate::from_ymd_opt(2032, 3, 1).unwrap().and_hms_opt(8, 0, 0).unwrap();
+    let contrato = contrato_encargos("idlocador", "idlocatario", "idmaquina", fim - TimeDelta::days(3));
+    let regras = RegrasAtraso{
+        tarifahora: Tarifa::unitaria(Dinheiro::de_reais(1)),
+        percentualmulta: Percentual::de_inteiro(2),
+        percentualjurosmensal: Percentual::de_inteiro(1)
+    };
+
+    assert!(calcula_encargos_atraso(&contrato, fim, fim, &regras, fim).unwrap().is_empty());
+    assert!(calcula_encargos_atraso(&contrato, fim, fim - TimeDelta::hours(5), &regras, fim).unwrap().is_empty());
+
+    // Um dia e um minuto de atraso contam como 25 horas e dois dias.
+    let devolucao = fim + TimeDelta::days(1) + TimeDelta::minutes(1);
+    let encargos = calcula_encargos_atraso(&contrato, fim, devolucao, &regras, devolucao).unwrap();
+    let valores: Vec<(TipoEncargo, i64)> = encargos.iter().map(|encargo| (encargo.tipo, encargo.valor.centavos())).collect();
+    assert_eq!(valores, vec![(TipoEncargo::TempoExtra, 2500), (TipoEncargo::Multa, 1800), (TipoEncargo::Juros, 60)]);
+    assert!(encargos[0].descricao.contains("25 hora(s)"));
+    assert_eq!(encargos[1].descricao, "Multa de 2% sobre o valor do contrato.");
+
+    let sem_multa = RegrasAtraso{percentualmulta: Percentual::ZERO, ..regras};
+    let encargos = calcula_encargos_atraso(&contrato, fim, devolucao, &sem_multa, devolucao).unwrap();
+    assert!(encargos.iter().all(|encargo| encargo.tipo != TipoEncargo::Multa));
+}
+
+#[tokio::test]
+async fn test_registra_devolucao_contrato(){
+    let idlocador = cadastra_usuario(State(pool_testes()), Json(usuario_padrao("990"))).await.unwrap().1.0.idusuario;
+    let idlocatario = cadastra_usuario(State(pool_testes()), Json(usuario_padrao("991"))).await.unwrap().1.0.idusuario;
+    let locador = usuario_autenticado(&idlocador);
+    let locatario = usuario_autenticado(&idlocatario);
+    let idmaquina = cadastra_maquina(State(pool_testes()), locador.clone(), Json(
+        converte_tipo_maquina(maquina_padrao("990").await, idlocador.clone()).await)).await.unwrap().1.0.idmaquina;
+
+    // Três dias de prazo, sem reserva: o prazo terminou há sete dias.
+    let datacontrato = chrono::Utc::now().naive_utc() - TimeDelta::days(10);
+    let conn = &obtem_conn(&pool_testes()).await.unwrap();
+    let idcontrato = models::contratos::cadastra_contrato(conn, contrato_encargos(&idlocador, &idlocatario, &idmaquina, datacontrato)).await.unwrap();
+    let input = |datadevolucao: NaiveDateTime| Json(DevolucaoContratoInput{
+        id: idcontrato.clone(),
+        datadevolucao: Some(datadevolucao)
+    });
+    let devolucao = datacontrato + TimeDelta::days(3) + TimeDelta::hours(2);
+
+    let res = registra_devolucao_contrato(State(pool_testes()), locatario.clone(), input(devolucao)).await;
+    assert!(matches!(res, Err(ErroApi::Proibido(_))));
+    let res = registra_devolucao_contrato(State(pool_testes()), locador.clone(), input(chrono::Utc::now().naive_utc() + TimeDelta::days(1))).await;
+    assert!(matches!(res, Err(ErroApi::Validacao{..})));
+    let res = registra_devolucao_contrato(State(pool_testes()), locador.clone(), input(datacontrato - TimeDelta::days(1))).await;
+    assert!(matches!(res, Err(ErroApi::Validacao{..})));
+
+    // Sem locadora cadastrada, valem a multa de 2% e os juros de 1% ao mês.
+    let devolvido = registra_devolucao_contrato(State(pool_testes()), locador.clone(), input(devolucao)).await.unwrap().1.0;
+    assert_eq!(devolvido.statuscontrato, StatusContrato::Concluido);
+    let valores: Vec<i64> = devolvido.encargos.iter().map(|encargo| encargo.valor.centavos()).collect();
+    assert_eq!(valores, vec![275, 1800, 30]);
+    let fatura = devolvido.fatura.unwrap();
+    assert_eq!(fatura.valor, Dinheiro::de_centavos(2105));
+    assert_eq!(fatura.numeroparcela, 1);
+
+    let res = registra_devolucao_contrato(State(pool_testes()), locador.clone(), input(devolucao)).await;
+    assert!(matches!(res, Err(ErroApi::Conflito(_))));
+
+    let encargos = busca_encargos_idcontrato(State(pool_testes()), locatario.clone(), Query(IdInput{id: idcontrato.clone()})).await.unwrap().1.0;
+    assert_eq!(encargos.len(), 3);
+    let res = busca_encargos_idcontrato(State(pool_testes()), usuario_autenticado("idteste992"), Query(IdInput{id: idcontrato.clone()})).await;
+    assert!(matches!(res, Err(ErroApi::Proibido(_))));
+
+    // Devolvida antes do fim, a máquina não gera encargos, e o contrato só é concluído quando o
+    // período terminar.
+    let antecipado = Contrato{
+        idcontrato: "idcontrato993".to_string(),
+        idsolicitacaocontrato: "idsolicitacao993".to_string(),
+        ..contrato_encargos(&idlocador, &idlocatario, &idmaquina, chrono::Utc::now().naive_utc() - TimeDelta::days(1))
+    };
+    let idantecipado = models::contratos::cadastra_contrato(conn, antecipado).await.unwrap();
+    let devolvido = registra_devolucao_contrato(State(pool_testes()), locador.clone(), Json(DevolucaoContratoInput{
+        id: idantecipado.clone(),
+        datadevolucao: None
+    })).await.unwrap().1.0;
+    assert_eq!(devolvido.statuscontrato, StatusContrato::Ativo);
+    assert!(devolvido.encargos.is_empty() && devolvido.fatura.is_none());
+    let res = models::contratos::encerra_periodo_contrato(conn, idantecipado.clone()).await;
+    assert!(matches!(res, Err(ErroApi::Conflito(_))));
+
+    let ids = [idlocador.clone(), idlocatario.clone()];
+    executa(conn, move |conn| {
+        use crate::schema::{contratos, notificacoes};
+
+        diesel::delete(contratos::table.filter(contratos::idcontrato.eq_any([idcontrato, idantecipado])))
+            .execute(conn)
+            .map_err(ErroApi::from)?;
+        diesel::delete(notificacoes::table.filter(notificacoes::idusuario.eq_any(ids)))
+            .execute(conn)
+            .map_err(ErroApi::from)
+    }).await.unwrap();
+    assert!(deleta_usuario(idlocador).await.is_ok());
+    assert!(deleta_usuario(idlocatario).await.is_ok());
+    assert!(deleta_maquina_id(idmaquina).await.is_ok());
+}
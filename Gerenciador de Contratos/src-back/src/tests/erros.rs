@@ -0,0 +1,72 @@
+use axum::{extract::{Query, State}, http::StatusCode, response::IntoResponse};
+use diesel::result::{DatabaseErrorInformation, DatabaseErrorKind, Error as DieselError};
+
+use crate::{controllers::{maquinas::busca_maquina_id, usuarios::IdInput}, erros::ErroApi, tests::pool_testes};
+
+struct InfoErroTeste;
+
+impl DatabaseErrorInformation for InfoErroTeste{
+    fn message(&self) -> &str { "duplicate key value violates unique constraint" }
+    fn details(&self) -> Option<&str> { None }
+    fn hint(&self) -> Option<&str> { None }
+    fn table_name(&self) -> Option<&str> { Some("usuarios") }
+    fn column_name(&self) -> Option<&str> { Some("email") }
+    fn constraint_name(&self) -> Option<&str> { None }
+    fn statement_position(&self) -> Option<i32> { None }
+}
+
+#[test]
+fn test_status_erros(){
+    assert_eq!(ErroApi::NaoEncontrado("".to_string()).status(), StatusCode::NOT_FOUND);
+    assert_eq!(ErroApi::validacao("").status(), StatusCode::BAD_REQUEST);
+    assert_eq!(ErroApi::Conflito("".to_string()).status(), StatusCode::CONFLICT);
+    assert_eq!(ErroApi::NaoAutorizado("".to_string()).status(), StatusCode::UNAUTHORIZED);
+    assert_eq!(ErroApi::ServicoExterno("".to_string()).status(), StatusCode::BAD_GATEWAY);
+    assert_eq!(ErroApi::ContaNaoAtivada("".to_string()).status(), StatusCode::FORBIDDEN);
+    assert_eq!(ErroApi::ContaSuspensa("".to_string()).codigo(), "conta_suspensa");
+    assert_eq!(ErroApi::VinculoPendente("".to_string()).status(), StatusCode::CONFLICT);
+    assert_eq!(ErroApi::VinculoPendente("".to_string()).codigo(), "vinculo_pendente");
+
+    let resposta = ErroApi::Proibido("".to_string()).into_response();
+    assert_eq!(resposta.status(), StatusCode::FORBIDDEN);
+}
+
+#[test]
+fn test_corpo_erro(){
+    let corpo = ErroApi::validacao_campo("senha", "A senha é muito curta.").corpo();
+    assert_eq!(corpo.code, "validacao");
+    assert_eq!(corpo.message, "A senha é muito curta.");
+    assert_eq!(corpo.field, Some("senha".to_string()));
+
+    let corpo = ErroApi::Conflito("E-mail em uso.".to_string()).corpo();
+    assert_eq!(corpo.code, "conflito");
+    assert_eq!(corpo.field, None);
+}
+
+#[test]
+fn test_converte_erro_diesel(){
+    let erro = ErroApi::from(DieselError::NotFound);
+    assert!(matches!(erro, ErroApi::NaoEncontrado(_)));
+
+    let erro = ErroApi::from(DieselError::DatabaseError(DatabaseErrorKind::UniqueViolation, Box::new(InfoErroTeste)));
+    assert!(matches!(erro, ErroApi::Conflito(_)));
+    assert_eq!(erro.corpo().message, "Já existe um registro com esses dados.");
+
+    let erro = ErroApi::from(DieselError::DatabaseError(DatabaseErrorKind::NotNullViolation, Box::new(InfoErroTeste)));
+    assert_eq!(erro.corpo().field, Some("email".to_string()));
+    assert_eq!(erro.corpo().message, "Um campo obrigatório não foi informado.");
+
+    let erro = ErroApi::from(DieselError::DatabaseError(DatabaseErrorKind::CheckViolation, Box::new(InfoErroTeste)));
+    assert!(matches!(erro, ErroApi::Validacao{..}));
+    assert!(!erro.corpo().message.contains("constraint"));
+
+    let erro = ErroApi::from(DieselError::RollbackTransaction);
+    assert_eq!(erro.status(), StatusCode::INTERNAL_SERVER_ERROR);
+}
+
+#[tokio::test]
+async fn test_busca_maquina_id_nao_encontrada(){
+    let erro = busca_maquina_id(State(pool_testes()), Query(IdInput{id: "idinexistente900".to_string()})).await.unwrap_err();
+
+    assert_eq!(erro.status(), StatusCode::NOT_FOUND);
+}
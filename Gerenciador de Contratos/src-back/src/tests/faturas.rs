@@ -0,0 +1,197 @@
+use std::str::FromStr;
+
+use axum::{extract::{Query, State}, Json};
+use chrono::{NaiveDate, NaiveDateTime, TimeDelta};
+use diesel::{ExpressionMethods, QueryDsl, RunQueryDsl};
+
+use crate::{controllers::{contratos::renderiza_modelo, faturas::{busca_faturas_idlocatario, busca_recibo_fatura, calcula_parcelas, gera_faturas_contrato, modelo_recibo, registra_pagamento_fatura, vence_faturas, FaturasContratoInput, FaturasUsuarioInput, PagamentoFaturaInput, ParcelaFatura, PlanoFaturamento, ReciboPDF, VERSAO_MODELO_RECIBO}, maquinas::{cadastra_maquina, deleta_maquina_id}, obtem_conn, usuarios::{cadastra_usuario, IdInput}}, dinheiro::Dinheiro, erros::ErroApi, models::{self, contratos::{Contrato, StatusContrato}, executa, faturas::{StatusFatura, DIAS_VENCIMENTO_FATURA}, precos_maquinas::MedidaTempo, usuarios::deleta_usuario}, tests::{maquinas::{converte_tipo_maquina, maquina_padrao}, pool_testes, sessoes::usuario_autenticado, usuarios::usuario_padrao}};
+
+fn data(mes: u32, dia: u32) -> NaiveDateTime{
+    NaiveDate::from_ymd_opt(2032, mes, dia).unwrap().and_hms_opt(8, 0, 0).unwrap()
+}
+
+#[test]
+fn test_calcula_parcelas(){
+    let prazo = TimeDelta::days(DIAS_VENCIMENTO_FATURA);
+
+    let avista = calcula_parcelas(PlanoFaturamento::AVista, Dinheiro::de_reais(1500), data(1, 31), data(4, 30), &[]).unwrap();
+    assert_eq!(avista, vec![ParcelaFatura{valor: Dinheiro::de_reais(1500), datavencimento: data(1, 31) + prazo}]);
+
+    // De 31/01 a 30/04 são três meses, e o fim de fevereiro fica no último dia do mês.
+    let mensal = calcula_parcelas(PlanoFaturamento::Mensal, Dinheiro::de_reais(1000), data(1, 31), data(4, 30), &[]).unwrap();
+    assert_eq!(mensal.len(), 3);
+    assert_eq!(mensal.iter().map(|parcela| parcela.valor.centavos()).collect::<Vec<_>>(), vec![33334, 33333, 33333]);
+    assert_eq!(mensal[1].datavencimento, data(2, 29) + prazo);
+
+    // Contratos mais curtos que um mês têm uma parcela só.
+    let curto = calcula_parcelas(PlanoFaturamento::Mensal, Dinheiro::de_reais(80), data(3, 1), data(3, 6), &[]).unwrap();
+    assert_eq!(curto.len(), 1);
+    let longo = calcula_parcelas(PlanoFaturamento::Mensal, Dinheiro::de_reais(80), data(1, 1), data(1, 1) + TimeDelta::days(365 * 11), &[]);
+    assert!(matches!(longo, Err(ErroApi::Validacao{..})));
+
+    let parcelas = [
+        ParcelaFatura{valor: Dinheiro::de_reais(700), datavencimento: data(5, 1)},
+        ParcelaFatura{valor: Dinheiro::de_reais(300), datavencimento: data(3, 1)}
+    ];
+    let personalizado = calcula_parcelas(PlanoFaturamento::Personalizado, Dinheiro::de_reais(1000), data(1, 1), data(6, 1), &parcelas).unwrap();
+    assert_eq!(personalizado[0].datavencimento, data(3, 1));
+    assert_eq!(personalizado[1].valor, Dinheiro::de_reais(700));
+
+    let res = calcula_parcelas(PlanoFaturamento::Personalizado, Dinheiro::de_centavos(99999), data(1, 1), data(6, 1), &parcelas);
+    assert!(matches!(res, Err(ErroApi::Validacao{..})));
+    let res = calcula_parcelas(PlanoFaturamento::Personalizado, Dinheiro::de_reais(1000), data(1, 1), data(6, 1), &[]);
+    assert!(matches!(res, Err(ErroApi::Validacao{..})));
+}
+
+#[test]
+fn test_renderiza_recibo(){
+    let recibo = ReciboPDF{
+        idfatura: "idfatura980".to_string(),
+        idcontrato: "idcontrato980".to_string(),
+        numeroparcela: 2,
+        nomelocatario: "Locatário Teste".to_string(),
+        documentolocatario: "113.144.189-10".to_string(),
+        tipodocumentolocatario: "CPF".to_string(),
+        nomelocador: "Locador Teste".to_string(),
+        documentolocador: "12.345.678/0001-90".to_string(),
+        tipodocumentolocador: "CNPJ".to_string(),
+        nomemaquina: "Retroescavadeira".to_string(),
+        valorpago: Dinheiro::de_centavos(123450),
+        datavencimento: data(3, 6),
+        datapagamento: data(3, 4),
+        cidadeforo: "Patos de Minas".to_string()
+    };
+    let modelo = modelo_recibo(VERSAO_MODELO_RECIBO).unwrap();
+    let blocos = renderiza_modelo(modelo, &recibo.campos()).unwrap();
+    let texto = format!("{:?}", blocos);
+    assert!(texto.contains("R$ 1.234,50"));
+    assert!(texto.contains("parcela 2 do contrato de locação nº idcontrato980"));
+    assert!(texto.contains("Patos de Minas, 04/03/2032."));
+}
+
+fn contrato_faturas(idlocador: &str, idlocatario: &str, idmaquina: &str) -> Contrato{
+    Contrato{
+        idcontrato: "idcontrato981".to_string(),
+        idlocatario: idlocatario.to_string(),
+        idlocador: idlocador.to_string(),
+        idenderecolocatario: "idenderecolocatario981".to_string(),
+        idenderecolocador: "idenderecolocador981".to_string(),
+        idenderecoretirada: "idenderecolocador981".to_string(),
+        idmaquina: idmaquina.to_string(),
+        idsolicitacaocontrato: "idsolicitacao981".to_string(),
+        idcontabancarialocador: "idconta981".to_string(),
+        medidatempolocacao: MedidaTempo::Meses,
+        cidadeforo: "Cidade".to_string(),
+        statuscontrato: StatusContrato::Ativo,
+        prazolocacao: 3.0,
+        valorlocacao: Dinheiro::de_reais(900),
+        datacontrato: chrono::Utc::now().naive_utc() - TimeDelta::days(20),
+        motivoencerramento: None,
+        dataencerramento: None,
+        dataavisotermino: None,
+        datadevolucao: None,
+        dataavisoatraso: None
+    }
+}
+
+#[tokio::test]
+async fn test_faturas_contrato(){
+    let idlocador = cadastra_usuario(State(pool_testes()), Json(usuario_padrao("980"))).await.unwrap().1.0.idusuario;
+    let idlocatario = cadastra_usuario(State(pool_testes()), Json(usuario_padrao("981"))).await.unwrap().1.0.idusuario;
+    let locador = usuario_autenticado(&idlocador);
+    let locatario = usuario_autenticado(&idlocatario);
+    let idmaquina = cadastra_maquina(State(pool_testes()), locador.clone(), Json(
+        converte_tipo_maquina(maquina_padrao("980").await, idlocador.clone()).await)).await.unwrap().1.0.idmaquina;
+
+    let conn = &obtem_conn(&pool_testes()).await.unwrap();
+    let idcontrato = models::contratos::cadastra_contrato(conn, contrato_faturas(&idlocador, &idlocatario, &idmaquina)).await.unwrap();
+    let input = |plano: PlanoFaturamento| Json(FaturasContratoInput{
+        idcontrato: idcontrato.clone(),
+        plano,
+        parcelas: vec![]
+    });
+
+    let res = gera_faturas_contrato(State(pool_testes()), locatario.clone(), input(PlanoFaturamento::Mensal)).await;
+    assert!(matches!(res, Err(ErroApi::Proibido(_))));
+    let (status, faturas) = gera_faturas_contrato(State(pool_testes()), locador.clone(), input(PlanoFaturamento::Mensal)).await.unwrap();
+    assert_eq!(status.as_u16(), 201);
+    assert_eq!(faturas.0.len(), 3);
+    assert!(faturas.0.iter().all(|fatura| fatura.valor == Dinheiro::de_reais(300) && fatura.status == StatusFatura::Pendente));
+    let res = gera_faturas_contrato(State(pool_testes()), locador.clone(), input(PlanoFaturamento::AVista)).await;
+    assert!(matches!(res, Err(ErroApi::Conflito(_))));
+
+    // O contrato começou há 20 dias, então só a primeira parcela já venceu.
+    assert!(vence_faturas(&pool_testes()).await.unwrap() >= 1);
+    let vencidas = busca_faturas_idlocatario(State(pool_testes()), locatario.clone(), Query(FaturasUsuarioInput{
+        id: idlocatario.clone(),
+        status: Some("Vencida".to_string())
+    })).await.unwrap().1.0;
+    assert_eq!(vencidas.len(), 1);
+    assert_eq!(vencidas[0].numeroparcela, 1);
+
+    let pagamento = |valor: Dinheiro| Json(PagamentoFaturaInput{
+        idfatura: vencidas[0].idfatura.clone(),
+        valor
+    });
+    let res = registra_pagamento_fatura(State(pool_testes()), locatario.clone(), pagamento(Dinheiro::de_reais(300))).await;
+    assert!(matches!(res, Err(ErroApi::Proibido(_))));
+    let parcial = registra_pagamento_fatura(State(pool_testes()), locador.clone(), pagamento(Dinheiro::de_reais(100))).await.unwrap().1.0;
+    assert_eq!(parcial.valorpago, Dinheiro::de_reais(100));
+    assert_eq!(parcial.status, StatusFatura::Vencida);
+    let res = busca_recibo_fatura(State(pool_testes()), locatario.clone(), Query(IdInput{id: parcial.idfatura.clone()})).await;
+    assert!(matches!(res, Err(ErroApi::Conflito(_))));
+    let res = registra_pagamento_fatura(State(pool_testes()), locador.clone(), pagamento(Dinheiro::de_centavos(20001))).await;
+    assert!(matches!(res, Err(ErroApi::Validacao{..})));
+    let paga = registra_pagamento_fatura(State(pool_testes()), locador.clone(), pagamento(Dinheiro::de_reais(200))).await.unwrap().1.0;
+    assert_eq!(paga.status, StatusFatura::Paga);
+    assert!(paga.datapagamento.is_some());
+    let res = registra_pagamento_fatura(State(pool_testes()), locador.clone(), pagamento(Dinheiro::de_reais(1))).await;
+    assert!(matches!(res, Err(ErroApi::Conflito(_))));
+
+    let recibo = busca_recibo_fatura(State(pool_testes()), locatario.clone(), Query(IdInput{id: paga.idfatura.clone()})).await.unwrap();
+    assert_eq!(recibo.status().as_u16(), 200);
+    let res = busca_recibo_fatura(State(pool_testes()), usuario_autenticado("idteste982"), Query(IdInput{id: paga.idfatura.clone()})).await;
+    assert!(matches!(res, Err(ErroApi::Proibido(_))));
+
+    // Depois de uma renovação, só o valor adicional é faturado.
+    models::contratos::renova_contrato(conn, idcontrato.clone(), 1.0, Dinheiro::de_reais(300)).await.unwrap();
+    let adicionais = gera_faturas_contrato(State(pool_testes()), locador.clone(), input(PlanoFaturamento::AVista)).await.unwrap().1.0;
+    assert_eq!(adicionais.len(), 1);
+    assert_eq!(adicionais[0].numeroparcela, 4);
+    assert_eq!(adicionais[0].valor, Dinheiro::de_reais(300));
+
+    assert!(StatusFatura::from_str("Atrasada").is_err());
+
+    // Um contrato que terminou sem faturas ainda pode ser faturado, a partir do início da locação.
+    let concluido = Contrato{
+        idcontrato: "idcontrato982".to_string(),
+        idsolicitacaocontrato: "idsolicitacao982".to_string(),
+        statuscontrato: StatusContrato::Concluido,
+        ..contrato_faturas(&idlocador, &idlocatario, &idmaquina)
+    };
+    let idconcluido = models::contratos::cadastra_contrato(conn, concluido).await.unwrap();
+    let datacontrato = models::contratos::busca_contrato_id(conn, idconcluido.clone()).await.unwrap().datacontrato;
+    let faturas = gera_faturas_contrato(State(pool_testes()), locador.clone(), Json(FaturasContratoInput{
+        idcontrato: idconcluido.clone(),
+        plano: PlanoFaturamento::AVista,
+        parcelas: vec![]
+    })).await.unwrap().1.0;
+    assert_eq!(faturas.len(), 1);
+    assert_eq!(faturas[0].valor, Dinheiro::de_reais(900));
+    assert_eq!(faturas[0].datavencimento, datacontrato + TimeDelta::days(DIAS_VENCIMENTO_FATURA));
+
+    let ids = [idlocador.clone(), idlocatario.clone()];
+    executa(conn, move |conn| {
+        use crate::schema::{contratos, notificacoes};
+
+        diesel::delete(contratos::table.filter(contratos::idcontrato.eq_any([idcontrato, idconcluido])))
+            .execute(conn)
+            .map_err(ErroApi::from)?;
+        diesel::delete(notificacoes::table.filter(notificacoes::idusuario.eq_any(ids)))
+            .execute(conn)
+            .map_err(ErroApi::from)
+    }).await.unwrap();
+    assert!(deleta_usuario(idlocador).await.is_ok());
+    assert!(deleta_usuario(idlocatario).await.is_ok());
+    assert!(deleta_maquina_id(idmaquina).await.is_ok());
+}
@@ -0,0 +1,149 @@
+use std::collections::HashMap;
+
+use axum::{body::{to_bytes, Body}, extract::{Query, State}, http::{header::CONTENT_TYPE, Request, StatusCode}, middleware, routing::post, Json, Router};
+use diesel::{ExpressionMethods, QueryDsl, RunQueryDsl};
+use tower::ServiceExt;
+use uuid::Uuid;
+
+use crate::{controllers::{gera_hash, obtem_conn, sessoes::{gera_tokens_sessao, renova_sessao, valida_token, RenovaSessaoInput}, usuarios::{cadastra_usuario, IdInput}}, ids::{e_id_legado, gera_id, gera_id_publico, gera_id_publico_unico, ids_legados_json, ids_legados_query, normaliza_id_publico, resolve_ids_legados, troca_ids_json, TAMANHO_ID_PUBLICO}, models::{executa, usuarios::deleta_usuario}, tests::{pool_testes, usuarios::{ativa_usuario_teste, usuario_padrao}}};
+
+async fn registra_id_legado(legado: &str, novo: &str){
+    let conn = &obtem_conn(&pool_testes()).await.unwrap();
+    let (legado, novo) = (legado.to_string(), novo.to_string());
+    executa(conn, move |conn| {
+        use crate::schema::ids_legados::dsl::*;
+
+        diesel::insert_into(ids_legados)
+            .values((idlegado.eq(legado), idnovo.eq(novo), tabela.eq("usuarios")))
+            .execute(conn)
+            .unwrap();
+        return Ok(())
+    }).await.unwrap();
+}
+
+async fn remove_id_legado(legado: &str){
+    let conn = &obtem_conn(&pool_testes()).await.unwrap();
+    let legado = legado.to_string();
+    executa(conn, move |conn| {
+        use crate::schema::ids_legados::dsl::*;
+
+        diesel::delete(ids_legados.filter(idlegado.eq(legado))).execute(conn).unwrap();
+        return Ok(())
+    }).await.unwrap();
+}
+
+#[test]
+fn test_gera_id(){
+    let primeiro = gera_id();
+    let segundo = gera_id();
+
+    let uuid = Uuid::parse_str(&primeiro).unwrap();
+    assert_eq!(uuid.get_version_num(), 7);
+    assert_eq!(primeiro.len(), 36);
+    // UUIDv7 cresce com o tempo.
+    assert!(primeiro < segundo);
+}
+
+#[test]
+fn test_gera_id_publico(){
+    let id = gera_id_publico();
+    assert_eq!(id.len(), TAMANHO_ID_PUBLICO);
+    assert!(id.chars().all(|c| c.is_ascii_digit() || (c.is_ascii_uppercase() && !"ILOU".contains(c))));
+    assert_eq!(normaliza_id_publico(&format!(" {} ", id.to_lowercase())), id);
+    assert_eq!(normaliza_id_publico("abio-l"), "AB10-1");
+    assert_eq!(normaliza_id_publico("123456"), "123456");
+
+    // Os IDs já em uso são sorteados de novo.
+    let mut usados = Vec::new();
+    let id = gera_id_publico_unico(|id| {
+        usados.push(id.to_string());
+        return Ok(usados.len() < 3)
+    }).unwrap();
+    assert_eq!(usados.len(), 3);
+    assert_eq!(usados[2], id);
+
+    assert!(gera_id_publico_unico(|_| Ok(true)).is_err());
+}
+
+#[test]
+fn test_e_id_legado(){
+    assert!(e_id_legado(&gera_hash("idteste980")));
+    assert!(!e_id_legado(&gera_id()));
+    assert!(!e_id_legado("123456"));
+    assert!(!e_id_legado("$2b$12$curto"));
+}
+
+#[test]
+fn test_troca_ids_legados(){
+    let legado = gera_hash("idteste981");
+    let novo = gera_id();
+    let codificado = legado.replace('$', "%24").replace('/', "%2F");
+
+    let encontrados = ids_legados_query(&format!("pagina=1&id={}", codificado));
+    assert_eq!(encontrados, vec![(codificado, legado.clone())]);
+    assert!(ids_legados_query("id=123456&texto=%242b").is_empty());
+
+    let corpo = format!(r#"{{"idusuario": "{}", "ids": ["{}", "outro"]}}"#, legado, legado);
+    assert_eq!(ids_legados_json(&corpo), vec![legado.clone(), legado.clone()]);
+
+    let ids = HashMap::from([(legado.clone(), novo.clone())]);
+    assert_eq!(troca_ids_json(&corpo, &ids), format!(r#"{{"idusuario": "{}", "ids": ["{}", "outro"]}}"#, novo, novo));
+}
+
+#[tokio::test]
+async fn test_resolve_ids_legados(){
+    let legado = gera_hash("idteste982");
+    let novo = gera_id();
+    registra_id_legado(&legado, &novo).await;
+
+    let app = Router::new()
+        .route("/eco", post(|Query(params): Query<IdInput>, corpo: String| async move { format!("{}|{}", params.id, corpo) }))
+        .layer(middleware::from_fn_with_state(pool_testes(), resolve_ids_legados));
+    let requisicao = |id: &str| Request::post(format!("/eco?id={}", id.replace('$', "%24").replace('/', "%2F")))
+        .header(CONTENT_TYPE, "application/json")
+        .body(Body::from(format!(r#"{{"idusuario":"{}"}}"#, id)))
+        .unwrap();
+
+    let resposta = app.clone().oneshot(requisicao(&legado)).await.unwrap();
+    assert_eq!(resposta.status(), StatusCode::OK);
+    let corpo = to_bytes(resposta.into_body(), usize::MAX).await.unwrap();
+    assert_eq!(String::from_utf8(corpo.to_vec()).unwrap(), format!(r#"{}|{{"idusuario":"{}"}}"#, novo, novo));
+
+    // IDs antigos sem correspondência seguem como vieram.
+    let desconhecido = gera_hash("idteste983");
+    let resposta = app.oneshot(requisicao(&desconhecido)).await.unwrap();
+    let corpo = to_bytes(resposta.into_body(), usize::MAX).await.unwrap();
+    assert_eq!(String::from_utf8(corpo.to_vec()).unwrap(), format!(r#"{}|{{"idusuario":"{}"}}"#, desconhecido, desconhecido));
+
+    remove_id_legado(&legado).await;
+}
+
+#[tokio::test]
+async fn test_cadastra_usuario_ids_novos(){
+    let usuario = usuario_padrao("984");
+    let id = cadastra_usuario(State(pool_testes()), Json(usuario)).await.unwrap().1.0.idusuario;
+    assert_eq!(Uuid::parse_str(&id).unwrap().get_version_num(), 7);
+
+    let conn = &obtem_conn(&pool_testes()).await.unwrap();
+    let idbusca = id.clone();
+    let publico = executa(conn, move |conn| {
+        use crate::schema::usuarios::dsl::*;
+
+        return Ok(usuarios.filter(idusuario.eq(idbusca)).select(idpublico).get_result::<String>(conn).unwrap())
+    }).await.unwrap();
+    assert_eq!(publico.len(), TAMANHO_ID_PUBLICO);
+
+    // A sessão criada com o ID antigo é renovada com o novo.
+    let legado = gera_hash("idteste984");
+    registra_id_legado(&legado, &id).await;
+    ativa_usuario_teste(&id).await;
+    let tokens = gera_tokens_sessao(&legado).unwrap();
+    let renovados = renova_sessao(State(pool_testes()), Json(RenovaSessaoInput{
+        token_renovacao: tokens.token_renovacao
+    })).await.unwrap().1;
+    assert_eq!(renovados.0.idusuario, id);
+    assert_eq!(valida_token(&renovados.0.token_acesso, "acesso").unwrap().sub, id);
+
+    remove_id_legado(&legado).await;
+    assert!(deleta_usuario(id).await.is_ok());
+}
@@ -0,0 +1,131 @@
+use std::{collections::HashMap, time::{Duration, Instant}};
+
+use axum::{body::{to_bytes, Body}, http::{header::{CONTENT_TYPE, RETRY_AFTER}, Request, StatusCode}, routing::post, Router};
+use tower::ServiceExt;
+
+use crate::{erros::{CorpoErro, ErroApi}, limites::{CamadaLimite, ConfiguracaoLimites, Limitador}};
+
+fn configuracao_teste() -> ConfiguracaoLimites{
+    ConfiguracaoLimites{
+        requisicoes_ip: 3,
+        janela_ip: Duration::from_secs(60),
+        requisicoes_conta: 2,
+        janela_conta: Duration::from_secs(30),
+        falhas_atraso: 2,
+        atraso_inicial: Duration::from_secs(1),
+        falhas_bloqueio: 4,
+        duracao_bloqueio: Duration::from_secs(600),
+        confia_proxy: false
+    }
+}
+
+fn segundos(erro: ErroApi) -> u64{
+    match erro{
+        ErroApi::LimiteExcedido{segundos, ..} => segundos,
+        outro => panic!("Esperava LimiteExcedido, veio {:?}", outro)
+    }
+}
+
+#[test]
+fn test_limite_por_ip(){
+    let limitador = Limitador::memoria(configuracao_teste());
+    let agora = Instant::now();
+
+    for _ in 0..3{
+        assert!(limitador.verifica("realiza_login", "10.0.0.1", None, agora).is_ok());
+    }
+    assert_eq!(segundos(limitador.verifica("realiza_login", "10.0.0.1", None, agora).unwrap_err()), 60);
+
+    // Outros IPs e outras rotas têm contadores próprios.
+    assert!(limitador.verifica("realiza_login", "10.0.0.2", None, agora).is_ok());
+    assert!(limitador.verifica("cadastra_usuario", "10.0.0.1", None, agora).is_ok());
+
+    let depois = agora + Duration::from_secs(60);
+    assert!(limitador.verifica("realiza_login", "10.0.0.1", None, depois).is_ok());
+}
+
+#[test]
+fn test_limite_por_conta(){
+    let limitador = Limitador::memoria(configuracao_teste());
+    let agora = Instant::now();
+    let conta = Some("testeunit@gmail.com");
+
+    assert!(limitador.verifica("envia_codigo_recuperacao", "10.0.0.1", conta, agora).is_ok());
+    assert!(limitador.verifica("envia_codigo_recuperacao", "10.0.0.2", conta, agora).is_ok());
+    let erro = limitador.verifica("envia_codigo_recuperacao", "10.0.0.3", conta, agora + Duration::from_secs(10)).unwrap_err();
+    assert_eq!(segundos(erro), 20);
+}
+
+#[test]
+fn test_espera_progressiva_e_bloqueio(){
+    let limitador = Limitador::memoria(configuracao_teste());
+    let inicio = Instant::now();
+    let conta = Some("testeunit@gmail.com");
+    let verifica = |ip: &str, agora: Instant| limitador.verifica("realiza_login", ip, conta, agora);
+
+    limitador.registra_falha("realiza_login", "10.0.0.1", conta, inicio);
+    assert!(verifica("10.0.0.1", inicio).is_ok());
+
+    // A partir da segunda falha, a espera começa em 1 segundo e dobra a cada erro, para a conta em qualquer IP.
+    limitador.registra_falha("realiza_login", "10.0.0.1", conta, inicio);
+    assert_eq!(segundos(verifica("10.0.0.9", inicio).unwrap_err()), 1);
+    let agora = inicio + Duration::from_secs(1);
+    assert!(verifica("10.0.0.9", agora).is_ok());
+
+    limitador.registra_falha("realiza_login", "10.0.0.9", conta, agora);
+    assert_eq!(segundos(verifica("10.0.0.9", agora).unwrap_err()), 2);
+
+    let agora = agora + Duration::from_secs(2);
+    limitador.registra_falha("realiza_login", "10.0.0.9", conta, agora);
+    let erro = verifica("10.0.0.9", agora + Duration::from_secs(300)).unwrap_err();
+    assert!(erro.to_string().contains("5 minutos"));
+    assert_eq!(segundos(erro), 300);
+
+    let agora = agora + Duration::from_secs(600);
+    assert!(verifica("10.0.0.9", agora).is_ok());
+
+    // O acerto zera as falhas da conta.
+    limitador.registra_falha("realiza_login", "10.0.0.9", conta, agora);
+    limitador.registra_sucesso("realiza_login", conta);
+    assert!(verifica("10.0.0.9", agora).is_ok());
+}
+
+#[test]
+fn test_configuracao_limites_variaveis(){
+    let variaveis: HashMap<&str, &str> = HashMap::from([
+        ("LIMITE_IP_REQUISICOES", "50"),
+        ("LIMITE_CONTA_JANELA", "120"),
+        ("LIMITE_CONFIA_PROXY", "true"),
+    ]);
+    let configuracao = ConfiguracaoLimites::de_variaveis(|nome| variaveis.get(nome).map(|v| v.to_string())).unwrap();
+    assert_eq!(configuracao.requisicoes_ip, 50);
+    assert_eq!(configuracao.janela_conta, Duration::from_secs(120));
+    assert!(configuracao.confia_proxy);
+    assert_eq!(configuracao.falhas_bloqueio, ConfiguracaoLimites::default().falhas_bloqueio);
+
+    assert!(ConfiguracaoLimites::de_variaveis(|nome| (nome == "LIMITE_IP_JANELA").then(|| "0".to_string())).is_err());
+    assert!(ConfiguracaoLimites::de_variaveis(|nome| (nome == "LIMITE_CONFIA_PROXY").then(|| "sim".to_string())).is_err());
+}
+
+#[tokio::test]
+async fn test_camada_limite(){
+    let limitador = Limitador::memoria(configuracao_teste());
+    let app = Router::new()
+        .route("/realiza_login", post(|| async { StatusCode::UNAUTHORIZED }).layer(CamadaLimite::new(limitador, "realiza_login").com_falhas()));
+    let requisicao = || Request::post("/realiza_login")
+        .header(CONTENT_TYPE, "application/json")
+        .body(Body::from(r#"{"email": "testeunit@gmail.com", "senha": "errada"}"#))
+        .unwrap();
+
+    for _ in 0..2{
+        let resposta = app.clone().oneshot(requisicao()).await.unwrap();
+        assert_eq!(resposta.status(), StatusCode::UNAUTHORIZED);
+    }
+
+    let resposta = app.clone().oneshot(requisicao()).await.unwrap();
+    assert_eq!(resposta.status(), StatusCode::TOO_MANY_REQUESTS);
+    assert_eq!(resposta.headers().get(RETRY_AFTER).unwrap(), "1");
+    let corpo = to_bytes(resposta.into_body(), usize::MAX).await.unwrap();
+    let corpo: CorpoErro = axum::Json::<CorpoErro>::from_bytes(&corpo).unwrap().0;
+    assert_eq!(corpo.code, "limite_excedido");
+}
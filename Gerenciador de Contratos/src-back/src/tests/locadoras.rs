@@ -1,6 +1,6 @@
-use axum::{extract::Query, Json};
+use axum::{extract::{Query, State}, Json};
 
-use crate::{controllers::{contas_bancarias::cadastra_conta_bancaria, enderecos::cadastra_endereco_usuario, locadoras::{busca_locadora_idusuario, cadastra_locadora, LocadoraInput}, usuarios::{cadastra_usuario, deleta_usuario, IdInput}}, models::{contas_bancarias::deleta_conta_bancaria, enderecos::deleta_endereco, locadoras::deleta_locadora}, tests::{contas_bancarias::conta_bancaria_padrao, enderecos_usuarios::endereco_usuario_padrao, usuarios::usuario_padrao}};
+use crate::{controllers::{contas_bancarias::cadastra_conta_bancaria, enderecos::cadastra_endereco_usuario, locadoras::{atualiza_regras_atraso_locadora, busca_locadora_idusuario, cadastra_locadora, LocadoraInput, RegrasAtrasoInput}, papeis_usuarios::busca_papeis_usuario, usuarios::{cadastra_usuario, deleta_usuario, IdInput}}, models::{contas_bancarias::deleta_conta_bancaria, enderecos::deleta_endereco, locadoras::deleta_locadora, papeis_usuarios::Papel}, dinheiro::Percentual, erros::ErroApi, tests::{pool_testes, sessoes::{admin_autenticado, usuario_autenticado}, contas_bancarias::conta_bancaria_padrao, enderecos_usuarios::endereco_usuario_padrao, usuarios::usuario_padrao}};
 
 pub fn locadora_padrao(idusuario: String, idendereco: String, idconta: String) -> LocadoraInput{
     LocadoraInput{
@@ -14,27 +14,43 @@ pub fn locadora_padrao(idusuario: String, idendereco: String, idconta: String) -
 async fn test_cadastra_locadora_ok(){
     let usuario = usuario_padrao("700");
 
-    let usuario = cadastra_usuario(Json(usuario)).await.unwrap().1;
+    let usuario = cadastra_usuario(State(pool_testes()), Json(usuario)).await.unwrap().1;
     let idusuario = usuario.0.idusuario.to_string();
 
     let endereco = endereco_usuario_padrao(&idusuario, "700").await;
-    let idendereco = cadastra_endereco_usuario(Json(endereco)).await.unwrap().1.0.idendereco;
+    let idendereco = cadastra_endereco_usuario(State(pool_testes()), usuario_autenticado(&idusuario), Json(endereco)).await.unwrap().1.0.idendereco;
     
     let conta = conta_bancaria_padrao(&idusuario, "700");
-    let idconta = cadastra_conta_bancaria(Json(conta)).await.unwrap().1.0;
+    let idconta = cadastra_conta_bancaria(State(pool_testes()), usuario_autenticado(&idusuario), Json(conta)).await.unwrap().1.0;
 
     let locadora = locadora_padrao(idusuario.clone(), idendereco.clone(), idconta.clone());
-    let idlocadora = cadastra_locadora(Json(locadora)).await.unwrap().1.0;
+    let idlocadora = cadastra_locadora(State(pool_testes()), usuario_autenticado(&idusuario), Json(locadora)).await.unwrap().1.0;
+
+    // Quem cadastra uma locadora passa a ter o papel de locador.
+    let papeis = busca_papeis_usuario(State(pool_testes()), usuario_autenticado(&idusuario), Query(IdInput{id: idusuario.clone()})).await.unwrap().1.0;
+    assert_eq!(papeis, vec![Papel::Locador]);
+
+    let regras = |percentualmulta: i64| Json(RegrasAtrasoInput{
+        idusuario: idusuario.clone(),
+        percentualmulta: Percentual::de_inteiro(percentualmulta),
+        percentualjurosmensal: Percentual::de_pontos_base(150)
+    });
+    let res = atualiza_regras_atraso_locadora(State(pool_testes()), usuario_autenticado(&idusuario), regras(120)).await;
+    assert!(matches!(res, Err(ErroApi::Validacao{..})));
+    let res = atualiza_regras_atraso_locadora(State(pool_testes()), usuario_autenticado("idteste701"), regras(5)).await;
+    assert!(matches!(res, Err(ErroApi::Proibido(_))));
+    let locadora = atualiza_regras_atraso_locadora(State(pool_testes()), usuario_autenticado(&idusuario), regras(5)).await.unwrap().1.0;
+    assert_eq!((locadora.percentualmulta, locadora.percentualjurosmensal), (Percentual::de_inteiro(5), Percentual::de_pontos_base(150)));
 
     assert!(deleta_locadora(idlocadora).await.is_ok());
     assert!(deleta_endereco(idendereco).await.is_ok());
     assert!(deleta_conta_bancaria(idconta).await.is_ok());
-    assert!(deleta_usuario(Query(IdInput{id: idusuario})).await.is_ok());
+    assert!(deleta_usuario(usuario_autenticado(&idusuario), Query(IdInput{id: idusuario})).await.is_ok());
 }
 
 #[tokio::test]
 async fn test_cadastra_locadora_err(){
-    assert!(cadastra_locadora(Json(LocadoraInput{ idusuario: "idrandom".to_string(),
+    assert!(cadastra_locadora(State(pool_testes()), usuario_autenticado("idrandom"), Json(LocadoraInput{ idusuario: "idrandom".to_string(),
      idendereco: "".to_string(), 
      idconta: "idrandom2".to_string() })).await.is_err());
 }
@@ -43,48 +59,50 @@ async fn test_cadastra_locadora_err(){
 async fn test_busca_locadora_idusuario_ok(){
     let usuario = usuario_padrao("701");
 
-    let usuario = cadastra_usuario(Json(usuario)).await.unwrap().1;
+    let usuario = cadastra_usuario(State(pool_testes()), Json(usuario)).await.unwrap().1;
     let idusuario = usuario.0.idusuario.to_string();
 
     let endereco = endereco_usuario_padrao(&idusuario, "701").await;
-    let idendereco = cadastra_endereco_usuario(Json(endereco)).await.unwrap().1.0.idendereco;
+    let idendereco = cadastra_endereco_usuario(State(pool_testes()), usuario_autenticado(&idusuario), Json(endereco)).await.unwrap().1.0.idendereco;
     
     let conta = conta_bancaria_padrao(&idusuario, "701");
-    let idconta = cadastra_conta_bancaria(Json(conta)).await.unwrap().1.0;
+    let idconta = cadastra_conta_bancaria(State(pool_testes()), usuario_autenticado(&idusuario), Json(conta)).await.unwrap().1.0;
 
     let locadora = locadora_padrao(idusuario.clone(), idendereco.clone(), idconta.clone());
-    let idlocadora = cadastra_locadora(Json(locadora)).await.unwrap().1.0;
+    let idlocadora = cadastra_locadora(State(pool_testes()), usuario_autenticado(&idusuario), Json(locadora)).await.unwrap().1.0;
 
-    let idresultado = busca_locadora_idusuario(Query(IdInput{id: idusuario.clone()})).await.unwrap().1.0.idusuario;
+    let idresultado = busca_locadora_idusuario(State(pool_testes()), usuario_autenticado(&idusuario), Query(IdInput{id: idusuario.clone()})).await.unwrap().1.0.idusuario;
+    let res = busca_locadora_idusuario(State(pool_testes()), usuario_autenticado("outroUsuario"), Query(IdInput{id: idusuario.clone()})).await;
+    assert!(matches!(res, Err(ErroApi::Proibido(_))));
 
     assert_eq!(idusuario, idresultado);
 
     assert!(deleta_locadora(idlocadora).await.is_ok());
     assert!(deleta_endereco(idendereco).await.is_ok());
     assert!(deleta_conta_bancaria(idconta).await.is_ok());
-    assert!(deleta_usuario(Query(IdInput{id: idusuario})).await.is_ok());
+    assert!(deleta_usuario(usuario_autenticado(&idusuario), Query(IdInput{id: idusuario})).await.is_ok());
 }
 
 #[tokio::test]
 async fn test_busca_locadora_idusuario_err(){
     let usuario = usuario_padrao("702");
 
-    let usuario = cadastra_usuario(Json(usuario)).await.unwrap().1;
+    let usuario = cadastra_usuario(State(pool_testes()), Json(usuario)).await.unwrap().1;
     let idusuario = usuario.0.idusuario.to_string();
 
     let endereco = endereco_usuario_padrao(&idusuario, "702").await;
-    let idendereco = cadastra_endereco_usuario(Json(endereco)).await.unwrap().1.0.idendereco;
+    let idendereco = cadastra_endereco_usuario(State(pool_testes()), usuario_autenticado(&idusuario), Json(endereco)).await.unwrap().1.0.idendereco;
     
     let conta = conta_bancaria_padrao(&idusuario, "702");
-    let idconta = cadastra_conta_bancaria(Json(conta)).await.unwrap().1.0;
+    let idconta = cadastra_conta_bancaria(State(pool_testes()), usuario_autenticado(&idusuario), Json(conta)).await.unwrap().1.0;
 
     let locadora = locadora_padrao(idusuario.clone(), idendereco.clone(), idconta.clone());
-    let idlocadora = cadastra_locadora(Json(locadora)).await.unwrap().1.0;
+    let idlocadora = cadastra_locadora(State(pool_testes()), usuario_autenticado(&idusuario), Json(locadora)).await.unwrap().1.0;
 
-    assert!(busca_locadora_idusuario(Query(IdInput{id: "idinvalido".to_string()})).await.is_err());
+    assert!(busca_locadora_idusuario(State(pool_testes()), admin_autenticado(&idusuario), Query(IdInput{id: "idinvalido".to_string()})).await.is_err());
 
     assert!(deleta_locadora(idlocadora).await.is_ok());
     assert!(deleta_endereco(idendereco).await.is_ok());
     assert!(deleta_conta_bancaria(idconta).await.is_ok());
-    assert!(deleta_usuario(Query(IdInput{id: idusuario})).await.is_ok());
+    assert!(deleta_usuario(usuario_autenticado(&idusuario), Query(IdInput{id: idusuario})).await.is_ok());
 }
\ No newline at end of file
@@ -1,11 +1,11 @@
-use axum::{extract::Query, Json};
+use axum::{extract::{Query, State}, Json};
 
-use crate::{controllers::{maquinas::{busca_maquina_id, cadastra_maquina, deleta_maquina_id, lista_todas_maquinas, MaquinaInput}, maquinas_usuarios::busca_maquinas_usuario_idusuario, usuarios::{cadastra_usuario, IdInput}}, models::usuarios::deleta_usuario, tests::usuarios::usuario_padrao};
+use crate::{controllers::{maquinas::{busca_maquina_id, busca_textual_maquina, cadastra_maquina, deleta_maquina_id, destaca_trecho, lista_todas_maquinas, normaliza_pesquisa, pesquisa_maquina, BuscaTextualInput, MaquinaInput, PesquisaMaquinaInput}, maquinas_usuarios::busca_maquinas_usuario_idusuario, usuarios::{cadastra_usuario, IdInput}}, dinheiro::Dinheiro, models::{maquinas::{padrao_ilike, FIM_DESTAQUE, INICIO_DESTAQUE}, usuarios::deleta_usuario}, tests::{pool_testes, sessoes::{admin_autenticado, usuario_autenticado}, usuarios::usuario_padrao}};
 
 pub struct MaquinaInputTeste{
     pub nome: String,
     pub numeroserie: String,
-    pub valoraluguel: f64,
+    pub valoraluguel: Dinheiro,
     pub disponivelaluguel: String,
     pub status: String,
     pub categoria: String,
@@ -16,7 +16,7 @@ pub struct MaquinaInputTeste{
 pub async fn maquina_padrao(numeroteste: &str) -> MaquinaInputTeste{
     let nome = format!("Maquina Teste {}", numeroteste);
     let numeroserie = format!("TEST-NS{}", numeroteste);
-    let valoraluguel: f64 = numeroteste.parse().unwrap_or(169.1);
+    let valoraluguel = numeroteste.parse().unwrap_or(Dinheiro::de_centavos(16910));
     let disponivelaluguel = "Sim".to_string();
     let status = "Ativo".to_string();
     let descricao =  format!("Descrição N{}", numeroteste);
@@ -53,10 +53,10 @@ async fn test_cadastra_maquina_ok(){
 
     let usuario = usuario_padrao("201");
 
-    let usuario = cadastra_usuario(Json(usuario)).await.unwrap().1;
+    let usuario = cadastra_usuario(State(pool_testes()), Json(usuario)).await.unwrap().1;
     let idusuario = usuario.0.idusuario.to_string();
 
-    let idsmaquina = cadastra_maquina(Json(
+    let idsmaquina = cadastra_maquina(State(pool_testes()), usuario_autenticado(&idusuario), Json(
         converte_tipo_maquina(maquina, idusuario.clone()
         ).await)).await.unwrap().1;
     let id = idsmaquina.0.idmaquina.to_string();
@@ -71,15 +71,15 @@ async fn test_lista_todas_maquinas_ok(){
 
     let usuario = usuario_padrao("202");
 
-    let usuario = cadastra_usuario(Json(usuario)).await.unwrap().1;
+    let usuario = cadastra_usuario(State(pool_testes()), Json(usuario)).await.unwrap().1;
     let idusuario = usuario.0.idusuario.to_string();
 
-    let idsmaquina = cadastra_maquina(Json(
+    let idsmaquina = cadastra_maquina(State(pool_testes()), usuario_autenticado(&idusuario), Json(
         converte_tipo_maquina(maquina, idusuario.clone()
         ).await)).await.unwrap().1;
     let id = idsmaquina.0.idmaquina.to_string();
 
-    assert!(lista_todas_maquinas().await.is_ok());
+    assert!(lista_todas_maquinas(State(pool_testes())).await.is_ok());
     
     assert!(deleta_usuario(idusuario).await.is_ok());
     assert!(deleta_maquina_id(id).await.is_ok());
@@ -91,15 +91,15 @@ async fn busca_maquina_id_ok(){
 
     let usuario = usuario_padrao("203");
 
-    let usuario = cadastra_usuario(Json(usuario)).await.unwrap().1;
+    let usuario = cadastra_usuario(State(pool_testes()), Json(usuario)).await.unwrap().1;
     let idusuario = usuario.0.idusuario.to_string();
 
-    let idsmaquina = cadastra_maquina(Json(
+    let idsmaquina = cadastra_maquina(State(pool_testes()), usuario_autenticado(&idusuario), Json(
         converte_tipo_maquina(maquina, idusuario.clone()
         ).await)).await.unwrap().1;
     let id = idsmaquina.0.idmaquina.to_string();
 
-    assert!(busca_maquina_id(Query(IdInput{id: id.clone()})).await.is_ok());
+    assert!(busca_maquina_id(State(pool_testes()), Query(IdInput{id: id.clone()})).await.is_ok());
     
     assert!(deleta_usuario(idusuario).await.is_ok());
     assert!(deleta_maquina_id(id).await.is_ok());
@@ -111,15 +111,15 @@ async fn busca_maquina_id_err(){
 
     let usuario = usuario_padrao("204");
 
-    let usuario = cadastra_usuario(Json(usuario)).await.unwrap().1;
+    let usuario = cadastra_usuario(State(pool_testes()), Json(usuario)).await.unwrap().1;
     let idusuario = usuario.0.idusuario.to_string();
 
-    let idsmaquina = cadastra_maquina(Json(
+    let idsmaquina = cadastra_maquina(State(pool_testes()), usuario_autenticado(&idusuario), Json(
         converte_tipo_maquina(maquina, idusuario.clone()
         ).await)).await.unwrap().1;
     let id = idsmaquina.0.idmaquina.to_string();
 
-    assert!(busca_maquina_id(Query(IdInput{id: "idinvalido".to_string()})).await.is_err());
+    assert!(busca_maquina_id(State(pool_testes()), Query(IdInput{id: "idinvalido".to_string()})).await.is_err());
     
     assert!(deleta_usuario(idusuario).await.is_ok());
     assert!(deleta_maquina_id(id).await.is_ok());
@@ -131,15 +131,15 @@ async fn busca_maquinas_usuario_idusuario_ok(){
 
     let usuario = usuario_padrao("205");
 
-    let usuario = cadastra_usuario(Json(usuario)).await.unwrap().1;
+    let usuario = cadastra_usuario(State(pool_testes()), Json(usuario)).await.unwrap().1;
     let idusuario = usuario.0.idusuario.to_string();
 
-    let idsmaquina = cadastra_maquina(Json(
+    let idsmaquina = cadastra_maquina(State(pool_testes()), usuario_autenticado(&idusuario), Json(
         converte_tipo_maquina(maquina, idusuario.clone()
         ).await)).await.unwrap().1;
     let id = idsmaquina.0.idmaquina.to_string();
 
-    assert!(busca_maquinas_usuario_idusuario(Query(IdInput{
+    assert!(busca_maquinas_usuario_idusuario(State(pool_testes()), usuario_autenticado(&idusuario), Query(IdInput{
         id: idusuario.clone()
     })).await.is_ok());
     
@@ -153,15 +153,15 @@ async fn busca_maquinas_usuario_idusuario_err(){
 
     let usuario = usuario_padrao("206");
 
-    let usuario = cadastra_usuario(Json(usuario)).await.unwrap().1;
+    let usuario = cadastra_usuario(State(pool_testes()), Json(usuario)).await.unwrap().1;
     let idusuario = usuario.0.idusuario.to_string();
 
-    let idsmaquina = cadastra_maquina(Json(
+    let idsmaquina = cadastra_maquina(State(pool_testes()), usuario_autenticado(&idusuario), Json(
         converte_tipo_maquina(maquina, idusuario.clone()
         ).await)).await.unwrap().1;
     let id = idsmaquina.0.idmaquina.to_string();
 
-    assert!(busca_maquinas_usuario_idusuario(Query(IdInput{
+    assert!(busca_maquinas_usuario_idusuario(State(pool_testes()), admin_autenticado(&idusuario), Query(IdInput{
         id: "idinválido".to_string()
     })).await.is_err());
     
@@ -169,9 +169,112 @@ async fn busca_maquinas_usuario_idusuario_err(){
     assert!(deleta_maquina_id(id).await.is_ok());
 }
 
+#[test]
+fn test_padrao_ilike_escapa_curingas(){
+    assert_eq!(padrao_ilike(" 100%_a\\b "), "%100\\%\\_a\\\\b%");
+}
+
+#[test]
+fn test_normaliza_pesquisa(){
+    let filtro = normaliza_pesquisa(&PesquisaMaquinaInput{
+        texto: Some("   ".to_string()),
+        pagina: Some(3),
+        tamanho_pagina: Some(10),
+        ..Default::default()
+    }).unwrap();
+    assert_eq!(filtro.texto, None);
+    assert_eq!(filtro.limite, 10);
+    assert_eq!(filtro.deslocamento, 20);
+
+    let erro = normaliza_pesquisa(&PesquisaMaquinaInput{
+        valor_minimo: Some(Dinheiro::de_reais(200)),
+        valor_maximo: Some(Dinheiro::de_reais(100)),
+        ..Default::default()
+    }).unwrap_err();
+    assert_eq!(erro.corpo().field, Some("valor_minimo".to_string()));
+
+    assert!(normaliza_pesquisa(&PesquisaMaquinaInput{pagina: Some(0), ..Default::default()}).is_err());
+    assert!(normaliza_pesquisa(&PesquisaMaquinaInput{tamanho_pagina: Some(101), ..Default::default()}).is_err());
+}
+
+#[tokio::test]
+async fn test_pesquisa_maquina_ok(){
+    let maquina = maquina_padrao("207").await;
+
+    let usuario = usuario_padrao("207");
+
+    let usuario = cadastra_usuario(State(pool_testes()), Json(usuario)).await.unwrap().1;
+    let idusuario = usuario.0.idusuario.to_string();
+
+    let idsmaquina = cadastra_maquina(State(pool_testes()), usuario_autenticado(&idusuario), Json(
+        converte_tipo_maquina(maquina, idusuario.clone()
+        ).await)).await.unwrap().1;
+    let id = idsmaquina.0.idmaquina.to_string();
+
+    let resultado = pesquisa_maquina(State(pool_testes()), Json(PesquisaMaquinaInput{
+        texto: Some("TEST-NS207".to_string()),
+        valor_minimo: Some(Dinheiro::de_reais(207)),
+        valor_maximo: Some(Dinheiro::de_reais(207)),
+        ..Default::default()
+    })).await.unwrap().1;
+    assert_eq!(resultado.0.total, 1);
+    assert_eq!(resultado.0.maquinas[0].idmaquina, id);
+
+    // Curingas digitados pelo usuário não podem casar com qualquer texto.
+    let resultado = pesquisa_maquina(State(pool_testes()), Json(PesquisaMaquinaInput{
+        texto: Some("TEST-NS2%7".to_string()),
+        ..Default::default()
+    })).await.unwrap().1;
+    assert_eq!(resultado.0.total, 0);
+
+    assert!(deleta_usuario(idusuario).await.is_ok());
+    assert!(deleta_maquina_id(id).await.is_ok());
+}
+
+#[test]
+fn test_destaca_trecho(){
+    let trecho = format!("<b>{}Retroescavadeira{}</b>", INICIO_DESTAQUE, FIM_DESTAQUE);
+    assert_eq!(destaca_trecho(&trecho), "&lt;b&gt;<mark>Retroescavadeira</mark>&lt;/b&gt;");
+}
+
+#[tokio::test]
+async fn test_busca_textual_maquina_ok(){
+    let mut maquina = maquina_padrao("208").await;
+    maquina.nome = "Retroescavadeira Teste 208".to_string();
+
+    let usuario = usuario_padrao("208");
+
+    let usuario = cadastra_usuario(State(pool_testes()), Json(usuario)).await.unwrap().1;
+    let idusuario = usuario.0.idusuario.to_string();
+
+    let idsmaquina = cadastra_maquina(State(pool_testes()), usuario_autenticado(&idusuario), Json(
+        converte_tipo_maquina(maquina, idusuario.clone()
+        ).await)).await.unwrap().1;
+    let id = idsmaquina.0.idmaquina.to_string();
+
+    // Sem acento e com a palavra separada.
+    for texto in ["descricao N208", "retro escavadeira"]{
+        let resultado = busca_textual_maquina(State(pool_testes()), Json(BuscaTextualInput{
+            texto: texto.to_string(),
+            pagina: None,
+            tamanho_pagina: Some(100)
+        })).await.unwrap().1;
+        assert!(resultado.0.resultados.iter().any(|r| r.maquina.idmaquina == id));
+    }
+
+    assert!(busca_textual_maquina(State(pool_testes()), Json(BuscaTextualInput{
+        texto: "   ".to_string(),
+        pagina: None,
+        tamanho_pagina: None
+    })).await.is_err());
+
+    assert!(deleta_usuario(idusuario).await.is_ok());
+    assert!(deleta_maquina_id(id).await.is_ok());
+}
+
 // #[tokio::test]
 
 // async fn test_lista_todas_maquinas_err(){
 //     // Esse teste falha quando temos máquinas cadastradas no banco :)
-//     assert!(lista_todas_maquinas().await.is_err());
+//     assert!(lista_todas_maquinas(State(pool_testes())).await.is_err());
 // }
\ No newline at end of file
@@ -6,10 +6,59 @@ pub mod enderecos;
 pub mod locatarios;
 pub mod contas_bancarias;
 pub mod locadoras;
+pub mod sessoes;
+pub mod emails;
+pub mod limites;
+pub mod senhas;
+pub mod ids;
+pub mod oauth;
+pub mod erros;
+pub mod pdf;
+pub mod reservas_maquinas;
+pub mod solicitacoes_contratos;
+pub mod contratos;
+pub mod papeis_usuarios;
+pub mod pix;
+pub mod faturas;
+pub mod encargos_contratos;
+pub mod dinheiro;
+pub mod precos_maquinas;
+
+use std::sync::OnceLock;
+
+use diesel::migration::MigrationSource;
+use diesel::pg::Pg;
+
+use crate::{controllers::{cria_pool, obtem_conn, Pool}, models::migracoes::{aplica_migracoes, possui_migracoes_pendentes, MIGRACOES}};
+
+/// Pool compartilhado entre os testes, criado na primeira chamada.
+pub fn pool_testes() -> Pool{
+    static POOL: OnceLock<Pool> = OnceLock::new();
+    POOL.get_or_init(|| cria_pool().unwrap()).clone()
+}
+
+#[tokio::test]
+async fn test_cria_pool_ok(){
+    let pool = cria_pool().unwrap();
+
+    assert!(pool.get().await.is_ok());
+}
+
+#[test]
+fn test_migracoes_embutidas(){
+    let pasta = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("src/migrations");
+    let quantidade = std::fs::read_dir(pasta).unwrap()
+        .filter(|entrada| entrada.as_ref().unwrap().path().is_dir())
+        .count();
+
+    let migracoes = MigrationSource::<Pg>::migrations(&MIGRACOES).unwrap();
+    assert_eq!(migracoes.len(), quantidade);
+}
 
 #[tokio::test]
-async fn test_cria_conn_ok(){
-    use crate::controllers::cria_conn;
+async fn test_aplica_migracoes_ok(){
+    let conn = obtem_conn(&pool_testes()).await.unwrap();
 
-    assert!(cria_conn().is_ok());
-}
\ No newline at end of file
+    assert!(aplica_migracoes(&conn).await.is_ok());
+    assert!(!possui_migracoes_pendentes(&conn).await.unwrap());
+}
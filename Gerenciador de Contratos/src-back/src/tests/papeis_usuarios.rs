@@ -0,0 +1,123 @@
+use std::str::FromStr;
+
+use axum::{body::Body, extract::{Query, State}, http::{header::AUTHORIZATION, Request, StatusCode}, middleware, routing::post, Json, Router};
+use tower::ServiceExt;
+
+use crate::{controllers::{imagens_maquinas::{cadastra_imagem_maquina, ImagemMaquinaInput}, maquinas::{atualiza_maquina, busca_maquina_id, cadastra_maquina, deleta_maquina_id, MaquinaReturn}, obtem_conn, papeis_usuarios::{atribui_papel_usuario, busca_papeis_usuario, remove_papel_usuario, PapelUsuarioInput}, sessoes::{exige_autenticacao, exige_permissao, gera_tokens_sessao, verifica_usuario, verifica_usuario_ou_admin}, usuarios::{cadastra_usuario, IdInput}}, erros::ErroApi, models::{papeis_usuarios::{atribui_papel, Papel, Permissao}, usuarios::deleta_usuario}, tests::{maquinas::{converte_tipo_maquina, maquina_padrao}, pool_testes, sessoes::{admin_autenticado, usuario_autenticado}, usuarios::usuario_padrao}};
+
+#[test]
+fn test_permissoes_papeis(){
+    assert_eq!(Papel::from_str(" locador ").unwrap(), Papel::Locador);
+    assert_eq!(Papel::Admin.to_string(), "admin");
+    assert!(matches!(Papel::from_str("dono"), Err(ErroApi::Validacao{..})));
+
+    let mut usuario = usuario_autenticado("idteste120");
+    assert!(!usuario.pode(Permissao::GerenciaMaquinas));
+    usuario.papeis = vec![Papel::Locatario];
+    assert!(usuario.pode(Permissao::SolicitaLocacao));
+    assert!(!usuario.pode(Permissao::GerenciaMaquinas));
+    assert!(!usuario.e_admin());
+    assert!(verifica_usuario_ou_admin(&usuario, "outroid120").is_err());
+
+    let admin = admin_autenticado("admin120");
+    for permissao in [Permissao::GerenciaMaquinas, Permissao::SolicitaLocacao, Permissao::Modera, Permissao::GerenciaPapeis]{
+        assert!(admin.pode(permissao));
+    }
+    assert!(verifica_usuario_ou_admin(&admin, "outroid120").is_ok());
+    // Nas ações sobre os dados do próprio usuário, nem o admin passa.
+    assert!(verifica_usuario(&admin, "outroid120").is_err());
+}
+
+#[tokio::test]
+async fn test_rota_exige_permissao(){
+    let idusuario = cadastra_usuario(State(pool_testes()), Json(usuario_padrao("121"))).await.unwrap().1.0.idusuario;
+    let token = gera_tokens_sessao(&idusuario).unwrap().token_acesso;
+
+    let app = Router::new()
+        .route("/cadastra_maquina", post(|| async { "ok" })
+            .layer(middleware::from_fn_with_state(Permissao::GerenciaMaquinas, exige_permissao)))
+        .route_layer(middleware::from_fn_with_state(pool_testes(), exige_autenticacao));
+    let requisicao = |token: Option<&str>| {
+        let mut requisicao = Request::post("/cadastra_maquina");
+        if let Some(token) = token{
+            requisicao = requisicao.header(AUTHORIZATION, format!("Bearer {}", token));
+        }
+        requisicao.body(Body::empty()).unwrap()
+    };
+
+    assert_eq!(app.clone().oneshot(requisicao(None)).await.unwrap().status(), StatusCode::UNAUTHORIZED);
+    assert_eq!(app.clone().oneshot(requisicao(Some(&token))).await.unwrap().status(), StatusCode::FORBIDDEN);
+
+    // Os papéis são lidos a cada requisição, então o token atual já vale.
+    let conn = &obtem_conn(&pool_testes()).await.unwrap();
+    atribui_papel(conn, idusuario.clone(), Papel::Locador).await.unwrap();
+    assert_eq!(app.oneshot(requisicao(Some(&token))).await.unwrap().status(), StatusCode::OK);
+
+    assert!(deleta_usuario(idusuario).await.is_ok());
+}
+
+#[tokio::test]
+async fn test_atualiza_maquina_dono(){
+    let iddono = cadastra_usuario(State(pool_testes()), Json(usuario_padrao("122"))).await.unwrap().1.0.idusuario;
+    let idmaquina = cadastra_maquina(State(pool_testes()), usuario_autenticado(&iddono), Json(
+        converte_tipo_maquina(maquina_padrao("122").await, iddono.clone()).await)).await.unwrap().1.0.idmaquina;
+    let maquina = || async {
+        let maquina = busca_maquina_id(State(pool_testes()), Query(IdInput{id: idmaquina.clone()})).await.unwrap().1.0;
+        MaquinaReturn::from(maquina)
+    };
+
+    let res = atualiza_maquina(State(pool_testes()), usuario_autenticado("outrousuario122"), Json(maquina().await)).await;
+    assert!(matches!(res, Err(ErroApi::Proibido(_))));
+    let res = cadastra_imagem_maquina(State(pool_testes()), usuario_autenticado("outrousuario122"), Json(ImagemMaquinaInput{
+        idimagem: "idimagem122".to_string(),
+        idmaquina: idmaquina.clone()
+    })).await;
+    assert!(matches!(res, Err(ErroApi::Proibido(_))));
+
+    let mut atualizada = maquina().await;
+    atualizada.nome = "Maquina Teste 122 Atualizada".to_string();
+    assert_eq!(atualiza_maquina(State(pool_testes()), usuario_autenticado(&iddono), Json(atualizada)).await.unwrap(), StatusCode::OK);
+
+    // O admin modera qualquer máquina.
+    let mut moderada = maquina().await;
+    assert_eq!(moderada.nome, "Maquina Teste 122 Atualizada");
+    moderada.disponivelaluguel = "Não".to_string();
+    assert_eq!(atualiza_maquina(State(pool_testes()), admin_autenticado("admin122"), Json(moderada)).await.unwrap(), StatusCode::OK);
+    assert_eq!(maquina().await.disponivelaluguel, "Não");
+
+    assert!(deleta_maquina_id(idmaquina).await.is_ok());
+    assert!(deleta_usuario(iddono).await.is_ok());
+}
+
+#[tokio::test]
+async fn test_gerencia_papeis_usuario(){
+    let idusuario = cadastra_usuario(State(pool_testes()), Json(usuario_padrao("123"))).await.unwrap().1.0.idusuario;
+    let entrada = |idusuario: &str, papel: &str| Json(PapelUsuarioInput{
+        idusuario: idusuario.to_string(),
+        papel: papel.to_string()
+    });
+    let papeis = |usuario| busca_papeis_usuario(State(pool_testes()), usuario, Query(IdInput{id: idusuario.clone()}));
+
+    assert!(papeis(usuario_autenticado(&idusuario)).await.unwrap().1.0.is_empty());
+    assert!(matches!(papeis(usuario_autenticado("outrousuario123")).await, Err(ErroApi::Proibido(_))));
+
+    assert_eq!(atribui_papel_usuario(State(pool_testes()), entrada(&idusuario, "admin")).await.unwrap(), StatusCode::OK);
+    assert_eq!(atribui_papel_usuario(State(pool_testes()), entrada(&idusuario, "locatario")).await.unwrap(), StatusCode::OK);
+    // Dar de novo um papel que o usuário já tem não muda nada.
+    assert_eq!(atribui_papel_usuario(State(pool_testes()), entrada(&idusuario, "admin")).await.unwrap(), StatusCode::OK);
+    assert_eq!(papeis(admin_autenticado("admin123")).await.unwrap().1.0, vec![Papel::Admin, Papel::Locatario]);
+
+    assert!(matches!(atribui_papel_usuario(State(pool_testes()), entrada(&idusuario, "dono")).await, Err(ErroApi::Validacao{..})));
+    assert!(matches!(atribui_papel_usuario(State(pool_testes()), entrada("idinexistente123", "locador")).await, Err(ErroApi::NaoEncontrado(_))));
+
+    let admin = admin_autenticado(&idusuario);
+    let res = remove_papel_usuario(State(pool_testes()), admin.clone(), entrada(&idusuario, "admin")).await;
+    assert!(matches!(res, Err(ErroApi::Conflito(_))));
+    assert_eq!(remove_papel_usuario(State(pool_testes()), admin.clone(), entrada(&idusuario, "locatario")).await.unwrap(), StatusCode::OK);
+    let res = remove_papel_usuario(State(pool_testes()), admin, entrada(&idusuario, "locatario")).await;
+    assert!(matches!(res, Err(ErroApi::Conflito(_))));
+    assert_eq!(remove_papel_usuario(State(pool_testes()), admin_autenticado("admin123"), entrada(&idusuario, "admin")).await.unwrap(), StatusCode::OK);
+    assert!(papeis(usuario_autenticado(&idusuario)).await.unwrap().1.0.is_empty());
+
+    assert!(deleta_usuario(idusuario).await.is_ok());
+}
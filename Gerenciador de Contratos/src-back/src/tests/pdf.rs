@@ -0,0 +1,103 @@
+use crate::{controllers::contratos::{modelo_contrato, renderiza_modelo, ContratoPDF, VERSAO_MODELO_CONTRATO}, dinheiro::{formata_reais, Dinheiro}, pdf::{gera_pdf, BlocoPdf}};
+
+pub fn contrato_pdf_padrao() -> ContratoPDF{
+    ContratoPDF{
+        idcontrato: "idcontrato900".to_string(),
+        nomelocatario: "Locatário Teste".to_string(),
+        documentolocatario: "113.144.189-10".to_string(),
+        tipodocumentolocatario: "CPF".to_string(),
+        nomelocador: "Locador Teste".to_string(),
+        documentolocador: "12.345.678/0001-90".to_string(),
+        tipodocumentolocador: "CNPJ".to_string(),
+        estadoenderecolocatario: "MG".to_string(),
+        cidadeenderecolocatario: "Uberlândia".to_string(),
+        cependerecolocatario: "38400-000".to_string(),
+        bairroenderecolocatario: "Centro".to_string(),
+        logradouroenderecolocatario: "Rua A".to_string(),
+        numeroenderecolocatario: "10".to_string(),
+        complementoenderecolocatario: "".to_string(),
+        estadoenderecolocador: "MG".to_string(),
+        cidadeenderecolocador: "Patos de Minas".to_string(),
+        cependerecolocador: "38700-000".to_string(),
+        bairroenderecolocador: "Industrial".to_string(),
+        logradouroenderecolocador: "Avenida B".to_string(),
+        numeroenderecolocador: "200".to_string(),
+        complementoenderecolocador: "Galpão 2".to_string(),
+        estadoenderecoretirada: "MG".to_string(),
+        cidadeenderecoretirada: "Patos de Minas".to_string(),
+        cependerecoretirada: "38700-000".to_string(),
+        bairroenderecoretirada: "Industrial".to_string(),
+        logradouroenderecoretirada: "Avenida B".to_string(),
+        numeroenderecoretirada: "200".to_string(),
+        complementoenderecoretirada: "Galpão 2".to_string(),
+        nomemaquina: "Retroescavadeira".to_string(),
+        numeroseriemaquina: "SN900".to_string(),
+        valoraluguelmaquina: Dinheiro::de_reais(12500),
+        numerocontabanco: "12345-6".to_string(),
+        numeroagenciabanco: "0001".to_string(),
+        nomebanco: "Banco Teste".to_string(),
+        medidatempolocacao: "Dias".to_string(),
+        prazolocacao: 15.0,
+        valorlocacao: Dinheiro::de_centavos(681250),
+        cidadeforo: "Patos de Minas".to_string(),
+        datacontrato: "2025-03-11 03:58:11.123456".to_string(),
+        datainicio: "2025-03-12 08:00:00".to_string(),
+        datafim: "2025-03-27 08:00:00".to_string()
+    }
+}
+
+#[test]
+fn test_formata_reais(){
+    assert_eq!(formata_reais(Dinheiro::de_centavos(50)), "R$ 0,50");
+    assert_eq!(formata_reais(Dinheiro::de_centavos(123450)), "R$ 1.234,50");
+    assert_eq!(formata_reais(Dinheiro::de_centavos(123456789)), "R$ 1.234.567,89");
+    assert_eq!(formata_reais(Dinheiro::de_centavos(-5)), "-R$ 0,05");
+}
+
+#[test]
+fn test_renderiza_modelo_ok(){
+    let modelo = modelo_contrato(VERSAO_MODELO_CONTRATO).unwrap();
+    let blocos = renderiza_modelo(modelo, &contrato_pdf_padrao().campos()).unwrap();
+
+    assert_eq!(blocos[0], BlocoPdf::Titulo("CONTRATO DE LOCAÇÃO DE MÁQUINA".to_string()));
+    let texto: Vec<String> = blocos.iter().filter_map(|b| match b{
+        BlocoPdf::Paragrafo(t) => Some(t.clone()),
+        _ => None
+    }).collect();
+    let texto = texto.join("\n");
+    assert!(texto.contains("R$ 6.812,50"));
+    assert!(texto.contains("Patos de Minas, 11/03/2025."));
+    assert!(texto.contains("de 12/03/2025 às 08:00 a 27/03/2025 às 08:00"));
+    assert!(!texto.contains(", , "));
+}
+
+#[test]
+fn test_renderiza_modelo_err(){
+    assert!(renderiza_modelo("Contrato {{campoinexistente}}", &contrato_pdf_padrao().campos()).is_err());
+}
+
+#[test]
+fn test_gera_pdf(){
+    let modelo = modelo_contrato(VERSAO_MODELO_CONTRATO).unwrap();
+    let blocos = renderiza_modelo(modelo, &contrato_pdf_padrao().campos()).unwrap();
+    let pdf = gera_pdf("Contrato", "Contrato idcontrato900", &blocos);
+
+    assert!(pdf.starts_with(b"%PDF-1.4"));
+    assert!(pdf.ends_with(b"%%EOF\n"));
+    assert_eq!(pdf, gera_pdf("Contrato", "Contrato idcontrato900", &blocos));
+
+    // O `startxref` precisa apontar para o início da tabela `xref`.
+    let texto = String::from_utf8_lossy(&pdf);
+    let inicio_xref: usize = texto.rsplit("startxref\n").next().unwrap().lines().next().unwrap().parse().unwrap();
+    assert!(pdf[inicio_xref..].starts_with(b"xref"));
+}
+
+#[test]
+fn test_gera_pdf_paginas(){
+    let blocos: Vec<BlocoPdf> = (0..200).map(|i| BlocoPdf::Paragrafo(format!("Parágrafo de teste número {}.", i))).collect();
+    let pdf = gera_pdf("Teste", "Teste", &blocos);
+    let texto = String::from_utf8_lossy(&pdf);
+
+    assert!(texto.matches("/Type /Page ").count() > 1);
+    assert!(!texto.contains("/Count 1 "));
+}
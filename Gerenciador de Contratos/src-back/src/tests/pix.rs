@@ -0,0 +1,246 @@
+use std::str::FromStr;
+
+use axum::{extract::{Query, State}, Json};
+use diesel::{ExpressionMethods, QueryDsl, RunQueryDsl};
+
+use crate::{controllers::{chaves_pix::{cadastra_chave_pix, deleta_chave_pix, ChavePixInput}, cobrancas_pix::{busca_cobrancas_pix_idcontrato, gera_cobranca_pix, registra_pagamento_pix, CobrancaPixInput, ConfirmacaoPagamentoPixInput}, faturas::{gera_faturas_contrato, registra_pagamento_fatura, FaturasContratoInput, PagamentoFaturaInput, PlanoFaturamento}, maquinas::{cadastra_maquina, deleta_maquina_id}, obtem_conn, usuarios::{cadastra_usuario, IdInput}}, dinheiro::Dinheiro, erros::ErroApi, ids::gera_id, models::{self, chaves_pix::TipoChavePix, cobrancas_pix::StatusCobranca, contratos::{Contrato, StatusContrato}, executa, faturas::StatusFatura, precos_maquinas::MedidaTempo, usuarios::deleta_usuario}, pix::{crc16, gera_br_code, normaliza_chave_pix, normaliza_texto_br_code, ConfiguracaoPix, DadosBrCode, DestinoPix}, tests::{maquinas::{converte_tipo_maquina, maquina_padrao}, pool_testes, sessoes::usuario_autenticado, usuarios::usuario_padrao}};
+
+/// Lê os campos EMV do payload, na ordem.
+fn campos_br_code(payload: &str) -> Vec<(String, String)>{
+    let mut campos = Vec::new();
+    let mut resto = payload;
+    while !resto.is_empty(){
+        let tamanho: usize = resto[2..4].parse().unwrap();
+        campos.push((resto[..2].to_string(), resto[4..4 + tamanho].to_string()));
+        resto = &resto[4 + tamanho..];
+    }
+    campos
+}
+
+fn verifica_crc(payload: &str){
+    let (dados, crc) = payload.split_at(payload.len() - 4);
+    assert_eq!(format!("{:04X}", crc16(dados.as_bytes())), crc);
+}
+
+#[test]
+fn test_gera_br_code(){
+    // Exemplo do manual do BR Code do Banco Central.
+    let exemplo = "00020126580014br.gov.bcb.pix0136123e4567-e12b-12d1-a456-4266554400005204000053039865802BR5913Fulano de Tal6008BRASILIA62070503***63041D3D";
+    verifica_crc(exemplo);
+    let payload = gera_br_code(&DadosBrCode{
+        destino: DestinoPix::Chave("123e4567-e12b-12d1-a456-426655440000".to_string()),
+        valor: None,
+        nome_recebedor: "Fulano de Tal".to_string(),
+        cidade: "BRASILIA".to_string(),
+        txid: None
+    }).unwrap();
+    assert_eq!(payload, exemplo);
+
+    let payload = gera_br_code(&DadosBrCode{
+        destino: DestinoPix::Url("pix.provedor.com.br/qr/v2/abc123".to_string()),
+        valor: Some(Dinheiro::de_centavos(123450)),
+        nome_recebedor: "Locadora São João de Máquinas Pesadas".to_string(),
+        cidade: "São José dos Campos".to_string(),
+        txid: Some("abc123".to_string())
+    }).unwrap();
+    verifica_crc(&payload);
+    let campos = campos_br_code(&payload);
+    let valor = |id: &str| campos.iter().find(|(campo, _)| campo == id).unwrap().1.clone();
+    assert_eq!(valor("01"), "12");
+    assert_eq!(valor("26"), "0014br.gov.bcb.pix2532pix.provedor.com.br/qr/v2/abc123");
+    assert_eq!(valor("54"), "1234.50");
+    assert_eq!(valor("59"), "Locadora Sao Joao de Maqu");
+    assert_eq!(valor("60"), "Sao Jose dos Ca");
+    assert_eq!(valor("62"), "0506abc123");
+
+    assert_eq!(normaliza_texto_br_code("  Ação \t Çedilha ", 25), "Acao Cedilha");
+    let res = gera_br_code(&DadosBrCode{
+        destino: DestinoPix::Chave("chave".to_string()),
+        valor: Some(Dinheiro::ZERO),
+        nome_recebedor: "Fulano".to_string(),
+        cidade: "Cidade".to_string(),
+        txid: None
+    });
+    assert!(matches!(res, Err(ErroApi::Validacao{..})));
+}
+
+#[test]
+fn test_normaliza_chave_pix(){
+    assert_eq!(normaliza_chave_pix(TipoChavePix::Cpf, "529.982.247-25").unwrap(), "52998224725");
+    assert!(normaliza_chave_pix(TipoChavePix::Cpf, "529.982.247-26").is_err());
+    assert!(normaliza_chave_pix(TipoChavePix::Cpf, "111.111.111-11").is_err());
+    assert_eq!(normaliza_chave_pix(TipoChavePix::Cnpj, "11.222.333/0001-81").unwrap(), "11222333000181");
+    assert!(normaliza_chave_pix(TipoChavePix::Cnpj, "11.222.333/0001-80").is_err());
+    assert_eq!(normaliza_chave_pix(TipoChavePix::Email, " Locadora@Exemplo.com ").unwrap(), "locadora@exemplo.com");
+    assert!(normaliza_chave_pix(TipoChavePix::Email, "locadora.exemplo.com").is_err());
+    assert_eq!(normaliza_chave_pix(TipoChavePix::Telefone, "(11) 91234-5678").unwrap(), "+5511912345678");
+    assert_eq!(normaliza_chave_pix(TipoChavePix::Telefone, "+55 11 3123-4567").unwrap(), "+551131234567");
+    assert!(normaliza_chave_pix(TipoChavePix::Telefone, "1234-5678").is_err());
+    assert!(normaliza_chave_pix(TipoChavePix::Telefone, "(01) 91234-5678").is_err());
+    assert_eq!(normaliza_chave_pix(TipoChavePix::Aleatoria, "123E4567-E12B-12D1-A456-426655440000").unwrap(),
+        "123e4567-e12b-12d1-a456-426655440000");
+    assert!(normaliza_chave_pix(TipoChavePix::Aleatoria, "123e4567e12b12d1a456426655440000").is_err());
+
+    assert_eq!(TipoChavePix::from_str("aleatoria").unwrap(), TipoChavePix::Aleatoria);
+    assert!(TipoChavePix::from_str("evp").is_err());
+}
+
+#[test]
+fn test_configuracao_pix(){
+    let configuracao = ConfiguracaoPix::de_variaveis(|nome| match nome{
+        "PIX_URL_COBRANCA" => Some("https://pix.provedor.com.br/qr/v2/".to_string()),
+        "PIX_TOKEN_CONFIRMACAO" => Some(" token-provedor ".to_string()),
+        _ => None
+    });
+    assert_eq!(configuracao.url_cobranca.as_deref(), Some("pix.provedor.com.br/qr/v2"));
+    assert!(configuracao.verifica_token("token-provedor").is_ok());
+    assert!(matches!(configuracao.verifica_token("outro"), Err(ErroApi::NaoAutorizado(_))));
+
+    let vazia = ConfiguracaoPix::de_variaveis(|_| Some(" ".to_string()));
+    assert_eq!(vazia, ConfiguracaoPix::default());
+    assert!(matches!(vazia.verifica_token("token-provedor"), Err(ErroApi::Indisponivel(_))));
+}
+
+fn contrato_pix(idlocador: &str, idlocatario: &str, idmaquina: &str) -> Contrato{
+    Contrato{
+        idcontrato: gera_id(),
+        idlocatario: idlocatario.to_string(),
+        idlocador: idlocador.to_string(),
+        idenderecolocatario: "idenderecolocatario130".to_string(),
+        idenderecolocador: "idenderecolocador130".to_string(),
+        idenderecoretirada: "idenderecolocador130".to_string(),
+        idmaquina: idmaquina.to_string(),
+        idsolicitacaocontrato: "idsolicitacao130".to_string(),
+        idcontabancarialocador: "idconta130".to_string(),
+        medidatempolocacao: MedidaTempo::Dias,
+        cidadeforo: "São Paulo".to_string(),
+        statuscontrato: StatusContrato::Ativo,
+        prazolocacao: 5.0,
+        valorlocacao: Dinheiro::de_reais(250),
+        datacontrato: chrono::Utc::now().naive_utc(),
+        motivoencerramento: None,
+        dataencerramento: None,
+        dataavisotermino: None,
+        datadevolucao: None,
+        dataavisoatraso: None
+    }
+}
+
+#[tokio::test]
+async fn test_cobranca_pix(){
+    let idlocador = cadastra_usuario(State(pool_testes()), Json(usuario_padrao("130"))).await.unwrap().1.0.idusuario;
+    let idlocatario = cadastra_usuario(State(pool_testes()), Json(usuario_padrao("131"))).await.unwrap().1.0.idusuario;
+    let locador = usuario_autenticado(&idlocador);
+    let locatario = usuario_autenticado(&idlocatario);
+    let idmaquina = cadastra_maquina(State(pool_testes()), locador.clone(), Json(
+        converte_tipo_maquina(maquina_padrao("130").await, idlocador.clone()).await)).await.unwrap().1.0.idmaquina;
+
+    let chave = |idusuario: &str, chave: &str| Json(ChavePixInput{
+        idusuario: idusuario.to_string(),
+        tipo: "email".to_string(),
+        chave: chave.to_string()
+    });
+    let res = cadastra_chave_pix(State(pool_testes()), locatario.clone(), chave(&idlocador, "testeunit130@gmail.com")).await;
+    assert!(matches!(res, Err(ErroApi::Proibido(_))));
+    let chavepix = cadastra_chave_pix(State(pool_testes()), locador.clone(), chave(&idlocador, "TesteUnit130@gmail.com")).await.unwrap().1.0;
+    assert_eq!(chavepix.chave, "testeunit130@gmail.com");
+    let res = cadastra_chave_pix(State(pool_testes()), locador.clone(), chave(&idlocador, "testeunit130@gmail.com")).await;
+    assert!(matches!(res, Err(ErroApi::Conflito(_))));
+
+    let conn = &obtem_conn(&pool_testes()).await.unwrap();
+    let idcontrato = models::contratos::cadastra_contrato(conn, contrato_pix(&idlocador, &idlocatario, &idmaquina)).await.unwrap();
+    let fatura = gera_faturas_contrato(State(pool_testes()), locador.clone(), Json(FaturasContratoInput{
+        idcontrato: idcontrato.clone(),
+        plano: PlanoFaturamento::AVista,
+        parcelas: vec![]
+    })).await.unwrap().1.0.remove(0);
+    let input = || Json(CobrancaPixInput{
+        idfatura: fatura.idfatura.clone(),
+        idchavepix: None,
+        dinamica: false
+    });
+
+    let res = gera_cobranca_pix(State(pool_testes()), usuario_autenticado("idteste132"), input()).await;
+    assert!(matches!(res, Err(ErroApi::Proibido(_))));
+
+    let (status, cobranca) = gera_cobranca_pix(State(pool_testes()), locatario.clone(), input()).await.unwrap();
+    assert_eq!(status.as_u16(), 201);
+    assert_eq!(cobranca.cobranca.valor, Dinheiro::de_reais(250));
+    assert_eq!(cobranca.cobranca.idfatura.as_deref(), Some(fatura.idfatura.as_str()));
+    assert_eq!(cobranca.cobranca.status, StatusCobranca::Pendente);
+    assert!(cobranca.cobranca.payload.contains("0014br.gov.bcb.pix0122testeunit130@gmail.com"));
+    assert!(cobranca.cobranca.payload.contains("5406250.00"));
+    assert!(cobranca.cobranca.payload.contains("6009Sao Paulo"));
+    verifica_crc(&cobranca.cobranca.payload);
+    assert!(cobranca.qrcode.contains("<svg"));
+
+    // A cobrança pendente é reaproveitada.
+    let (status, repetida) = gera_cobranca_pix(State(pool_testes()), locador.clone(), input()).await.unwrap();
+    assert_eq!(status.as_u16(), 200);
+    assert_eq!(repetida.cobranca.txid, cobranca.cobranca.txid);
+
+    // Parte da fatura foi paga fora do PIX: a cobrança do valor antigo é cancelada e não aceita
+    // pagamento, e a nova é só do que falta.
+    registra_pagamento_fatura(State(pool_testes()), locador.clone(), Json(PagamentoFaturaInput{
+        idfatura: fatura.idfatura.clone(),
+        valor: Dinheiro::de_reais(50)
+    })).await.unwrap();
+    let res = registra_pagamento_pix(&pool_testes(), ConfirmacaoPagamentoPixInput{
+        txid: cobranca.cobranca.txid.clone(),
+        valor: Dinheiro::de_reais(250),
+        idpagamento: "E129".to_string()
+    }).await;
+    assert!(matches!(res, Err(ErroApi::Conflito(_))));
+    let (status, cobranca) = gera_cobranca_pix(State(pool_testes()), locatario.clone(), input()).await.unwrap();
+    assert_eq!(status.as_u16(), 201);
+    assert_ne!(cobranca.cobranca.txid, repetida.cobranca.txid);
+    assert_eq!(cobranca.cobranca.valor, Dinheiro::de_reais(200));
+
+    let pagamento = |valor: Dinheiro, idpagamento: &str| ConfirmacaoPagamentoPixInput{
+        txid: cobranca.cobranca.txid.clone(),
+        valor,
+        idpagamento: idpagamento.to_string()
+    };
+    let res = registra_pagamento_pix(&pool_testes(), pagamento(Dinheiro::de_reais(25), "E130")).await;
+    assert!(matches!(res, Err(ErroApi::Validacao{..})));
+    let paga = registra_pagamento_pix(&pool_testes(), pagamento(Dinheiro::de_reais(200), "E130")).await.unwrap();
+    assert_eq!(paga.status, StatusCobranca::Paga);
+    assert!(paga.datapagamento.is_some());
+    let quitada = models::faturas::busca_fatura_id(conn, fatura.idfatura.clone()).await.unwrap();
+    assert_eq!(quitada.status, StatusFatura::Paga);
+    assert_eq!(quitada.valorpago, Dinheiro::de_reais(250));
+    // O provedor pode repetir o aviso, mas outro pagamento da mesma cobrança é recusado.
+    assert!(registra_pagamento_pix(&pool_testes(), pagamento(Dinheiro::de_reais(200), "E130")).await.is_ok());
+    assert_eq!(models::faturas::busca_fatura_id(conn, fatura.idfatura.clone()).await.unwrap().valorpago, Dinheiro::de_reais(250));
+    let res = registra_pagamento_pix(&pool_testes(), pagamento(Dinheiro::de_reais(200), "E131")).await;
+    assert!(matches!(res, Err(ErroApi::Conflito(_))));
+
+    let res = gera_cobranca_pix(State(pool_testes()), locatario.clone(), input()).await;
+    assert!(matches!(res, Err(ErroApi::Conflito(_))));
+    let cobrancas = busca_cobrancas_pix_idcontrato(State(pool_testes()), locador.clone(), Query(IdInput{id: idcontrato.clone()})).await.unwrap().1.0;
+    assert_eq!(cobrancas.len(), 2);
+    assert_eq!(cobrancas[0].status, StatusCobranca::Cancelada);
+    assert_eq!(cobrancas[1].idpagamento.as_deref(), Some("E130"));
+
+    let notificacoes = models::notificacoes::busca_notificacoes_idusuario(conn, idlocatario.clone()).await.unwrap();
+    assert!(notificacoes.iter().any(|notificacao| notificacao.titulo == "Fatura paga!"));
+
+    let res = deleta_chave_pix(State(pool_testes()), locatario, Query(IdInput{id: chavepix.idchavepix.clone()})).await;
+    assert!(matches!(res, Err(ErroApi::Proibido(_))));
+    assert!(deleta_chave_pix(State(pool_testes()), locador, Query(IdInput{id: chavepix.idchavepix})).await.is_ok());
+
+    let ids = [idlocador.clone(), idlocatario.clone()];
+    executa(conn, move |conn| {
+        use crate::schema::{contratos, notificacoes};
+
+        diesel::delete(contratos::table.filter(contratos::idcontrato.eq(idcontrato)))
+            .execute(conn)
+            .map_err(ErroApi::from)?;
+        diesel::delete(notificacoes::table.filter(notificacoes::idusuario.eq_any(ids)))
+            .execute(conn)
+            .map_err(ErroApi::from)
+    }).await.unwrap();
+    assert!(deleta_usuario(idlocador).await.is_ok());
+    assert!(deleta_usuario(idlocatario).await.is_ok());
+    assert!(deleta_maquina_id(idmaquina).await.is_ok());
+}
@@ -0,0 +1,209 @@
+use std::str::FromStr;
+
+use axum::{extract::{Query, State}, Json};
+
+use crate::{controllers::{maquinas::{cadastra_maquina, deleta_maquina_id}, precos_maquinas::{atualiza_precos_maquina, busca_precos_maquina, orca_aluguel_maquina, valida_precos_maquina, DescontoMaquinaInput, OrcamentoAluguelInput, PrecosMaquinaInput, TarifaMaquinaInput}, solicitacoes_contratos::{cadastra_solicitacao_contrato, SolicitacaoContratoInput}, usuarios::{cadastra_usuario, IdInput}}, dinheiro::{Dinheiro, Percentual}, erros::ErroApi, models::{precos_maquinas::{DescontoMaquina, MedidaTempo, PrecoMaquina}, usuarios::deleta_usuario}, precos::{calcula_orcamento, ConfiguracaoPrecos, TabelaPrecos, TipoItemOrcamento}, tests::{maquinas::{converte_tipo_maquina, maquina_padrao}, pool_testes, sessoes::usuario_autenticado, usuarios::usuario_padrao}};
+
+fn tabela_precos(precos: Vec<(MedidaTempo, Dinheiro, f64)>, descontos: Vec<(i32, i64)>) -> TabelaPrecos{
+    TabelaPrecos{
+        valoraluguel: Dinheiro::de_reais(900),
+        precos: precos.into_iter().map(|(medida, valor, prazominimo)| PrecoMaquina{
+            idmaquina: "idmaquina".to_string(),
+            medida,
+            valor,
+            prazominimo
+        }).collect(),
+        descontos: descontos.into_iter().map(|(diasminimos, percentualdesconto)| DescontoMaquina{
+            idmaquina: "idmaquina".to_string(),
+            diasminimos,
+            percentualdesconto: Percentual::de_inteiro(percentualdesconto)
+        }).collect()
+    }
+}
+
+fn valores_itens(tabela: &TabelaPrecos, medida: MedidaTempo, prazo: f64, configuracao: &ConfiguracaoPrecos) -> Vec<(TipoItemOrcamento, i64)>{
+    let orcamento = calcula_orcamento(tabela, medida, prazo, configuracao).unwrap();
+    assert_eq!(orcamento.total, orcamento.itens.iter().map(|item| item.valor).sum());
+    orcamento.itens.iter().map(|item| (item.tipo, item.valor.centavos())).collect()
+}
+
+#[test]
+fn test_medida_tempo(){
+    assert_eq!(MedidaTempo::from_str(" Semanas ").unwrap(), MedidaTempo::Semanas);
+    assert!(matches!(MedidaTempo::from_str("Anos"), Err(ErroApi::Validacao{campo: Some(campo), ..}) if campo == "medidatempolocacao"));
+    assert_eq!(serde_json::to_string(&MedidaTempo::Horas).unwrap(), "\"Horas\"");
+    assert!(serde_json::from_str::<MedidaTempo>("\"horas\"").is_err());
+    assert_eq!(MedidaTempo::Horas.em_dias(36.0), 1.5);
+    assert_eq!(MedidaTempo::Meses.em_dias(2.0), 60.0);
+}
+
+#[test]
+fn test_orcamento_valor_mensal(){
+    // Sem tarifas próprias, R$ 900,00 por mês valem R$ 30,00 por dia e R$ 1,25 por hora.
+    let tabela = tabela_precos(vec![], vec![]);
+    let configuracao = ConfiguracaoPrecos::default();
+    assert_eq!(valores_itens(&tabela, MedidaTempo::Dias, 3.0, &configuracao),
+        vec![(TipoItemOrcamento::Aluguel, 9000), (TipoItemOrcamento::Taxa, 450)]);
+    assert_eq!(valores_itens(&tabela, MedidaTempo::Horas, 10.0, &configuracao),
+        vec![(TipoItemOrcamento::Aluguel, 1250), (TipoItemOrcamento::Taxa, 63)]);
+
+    let orcamento = calcula_orcamento(&tabela, MedidaTempo::Meses, 2.0, &configuracao).unwrap();
+    assert_eq!(orcamento.valorunidade, Dinheiro::de_reais(900));
+    assert_eq!(orcamento.total, Dinheiro::de_reais(1890));
+
+    assert!(calcula_orcamento(&tabela, MedidaTempo::Dias, 0.0, &configuracao).is_err());
+    assert!(calcula_orcamento(&tabela, MedidaTempo::Dias, f64::NAN, &configuracao).is_err());
+    assert!(calcula_orcamento(&tabela, MedidaTempo::Meses, 1000.0, &configuracao).is_err());
+}
+
+#[test]
+fn test_orcamento_tarifa_propria_e_descontos(){
+    let tabela = tabela_precos(vec![(MedidaTempo::Dias, Dinheiro::de_reais(50), 2.0)], vec![(30, 10), (7, 5)]);
+    let configuracao = ConfiguracaoPrecos::default();
+
+    let res = calcula_orcamento(&tabela, MedidaTempo::Dias, 1.0, &configuracao);
+    assert!(matches!(res, Err(ErroApi::Validacao{campo: Some(campo), ..}) if campo == "prazolocacao"));
+    // Abaixo da primeira faixa, não há desconto.
+    assert_eq!(valores_itens(&tabela, MedidaTempo::Dias, 2.0, &configuracao),
+        vec![(TipoItemOrcamento::Aluguel, 10000), (TipoItemOrcamento::Taxa, 500)]);
+    // Vale a maior faixa alcançada, e a taxa incide sobre o aluguel com desconto.
+    assert_eq!(valores_itens(&tabela, MedidaTempo::Dias, 40.0, &configuracao),
+        vec![(TipoItemOrcamento::Aluguel, 200000), (TipoItemOrcamento::Desconto, -20000), (TipoItemOrcamento::Taxa, 9000)]);
+    // As faixas contam em dias em qualquer medida; as semanas saem do valor mensal, a 7/30 dele.
+    assert_eq!(valores_itens(&tabela, MedidaTempo::Semanas, 2.0, &configuracao),
+        vec![(TipoItemOrcamento::Aluguel, 42000), (TipoItemOrcamento::Desconto, -2100), (TipoItemOrcamento::Taxa, 1995)]);
+}
+
+#[test]
+fn test_orcamento_mesmo_periodo_em_medidas_diferentes(){
+    // Sem tarifas próprias, o mesmo período custa o mesmo em qualquer medida.
+    let tabela = tabela_precos(vec![], vec![(7, 5)]);
+    let configuracao = ConfiguracaoPrecos::default();
+    let semanas = valores_itens(&tabela, MedidaTempo::Semanas, 2.0, &configuracao);
+    assert_eq!(semanas, valores_itens(&tabela, MedidaTempo::Dias, 14.0, &configuracao));
+    assert_eq!(semanas, valores_itens(&tabela, MedidaTempo::Horas, 336.0, &configuracao));
+    assert_eq!(valores_itens(&tabela, MedidaTempo::Meses, 1.0, &configuracao),
+        valores_itens(&tabela, MedidaTempo::Dias, 30.0, &configuracao));
+    assert_eq!(MedidaTempo::Semanas.em_dias(2.0), 14.0);
+}
+
+#[test]
+fn test_orcamento_taxa_minima(){
+    let tabela = tabela_precos(vec![], vec![]);
+    let configuracao = ConfiguracaoPrecos{
+        percentual_taxa: Percentual::de_inteiro(5),
+        taxa_minima: Dinheiro::de_reais(10)
+    };
+    let orcamento = calcula_orcamento(&tabela, MedidaTempo::Horas, 10.0, &configuracao).unwrap();
+    assert_eq!(orcamento.itens[1].valor, Dinheiro::de_reais(10));
+    assert_eq!(orcamento.itens[1].descricao, "Taxa mínima da plataforma.");
+    assert_eq!(orcamento.total, Dinheiro::de_centavos(2250));
+
+    let sem_taxa = ConfiguracaoPrecos{
+        percentual_taxa: Percentual::ZERO,
+        taxa_minima: Dinheiro::ZERO
+    };
+    assert_eq!(valores_itens(&tabela, MedidaTempo::Horas, 10.0, &sem_taxa), vec![(TipoItemOrcamento::Aluguel, 1250)]);
+}
+
+#[test]
+fn test_configuracao_precos(){
+    assert_eq!(ConfiguracaoPrecos::de_variaveis(|_| None).unwrap(), ConfiguracaoPrecos::default());
+
+    let configuracao = ConfiguracaoPrecos::de_variaveis(|nome| match nome{
+        "PRECO_TAXA_PERCENTUAL" => Some(" 7.5 ".to_string()),
+        "PRECO_TAXA_MINIMA" => Some("2.50".to_string()),
+        _ => None
+    }).unwrap();
+    assert_eq!(configuracao.percentual_taxa, Percentual::de_pontos_base(750));
+    assert_eq!(configuracao.taxa_minima, Dinheiro::de_centavos(250));
+
+    assert!(ConfiguracaoPrecos::de_variaveis(|nome| (nome == "PRECO_TAXA_PERCENTUAL").then(|| "150".to_string())).is_err());
+    assert!(ConfiguracaoPrecos::de_variaveis(|nome| (nome == "PRECO_TAXA_PERCENTUAL").then(|| "cinco".to_string())).is_err());
+    assert!(ConfiguracaoPrecos::de_variaveis(|nome| (nome == "PRECO_TAXA_MINIMA").then(|| "-1".to_string())).is_err());
+}
+
+fn precos_input(idmaquina: &str, tarifas: Vec<(MedidaTempo, Dinheiro, Option<f64>)>, descontos: Vec<(i32, i64)>) -> PrecosMaquinaInput{
+    PrecosMaquinaInput{
+        idmaquina: idmaquina.to_string(),
+        tarifas: tarifas.into_iter().map(|(medida, valor, prazominimo)| TarifaMaquinaInput{
+            medida,
+            valor,
+            prazominimo
+        }).collect(),
+        descontos: descontos.into_iter().map(|(diasminimos, percentualdesconto)| DescontoMaquinaInput{
+            diasminimos,
+            percentualdesconto: Percentual::de_inteiro(percentualdesconto)
+        }).collect()
+    }
+}
+
+#[test]
+fn test_valida_precos_maquina(){
+    let (precos, descontos) = valida_precos_maquina(&precos_input(" idmaquina ",
+        vec![(MedidaTempo::Dias, Dinheiro::de_reais(50), None)], vec![(30, 10), (7, 5)])).unwrap();
+    assert_eq!(precos[0].idmaquina, "idmaquina");
+    assert_eq!(precos[0].prazominimo, 0.0);
+    assert_eq!(descontos.iter().map(|desconto| desconto.diasminimos).collect::<Vec<_>>(), vec![7, 30]);
+
+    let invalidos = [
+        precos_input("idmaquina", vec![(MedidaTempo::Dias, Dinheiro::de_reais(50), None), (MedidaTempo::Dias, Dinheiro::de_reais(40), None)], vec![]),
+        precos_input("idmaquina", vec![(MedidaTempo::Horas, Dinheiro::ZERO, None)], vec![]),
+        precos_input("idmaquina", vec![(MedidaTempo::Horas, Dinheiro::de_reais(5), Some(-1.0))], vec![]),
+        precos_input("idmaquina", vec![], vec![(0, 5)]),
+        precos_input("idmaquina", vec![], vec![(7, 5), (7, 10)]),
+        precos_input("idmaquina", vec![], vec![(7, 100)]),
+        precos_input("idmaquina", vec![], vec![(7, 0)])
+    ];
+    for input in invalidos{
+        assert!(matches!(valida_precos_maquina(&input), Err(ErroApi::Validacao{..})));
+    }
+}
+
+#[tokio::test]
+async fn test_atualiza_precos_e_orca_aluguel(){
+    let usuario = cadastra_usuario(State(pool_testes()), Json(usuario_padrao("995"))).await.unwrap().1;
+    let idusuario = usuario.0.idusuario.to_string();
+    let idmaquina = cadastra_maquina(State(pool_testes()), usuario_autenticado(&idusuario), Json(
+        converte_tipo_maquina(maquina_padrao("995").await, idusuario.clone()).await)).await.unwrap().1.0.idmaquina;
+
+    let input = || precos_input(&idmaquina, vec![(MedidaTempo::Dias, Dinheiro::de_reais(50), Some(2.0))], vec![(7, 10)]);
+    let res = atualiza_precos_maquina(State(pool_testes()), usuario_autenticado("outrousuario995"), Json(input())).await;
+    assert!(matches!(res, Err(ErroApi::Proibido(_))));
+
+    let precos = atualiza_precos_maquina(State(pool_testes()), usuario_autenticado(&idusuario), Json(input())).await.unwrap().1.0;
+    assert_eq!(precos.tarifas.len(), 4);
+    assert!(precos.tarifas.iter().all(|tarifa| tarifa.propria == (tarifa.medida == MedidaTempo::Dias)));
+
+    let orca = |medida, prazo| orca_aluguel_maquina(State(pool_testes()), Json(OrcamentoAluguelInput{
+        idmaquina: idmaquina.clone(),
+        medida,
+        prazo
+    }));
+    assert!(matches!(orca(MedidaTempo::Dias, 1.0).await, Err(ErroApi::Validacao{..})));
+    let orcamento = orca(MedidaTempo::Dias, 10.0).await.unwrap().1.0;
+    assert_eq!(orcamento.itens[0].valor, Dinheiro::de_reais(500));
+    assert_eq!(orcamento.itens[1].valor, Dinheiro::de_reais(-50));
+
+    // O valor da solicitação precisa ser o total do orçamento.
+    let res = cadastra_solicitacao_contrato(State(pool_testes()), usuario_autenticado("idlocatario995"), Json(SolicitacaoContratoInput{
+        idlocador: idusuario.clone(),
+        idlocatario: "idlocatario995".to_string(),
+        idmaquina: idmaquina.clone(),
+        medidatempolocacao: MedidaTempo::Dias,
+        origemsolicitacao: "Teste".to_string(),
+        valorsolicitacao: orcamento.total - Dinheiro::de_centavos(1),
+        prazolocacao: 10.0,
+        datainicio: None
+    })).await;
+    assert!(matches!(res, Err(ErroApi::Validacao{campo: Some(campo), ..}) if campo == "valorsolicitacao"));
+
+    // Uma tabela vazia volta a usar só o valor mensal.
+    atualiza_precos_maquina(State(pool_testes()), usuario_autenticado(&idusuario), Json(precos_input(&idmaquina, vec![], vec![]))).await.unwrap();
+    let precos = busca_precos_maquina(State(pool_testes()), Query(IdInput{id: idmaquina.clone()})).await.unwrap().1.0;
+    assert!(precos.tarifas.iter().all(|tarifa| !tarifa.propria));
+    assert!(precos.descontos.is_empty());
+
+    assert!(deleta_usuario(idusuario).await.is_ok());
+    assert!(deleta_maquina_id(idmaquina).await.is_ok());
+}
@@ -0,0 +1,142 @@
+use axum::{extract::{Query, State}, Json};
+use chrono::{NaiveDate, NaiveDateTime, TimeDelta};
+use diesel::{ExpressionMethods, QueryDsl, RunQueryDsl};
+
+use crate::{controllers::{maquinas::{cadastra_maquina, deleta_maquina_id}, obtem_conn, reservas_maquinas::{busca_calendario_maquina, CalendarioMaquinaInput}, usuarios::cadastra_usuario}, dinheiro::{Dinheiro, Percentual}, erros::ErroApi, models::{self, contratos::StatusContrato, encargos_contratos::RegrasAtraso, executa, precos_maquinas::MedidaTempo, reservas_maquinas::{calcula_fim_locacao, ReservaMaquina}, solicitacoes_contratos::{SolicitacaoContrato, StatusSolicitacao}, usuarios::deleta_usuario}, precos::Tarifa, tests::{contratos::contrato_padrao, maquinas::{converte_tipo_maquina, maquina_padrao}, pool_testes, sessoes::usuario_autenticado, usuarios::usuario_padrao}};
+
+fn data(dia: u32, hora: u32) -> NaiveDateTime{
+    NaiveDate::from_ymd_opt(2031, 1, dia).unwrap().and_hms_opt(hora, 0, 0).unwrap()
+}
+
+pub fn solicitacao_reserva(idmaquina: &str, numeroteste: &str, inicio: NaiveDateTime, dias: f64)
+    -> (SolicitacaoContrato, ReservaMaquina){
+    let idsolicitacao = format!("idsolicitacao{}", numeroteste);
+    let solicitacao = SolicitacaoContrato{
+        idsolicitacao: idsolicitacao.clone(),
+        idlocador: format!("idlocador{}", numeroteste),
+        idlocatario: format!("idlocatario{}", numeroteste),
+        idmaquina: idmaquina.to_string(),
+        medidatempolocacao: MedidaTempo::Dias,
+        origemsolicitacao: "Teste".to_string(),
+        statussolicitacao: StatusSolicitacao::AguardandoAprovacao,
+        prazolocacao: dias,
+        valorsolicitacao: Dinheiro::de_reais(100),
+        datasolicitacao: chrono::Utc::now().naive_utc()
+    };
+    let reserva = ReservaMaquina{
+        idreserva: format!("idreserva{}", numeroteste),
+        idmaquina: idmaquina.to_string(),
+        idsolicitacao,
+        datainicio: inicio,
+        datafim: calcula_fim_locacao(inicio, dias, MedidaTempo::Dias).unwrap()
+    };
+    (solicitacao, reserva)
+}
+
+#[test]
+fn test_calcula_fim_locacao(){
+    assert_eq!(calcula_fim_locacao(data(1, 8), 1.5, MedidaTempo::Horas).unwrap(), data(1, 9) + TimeDelta::minutes(30));
+    assert_eq!(calcula_fim_locacao(data(1, 8), 2.0, MedidaTempo::Semanas).unwrap(), data(15, 8));
+    // Um mês depois de 31 de janeiro é o último dia de fevereiro.
+    assert_eq!(calcula_fim_locacao(data(31, 8), 1.0, MedidaTempo::Meses).unwrap(),
+        NaiveDate::from_ymd_opt(2031, 2, 28).unwrap().and_hms_opt(8, 0, 0).unwrap());
+
+    assert!(calcula_fim_locacao(data(1, 8), 0.0, MedidaTempo::Dias).is_err());
+    assert!(calcula_fim_locacao(data(1, 8), 1.5, MedidaTempo::Meses).is_err());
+}
+
+#[tokio::test]
+async fn test_reserva_sobreposta_err(){
+    let usuario = cadastra_usuario(State(pool_testes()), Json(usuario_padrao("950"))).await.unwrap().1;
+    let idusuario = usuario.0.idusuario.to_string();
+    let idmaquina = cadastra_maquina(State(pool_testes()), usuario_autenticado(&idusuario), Json(
+        converte_tipo_maquina(maquina_padrao("950").await, idusuario.clone()).await)).await.unwrap().1.0.idmaquina;
+
+    let conn = &obtem_conn(&pool_testes()).await.unwrap();
+
+    // Do dia 10 ao dia 15, aprovada.
+    let (solicitacao, reserva) = solicitacao_reserva(&idmaquina, "950", data(10, 8), 5.0);
+    let idaprovada = models::solicitacoes_contratos::cadastra_solicitacao_contrato(conn, solicitacao, reserva).await.unwrap();
+    models::solicitacoes_contratos::atualiza_status_solicitacao(conn, idaprovada.clone(), StatusSolicitacao::Aprovada,
+        Some("idlocador950".to_string())).await.unwrap();
+
+    // Do dia 14 ao dia 16: conflita com a aprovada.
+    let (solicitacao, reserva) = solicitacao_reserva(&idmaquina, "951", data(14, 8), 2.0);
+    let res = models::solicitacoes_contratos::cadastra_solicitacao_contrato(conn, solicitacao, reserva).await;
+    assert!(matches!(res, Err(ErroApi::Conflito(_))));
+
+    // Do dia 15 ao dia 17: começa quando a aprovada termina.
+    let (solicitacao, reserva) = solicitacao_reserva(&idmaquina, "952", data(15, 8), 2.0);
+    assert!(models::solicitacoes_contratos::cadastra_solicitacao_contrato(conn, solicitacao, reserva).await.is_ok());
+
+    let calendario = busca_calendario_maquina(State(pool_testes()), Query(CalendarioMaquinaInput{
+        idmaquina: idmaquina.clone(),
+        inicio: data(1, 0),
+        fim: data(31, 0)
+    })).await.unwrap().1.0;
+    assert_eq!(calendario.len(), 1);
+    assert_eq!(calendario[0].datainicio, data(10, 8));
+
+    executa(conn, |conn| {
+        use crate::schema::solicitacoes_contratos::dsl::*;
+
+        diesel::delete(solicitacoes_contratos.filter(idsolicitacao.eq_any(["idsolicitacao950", "idsolicitacao952"])))
+            .execute(conn)
+            .map_err(ErroApi::from)
+    }).await.unwrap();
+    assert!(deleta_usuario(idusuario).await.is_ok());
+    assert!(deleta_maquina_id(idmaquina).await.is_ok());
+}
+
+#[tokio::test]
+async fn test_reserva_atrasada_ocupa_maquina(){
+    let usuario = cadastra_usuario(State(pool_testes()), Json(usuario_padrao("953"))).await.unwrap().1;
+    let idusuario = usuario.0.idusuario.to_string();
+    let idmaquina = cadastra_maquina(State(pool_testes()), usuario_autenticado(&idusuario), Json(
+        converte_tipo_maquina(maquina_padrao("953").await, idusuario.clone()).await)).await.unwrap().1.0.idmaquina;
+
+    let conn = &obtem_conn(&pool_testes()).await.unwrap();
+
+    // O período terminou há cinco dias, mas a máquina não foi devolvida.
+    let agora = chrono::Utc::now().naive_utc();
+    let (solicitacao, reserva) = solicitacao_reserva(&idmaquina, "953", agora - TimeDelta::days(10), 5.0);
+    let idatrasada = models::solicitacoes_contratos::cadastra_solicitacao_contrato(conn, solicitacao, reserva).await.unwrap();
+    models::solicitacoes_contratos::aprova_solicitacao(conn, contrato_padrao(&idmaquina, "953", 5.0),
+        "idlocador953".to_string(), false).await.unwrap();
+
+    let terminados = models::contratos::busca_contratos_terminados(conn, agora).await.unwrap();
+    assert!(terminados.iter().any(|contrato| contrato.idcontrato == "idcontrato953"));
+    let atrasado = models::contratos::encerra_periodo_contrato(conn, "idcontrato953".to_string()).await.unwrap();
+    assert_eq!(atrasado.statuscontrato, StatusContrato::Atrasado);
+    assert!(atrasado.dataavisoatraso.is_some());
+    let res = models::contratos::encerra_periodo_contrato(conn, "idcontrato953".to_string()).await;
+    assert!(matches!(res, Err(ErroApi::Conflito(_))));
+
+    // Enquanto o contrato estiver atrasado, nenhum período novo fica livre.
+    let (solicitacao, reserva) = solicitacao_reserva(&idmaquina, "954", agora + TimeDelta::days(30), 2.0);
+    let res = models::solicitacoes_contratos::cadastra_solicitacao_contrato(conn, solicitacao.clone(), reserva.clone()).await;
+    assert!(matches!(res, Err(ErroApi::Conflito(_))));
+
+    // A devolução conclui o contrato atrasado.
+    let regras = RegrasAtraso{
+        tarifahora: Tarifa::unitaria(Dinheiro::de_reais(1)),
+        percentualmulta: Percentual::ZERO,
+        percentualjurosmensal: Percentual::ZERO
+    };
+    let (concluido, _, _) = models::contratos::registra_devolucao_contrato(conn, "idcontrato953".to_string(), agora, regras).await.unwrap();
+    assert_eq!(concluido.statuscontrato, StatusContrato::Concluido);
+    assert!(models::solicitacoes_contratos::cadastra_solicitacao_contrato(conn, solicitacao, reserva).await.is_ok());
+
+    executa(conn, move |conn| {
+        use crate::schema::{contratos, solicitacoes_contratos};
+
+        diesel::delete(contratos::table.filter(contratos::idcontrato.eq("idcontrato953")))
+            .execute(conn)
+            .map_err(ErroApi::from)?;
+        diesel::delete(solicitacoes_contratos::table.filter(solicitacoes_contratos::idsolicitacao.eq_any([idatrasada, "idsolicitacao954".to_string()])))
+            .execute(conn)
+            .map_err(ErroApi::from)
+    }).await.unwrap();
+    assert!(deleta_usuario(idusuario).await.is_ok());
+    assert!(deleta_maquina_id(idmaquina).await.is_ok());
+}
@@ -0,0 +1,82 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::{controllers::gera_hash, senhas::{ConfiguracaoSenhas, ParametrosArgon2, PoliticaSenha, VerificacaoSenha}};
+
+fn configuracao_teste() -> ConfiguracaoSenhas{
+    ConfiguracaoSenhas{
+        argon2: ParametrosArgon2{
+            memoria: 1024,
+            iteracoes: 1,
+            paralelismo: 1
+        },
+        politica: PoliticaSenha::default()
+    }
+}
+
+#[test]
+fn test_hash_argon2id(){
+    let configuracao = configuracao_teste();
+    let hash = configuracao.gera_hash("SenhaTeste01.").unwrap();
+    assert!(hash.starts_with("$argon2id$v=19$m=1024,t=1,p=1$"));
+    // O sal é aleatório.
+    assert_ne!(hash, configuracao.gera_hash("SenhaTeste01.").unwrap());
+
+    assert_eq!(configuracao.verifica("SenhaTeste01.", &hash), VerificacaoSenha::Correta);
+    assert_eq!(configuracao.verifica("SenhaTeste02.", &hash), VerificacaoSenha::Incorreta);
+    assert_eq!(configuracao.verifica("SenhaTeste01.", "não é um hash"), VerificacaoSenha::Incorreta);
+}
+
+#[test]
+fn test_verifica_hash_antigo(){
+    let configuracao = configuracao_teste();
+
+    let bcrypt = gera_hash("SenhaTeste01.");
+    assert_eq!(configuracao.verifica("SenhaTeste01.", &bcrypt), VerificacaoSenha::PrecisaAtualizar);
+    assert_eq!(configuracao.verifica("SenhaTeste02.", &bcrypt), VerificacaoSenha::Incorreta);
+
+    let mut mais_forte = configuracao_teste();
+    mais_forte.argon2.iteracoes = 2;
+    let hash = configuracao.gera_hash("SenhaTeste01.").unwrap();
+    assert_eq!(mais_forte.verifica("SenhaTeste01.", &hash), VerificacaoSenha::PrecisaAtualizar);
+    assert!(mais_forte.verifica("SenhaTeste01.", &hash).correta());
+}
+
+#[test]
+fn test_politica_senha(){
+    let mut politica = PoliticaSenha::default();
+    assert!(politica.valida("SenhaForte01_").is_ok());
+    assert!(politica.valida(&format!("Aa1.{}", "a".repeat(200))).is_err());
+
+    politica.exige_simbolo = false;
+    politica.exige_minuscula = true;
+    assert!(politica.valida("SenhaForte01").is_ok());
+    assert!(politica.valida("SENHAFORTE01").is_err());
+
+    politica.senhas_vazadas = HashSet::from(["senhaforte01".to_string()]);
+    assert_eq!(politica.valida("SenhaForte01").unwrap_err(), "Essa senha aparece em vazamentos de dados conhecidos. Escolha outra.");
+}
+
+#[test]
+fn test_configuracao_senhas_variaveis(){
+    let arquivo = std::env::temp_dir().join(format!("senhas-vazadas-{}.txt", std::process::id()));
+    std::fs::write(&arquivo, "# lista de teste\nSenha123!\n\nqwerty\n").unwrap();
+
+    let variaveis: HashMap<&str, String> = HashMap::from([
+        ("SENHA_ARGON2_ITERACOES", "3".to_string()),
+        ("SENHA_TAMANHO_MINIMO", "12".to_string()),
+        ("SENHA_EXIGE_SIMBOLO", "false".to_string()),
+        ("SENHA_LISTA_VAZADAS", arquivo.display().to_string()),
+    ]);
+    let configuracao = ConfiguracaoSenhas::de_variaveis(|nome| variaveis.get(nome).cloned()).unwrap();
+    std::fs::remove_file(&arquivo).unwrap();
+
+    assert_eq!(configuracao.argon2.iteracoes, 3);
+    assert_eq!(configuracao.argon2.memoria, ParametrosArgon2::default().memoria);
+    assert_eq!(configuracao.politica.tamanho_minimo, 12);
+    assert!(!configuracao.politica.exige_simbolo);
+    assert_eq!(configuracao.politica.senhas_vazadas, HashSet::from(["senha123!".to_string(), "qwerty".to_string()]));
+
+    assert!(ConfiguracaoSenhas::de_variaveis(|nome| (nome == "SENHA_ARGON2_ITERACOES").then(|| "0".to_string())).is_err());
+    assert!(ConfiguracaoSenhas::de_variaveis(|nome| (nome == "SENHA_LISTA_VAZADAS").then(|| "/nao/existe.txt".to_string())).is_err());
+    assert!(ConfiguracaoSenhas::de_variaveis(|nome| (nome == "SENHA_EXIGE_NUMERO").then(|| "sim".to_string())).is_err());
+}
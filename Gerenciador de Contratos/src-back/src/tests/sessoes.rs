@@ -0,0 +1,82 @@
+use axum::{extract::State, Json};
+
+use crate::{controllers::{obtem_conn, sessoes::{gera_tokens_sessao, renova_sessao, valida_token, verifica_usuario, RenovaSessaoInput, UsuarioAutenticado}, usuarios::{cadastra_usuario, realiza_login, CredenciaisUsuario}}, erros::ErroApi, models::{papeis_usuarios::Papel, usuarios::{atualiza_status_usuario, deleta_usuario, StatusUsuario}}, tests::{pool_testes, usuarios::{ativa_usuario_teste, usuario_padrao}}};
+
+pub fn usuario_autenticado(idusuario: &str) -> UsuarioAutenticado{
+    UsuarioAutenticado{
+        idusuario: idusuario.to_string(),
+        papeis: vec![]
+    }
+}
+
+pub fn admin_autenticado(idusuario: &str) -> UsuarioAutenticado{
+    UsuarioAutenticado{
+        idusuario: idusuario.to_string(),
+        papeis: vec![Papel::Admin]
+    }
+}
+
+#[tokio::test]
+async fn test_gera_tokens_sessao_ok(){
+    let tokens = gera_tokens_sessao("idteste800").unwrap();
+
+    let claims = valida_token(&tokens.token_acesso, "acesso").unwrap();
+    assert_eq!(claims.sub, "idteste800");
+
+    let claims = valida_token(&tokens.token_renovacao, "renovacao").unwrap();
+    assert_eq!(claims.sub, "idteste800");
+}
+
+#[tokio::test]
+async fn test_valida_token_err(){
+    let tokens = gera_tokens_sessao("idteste801").unwrap();
+
+    assert!(valida_token(&tokens.token_renovacao, "acesso").is_err());
+    assert!(valida_token(&tokens.token_acesso, "renovacao").is_err());
+    assert!(valida_token("token.invalido.801", "acesso").is_err());
+}
+
+#[tokio::test]
+async fn test_verifica_usuario(){
+    let usuario = usuario_autenticado("idteste802");
+
+    assert!(verifica_usuario(&usuario, "idteste802").is_ok());
+    assert!(verifica_usuario(&usuario, "outroid802").is_err());
+}
+
+#[tokio::test]
+async fn test_renova_sessao_ok(){
+    let usuario = usuario_padrao("803");
+    let email = usuario.email.clone();
+    let senha = usuario.senha.clone();
+
+    let usuario = cadastra_usuario(State(pool_testes()), Json(usuario)).await.unwrap().1;
+    let id = usuario.0.idusuario.to_string();
+    ativa_usuario_teste(&id).await;
+
+    let tokens = realiza_login(State(pool_testes()), Json(CredenciaisUsuario{
+        email,
+        senha
+    })).await.unwrap().1.0;
+    assert_eq!(tokens.idusuario, id);
+
+    let renova = |token_renovacao: String| renova_sessao(State(pool_testes()), Json(RenovaSessaoInput{
+        token_renovacao
+    }));
+    let renovados = renova(tokens.token_renovacao.clone()).await.unwrap().1.0;
+    assert_eq!(renovados.idusuario, id);
+
+    assert!(renova(renovados.token_acesso.clone()).await.is_err());
+    // Cada token de renovação vale uma vez só.
+    assert!(matches!(renova(tokens.token_renovacao).await, Err(ErroApi::NaoAutorizado(_))));
+    let renovados = renova(renovados.token_renovacao).await.unwrap().1.0;
+
+    // Contas suspensas ou apagadas não renovam a sessão.
+    let conn = &obtem_conn(&pool_testes()).await.unwrap();
+    atualiza_status_usuario(conn, id.clone(), StatusUsuario::Suspenso).await.unwrap();
+    let proximos = gera_tokens_sessao(&id).unwrap();
+    assert!(matches!(renova(proximos.token_renovacao).await, Err(ErroApi::ContaSuspensa(_))));
+
+    assert!(deleta_usuario(id).await.is_ok());
+    assert!(matches!(renova(renovados.token_renovacao).await, Err(ErroApi::NaoAutorizado(_))));
+}
@@ -0,0 +1,211 @@
+use std::str::FromStr;
+
+use axum::{extract::{Query, State}, Json};
+use diesel::{ExpressionMethods, QueryDsl, RunQueryDsl};
+
+use crate::{controllers::{maquinas::{cadastra_maquina, deleta_maquina_id}, notificacoes::busca_notificacoes_idusuario, obtem_conn, solicitacoes_contratos::{busca_solicitacao_idsolicitacao, busca_solicitacoes_idlocador, busca_solicitacoes_idlocatario, cadastra_solicitacao_contrato, SolicitacaoContratoInput}, usuarios::{cadastra_usuario, IdInput}}, dinheiro::Dinheiro, erros::ErroApi, models::{self, executa, precos_maquinas::MedidaTempo, solicitacoes_contratos::{PapelSolicitacao, StatusSolicitacao}, usuarios::deleta_usuario}, tests::{contratos::contrato_padrao, maquinas::{converte_tipo_maquina, maquina_padrao}, pool_testes, reservas_maquinas::solicitacao_reserva, sessoes::{admin_autenticado, usuario_autenticado}, usuarios::usuario_padrao}};
+
+#[test]
+fn test_status_solicitacao_texto(){
+    assert_eq!(StatusSolicitacao::from_str("Solicitação aprovada").unwrap(), StatusSolicitacao::Aprovada);
+    assert_eq!(StatusSolicitacao::Cancelada.to_string(), "Solicitação cancelada");
+    assert!(StatusSolicitacao::from_str("Aprovadíssima").is_err());
+}
+
+#[test]
+fn test_valida_transicao(){
+    let aguardando = StatusSolicitacao::AguardandoAprovacao;
+
+    assert!(aguardando.valida_transicao(StatusSolicitacao::Aprovada, PapelSolicitacao::Locador).is_ok());
+    assert!(aguardando.valida_transicao(StatusSolicitacao::Recusada, PapelSolicitacao::Locador).is_ok());
+    assert!(aguardando.valida_transicao(StatusSolicitacao::Cancelada, PapelSolicitacao::Locatario).is_ok());
+    assert!(aguardando.valida_transicao(StatusSolicitacao::Expirada, PapelSolicitacao::Sistema).is_ok());
+
+    assert!(matches!(aguardando.valida_transicao(StatusSolicitacao::Aprovada, PapelSolicitacao::Locatario), Err(ErroApi::Proibido(_))));
+    assert!(matches!(aguardando.valida_transicao(StatusSolicitacao::Cancelada, PapelSolicitacao::Locador), Err(ErroApi::Proibido(_))));
+    assert!(matches!(aguardando.valida_transicao(StatusSolicitacao::Expirada, PapelSolicitacao::Locador), Err(ErroApi::Proibido(_))));
+
+    // Os status finais não mudam mais.
+    assert!(matches!(StatusSolicitacao::Recusada.valida_transicao(StatusSolicitacao::Aprovada, PapelSolicitacao::Locador), Err(ErroApi::Conflito(_))));
+    assert!(matches!(StatusSolicitacao::Aprovada.valida_transicao(StatusSolicitacao::Cancelada, PapelSolicitacao::Locatario), Err(ErroApi::Conflito(_))));
+
+    // O admin modera, mas não aprova no lugar do locador.
+    assert!(aguardando.valida_transicao(StatusSolicitacao::Recusada, PapelSolicitacao::Admin).is_ok());
+    assert!(aguardando.valida_transicao(StatusSolicitacao::Cancelada, PapelSolicitacao::Admin).is_ok());
+    assert!(matches!(aguardando.valida_transicao(StatusSolicitacao::Aprovada, PapelSolicitacao::Admin), Err(ErroApi::Proibido(_))));
+}
+
+#[tokio::test]
+async fn test_atualiza_status_solicitacao(){
+    let usuario = cadastra_usuario(State(pool_testes()), Json(usuario_padrao("960"))).await.unwrap().1;
+    let idusuario = usuario.0.idusuario.to_string();
+    let idmaquina = cadastra_maquina(State(pool_testes()), usuario_autenticado(&idusuario), Json(
+        converte_tipo_maquina(maquina_padrao("960").await, idusuario.clone()).await)).await.unwrap().1.0.idmaquina;
+
+    let conn = &obtem_conn(&pool_testes()).await.unwrap();
+
+    let inicio = chrono::Utc::now().naive_utc() + chrono::TimeDelta::days(30);
+    let (solicitacao, reserva) = solicitacao_reserva(&idmaquina, "960", inicio, 2.0);
+    let id = models::solicitacoes_contratos::cadastra_solicitacao_contrato(conn, solicitacao, reserva).await.unwrap();
+
+    let res = models::solicitacoes_contratos::atualiza_status_solicitacao(conn, id.clone(), StatusSolicitacao::Aprovada,
+        Some("idlocatario960".to_string())).await;
+    assert!(matches!(res, Err(ErroApi::Proibido(_))));
+    let res = models::solicitacoes_contratos::atualiza_status_solicitacao(conn, id.clone(), StatusSolicitacao::Cancelada,
+        Some("outrousuario960".to_string())).await;
+    assert!(matches!(res, Err(ErroApi::Proibido(_))));
+
+    let solicitacao = models::solicitacoes_contratos::atualiza_status_solicitacao(conn, id.clone(), StatusSolicitacao::Cancelada,
+        Some("idlocatario960".to_string())).await.unwrap();
+    assert_eq!(solicitacao.statussolicitacao, StatusSolicitacao::Cancelada);
+
+    let res = models::solicitacoes_contratos::atualiza_status_solicitacao(conn, id.clone(), StatusSolicitacao::Aprovada,
+        Some("idlocador960".to_string())).await;
+    assert!(matches!(res, Err(ErroApi::Conflito(_))));
+
+    let historico = models::solicitacoes_contratos::busca_historico_solicitacao(conn, id.clone()).await.unwrap();
+    assert_eq!(historico.len(), 2);
+    assert_eq!(historico[1].statusanterior, Some(StatusSolicitacao::AguardandoAprovacao));
+    assert_eq!(historico[1].statusnovo, StatusSolicitacao::Cancelada);
+    assert_eq!(historico[1].papelator, "locatario");
+    assert_eq!(historico[1].idator, Some("idlocatario960".to_string()));
+
+    executa(conn, move |conn| {
+        use crate::schema::solicitacoes_contratos::dsl::*;
+
+        diesel::delete(solicitacoes_contratos.filter(idsolicitacao.eq(id)))
+            .execute(conn)
+            .map_err(ErroApi::from)
+    }).await.unwrap();
+    assert!(deleta_usuario(idusuario).await.is_ok());
+    assert!(deleta_maquina_id(idmaquina).await.is_ok());
+}
+
+#[tokio::test]
+async fn test_modera_solicitacao(){
+    let usuario = cadastra_usuario(State(pool_testes()), Json(usuario_padrao("961"))).await.unwrap().1;
+    let idusuario = usuario.0.idusuario.to_string();
+    let idmaquina = cadastra_maquina(State(pool_testes()), usuario_autenticado(&idusuario), Json(
+        converte_tipo_maquina(maquina_padrao("961").await, idusuario.clone()).await)).await.unwrap().1.0.idmaquina;
+
+    // O locador da solicitação precisa ser o dono da máquina.
+    let res = cadastra_solicitacao_contrato(State(pool_testes()), usuario_autenticado("idlocatario961"), Json(SolicitacaoContratoInput{
+        idlocador: "outrousuario961".to_string(),
+        idlocatario: "idlocatario961".to_string(),
+        idmaquina: idmaquina.clone(),
+        medidatempolocacao: MedidaTempo::Dias,
+        origemsolicitacao: "Teste".to_string(),
+        valorsolicitacao: Dinheiro::de_reais(100),
+        prazolocacao: 2.0,
+        datainicio: None
+    })).await;
+    assert!(matches!(res, Err(ErroApi::Validacao{campo: Some(campo), ..}) if campo == "idlocador"));
+
+    // O locatário não pode solicitar a própria máquina.
+    let res = cadastra_solicitacao_contrato(State(pool_testes()), usuario_autenticado(&idusuario), Json(SolicitacaoContratoInput{
+        idlocador: idusuario.clone(),
+        idlocatario: idusuario.clone(),
+        idmaquina: idmaquina.clone(),
+        medidatempolocacao: MedidaTempo::Dias,
+        origemsolicitacao: "Teste".to_string(),
+        valorsolicitacao: Dinheiro::de_reais(100),
+        prazolocacao: 2.0,
+        datainicio: None
+    })).await;
+    assert!(matches!(res, Err(ErroApi::Validacao{campo: Some(campo), ..}) if campo == "idlocatario"));
+
+    let conn = &obtem_conn(&pool_testes()).await.unwrap();
+    let inicio = chrono::Utc::now().naive_utc() + chrono::TimeDelta::days(30);
+    let (solicitacao, reserva) = solicitacao_reserva(&idmaquina, "961", inicio, 2.0);
+    let id = models::solicitacoes_contratos::cadastra_solicitacao_contrato(conn, solicitacao, reserva).await.unwrap();
+
+    let busca = |usuario| busca_solicitacao_idsolicitacao(State(pool_testes()), usuario, Query(IdInput{id: id.clone()}));
+    assert!(matches!(busca(usuario_autenticado("outrousuario961")).await, Err(ErroApi::Proibido(_))));
+    assert!(busca(usuario_autenticado("idlocatario961")).await.is_ok());
+    assert!(busca(admin_autenticado("admin961")).await.is_ok());
+
+    let res = models::solicitacoes_contratos::modera_status_solicitacao(conn, id.clone(), StatusSolicitacao::Aprovada,
+        "admin961".to_string()).await;
+    assert!(matches!(res, Err(ErroApi::Proibido(_))));
+    let solicitacao = models::solicitacoes_contratos::modera_status_solicitacao(conn, id.clone(), StatusSolicitacao::Recusada,
+        "admin961".to_string()).await.unwrap();
+    assert_eq!(solicitacao.statussolicitacao, StatusSolicitacao::Recusada);
+
+    let historico = models::solicitacoes_contratos::busca_historico_solicitacao(conn, id.clone()).await.unwrap();
+    assert_eq!(historico[1].papelator, "admin");
+    assert_eq!(historico[1].idator, Some("admin961".to_string()));
+
+    executa(conn, move |conn| {
+        use crate::schema::solicitacoes_contratos::dsl::*;
+
+        diesel::delete(solicitacoes_contratos.filter(idsolicitacao.eq(id)))
+            .execute(conn)
+            .map_err(ErroApi::from)
+    }).await.unwrap();
+    assert!(deleta_usuario(idusuario).await.is_ok());
+    assert!(deleta_maquina_id(idmaquina).await.is_ok());
+}
+
+#[tokio::test]
+async fn test_aprova_solicitacao_com_contrato(){
+    let usuario = cadastra_usuario(State(pool_testes()), Json(usuario_padrao("962"))).await.unwrap().1;
+    let idusuario = usuario.0.idusuario.to_string();
+    let idmaquina = cadastra_maquina(State(pool_testes()), usuario_autenticado(&idusuario), Json(
+        converte_tipo_maquina(maquina_padrao("962").await, idusuario.clone()).await)).await.unwrap().1.0.idmaquina;
+
+    let conn = &obtem_conn(&pool_testes()).await.unwrap();
+    let inicio = chrono::Utc::now().naive_utc() + chrono::TimeDelta::days(30);
+    let (solicitacao, reserva) = solicitacao_reserva(&idmaquina, "962", inicio, 2.0);
+    let id = models::solicitacoes_contratos::cadastra_solicitacao_contrato(conn, solicitacao, reserva).await.unwrap();
+
+    // Se o contrato não puder ser gravado, a aprovação também é desfeita.
+    let idcontrato = models::contratos::cadastra_contrato(conn, contrato_padrao(&idmaquina, "962", 2.0)).await.unwrap();
+    let res = models::solicitacoes_contratos::aprova_solicitacao(conn, contrato_padrao(&idmaquina, "962", 2.0),
+        "idlocador962".to_string(), false).await;
+    assert!(res.is_err());
+    let solicitacao = models::solicitacoes_contratos::busca_solicitacao_idsolicitacao(conn, id.clone()).await.unwrap();
+    assert_eq!(solicitacao.statussolicitacao, StatusSolicitacao::AguardandoAprovacao);
+
+    let idremovido = idcontrato.clone();
+    executa(conn, move |conn| {
+        use crate::schema::contratos::dsl::*;
+
+        diesel::delete(contratos.filter(idcontrato.eq(idremovido)))
+            .execute(conn)
+            .map_err(ErroApi::from)
+    }).await.unwrap();
+
+    let solicitacao = models::solicitacoes_contratos::aprova_solicitacao(conn, contrato_padrao(&idmaquina, "962", 2.0),
+        "idlocador962".to_string(), false).await.unwrap();
+    assert_eq!(solicitacao.statussolicitacao, StatusSolicitacao::Aprovada);
+    assert!(models::contratos::busca_contrato_id(conn, idcontrato.clone()).await.is_ok());
+
+    executa(conn, move |conn| {
+        use crate::schema::{contratos, solicitacoes_contratos};
+
+        diesel::delete(contratos::table.filter(contratos::idcontrato.eq(idcontrato)))
+            .execute(conn)
+            .map_err(ErroApi::from)?;
+        diesel::delete(solicitacoes_contratos::table.filter(solicitacoes_contratos::idsolicitacao.eq(id)))
+            .execute(conn)
+            .map_err(ErroApi::from)
+    }).await.unwrap();
+    assert!(deleta_usuario(idusuario).await.is_ok());
+    assert!(deleta_maquina_id(idmaquina).await.is_ok());
+}
+
+#[tokio::test]
+async fn test_sem_solicitacoes(){
+    let idusuario = cadastra_usuario(State(pool_testes()), Json(usuario_padrao("963"))).await.unwrap().1.0.idusuario;
+    let usuario = usuario_autenticado(&idusuario);
+
+    // Uma lista vazia não é erro.
+    let recebidas = busca_solicitacoes_idlocador(State(pool_testes()), usuario.clone(), Query(IdInput{id: idusuario.clone()})).await.unwrap().1.0;
+    assert!(recebidas.is_empty());
+    let emitidas = busca_solicitacoes_idlocatario(State(pool_testes()), usuario.clone(), Query(IdInput{id: idusuario.clone()})).await.unwrap().1.0;
+    assert!(emitidas.is_empty());
+    let notificacoes = busca_notificacoes_idusuario(State(pool_testes()), usuario, Query(IdInput{id: idusuario.clone()})).await.unwrap().1.0;
+    assert!(notificacoes.is_empty());
+
+    assert!(deleta_usuario(idusuario).await.is_ok());
+}
@@ -1,7 +1,6 @@
-use axum::{extract::Query, Json};
-use pwhash::bcrypt::verify;
+use axum::{extract::{Query, State}, Json};
 
-use crate::{controllers::{self, cria_conn, usuarios::{atualiza_email_usuario, atualiza_senha_usuario, atualiza_usuario, busca_email_usuario, busca_usuario_email, busca_usuario_id, cadastra_usuario, formata_documento, realiza_login, valida_email, valida_senha, AtualizaEmailInput, AtualizaSenhaInput, AtualizaUsuarioInput, CredenciaisUsuario, EmailInput, IdInput, UsuarioInput}}, models::usuarios::{busca_senha_usuario, deleta_usuario, Usuario}};
+use crate::{controllers::{self, gera_hash, obtem_conn, usuarios::{ativa_conta, atualiza_email_usuario, atualiza_senha_usuario, atualiza_usuario, busca_email_usuario, busca_perfil_usuario, busca_usuario_email, busca_usuario_id, cadastra_usuario, formata_documento, realiza_login, reenvia_codigo_ativacao, valida_email, valida_senha, AtivaContaInput, AtualizaEmailInput, AtualizaSenhaInput, AtualizaUsuarioInput, CredenciaisUsuario, EmailInput, IdInput, UsuarioInput}}, emails::transporte_email, erros::ErroApi, senhas::{verifica_senha, VerificacaoSenha}, models::usuarios::{atualiza_senha_usuario as atualiza_senha_usuario_db, atualiza_status_usuario, busca_senha_usuario, deleta_usuario, StatusUsuario, Usuario}, tests::{pool_testes, sessoes::{admin_autenticado, usuario_autenticado}}};
 
 pub fn usuario_padrao(numeroteste: &str) -> UsuarioInput{
     let email = format!("testeunit{}@gmail.com", numeroteste);
@@ -17,11 +16,37 @@ pub fn usuario_padrao(numeroteste: &str) -> UsuarioInput{
     }
 }
 
+/// Ativa a conta sem passar pelo código enviado por e-mail.
+pub async fn ativa_usuario_teste(id: &str){
+    let conn = &obtem_conn(&pool_testes()).await.unwrap();
+    atualiza_status_usuario(conn, id.to_string(), StatusUsuario::Ativo).await.unwrap();
+}
+
+/// O último código de ativação enviado para o e-mail pelo transporte em memória.
+fn codigo_ativacao_enviado(email: &str) -> String{
+    codigo_enviado(email, "Ativação de Conta")
+}
+
+/// O último código enviado para o e-mail em uma mensagem com esse título.
+pub fn codigo_enviado(email: &str, titulo: &str) -> String{
+    let assunto = format!("MaqExpress | {}", titulo);
+    let enviados = transporte_email().unwrap().enviados();
+    let enviado = enviados.iter()
+        .rev()
+        .find(|enviado| enviado.destinatario == email && enviado.assunto == assunto)
+        .unwrap();
+    enviado.texto.lines()
+        .map(str::trim)
+        .find(|linha| !linha.is_empty() && linha.chars().all(|c| c.is_ascii_digit()))
+        .unwrap()
+        .to_string()
+}
+
 #[tokio::test]
 async fn test_cadastra_usuario_ok(){
     let usuario = usuario_padrao("002");
 
-    let usuario = cadastra_usuario(Json(usuario)).await.unwrap().1;
+    let usuario = cadastra_usuario(State(pool_testes()), Json(usuario)).await.unwrap().1;
     let id = usuario.0.idusuario.to_string();
 
     assert!(deleta_usuario(id).await.is_ok());
@@ -39,7 +64,7 @@ async fn test_cadastra_usuario_err(){
         senha,
         documento
     };
-    let usuario = cadastra_usuario(Json(usuario)).await.unwrap_err().1.to_string();
+    let usuario = cadastra_usuario(State(pool_testes()), Json(usuario)).await.unwrap_err().to_string();
     let id = usuario.clone();
 
     assert!(deleta_usuario(id).await.is_err());
@@ -49,9 +74,9 @@ async fn test_cadastra_usuario_err(){
 async fn test_busca_email_usuario_ok(){
     let usuario = usuario_padrao("003");
 
-    let usuario = cadastra_usuario(Json(usuario)).await.unwrap().1;
+    let usuario = cadastra_usuario(State(pool_testes()), Json(usuario)).await.unwrap().1;
     let id = usuario.0.idusuario.to_string();
-    assert!(busca_email_usuario(Query(controllers::usuarios::UserId { idusuario: id.clone() })).await.is_ok());
+    assert!(busca_email_usuario(State(pool_testes()), usuario_autenticado(&id), Query(controllers::usuarios::UserId { idusuario: id.clone() })).await.is_ok());
 
     assert!(deleta_usuario(id).await.is_ok());
 }
@@ -59,7 +84,7 @@ async fn test_busca_email_usuario_ok(){
 #[tokio::test]
 async fn test_busca_email_usuario_err(){
     let id = "123456789";
-    assert!(busca_email_usuario(Query(controllers::usuarios::UserId { idusuario: id.to_string() })).await.is_err());
+    assert!(busca_email_usuario(State(pool_testes()), usuario_autenticado(&id), Query(controllers::usuarios::UserId { idusuario: id.to_string() })).await.is_err());
 }
 
 #[tokio::test]
@@ -189,9 +214,9 @@ async fn test_atualiza_email_usuario_ok(){
 
     let email_novo = "xtesteunit4@gmail.comx".to_string();
 
-    let usuario = cadastra_usuario(Json(usuario)).await.unwrap().1;
+    let usuario = cadastra_usuario(State(pool_testes()), Json(usuario)).await.unwrap().1;
     let id = usuario.0.idusuario.to_string();
-    assert!(atualiza_email_usuario(Json(AtualizaEmailInput{
+    assert!(atualiza_email_usuario(State(pool_testes()), usuario_autenticado(&id), Json(AtualizaEmailInput{
         email_antigo: email,
         email_novo,
         senha
@@ -214,7 +239,7 @@ async fn test_atualiza_email_usuario_err(){
         documento
     };
 
-    let usuario = cadastra_usuario(Json(usuario)).await.unwrap().1;
+    let usuario = cadastra_usuario(State(pool_testes()), Json(usuario)).await.unwrap().1;
     let id1 = usuario.0.idusuario.to_string();
 
     let email2 = "testeunit6@gmail.com".to_string();
@@ -229,10 +254,10 @@ async fn test_atualiza_email_usuario_err(){
         documento
     };
 
-    let usuario = cadastra_usuario(Json(usuario)).await.unwrap().1;
+    let usuario = cadastra_usuario(State(pool_testes()), Json(usuario)).await.unwrap().1;
     let id2 = usuario.0.idusuario.to_string();
 
-    assert!(atualiza_email_usuario(Json(AtualizaEmailInput{
+    assert!(atualiza_email_usuario(State(pool_testes()), usuario_autenticado(&id1), Json(AtualizaEmailInput{
         email_antigo: email1,
         email_novo: email2,
         senha
@@ -247,10 +272,10 @@ pub async fn busca_usuario_email_ok(){
     let usuario = usuario_padrao("007");
     let email = usuario.email.clone();
 
-    let usuario = cadastra_usuario(Json(usuario)).await.unwrap().1;
+    let usuario = cadastra_usuario(State(pool_testes()), Json(usuario)).await.unwrap().1;
     let id = usuario.0.idusuario.to_string();
     
-    assert!(busca_usuario_email(Query(
+    assert!(busca_usuario_email(State(pool_testes()), Query(
         EmailInput{
             email
         }
@@ -263,10 +288,10 @@ pub async fn busca_usuario_email_ok(){
 pub async fn busca_usuario_email_err(){
     let usuario = usuario_padrao("008");
 
-    let usuario = cadastra_usuario(Json(usuario)).await.unwrap().1;
+    let usuario = cadastra_usuario(State(pool_testes()), Json(usuario)).await.unwrap().1;
     let id = usuario.0.idusuario.to_string();
 
-    assert!(busca_usuario_email(Query(
+    assert!(busca_usuario_email(State(pool_testes()), Query(
         EmailInput{
             email: "emailinvalido1@gmail.com".to_string()
         }
@@ -281,13 +306,14 @@ async fn test_busca_senha_usuario_ok(){
     let email = usuario.email.clone();
     let senha = usuario.senha.clone();
 
-        let usuario = cadastra_usuario(Json(usuario)).await.unwrap().1;
+        let usuario = cadastra_usuario(State(pool_testes()), Json(usuario)).await.unwrap().1;
     let id = usuario.0.idusuario.to_string();
 
-    let conn = &mut cria_conn().unwrap();
+    let conn = &pool_testes().get().await.unwrap();
 
     let hash = busca_senha_usuario(conn, email).await.unwrap();
-    assert!(verify(senha, &hash));
+    assert!(hash.starts_with("$argon2id$"));
+    assert_eq!(verifica_senha(&senha, &hash).unwrap(), VerificacaoSenha::Correta);
 
     assert!(deleta_usuario(id).await.is_ok());
 }
@@ -297,14 +323,14 @@ async fn test_busca_senha_usuario_err(){
     let usuario = usuario_padrao("010");
     let email = usuario.email.clone();
 
-    let usuario = cadastra_usuario(Json(usuario)).await.unwrap().1;
+    let usuario = cadastra_usuario(State(pool_testes()), Json(usuario)).await.unwrap().1;
     let id = usuario.0.idusuario.to_string();
 
-    let conn = &mut cria_conn().unwrap();
+    let conn = &pool_testes().get().await.unwrap();
 
     let hash = busca_senha_usuario(conn, email).await.unwrap();
     let senha_incorreta = "SenhaIncorreta1.";
-    assert!(!verify(senha_incorreta, &hash));
+    assert_eq!(verifica_senha(senha_incorreta, &hash).unwrap(), VerificacaoSenha::Incorreta);
 
     assert!(deleta_usuario(id).await.is_ok());
 }
@@ -315,10 +341,11 @@ async fn test_realiza_login_ok(){
     let email = usuario.email.clone();
     let senha = usuario.senha.clone();
 
-    let usuario = cadastra_usuario(Json(usuario)).await.unwrap().1;
+    let usuario = cadastra_usuario(State(pool_testes()), Json(usuario)).await.unwrap().1;
     let id = usuario.0.idusuario.to_string();
+    ativa_usuario_teste(&id).await;
 
-    assert!(realiza_login(Json(
+    assert!(realiza_login(State(pool_testes()), Json(
         CredenciaisUsuario{
             email,
             senha
@@ -334,10 +361,11 @@ async fn test_realiza_login_err(){
     let email = usuario.email.clone();
     let senha = usuario.senha.clone();
 
-    let usuario = cadastra_usuario(Json(usuario)).await.unwrap().1;
+    let usuario = cadastra_usuario(State(pool_testes()), Json(usuario)).await.unwrap().1;
     let id = usuario.0.idusuario.to_string();
+    ativa_usuario_teste(&id).await;
 
-    assert!(realiza_login(Json(
+    assert!(realiza_login(State(pool_testes()), Json(
         CredenciaisUsuario{
             email,
             senha
@@ -353,10 +381,11 @@ async fn test_atualiza_senha_usuario_ok(){
     let email = usuario.email.clone();
     let senha = usuario.senha.clone();
 
-    let usuario = cadastra_usuario(Json(usuario)).await.unwrap().1;
+    let usuario = cadastra_usuario(State(pool_testes()), Json(usuario)).await.unwrap().1;
     let id = usuario.0.idusuario.to_string();
+    ativa_usuario_teste(&id).await;
 
-    assert!(realiza_login(Json(
+    assert!(realiza_login(State(pool_testes()), Json(
         CredenciaisUsuario{
             email: email.clone(),
             senha: senha.clone()
@@ -365,13 +394,13 @@ async fn test_atualiza_senha_usuario_ok(){
 
     let senha_nova = "SenhaTeste13.Nova".to_string();
 
-    assert!(atualiza_senha_usuario(Json(AtualizaSenhaInput{
+    assert!(atualiza_senha_usuario(State(pool_testes()), usuario_autenticado(&id), Json(AtualizaSenhaInput{
         idusuario: id.clone(),
         senha_antiga: senha,
         senha_nova: senha_nova.clone()
     })).await.is_ok());
 
-    assert!(realiza_login(Json(CredenciaisUsuario{
+    assert!(realiza_login(State(pool_testes()), Json(CredenciaisUsuario{
         email: email,
         senha: senha_nova
     })).await.is_ok());
@@ -385,10 +414,11 @@ async fn test_atualiza_senha_usuario_err(){
     let email = usuario.email.clone();
     let senha = usuario.senha.clone();
 
-    let usuario = cadastra_usuario(Json(usuario)).await.unwrap().1;
+    let usuario = cadastra_usuario(State(pool_testes()), Json(usuario)).await.unwrap().1;
     let id = usuario.0.idusuario.to_string();
+    ativa_usuario_teste(&id).await;
 
-    assert!(realiza_login(Json(
+    assert!(realiza_login(State(pool_testes()), Json(
         CredenciaisUsuario{
             email: email.clone(),
             senha: senha.clone()
@@ -398,20 +428,20 @@ async fn test_atualiza_senha_usuario_err(){
     let senha_nova = "SenhaTeste14.Nova".to_string();
     let email_invalido = "email_invalido@gmai.com".to_string();
 
-    assert!(atualiza_senha_usuario(Json(AtualizaSenhaInput{
+    assert!(atualiza_senha_usuario(State(pool_testes()), usuario_autenticado(&id), Json(AtualizaSenhaInput{
         idusuario: "idinvalido".to_string(),
         senha_antiga: senha.clone(),
         senha_nova: senha_nova.clone()
     })).await.is_err());
 
     let senha_invalida = "senhainvalida".to_string();
-    assert!(atualiza_senha_usuario(Json(AtualizaSenhaInput{
+    assert!(atualiza_senha_usuario(State(pool_testes()), usuario_autenticado(&id), Json(AtualizaSenhaInput{
         idusuario: id.clone(),
         senha_antiga: senha.clone(),
         senha_nova: senha_invalida
     })).await.is_err());
 
-    assert!(realiza_login(Json(CredenciaisUsuario{
+    assert!(realiza_login(State(pool_testes()), Json(CredenciaisUsuario{
         email: email,
         senha: senha_nova
     })).await.is_err());
@@ -423,15 +453,29 @@ async fn test_atualiza_senha_usuario_err(){
 async fn test_busca_usuario_id_ok(){
     let usuario = usuario_padrao("015");
 
-    let usuario = cadastra_usuario(Json(usuario)).await.unwrap().1;
+    let usuario = cadastra_usuario(State(pool_testes()), Json(usuario)).await.unwrap().1;
     let id = usuario.0.idusuario.to_string();
     
-    assert!(busca_usuario_id(Query(
+    assert!(busca_usuario_id(State(pool_testes()), usuario_autenticado(&id), Query(
         IdInput{
             id: id.clone()
         }
     )).await.is_ok());
 
+    let res = busca_usuario_id(State(pool_testes()), usuario_autenticado("outroUsuario"), Query(
+        IdInput{
+            id: id.clone()
+        }
+    )).await;
+    assert!(matches!(res, Err(ErroApi::Proibido(_))));
+
+    let perfil = busca_perfil_usuario(State(pool_testes()), Query(
+        IdInput{
+            id: id.clone()
+        }
+    )).await.unwrap().1;
+    assert_eq!(perfil.nome, "Usuario Teste 015");
+
     assert!(deleta_usuario(id).await.is_ok());
 }
 
@@ -439,10 +483,10 @@ async fn test_busca_usuario_id_ok(){
 async fn test_busca_usuario_id_err(){
     let usuario = usuario_padrao("016");
 
-    let usuario = cadastra_usuario(Json(usuario)).await.unwrap().1;
+    let usuario = cadastra_usuario(State(pool_testes()), Json(usuario)).await.unwrap().1;
     let id = usuario.0.idusuario.to_string();
     
-    assert!(busca_usuario_id(Query(
+    assert!(busca_usuario_id(State(pool_testes()), admin_autenticado("admin"), Query(
         IdInput{
             id: "randomId".to_string()
         }
@@ -457,7 +501,7 @@ async fn test_atualiza_usuario_ok(){
     let email = usuario.email.clone();
     let senha = usuario.senha.clone();
 
-    let usuario = cadastra_usuario(Json(usuario)).await.unwrap().1;
+    let usuario = cadastra_usuario(State(pool_testes()), Json(usuario)).await.unwrap().1;
     
     let id = usuario.0.idusuario.to_string();
     
@@ -469,12 +513,125 @@ async fn test_atualiza_usuario_ok(){
         nome_novo: "Usuário Atualizado".to_string(), 
         documento_novo: "017.017.017-01".to_string() 
     });
-    assert!(atualiza_usuario(input).await.is_ok());
+    assert!(atualiza_usuario(State(pool_testes()), usuario_autenticado(&id), input).await.is_ok());
 
     let input: Query<EmailInput> = Query::from(axum::extract::Query(EmailInput{
         email: email_novo
     }));
-    assert!(busca_usuario_email(input).await.is_ok());
+    assert!(busca_usuario_email(State(pool_testes()), input).await.is_ok());
+
+    assert!(deleta_usuario(id).await.is_ok());
+}
+
+#[tokio::test]
+async fn test_realiza_login_conta_nao_ativa(){
+    let usuario = usuario_padrao("940");
+    let credenciais = || Json(CredenciaisUsuario{
+        email: usuario.email.clone(),
+        senha: usuario.senha.clone()
+    });
+
+    let id = cadastra_usuario(State(pool_testes()), Json(usuario.clone())).await.unwrap().1.0.idusuario;
+    let erro = realiza_login(State(pool_testes()), credenciais()).await.unwrap_err();
+    assert!(matches!(erro, ErroApi::ContaNaoAtivada(_)));
+    assert_eq!(erro.corpo().code, "conta_nao_ativada");
+
+    let conn = &obtem_conn(&pool_testes()).await.unwrap();
+    atualiza_status_usuario(conn, id.clone(), StatusUsuario::Suspenso).await.unwrap();
+    let erro = realiza_login(State(pool_testes()), credenciais()).await.unwrap_err();
+    assert!(matches!(erro, ErroApi::ContaSuspensa(_)));
+
+    // A senha errada continua dando o mesmo erro de sempre, sem revelar o status.
+    let erro = realiza_login(State(pool_testes()), Json(CredenciaisUsuario{
+        email: usuario.email.clone(),
+        senha: "SenhaErrada940.".to_string()
+    })).await.unwrap_err();
+    assert!(matches!(erro, ErroApi::NaoAutorizado(_)));
 
     assert!(deleta_usuario(id).await.is_ok());
-}
\ No newline at end of file
+}
+
+#[tokio::test]
+async fn test_ativa_conta(){
+    let usuario = usuario_padrao("941");
+    let email = usuario.email.clone();
+    let senha = usuario.senha.clone();
+
+    let id = cadastra_usuario(State(pool_testes()), Json(usuario)).await.unwrap().1.0.idusuario;
+    let codigo = codigo_ativacao_enviado(&email);
+
+    let erro = ativa_conta(State(pool_testes()), Json(AtivaContaInput{
+        email: email.clone(),
+        codigo: "000".to_string()
+    })).await.unwrap_err();
+    assert_eq!(erro.corpo().field, Some("codigo".to_string()));
+
+    let ativado = ativa_conta(State(pool_testes()), Json(AtivaContaInput{
+        email: email.clone(),
+        codigo: codigo.clone()
+    })).await.unwrap().1.0;
+    assert_eq!(ativado.idusuario, id);
+
+    assert!(realiza_login(State(pool_testes()), Json(CredenciaisUsuario{
+        email: email.clone(),
+        senha
+    })).await.is_ok());
+
+    let erro = ativa_conta(State(pool_testes()), Json(AtivaContaInput{
+        email: email.clone(),
+        codigo
+    })).await.unwrap_err();
+    assert!(matches!(erro, ErroApi::Conflito(_)));
+
+    let erro = reenvia_codigo_ativacao(State(pool_testes()), Json(EmailInput{email})).await.unwrap_err();
+    assert!(matches!(erro, ErroApi::Conflito(_)));
+
+    assert!(deleta_usuario(id).await.is_ok());
+}
+
+#[tokio::test]
+async fn test_reenvia_codigo_ativacao_espera(){
+    let usuario = usuario_padrao("942");
+    let email = usuario.email.clone();
+
+    let id = cadastra_usuario(State(pool_testes()), Json(usuario)).await.unwrap().1.0.idusuario;
+
+    // O código do cadastro acabou de ser enviado.
+    let erro = reenvia_codigo_ativacao(State(pool_testes()), Json(EmailInput{email: email.clone()})).await.unwrap_err();
+    assert!(matches!(erro, ErroApi::Conflito(_)));
+
+    let erro = reenvia_codigo_ativacao(State(pool_testes()), Json(EmailInput{
+        email: "testeunit942naoexiste@gmail.com".to_string()
+    })).await.unwrap_err();
+    assert!(matches!(erro, ErroApi::NaoEncontrado(_)));
+
+    assert!(deleta_usuario(id).await.is_ok());
+}
+
+#[tokio::test]
+async fn test_realiza_login_atualiza_hash_bcrypt(){
+    let usuario = usuario_padrao("943");
+    let email = usuario.email.clone();
+    let senha = usuario.senha.clone();
+
+    let id = cadastra_usuario(State(pool_testes()), Json(usuario)).await.unwrap().1.0.idusuario;
+    ativa_usuario_teste(&id).await;
+
+    // Simula uma conta cadastrada antes do Argon2id.
+    let conn = &obtem_conn(&pool_testes()).await.unwrap();
+    atualiza_senha_usuario_db(conn, email.clone(), gera_hash(&senha)).await.unwrap();
+
+    assert!(realiza_login(State(pool_testes()), Json(CredenciaisUsuario{
+        email: email.clone(),
+        senha: senha.clone()
+    })).await.is_ok());
+
+    let hash = busca_senha_usuario(conn, email.clone()).await.unwrap();
+    assert!(hash.starts_with("$argon2id$"));
+    assert!(realiza_login(State(pool_testes()), Json(CredenciaisUsuario{
+        email,
+        senha
+    })).await.is_ok());
+
+    assert!(deleta_usuario(id).await.is_ok());
+}
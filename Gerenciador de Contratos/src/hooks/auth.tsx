@@ -41,7 +41,9 @@ export default function AuthProvider({
       const data = await loginUser(email, password);
 
       localStorage.setItem("USER_ID", data.idusuario);
-      setUser(data);
+      localStorage.setItem("ACCESS_TOKEN", data.token_acesso);
+      localStorage.setItem("REFRESH_TOKEN", data.token_renovacao);
+      setUser({ idusuario: data.idusuario });
     // eslint-disable-next-line @typescript-eslint/no-explicit-any
     } catch (error: any) {
       const statusCode = error.response?.status;
@@ -59,6 +61,8 @@ export default function AuthProvider({
 
   const signOut = useCallback(() => {
     localStorage.removeItem("USER_ID");
+    localStorage.removeItem("ACCESS_TOKEN");
+    localStorage.removeItem("REFRESH_TOKEN");
     setUser(null);
   }, []);
 
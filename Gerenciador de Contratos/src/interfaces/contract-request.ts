@@ -7,6 +7,6 @@ export interface ContractRequest{
    origemsolicitacao: string;
    statussolicitacao: string;
    prazolocacao: number;
-   valorsolicitacao: number;
+   valorsolicitacao: string;
    datasolicitacao: string;
 }
\ No newline at end of file
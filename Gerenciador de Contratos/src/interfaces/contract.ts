@@ -35,7 +35,7 @@ export interface ContractPDF {
 
   nomemaquina: string;
   numeroseriemaquina: string;
-  valoraluguelmaquina: number;
+  valoraluguelmaquina: string;
 
   numerocontabanco: string;
   numeroagenciabanco: string;
@@ -44,9 +44,11 @@ export interface ContractPDF {
   medidatempolocacao: string;
 
   prazolocacao: number;
-  valorlocacao: number;
+  valorlocacao: string;
 
   datacontrato: string;
+  datainicio: string;
+  datafim: string;
 }
 
 export interface Contract {
@@ -63,6 +65,6 @@ export interface Contract {
   cidadeforo: string;
   statuscontrato: string;
   prazolocacao: number;
-  valorlocacao: number;
+  valorlocacao: string;
   datacontrato: string;
 }
\ No newline at end of file
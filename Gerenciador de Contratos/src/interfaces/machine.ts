@@ -4,7 +4,7 @@ export interface Machine {
     nome: string;
     numeroserie: string;
     categoria: string;
-    valoraluguel: number;
+    valoraluguel: string;
     disponivelaluguel: string;
     status: string;
     datacadastro: string; // ISO 8601 ("2023-01-01T12:00:00")
@@ -2,16 +2,31 @@ export interface UserId {
   idusuario: string
 }
 
+export interface UserSession extends UserId {
+  token_acesso: string
+  token_renovacao: string
+  expira_em: number
+}
+
+export interface ProviderLoginStart {
+  url: string
+  estado: string
+}
+
 export interface User{
   idusuario: string,
   nome: string,
   email: string,
-  senha: string,
   documento: string,
   datacadastro: string,
   origemconta: string
 }
 
+export interface UserProfile{
+  idusuario: string,
+  nome: string
+}
+
 export interface UserInput{
   nome: string;
   email: string;
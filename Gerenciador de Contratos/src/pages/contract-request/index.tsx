@@ -10,8 +10,8 @@ import { Label } from "@/components/ui/label";
 import { ContractRequest as SolicitacaoContrato } from "@/interfaces/contract-request";
 import { loadContractRequestsByOwnerId, loadContractRequestsByRenterId, updateContractRequestStatus } from "@/services/api/contract-request";
 import { formatDate } from "@/services/api/format/format";
-import { loadUserById } from "@/services/api/user/user";
-import { User } from "@/interfaces/user";
+import { loadUserProfileById } from "@/services/api/user/user";
+import { UserProfile } from "@/interfaces/user";
 import { loadMachineById } from "@/services/api/machine/machine";
 import { Machine } from "@/interfaces/machine";
 import { loadContractByRequestId, loadPdfByRequestId } from "@/services/api/contract";
@@ -176,7 +176,7 @@ export default function ContractRequest() {
   }
 
   const RequestCard = ({ request }: { request: SolicitacaoContrato }) => {
-    const [requestOrigin, setRequestOrigin] = useState<User>();
+    const [requestOrigin, setRequestOrigin] = useState<UserProfile>();
     const [requestMachine, setRequestMachine] = useState<Machine>();
     const [showRequestInfo, setShowRequestInfo] = useState(false);
 
@@ -186,7 +186,7 @@ export default function ContractRequest() {
 
     const loadRequestOrigin = async () => {
       try{
-        const res = await loadUserById(request.origemsolicitacao);
+        const res = await loadUserProfileById(request.origemsolicitacao);
         setRequestOrigin(res);
       }catch(error){
         console.error(error);
@@ -1,74 +1,33 @@
 import { useEffect, useState } from "react";
 import { useNavigate, useSearchParams } from "react-router-dom";
 import Layout from "@/layouts/default";
-import { useAuth } from "@/hooks/auth";
+import { loginWithProvider } from "@/services/api/user/user";
 
 function GoogleAuthCallback() {
   const [searchParams] = useSearchParams();
   const [message, setMessage] = useState("Carregando...");
-  const API_URL = "http://localhost:3003";
   const navigate = useNavigate();
-  const { signIn } = useAuth();
-  const loadUserId = async (email: string) => {
-    try {
-      const res = await fetch(
-        `${API_URL}/busca_usuario_email/?email=${encodeURIComponent(email)}`,
-        {
-          method: "GET",
-          headers: {
-            "Content-Type": "application/json",
-          },
-        },
-      );
-
-      if (!res.ok) {
-        throw new Error(await res.text());
-      }
-
-      const userid = await res.json();
-      console.log("idusuario: ", userid);
-      localStorage.setItem("USER_ID", userid);
-      const credentials = { email: email, password: email };
-      await signIn(credentials);
-      return userid;
-    } catch (error) {
-      console.error(error);
-    }
-  };
 
   useEffect(() => {
     const sendCodeToBackend = async () => {
       const code = searchParams.get("code");
-      console.log("code: ", code);
+      const state = searchParams.get("state");
 
-      if (!code) {
-        console.error("Código não encontrado na URL");
+      if (!code || !state) {
+        console.error("Código ou state não encontrado na URL");
+        setMessage("erro ao autenticar");
         return;
       }
 
       try {
-        const res = await fetch("http://localhost:3003/auth/google", {
-          method: "POST",
-          headers: {
-            "Content-Type": "application/json",
-          },
-          body: JSON.stringify({
-            code: code,
-          }),
-        });
         console.log("autenticando...");
-        if (!res.ok) {
-          const erro = await res.text();
-          console.log("Erro ao tentar autenticar: ", erro);
-          throw new Error(erro);
-        }
-        const obj = await res.json();
-        console.log("email: ", obj.email);
+        const obj = await loginWithProvider("google", code, state);
         console.log("Autenticado!");
         setMessage("autenticado");
         alert("Autenticação realizada com sucesso!");
-        await loadUserId(obj.email);
-        localStorage.setItem("PROFILE_IMAGE_URL", obj.picture);
+        localStorage.setItem("USER_ID", obj.idusuario);
+        localStorage.setItem("ACCESS_TOKEN", obj.token_acesso);
+        localStorage.setItem("REFRESH_TOKEN", obj.token_renovacao);
         navigate("/user-profile");
       } catch (error) {
         console.error("Erro ao enviar código ao backend:", error);
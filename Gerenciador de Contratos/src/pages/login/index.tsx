@@ -8,7 +8,7 @@ import { Button } from "@/components/ui/button";
 import { handleAxiosError } from "@/services/api/error/error";
 import { useAuth } from "@/hooks/auth";
 import GoogleLoginButton from "@/components/google-login-button";
-import { createUser } from "@/services/api/user/user";
+import { createUser, startProviderLogin } from "@/services/api/user/user";
 import { UserInput } from "@/interfaces/user";
 
 export default function AuthPage() {
@@ -80,14 +80,15 @@ export default function AuthPage() {
 
   const redirectGoogle = async () => {
     console.log("Redirecionando para o Google...");
-    const redirectUri = "http://localhost:5173/auth/google/callback";
-    const clientId =
-      "853000099698-mja71sb0chsva2m9eu3prpktl31psg5q.apps.googleusercontent.com";
-
-    const url = `https://accounts.google.com/o/oauth2/v2/auth?scope=email&response_type=code&redirect_uri=${encodeURIComponent(redirectUri)}&client_id=${clientId}`;
-    console.log("URL gerada:", url);
-
-    window.location.href = url;
+    try {
+      // O backend gera o endereço com o state, o nonce e o PKCE do login.
+      const { url } = await startProviderLogin("google");
+      window.location.href = url;
+      // eslint-disable-next-line @typescript-eslint/no-explicit-any
+    } catch (erro: any) {
+      console.error(erro);
+      alert(`Não foi possível entrar com o Google. ${erro.message}`);
+    }
   };
 
   return (
@@ -13,19 +13,24 @@ import NotificationFilter from "@/components/notification-filter";
 
 export const NotificationList = () => {
   const [notifications, setNotifications] = useState<Notification[]>([]);
+  const [loadError, setLoadError] = useState(false);
   const [filter, setFilter] = useState("");
 
   useEffect(() => {
     const id = localStorage.getItem("USER_ID");
     const loadNotifications = async (id: string) => {
-      const notificationArray = await loadNotificationsByUserId(id);
-      console.log(notificationArray || "Nenhuma notificação");
-      setNotifications(notificationArray);
+      try {
+        const notificationArray = await loadNotificationsByUserId(id);
+        setNotifications(notificationArray);
+      } catch (error) {
+        console.error(error);
+        setLoadError(true);
+      }
     };
-    if (notifications.length === 0 && id) {
+    if (id) {
       loadNotifications(id);
     }
-  }, [notifications]);
+  }, []);
 
   const filteredNotifications = notifications.filter((notification) =>
     filter ? notification.status === filter : true
@@ -48,18 +53,20 @@ export const NotificationList = () => {
             <Card className="bg-[hsl(var(--machine-card-bg))] border-[hsl(var(--primary))]">
               <CardHeader>
                 <h2 className="text-[hsl(var(--primary))] ">
-                  Erro ao carregar lista de notificações
+                  {loadError ? "Erro ao carregar lista de notificações" : "Nenhuma notificação"}
                 </h2>
               </CardHeader>
               <CardContent>
                 <div>
                   <p className="mb-2 text-[hsl(var(--primary))]">
-                    Você ainda não possui notificações.
+                    {loadError ? "Não foi possível carregar suas notificações." : "Você ainda não possui notificações."}
                     <br />
                   </p>
-                  <div className="flex justify-center items-center">
-                    <Button className="m-2">Relatar problema</Button>
-                  </div>
+                  {loadError && (
+                    <div className="flex justify-center items-center">
+                      <Button className="m-2">Relatar problema</Button>
+                    </div>
+                  )}
                 </div>
               </CardContent>
             </Card>
@@ -20,6 +20,7 @@ export default function PasswordRecovery() {
   const [isUpdating, setIsUpdating] = useState(false);
   const [isCodeInputVisible, setIsCodeInputVisible] = useState(false);
   const [code, setCode] = useState("");
+  const [resetToken, setResetToken] = useState("");
 
   const [isPasswordInputVisible, setIsPasswordInputVisible] = useState(false);
   const [newPassword, setNewPassword] = useState("");
@@ -29,32 +30,6 @@ export default function PasswordRecovery() {
 
   const API_URL = process.env.VITE_URL_BASE || "http://localhost:3003";
 
-  const loadUserId = async () => {
-    try {
-      const res = await fetch(
-        `${API_URL}/busca_usuario_email/?email=${encodeURIComponent(email)}`,
-        {
-          method: "GET",
-          headers: {
-            "Content-Type": "application/json",
-          },
-        },
-      );
-
-      if (!res.ok) {
-        throw new Error(await res.text());
-      }
-
-      const userid = await res.json();
-      console.log("idusuario: ", userid);
-      return userid;
-    } catch (error) {
-      setTimeout(() => setIsUpdating(false));
-      setMessage("Erro ao enviar o código de recuperação.");
-      console.error(error);
-    }
-  };
-
   const handleSubmit = async () => {
     if (!email || !/\S+@\S+\.\S+/.test(email)) {
       setMessage("Por favor, insira um e-mail válido.");
@@ -103,19 +78,21 @@ export default function PasswordRecovery() {
     setIsUpdating(true);
 
     try {
-      const userid = await loadUserId();
       const res = await fetch(`${API_URL}/verifica_codigo_recuperacao`, {
         method: "POST",
         headers: {
           "Content-Type": "application/json",
         },
-        body: JSON.stringify({ idusuario: userid, codigodigitado: code }),
+        body: JSON.stringify({ email, codigodigitado: code }),
       });
 
       if (!res.ok) {
         throw new Error(await res.text());
       }
 
+      const { tokenredefinicao } = await res.json();
+      setResetToken(tokenredefinicao);
+
       setMessage("Código verificado com sucesso!");
       setIsCodeInputVisible(false);
       setIsPasswordInputVisible(true);
@@ -136,13 +113,15 @@ export default function PasswordRecovery() {
     setIsUpdating(true);
 
     try {
-      const userid = await loadUserId();
       const res = await fetch(`${API_URL}/redefine_senha_usuario`, {
         method: "PATCH",
         headers: {
           "Content-Type": "application/json",
         },
-        body: JSON.stringify({ idusuario: userid, senha_nova: newPassword }),
+        body: JSON.stringify({
+          tokenredefinicao: resetToken,
+          senha_nova: newPassword,
+        }),
       });
 
       if (!res.ok) {
@@ -248,9 +248,9 @@ export const PdfDocument = ({ contract }: { contract: ContractPDF }) => {
 
           <Text style={styles.paragraph}>
             <Text style={styles.bold}>2.1 </Text> O prazo da presente locação é
-            de {contract.prazolocacao} {contract.medidatempolocacao}, passando a
-            vigorar a partir da data de entrega dos equipamentos, nos termos
-            previstos da Cláusula Primeira, item 1.1.1.
+            de {contract.prazolocacao} {contract.medidatempolocacao}, de{" "}
+            {formatDate(contract.datainicio)} a {formatDate(contract.datafim)},
+            nos termos previstos da Cláusula Primeira, item 1.1.1.
           </Text>
 
           <Text style={styles.paragraph}>
@@ -47,7 +47,7 @@ export default function UpdateMachine() {
         nome: name,
         descricao: description,
         categoria: category,
-        valoraluguel: rentValue,
+        valoraluguel: rentValue.toFixed(2),
         disponivelaluguel: rentDisponibility,
         idpublico: machine.idpublico,
         numeroserie: serialNumber,
@@ -72,7 +72,7 @@ export default function UpdateMachine() {
       setName(machineData.nome);
       setCategory(machineData.categoria);
       setDescription(machineData.descricao);
-      setRentValue(machineData.valoraluguel);
+      setRentValue(Number(machineData.valoraluguel));
       setSerialNumber(machineData.numeroserie);
     }
     if (publicid) {
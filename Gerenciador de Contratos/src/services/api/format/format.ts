@@ -16,11 +16,12 @@ export const formatDate = (dateparam: string | undefined, diff = -3) => {
     return `${hours}:${minutes} ${day}/${month}/${year}`;
   };
 
-export const formatCurrency = (value: number | bigint) => {
+// O back-end manda os valores em reais como texto ("1234.50").
+export const formatCurrency = (value: number | bigint | string) => {
   return new Intl.NumberFormat("pt-BR", {
     style: "currency",
     currency: "BRL",
     minimumFractionDigits: 2,
     maximumFractionDigits: 2,
-  }).format(value);
+  }).format(typeof value === "string" ? Number(value) : value);
 };
\ No newline at end of file
@@ -4,4 +4,13 @@ const client = axios.create({
   baseURL: 'http://localhost:3003',
 });
 
+client.interceptors.request.use((config) => {
+  const token = localStorage.getItem("ACCESS_TOKEN");
+
+  if (token) {
+    config.headers.Authorization = `Bearer ${token}`;
+  }
+  return config;
+});
+
 export { client };
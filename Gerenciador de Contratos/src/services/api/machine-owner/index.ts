@@ -1,12 +1,12 @@
-import { User } from "@/interfaces/user";
+import { UserProfile } from "@/interfaces/user";
 import { client } from "..";
 
 export async function loadMachineOwnerByMachineId(
   id: string
-):Promise<User>{
+):Promise<UserProfile>{
   try {
     const url = `/busca_usuario_idmaquina/?id=${encodeURIComponent(id)}`;
-    const response = await client.get<User>(url);
+    const response = await client.get<UserProfile>(url);
 
     if (response.status === 200) {
       return response.data;
@@ -161,15 +161,14 @@ export async function loadMachineRentValue(
   dados: MachineRentValue,
 ): Promise<number> {
   try {
-    const response = await client.post<number>("/calcula_valor_aluguel", {
+    const response = await client.post<string>("/calcula_valor_aluguel", {
       idmaquina: dados.idmaquina,
       medida_prazo: dados.medida_prazo,
       prazo: dados.prazo,
     });
 
     if (response.status === 200) {
-      const data = response.data;
-      return data;
+      return Number(response.data);
     }
     console.warn("Resposta inesperada:", response.status);
     throw new Error(
@@ -1,4 +1,4 @@
-import { User, UserId, UserInput } from "@/interfaces/user";
+import { ProviderLoginStart, User, UserId, UserInput, UserProfile, UserSession } from "@/interfaces/user";
 import { client } from "..";
 
 
@@ -27,9 +27,9 @@ export async function createUser(
 export async function loginUser(
   email: string,
   password: string
-): Promise<UserId> {
+): Promise<UserSession> {
   try {
-    const response = await client.post<UserId>("/realiza_login", {
+    const response = await client.post<UserSession>("/realiza_login", {
       email,
       senha: password,
     });
@@ -48,6 +48,56 @@ export async function loginUser(
   }
 }
 
+export async function startProviderLogin(
+  provider: string
+): Promise<ProviderLoginStart> {
+  try {
+    const response = await client.get<ProviderLoginStart>(
+      `/auth/${encodeURIComponent(provider)}/inicia`
+    );
+
+    if (response.status === 200) {
+      return response.data;
+    }
+    console.warn("Resposta inesperada:", response.status);
+    throw new Error(`Erro ao iniciar o login. Status code: ${response.status}`);
+  // eslint-disable-next-line @typescript-eslint/no-explicit-any
+  } catch (error: any) {
+    console.error("Erro ao iniciar o login:", error.response?.status, error.message);
+    throw new Error(
+      `Falha ao iniciar o login: ${error.response?.status || "sem status"}`
+    );
+  }
+}
+
+export async function loginWithProvider(
+  provider: string,
+  code: string,
+  state: string
+): Promise<UserSession> {
+  try {
+    const response = await client.post<UserSession>(
+      `/auth/${encodeURIComponent(provider)}`,
+      {
+        code,
+        estado: state,
+      }
+    );
+
+    if (response.status === 200) {
+      return response.data;
+    }
+    console.warn("Resposta inesperada:", response.status);
+    throw new Error(`Erro ao realizar login. Status code: ${response.status}`);
+  // eslint-disable-next-line @typescript-eslint/no-explicit-any
+  } catch (error: any) {
+    console.error("Erro ao realizar login:", error.response?.status, error.message);
+    throw new Error(
+      `Falha ao realizar login: ${error.response?.status || "sem status"}`
+    );
+  }
+}
+
 export async function loadUserById(
   id: string
 ):Promise<User>{
@@ -67,4 +117,24 @@ export async function loadUserById(
       `Falha ao buscar o usuário: ${error.response?.status || "sem status"}`
     );
   }  
+}
+export async function loadUserProfileById(
+  id: string
+):Promise<UserProfile>{
+  try {
+    const url = `/busca_perfil_usuario/?id=${encodeURIComponent(id)}`;
+    const response = await client.get<UserProfile>(url);
+
+    if (response.status === 200) {
+      return response.data;
+    }
+    console.warn("Resposta inesperada:", response.status);
+    throw new Error(`Erro ao buscar o perfil do usuário. Status code: ${response.status}`);
+  // eslint-disable-next-line @typescript-eslint/no-explicit-any
+  } catch (error: any) {
+    console.error("Erro ao buscar o perfil do usuário:", error.response?.status, error.message);
+    throw new Error(
+      `Falha ao buscar o perfil do usuário: ${error.response?.status || "sem status"}`
+    );
+  }  
 }
\ No newline at end of file